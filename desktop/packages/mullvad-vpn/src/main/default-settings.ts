@@ -110,6 +110,12 @@ export function getDefaultApiAccessMethods(): ApiAccessMethodSettings {
       enabled: false,
       type: 'encrypted-dns-proxy',
     },
+    systemProxy: {
+      id: '',
+      name: 'System proxy',
+      enabled: false,
+      type: 'system-proxy',
+    },
     custom: [],
   };
 }
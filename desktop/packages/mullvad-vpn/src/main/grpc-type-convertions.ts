@@ -25,6 +25,7 @@ import {
   FeatureIndicator,
   FirewallPolicyError,
   FirewallPolicyErrorType,
+  HttpAuth,
   IBridgeConstraints,
   ICustomList,
   IDevice,
@@ -54,6 +55,7 @@ import {
   RelayProtocol,
   RelaySettings,
   SocksAuth,
+  SystemProxyMethod,
   TunnelParameterError,
   TunnelProtocol,
   TunnelState,
@@ -359,10 +361,12 @@ function convertFromFeatureIndicator(
     case grpcTypes.FeatureIndicator.QUANTUM_RESISTANCE:
       return FeatureIndicator.quantumResistance;
     case grpcTypes.FeatureIndicator.MULTIHOP:
+    case grpcTypes.FeatureIndicator.CASCADE:
       return FeatureIndicator.multihop;
     case grpcTypes.FeatureIndicator.BRIDGE_MODE:
       return FeatureIndicator.bridgeMode;
     case grpcTypes.FeatureIndicator.SPLIT_TUNNELING:
+    case grpcTypes.FeatureIndicator.INVERSE_SPLIT_TUNNELING:
       return FeatureIndicator.splitTunneling;
     case grpcTypes.FeatureIndicator.LOCKDOWN_MODE:
       return FeatureIndicator.lockdownMode;
@@ -384,6 +388,8 @@ function convertFromFeatureIndicator(
       return FeatureIndicator.daita;
     case grpcTypes.FeatureIndicator.SHADOWSOCKS:
       return FeatureIndicator.shadowsocks;
+    case grpcTypes.FeatureIndicator.QUIC:
+      return FeatureIndicator.quic;
   }
 }
 
@@ -1103,6 +1109,11 @@ function fillApiAccessMethodSetting<T extends grpcTypes.NewAccessMethodSetting>(
       accessMethod.setEncryptedDnsProxy(encryptedDnsProxy);
       break;
     }
+    case 'system-proxy': {
+      const systemProxy = new grpcTypes.AccessMethod.SystemProxy();
+      accessMethod.setSystemProxy(systemProxy);
+      break;
+    }
     default:
       accessMethod.setCustom(convertToCustomProxy(method));
   }
@@ -1136,6 +1147,17 @@ export function convertToCustomProxy(proxy: CustomProxy): grpcTypes.CustomProxy
       customProxy.setSocks5remote(socks5Remote);
       break;
     }
+    case 'http-connect': {
+      const httpConnect = new grpcTypes.HttpConnect();
+      httpConnect.setIp(proxy.ip);
+      httpConnect.setPort(proxy.port);
+      if (proxy.authentication !== undefined) {
+        httpConnect.setAuth(convertToHttpAuth(proxy.authentication));
+      }
+      httpConnect.setTls(proxy.tls);
+      customProxy.setHttpConnect(httpConnect);
+      break;
+    }
     case 'shadowsocks': {
       const shadowsocks = new grpcTypes.Shadowsocks();
       shadowsocks.setIp(proxy.ip);
@@ -1157,6 +1179,13 @@ function convertToSocksAuth(authentication: SocksAuth): grpcTypes.SocksAuth {
   return auth;
 }
 
+function convertToHttpAuth(authentication: HttpAuth): grpcTypes.HttpAuth {
+  const auth = new grpcTypes.HttpAuth();
+  auth.setUsername(authentication.username);
+  auth.setPassword(authentication.password);
+  return auth;
+}
+
 function convertFromApiAccessMethodSettings(
   accessMethods: grpcTypes.ApiAccessMethodSettings,
 ): ApiAccessMethodSettings {
@@ -1172,6 +1201,9 @@ function convertFromApiAccessMethodSettings(
       "no 'Encrypted DNS proxy' access method was found",
     ),
   ) as AccessMethodSetting<EncryptedDnsProxy>;
+  const systemProxy = convertFromApiAccessMethodSetting(
+    ensureExists(accessMethods.getSystemProxy(), "no 'System proxy' access method was found"),
+  ) as AccessMethodSetting<SystemProxyMethod>;
   const custom = accessMethods
     .getCustomList()
     .filter((setting) => setting.hasId() && setting.hasAccessMethod())
@@ -1183,6 +1215,7 @@ function convertFromApiAccessMethodSettings(
     direct,
     mullvadBridges: bridges,
     encryptedDnsProxy,
+    systemProxy,
     custom,
   };
 }
@@ -1193,7 +1226,8 @@ function isCustomProxy(
   return (
     accessMethod.type !== 'direct' &&
     accessMethod.type !== 'bridges' &&
-    accessMethod.type !== 'encrypted-dns-proxy'
+    accessMethod.type !== 'encrypted-dns-proxy' &&
+    accessMethod.type !== 'system-proxy'
   );
 }
 
@@ -1219,6 +1253,8 @@ function convertFromAccessMethod(method: grpcTypes.AccessMethod): AccessMethod {
       return { type: 'bridges' };
     case grpcTypes.AccessMethod.AccessMethodCase.ENCRYPTED_DNS_PROXY:
       return { type: 'encrypted-dns-proxy' };
+    case grpcTypes.AccessMethod.AccessMethodCase.SYSTEM_PROXY:
+      return { type: 'system-proxy' };
     case grpcTypes.AccessMethod.AccessMethodCase.CUSTOM: {
       return convertFromCustomProxy(method.getCustom()!);
     }
@@ -1251,6 +1287,17 @@ function convertFromCustomProxy(proxy: grpcTypes.CustomProxy): CustomProxy {
         authentication: auth === undefined ? undefined : convertFromSocksAuth(auth),
       };
     }
+    case grpcTypes.CustomProxy.ProxyMethodCase.HTTP_CONNECT: {
+      const httpConnect = proxy.getHttpConnect()!;
+      const auth = httpConnect.getAuth();
+      return {
+        type: 'http-connect',
+        ip: httpConnect.getIp(),
+        port: httpConnect.getPort(),
+        authentication: auth === undefined ? undefined : convertFromHttpAuth(auth),
+        tls: httpConnect.getTls(),
+      };
+    }
     case grpcTypes.CustomProxy.ProxyMethodCase.SHADOWSOCKS: {
       const shadowsocks = proxy.getShadowsocks()!;
       return {
@@ -1273,6 +1320,13 @@ function convertFromSocksAuth(auth: grpcTypes.SocksAuth): SocksAuth {
   };
 }
 
+function convertFromHttpAuth(auth: grpcTypes.HttpAuth): HttpAuth {
+  return {
+    username: auth.getUsername(),
+    password: auth.getPassword(),
+  };
+}
+
 export function ensureExists<T>(value: T | undefined, errorMessage: string): T {
   if (value) {
     return value;
@@ -123,6 +123,10 @@ export default function ApiAccessMethods() {
                       method={methods.encryptedDnsProxy}
                       inUse={methods.encryptedDnsProxy.id === currentMethod?.id}
                     />
+                    <ApiAccessMethod
+                      method={methods.systemProxy}
+                      inUse={methods.systemProxy.id === currentMethod?.id}
+                    />
                     {methods.custom.map((method) => (
                       <ApiAccessMethod
                         key={method.id}
@@ -295,6 +299,20 @@ function ApiAccessMethod(props: ApiAccessMethodProps) {
           ]}
         />
       )}
+      {props.method.type === 'system-proxy' && (
+        <StyledMethodInfoButton
+          message={[
+            messages.pgettext(
+              'api-access-methods-view',
+              'With the “System proxy” method, the app communicates with our Mullvad API through the proxy configured on this computer.',
+            ),
+            messages.pgettext(
+              'api-access-methods-view',
+              'The proxy is read from the environment variables of the Mullvad service, such as https_proxy. On Linux, proxies configured in NetworkManager are also used.',
+            ),
+          ]}
+        />
+      )}
       <ContextMenuContainer>
         <ContextMenuTrigger>
           <StyledMethodTriggerImage
@@ -190,6 +190,7 @@ export enum FeatureIndicator {
   lockdownMode,
   udp2tcp,
   shadowsocks,
+  quic,
   lanSharing,
   dnsContentBlockers,
   customDns,
@@ -496,6 +497,11 @@ export interface SocksAuth {
   password: string;
 }
 
+export interface HttpAuth {
+  username: string;
+  password: string;
+}
+
 export type Socks5LocalCustomProxy = {
   type: 'socks5-local';
   remoteIp: string;
@@ -511,6 +517,14 @@ export type Socks5RemoteCustomProxy = {
   authentication?: SocksAuth;
 };
 
+export type HttpConnectCustomProxy = {
+  type: 'http-connect';
+  ip: string;
+  port: number;
+  authentication?: HttpAuth;
+  tls: boolean;
+};
+
 export type ShadowsocksCustomProxy = {
   type: 'shadowsocks';
   ip: string;
@@ -519,13 +533,23 @@ export type ShadowsocksCustomProxy = {
   cipher: string;
 };
 
-export type CustomProxy = Socks5LocalCustomProxy | Socks5RemoteCustomProxy | ShadowsocksCustomProxy;
+export type CustomProxy =
+  | Socks5LocalCustomProxy
+  | Socks5RemoteCustomProxy
+  | HttpConnectCustomProxy
+  | ShadowsocksCustomProxy;
 export type NamedCustomProxy = CustomProxy & { name: string };
 
 export type DirectMethod = { type: 'direct' };
 export type BridgesMethod = { type: 'bridges' };
 export type EncryptedDnsProxy = { type: 'encrypted-dns-proxy' };
-export type AccessMethod = DirectMethod | BridgesMethod | EncryptedDnsProxy | CustomProxy;
+export type SystemProxyMethod = { type: 'system-proxy' };
+export type AccessMethod =
+  | DirectMethod
+  | BridgesMethod
+  | EncryptedDnsProxy
+  | SystemProxyMethod
+  | CustomProxy;
 
 export type NamedAccessMethod<T extends AccessMethod> = T & { name: string };
 
@@ -542,6 +566,7 @@ export type ApiAccessMethodSettings = {
   direct: AccessMethodSetting<DirectMethod>;
   mullvadBridges: AccessMethodSetting<BridgesMethod>;
   encryptedDnsProxy: AccessMethodSetting<EncryptedDnsProxy>;
+  systemProxy: AccessMethodSetting<SystemProxyMethod>;
   custom: Array<AccessMethodSetting<CustomProxy>>;
 };
 
@@ -0,0 +1,244 @@
+//! Provides a stream that is tunneled through an HTTP proxy using the `CONNECT` method, as
+//! described in RFC 9110: <https://datatracker.ietf.org/doc/html/rfc9110#name-connect>.
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, LazyLock},
+    task::{self, Poll},
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use talpid_types::net::proxy::HttpAuth;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_rustls::{
+    rustls::{self, pki_types::ServerName, ClientConfig},
+    TlsConnector,
+};
+
+/// Upper bound on the size of the response header sent by the proxy.
+const MAX_RESPONSE_HEADER_SIZE: usize = 8 * 1024;
+
+/// A stream to an HTTP proxy, which may be encrypted using TLS.
+pub enum ProxyStream<S> {
+    Plain(S),
+    Tls(Box<tokio_rustls::client::TlsStream<S>>),
+}
+
+impl<S> ProxyStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Set up a TLS session with the proxy at `proxy_ip`. The certificate of the proxy is
+    /// verified against the web PKI and must be valid for `proxy_ip`.
+    pub async fn connect_tls(stream: S, proxy_ip: IpAddr) -> io::Result<Self> {
+        static TLS_CONFIG: LazyLock<Arc<ClientConfig>> = LazyLock::new(|| {
+            let root_store =
+                rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            let config = ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .expect("ring crypto provider should support the default TLS versions")
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+            Arc::new(config)
+        });
+
+        let connector = TlsConnector::from(TLS_CONFIG.clone());
+        let stream = connector
+            .connect(ServerName::IpAddress(proxy_ip.into()), stream)
+            .await?;
+        Ok(ProxyStream::Tls(Box::new(stream)))
+    }
+
+    /// Ask the proxy to open a tunnel to `target`. Once this returns successfully, the stream is
+    /// connected to `target`.
+    pub async fn connect(
+        mut self,
+        target: &SocketAddr,
+        auth: Option<&HttpAuth>,
+    ) -> io::Result<Self> {
+        self.write_all(connect_request(target, auth).as_bytes())
+            .await?;
+        self.flush().await?;
+
+        let header = self.read_response_header().await?;
+        let status = parse_status_code(&header)?;
+        match status {
+            200..=299 => Ok(self),
+            407 => Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "HTTP proxy requires authentication",
+            )),
+            status => Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("HTTP proxy refused to connect: status {status}"),
+            )),
+        }
+    }
+
+    /// Read the response header, up to and including the empty line that terminates it. Reading
+    /// is done one byte at a time so that no data sent by the target is consumed.
+    async fn read_response_header(&mut self) -> io::Result<Vec<u8>> {
+        let mut header = Vec::new();
+        while !header.ends_with(b"\r\n\r\n") {
+            if header.len() >= MAX_RESPONSE_HEADER_SIZE {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "HTTP proxy response header is too large",
+                ));
+            }
+            header.push(self.read_u8().await?);
+        }
+        Ok(header)
+    }
+}
+
+fn connect_request(target: &SocketAddr, auth: Option<&HttpAuth>) -> String {
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(auth) = auth {
+        let credentials =
+            BASE64_STANDARD.encode(format!("{}:{}", auth.username(), auth.password()));
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// Parse the status code from a status line such as `HTTP/1.1 200 Connection established`.
+fn parse_status_code(header: &[u8]) -> io::Result<u16> {
+    let invalid_response = || io::Error::new(ErrorKind::InvalidData, "Invalid HTTP proxy response");
+
+    let header = std::str::from_utf8(header).map_err(|_| invalid_response())?;
+    let status_line = header.lines().next().ok_or_else(invalid_response)?;
+    let mut parts = status_line.split_whitespace();
+    match parts.next() {
+        Some(version) if version.starts_with("HTTP/1.") => (),
+        _ => return Err(invalid_response()),
+    }
+    parts
+        .next()
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid_response)
+}
+
+impl<S> AsyncRead for ProxyStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ProxyStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ProxyStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S> AsyncWrite for ProxyStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ProxyStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            ProxyStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ProxyStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            ProxyStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ProxyStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            ProxyStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+
+    const TARGET: SocketAddr =
+        SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::new(45, 83, 223, 196)), 443);
+
+    /// Stand-in for an HTTP proxy. It accepts a single connection, checks the request header,
+    /// replies with `response` and then echoes everything it receives.
+    async fn spawn_proxy(response: &'static str) -> (SocketAddr, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            let (mut reader, mut writer) = stream.split();
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            String::from_utf8(request).unwrap()
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_proxy() {
+        let (proxy_addr, proxy) = spawn_proxy("HTTP/1.1 200 Connection established\r\n\r\n").await;
+        let auth = HttpAuth::new("user".to_owned(), "hunter2".to_owned()).unwrap();
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let mut stream = ProxyStream::Plain(stream)
+            .connect(&TARGET, Some(&auth))
+            .await
+            .expect("proxy should accept the CONNECT request");
+
+        stream.write_all(b"ping").await.unwrap();
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"ping");
+        drop(stream);
+
+        let request = proxy.await.unwrap();
+        assert!(request.starts_with("CONNECT 45.83.223.196:443 HTTP/1.1\r\n"));
+        // "user:hunter2" in base64
+        assert!(request.contains("Proxy-Authorization: Basic dXNlcjpodW50ZXIy\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_authentication_required() {
+        let (proxy_addr, _proxy) = spawn_proxy(
+            "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic\r\n\r\n",
+        )
+        .await;
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let error = match ProxyStream::Plain(stream).connect(&TARGET, None).await {
+            Ok(_) => panic!("proxy should reject the CONNECT request"),
+            Err(error) => error,
+        };
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_parse_status_code() {
+        assert_eq!(parse_status_code(b"HTTP/1.0 200 OK\r\n\r\n").unwrap(), 200);
+        assert!(parse_status_code(b"SSH-2.0-OpenSSH\r\n\r\n").is_err());
+        assert!(parse_status_code(b"HTTP/1.1 abc\r\n\r\n").is_err());
+    }
+}
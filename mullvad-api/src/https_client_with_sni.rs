@@ -1,5 +1,6 @@
 use crate::{
     abortable_stream::{AbortableStream, AbortableStreamHandle},
+    http_connect::ProxyStream as HttpProxyStream,
     proxy::{ApiConnection, ApiConnectionMode, ProxyConfig},
     tls_stream::TlsStream,
     DnsResolver,
@@ -78,6 +79,8 @@ enum InnerConnectionMode {
     Shadowsocks(ShadowsocksConfig),
     /// Connect to the destination via a Socks proxy.
     Socks5(SocksConfig),
+    /// Connect to the destination via an HTTP proxy, using the `CONNECT` method.
+    HttpConnect(HttpConnectConfig),
     /// Connect to the destination via Mullvad Encrypted DNS proxy.
     /// See [`mullvad-encrypted-dns-proxy`] for how the proxy works.
     EncryptedDnsProxy(EncryptedDNSConfig),
@@ -163,6 +166,28 @@ impl InnerConnectionMode {
                 )
                 .await
             }
+            // Set up an HTTP CONNECT tunnel, optionally over TLS.
+            InnerConnectionMode::HttpConnect(http) => {
+                let first_hop = http.peer;
+                let make_proxy_stream = |tcp_stream: TcpStream| async move {
+                    let stream = if http.tls {
+                        HttpProxyStream::connect_tls(tcp_stream, http.peer.ip()).await?
+                    } else {
+                        HttpProxyStream::Plain(tcp_stream)
+                    };
+                    stream.connect(addr, http.authentication.as_ref()).await
+                };
+                Self::connect_proxied(
+                    first_hop,
+                    hostname,
+                    make_proxy_stream,
+                    #[cfg(target_os = "android")]
+                    socket_bypass_tx,
+                    #[cfg(any(feature = "api-override", test))]
+                    disable_tls,
+                )
+                .await
+            }
             InnerConnectionMode::EncryptedDnsProxy(proxy_config) => {
                 let first_hop = SocketAddr::V4(proxy_config.addr);
                 let make_proxy_stream = |tcp_stream| async {
@@ -251,6 +276,13 @@ struct SocksConfig {
     authentication: Option<proxy::SocksAuth>,
 }
 
+#[derive(Clone)]
+struct HttpConnectConfig {
+    peer: SocketAddr,
+    authentication: Option<proxy::HttpAuth>,
+    tls: bool,
+}
+
 #[derive(thiserror::Error, Debug)]
 enum ProxyConfigError {
     #[error("Unrecognized cipher selected: {0}")]
@@ -284,6 +316,13 @@ impl TryFrom<ApiConnectionMode> for InnerConnectionMode {
                     peer: config.endpoint,
                     authentication: config.auth,
                 }),
+                ProxyConfig::HttpConnect(config) => {
+                    InnerConnectionMode::HttpConnect(HttpConnectConfig {
+                        peer: config.endpoint,
+                        authentication: config.auth,
+                        tls: config.tls,
+                    })
+                }
                 ProxyConfig::EncryptedDnsProxy(config) => {
                     InnerConnectionMode::EncryptedDnsProxy(config)
                 }
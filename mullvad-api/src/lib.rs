@@ -24,6 +24,7 @@ use availability::ApiAvailability;
 pub mod rest;
 
 mod abortable_stream;
+mod http_connect;
 mod https_client_with_sni;
 pub mod proxy;
 mod tls_stream;
@@ -6,6 +6,7 @@ use std::{
     path::Path,
     pin::Pin,
     task::{self, Poll},
+    time::Duration,
 };
 use talpid_types::{
     net::{proxy, Endpoint, TransportProtocol},
@@ -27,6 +28,19 @@ pub trait ConnectionModeProvider: Send {
 
     /// Receive changes to the connection mode, announced by the provider
     fn receive(&mut self) -> impl std::future::Future<Output = Option<ApiConnectionMode>> + Send;
+
+    /// Report the outcome of a request that was sent using the current connection mode
+    fn report(&self, _outcome: RequestOutcome) {}
+}
+
+/// The outcome of an API request, as seen from the connection mode that was used to send it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestOutcome {
+    /// The API responded. `latency` is the time from sending the request until the response
+    /// was received.
+    Success { latency: Duration },
+    /// The API could not be reached.
+    Failure,
 }
 
 pub struct StaticConnectionModeProvider {
@@ -66,6 +80,7 @@ pub enum ProxyConfig {
     Shadowsocks(proxy::Shadowsocks),
     Socks5Local(proxy::Socks5Local),
     Socks5Remote(proxy::Socks5Remote),
+    HttpConnect(proxy::HttpConnect),
     EncryptedDnsProxy(mullvad_encrypted_dns_proxy::config::ProxyConfig),
 }
 
@@ -80,6 +95,9 @@ impl ProxyConfig {
             ProxyConfig::Socks5Remote(remote) => {
                 Endpoint::from_socket_address(remote.endpoint, TransportProtocol::Tcp)
             }
+            ProxyConfig::HttpConnect(http) => {
+                Endpoint::from_socket_address(http.endpoint, TransportProtocol::Tcp)
+            }
             ProxyConfig::EncryptedDnsProxy(proxy) => {
                 let addr = SocketAddr::V4(proxy.addr);
                 Endpoint::from_socket_address(addr, TransportProtocol::Tcp)
@@ -94,6 +112,7 @@ impl From<proxy::CustomProxy> for ProxyConfig {
             proxy::CustomProxy::Shadowsocks(shadowsocks) => ProxyConfig::Shadowsocks(shadowsocks),
             proxy::CustomProxy::Socks5Local(socks) => ProxyConfig::Socks5Local(socks),
             proxy::CustomProxy::Socks5Remote(socks) => ProxyConfig::Socks5Remote(socks),
+            proxy::CustomProxy::HttpConnect(http) => ProxyConfig::HttpConnect(http),
         }
     }
 }
@@ -327,6 +327,8 @@ struct WireGuardRelay {
     daita: bool,
     #[serde(default)]
     shadowsocks_extra_addr_in: Vec<IpAddr>,
+    #[serde(default)]
+    quic: Option<relay_list::QuicEndpointData>,
 }
 
 impl WireGuardRelay {
@@ -338,6 +340,7 @@ impl WireGuardRelay {
                 public_key: self.public_key,
                 daita: self.daita,
                 shadowsocks_extra_addr_in: self.shadowsocks_extra_addr_in,
+                quic: self.quic,
             }),
         )
     }
@@ -4,7 +4,7 @@ use crate::{
     access::AccessTokenStore,
     availability::ApiAvailability,
     https_client_with_sni::{HttpsConnectorWithSni, HttpsConnectorWithSniHandle},
-    proxy::ConnectionModeProvider,
+    proxy::{ConnectionModeProvider, RequestOutcome},
     DnsResolver,
 };
 use futures::{
@@ -24,8 +24,11 @@ use std::{
     convert::Infallible,
     error::Error as StdError,
     str::FromStr,
-    sync::{Arc, Weak},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
 };
 use talpid_types::ErrorExt;
 
@@ -89,6 +92,12 @@ impl Error {
         )
     }
 
+    /// Return true if the error is a response produced by the API itself. Server errors are
+    /// excluded since they may come from a proxy in front of the API.
+    fn is_api_response(&self) -> bool {
+        matches!(self, Error::ApiError(status, _) if !status.is_server_error())
+    }
+
     /// Return true if there was no route to the destination
     pub fn is_offline(&self) -> bool {
         match self {
@@ -145,6 +154,7 @@ pub(crate) struct RequestService<T: ConnectionModeProvider> {
     connection_mode_provider: T,
     connection_mode_generation: usize,
     api_availability: ApiAvailability,
+    stats: RequestStats,
 }
 
 impl<T: ConnectionModeProvider + 'static> RequestService<T> {
@@ -172,6 +182,7 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                 .build(connector);
 
         let command_tx = Arc::new(command_tx);
+        let stats = RequestStats::default();
 
         let service = Self {
             command_tx: Arc::downgrade(&command_tx),
@@ -181,8 +192,12 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             connection_mode_provider,
             connection_mode_generation: 0,
             api_availability,
+            stats: stats.clone(),
+        };
+        let handle = RequestServiceHandle {
+            tx: command_tx,
+            stats,
         };
-        let handle = RequestServiceHandle { tx: command_tx };
         tokio::spawn(service.into_future());
         handle
     }
@@ -194,6 +209,10 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                     let Some(new_mode) = new_mode else {
                         break;
                     };
+                    // Outcomes of requests sent using the previous mode should not be
+                    // attributed to the new one
+                    self.connection_mode_generation =
+                        self.connection_mode_generation.wrapping_add(1);
                     self.connector_handle.set_connection_mode(new_mode);
                 }
                 command = self.command_rx.next() => {
@@ -223,6 +242,11 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                     self.connection_mode_provider.rotate().await;
                 }
             }
+            RequestCommand::Report(generation, outcome) => {
+                if generation == self.connection_mode_generation {
+                    self.connection_mode_provider.report(outcome);
+                }
+            }
         }
     }
 
@@ -239,19 +263,45 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             .into_future(self.client.clone(), api_availability.clone());
 
         let connection_mode_generation = self.connection_mode_generation;
+        let stats = self.stats.clone();
 
         tokio::spawn(async move {
+            // Do not count the time spent waiting for the API to be unsuspended as latency
+            let _ = api_availability.wait_for_unsuspend().await;
+            let started = Instant::now();
+
             let response = request_future.await.map_err(|error| error.map_aborted());
+            stats.record(&response);
+
+            let outcome = match &response {
+                Ok(_) => Some(RequestOutcome::Success {
+                    latency: started.elapsed(),
+                }),
+                Err(err) if err.is_aborted() => None,
+                Err(err) if err.is_network_error() => {
+                    (!api_availability.is_offline()).then_some(RequestOutcome::Failure)
+                }
+                // The API was reached, even though the request itself failed
+                Err(err) if err.is_api_response() => Some(RequestOutcome::Success {
+                    latency: started.elapsed(),
+                }),
+                // It is not known whether the API was reached
+                Err(_) => None,
+            };
+
+            if let (Some(outcome), Some(tx)) = (outcome, &tx) {
+                let _ =
+                    tx.unbounded_send(RequestCommand::Report(connection_mode_generation, outcome));
+            }
 
             // Switch API endpoint if the request failed due to a network error
-            if let Err(err) = &response {
-                if err.is_network_error() && !api_availability.is_offline() {
+            if outcome == Some(RequestOutcome::Failure) {
+                if let Err(err) = &response {
                     log::error!("{}", err.display_chain_with_msg("HTTP request failed"));
-                    if let Some(tx) = tx {
-                        let _ = tx.unbounded_send(RequestCommand::NextApiConfig(
-                            connection_mode_generation,
-                        ));
-                    }
+                }
+                if let Some(tx) = tx {
+                    let _ = tx
+                        .unbounded_send(RequestCommand::NextApiConfig(connection_mode_generation));
                 }
             }
 
@@ -264,9 +314,15 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
 /// A handle to interact with a spawned `RequestService`.
 pub struct RequestServiceHandle {
     tx: Arc<mpsc::UnboundedSender<RequestCommand>>,
+    stats: RequestStats,
 }
 
 impl RequestServiceHandle {
+    /// Returns the counters of completed requests.
+    pub fn stats(&self) -> RequestStats {
+        self.stats.clone()
+    }
+
     /// Resets the corresponding RequestService, dropping all in-flight requests.
     pub fn reset(&self) {
         let _ = self.tx.unbounded_send(RequestCommand::Reset);
@@ -288,6 +344,38 @@ impl RequestServiceHandle {
     }
 }
 
+/// Counts the requests completed by a `RequestService`. Requests that are aborted, e.g. because
+/// the service is reset, are not counted.
+#[derive(Clone, Default, Debug)]
+pub struct RequestStats(Arc<RequestStatsInner>);
+
+#[derive(Default, Debug)]
+struct RequestStatsInner {
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl RequestStats {
+    /// Number of requests that received the expected response.
+    pub fn succeeded(&self) -> u64 {
+        self.0.succeeded.load(Ordering::Relaxed)
+    }
+
+    /// Number of requests that failed, either due to network errors or an error response.
+    pub fn failed(&self) -> u64 {
+        self.0.failed.load(Ordering::Relaxed)
+    }
+
+    fn record<T>(&self, result: &Result<T>) {
+        let counter = match result {
+            Ok(_) => &self.0.succeeded,
+            Err(error) if error.is_aborted() => return,
+            Err(_) => &self.0.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum RequestCommand {
     NewRequest(
@@ -296,6 +384,7 @@ pub(crate) enum RequestCommand {
     ),
     Reset,
     NextApiConfig(usize),
+    Report(usize, RequestOutcome),
 }
 
 /// A REST request that is sent to the RequestService to be executed.
@@ -1,11 +1,14 @@
+use crate::print_option;
 use anyhow::{anyhow, Result};
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+use mullvad_types::access_method::{AccessMethod, AccessMethodSetting, BuiltInAccessMethod};
 use talpid_types::net::proxy::CustomProxy;
 
 use clap::{Args, Subcommand};
 
-use super::proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd};
+use super::proxies::{
+    HttpConnectAdd, ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd,
+};
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum ApiAccess {
@@ -17,6 +20,8 @@ pub enum ApiAccess {
     /// Lists all API access methods
     ///
     /// * = Enabled
+    ///
+    /// The proxy that the "System proxy" access method would use is listed as well.
     List,
     /// Edit a custom API access method
     Edit(EditCustomCommands),
@@ -33,9 +38,22 @@ pub enum ApiAccess {
     /// connection use https and is therefore encrypted.
     ///
     /// Selecting "Mullvad Bridges" respects your current bridge settings
+    ///
+    /// Selecting "System proxy" connects through the proxy set in the environment of the daemon,
+    /// or on Linux, the proxy configured in NetworkManager
     Use(SelectItem),
     /// Try to reach the Mullvad API using a specific access method
     Test(SelectItem),
+    /// Show how well each API access method has worked
+    ///
+    /// When the Mullvad API cannot be reached, the pinned access method is tried first, followed
+    /// by the remaining enabled ones in order of decreasing health. Recent requests affect the
+    /// health more than old ones.
+    Status,
+    /// Always try a specific API access method first
+    Pin(SelectItem),
+    /// Stop always trying the pinned API access method first
+    Unpin,
 }
 
 impl ApiAccess {
@@ -64,6 +82,15 @@ impl ApiAccess {
             ApiAccess::Get => {
                 Self::get().await?;
             }
+            ApiAccess::Status => {
+                Self::status().await?;
+            }
+            ApiAccess::Pin(cmd) => {
+                Self::pin(cmd).await?;
+            }
+            ApiAccess::Unpin => {
+                Self::unpin().await?;
+            }
         };
         Ok(())
     }
@@ -77,6 +104,18 @@ impl ApiAccess {
                 index + 1,
                 pp::ApiAccessMethodFormatter::new(api_access_method)
             );
+            if matches!(
+                api_access_method.access_method,
+                AccessMethod::BuiltIn(BuiltInAccessMethod::SystemProxy)
+            ) {
+                let detected = rpc.get_system_proxy().await?;
+                print!(
+                    "{}",
+                    pp::SystemProxyFormatter {
+                        detected: &detected
+                    }
+                );
+            }
         }
         Ok(())
     }
@@ -153,6 +192,9 @@ impl ApiAccess {
                         }
                     })
                 }
+                CustomProxy::HttpConnect(http) => {
+                    AccessMethod::from(cmd.params.merge_http_connect(&http)?)
+                }
             },
         };
 
@@ -238,6 +280,52 @@ impl ApiAccess {
         Ok(())
     }
 
+    /// Show the statistics of all API access methods.
+    async fn status() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let access_methods = rpc.get_settings().await?.api_access_methods;
+        let current = rpc.get_current_api_access_method().await?;
+        let stats = rpc.get_api_access_method_stats().await?;
+
+        for (index, access_method) in access_methods.iter().enumerate() {
+            let id = access_method.get_id();
+            print!("{}. {}", index + 1, access_method.get_name());
+            if access_method.enabled() {
+                print!(" *");
+            }
+            if id == current.get_id() {
+                print!(" (in use)");
+            }
+            println!();
+
+            if access_methods.pinned().map(AccessMethodSetting::get_id) == Some(id.clone()) {
+                print_option!("Pinned", "yes");
+            }
+            if let Some(stats) = stats.iter().find(|stats| stats.id == id) {
+                print!("{}", pp::AccessMethodStatsFormatter { stats });
+            }
+        }
+        Ok(())
+    }
+
+    /// Pin an API access method, so that it is always tried first.
+    async fn pin(item: SelectItem) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let access_method = Self::get_access_method(&mut rpc, &item).await?;
+        rpc.set_pinned_access_method(Some(access_method.get_id()))
+            .await?;
+        println!("Pinned access method \"{}\"", access_method.get_name());
+        Ok(())
+    }
+
+    /// Unpin the pinned API access method.
+    async fn unpin() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_pinned_access_method(None).await?;
+        println!("Unpinned access method");
+        Ok(())
+    }
+
     async fn get_access_method(
         rpc: &mut MullvadProxyClient,
         item: &SelectItem,
@@ -266,6 +354,17 @@ pub enum AddCustomCommands {
         #[clap(flatten)]
         add: ShadowsocksAdd,
     },
+    /// Configure an HTTP proxy which supports the CONNECT method
+    Http {
+        /// An easy to remember name for this custom proxy
+        name: String,
+        /// Disable the use of this custom access method. It has to be manually
+        /// enabled at a later stage to be used when accessing the Mullvad API.
+        #[arg(default_value_t = false, short, long)]
+        disabled: bool,
+        #[clap(flatten)]
+        add: HttpConnectAdd,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -298,6 +397,7 @@ impl AddCustomCommands {
     fn name(&self) -> &str {
         match self {
             AddCustomCommands::Shadowsocks { name, .. }
+            | AddCustomCommands::Http { name, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { name, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { name, .. }) => name,
         }
@@ -306,6 +406,7 @@ impl AddCustomCommands {
     fn enabled(&self) -> bool {
         match self {
             AddCustomCommands::Shadowsocks { disabled, .. }
+            | AddCustomCommands::Http { disabled, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { disabled, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { disabled, .. }) => !disabled,
         }
@@ -401,6 +502,9 @@ mod conversions {
                         add.password,
                     ),
                 )),
+                AddCustomCommands::Http { add, .. } => {
+                    talpid_types::HttpConnect::try_from(add).map(daemon_types::AccessMethod::from)
+                }
             }
         }
     }
@@ -408,8 +512,75 @@ mod conversions {
 
 /// Pretty printing of [`AccessMethodSetting`]s
 mod pp {
-    use crate::cmds::proxies::pp::CustomProxyFormatter;
-    use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+    use crate::{cmds::proxies::pp::CustomProxyFormatter, print_option};
+    use mullvad_types::access_method::{
+        AccessMethod, AccessMethodSetting, AccessMethodStats, DetectedSystemProxy,
+    };
+
+    pub struct SystemProxyFormatter<'a> {
+        pub detected: &'a DetectedSystemProxy,
+    }
+
+    impl std::fmt::Display for SystemProxyFormatter<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.detected {
+                DetectedSystemProxy::None => {
+                    print_option!("Detected", "No proxy configured");
+                    Ok(())
+                }
+                DetectedSystemProxy::Found { source, proxy } => {
+                    print_option!("Source", source);
+                    let formatter = CustomProxyFormatter {
+                        custom_proxy: proxy,
+                    };
+                    write!(f, "{}", formatter)
+                }
+                DetectedSystemProxy::Bypassed { source } => {
+                    print_option!("Source", source);
+                    print_option!("Detected", "The API is excluded from the proxy");
+                    Ok(())
+                }
+                DetectedSystemProxy::Unusable { source, reason } => {
+                    print_option!("Source", source);
+                    print_option!("Error", reason);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    pub struct AccessMethodStatsFormatter<'a> {
+        pub stats: &'a AccessMethodStats,
+    }
+
+    impl std::fmt::Display for AccessMethodStatsFormatter<'_> {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let format_time = |time: Option<chrono::DateTime<chrono::Utc>>| match time {
+                Some(time) => time
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+                None => "never".to_string(),
+            };
+
+            let stats = self.stats;
+            print_option!("Health", format!("{:.0}%", stats.health * 100.0));
+            print_option!("Successful requests", stats.successes);
+            print_option!(
+                "Failed requests",
+                format!(
+                    "{} ({} in a row)",
+                    stats.failures, stats.consecutive_failures
+                )
+            );
+            if let Some(latency) = stats.latency {
+                print_option!("Average latency", format!("{} ms", latency.as_millis()));
+            }
+            print_option!("Last success", format_time(stats.last_success));
+            print_option!("Last failure", format_time(stats.last_failure));
+            Ok(())
+        }
+    }
 
     pub struct ApiAccessMethodFormatter<'a> {
         api_access_method: &'a AccessMethodSetting,
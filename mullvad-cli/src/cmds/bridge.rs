@@ -9,12 +9,12 @@ use mullvad_types::{
     },
     relay_list::RelayEndpointData,
 };
-use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+use talpid_types::net::proxy::{CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote};
 
 use crate::cmds::proxies::pp::CustomProxyFormatter;
 
 use super::{
-    proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd},
+    proxies::{HttpConnectAdd, ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd},
     relay::resolve_location_constraint,
     relay_constraints::LocationArgs,
 };
@@ -104,6 +104,11 @@ pub enum AddCustomCommands {
         #[clap(flatten)]
         add: ShadowsocksAdd,
     },
+    /// Configure an HTTP proxy which supports the CONNECT method
+    Http {
+        #[clap(flatten)]
+        add: HttpConnectAdd,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -305,7 +310,9 @@ impl Bridge {
             CustomProxy::Shadowsocks(ss) => *ss = edit.merge_shadowsocks(ss),
             CustomProxy::Socks5Local(local) => *local = edit.merge_socks_local(local),
             CustomProxy::Socks5Remote(remote) => *remote = edit.merge_socks_remote(remote)?,
+            CustomProxy::HttpConnect(http) => *http = edit.merge_http_connect(http)?,
         };
+        ensure_supported_by_openvpn(custom_bridge)?;
 
         rpc.set_bridge_settings(settings.bridge_settings)
             .await
@@ -349,7 +356,13 @@ impl Bridge {
             AddCustomCommands::Shadowsocks { add } => {
                 CustomProxy::Shadowsocks(Shadowsocks::from(add))
             }
+            AddCustomCommands::Http { add } => {
+                CustomProxy::HttpConnect(HttpConnect::try_from(add)?)
+            }
         });
+        if let Some(custom_bridge) = &settings.bridge_settings.custom {
+            ensure_supported_by_openvpn(custom_bridge)?;
+        }
 
         settings.bridge_settings.bridge_type = BridgeType::Custom;
 
@@ -358,3 +371,11 @@ impl Bridge {
             .map_err(anyhow::Error::from)
     }
 }
+
+/// OpenVPN can only talk plain HTTP to a proxy, so refuse bridges that require TLS.
+fn ensure_supported_by_openvpn(custom_bridge: &CustomProxy) -> Result<()> {
+    if let CustomProxy::HttpConnect(HttpConnect { tls: true, .. }) = custom_bridge {
+        bail!("HTTP proxies can only be used as bridges without TLS");
+    }
+    Ok(())
+}
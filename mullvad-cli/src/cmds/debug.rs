@@ -1,4 +1,5 @@
 use anyhow::Result;
+use mullvad_leak_checker::{dns::DnsLeakOpt, ipv6::Ipv6LeakOpt, LeakStatus};
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
@@ -9,6 +10,19 @@ use mullvad_types::{
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
     BlockConnection,
+
+    /// Try to leak traffic outside the tunnel from this process. Requires root/admin privileges.
+    #[clap(subcommand)]
+    LeakCheck(LeakCheckCommands),
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum LeakCheckCommands {
+    /// Send DNS queries on a non-tunnel interface and check whether they get answered.
+    Dns(#[clap(flatten)] DnsLeakOpt),
+
+    /// Try to reach hosts over IPv6 on non-tunnel interfaces using UDP, TCP and ICMPv6.
+    Ipv6(#[clap(flatten)] Ipv6LeakOpt),
 }
 
 impl DebugCommands {
@@ -41,6 +55,21 @@ impl DebugCommands {
                 eprintln!("WARNING: ENTERED BLOCKED MODE");
                 Ok(())
             }
+            DebugCommands::LeakCheck(command) => {
+                let status = match command {
+                    LeakCheckCommands::Dns(opt) => {
+                        mullvad_leak_checker::dns::try_run_leak_test(&opt).await?
+                    }
+                    LeakCheckCommands::Ipv6(opt) => {
+                        mullvad_leak_checker::ipv6::try_run_leak_test(&opt).await?
+                    }
+                };
+                match status {
+                    LeakStatus::NoLeak => println!("No leak detected"),
+                    LeakStatus::LeakDetected(info) => println!("Leak detected: {info:#?}"),
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1,7 +1,10 @@
-use anyhow::Result;
-use clap::Subcommand;
+use anyhow::{anyhow, Result};
+use clap::{ArgGroup, Subcommand};
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+use mullvad_types::settings::{
+    CustomDnsOptions, DefaultDnsOptions, DnsEncryption, DnsOptions, DnsState, EncryptedDnsProtocol,
+    SpkiPin, SplitDnsRule,
+};
 use std::net::IpAddr;
 
 #[derive(Subcommand, Debug)]
@@ -14,6 +17,35 @@ pub enum Dns {
         #[clap(subcommand)]
         cmd: DnsSet,
     },
+
+    /// Resolve specific domains using other DNS servers, such as an internal resolver on the
+    /// local network. Only supported on Linux
+    #[clap(subcommand)]
+    Split(SplitDns),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SplitDns {
+    /// Resolve a domain and its subdomains using the given servers, replacing any existing rule
+    /// for the domain. Servers on the local network are only reachable if local network sharing
+    /// is enabled
+    Add {
+        /// Domain such as corp.example
+        domain: String,
+
+        /// One or more IP addresses pointing to DNS resolvers
+        #[arg(required(true), num_args = 1..)]
+        servers: Vec<IpAddr>,
+    },
+
+    /// Remove the rule for a domain
+    Remove {
+        /// Domain such as corp.example
+        domain: String,
+    },
+
+    /// Remove all rules
+    Clear,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -47,10 +79,30 @@ pub enum DnsSet {
     },
 
     /// Set a list of custom DNS servers
+    #[command(group(ArgGroup::new("encryption").args(["doh", "dot"])))]
     Custom {
         /// One or more IP addresses pointing to DNS resolvers
         #[arg(required(true), num_args = 1..)]
         servers: Vec<IpAddr>,
+
+        /// Query the servers using DNS-over-HTTPS, with a URL such as
+        /// https://dns.example.com/dns-query. The path defaults to /dns-query
+        #[arg(long, value_parser = EncryptedDnsProtocol::from_doh_url)]
+        doh: Option<EncryptedDnsProtocol>,
+
+        /// Query the servers using DNS-over-TLS. The server certificate must be valid for the
+        /// hostname. The port defaults to 853
+        #[arg(
+            long,
+            value_name = "HOSTNAME[:PORT]",
+            value_parser = EncryptedDnsProtocol::from_dot_hostname
+        )]
+        dot: Option<EncryptedDnsProtocol>,
+
+        /// Only accept a server certificate with this public key, given as the base64 encoded
+        /// SHA-256 digest of its SubjectPublicKeyInfo
+        #[arg(long, requires = "encryption")]
+        spki_pin: Option<SpkiPin>,
     },
 }
 
@@ -80,8 +132,20 @@ impl Dns {
                 .await
             }
             Dns::Set {
-                cmd: DnsSet::Custom { servers },
-            } => Self::set_custom(servers).await,
+                cmd:
+                    DnsSet::Custom {
+                        servers,
+                        doh,
+                        dot,
+                        spki_pin,
+                    },
+            } => {
+                let encryption = doh
+                    .or(dot)
+                    .map(|protocol| DnsEncryption { protocol, spki_pin });
+                Self::set_custom(servers, encryption).await
+            }
+            Dns::Split(cmd) => Self::split(cmd).await,
         }
     }
 
@@ -110,6 +174,16 @@ impl Dns {
                 for server in &options.custom_options.addresses {
                     println!("{server}");
                 }
+                if let Some(encryption) = &options.custom_options.encryption {
+                    println!("Encryption: {encryption}");
+                }
+            }
+        }
+
+        if !options.split_rules.is_empty() {
+            println!("Split DNS:");
+            for rule in &options.split_rules {
+                println!("{rule}");
             }
         }
 
@@ -143,16 +217,47 @@ impl Dns {
         Ok(())
     }
 
-    async fn set_custom(servers: Vec<IpAddr>) -> Result<()> {
+    async fn set_custom(servers: Vec<IpAddr>, encryption: Option<DnsEncryption>) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let settings = rpc.get_settings().await?;
         rpc.set_dns_options(DnsOptions {
             state: DnsState::Custom,
-            custom_options: CustomDnsOptions { addresses: servers },
+            custom_options: CustomDnsOptions {
+                addresses: servers,
+                encryption,
+            },
             ..settings.tunnel_options.dns_options
         })
         .await?;
         println!("Updated DNS settings");
         Ok(())
     }
+
+    async fn split(cmd: SplitDns) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+
+        match cmd {
+            SplitDns::Add { domain, servers } => {
+                let rule = SplitDnsRule::new(&domain, servers)?;
+                options
+                    .split_rules
+                    .retain(|existing| existing.domain() != rule.domain());
+                options.split_rules.push(rule);
+            }
+            SplitDns::Remove { domain } => {
+                let domain = SplitDnsRule::normalize_domain(&domain)?;
+                let len = options.split_rules.len();
+                options.split_rules.retain(|rule| rule.domain() != domain);
+                if options.split_rules.len() == len {
+                    return Err(anyhow!("No split DNS rule for {domain}"));
+                }
+            }
+            SplitDns::Clear => options.split_rules.clear(),
+        }
+
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
 }
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use clap::Subcommand;
+use ipnetwork::IpNetwork;
+use mullvad_management_interface::MullvadProxyClient;
+use talpid_types::net::{firewall::FirewallException, TransportProtocol};
+
+#[derive(Subcommand, Debug)]
+pub enum Firewall {
+    /// List the firewall exceptions
+    List,
+
+    /// Allow traffic through the firewall
+    #[clap(subcommand)]
+    Add(Exception),
+
+    /// Remove a firewall exception
+    #[clap(subcommand)]
+    Remove(Exception),
+
+    /// Remove all firewall exceptions
+    Clear,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Exception {
+    /// Allow connections to a host or network outside the tunnel, also while lockdown mode or
+    /// the kill switch is blocking all other traffic
+    Outbound {
+        /// IP address or network, such as 203.0.113.5 or 203.0.113.0/24. The prefix must be at
+        /// least /8 for IPv4 and /16 for IPv6
+        network: IpNetwork,
+
+        /// Destination port. All ports are allowed if this is omitted
+        #[arg(long, short = 'p')]
+        port: Option<u16>,
+
+        #[arg(long, default_value_t = TransportProtocol::Tcp)]
+        protocol: TransportProtocol,
+    },
+
+    /// Allow incoming connections to a local port on the tunnel interface
+    Inbound {
+        port: u16,
+
+        #[arg(long, default_value_t = TransportProtocol::Tcp)]
+        protocol: TransportProtocol,
+    },
+}
+
+impl From<Exception> for FirewallException {
+    fn from(exception: Exception) -> Self {
+        match exception {
+            Exception::Outbound {
+                network,
+                port,
+                protocol,
+            } => FirewallException::Outbound {
+                network,
+                port,
+                protocol,
+            },
+            Exception::Inbound { port, protocol } => FirewallException::Inbound { port, protocol },
+        }
+    }
+}
+
+impl Firewall {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Firewall::List => Self::list().await,
+            Firewall::Add(exception) => {
+                let exception = FirewallException::from(exception);
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.add_firewall_exception(exception).await?;
+                println!("Allowing {exception}");
+                Ok(())
+            }
+            Firewall::Remove(exception) => {
+                let exception = FirewallException::from(exception);
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.remove_firewall_exception(exception).await?;
+                println!("Stopped allowing {exception}");
+                Ok(())
+            }
+            Firewall::Clear => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.clear_firewall_exceptions().await?;
+                println!("Removed all firewall exceptions");
+                Ok(())
+            }
+        }
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let exceptions = rpc.get_settings().await?.firewall_exceptions;
+        if exceptions.is_empty() {
+            println!("No firewall exceptions");
+        }
+        for exception in exceptions {
+            println!("{exception}");
+        }
+        Ok(())
+    }
+}
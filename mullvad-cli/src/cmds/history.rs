@@ -0,0 +1,87 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
+    states::TunnelState,
+};
+
+#[derive(Args, Debug)]
+pub struct History {
+    /// Only show transitions after this time, in RFC 3339 format, e.g. 2024-01-31T12:00:00Z
+    #[arg(long)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only show transitions before this time, in RFC 3339 format
+    #[arg(long)]
+    until: Option<DateTime<Utc>>,
+
+    /// Show at most this many of the most recent transitions
+    #[arg(long, short = 'n')]
+    limit: Option<usize>,
+}
+
+impl History {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let entries = rpc
+            .get_connection_history(ConnectionHistoryFilter {
+                since: self.since,
+                until: self.until,
+                limit: self.limit,
+            })
+            .await?;
+
+        if entries.is_empty() {
+            println!("No tunnel state transitions recorded");
+        }
+        for entry in &entries {
+            print_entry(entry);
+        }
+        Ok(())
+    }
+}
+
+fn print_entry(entry: &ConnectionHistoryEntry) {
+    let time = entry
+        .time
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S");
+
+    let details = match &entry.state {
+        TunnelState::Disconnected { .. } => "Disconnected".to_string(),
+        TunnelState::Connecting {
+            endpoint, location, ..
+        }
+        | TunnelState::Connected {
+            endpoint, location, ..
+        } => {
+            let state = if entry.state.is_connected() {
+                "Connected"
+            } else {
+                "Connecting"
+            };
+            let hostname = location
+                .as_ref()
+                .and_then(|location| location.hostname.as_deref())
+                .unwrap_or("unknown relay");
+            let mut details = format!("{state} to {hostname} ({endpoint})");
+            if let Some(retry_attempt) = entry.retry_attempt {
+                details.push_str(&format!(", attempt {}", retry_attempt + 1));
+            }
+            details
+        }
+        TunnelState::Disconnecting(action) => format!("Disconnecting ({action:?})"),
+        TunnelState::Error(error_state) => {
+            let blocking = if error_state.is_blocking() {
+                "blocking"
+            } else {
+                "not blocking"
+            };
+            format!("Error ({blocking}): {}", error_state.cause())
+        }
+    };
+
+    println!("{time}  {details}");
+}
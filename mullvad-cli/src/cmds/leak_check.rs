@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use itertools::Itertools;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::leak_check::{LeakStatus, LeakTestResult};
+
+#[derive(Args, Debug)]
+pub struct LeakCheck {
+    /// Format output as JSON
+    #[arg(long, short = 'j')]
+    json: bool,
+}
+
+impl LeakCheck {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let results = rpc.run_leak_check().await?;
+
+        if self.json {
+            let json =
+                serde_json::to_string(&results).context("Failed to format output as JSON")?;
+            println!("{json}");
+        } else {
+            for result in &results {
+                print_result(result);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_result(result: &LeakTestResult) {
+    let status = match &result.status {
+        LeakStatus::NoLeak => "no leak".to_string(),
+        LeakStatus::LeakDetected(info) => {
+            let mut status = "LEAK DETECTED".to_string();
+            if let Some(interface) = &info.interface {
+                status.push_str(&format!(" on {interface}"));
+            }
+            if !info.addresses.is_empty() {
+                status.push_str(&format!(" ({})", info.addresses.iter().join(", ")));
+            }
+            status
+        }
+        LeakStatus::Error(error) => format!("failed to run: {error}"),
+    };
+    print_option!(result.test, status);
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::metrics::MetricsEndpoint;
+
+use super::BooleanOption;
+
+#[derive(Subcommand, Debug)]
+pub enum Metrics {
+    /// Display the metrics endpoint settings
+    Get,
+
+    /// Enable or disable the metrics endpoint
+    Set {
+        state: BooleanOption,
+
+        /// Address to serve metrics on. Either a loopback socket address, such as
+        /// 127.0.0.1:9583, or `unix` for a unix socket that only root can connect to
+        #[arg(long)]
+        endpoint: Option<MetricsEndpoint>,
+    },
+}
+
+impl Metrics {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Metrics::Get => Self::get().await,
+            Metrics::Set { state, endpoint } => Self::set(state, endpoint).await,
+        }
+    }
+
+    async fn get() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let metrics = rpc.get_settings().await?.metrics;
+        println!("Metrics endpoint: {}", BooleanOption::from(metrics.enabled));
+        println!("Address: {}", metrics.endpoint);
+        Ok(())
+    }
+
+    async fn set(state: BooleanOption, endpoint: Option<MetricsEndpoint>) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut metrics = rpc.get_settings().await?.metrics;
+        metrics.enabled = *state;
+        if let Some(endpoint) = endpoint {
+            metrics.endpoint = endpoint;
+        }
+        rpc.set_metrics_settings(metrics.clone()).await?;
+        if metrics.enabled {
+            println!("Serving metrics on {}", metrics.endpoint);
+        } else {
+            println!("Disabled metrics endpoint");
+        }
+        Ok(())
+    }
+}
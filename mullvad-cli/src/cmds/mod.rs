@@ -9,10 +9,16 @@ pub mod bridge;
 pub mod custom_list;
 pub mod debug;
 pub mod dns;
+#[cfg(target_os = "linux")]
+pub mod firewall;
+pub mod history;
 pub mod lan;
+pub mod leak_check;
 pub mod lockdown;
+pub mod metrics;
 pub mod obfuscation;
 pub mod patch;
+pub mod profile;
 pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
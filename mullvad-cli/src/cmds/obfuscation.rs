@@ -4,9 +4,13 @@ use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
     relay_constraints::{
-        ObfuscationSettings, SelectedObfuscation, ShadowsocksSettings, Udp2TcpObfuscationSettings,
+        ObfuscationSettings, SelectedObfuscation, ShadowsocksSettings, Socks5ObfuscationSettings,
+        Udp2TcpObfuscationSettings,
     },
 };
+use talpid_types::net::proxy::Socks5Remote;
+
+use super::proxies::Socks5RemoteAdd;
 
 #[derive(Subcommand, Debug)]
 pub enum Obfuscation {
@@ -36,6 +40,13 @@ pub enum SetCommands {
         #[arg(long, short = 'p')]
         port: Constraint<u16>,
     },
+
+    /// Configure the SOCKS5 proxy to relay WireGuard traffic through. The proxy must support
+    /// UDP ASSOCIATE. Only supported on Linux and macOS.
+    Socks5 {
+        #[clap(flatten)]
+        proxy: Socks5RemoteAdd,
+    },
 }
 
 impl Obfuscation {
@@ -50,6 +61,7 @@ impl Obfuscation {
                 );
                 println!("udp2tcp settings: {}", obfuscation_settings.udp2tcp);
                 println!("Shadowsocks settings: {}", obfuscation_settings.shadowsocks);
+                println!("SOCKS5 settings: {}", obfuscation_settings.socks5);
                 Ok(())
             }
             Obfuscation::Set(subcmd) => Self::set(subcmd).await,
@@ -82,6 +94,14 @@ impl Obfuscation {
                 })
                 .await?;
             }
+            SetCommands::Socks5 { proxy } => {
+                let proxy = Socks5Remote::try_from(proxy)?;
+                rpc.set_obfuscation_settings(ObfuscationSettings {
+                    socks5: Socks5ObfuscationSettings { proxy: Some(proxy) },
+                    ..current_settings
+                })
+                .await?;
+            }
         }
 
         println!("Updated obfuscation settings");
@@ -0,0 +1,188 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    custom_list::CustomListsSettings,
+    profile::{MacAddress, NetworkRule, SettingsOverrides, SettingsProfile},
+    relay_constraints::RelaySettingsFormatter,
+};
+
+use super::BooleanOption;
+
+#[derive(Subcommand, Debug)]
+pub enum Profile {
+    /// Show all settings profiles and which one is active
+    List,
+
+    /// Create a new settings profile
+    Create(CreateArgs),
+
+    /// Delete a settings profile
+    Delete {
+        /// A settings profile
+        name: String,
+    },
+
+    /// Activate a settings profile. It stays active until it is deactivated or the network
+    /// changes
+    Activate {
+        /// A settings profile
+        name: String,
+    },
+
+    /// Deactivate the active settings profile and use the regular settings
+    Deactivate,
+}
+
+#[derive(Args, Debug)]
+pub struct CreateArgs {
+    /// A name for the new settings profile
+    name: String,
+
+    /// Override the local network sharing setting
+    #[arg(long, value_parser = BooleanOption::custom_parser("allow", "block"))]
+    lan: Option<BooleanOption>,
+
+    /// Override the lockdown mode setting
+    #[arg(long)]
+    lockdown_mode: Option<BooleanOption>,
+
+    /// Override whether IPv6 is enabled in the tunnel
+    #[arg(long)]
+    ipv6: Option<BooleanOption>,
+
+    /// Use the current relay settings while the profile is active
+    #[arg(long)]
+    current_relay: bool,
+
+    /// Use the current DNS settings while the profile is active
+    #[arg(long)]
+    current_dns: bool,
+
+    /// Activate the profile when the default gateway has this MAC address
+    #[arg(long = "gateway-mac")]
+    gateway_macs: Vec<MacAddress>,
+
+    /// Activate the profile when the default route goes through this interface
+    #[arg(long = "interface")]
+    interfaces: Vec<String>,
+
+    /// Activate the profile when the DHCP server hands out this domain name
+    #[arg(long = "dhcp-domain")]
+    dhcp_domains: Vec<String>,
+}
+
+impl Profile {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            Profile::List => Self::list().await,
+            Profile::Create(args) => Self::create(args).await,
+            Profile::Delete { name } => Self::delete(name).await,
+            Profile::Activate { name } => Self::activate(name).await,
+            Profile::Deactivate => Self::deactivate().await,
+        }
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let list = rpc.get_settings_profiles().await?;
+        let custom_lists = rpc.get_settings().await?.custom_lists;
+        for profile in &list.profiles {
+            if list.active_profile.as_ref() == Some(&profile.name) {
+                println!("{} (active)", profile.name);
+            } else {
+                println!("{}", profile.name);
+            }
+            Self::print_profile_content(profile, &custom_lists);
+        }
+        Ok(())
+    }
+
+    async fn create(args: CreateArgs) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+
+        let settings = if args.current_relay || args.current_dns {
+            Some(rpc.get_effective_settings().await?)
+        } else {
+            None
+        };
+        let overrides = SettingsOverrides {
+            relay_settings: settings
+                .as_ref()
+                .filter(|_| args.current_relay)
+                .map(|settings| settings.relay_settings.clone()),
+            allow_lan: args.lan.map(|lan| *lan),
+            block_when_disconnected: args.lockdown_mode.map(|lockdown| *lockdown),
+            enable_ipv6: args.ipv6.map(|ipv6| *ipv6),
+            dns_options: settings
+                .as_ref()
+                .filter(|_| args.current_dns)
+                .map(|settings| settings.tunnel_options.dns_options.clone()),
+        };
+
+        let rules = args
+            .gateway_macs
+            .into_iter()
+            .map(NetworkRule::GatewayMac)
+            .chain(args.interfaces.into_iter().map(NetworkRule::Interface))
+            .chain(args.dhcp_domains.into_iter().map(NetworkRule::DhcpDomain))
+            .collect();
+
+        rpc.create_settings_profile(SettingsProfile {
+            name: args.name,
+            overrides,
+            rules,
+        })
+        .await?;
+        println!("Created settings profile");
+        Ok(())
+    }
+
+    async fn delete(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.delete_settings_profile(name).await?;
+        println!("Deleted settings profile");
+        Ok(())
+    }
+
+    async fn activate(name: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.activate_settings_profile(name).await?;
+        println!("Activated settings profile");
+        Ok(())
+    }
+
+    async fn deactivate() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.deactivate_settings_profile().await?;
+        println!("Deactivated settings profile");
+        Ok(())
+    }
+
+    fn print_profile_content(profile: &SettingsProfile, custom_lists: &CustomListsSettings) {
+        let overrides = &profile.overrides;
+        if let Some(relay_settings) = &overrides.relay_settings {
+            let relay_settings = RelaySettingsFormatter {
+                settings: relay_settings,
+                custom_lists,
+            };
+            println!("\tRelay settings: {relay_settings}");
+        }
+        if let Some(allow_lan) = overrides.allow_lan {
+            let allow_lan = BooleanOption::with_labels(allow_lan, "allow", "block");
+            println!("\tLocal network sharing: {allow_lan}");
+        }
+        if let Some(lockdown_mode) = overrides.block_when_disconnected {
+            println!("\tLockdown mode: {}", BooleanOption::from(lockdown_mode));
+        }
+        if let Some(ipv6) = overrides.enable_ipv6 {
+            println!("\tIPv6: {}", BooleanOption::from(ipv6));
+        }
+        if overrides.dns_options.is_some() {
+            println!("\tDNS settings: overridden");
+        }
+        for rule in &profile.rules {
+            println!("\tActivate on {rule}");
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use clap::Args;
 use std::net::{IpAddr, SocketAddr};
 use talpid_types::net::{
-    proxy::{Shadowsocks, Socks5Local, Socks5Remote, SocksAuth, SHADOWSOCKS_CIPHERS},
+    proxy::{
+        HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        SHADOWSOCKS_CIPHERS,
+    },
     Endpoint, TransportProtocol,
 };
 
@@ -66,6 +69,35 @@ impl TryFrom<Socks5RemoteAdd> for Socks5Remote {
     }
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct HttpConnectAdd {
+    /// The IP of the remote HTTP proxy
+    pub remote_ip: IpAddr,
+    /// The port of the remote HTTP proxy
+    pub remote_port: u16,
+    /// Connect to the proxy over TLS. The certificate of the proxy must be valid for
+    /// `remote_ip`.
+    #[arg(long)]
+    pub tls: bool,
+
+    #[clap(flatten)]
+    pub authentication: Option<HttpAuthentication>,
+}
+
+impl TryFrom<HttpConnectAdd> for HttpConnect {
+    type Error = Error;
+    fn try_from(add: HttpConnectAdd) -> Result<Self, Self::Error> {
+        Ok(Self {
+            endpoint: SocketAddr::new(add.remote_ip, add.remote_port),
+            auth: add
+                .authentication
+                .map(|auth| HttpAuth::new(auth.username, auth.password))
+                .transpose()?,
+            tls: add.tls,
+        })
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ShadowsocksAdd {
     /// The IP of the remote Shadowsocks-proxy
@@ -100,21 +132,32 @@ pub struct SocksAuthentication {
     pub password: String,
 }
 
+#[derive(Args, Debug, Clone)]
+#[group(requires_all = ["username", "password"])] // https://github.com/clap-rs/clap/issues/5092
+pub struct HttpAuthentication {
+    /// Username for Basic authentication against a remote HTTP proxy
+    #[arg(short, long, required = false)]
+    pub username: String,
+    /// Password for Basic authentication against a remote HTTP proxy
+    #[arg(short, long, required = false)]
+    pub password: String,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ProxyEditParams {
-    /// Username for authentication \[Socks5 (Remote proxy)\]
+    /// Username for authentication \[Socks5 (Remote proxy), HTTP\]
     #[arg(long)]
     pub username: Option<String>,
-    /// Password for authentication \[Socks5 (Remote proxy), Shadowsocks\]
+    /// Password for authentication \[Socks5 (Remote proxy), HTTP, Shadowsocks\]
     #[arg(long)]
     pub password: Option<String>,
     /// Cipher to use \[Shadowsocks\]
     #[arg(value_parser = SHADOWSOCKS_CIPHERS, long)]
     pub cipher: Option<String>,
-    /// The IP of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks\]
+    /// The IP of the remote proxy server \[Socks5 (Local & Remote proxy), HTTP, Shadowsocks\]
     #[arg(long)]
     pub ip: Option<IpAddr>,
-    /// The port of the remote proxy server \[Socks5 (Local & Remote proxy), Shadowsocks\]
+    /// The port of the remote proxy server \[Socks5 (Local & Remote proxy), HTTP, Shadowsocks\]
     #[arg(long)]
     pub port: Option<u16>,
     /// The port that the server on localhost is listening on \[Socks5 (Local proxy)\]
@@ -123,6 +166,9 @@ pub struct ProxyEditParams {
     /// The transport protocol used by the remote proxy \[Socks5 (Local proxy)\]
     #[arg(long)]
     pub transport_protocol: Option<TransportProtocol>,
+    /// Whether to connect to the proxy over TLS \[HTTP\]
+    #[arg(long)]
+    pub tls: Option<bool>,
 }
 
 impl ProxyEditParams {
@@ -165,6 +211,32 @@ impl ProxyEditParams {
         Ok(config)
     }
 
+    pub fn merge_http_connect(self, http: &HttpConnect) -> Result<HttpConnect, Error> {
+        let ip = self.ip.unwrap_or(http.endpoint.ip());
+        let port = self.port.unwrap_or(http.endpoint.port());
+        let mut config = match &http.auth {
+            None => match (self.username, self.password) {
+                (Some(username), Some(password)) => {
+                    let auth = HttpAuth::new(username, password)?;
+                    HttpConnect::new_with_authentication((ip, port), auth)
+                }
+                (None, None) => HttpConnect::new((ip, port)),
+                _ => {
+                    println!("HTTP proxy does not have a username and password set already, so you must provide both or neither when you edit.");
+                    HttpConnect::new((ip, port))
+                }
+            },
+            Some(credentials) => {
+                let username = self.username.unwrap_or(credentials.username().to_string());
+                let password = self.password.unwrap_or(credentials.password().to_string());
+                let auth = HttpAuth::new(username, password)?;
+                HttpConnect::new_with_authentication((ip, port), auth)
+            }
+        };
+        config.tls = self.tls.unwrap_or(http.tls);
+        Ok(config)
+    }
+
     pub fn merge_shadowsocks(self, shadowsocks: &Shadowsocks) -> Shadowsocks {
         let ip = self.ip.unwrap_or(shadowsocks.endpoint.ip());
         let port = self.port.unwrap_or(shadowsocks.endpoint.port());
@@ -212,6 +284,16 @@ pub mod pp {
                     print_option!("Local port", local.local_port);
                     Ok(())
                 }
+                CustomProxy::HttpConnect(http) => {
+                    let protocol = if http.tls { "HTTP (TLS)" } else { "HTTP" };
+                    print_option!("Protocol", protocol);
+                    print_option!("Peer", http.endpoint);
+                    if let Some(credentials) = &http.auth {
+                        print_option!("Username", credentials.username());
+                        print_option!("Password", credentials.password());
+                    }
+                    Ok(())
+                }
             }
         }
     }
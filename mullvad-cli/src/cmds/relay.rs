@@ -1,5 +1,5 @@
 use anyhow::{anyhow, bail, Context, Result};
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
@@ -8,7 +8,8 @@ use mullvad_types::{
     relay_constraints::{
         GeographicLocationConstraint, LocationConstraint, LocationConstraintFormatter,
         OpenVpnConstraints, Ownership, Provider, Providers, RelayConstraints, RelayOverride,
-        RelaySettings, TransportPort, WireguardConstraints,
+        RelaySettings, RetryAttempt, SelectedObfuscation, TransportPort, WireguardConstraints,
+        MAX_MIDDLE_HOPS,
     },
     relay_list::{RelayEndpointData, RelayListCountry},
     ConnectionConfig, CustomTunnelEndpoint,
@@ -17,6 +18,7 @@ use std::{
     collections::HashMap,
     io::BufRead,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
 };
 use talpid_types::net::{
     all_of_the_internet, openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
@@ -43,6 +45,10 @@ pub enum Relay {
     /// Override options for individual relays/servers
     #[clap(subcommand)]
     Override(OverrideCommands),
+
+    /// Configure which connection parameters to try on successive connection attempts
+    #[clap(subcommand)]
+    RetryOrder(RetryOrderCommands),
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -96,12 +102,34 @@ pub enum SetCommands {
     #[clap(subcommand)]
     Tunnel(SetTunnelCommands),
 
+    /// Enable WireGuard multihop through the given relays. The exit relay is
+    /// selected using 'mullvad relay set location'.
+    #[command(override_usage = "mullvad relay set multihop --hops <ENTRY> [MIDDLE]
+
+  Enter through any relay in Sweden:
+
+\tmullvad relay set multihop --hops se
+
+  Enter through se-got-wg-004 and pass through a relay in Zurich before the exit:
+
+\tmullvad relay set multihop --hops se-got-wg-004 \"ch zrh\"")]
+    Multihop {
+        /// Locations of the entry relay and any relays between it and the exit relay, in
+        /// order. Each location is a country, city and hostname separated by spaces, only a
+        /// hostname, or 'any'.
+        #[arg(long, required = true, num_args = 1..=MAX_MIDDLE_HOPS + 1)]
+        hops: Vec<LocationArgs>,
+    },
+
     /// Set tunnel protocol to use: 'any', 'wireguard', or 'openvpn'.
     TunnelProtocol { protocol: Constraint<TunnelType> },
 
+    /// Prefer relays with a low round-trip time among the relays matching the
+    /// other constraints. Latencies are measured while disconnected.
+    PreferLowLatency { policy: BooleanOption },
+
     /// Set a custom VPN relay to use
-    #[clap(subcommand)]
-    Custom(SetCustomCommands),
+    Custom(SetCustomArgs),
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -174,6 +202,28 @@ pub enum EntryArgs {
     CustomList { custom_list_name: String },
 }
 
+#[derive(Args, Debug, Clone)]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+pub struct SetCustomArgs {
+    /// Read a custom WireGuard relay from a wg-quick config file. The MTU and DNS servers in
+    /// the file replace the current tunnel settings.
+    #[arg(long, value_name = "FILE")]
+    from_wg_conf: Option<PathBuf>,
+
+    /// In-tunnel IPv4 gateway of the relay in the wg-quick config. Required unless it can be
+    /// derived from the network of an IPv4 Address in the config, such as 10.0.0.1 for
+    /// 10.0.0.2/24
+    #[arg(long, requires = "from_wg_conf")]
+    v4_gateway: Option<Ipv4Addr>,
+
+    /// In-tunnel IPv6 gateway of the relay in the wg-quick config
+    #[arg(long, requires = "from_wg_conf")]
+    v6_gateway: Option<Ipv6Addr>,
+
+    #[clap(subcommand)]
+    relay: Option<SetCustomCommands>,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum SetCustomCommands {
     /// Use a custom OpenVPN relay
@@ -258,6 +308,90 @@ pub enum OverrideUnsetCommands {
     Ipv6 { hostname: String },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum RetryOrderCommands {
+    /// Display the current retry order
+    Get,
+    /// Add a connection attempt to the retry order. If the built-in retry order is in use, this
+    /// replaces it with a retry order consisting only of the new attempt
+    Add {
+        /// Position to insert the attempt at, starting from 1. Defaults to the end of the list
+        #[arg(long)]
+        position: Option<usize>,
+
+        #[clap(subcommand)]
+        attempt: RetryAttemptArgs,
+    },
+    /// Remove the connection attempt at the given position, starting from 1
+    Remove { position: usize },
+    /// Restore the built-in retry order
+    Reset,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum RetryAttemptArgs {
+    /// Only use the relay constraints
+    Any,
+
+    /// Connect to a WireGuard relay
+    Wireguard {
+        /// Port to use
+        #[arg(long, short = 'p')]
+        port: Option<u16>,
+
+        /// IP protocol to use
+        #[arg(long, short = 'i')]
+        ip_version: Option<IpVersion>,
+
+        /// Obfuscation protocol to use
+        #[arg(long, short = 'o', default_value_t = SelectedObfuscation::Auto)]
+        obfuscation: SelectedObfuscation,
+    },
+
+    /// Connect to an OpenVPN relay
+    Openvpn {
+        /// Port to use
+        #[arg(long, short = 'p', requires = "transport_protocol")]
+        port: Option<u16>,
+
+        /// Transport protocol to use
+        #[arg(long, short = 't')]
+        transport_protocol: Option<TransportProtocol>,
+
+        /// Connect via a bridge. This requires TCP
+        #[arg(long)]
+        bridge: bool,
+    },
+}
+
+impl From<RetryAttemptArgs> for RetryAttempt {
+    fn from(args: RetryAttemptArgs) -> Self {
+        match args {
+            RetryAttemptArgs::Any => RetryAttempt::Any,
+            RetryAttemptArgs::Wireguard {
+                port,
+                ip_version,
+                obfuscation,
+            } => RetryAttempt::Wireguard {
+                port: Constraint::from(port),
+                ip_version: Constraint::from(ip_version),
+                obfuscation,
+            },
+            RetryAttemptArgs::Openvpn {
+                port,
+                transport_protocol,
+                bridge,
+            } => RetryAttempt::Openvpn {
+                port: Constraint::from(transport_protocol.map(|protocol| TransportPort {
+                    protocol,
+                    port: Constraint::from(port),
+                })),
+                bridge,
+            },
+        }
+    }
+}
+
 impl Relay {
     pub async fn handle(self) -> Result<()> {
         match self {
@@ -266,6 +400,7 @@ impl Relay {
             Relay::Update => Self::update().await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
+            Relay::RetryOrder(subcmd) => Self::retry_order(subcmd).await,
         }
     }
 
@@ -297,6 +432,10 @@ impl Relay {
 
                 print_option!("Provider(s)", constraints.providers,);
                 print_option!("Ownership", constraints.ownership,);
+                print_option!(
+                    "Prefer low latency",
+                    BooleanOption::from(constraints.prefer_low_latency),
+                );
 
                 println!("OpenVPN constraints");
 
@@ -336,6 +475,17 @@ impl Relay {
                             custom_lists: &settings.custom_lists
                         }),
                 );
+                for location in &constraints.wireguard_constraints.middle_locations {
+                    print_option!(
+                        "Multihop middle",
+                        location
+                            .as_ref()
+                            .map(|location| LocationConstraintFormatter {
+                                constraint: location,
+                                custom_lists: &settings.custom_lists
+                            }),
+                    );
+                }
             }
         }
 
@@ -417,7 +567,7 @@ impl Relay {
 
     async fn set(subcmd: SetCommands) -> Result<()> {
         match subcmd {
-            SetCommands::Custom(subcmd) => Self::set_custom(subcmd).await,
+            SetCommands::Custom(args) => Self::set_custom(args).await,
             SetCommands::Location(location) => Self::set_location(location).await,
             SetCommands::CustomList { custom_list_name } => {
                 Self::set_custom_list(custom_list_name).await
@@ -425,7 +575,9 @@ impl Relay {
             SetCommands::Provider { providers } => Self::set_providers(providers).await,
             SetCommands::Ownership { ownership } => Self::set_ownership(ownership).await,
             SetCommands::Tunnel(subcmd) => Self::set_tunnel(subcmd).await,
+            SetCommands::Multihop { hops } => Self::set_multihop(hops).await,
             SetCommands::TunnelProtocol { protocol } => Self::set_tunnel_protocol(protocol).await,
+            SetCommands::PreferLowLatency { policy } => Self::set_prefer_low_latency(*policy).await,
         }
     }
 
@@ -447,7 +599,13 @@ impl Relay {
         }
     }
 
-    async fn set_custom(subcmd: SetCustomCommands) -> Result<()> {
+    async fn set_custom(args: SetCustomArgs) -> Result<()> {
+        if let Some(path) = args.from_wg_conf {
+            return Self::set_custom_from_wg_conf(&path, args.v4_gateway, args.v6_gateway).await;
+        }
+        let Some(subcmd) = args.relay else {
+            bail!("Specify either a custom relay or a wg-quick config file");
+        };
         let custom_endpoint = match subcmd {
             SetCustomCommands::Openvpn {
                 host,
@@ -484,6 +642,22 @@ impl Relay {
         Ok(())
     }
 
+    async fn set_custom_from_wg_conf(
+        path: &Path,
+        v4_gateway: Option<Ipv4Addr>,
+        v6_gateway: Option<Ipv6Addr>,
+    ) -> Result<()> {
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.import_wireguard_config(config, v4_gateway, v6_gateway)
+            .await?;
+        println!("Relay constraints, MTU and DNS settings updated");
+
+        Ok(())
+    }
+
     fn read_custom_openvpn_relay(
         host: String,
         port: u16,
@@ -543,6 +717,7 @@ impl Relay {
                     psk: None,
                     constant_packet_size: false,
                 },
+                middle_peers: vec![],
                 exit_peer: None,
                 ipv4_gateway,
                 ipv6_gateway,
@@ -709,6 +884,33 @@ impl Relay {
         .await
     }
 
+    async fn set_multihop(hops: Vec<LocationArgs>) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+
+        let mut hop_locations = vec![];
+        for location_args in hops {
+            let relay_filter = |relay: &mullvad_types::relay_list::Relay| {
+                relay.active && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+            };
+            let location_constraint =
+                resolve_location_constraint(&mut rpc, location_args, relay_filter).await?;
+            hop_locations.push(location_constraint.map(LocationConstraint::from));
+        }
+        let mut hop_locations = hop_locations.into_iter();
+
+        let mut wireguard_constraints = Self::get_wireguard_constraints(&mut rpc).await?;
+        wireguard_constraints.use_multihop(true);
+        wireguard_constraints.entry_location = hop_locations
+            .next()
+            .context("At least one hop must be specified")?;
+        wireguard_constraints.middle_locations = hop_locations.collect();
+
+        Self::update_constraints(|constraints| {
+            constraints.wireguard_constraints = wireguard_constraints;
+        })
+        .await
+    }
+
     async fn get_wireguard_constraints(
         rpc: &mut MullvadProxyClient,
     ) -> Result<WireguardConstraints> {
@@ -728,6 +930,13 @@ impl Relay {
         .await
     }
 
+    async fn set_prefer_low_latency(prefer_low_latency: bool) -> Result<()> {
+        Self::update_constraints(|constraints| {
+            constraints.prefer_low_latency = prefer_low_latency;
+        })
+        .await
+    }
+
     async fn update_override(
         hostname: &str,
         update_fn: impl FnOnce(&mut RelayOverride),
@@ -889,6 +1098,56 @@ impl Relay {
         }
         Ok(())
     }
+
+    async fn retry_order(subcmd: RetryOrderCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let retry_order = rpc.get_settings().await?.retry_order;
+
+        match subcmd {
+            RetryOrderCommands::Get => match retry_order {
+                Some(retry_order) => {
+                    for (index, attempt) in retry_order.iter().enumerate() {
+                        println!("{:>3}. {attempt}", index + 1);
+                    }
+                }
+                None => println!("Using the built-in retry order"),
+            },
+            RetryOrderCommands::Add { position, attempt } => {
+                let mut retry_order = retry_order.unwrap_or_default();
+                let index = match position {
+                    Some(position) if position == 0 || position > retry_order.len() + 1 => {
+                        bail!("Position must be between 1 and {}", retry_order.len() + 1);
+                    }
+                    Some(position) => position - 1,
+                    None => retry_order.len(),
+                };
+                retry_order.insert(index, RetryAttempt::from(attempt));
+                rpc.set_retry_order(retry_order).await?;
+                println!("Updated retry order");
+            }
+            RetryOrderCommands::Remove { position } => {
+                let Some(mut retry_order) = retry_order else {
+                    bail!("The built-in retry order is in use");
+                };
+                if position == 0 || position > retry_order.len() {
+                    bail!("Position must be between 1 and {}", retry_order.len());
+                }
+                retry_order.remove(position - 1);
+                if retry_order.is_empty() {
+                    rpc.reset_retry_order().await?;
+                    println!("Removed the last attempt. Using the built-in retry order");
+                } else {
+                    rpc.set_retry_order(retry_order).await?;
+                    println!("Updated retry order");
+                }
+            }
+            RetryOrderCommands::Reset => {
+                rpc.reset_retry_order().await?;
+                println!("Using the built-in retry order");
+            }
+        }
+        Ok(())
+    }
 }
 
 fn parse_transport_port(
@@ -1,9 +1,11 @@
+use anyhow::{bail, Context};
 use clap::Args;
 use mullvad_types::{
     constraints::Constraint,
     location::{CityCode, CountryCode, Hostname},
     relay_constraints::{GeographicLocationConstraint, LocationConstraint},
 };
+use std::str::FromStr;
 
 #[derive(Args, Debug, Clone)]
 pub struct LocationArgs {
@@ -15,6 +17,26 @@ pub struct LocationArgs {
     pub hostname: Option<Hostname>,
 }
 
+impl FromStr for LocationArgs {
+    type Err = anyhow::Error;
+
+    /// Parse a location given as a single, whitespace-separated argument, such as "se got".
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        let mut parts = location.split_whitespace().map(str::to_owned);
+        let country = parts.next().context("Empty location")?;
+        let city = parts.next();
+        let hostname = parts.next();
+        if parts.next().is_some() {
+            bail!("A location consists of at most a country, city and hostname");
+        }
+        Ok(LocationArgs {
+            country,
+            city,
+            hostname,
+        })
+    }
+}
+
 impl From<LocationArgs> for Constraint<GeographicLocationConstraint> {
     fn from(value: LocationArgs) -> Self {
         if value.country.eq_ignore_ascii_case("any") {
@@ -1,6 +1,13 @@
 use anyhow::Result;
-use clap::Subcommand;
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Subcommand,
+};
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::settings::SplitTunnelMode;
+use std::path::PathBuf;
+
+use super::super::BooleanOption;
 
 /// Manage split tunneling. To launch applications outside the tunnel, use the program
 /// 'mullvad-exclude' instead of this command
@@ -14,17 +21,50 @@ pub enum SplitTunnel {
     Delete { pid: i32 },
     /// Stop excluding all processes from the tunnel
     Clear,
+
+    /// Enable or disable excluding the applications in the app list
+    Set { policy: BooleanOption },
+
+    /// Set whether the applications in the app list are excluded from the tunnel, or are the
+    /// only ones that use it
+    Mode {
+        #[arg(value_parser = PossibleValuesParser::new(["exclude", "include"]).map(|mode| {
+            if mode == "include" {
+                SplitTunnelMode::Include
+            } else {
+                SplitTunnelMode::Exclude
+            }
+        }))]
+        mode: SplitTunnelMode,
+    },
+
+    /// Manage applications to exclude from the tunnel. Processes running these executables are
+    /// excluded automatically, also after a restart
+    #[clap(subcommand)]
+    App(App),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum App {
+    /// Display the excluded applications
+    List,
+    /// Exclude an executable from the tunnel
+    Add { path: PathBuf },
+    /// Stop excluding an executable from the tunnel
+    Remove { path: PathBuf },
+    /// Stop excluding all executables from the tunnel
+    Clear,
 }
 
 impl SplitTunnel {
     pub async fn handle(self) -> Result<()> {
         match self {
             SplitTunnel::List => {
-                let pids = MullvadProxyClient::new()
-                    .await?
-                    .get_split_tunnel_processes()
-                    .await?;
+                let mut rpc = MullvadProxyClient::new().await?;
+                let pids = rpc.get_split_tunnel_processes().await?;
+                let mechanism = rpc.get_split_tunnel_mechanism().await?;
 
+                println!("Mechanism: {mechanism}");
                 println!("Excluded PIDs:");
                 for pid in &pids {
                     println!("{pid}");
@@ -56,6 +96,72 @@ impl SplitTunnel {
                 println!("Stopped excluding all processes");
                 Ok(())
             }
+            SplitTunnel::Set { policy } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .set_split_tunnel_state(*policy)
+                    .await?;
+                println!("Split tunnel policy: {policy}");
+                Ok(())
+            }
+            SplitTunnel::Mode { mode } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .set_split_tunnel_mode(mode)
+                    .await?;
+                println!("Split tunnel mode: {mode}");
+                Ok(())
+            }
+            SplitTunnel::App(subcmd) => Self::app(subcmd).await,
+        }
+    }
+
+    async fn app(subcmd: App) -> Result<()> {
+        match subcmd {
+            App::List => {
+                let settings = MullvadProxyClient::new()
+                    .await?
+                    .get_settings()
+                    .await?
+                    .split_tunnel;
+
+                let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
+                println!("Split tunneling state: {enable_exclusions}");
+                println!("Split tunneling mode: {}", settings.mode);
+
+                match settings.mode {
+                    SplitTunnelMode::Exclude => println!("Excluded applications:"),
+                    SplitTunnelMode::Include => println!("Included applications:"),
+                }
+                for path in &settings.apps {
+                    println!("{}", path.display());
+                }
+                Ok(())
+            }
+            App::Add { path } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .add_split_tunnel_app(path)
+                    .await?;
+                println!("Added path to excluded apps list");
+                Ok(())
+            }
+            App::Remove { path } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .remove_split_tunnel_app(path)
+                    .await?;
+                println!("Stopped excluding app from tunnel");
+                Ok(())
+            }
+            App::Clear => {
+                MullvadProxyClient::new()
+                    .await?
+                    .clear_split_tunnel_apps()
+                    .await?;
+                println!("Stopped excluding all apps");
+                Ok(())
+            }
         }
     }
 }
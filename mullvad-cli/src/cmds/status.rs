@@ -1,8 +1,8 @@
 use anyhow::{Context, Result};
 use clap::{Args, Subcommand};
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use mullvad_management_interface::{client::DaemonEvent, MullvadProxyClient};
-use mullvad_types::{device::DeviceState, states::TunnelState};
+use mullvad_types::{device::DeviceState, states::TunnelState, wireguard::TunnelStats};
 use serde::Serialize;
 use std::fmt::Debug;
 
@@ -27,6 +27,15 @@ pub struct StatusArgs {
     /// Format output as JSON
     #[arg(long, short = 'j', conflicts_with_all = ["verbose", "debug"])]
     json: bool,
+
+    /// Show traffic statistics for the tunnel. When listening, they are printed every second
+    #[arg(long)]
+    stats: bool,
+}
+
+enum Update {
+    Event(DaemonEvent),
+    Stats(TunnelStats),
 }
 
 impl Status {
@@ -35,9 +44,31 @@ impl Status {
         args: StatusArgs,
         mut previous_tunnel_state: TunnelState,
     ) -> Result<()> {
-        let mut event_stream = rpc.events_listen().await?;
-        while let Some(event) = event_stream.next().await {
-            match event? {
+        let mut stats_rpc = rpc.clone();
+        let events = rpc
+            .events_listen()
+            .await?
+            .map(|event| event.map(Update::Event));
+        let stats = if args.stats {
+            stats_rpc
+                .stream_tunnel_stats()
+                .await?
+                .map(|stats| stats.map(Update::Stats))
+                .left_stream()
+        } else {
+            stream::pending().right_stream()
+        };
+
+        let mut updates = stream::select(events, stats);
+        while let Some(update) = updates.next().await {
+            let event = match update? {
+                Update::Event(event) => event,
+                Update::Stats(stats) => {
+                    print_stats(&args, &stats)?;
+                    continue;
+                }
+            };
+            match event {
                 DaemonEvent::TunnelState(new_state) => {
                     if args.debug {
                         println!("New tunnel state: {new_state:#?}");
@@ -68,6 +99,13 @@ impl Status {
                 DaemonEvent::NewAccessMethod(access_method) => {
                     print_debug_or_json(&args, "New access method", &access_method)?;
                 }
+                DaemonEvent::LeakDetected(leak) => {
+                    if args.debug || args.json {
+                        print_debug_or_json(&args, "Leak detected", &leak)?;
+                    } else {
+                        println!("Leak detected by the {} leak test", leak.test);
+                    }
+                }
             }
         }
         Ok(())
@@ -90,6 +128,14 @@ pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
         format::print_state(&state, None, args.verbose);
     }
 
+    if args.stats && cmd != Some(Status::Listen) {
+        // The first sample has no previous one to compute the throughput from
+        let mut stats_stream = rpc.stream_tunnel_stats().await?.skip(1);
+        if let Some(stats) = stats_stream.next().await {
+            print_stats(&args, &stats?)?;
+        }
+    }
+
     if cmd == Some(Status::Listen) {
         Status::listen(rpc, args, state).await?;
     }
@@ -111,6 +157,15 @@ fn print_account_logged_out(state: &TunnelState, device: &DeviceState) {
     }
 }
 
+fn print_stats(args: &StatusArgs, stats: &TunnelStats) -> Result<()> {
+    if args.debug || args.json {
+        print_debug_or_json(args, "Tunnel stats", stats)
+    } else {
+        format::print_tunnel_stats(stats);
+        Ok(())
+    }
+}
+
 fn print_debug_or_json<T: Debug + Serialize>(
     args: &StatusArgs,
     debug_message: &str,
@@ -1,10 +1,12 @@
 use anyhow::Result;
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
     wireguard::{QuantumResistantState, RotationInterval, DEFAULT_ROTATION_INTERVAL},
 };
+use std::time::Duration;
+use talpid_types::net::wireguard::PingTarget;
 
 use super::BooleanOption;
 use crate::print_option;
@@ -17,6 +19,10 @@ pub enum Tunnel {
     /// Set tunnel options
     #[clap(subcommand)]
     Set(TunnelOptions),
+
+    /// Print the config of the connected WireGuard tunnel in the wg-quick format.
+    /// The output contains the private key of this device.
+    ExportWgConf,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -47,6 +53,8 @@ pub enum TunnelOptions {
         /// The key rotation interval. Number of hours, or 'any'
         #[arg(long)]
         rotation_interval: Option<Constraint<RotationInterval>>,
+        #[clap(flatten)]
+        connectivity: ConnectivityCheckArgs,
         /// Rotate WireGuard key
         #[clap(subcommand)]
         rotate_key: Option<RotateKey>,
@@ -57,6 +65,36 @@ pub enum TunnelOptions {
     Ipv6 { state: BooleanOption },
 }
 
+/// Advanced options for the check that detects broken WireGuard tunnels
+#[derive(Args, Debug, Clone)]
+pub struct ConnectivityCheckArgs {
+    /// Seconds to wait for traffic when connecting. Doubled after each failed attempt
+    #[arg(long, value_name = "SECONDS")]
+    connectivity_establish_timeout: Option<u64>,
+    /// Seconds without a reply to pings before the tunnel is considered broken
+    #[arg(long, value_name = "SECONDS")]
+    connectivity_ping_timeout: Option<u64>,
+    /// Seconds without any traffic before pings are sent
+    #[arg(long, value_name = "SECONDS")]
+    connectivity_traffic_timeout: Option<u64>,
+    /// Seconds between pings
+    #[arg(long, value_name = "SECONDS")]
+    connectivity_ping_interval: Option<u64>,
+    /// Host to ping inside the tunnel: 'gateway' or an IPv4 address
+    #[arg(long, value_name = "gateway|IP")]
+    connectivity_ping_target: Option<PingTarget>,
+}
+
+impl ConnectivityCheckArgs {
+    fn is_empty(&self) -> bool {
+        self.connectivity_establish_timeout.is_none()
+            && self.connectivity_ping_timeout.is_none()
+            && self.connectivity_traffic_timeout.is_none()
+            && self.connectivity_ping_interval.is_none()
+            && self.connectivity_ping_target.is_none()
+    }
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum RotateKey {
     /// Replace the WireGuard key with a new one
@@ -68,9 +106,16 @@ impl Tunnel {
         match self {
             Tunnel::Get => Self::get().await,
             Tunnel::Set(options) => Self::set(options).await,
+            Tunnel::ExportWgConf => Self::export_wg_conf().await,
         }
     }
 
+    async fn export_wg_conf() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        print!("{}", rpc.export_wireguard_config().await?);
+        Ok(())
+    }
+
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let tunnel_options = rpc.get_settings().await?.tunnel_options;
@@ -117,6 +162,25 @@ impl Tunnel {
             },
         );
 
+        let connectivity = tunnel_options.wireguard.connectivity;
+        print_option!(
+            "Establish timeout",
+            format_args!("{}s", connectivity.establish_timeout.as_secs()),
+        );
+        print_option!(
+            "Ping timeout",
+            format_args!("{}s", connectivity.ping_timeout.as_secs()),
+        );
+        print_option!(
+            "Traffic timeout",
+            format_args!("{}s", connectivity.traffic_timeout.as_secs()),
+        );
+        print_option!(
+            "Ping interval",
+            format_args!("{}s", connectivity.ping_interval.as_secs()),
+        );
+        print_option!("Ping target", connectivity.ping_target);
+
         println!("Generic options");
 
         print_option!(
@@ -140,6 +204,7 @@ impl Tunnel {
                 daita,
                 daita_direct_only,
                 rotation_interval,
+                connectivity,
                 rotate_key,
             } => {
                 Self::handle_wireguard(
@@ -148,6 +213,7 @@ impl Tunnel {
                     daita,
                     daita_direct_only,
                     rotation_interval,
+                    connectivity,
                     rotate_key,
                 )
                 .await
@@ -180,6 +246,7 @@ impl Tunnel {
         daita: Option<BooleanOption>,
         daita_direct_only: Option<BooleanOption>,
         rotation_interval: Option<Constraint<RotationInterval>>,
+        connectivity: ConnectivityCheckArgs,
         rotate_key: Option<RotateKey>,
     ) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
@@ -221,6 +288,32 @@ impl Tunnel {
             }
         }
 
+        if !connectivity.is_empty() {
+            let mut options = rpc
+                .get_settings()
+                .await?
+                .tunnel_options
+                .wireguard
+                .connectivity;
+            if let Some(secs) = connectivity.connectivity_establish_timeout {
+                options.establish_timeout = Duration::from_secs(secs);
+            }
+            if let Some(secs) = connectivity.connectivity_ping_timeout {
+                options.ping_timeout = Duration::from_secs(secs);
+            }
+            if let Some(secs) = connectivity.connectivity_traffic_timeout {
+                options.traffic_timeout = Duration::from_secs(secs);
+            }
+            if let Some(secs) = connectivity.connectivity_ping_interval {
+                options.ping_interval = Duration::from_secs(secs);
+            }
+            if let Some(target) = connectivity.connectivity_ping_target {
+                options.ping_target = target;
+            }
+            rpc.set_wireguard_connectivity_check(options).await?;
+            println!("Connectivity check settings have been updated");
+        }
+
         if matches!(rotate_key, Some(RotateKey::RotateKey)) {
             rpc.rotate_wireguard_key().await?;
             println!("Rotated WireGuard key");
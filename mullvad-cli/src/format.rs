@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use itertools::Itertools;
 use mullvad_types::{
     auth_failed::AuthFailed, features::FeatureIndicators, location::GeoIpLocation,
-    states::TunnelState,
+    states::TunnelState, wireguard::TunnelStats,
 };
 use talpid_types::{
     net::{Endpoint, TunnelEndpoint},
@@ -71,12 +71,14 @@ pub fn print_state(state: &TunnelState, previous_state: Option<&TunnelState>, ve
             endpoint,
             location,
             feature_indicators,
+            active_profile,
         } => {
             let (old_endpoint, old_location, old_feature_indicators) = match previous_state {
                 Some(Connecting {
                     endpoint,
                     location,
                     feature_indicators,
+                    ..
                 }) => {
                     if verbose {
                         println!("Connecting")
@@ -98,17 +100,20 @@ pub fn print_state(state: &TunnelState, previous_state: Option<&TunnelState>, ve
                 old_feature_indicators,
                 verbose,
             );
+            print_active_profile(active_profile.as_deref(), previous_state, verbose);
         }
         Connected {
             endpoint,
             location,
             feature_indicators,
+            active_profile,
         } => {
             let (old_endpoint, old_location, old_feature_indicators) = match previous_state {
                 Some(Connected {
                     endpoint,
                     location,
                     feature_indicators,
+                    ..
                 }) => {
                     if verbose {
                         println!("Connected")
@@ -119,6 +124,7 @@ pub fn print_state(state: &TunnelState, previous_state: Option<&TunnelState>, ve
                     endpoint,
                     location,
                     feature_indicators,
+                    ..
                 }) => {
                     println!("Connected");
                     (Some(endpoint), location, Some(feature_indicators))
@@ -138,6 +144,7 @@ pub fn print_state(state: &TunnelState, previous_state: Option<&TunnelState>, ve
                 old_feature_indicators,
                 verbose,
             );
+            print_active_profile(active_profile.as_deref(), previous_state, verbose);
         }
         Disconnecting(ActionAfterDisconnect::Reconnect) => {}
         Disconnecting(_) => println!("Disconnecting"),
@@ -145,6 +152,25 @@ pub fn print_state(state: &TunnelState, previous_state: Option<&TunnelState>, ve
     }
 }
 
+fn print_active_profile(
+    active_profile: Option<&str>,
+    previous_state: Option<&TunnelState>,
+    verbose: bool,
+) {
+    let old_profile = match previous_state {
+        Some(
+            TunnelState::Connecting { active_profile, .. }
+            | TunnelState::Connected { active_profile, .. },
+        ) => active_profile.as_deref(),
+        _ => None,
+    };
+    if let Some(profile) = active_profile {
+        if verbose || old_profile != Some(profile) {
+            print_option!("Settings profile", profile);
+        }
+    }
+}
+
 fn connection_information(
     endpoint: Option<&TunnelEndpoint>,
     location: Option<&GeoIpLocation>,
@@ -159,6 +185,10 @@ fn connection_information(
         .filter(|_| verbose)
         .and_then(|endpoint| endpoint.tunnel_interface.clone());
     info.insert("Tunnel interface", tunnel_interface_fmt);
+    let tunnel_mtu_fmt = endpoint
+        .and_then(|endpoint| endpoint.mtu)
+        .map(|mtu| mtu.to_string());
+    info.insert("Tunnel MTU", tunnel_mtu_fmt);
 
     let bridge_type_fmt = endpoint
         .filter(|_| verbose)
@@ -210,6 +240,55 @@ fn print_connection_info(
     }
 }
 
+pub fn print_tunnel_stats(stats: &TunnelStats) {
+    if stats.peers.is_empty() {
+        println!("No tunnel traffic statistics available");
+        return;
+    }
+    for peer in &stats.peers {
+        println!("Peer {}", peer.public_key);
+        print_option!(
+            "Sent",
+            format!(
+                "{} ({}/s)",
+                format_bytes(peer.tx_bytes),
+                format_bytes(peer.tx_rate)
+            )
+        );
+        print_option!(
+            "Received",
+            format!(
+                "{} ({}/s)",
+                format_bytes(peer.rx_bytes),
+                format_bytes(peer.rx_rate)
+            )
+        );
+        let last_handshake = match peer.last_handshake {
+            Some(time) => {
+                let elapsed = chrono::Utc::now().signed_duration_since(time);
+                format!("{} seconds ago", elapsed.num_seconds().max(0))
+            }
+            None => "Never".to_string(),
+        };
+        print_option!("Last handshake", last_handshake);
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{value:.2} {}", UNITS[unit])
+    }
+}
+
 pub fn format_location(location: &GeoIpLocation) -> String {
     let mut formatted_location = location.country.to_string();
     if let Some(city) = &location.city {
@@ -42,6 +42,12 @@ enum Cli {
     #[clap(subcommand)]
     Lan(lan::Lan),
 
+    /// Manage exceptions to the firewall. These hosts and ports are reachable in every tunnel
+    /// state
+    #[cfg(target_os = "linux")]
+    #[clap(subcommand)]
+    Firewall(firewall::Firewall),
+
     /// Connect to a VPN relay
     Connect {
         /// Wait until connected before exiting
@@ -112,6 +118,17 @@ enum Cli {
     #[clap(subcommand)]
     Tunnel(tunnel::Tunnel),
 
+    /// Show recent tunnel state transitions, including the relay, endpoint and any errors
+    History(history::History),
+
+    /// Export statistics about the daemon and the tunnel in the Prometheus text format on a local
+    /// HTTP endpoint
+    #[clap(subcommand)]
+    Metrics(metrics::Metrics),
+
+    /// Try to leak traffic outside the connected tunnel and report the result of each leak test
+    LeakCheck(leak_check::LeakCheck),
+
     /// Show information about the current Mullvad version
     /// and available versions
     Version,
@@ -135,6 +152,11 @@ enum Cli {
     #[clap(subcommand)]
     CustomList(custom_list::CustomList),
 
+    /// Manage settings profiles that override the regular settings, either when activated
+    /// manually or when a matching network is joined
+    #[clap(subcommand)]
+    Profile(profile::Profile),
+
     /// Apply a JSON patch generated by 'export-settings'
     #[clap(arg_required_else_help = true)]
     ImportSettings {
@@ -164,6 +186,8 @@ async fn main() -> Result<()> {
         Cli::LockdownMode(cmd) => cmd.handle().await,
         Cli::Dns(cmd) => cmd.handle().await,
         Cli::Lan(cmd) => cmd.handle().await,
+        #[cfg(target_os = "linux")]
+        Cli::Firewall(cmd) => cmd.handle().await,
         Cli::Obfuscation(cmd) => cmd.handle().await,
         Cli::ApiAccess(cmd) => cmd.handle().await,
         Cli::Version => version::print().await,
@@ -172,7 +196,11 @@ async fn main() -> Result<()> {
         Cli::Tunnel(cmd) => cmd.handle().await,
         Cli::SplitTunnel(cmd) => cmd.handle().await,
         Cli::Status { cmd, args } => status::handle(cmd, args).await,
+        Cli::History(cmd) => cmd.handle().await,
+        Cli::Metrics(cmd) => cmd.handle().await,
+        Cli::LeakCheck(cmd) => cmd.handle().await,
         Cli::CustomList(cmd) => cmd.handle().await,
+        Cli::Profile(cmd) => cmd.handle().await,
         Cli::ImportSettings { file } => patch::import(file).await,
         Cli::ExportSettings { file } => patch::export(file).await,
 
@@ -93,6 +93,30 @@ impl Daemon {
         Ok(())
     }
 
+    /// Pin an [`AccessMethodSetting`], so that it is always tried first when
+    /// connecting to the API. Passing `None` unpins the currently pinned
+    /// access method.
+    ///
+    /// Like [`Daemon::use_api_access_method`], this enables the access method
+    /// if it is disabled.
+    pub async fn pin_api_access_method(
+        &mut self,
+        access_method: Option<access_method::Id>,
+    ) -> Result<(), Error> {
+        self.settings
+            .try_update(|settings| -> Result<(), Error> {
+                let access_methods = &mut settings.api_access_methods;
+                access_methods.set_pinned(access_method.clone())?;
+                if let Some(id) = access_method {
+                    access_methods
+                        .update(|setting| setting.get_id() == id, |setting| setting.enable());
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
     pub fn get_api_access_method(
         &mut self,
         access_method: access_method::Id,
@@ -0,0 +1,393 @@
+//! Keeps track of how well each API access method has worked, so that the methods which are most
+//! likely to reach the API can be tried first. The statistics are kept in memory and periodically
+//! stored in the cache directory so that they survive restarts.
+
+use chrono::{DateTime, Utc};
+use mullvad_api::proxy::RequestOutcome;
+use mullvad_types::access_method::{AccessMethodSetting, AccessMethodStats, Id, Settings};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+    time::Instant,
+};
+
+/// Name of the statistics file in the cache directory.
+pub const ACCESS_METHOD_STATS_FILENAME: &str = "api-access-stats.json";
+
+/// Time after which an outcome only counts half as much towards the health of an access method.
+const HEALTH_HALF_LIFE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Maximum time that recorded outcomes are kept only in memory.
+const SAVE_DELAY: Duration = Duration::from_secs(60);
+
+/// Weight of the most recent response time in the moving latency average.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// What is known about a single access method.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Record {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u64,
+    latency: Option<Duration>,
+    last_success: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+    /// Number of successes, where each success is weighted by its age. The weights are as of
+    /// the most recent outcome.
+    decayed_successes: f64,
+    /// Like `decayed_successes`, but for failures.
+    decayed_failures: f64,
+}
+
+impl Record {
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        self.last_success.max(self.last_failure)
+    }
+
+    /// Factor by which the decayed counts have shrunk between the last outcome and `now`.
+    fn decay(&self, now: DateTime<Utc>) -> f64 {
+        let Some(updated) = self.updated() else {
+            return 1.0;
+        };
+        let elapsed = (now - updated).to_std().unwrap_or_default();
+        0.5f64.powf(elapsed.as_secs_f64() / HEALTH_HALF_LIFE.as_secs_f64())
+    }
+
+    /// A score between 0 and 1. Methods that have never been used score 0.5, and as outcomes
+    /// age the score returns towards 0.5.
+    fn health(&self, now: DateTime<Utc>) -> f64 {
+        let decay = self.decay(now);
+        let successes = self.decayed_successes * decay;
+        let failures = self.decayed_failures * decay;
+        (successes + 1.0) / (successes + failures + 2.0)
+    }
+
+    fn record(&mut self, outcome: RequestOutcome, now: DateTime<Utc>) {
+        let decay = self.decay(now);
+        self.decayed_successes *= decay;
+        self.decayed_failures *= decay;
+
+        match outcome {
+            RequestOutcome::Success { latency } => {
+                self.successes += 1;
+                self.consecutive_failures = 0;
+                self.decayed_successes += 1.0;
+                self.last_success = Some(now);
+                self.latency = Some(match self.latency {
+                    Some(average) => {
+                        average.mul_f64(1.0 - LATENCY_SMOOTHING)
+                            + latency.mul_f64(LATENCY_SMOOTHING)
+                    }
+                    None => latency,
+                });
+            }
+            RequestOutcome::Failure => {
+                self.failures += 1;
+                self.consecutive_failures += 1;
+                self.decayed_failures += 1.0;
+                self.last_failure = Some(now);
+            }
+        }
+    }
+}
+
+pub struct AccessMethodStatsStore {
+    path: PathBuf,
+    records: HashMap<Id, Record>,
+    /// When the unsaved outcomes should be written to disk, if there are any.
+    save_at: Option<Instant>,
+}
+
+impl AccessMethodStatsStore {
+    /// Load the statistics from the cache directory. Missing or damaged statistics are not an
+    /// error, since all access methods are simply considered equally healthy then.
+    pub async fn new(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(ACCESS_METHOD_STATS_FILENAME);
+        let records = match fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse API access method statistics")
+                );
+                HashMap::new()
+            }),
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read API access method statistics")
+                    );
+                }
+                HashMap::new()
+            }
+        };
+        AccessMethodStatsStore {
+            path,
+            records,
+            save_at: None,
+        }
+    }
+
+    /// Record the outcome of a request sent using the access method `id`. The outcome is saved
+    /// to disk within [`SAVE_DELAY`], see [`Self::save_due`].
+    pub fn record(&mut self, id: Id, outcome: RequestOutcome) {
+        self.records
+            .entry(id)
+            .or_default()
+            .record(outcome, Utc::now());
+        self.save_at
+            .get_or_insert_with(|| Instant::now() + SAVE_DELAY);
+    }
+
+    /// Returns a future that resolves once there are unsaved outcomes that should be written to
+    /// disk using [`Self::save`]. It never resolves if everything has been saved.
+    pub fn save_due(&self) -> impl Future<Output = ()> {
+        let save_at = self.save_at;
+        async move {
+            match save_at {
+                Some(save_at) => tokio::time::sleep_until(save_at).await,
+                None => std::future::pending().await,
+            }
+        }
+    }
+
+    /// Write any unsaved outcomes to disk.
+    pub async fn save(&mut self) {
+        if self.save_at.take().is_none() {
+            return;
+        }
+        if let Err(error) = self.write().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to save API access method statistics")
+            );
+        }
+    }
+
+    /// Forget about access methods that no longer exist.
+    pub fn retain(&mut self, settings: &Settings) {
+        self.records
+            .retain(|id, _| settings.iter().any(|setting| setting.get_id() == *id));
+    }
+
+    /// Return the enabled access methods in the order that they should be tried.
+    pub fn rank(&self, settings: &Settings) -> Vec<AccessMethodSetting> {
+        self.rank_at(settings, Utc::now())
+    }
+
+    /// Return the statistics of all access methods, in the order that they are listed in
+    /// `settings`.
+    pub fn stats(&self, settings: &Settings) -> Vec<AccessMethodStats> {
+        let now = Utc::now();
+        settings
+            .iter()
+            .map(|setting| {
+                let id = setting.get_id();
+                let record = self.records.get(&id).cloned().unwrap_or_default();
+                AccessMethodStats {
+                    id,
+                    successes: record.successes,
+                    failures: record.failures,
+                    consecutive_failures: record.consecutive_failures,
+                    latency: record.latency,
+                    last_success: record.last_success,
+                    last_failure: record.last_failure,
+                    health: record.health(now),
+                }
+            })
+            .collect()
+    }
+
+    /// The pinned access method always comes first. The remaining ones are ordered by decreasing
+    /// health, and methods that are equally healthy keep the order they are listed in.
+    fn rank_at(&self, settings: &Settings, now: DateTime<Utc>) -> Vec<AccessMethodSetting> {
+        let pinned = settings.pinned().map(AccessMethodSetting::get_id);
+        let health = |setting: &AccessMethodSetting| {
+            self.records
+                .get(&setting.get_id())
+                .map(|record| record.health(now))
+                .unwrap_or(0.5)
+        };
+
+        let mut ranked: Vec<_> = settings
+            .iter()
+            .filter(|setting| setting.enabled())
+            .cloned()
+            .collect();
+        ranked.sort_by(|a, b| {
+            let a_pinned = pinned.as_ref() == Some(&a.get_id());
+            let b_pinned = pinned.as_ref() == Some(&b.get_id());
+            b_pinned
+                .cmp(&a_pinned)
+                .then_with(|| health(b).total_cmp(&health(a)))
+        });
+        ranked
+    }
+
+    async fn write(&self) -> io::Result<()> {
+        let mut file = mullvad_fs::AtomicFile::new(&self.path).await?;
+        let json = serde_json::to_string_pretty(&self.records)
+            .map_err(|_| io::Error::other("serialization failed"))?;
+        file.write_all(json.as_bytes()).await?;
+        file.write_all(b"\n").await?;
+        file.finalize().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::access_method::{AccessMethod, BuiltInAccessMethod};
+
+    const SUCCESS: RequestOutcome = RequestOutcome::Success {
+        latency: Duration::from_millis(100),
+    };
+
+    fn store() -> AccessMethodStatsStore {
+        AccessMethodStatsStore {
+            path: PathBuf::new(),
+            records: HashMap::new(),
+            save_at: None,
+        }
+    }
+
+    fn record(
+        store: &mut AccessMethodStatsStore,
+        setting: &AccessMethodSetting,
+        outcome: RequestOutcome,
+        count: usize,
+        time: DateTime<Utc>,
+    ) {
+        let record = store.records.entry(setting.get_id()).or_default();
+        for _ in 0..count {
+            record.record(outcome, time);
+        }
+    }
+
+    fn names(ranked: Vec<AccessMethodSetting>) -> Vec<String> {
+        ranked.into_iter().map(|setting| setting.name).collect()
+    }
+
+    #[test]
+    fn test_failing_method_is_tried_last() {
+        let settings = Settings::default();
+        let now = Utc::now();
+        let mut store = store();
+
+        assert_eq!(
+            names(store.rank_at(&settings, now)),
+            ["Direct", "Mullvad Bridges", "Encrypted DNS proxy"],
+            "untested methods should be tried in list order"
+        );
+
+        record(
+            &mut store,
+            settings.direct(),
+            RequestOutcome::Failure,
+            50,
+            now,
+        );
+        record(&mut store, settings.encrypted_dns_proxy(), SUCCESS, 1, now);
+        assert_eq!(
+            names(store.rank_at(&settings, now)),
+            ["Encrypted DNS proxy", "Mullvad Bridges", "Direct"]
+        );
+    }
+
+    #[test]
+    fn test_old_outcomes_decay() {
+        let settings = Settings::default();
+        let now = Utc::now();
+        let long_ago = now - chrono::Duration::days(7);
+        let mut store = store();
+
+        record(
+            &mut store,
+            settings.direct(),
+            RequestOutcome::Failure,
+            50,
+            long_ago,
+        );
+        record(
+            &mut store,
+            settings.mullvad_bridges(),
+            RequestOutcome::Failure,
+            1,
+            now,
+        );
+
+        let direct = &store.records[&settings.direct().get_id()];
+        assert!(direct.health(long_ago) < 0.1);
+        assert!(direct.health(now) > 0.45);
+        assert_eq!(
+            names(store.rank_at(&settings, now)),
+            ["Encrypted DNS proxy", "Direct", "Mullvad Bridges"]
+        );
+    }
+
+    #[test]
+    fn test_pinned_method_is_tried_first() {
+        let mut settings = Settings::default();
+        let proxy = AccessMethodSetting::new(
+            "proxy".to_owned(),
+            true,
+            AccessMethod::from(talpid_types::net::proxy::Socks5Remote::new((
+                [127, 0, 0, 1],
+                1080,
+            ))),
+        );
+        settings.append(proxy.clone());
+        settings.set_pinned(Some(proxy.get_id())).unwrap();
+        settings.update(
+            |setting| setting.access_method == AccessMethod::from(BuiltInAccessMethod::Bridge),
+            |setting| setting.disable(),
+        );
+
+        let now = Utc::now();
+        let mut store = store();
+        record(&mut store, &proxy, RequestOutcome::Failure, 10, now);
+
+        assert_eq!(
+            names(store.rank_at(&settings, now)),
+            ["proxy", "Direct", "Encrypted DNS proxy"],
+            "pinned method should be first, and disabled methods should be excluded"
+        );
+    }
+
+    #[test]
+    fn test_latency_average() {
+        let mut record = Record::default();
+        let now = Utc::now();
+        record.record(SUCCESS, now);
+        record.record(
+            RequestOutcome::Success {
+                latency: Duration::from_millis(600),
+            },
+            now,
+        );
+        assert_eq!(record.latency, Some(Duration::from_millis(200)));
+        assert_eq!(record.successes, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_save_is_not_postponed_by_new_outcomes() {
+        let settings = Settings::default();
+        let mut store = store();
+        let start = Instant::now();
+
+        store.record(settings.direct().get_id(), SUCCESS);
+        tokio::time::sleep(SAVE_DELAY / 2).await;
+        store.record(settings.direct().get_id(), RequestOutcome::Failure);
+
+        store.save_due().await;
+        assert_eq!(start.elapsed(), SAVE_DELAY);
+    }
+}
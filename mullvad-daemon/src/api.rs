@@ -5,22 +5,21 @@
 //! establishing connections when performing API requests.
 #[cfg(target_os = "android")]
 use crate::DaemonCommand;
-use crate::DaemonEventSender;
+use crate::{access_method_stats::AccessMethodStatsStore, system_proxy, DaemonEventSender};
 use futures::{
     channel::{mpsc, oneshot},
-    StreamExt,
+    FutureExt, StreamExt,
 };
-#[cfg(feature = "api-override")]
-use mullvad_api::ApiEndpoint;
 use mullvad_api::{
     availability::ApiAvailability,
-    proxy::{ApiConnectionMode, ConnectionModeProvider, ProxyConfig},
-    AddressCache,
+    proxy::{ApiConnectionMode, ConnectionModeProvider, ProxyConfig, RequestOutcome},
+    AddressCache, ApiEndpoint,
 };
 use mullvad_encrypted_dns_proxy::state::EncryptedDnsProxyState;
 use mullvad_relay_selector::RelaySelector;
 use mullvad_types::access_method::{
-    AccessMethod, AccessMethodSetting, BuiltInAccessMethod, Id, Settings,
+    AccessMethod, AccessMethodSetting, AccessMethodStats, BuiltInAccessMethod, DetectedSystemProxy,
+    Id, Settings,
 };
 use std::{net::SocketAddr, path::PathBuf};
 use talpid_core::mpsc::Sender;
@@ -37,6 +36,12 @@ pub enum Message {
         ResponseTx<Option<ResolvedConnectionMode>>,
         AccessMethodSetting,
     ),
+    Stats(ResponseTx<Vec<AccessMethodStats>>),
+    SystemProxy(ResponseTx<DetectedSystemProxy>),
+    /// The outcome of an API request sent using the current access method.
+    Report(RequestOutcome),
+    /// Save the access method statistics and stop.
+    Shutdown(oneshot::Sender<()>),
 }
 
 /// Calling [`AccessMethodEvent::send`] will cause a
@@ -127,6 +132,10 @@ impl std::fmt::Display for Message {
             Message::Rotate(_) => f.write_str("Rotate"),
             Message::Update(..) => f.write_str("Update"),
             Message::Resolve(..) => f.write_str("Resolve"),
+            Message::Stats(_) => f.write_str("Stats"),
+            Message::SystemProxy(_) => f.write_str("SystemProxy"),
+            Message::Report(_) => f.write_str("Report"),
+            Message::Shutdown(_) => f.write_str("Shutdown"),
         }
     }
 }
@@ -204,6 +213,35 @@ impl AccessModeSelectorHandle {
             log::debug!("Failed while getting the next access method");
         })
     }
+
+    /// Return the statistics of all access methods.
+    pub async fn stats(&self) -> Result<Vec<AccessMethodStats>> {
+        self.send_command(Message::Stats).await.inspect_err(|_| {
+            log::debug!("Failed to get access method statistics");
+        })
+    }
+
+    /// Look for a proxy configured on the system, as done when the `System proxy` access method
+    /// is used.
+    pub async fn system_proxy(&self) -> Result<DetectedSystemProxy> {
+        self.send_command(Message::SystemProxy)
+            .await
+            .inspect_err(|_| {
+                log::debug!("Failed to detect the system proxy");
+            })
+    }
+
+    /// Report the outcome of an API request sent using the current access method.
+    pub fn report(&self, outcome: RequestOutcome) {
+        let _ = self.cmd_tx.unbounded_send(Message::Report(outcome));
+    }
+
+    /// Save the access method statistics and stop the [`AccessModeSelector`].
+    pub async fn shutdown(&self) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.cmd_tx.unbounded_send(Message::Shutdown(tx));
+        let _ = rx.await;
+    }
 }
 
 pub struct AccessModeConnectionModeProvider {
@@ -241,6 +279,10 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
             handle.rotate().await.ok();
         }
     }
+
+    fn report(&self, outcome: RequestOutcome) {
+        self.handle.report(outcome);
+    }
 }
 
 /// A small actor which takes care of handling the logic around rotating
@@ -251,8 +293,11 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
 /// [`ApiConnectionMode::Direct`]) via a bridge ([`ApiConnectionMode::Proxied`])
 /// or via any supported custom proxy protocol
 /// ([`talpid_types::net::proxy::CustomProxy`]).
+///
+/// Access methods are tried in the order given by [`AccessMethodStatsStore::rank`],
+/// i.e. the pinned access method first, followed by the others in order of
+/// decreasing health.
 pub struct AccessModeSelector {
-    #[cfg(feature = "api-override")]
     api_endpoint: ApiEndpoint,
     cmd_rx: mpsc::UnboundedReceiver<Message>,
     cache_dir: PathBuf,
@@ -265,8 +310,11 @@ pub struct AccessModeSelector {
     access_method_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
     connection_mode_provider_sender: mpsc::UnboundedSender<ApiConnectionMode>,
     current: ResolvedConnectionMode,
-    /// `index` is used to keep track of the [`AccessMethodSetting`] to use.
-    index: usize,
+    /// Statistics used for deciding which [`AccessMethodSetting`] to try next.
+    stats: AccessMethodStatsStore,
+    /// Access methods that have failed since an access method last worked. These are not
+    /// retried until every enabled access method has been tried.
+    attempted: Vec<Id>,
 }
 
 impl AccessModeSelector {
@@ -275,7 +323,7 @@ impl AccessModeSelector {
         relay_selector: RelaySelector,
         #[cfg_attr(not(feature = "api-override"), allow(unused_mut))]
         mut access_method_settings: Settings,
-        #[cfg(feature = "api-override")] api_endpoint: ApiEndpoint,
+        api_endpoint: ApiEndpoint,
         access_method_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
         address_cache: AddressCache,
     ) -> Result<(AccessModeSelectorHandle, AccessModeConnectionModeProvider)> {
@@ -292,10 +340,15 @@ impl AccessModeSelector {
         // Initialize the Encrypted DNS cache
         let mut encrypted_dns_proxy_cache = EncryptedDnsProxyState::default();
 
-        // Always start looking from the position of `Direct`.
-        let (index, next) = Self::find_next_active(0, &access_method_settings);
-        let initial_connection_mode = Self::resolve_inner_with_default(
-            &next,
+        let stats = AccessMethodStatsStore::new(&cache_dir).await;
+        let mut attempted = vec![];
+        let next = Self::find_next_active(&attempted, &access_method_settings, &stats);
+        let initial_connection_mode = Self::resolve_first_usable(
+            next,
+            &mut attempted,
+            &access_method_settings,
+            &stats,
+            &api_endpoint,
             &relay_selector,
             &mut encrypted_dns_proxy_cache,
             &address_cache,
@@ -316,8 +369,8 @@ impl AccessModeSelector {
             access_method_event_sender,
             connection_mode_provider_sender: change_tx,
             current: initial_connection_mode,
-            index,
-            #[cfg(feature = "api-override")]
+            stats,
+            attempted,
             api_endpoint,
         };
 
@@ -332,7 +385,20 @@ impl AccessModeSelector {
     }
 
     async fn into_future(mut self) {
-        while let Some(cmd) = self.cmd_rx.next().await {
+        let mut shutdown_tx = None;
+        loop {
+            let save_due = self.stats.save_due().fuse();
+            futures::pin_mut!(save_due);
+            let cmd = futures::select! {
+                () = save_due => {
+                    self.stats.save().await;
+                    continue;
+                }
+                cmd = self.cmd_rx.next() => cmd,
+            };
+            let Some(cmd) = cmd else {
+                break;
+            };
             log::trace!("Processing {cmd} command");
             let execution = match cmd {
                 Message::Get(tx) => self.on_get_access_method(tx),
@@ -340,6 +406,16 @@ impl AccessModeSelector {
                 Message::Rotate(tx) => self.on_next_connection_mode(tx).await,
                 Message::Update(tx, values) => self.on_update_access_methods(tx, values).await,
                 Message::Resolve(tx, setting) => self.on_resolve_access_method(tx, setting).await,
+                Message::Stats(tx) => self.on_get_stats(tx),
+                Message::SystemProxy(tx) => self.on_get_system_proxy(tx),
+                Message::Report(outcome) => {
+                    self.on_report(outcome);
+                    Ok(())
+                }
+                Message::Shutdown(tx) => {
+                    shutdown_tx = Some(tx);
+                    break;
+                }
             };
             match execution {
                 Ok(_) => (),
@@ -352,6 +428,11 @@ impl AccessModeSelector {
                 }
             }
         }
+
+        self.stats.save().await;
+        if let Some(tx) = shutdown_tx {
+            let _ = tx.send(());
+        }
     }
 
     fn reply<T>(&self, tx: ResponseTx<T>, value: T) -> Result<()> {
@@ -378,19 +459,38 @@ impl AccessModeSelector {
             }
         }
 
-        let Some((index, method)) = self
+        let Some(method) = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == id)
+            .find(|access_method| access_method.get_id() == id)
         else {
             return;
         };
 
-        self.index = index;
+        self.attempted.clear();
         self.set_current(method.to_owned()).await;
     }
 
+    fn on_get_stats(&mut self, tx: ResponseTx<Vec<AccessMethodStats>>) -> Result<()> {
+        self.reply(tx, self.stats.stats(&self.access_method_settings))
+    }
+
+    fn on_get_system_proxy(&mut self, tx: ResponseTx<DetectedSystemProxy>) -> Result<()> {
+        // Detection may involve DNS lookups, so don't hold up other messages while it's running
+        let api_host = self.api_endpoint.host().to_owned();
+        tokio::spawn(async move {
+            let _ = tx.send(Ok(system_proxy::detect(&api_host).await));
+        });
+        Ok(())
+    }
+
+    fn on_report(&mut self, outcome: RequestOutcome) {
+        if matches!(outcome, RequestOutcome::Success { .. }) {
+            self.attempted.clear();
+        }
+        self.stats.record(self.current.setting.get_id(), outcome);
+    }
+
     async fn on_next_connection_mode(&mut self, tx: ResponseTx<ApiConnectionMode>) -> Result<()> {
         let next = self.next_connection_mode().await?;
         self.reply(tx, next)
@@ -410,15 +510,34 @@ impl AccessModeSelector {
             );
         }
 
-        let (next_index, next) =
-            Self::find_next_active(self.index + 1, &self.access_method_settings);
-        self.index = next_index;
+        let current = self.current.setting.get_id();
+        if !self.attempted.contains(&current) {
+            self.attempted.push(current);
+        }
+        let next =
+            Self::find_next_active(&self.attempted, &self.access_method_settings, &self.stats);
+        if self.attempted.contains(&next.get_id()) {
+            // Every access method has been tried, so start over
+            self.attempted.clear();
+        }
         self.set_current(next).await;
         Ok(self.current.connection_mode.clone())
     }
 
+    /// Set and announce `access_method` as the current one. If it cannot be used right now, the
+    /// next usable access method is selected instead.
     async fn set_current(&mut self, access_method: AccessMethodSetting) {
-        let resolved = self.resolve_with_default(access_method).await;
+        let resolved = Self::resolve_first_usable(
+            access_method,
+            &mut self.attempted,
+            &self.access_method_settings,
+            &self.stats,
+            &self.api_endpoint,
+            &self.relay_selector,
+            &mut self.encrypted_dns_proxy_cache,
+            &self.address_cache,
+        )
+        .await;
 
         // Note: If the daemon is busy waiting for a call to this function
         // to complete while we wait for the daemon to fully handle this
@@ -465,18 +584,21 @@ impl AccessModeSelector {
 
     /// Find the next access method to use.
     ///
-    /// * `start`: From which point in `access_methods` to start the search.
+    /// * `attempted`: Access methods to skip, unless all of them have been attempted.
     /// * `access_methods`: The search space.
-    fn find_next_active(start: usize, access_methods: &Settings) -> (usize, AccessMethodSetting) {
-        access_methods
+    /// * `stats`: Decides the order in which access methods are tried.
+    fn find_next_active(
+        attempted: &[Id],
+        access_methods: &Settings,
+        stats: &AccessMethodStatsStore,
+    ) -> AccessMethodSetting {
+        let ranked = stats.rank(access_methods);
+        ranked
             .iter()
+            .find(|access_method| !attempted.contains(&access_method.get_id()))
+            .or(ranked.first())
             .cloned()
-            .enumerate()
-            .cycle()
-            .skip(start)
-            .take(access_methods.cardinality())
-            .find(|(_index, access_method)| access_method.enabled())
-            .unwrap_or_else(|| (0, access_methods.direct().clone()))
+            .unwrap_or_else(|| access_methods.direct().clone())
     }
 
     async fn on_update_access_methods(
@@ -489,18 +611,38 @@ impl AccessModeSelector {
     }
 
     async fn update_access_methods(&mut self, access_methods: Settings) -> Result<()> {
+        let old_pinned = self
+            .access_method_settings
+            .pinned()
+            .map(|pinned| pinned.get_id());
         self.access_method_settings = access_methods;
+        self.stats.retain(&self.access_method_settings);
+        self.attempted.retain(|id| {
+            self.access_method_settings
+                .iter()
+                .any(|method| method.get_id() == *id)
+        });
+
+        // Switch to a newly pinned access method right away
+        if let Some(pinned) = self.access_method_settings.pinned() {
+            if pinned.enabled()
+                && Some(pinned.get_id()) != old_pinned
+                && pinned.get_id() != self.current.setting.get_id()
+            {
+                self.attempted.clear();
+                self.set_current(pinned.to_owned()).await;
+                return Ok(());
+            }
+        }
 
         let new_current = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == self.current.setting.get_id());
+            .find(|access_method| access_method.get_id() == self.current.setting.get_id());
 
         match new_current {
-            Some((index, new_current)) => {
+            Some(new_current) => {
                 // If the current method was modified, announce changes
-                self.index = index;
                 if self.current.setting != *new_current {
                     if new_current.enabled() {
                         self.set_current(new_current.to_owned()).await;
@@ -510,11 +652,7 @@ impl AccessModeSelector {
                 }
             }
             None => {
-                // Current method was removed: A new access method will suddenly have the same index
-                // as the one we are removing, but we want it to still be a
-                // candidate. A minor hack to achieve this is to simply decrement
-                // the current index.
-                self.index = self.index.saturating_sub(1);
+                // Current method was removed
                 self.next_connection_mode().await?;
             }
         }
@@ -536,6 +674,7 @@ impl AccessModeSelector {
     ) -> Option<ResolvedConnectionMode> {
         Self::resolve_inner(
             &access_method,
+            &self.api_endpoint,
             &self.relay_selector,
             &mut self.encrypted_dns_proxy_cache,
             &self.address_cache,
@@ -545,13 +684,18 @@ impl AccessModeSelector {
 
     async fn resolve_inner(
         access_method: &AccessMethodSetting,
+        api_endpoint: &ApiEndpoint,
         relay_selector: &RelaySelector,
         encrypted_dns_proxy_cache: &mut EncryptedDnsProxyState,
         address_cache: &AddressCache,
     ) -> Option<ResolvedConnectionMode> {
-        let connection_mode =
-            Self::resolve_connection_mode(access_method, relay_selector, encrypted_dns_proxy_cache)
-                .await?;
+        let connection_mode = Self::resolve_connection_mode(
+            access_method,
+            api_endpoint,
+            relay_selector,
+            encrypted_dns_proxy_cache,
+        )
+        .await?;
         let endpoint =
             resolve_allowed_endpoint(&connection_mode, address_cache.get_address().await);
         Some(ResolvedConnectionMode {
@@ -561,52 +705,64 @@ impl AccessModeSelector {
         })
     }
 
-    /// Resolve an access method into a set of connection details - fall back to
-    /// [`ApiConnectionMode::Direct`] in case `access_method` does not yield anything.
-    async fn resolve_with_default(
-        &mut self,
-        access_method: AccessMethodSetting,
-    ) -> ResolvedConnectionMode {
-        Self::resolve_inner_with_default(
-            &access_method,
-            &self.relay_selector,
-            &mut self.encrypted_dns_proxy_cache,
-            &self.address_cache,
-        )
-        .await
-    }
-
-    async fn resolve_inner_with_default(
-        access_method: &AccessMethodSetting,
+    /// Resolve `access_method` into a set of connection details. If it cannot be resolved, for
+    /// example because no system proxy is configured, it is added to `attempted` and the next
+    /// access method given by [`Self::find_next_active`] is tried instead.
+    ///
+    /// If no access method can be resolved, [`ApiConnectionMode::Direct`] is used as a last
+    /// resort. It is always reported as the direct access method, since that is what it is,
+    /// regardless of which access method was asked for.
+    #[allow(clippy::too_many_arguments)]
+    async fn resolve_first_usable(
+        mut access_method: AccessMethodSetting,
+        attempted: &mut Vec<Id>,
+        access_methods: &Settings,
+        stats: &AccessMethodStatsStore,
+        api_endpoint: &ApiEndpoint,
         relay_selector: &RelaySelector,
         encrypted_dns_proxy_cache: &mut EncryptedDnsProxyState,
         address_cache: &AddressCache,
     ) -> ResolvedConnectionMode {
-        match Self::resolve_inner(
-            access_method,
-            relay_selector,
-            encrypted_dns_proxy_cache,
-            address_cache,
-        )
-        .await
-        {
-            Some(resolved) => resolved,
-            None => {
-                log::trace!("Defaulting to direct API connection");
-                ResolvedConnectionMode {
-                    connection_mode: ApiConnectionMode::Direct,
-                    endpoint: resolve_allowed_endpoint(
-                        &ApiConnectionMode::Direct,
-                        address_cache.get_address().await,
-                    ),
-                    setting: access_method.clone(),
-                }
+        loop {
+            if let Some(resolved) = Self::resolve_inner(
+                &access_method,
+                api_endpoint,
+                relay_selector,
+                encrypted_dns_proxy_cache,
+                address_cache,
+            )
+            .await
+            {
+                return resolved;
+            }
+            log::warn!(
+                "Skipping API access method {name}, since it cannot be used right now",
+                name = access_method.name
+            );
+            if !attempted.contains(&access_method.get_id()) {
+                attempted.push(access_method.get_id());
+            }
+            let next = Self::find_next_active(attempted, access_methods, stats);
+            if attempted.contains(&next.get_id()) {
+                break;
             }
+            access_method = next;
+        }
+
+        log::debug!("No API access method can be used, defaulting to a direct connection");
+        ResolvedConnectionMode {
+            connection_mode: ApiConnectionMode::Direct,
+            endpoint: resolve_allowed_endpoint(
+                &ApiConnectionMode::Direct,
+                address_cache.get_address().await,
+            ),
+            setting: access_methods.direct().clone(),
         }
     }
 
     async fn resolve_connection_mode(
         access_method: &AccessMethodSetting,
+        api_endpoint: &ApiEndpoint,
         relay_selector: &RelaySelector,
         encrypted_dns_proxy_cache: &mut EncryptedDnsProxyState,
     ) -> Option<ApiConnectionMode> {
@@ -633,6 +789,17 @@ impl AccessModeSelector {
                     };
                     ApiConnectionMode::Proxied(ProxyConfig::from(edp))
                 }
+                AccessMethod::BuiltIn(BuiltInAccessMethod::SystemProxy) => {
+                    match system_proxy::detect(api_endpoint.host()).await {
+                        DetectedSystemProxy::Found { proxy, .. } => {
+                            ApiConnectionMode::Proxied(ProxyConfig::from(proxy))
+                        }
+                        detected => {
+                            log::warn!("Could not use the system proxy: {detected}");
+                            return None;
+                        }
+                    }
+                }
                 AccessMethod::Custom(config) => {
                     ApiConnectionMode::Proxied(ProxyConfig::from(config.clone()))
                 }
@@ -0,0 +1,177 @@
+//! Journal of tunnel state transitions. It is stored in the cache directory so that it survives
+//! restarts and can be included in problem reports.
+
+use mullvad_types::connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+/// Maximum number of entries to keep.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize connection history entry")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write connection history file")]
+    Write(#[source] io::Error),
+}
+
+pub struct ConnectionHistory {
+    path: PathBuf,
+    entries: VecDeque<ConnectionHistoryEntry>,
+    /// Number of lines in the file. Entries are appended to the file until it contains twice
+    /// [MAX_ENTRIES] lines, at which point it is rewritten with only the retained entries.
+    lines_on_disk: usize,
+}
+
+impl ConnectionHistory {
+    /// Load the history from the cache directory. A missing or damaged journal is not an error,
+    /// since the history is only used for troubleshooting.
+    pub async fn new(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(mullvad_paths::CONNECTION_HISTORY_FILENAME);
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(error) => {
+                if error.kind() != io::ErrorKind::NotFound {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to read connection history")
+                    );
+                }
+                String::new()
+            }
+        };
+
+        let (entries, lines_on_disk, damaged) = parse(&contents);
+        let mut history = ConnectionHistory {
+            path,
+            entries,
+            lines_on_disk,
+        };
+
+        if damaged {
+            log::warn!("Discarding damaged entries in the connection history");
+            if let Err(error) = history.rewrite().await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to rewrite connection history")
+                );
+            }
+        }
+
+        history
+    }
+
+    /// Add an entry to the history and persist it.
+    pub async fn record(&mut self, entry: ConnectionHistoryEntry) -> Result<(), Error> {
+        let mut line = serde_json::to_string(&entry).map_err(Error::Serialize)?;
+        line.push('\n');
+        push_bounded(&mut self.entries, entry);
+
+        if self.lines_on_disk >= 2 * MAX_ENTRIES {
+            return self.rewrite().await;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(Error::Write)?;
+        self.lines_on_disk += 1;
+        Ok(())
+    }
+
+    /// Returns the entries that match `filter`, oldest first.
+    pub fn get(&self, filter: &ConnectionHistoryFilter) -> Vec<ConnectionHistoryEntry> {
+        filter.apply(self.entries.iter())
+    }
+
+    async fn rewrite(&mut self) -> Result<(), Error> {
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&serde_json::to_string(entry).map_err(Error::Serialize)?);
+            contents.push('\n');
+        }
+
+        let temp_path = self.path.with_extension("jsonl.tmp");
+        fs::write(&temp_path, contents)
+            .await
+            .map_err(Error::Write)?;
+        fs::rename(&temp_path, &self.path)
+            .await
+            .map_err(Error::Write)?;
+        self.lines_on_disk = self.entries.len();
+        Ok(())
+    }
+}
+
+/// Parse the journal. Returns the retained entries, the number of lines, and whether any line
+/// could not be parsed, e.g. because the daemon was stopped while writing it.
+fn parse(contents: &str) -> (VecDeque<ConnectionHistoryEntry>, usize, bool) {
+    let mut entries = VecDeque::new();
+    let mut lines = 0;
+    let mut damaged = !contents.is_empty() && !contents.ends_with('\n');
+    for line in contents.lines() {
+        lines += 1;
+        match serde_json::from_str(line) {
+            Ok(entry) => push_bounded(&mut entries, entry),
+            Err(_) => damaged = true,
+        }
+    }
+    (entries, lines, damaged)
+}
+
+fn push_bounded(entries: &mut VecDeque<ConnectionHistoryEntry>, entry: ConnectionHistoryEntry) {
+    if entries.len() >= MAX_ENTRIES {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::states::TunnelState;
+    use talpid_types::tunnel::ActionAfterDisconnect;
+
+    fn entry(retry_attempt: u32) -> String {
+        let entry = ConnectionHistoryEntry {
+            time: chrono::Utc::now(),
+            retry_attempt: Some(retry_attempt),
+            state: TunnelState::Disconnecting(ActionAfterDisconnect::Reconnect),
+        };
+        serde_json::to_string(&entry).unwrap() + "\n"
+    }
+
+    #[test]
+    fn test_parse_bounded() {
+        let contents: String = (0..MAX_ENTRIES as u32 + 5).map(entry).collect();
+        let (entries, lines, damaged) = parse(&contents);
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0].retry_attempt, Some(5));
+        assert_eq!(lines, MAX_ENTRIES + 5);
+        assert!(!damaged);
+    }
+
+    #[test]
+    fn test_parse_damaged() {
+        let truncated = entry(2);
+        let contents = format!("{}{{\"time\":\n{}{}", entry(0), entry(1), &truncated[..10]);
+        let (entries, lines, damaged) = parse(&contents);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(lines, 4);
+        assert!(damaged);
+    }
+}
@@ -122,11 +122,16 @@ impl Daemon {
             match endpoint.tunnel_type {
                 TunnelType::Wireguard => {
                     if relay_settings.wireguard_constraints.multihop() {
-                        if let Constraint::Only(LocationConstraint::CustomList { list_id }) =
-                            &relay_settings.wireguard_constraints.entry_location
-                        {
-                            need_to_reconnect |=
-                                custom_list_id.map(|id| &id == list_id).unwrap_or(true);
+                        let hop_locations =
+                            std::iter::once(&relay_settings.wireguard_constraints.entry_location)
+                                .chain(&relay_settings.wireguard_constraints.middle_locations);
+                        for location in hop_locations {
+                            if let Constraint::Only(LocationConstraint::CustomList { list_id }) =
+                                location
+                            {
+                                need_to_reconnect |=
+                                    custom_list_id.map(|id| &id == list_id).unwrap_or(true);
+                            }
                         }
                     }
                 }
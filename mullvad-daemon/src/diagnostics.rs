@@ -0,0 +1,56 @@
+//! Collects the parts of [`mullvad_types::diagnostics::Diagnostics`] that are not kept by the
+//! daemon itself. Failures are described in place of the missing item, since a partial problem
+//! report is more useful than none.
+
+#[cfg(target_os = "linux")]
+use std::{io, process::Command};
+#[cfg(target_os = "linux")]
+use talpid_routing::RouteManagerHandle;
+#[cfg(target_os = "linux")]
+use talpid_types::ErrorExt;
+
+/// Return the active nftables ruleset.
+#[cfg(target_os = "linux")]
+pub async fn firewall() -> Option<String> {
+    Some(nft_ruleset().await.unwrap_or_else(|error| {
+        error.display_chain_with_msg("Failed to list the nftables ruleset")
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn firewall() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+async fn nft_ruleset() -> io::Result<String> {
+    let output =
+        tokio::task::spawn_blocking(|| Command::new("nft").args(["list", "ruleset"]).output())
+            .await
+            .map_err(io::Error::other)??;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "nft failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Return the routes in all routing tables, one per line.
+#[cfg(target_os = "linux")]
+pub async fn routes(route_manager: &RouteManagerHandle) -> Option<String> {
+    Some(match route_manager.get_routes().await {
+        Ok(routes) => routes.iter().map(|route| format!("{route}\n")).collect(),
+        Err(error) => error.display_chain_with_msg("Failed to list routes"),
+    })
+}
+
+/// Routes are not collected on macOS and Windows. The route managers there only look up the
+/// default routes and the routes that they add themselves, so they cannot list the routing tables
+/// yet.
+#[cfg(not(target_os = "linux"))]
+pub async fn routes() -> Option<String> {
+    None
+}
@@ -1,4 +1,4 @@
-use mullvad_types::settings::{DnsOptions, DnsState};
+use mullvad_types::settings::{DefaultDnsOptions, DnsOptions, DnsState};
 use std::net::{IpAddr, Ipv4Addr};
 use talpid_core::{dns::DnsConfig, firewall::is_local_address};
 
@@ -15,41 +15,24 @@ const DNS_SOCIAL_MEDIA_BLOCKING_IP_BIT: u8 = 1 << 5; // 0b00100000
 
 /// Return the DNS resolvers to use
 pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
-    match options.state {
-        DnsState::Default => {
-            // Check if we should use a custom blocking DNS resolver.
-            // And if so, compute the IP.
-            let mut last_byte: u8 = 0;
-
-            if options.default_options.block_ads {
-                last_byte |= DNS_AD_BLOCKING_IP_BIT;
-            }
-            if options.default_options.block_trackers {
-                last_byte |= DNS_TRACKER_BLOCKING_IP_BIT;
-            }
-            if options.default_options.block_malware {
-                last_byte |= DNS_MALWARE_BLOCKING_IP_BIT;
-            }
-            if options.default_options.block_adult_content {
-                last_byte |= DNS_ADULT_BLOCKING_IP_BIT;
-            }
-            if options.default_options.block_gambling {
-                last_byte |= DNS_GAMBLING_BLOCKING_IP_BIT;
-            }
-            if options.default_options.block_social_media {
-                last_byte |= DNS_SOCIAL_MEDIA_BLOCKING_IP_BIT;
-            }
+    config_from_state(options).with_split_rules(options.split_rules.clone())
+}
 
-            if last_byte != 0 {
-                let mut dns_ip = DNS_BLOCKING_IP_BASE.octets();
-                dns_ip[dns_ip.len() - 1] |= last_byte;
-                DnsConfig::from_addresses(&[IpAddr::V4(Ipv4Addr::from(dns_ip))], &[])
-            } else {
-                DnsConfig::default()
-            }
-        }
+/// Return the DNS resolvers to use for domains that aren't covered by a split DNS rule
+fn config_from_state(options: &DnsOptions) -> DnsConfig {
+    match options.state {
+        DnsState::Default => match blocking_resolver(&options.default_options) {
+            Some(address) => DnsConfig::from_addresses(&[address], &[]),
+            None => DnsConfig::default(),
+        },
         DnsState::Custom if options.custom_options.addresses.is_empty() => DnsConfig::default(),
         DnsState::Custom => {
+            // Encrypted upstreams are reached through the tunnel, even if they are private
+            #[cfg(not(target_os = "android"))]
+            if let Some(encryption) = &options.custom_options.encryption {
+                return DnsConfig::encrypted(&options.custom_options.addresses, encryption.clone());
+            }
+
             let (non_tunnel_config, tunnel_config): (Vec<_>, Vec<_>) = options
                 .custom_options
                 .addresses
@@ -61,10 +44,77 @@ pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
     }
 }
 
+/// Return the resolvers to put in an exported config, where `gateways` are the default resolvers.
+/// Encryption and split rules cannot be expressed there and are left out.
+pub fn plain_resolvers(options: &DnsOptions, gateways: &[IpAddr]) -> Vec<IpAddr> {
+    match options.state {
+        DnsState::Default => match blocking_resolver(&options.default_options) {
+            Some(address) => vec![address],
+            None => gateways.to_vec(),
+        },
+        DnsState::Custom if options.custom_options.addresses.is_empty() => gateways.to_vec(),
+        DnsState::Custom => options.custom_options.addresses.clone(),
+    }
+}
+
+/// Return the resolvers that the system resolver is meant to query outside the tunnel. These are
+/// the private addresses among the plaintext custom resolvers and the split DNS resolvers.
+pub fn non_tunnel_resolvers(options: &DnsOptions) -> Vec<IpAddr> {
+    let custom_resolvers = match options.state {
+        DnsState::Custom if options.custom_options.encryption.is_none() => {
+            options.custom_options.addresses.as_slice()
+        }
+        _ => &[],
+    };
+    let split_resolvers = options.split_rules.iter().flat_map(|rule| rule.resolvers());
+
+    let mut resolvers = vec![];
+    for &address in custom_resolvers.iter().chain(split_resolvers) {
+        if is_local_address(&address) && !resolvers.contains(&address) {
+            resolvers.push(address);
+        }
+    }
+    resolvers
+}
+
+/// Return the resolver that blocks the enabled content categories, if any are enabled
+fn blocking_resolver(options: &DefaultDnsOptions) -> Option<IpAddr> {
+    let mut last_byte: u8 = 0;
+
+    if options.block_ads {
+        last_byte |= DNS_AD_BLOCKING_IP_BIT;
+    }
+    if options.block_trackers {
+        last_byte |= DNS_TRACKER_BLOCKING_IP_BIT;
+    }
+    if options.block_malware {
+        last_byte |= DNS_MALWARE_BLOCKING_IP_BIT;
+    }
+    if options.block_adult_content {
+        last_byte |= DNS_ADULT_BLOCKING_IP_BIT;
+    }
+    if options.block_gambling {
+        last_byte |= DNS_GAMBLING_BLOCKING_IP_BIT;
+    }
+    if options.block_social_media {
+        last_byte |= DNS_SOCIAL_MEDIA_BLOCKING_IP_BIT;
+    }
+
+    if last_byte == 0 {
+        return None;
+    }
+    let mut dns_ip = DNS_BLOCKING_IP_BASE.octets();
+    dns_ip[dns_ip.len() - 1] |= last_byte;
+    Some(IpAddr::V4(Ipv4Addr::from(dns_ip)))
+}
+
 #[cfg(test)]
 mod test {
-    use crate::dns::addresses_from_options;
-    use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+    use crate::dns::{addresses_from_options, non_tunnel_resolvers, plain_resolvers};
+    use mullvad_types::settings::{
+        CustomDnsOptions, DefaultDnsOptions, DnsEncryption, DnsOptions, DnsState,
+        EncryptedDnsProtocol, SplitDnsRule,
+    };
     use talpid_core::dns::DnsConfig;
 
     #[test]
@@ -73,6 +123,7 @@ mod test {
             state: DnsState::Default,
             custom_options: CustomDnsOptions::default(),
             default_options: DefaultDnsOptions::default(),
+            split_rules: vec![],
         };
 
         assert_eq!(addresses_from_options(&public_cfg), DnsConfig::default());
@@ -87,6 +138,7 @@ mod test {
                 block_ads: true,
                 ..DefaultDnsOptions::default()
             },
+            split_rules: vec![],
         };
 
         assert_eq!(
@@ -95,6 +147,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_plain_resolvers() {
+        let gateway = "10.64.0.1".parse().unwrap();
+        let mut options = DnsOptions {
+            state: DnsState::Default,
+            custom_options: CustomDnsOptions::default(),
+            default_options: DefaultDnsOptions::default(),
+            split_rules: vec![],
+        };
+        assert_eq!(plain_resolvers(&options, &[gateway]), vec![gateway]);
+
+        options.default_options.block_malware = true;
+        assert_eq!(
+            plain_resolvers(&options, &[gateway]),
+            vec!["100.64.0.4".parse::<std::net::IpAddr>().unwrap()]
+        );
+    }
+
     // Public IPs should be tunneled, but most private IPs should not be
     #[test]
     fn test_custom_dns() {
@@ -104,8 +174,10 @@ mod test {
             state: DnsState::Custom,
             custom_options: CustomDnsOptions {
                 addresses: vec![public_ip, private_ip],
+                encryption: None,
             },
             default_options: DefaultDnsOptions::default(),
+            split_rules: vec![],
         };
 
         assert_eq!(
@@ -113,4 +185,69 @@ mod test {
             DnsConfig::from_addresses(&[public_ip], &[private_ip],)
         );
     }
+
+    #[test]
+    #[cfg(not(target_os = "android"))]
+    fn test_encrypted_custom_dns() {
+        let private_ip = "172.16.10.1".parse().unwrap();
+        let encryption = DnsEncryption {
+            protocol: EncryptedDnsProtocol::from_doh_url("https://dns.example.com/dns-query")
+                .unwrap(),
+            spki_pin: None,
+        };
+        let cfg = DnsOptions {
+            state: DnsState::Custom,
+            custom_options: CustomDnsOptions {
+                addresses: vec![private_ip],
+                encryption: Some(encryption.clone()),
+            },
+            default_options: DefaultDnsOptions::default(),
+            split_rules: vec![],
+        };
+
+        assert_eq!(
+            addresses_from_options(&cfg),
+            DnsConfig::encrypted(&[private_ip], encryption)
+        );
+    }
+
+    // Split DNS rules apply on top of both default and custom DNS
+    #[test]
+    fn test_split_dns_rules() {
+        let rule =
+            SplitDnsRule::new("*.corp.example", vec!["192.168.1.53".parse().unwrap()]).unwrap();
+        let cfg = DnsOptions {
+            state: DnsState::Default,
+            custom_options: CustomDnsOptions::default(),
+            default_options: DefaultDnsOptions::default(),
+            split_rules: vec![rule.clone()],
+        };
+
+        assert_eq!(
+            addresses_from_options(&cfg),
+            DnsConfig::default().with_split_rules(vec![rule])
+        );
+    }
+
+    // Only private plaintext resolvers are expected to be queried outside the tunnel
+    #[test]
+    fn test_non_tunnel_resolvers() {
+        let public_ip = "1.2.3.4".parse().unwrap();
+        let private_ip = "172.16.10.1".parse().unwrap();
+        let split_ip = "192.168.1.53".parse().unwrap();
+        let rule = SplitDnsRule::new("corp.example", vec![split_ip, public_ip]).unwrap();
+        let mut cfg = DnsOptions {
+            state: DnsState::Custom,
+            custom_options: CustomDnsOptions {
+                addresses: vec![public_ip, private_ip],
+                encryption: None,
+            },
+            default_options: DefaultDnsOptions::default(),
+            split_rules: vec![rule],
+        };
+        assert_eq!(non_tunnel_resolvers(&cfg), vec![private_ip, split_ip]);
+
+        cfg.state = DnsState::Default;
+        assert_eq!(non_tunnel_resolvers(&cfg), vec![split_ip]);
+    }
 }
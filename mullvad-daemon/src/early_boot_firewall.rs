@@ -15,24 +15,27 @@ pub enum Error {
 
 pub async fn initialize_firewall() -> Result<(), Error> {
     let mut firewall = Firewall::new(mullvad_types::TUNNEL_FWMARK)?;
-    let allow_lan = get_allow_lan().await.unwrap_or_else(|err| {
-        log::info!(
-            "Not allowing LAN traffic due to failing to read settings: {}",
-            err
-        );
-        false
-    });
+    let (allow_lan, exceptions) = match get_settings().await {
+        Ok(settings) => (settings.allow_lan, settings.firewall_exceptions.clone()),
+        Err(err) => {
+            log::info!(
+                "Not allowing LAN traffic or firewall exceptions due to failing to read settings: {}",
+                err
+            );
+            (false, vec![])
+        }
+    };
     let policy = FirewallPolicy::Blocked {
         allow_lan,
         allowed_endpoint: None,
+        exceptions,
     };
     log::info!("Applying firewall policy {policy}");
     firewall.apply_policy(policy)?;
     Ok(())
 }
 
-async fn get_allow_lan() -> Result<bool, Error> {
+async fn get_settings() -> Result<SettingsPersister, Error> {
     let path = mullvad_paths::settings_dir()?;
-    let settings = SettingsPersister::load(&path).await;
-    Ok(settings.allow_lan)
+    Ok(SettingsPersister::load(&path).await)
 }
@@ -1,13 +1,29 @@
-use futures::{select, FutureExt};
-pub use mullvad_leak_checker::LeakInfo;
-use std::time::Duration;
+use futures::{select, Future, FutureExt};
+use mullvad_types::leak_check::{LeakStatus, LeakTestResult};
+use std::{net::IpAddr, time::Duration};
 use talpid_routing::RouteManagerHandle;
-use talpid_types::{net::Endpoint, tunnel::TunnelStateTransition};
-use tokio::sync::mpsc;
+use talpid_types::{
+    net::{Endpoint, TunnelEndpoint},
+    tunnel::TunnelStateTransition,
+};
+use tokio::sync::{mpsc, oneshot};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("A leak check is already running")]
+    AlreadyRunning,
+
+    #[error("The leak check was interrupted by a tunnel state change")]
+    Interrupted,
+}
 
 /// An actor that tries to leak traffic outside the tunnel while we are connected.
 pub struct LeakChecker {
     task_event_tx: mpsc::UnboundedSender<TaskEvent>,
+    route_manager: RouteManagerHandle,
+    non_tunnel_resolvers: Vec<IpAddr>,
+    /// Cancels the running on-demand leak check when dropped.
+    on_demand_cancel_tx: Option<oneshot::Sender<()>>,
 }
 
 /// [LeakChecker] internal task state.
@@ -15,11 +31,13 @@ struct Task {
     events_rx: mpsc::UnboundedReceiver<TaskEvent>,
     route_manager: RouteManagerHandle,
     callbacks: Vec<Box<dyn LeakCheckerCallback>>,
+    non_tunnel_resolvers: Vec<IpAddr>,
 }
 
 enum TaskEvent {
     NewTunnelState(TunnelStateTransition),
     AddCallback(Box<dyn LeakCheckerCallback>),
+    SetNonTunnelResolvers(Vec<IpAddr>),
 }
 
 #[derive(PartialEq, Eq)]
@@ -32,7 +50,7 @@ pub enum CallbackResult {
 }
 
 pub trait LeakCheckerCallback: Send + 'static {
-    fn on_leak(&mut self, info: LeakInfo) -> CallbackResult;
+    fn on_leak(&mut self, leak: LeakTestResult) -> CallbackResult;
 }
 
 impl LeakChecker {
@@ -41,17 +59,25 @@ impl LeakChecker {
 
         let task = Task {
             events_rx,
-            route_manager,
+            route_manager: route_manager.clone(),
             callbacks: vec![],
+            non_tunnel_resolvers: vec![],
         };
 
         tokio::task::spawn(task.run());
 
-        LeakChecker { task_event_tx }
+        LeakChecker {
+            task_event_tx,
+            route_manager,
+            non_tunnel_resolvers: vec![],
+            on_demand_cancel_tx: None,
+        }
     }
 
     /// Call when we transition to a new tunnel state.
     pub fn on_tunnel_state_transition(&mut self, tunnel_state: TunnelStateTransition) {
+        // The results of a running on-demand check would no longer be valid
+        self.on_demand_cancel_tx = None;
         self.send(TaskEvent::NewTunnelState(tunnel_state))
     }
 
@@ -60,6 +86,61 @@ impl LeakChecker {
         self.send(TaskEvent::AddCallback(Box::new(callback)))
     }
 
+    /// Call when the DNS resolvers that are meant to be reached outside the tunnel change, such
+    /// as custom resolvers on the local network. Queries sent to them are not counted as leaks.
+    pub fn set_non_tunnel_resolvers(&mut self, resolvers: Vec<IpAddr>) {
+        self.non_tunnel_resolvers = resolvers.clone();
+        self.send(TaskEvent::SetNonTunnelResolvers(resolvers))
+    }
+
+    /// Run every leak test against the connected `tunnel`. Unlike the automatic check, this
+    /// returns the result of every test, and does not call the leak callbacks. It also sends DNS
+    /// queries directly to public resolvers, and asks am.i.mullvad.net whether traffic goes
+    /// through the tunnel, if that check is enabled.
+    ///
+    /// Only one on-demand check can run at a time. The check fails with [Error::Interrupted] if
+    /// the tunnel state changes before it is done.
+    pub fn run_leak_check(
+        &mut self,
+        tunnel: &TunnelEndpoint,
+    ) -> Result<impl Future<Output = Result<Vec<LeakTestResult>, Error>> + Send + 'static, Error>
+    {
+        // The receiver is dropped once the previous check is done
+        if self
+            .on_demand_cancel_tx
+            .as_ref()
+            .is_some_and(|cancel_tx| !cancel_tx.is_closed())
+        {
+            return Err(Error::AlreadyRunning);
+        }
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        self.on_demand_cancel_tx = Some(cancel_tx);
+
+        let route_manager = self.route_manager.clone();
+        let destination = tunnel.endpoint;
+        let tunnel_interface = tunnel.tunnel_interface.clone();
+        let non_tunnel_resolvers = self.non_tunnel_resolvers.clone();
+        Ok(async move {
+            let leak_test = async {
+                let mut results = run_leak_tests(
+                    &route_manager,
+                    destination,
+                    tunnel_interface,
+                    &non_tunnel_resolvers,
+                    true,
+                )
+                .await;
+                results.extend(run_am_i_mullvad_test().await);
+                results
+            };
+
+            select! {
+                results = leak_test.fuse() => Ok(results),
+                _ = cancel_rx.fuse() => Err(Error::Interrupted),
+            }
+        })
+    }
+
     /// Send a [TaskEvent] to the running [Task];
     fn send(&mut self, event: TaskEvent) {
         if self.task_event_tx.send(event).is_err() {
@@ -78,6 +159,9 @@ impl Task {
             match event {
                 TaskEvent::NewTunnelState(s) => self.on_new_tunnel_state(s).await,
                 TaskEvent::AddCallback(c) => self.on_add_callback(c),
+                TaskEvent::SetNonTunnelResolvers(resolvers) => {
+                    self.non_tunnel_resolvers = resolvers
+                }
             }
         }
     }
@@ -93,12 +177,22 @@ impl Task {
             };
 
             let ping_destination = tunnel.endpoint;
+            let tunnel_interface = tunnel.tunnel_interface.clone();
             let route_manager = self.route_manager.clone();
+            let non_tunnel_resolvers = self.non_tunnel_resolvers.clone();
             let leak_test = async {
                 // Give the connection a little time to settle before starting the test.
                 tokio::time::sleep(Duration::from_millis(5000)).await;
 
-                check_for_leaks(&route_manager, ping_destination).await
+                // Third parties are only contacted in on-demand checks
+                run_leak_tests(
+                    &route_manager,
+                    ping_destination,
+                    tunnel_interface,
+                    &non_tunnel_resolvers,
+                    false,
+                )
+                .await
             };
 
             // Make sure the tunnel state doesn't change while we're doing the leak test.
@@ -111,6 +205,10 @@ impl Task {
                             self.on_add_callback(c);
                             continue 'listen_for_events;
                         }
+                        TaskEvent::SetNonTunnelResolvers(resolvers) => {
+                            self.non_tunnel_resolvers = resolvers;
+                            continue 'listen_for_events;
+                        }
                     };
 
                     if let TunnelStateTransition::Connected(..) = new_state {
@@ -123,52 +221,161 @@ impl Task {
                 }
             };
 
-            let leak_result = select! {
+            let results = select! {
                 // If tunnel state changes, restart the test.
                 _ = another_tunnel_state.fuse() => continue 'leak_test,
 
-                leak_result = leak_test.fuse() => leak_result,
-            };
-
-            let leak_info = match leak_result {
-                Ok(Some(leak_info)) => leak_info,
-                Ok(None) => {
-                    log::debug!("No leak detected");
-                    break 'leak_test;
-                }
-                Err(e) => {
-                    log::debug!("Leak check errored: {e:#?}");
-                    break 'leak_test;
-                }
+                results = leak_test.fuse() => results,
             };
 
-            log::debug!("Leak detected: {leak_info:?}");
+            let leaks: Vec<_> = results
+                .into_iter()
+                .filter(|result| matches!(result.status, LeakStatus::LeakDetected(..)))
+                .collect();
+            if leaks.is_empty() {
+                log::debug!("No leak detected");
+            }
 
-            self.callbacks
-                .retain_mut(|callback| callback.on_leak(leak_info.clone()) == CallbackResult::Ok);
+            for leak in leaks {
+                log::debug!("Leak detected: {leak:?}");
+                self.callbacks
+                    .retain_mut(|callback| callback.on_leak(leak.clone()) == CallbackResult::Ok);
+            }
 
             break 'leak_test;
         }
     }
 }
 
+/// Run every leak test against the tunnel to `destination`.
 #[cfg(target_os = "android")]
 #[allow(clippy::unused_async)]
-async fn check_for_leaks(
+async fn run_leak_tests(
     _route_manager: &RouteManagerHandle,
     _destination: Endpoint,
-) -> anyhow::Result<Option<LeakInfo>> {
+    _tunnel_interface: Option<String>,
+    _non_tunnel_resolvers: &[IpAddr],
+    _query_public_resolvers: bool,
+) -> Vec<LeakTestResult> {
     // TODO: We currently don't have a way to get the non-tunnel interface on Android.
-    Ok(None)
+    vec![]
 }
 
+/// Run every leak test against the tunnel to `destination`. A test that fails to run does not
+/// stop the others. DNS queries sent to `non_tunnel_resolvers` are not counted as leaks.
+///
+/// Queries are only sent directly to public resolvers if `query_public_resolvers` is set.
+/// Otherwise, the DNS test only watches for queries that the system resolver sends outside the
+/// tunnel, which is not done on every platform.
 #[cfg(not(target_os = "android"))]
-async fn check_for_leaks(
+async fn run_leak_tests(
     route_manager: &RouteManagerHandle,
     destination: Endpoint,
-) -> anyhow::Result<Option<LeakInfo>> {
+    tunnel_interface: Option<String>,
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    non_tunnel_resolvers: &[IpAddr],
+    query_public_resolvers: bool,
+) -> Vec<LeakTestResult> {
+    use anyhow::anyhow;
+    use mullvad_leak_checker::{dns::DnsLeakOpt, ipv6::Ipv6LeakOpt, traceroute::TracerouteOpt};
+    use mullvad_types::leak_check::LeakTest;
+
+    let mut results = vec![];
+    let interface = get_relay_interface(route_manager, destination).await;
+
+    let status = match &interface {
+        Ok(interface) => {
+            log::debug!("Attempting to leak traffic on interface {interface:?} to {destination}");
+            mullvad_leak_checker::traceroute::try_run_leak_test(&TracerouteOpt {
+                interface: interface.clone(),
+                destination: destination.address.ip(),
+
+                #[cfg(unix)]
+                port: None,
+                #[cfg(unix)]
+                exclude_port: None,
+                #[cfg(unix)]
+                icmp: true,
+            })
+            .await
+        }
+        Err(error) => Err(anyhow!("{error:#}")),
+    };
+    results.push(leak_test_result(LeakTest::Traceroute, status));
+
+    let resolvers = if query_public_resolvers {
+        mullvad_leak_checker::dns::DEFAULT_RESOLVERS.to_vec()
+    } else {
+        vec![]
+    };
+    if !resolvers.is_empty() || cfg!(target_os = "linux") {
+        let status = match &interface {
+            Ok(interface) => {
+                log::debug!("Attempting to leak DNS queries on interface {interface:?}");
+                mullvad_leak_checker::dns::try_run_leak_test(&DnsLeakOpt {
+                    interface: interface.clone(),
+                    resolvers,
+                    port: mullvad_leak_checker::dns::DNS_PORT,
+
+                    #[cfg(target_os = "linux")]
+                    system_resolver: true,
+                    #[cfg(target_os = "linux")]
+                    allowed_resolvers: non_tunnel_resolvers.to_vec(),
+                })
+                .await
+            }
+            Err(error) => Err(anyhow!("{error:#}")),
+        };
+        results.push(leak_test_result(LeakTest::Dns, status));
+    }
+
+    log::debug!("Attempting to leak IPv6 traffic outside the tunnel");
+    let status = match get_ipv6_interfaces() {
+        Ok(Some(interfaces)) => {
+            mullvad_leak_checker::ipv6::try_run_leak_test(&Ipv6LeakOpt {
+                interfaces,
+                exclude_interfaces: tunnel_interface.into_iter().collect(),
+                destinations: vec![],
+                port: mullvad_leak_checker::dns::DNS_PORT,
+            })
+            .await
+        }
+        Ok(None) => Ok(mullvad_leak_checker::LeakStatus::NoLeak),
+        Err(error) => Err(error),
+    };
+    results.push(leak_test_result(LeakTest::Ipv6, status));
+
+    results
+}
+
+/// The am.i.mullvad.net check is not available without the `am-i-mullvad` feature.
+#[cfg(not(all(feature = "am-i-mullvad", not(target_os = "android"))))]
+#[allow(clippy::unused_async)]
+async fn run_am_i_mullvad_test() -> Option<LeakTestResult> {
+    None
+}
+
+/// Ask am.i.mullvad.net whether traffic goes through the tunnel. This contacts a third party, so
+/// it is only done in on-demand checks.
+#[cfg(all(feature = "am-i-mullvad", not(target_os = "android")))]
+async fn run_am_i_mullvad_test() -> Option<LeakTestResult> {
+    use mullvad_leak_checker::am_i_mullvad::AmIMullvadOpt;
+    use mullvad_types::leak_check::LeakTest;
+
+    log::debug!("Asking am.i.mullvad.net whether the traffic goes through the tunnel");
+    let status =
+        mullvad_leak_checker::am_i_mullvad::try_run_leak_test(&AmIMullvadOpt { interface: None })
+            .await;
+    Some(leak_test_result(LeakTest::AmIMullvad, status))
+}
+
+/// Get the non-tunnel interface that traffic to the relay at `destination` leaves through.
+#[cfg(not(target_os = "android"))]
+async fn get_relay_interface(
+    route_manager: &RouteManagerHandle,
+    destination: Endpoint,
+) -> anyhow::Result<mullvad_leak_checker::Interface> {
     use anyhow::{anyhow, Context};
-    use mullvad_leak_checker::{traceroute::TracerouteOpt, LeakStatus};
 
     #[cfg(target_os = "linux")]
     let interface = {
@@ -225,33 +432,64 @@ async fn check_for_leaks(
         mullvad_leak_checker::Interface::Luid(route.iface)
     };
 
-    log::debug!("Attempting to leak traffic on interface {interface:?} to {destination}");
-
-    mullvad_leak_checker::traceroute::try_run_leak_test(&TracerouteOpt {
-        interface,
-        destination: destination.address.ip(),
-
-        #[cfg(unix)]
-        port: None,
-        #[cfg(unix)]
-        exclude_port: None,
-        #[cfg(unix)]
-        icmp: true,
-    })
-    .await
-    .map_err(|e| anyhow!("{e:#}"))
-    .map(|status| match status {
-        LeakStatus::NoLeak => None,
-        LeakStatus::LeakDetected(info) => Some(info),
-    })
+    Ok(interface)
+}
+
+/// Get the interfaces to look for IPv6 leaks on. An empty list lets the leak checker probe every
+/// interface with a global IPv6 address, since IPv6 traffic may leave through another interface
+/// than the relay is reached on. Returns `None` if there is nothing to probe.
+#[cfg(not(target_os = "android"))]
+fn get_ipv6_interfaces() -> anyhow::Result<Option<Vec<mullvad_leak_checker::Interface>>> {
+    #[cfg(not(target_os = "windows"))]
+    return Ok(Some(vec![]));
+
+    // Interfaces can't be discovered on Windows, so only the one with the default route is probed
+    #[cfg(target_os = "windows")]
+    {
+        use anyhow::Context;
+        use talpid_windows::net::AddressFamily;
+
+        let Some(route) = talpid_routing::get_best_default_route(AddressFamily::Ipv6)
+            .context("Failed to get best default IPv6 route")?
+        else {
+            log::debug!("No IPv6 default route, skipping IPv6 leak test");
+            return Ok(None);
+        };
+        Ok(Some(vec![mullvad_leak_checker::Interface::Luid(
+            route.iface,
+        )]))
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn leak_test_result(
+    test: mullvad_types::leak_check::LeakTest,
+    status: anyhow::Result<mullvad_leak_checker::LeakStatus>,
+) -> LeakTestResult {
+    use mullvad_types::leak_check::LeakInfo;
+
+    let status = match status {
+        Ok(mullvad_leak_checker::LeakStatus::NoLeak) => LeakStatus::NoLeak,
+        Ok(mullvad_leak_checker::LeakStatus::LeakDetected(info)) => {
+            LeakStatus::LeakDetected(LeakInfo {
+                interface: info.interface().map(ToString::to_string),
+                addresses: info.addresses(),
+            })
+        }
+        Err(error) => {
+            log::debug!("The {test} leak test errored: {error:#}");
+            LeakStatus::Error(format!("{error:#}"))
+        }
+    };
+    LeakTestResult { test, status }
 }
 
 impl<T> LeakCheckerCallback for T
 where
-    T: FnMut(LeakInfo) -> bool + Send + 'static,
+    T: FnMut(LeakTestResult) -> bool + Send + 'static,
 {
-    fn on_leak(&mut self, info: LeakInfo) -> CallbackResult {
-        if self(info) {
+    fn on_leak(&mut self, leak: LeakTestResult) -> CallbackResult {
+        if self(leak) {
             CallbackResult::Ok
         } else {
             CallbackResult::Drop
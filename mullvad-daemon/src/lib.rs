@@ -2,14 +2,17 @@
 #![allow(rustdoc::private_intra_doc_links)]
 
 mod access_method;
+mod access_method_stats;
 pub mod account_history;
 mod android_dns;
 mod api;
 mod api_address_updater;
 #[cfg(not(target_os = "android"))]
 mod cleanup;
+mod connection_history;
 mod custom_list;
 pub mod device;
+mod diagnostics;
 mod dns;
 pub mod exception_logging;
 mod geoip;
@@ -18,13 +21,18 @@ pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 pub mod management_interface;
+mod metrics;
 mod migrations;
+#[cfg(not(target_os = "android"))]
+mod relay_latency;
 mod relay_list;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
 pub mod settings;
+mod settings_profile;
 pub mod shutdown;
+mod system_proxy;
 mod target_state;
 mod tunnel;
 pub mod version;
@@ -32,6 +40,7 @@ mod version_check;
 
 use crate::target_state::PersistentTargetState;
 use api::AccessMethodEvent;
+use connection_history::ConnectionHistory;
 use device::{AccountEvent, PrivateAccountAndDevice, PrivateDeviceEvent};
 use futures::{
     channel::{mpsc, oneshot},
@@ -39,41 +48,49 @@ use futures::{
     StreamExt,
 };
 use geoip::GeoIpHandler;
-use leak_checker::{LeakChecker, LeakInfo};
+use leak_checker::LeakChecker;
 use management_interface::ManagementInterfaceServer;
 use mullvad_api::ApiEndpoint;
 use mullvad_relay_selector::{RelaySelector, SelectorConfig};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use mullvad_types::settings::SplitApp;
+#[cfg(target_os = "linux")]
+use mullvad_types::settings::{SplitTunnelMode, SplitTunnelSettings};
 #[cfg(daita)]
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
-    access_method::{AccessMethod, AccessMethodSetting},
+    access_method::{AccessMethod, AccessMethodSetting, AccessMethodStats, DetectedSystemProxy},
     account::{AccountData, AccountNumber, VoucherSubmission},
     auth_failed::AuthFailed,
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
     custom_list::CustomList,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
+    diagnostics::{Diagnostics, RelayListMetadata},
     features::{compute_feature_indicators, FeatureIndicator, FeatureIndicators},
+    leak_check::{LeakStatus, LeakTestResult},
     location::{GeoIpLocation, LocationEventData},
+    metrics::MetricsSettings,
+    profile::{ProfileList, SettingsProfile},
     relay_constraints::{
         BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
+        RetryAttempt,
     },
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{CustomDnsOptions, DnsOptions, DnsState, Settings},
     states::{Secured, TargetState, TargetStateStrict, TunnelState},
     version::{AppVersion, AppVersionInfo},
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    ConnectionConfig,
 };
 use relay_list::{RelayListUpdater, RelayListUpdaterHandle, RELAYS_FILENAME};
 use settings::SettingsPersister;
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-use std::collections::HashSet;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
+    collections::HashSet,
     marker::PhantomData,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::PathBuf,
     pin::Pin,
     sync::{Arc, Weak},
@@ -87,10 +104,12 @@ use talpid_core::{
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(target_os = "linux")]
+use talpid_types::net::firewall::FirewallException;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
-    net::{IpVersion, TunnelType},
+    net::{wireguard::PeerStats, IpVersion, NetworkInfo, TunnelType},
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -200,6 +219,37 @@ pub enum Error {
     #[error("No custom bridge has been specified")]
     NoCustomProxySaved,
 
+    #[error("Invalid retry order")]
+    InvalidRetryOrder(#[source] mullvad_relay_selector::Error),
+
+    #[cfg(target_os = "linux")]
+    #[error("Invalid firewall exception")]
+    InvalidFirewallException(#[source] talpid_types::net::firewall::FirewallExceptionError),
+
+    #[error("Invalid metrics endpoint")]
+    InvalidMetricsEndpoint(#[source] mullvad_types::metrics::Error),
+
+    #[error("Failed to start metrics endpoint")]
+    MetricsEndpoint(#[source] metrics::Error),
+
+    #[error("Invalid connectivity check options")]
+    InvalidConnectivityCheck(#[source] talpid_types::net::wireguard::ConnectivityCheckError),
+
+    #[error("Not connected to a WireGuard relay")]
+    NoWireguardTunnel,
+
+    #[error("Failed to export WireGuard config")]
+    ExportWireguardConfig(#[source] mullvad_types::wg_quick::Error),
+
+    #[error("Failed to import WireGuard config")]
+    ImportWireguardConfig(#[source] mullvad_types::wg_quick::Error),
+
+    #[error("Leak checks can only be run while connected")]
+    LeakCheckNotConnected,
+
+    #[error("Failed to run leak check")]
+    LeakCheck(#[source] leak_checker::Error),
+
     #[cfg(target_os = "macos")]
     #[error("Failed to set exclusion group")]
     GroupIdError(#[source] io::Error),
@@ -221,6 +271,17 @@ pub enum DaemonCommand {
     Reconnect(oneshot::Sender<bool>),
     /// Request the current state.
     GetState(oneshot::Sender<TunnelState>),
+    /// Request the traffic statistics of the current tunnel.
+    GetTunnelStats(oneshot::Sender<Option<Vec<PeerStats>>>),
+    /// Request recorded tunnel state transitions
+    GetConnectionHistory(
+        oneshot::Sender<Vec<ConnectionHistoryEntry>>,
+        ConnectionHistoryFilter,
+    ),
+    /// Run every leak test against the connected tunnel
+    RunLeakCheck(ResponseTx<Vec<LeakTestResult>, Error>),
+    /// Request daemon state to include in a problem report
+    GetDiagnostics(oneshot::Sender<Diagnostics>),
     CreateNewAccount(ResponseTx<String, Error>),
     /// Request the metadata for an account.
     GetAccountData(
@@ -286,19 +347,42 @@ pub enum DaemonCommand {
     SetRelayOverride(ResponseTx<(), settings::Error>, RelayOverride),
     /// Remove all relay override options
     ClearAllRelayOverrides(ResponseTx<(), settings::Error>),
+    /// Set the order in which to try different connection parameters, or restore the built-in
+    /// retry order
+    SetRetryOrder(ResponseTx<(), Error>, Option<Vec<RetryAttempt>>),
+    /// Configure the local metrics endpoint
+    SetMetricsSettings(ResponseTx<(), Error>, MetricsSettings),
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
     /// Set automatic key rotation interval for wireguard tunnels
     SetWireguardRotationInterval(ResponseTx<(), settings::Error>, Option<RotationInterval>),
-    /// Get the daemon settings
+    /// Set the timeouts and ping target used to detect broken wireguard tunnels
+    SetWireguardConnectivityCheck(
+        ResponseTx<(), Error>,
+        talpid_types::net::wireguard::ConnectivityCheckOptions,
+    ),
+    /// Get the daemon settings, without the overrides of the active settings profile. These are
+    /// the settings that clients should modify
     GetSettings(oneshot::Sender<Settings>),
+    /// Get the settings currently in effect, including the overrides of the active settings
+    /// profile
+    GetEffectiveSettings(oneshot::Sender<Settings>),
     /// Reset all daemon settings to the defaults
     ResetSettings(ResponseTx<(), settings::Error>),
     /// Generate new wireguard key
     RotateWireguardKey(ResponseTx<(), Error>),
     /// Return a public key of the currently set wireguard private key, if there is one
     GetWireguardKey(ResponseTx<Option<PublicKey>, Error>),
+    /// Export the config of the connected WireGuard tunnel in the wg-quick format
+    ExportWireguardConfig(ResponseTx<String, Error>),
+    /// Use the peer, MTU and DNS servers of a wg-quick config, with optional in-tunnel gateways
+    ImportWireguardConfig(
+        ResponseTx<(), Error>,
+        String,
+        Option<Ipv4Addr>,
+        Option<Ipv6Addr>,
+    ),
     /// Create custom list
     CreateCustomList(ResponseTx<mullvad_types::custom_list::Id, Error>, String),
     /// Delete custom list
@@ -307,6 +391,14 @@ pub enum DaemonCommand {
     UpdateCustomList(ResponseTx<(), Error>, CustomList),
     /// Remove all custom lists
     ClearCustomLists(ResponseTx<(), Error>),
+    /// Add a settings profile
+    CreateSettingsProfile(ResponseTx<(), settings::Error>, SettingsProfile),
+    /// Remove a settings profile
+    DeleteSettingsProfile(ResponseTx<(), settings::Error>, String),
+    /// Return all settings profiles and the name of the active one
+    GetSettingsProfiles(oneshot::Sender<ProfileList>),
+    /// Activate a settings profile, or restore the base settings
+    SetActiveSettingsProfile(ResponseTx<(), settings::Error>, Option<String>),
     /// Add API access methods
     AddApiAccessMethod(
         ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -324,6 +416,15 @@ pub enum DaemonCommand {
     ClearCustomApiAccessMethods(ResponseTx<(), Error>),
     /// Get the currently used API access method
     GetCurrentAccessMethod(ResponseTx<AccessMethodSetting, Error>),
+    /// Pin an API access method, or unpin the current one
+    SetPinnedApiAccessMethod(
+        ResponseTx<(), Error>,
+        Option<mullvad_types::access_method::Id>,
+    ),
+    /// Get statistics about how well each API access method has worked
+    GetApiAccessMethodStats(ResponseTx<Vec<AccessMethodStats>, Error>),
+    /// Look for a proxy configured on the system
+    GetSystemProxy(ResponseTx<DetectedSystemProxy, Error>),
     /// Test an API access method
     TestApiAccessMethodById(ResponseTx<bool, Error>, mullvad_types::access_method::Id),
     /// Test a custom API access method
@@ -352,17 +453,28 @@ pub enum DaemonCommand {
     /// Clear list of processes excluded from the tunnel
     #[cfg(target_os = "linux")]
     ClearSplitTunnelProcesses(ResponseTx<(), split_tunnel::Error>),
+    /// Get the mechanism used to identify processes excluded from the tunnel
+    #[cfg(target_os = "linux")]
+    GetSplitTunnelMechanism(oneshot::Sender<split_tunnel::ExclusionMechanism>),
+    /// Set whether split apps are excluded from the tunnel or are the only ones using it
+    #[cfg(target_os = "linux")]
+    SetSplitTunnelMode(ResponseTx<(), settings::Error>, SplitTunnelMode),
+    /// Allow traffic through the firewall in every tunnel state
+    #[cfg(target_os = "linux")]
+    AddFirewallException(ResponseTx<(), Error>, FirewallException),
+    /// Remove a user-defined firewall exception
+    #[cfg(target_os = "linux")]
+    RemoveFirewallException(ResponseTx<(), Error>, FirewallException),
+    /// Remove all user-defined firewall exceptions
+    #[cfg(target_os = "linux")]
+    ClearFirewallExceptions(ResponseTx<(), Error>),
     /// Exclude traffic of an application from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     AddSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Remove application from list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     RemoveSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Clear list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ClearSplitTunnelApps(ResponseTx<(), Error>),
     /// Enable or disable split tunneling
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     SetSplitTunnelState(ResponseTx<(), Error>, bool),
     /// Returns all processes currently being excluded from the tunnel
     #[cfg(windows)]
@@ -418,13 +530,13 @@ pub(crate) enum InternalDaemonEvent {
     /// A generic event for when any settings change.
     SettingsChanged,
     /// The split tunnel paths or state were updated.
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
     /// A network leak was detected.
-    LeakDetected(LeakInfo),
+    LeakDetected(LeakTestResult),
+    /// The offline monitor detected that the host joined a different network.
+    NetworkChanged(NetworkInfo),
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 pub(crate) enum ExcludedPathsUpdate {
     SetState(bool),
     SetPaths(HashSet<SplitApp>),
@@ -575,6 +687,9 @@ pub struct Daemon {
     target_state: PersistentTargetState,
     #[cfg(target_os = "linux")]
     exclude_pids: split_tunnel::PidManager,
+    /// Excludes the split tunnel apps, unless monitoring processes is unavailable
+    #[cfg(target_os = "linux")]
+    exclude_apps: Option<split_tunnel::AppMonitor>,
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
@@ -597,6 +712,12 @@ pub struct Daemon {
     volume_update_tx: mpsc::UnboundedSender<()>,
     location_handler: GeoIpHandler,
     leak_checker: LeakChecker,
+    #[cfg(target_os = "linux")]
+    route_manager: RouteManagerHandle,
+    #[cfg(not(target_os = "android"))]
+    relay_latency_prober: relay_latency::RelayLatencyProber,
+    connection_history: ConnectionHistory,
+    metrics: metrics::MetricsExporter,
 }
 pub struct DaemonConfig {
     pub log_dir: Option<PathBuf>,
@@ -660,7 +781,7 @@ impl Daemon {
 
         let settings_event_listener = management_interface.notifier().clone();
         let mut settings = SettingsPersister::load(&config.settings_dir).await;
-        settings.register_change_listener(move |settings| {
+        settings.register_base_change_listener(move |settings| {
             // Notify management interface server of changes to the settings
             settings_event_listener.notify_settings(settings.to_owned());
         });
@@ -684,7 +805,6 @@ impl Daemon {
             config.cache_dir.clone(),
             relay_selector.clone(),
             settings.api_access_methods.clone(),
-            #[cfg(feature = "api-override")]
             config.endpoint.clone(),
             internal_event_tx.to_specialized_sender(),
             api_runtime.address_cache().clone(),
@@ -796,7 +916,23 @@ impl Daemon {
         .await
         .map_err(Error::RouteManager)?;
 
+        // The exclusion cgroup must exist before the first firewall policy is applied, since the
+        // cgroup v2 firewall rule refers to it.
+        #[cfg(target_os = "linux")]
+        let exclude_pids = split_tunnel::PidManager::new().map_err(Error::InitSplitTunneling)?;
+        // Excluding apps by path is not essential, so the daemon can start without it
+        #[cfg(target_os = "linux")]
+        let exclude_apps = split_tunnel::AppMonitor::spawn(exclude_pids.clone())
+            .inspect_err(|error| {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Unable to exclude apps from the tunnel")
+                );
+            })
+            .ok();
+
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
+        let (network_info_tx, mut network_info_rx) = mpsc::unbounded();
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
         let tunnel_state_machine_handle = tunnel_state_machine::spawn(
@@ -813,12 +949,17 @@ impl Daemon {
                 reset_firewall: *target_state != TargetState::Secured,
                 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
                 exclude_paths,
+                #[cfg(target_os = "linux")]
+                split_tunnel_mode: settings.split_tunnel.effective_mode(),
+                #[cfg(target_os = "linux")]
+                firewall_exceptions: settings.firewall_exceptions.clone(),
             },
             parameters_generator.clone(),
             config.log_dir,
             config.resource_dir.clone(),
             internal_event_tx.to_specialized_sender(),
             offline_state_tx,
+            network_info_tx,
             route_manager.clone(),
             #[cfg(target_os = "windows")]
             volume_update_rx,
@@ -837,6 +978,18 @@ impl Daemon {
 
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
+        let network_event_tx = internal_event_tx.clone();
+        tokio::spawn(async move {
+            while let Some(network) = network_info_rx.next().await {
+                if network_event_tx
+                    .send(InternalDaemonEvent::NetworkChanged(network))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
         let relay_list_listener = management_interface.notifier().clone();
         let on_relay_list_update = move |relay_list: &RelayList| {
             relay_list_listener.notify_relay_list(relay_list.clone());
@@ -872,7 +1025,10 @@ impl Daemon {
         );
 
         let leak_checker = {
-            let mut leak_checker = LeakChecker::new(route_manager);
+            let mut leak_checker = LeakChecker::new(route_manager.clone());
+            leak_checker.set_non_tunnel_resolvers(dns::non_tunnel_resolvers(
+                &settings.tunnel_options.dns_options,
+            ));
             let internal_event_tx = internal_event_tx.clone();
             leak_checker.add_leak_callback(move |info| {
                 internal_event_tx
@@ -882,6 +1038,23 @@ impl Daemon {
             leak_checker
         };
 
+        #[cfg(not(target_os = "android"))]
+        let relay_latency_prober = relay_latency::RelayLatencyProber::new(relay_selector.clone());
+
+        let connection_history = ConnectionHistory::new(&config.cache_dir).await;
+
+        let metrics = metrics::MetricsExporter::new(
+            api_handle.service().stats(),
+            relay_selector.clone(),
+            account_manager.clone(),
+        );
+        if let Err(error) = metrics.set_settings(&settings.metrics).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to start metrics endpoint")
+            );
+        }
+
         let daemon = Daemon {
             tunnel_state: TunnelState::Disconnected {
                 location: None,
@@ -890,7 +1063,9 @@ impl Daemon {
             },
             target_state,
             #[cfg(target_os = "linux")]
-            exclude_pids: split_tunnel::PidManager::new().map_err(Error::InitSplitTunneling)?,
+            exclude_pids,
+            #[cfg(target_os = "linux")]
+            exclude_apps,
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
@@ -913,10 +1088,22 @@ impl Daemon {
             volume_update_tx,
             location_handler,
             leak_checker,
+            #[cfg(target_os = "linux")]
+            route_manager,
+            #[cfg(not(target_os = "android"))]
+            relay_latency_prober,
+            connection_history,
+            metrics,
         };
 
         api_availability.unsuspend();
 
+        #[cfg(target_os = "linux")]
+        daemon.update_excluded_apps(
+            daemon.settings.split_tunnel.enable_exclusions,
+            &daemon.settings.split_tunnel.apps,
+        );
+
         Ok(daemon)
     }
 
@@ -980,6 +1167,7 @@ impl Daemon {
             tunnel_state_machine_handle,
             target_state,
             account_manager,
+            access_mode_handler,
             ..
         } = self;
 
@@ -994,6 +1182,8 @@ impl Daemon {
         // Wait for the management interface server to shut down
         management_interface.stop().await;
 
+        access_mode_handler.shutdown().await;
+
         drop(api_runtime);
     }
 
@@ -1030,13 +1220,20 @@ impl Daemon {
             LocationEvent(location_data) => self.handle_location_event(location_data),
             SettingsChanged => {
                 self.update_feature_indicators_on_settings_changed();
+                self.leak_checker
+                    .set_non_tunnel_resolvers(dns::non_tunnel_resolvers(
+                        &self.settings.tunnel_options.dns_options,
+                    ));
             }
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
-            LeakDetected(leak_info) => {
+            LeakDetected(leak) => {
                 log::warn!("Network leak detected! Please contact Mullvad support.");
-                log::warn!("{leak_info:?}")
+                log::warn!("{leak:?}");
+                self.management_interface
+                    .notifier()
+                    .notify_leak_detected(leak);
             }
+            NetworkChanged(network) => self.handle_network_change(network),
         }
         should_stop
     }
@@ -1045,8 +1242,32 @@ impl Daemon {
         &mut self,
         tunnel_state_transition: TunnelStateTransition,
     ) {
+        // The connected state is broadcast again when the tunnel MTU changes. Only update the
+        // reported state in that case, since nothing else about the tunnel is different.
+        if let (
+            TunnelState::Connected { endpoint, .. },
+            TunnelStateTransition::Connected(new_endpoint),
+        ) = (&mut self.tunnel_state, &tunnel_state_transition)
+        {
+            let same_tunnel = talpid_types::net::TunnelEndpoint {
+                mtu: endpoint.mtu,
+                ..new_endpoint.clone()
+            } == *endpoint;
+            if same_tunnel {
+                log::debug!("Tunnel MTU changed to {:?}", new_endpoint.mtu);
+                endpoint.mtu = new_endpoint.mtu;
+                self.management_interface
+                    .notifier()
+                    .notify_new_state(self.tunnel_state.clone());
+                return;
+            }
+        }
+
         self.leak_checker
             .on_tunnel_state_transition(tunnel_state_transition.clone());
+        #[cfg(not(target_os = "android"))]
+        self.relay_latency_prober
+            .on_tunnel_state_transition(tunnel_state_transition.clone());
 
         self.reset_rpc_sockets_on_tunnel_state_transition(&tunnel_state_transition);
         self.device_checker
@@ -1070,6 +1291,7 @@ impl Daemon {
                     endpoint,
                     location: self.parameters_generator.get_last_location().await,
                     feature_indicators,
+                    active_profile: self.settings.active_profile().map(str::to_owned),
                 }
             }
             TunnelStateTransition::Connected(endpoint) => {
@@ -1082,6 +1304,7 @@ impl Daemon {
                     endpoint,
                     location: self.parameters_generator.get_last_location().await,
                     feature_indicators,
+                    active_profile: self.settings.active_profile().map(str::to_owned),
                 }
             }
             TunnelStateTransition::Disconnecting(after_disconnect) => {
@@ -1104,6 +1327,9 @@ impl Daemon {
 
         log::debug!("New tunnel state: {:?}", tunnel_state);
 
+        self.record_connection_history(&tunnel_state).await;
+        self.metrics.on_tunnel_state(&tunnel_state);
+
         match tunnel_state {
             TunnelState::Disconnected { .. } => {
                 self.api_handle.availability.reset_inactivity_timer();
@@ -1146,6 +1372,26 @@ impl Daemon {
         self.fetch_am_i_mullvad();
     }
 
+    async fn record_connection_history(&mut self, tunnel_state: &TunnelState) {
+        let retry_attempt = match tunnel_state {
+            TunnelState::Connecting { .. } | TunnelState::Connected { .. } => {
+                Some(self.parameters_generator.get_last_retry_attempt().await)
+            }
+            _ => None,
+        };
+        let entry = ConnectionHistoryEntry {
+            time: chrono::Utc::now(),
+            retry_attempt,
+            state: tunnel_state.clone(),
+        };
+        if let Err(error) = self.connection_history.record(entry).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to record connection history")
+            );
+        }
+    }
+
     /// Get the geographical location from am.i.mullvad.net. When it arrives,
     /// update the "Out IP" field of the front ends by sending a
     /// [`InternalDaemonEvent::LocationEvent`].
@@ -1302,6 +1548,10 @@ impl Daemon {
             SetTargetState(tx, state) => self.on_set_target_state(tx, state).await,
             Reconnect(tx) => self.on_reconnect(tx),
             GetState(tx) => self.on_get_state(tx),
+            GetTunnelStats(tx) => self.on_get_tunnel_stats(tx),
+            GetConnectionHistory(tx, filter) => self.on_get_connection_history(tx, filter),
+            RunLeakCheck(tx) => self.on_run_leak_check(tx),
+            GetDiagnostics(tx) => self.on_get_diagnostics(tx),
             CreateNewAccount(tx) => self.on_create_new_account(tx),
             GetAccountData(tx, account_number) => self.on_get_account_data(tx, account_number),
             GetWwwAuthToken(tx) => self.on_get_www_auth_token(tx).await,
@@ -1352,18 +1602,35 @@ impl Daemon {
                 self.on_set_relay_override(tx, relay_override).await
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
+            SetRetryOrder(tx, retry_order) => self.on_set_retry_order(tx, retry_order).await,
+            SetMetricsSettings(tx, settings) => self.on_set_metrics_settings(tx, settings).await,
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
             }
+            SetWireguardConnectivityCheck(tx, options) => {
+                self.on_set_wireguard_connectivity_check(tx, options).await
+            }
             GetSettings(tx) => self.on_get_settings(tx),
+            GetEffectiveSettings(tx) => self.on_get_effective_settings(tx),
             ResetSettings(tx) => self.on_reset_settings(tx).await,
             RotateWireguardKey(tx) => self.on_rotate_wireguard_key(tx),
             GetWireguardKey(tx) => self.on_get_wireguard_key(tx).await,
+            ExportWireguardConfig(tx) => self.on_export_wireguard_config(tx).await,
+            ImportWireguardConfig(tx, config, ipv4_gateway, ipv6_gateway) => {
+                self.on_import_wireguard_config(tx, config, ipv4_gateway, ipv6_gateway)
+                    .await
+            }
             CreateCustomList(tx, name) => self.on_create_custom_list(tx, name).await,
             DeleteCustomList(tx, id) => self.on_delete_custom_list(tx, id).await,
             UpdateCustomList(tx, update) => self.on_update_custom_list(tx, update).await,
             ClearCustomLists(tx) => self.on_clear_custom_lists(tx).await,
+            CreateSettingsProfile(tx, profile) => {
+                self.on_create_settings_profile(tx, profile).await
+            }
+            DeleteSettingsProfile(tx, name) => self.on_delete_settings_profile(tx, name).await,
+            GetSettingsProfiles(tx) => self.on_get_settings_profiles(tx),
+            SetActiveSettingsProfile(tx, name) => self.on_set_active_settings_profile(tx, name),
             GetVersionInfo(tx) => self.on_get_version_info(tx),
             AddApiAccessMethod(tx, name, enabled, access_method) => {
                 self.on_add_access_method(tx, name, enabled, access_method)
@@ -1373,6 +1640,11 @@ impl Daemon {
             UpdateApiAccessMethod(tx, method) => self.on_update_api_access_method(tx, method).await,
             ClearCustomApiAccessMethods(tx) => self.on_clear_custom_api_access_methods(tx).await,
             GetCurrentAccessMethod(tx) => self.on_get_current_api_access_method(tx),
+            SetPinnedApiAccessMethod(tx, method) => {
+                self.on_set_pinned_api_access_method(tx, method).await
+            }
+            GetApiAccessMethodStats(tx) => self.on_get_api_access_method_stats(tx),
+            GetSystemProxy(tx) => self.on_get_system_proxy(tx),
             SetApiAccessMethod(tx, method) => self.on_set_api_access_method(tx, method).await,
             TestApiAccessMethodById(tx, method) => self.on_test_api_access_method(tx, method).await,
             TestCustomApiAccessMethod(tx, proxy) => self.on_test_proxy_as_access_method(tx, proxy),
@@ -1388,13 +1660,23 @@ impl Daemon {
             RemoveSplitTunnelProcess(tx, pid) => self.on_remove_split_tunnel_process(tx, pid),
             #[cfg(target_os = "linux")]
             ClearSplitTunnelProcesses(tx) => self.on_clear_split_tunnel_processes(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+            #[cfg(target_os = "linux")]
+            GetSplitTunnelMechanism(tx) => self.on_get_split_tunnel_mechanism(tx),
+            #[cfg(target_os = "linux")]
+            SetSplitTunnelMode(tx, mode) => self.on_set_split_tunnel_mode(tx, mode).await,
+            #[cfg(target_os = "linux")]
+            AddFirewallException(tx, exception) => {
+                self.on_add_firewall_exception(tx, exception).await
+            }
+            #[cfg(target_os = "linux")]
+            RemoveFirewallException(tx, exception) => {
+                self.on_remove_firewall_exception(tx, exception).await
+            }
+            #[cfg(target_os = "linux")]
+            ClearFirewallExceptions(tx) => self.on_clear_firewall_exceptions(tx).await,
             AddSplitTunnelApp(tx, app) => self.on_add_split_tunnel_app(tx, app),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             RemoveSplitTunnelApp(tx, path) => self.on_remove_split_tunnel_app(tx, path),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ClearSplitTunnelApps(tx) => self.on_clear_split_tunnel_apps(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             SetSplitTunnelState(tx, enabled) => self.on_set_split_tunnel_state(tx, enabled),
             #[cfg(windows)]
             GetSplitTunnelProcesses(tx) => self.on_get_split_tunnel_processes(tx),
@@ -1573,7 +1855,6 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn handle_new_excluded_paths(
         &mut self,
         update: ExcludedPathsUpdate,
@@ -1642,6 +1923,74 @@ impl Daemon {
         Self::oneshot_send(tx, self.tunnel_state.clone(), "current state");
     }
 
+    fn on_get_tunnel_stats(&self, tx: oneshot::Sender<Option<Vec<PeerStats>>>) {
+        self.send_tunnel_command(TunnelCommand::GetTunnelStats(tx));
+    }
+
+    fn on_get_connection_history(
+        &self,
+        tx: oneshot::Sender<Vec<ConnectionHistoryEntry>>,
+        filter: ConnectionHistoryFilter,
+    ) {
+        let entries = self.connection_history.get(&filter);
+        Self::oneshot_send(tx, entries, "connection history");
+    }
+
+    fn on_run_leak_check(&mut self, tx: ResponseTx<Vec<LeakTestResult>, Error>) {
+        let TunnelState::Connected { endpoint, .. } = &self.tunnel_state else {
+            Self::oneshot_send(
+                tx,
+                Err(Error::LeakCheckNotConnected),
+                "run_leak_check response",
+            );
+            return;
+        };
+        let leak_check = match self.leak_checker.run_leak_check(endpoint) {
+            Ok(leak_check) => leak_check,
+            Err(error) => {
+                Self::oneshot_send(tx, Err(Error::LeakCheck(error)), "run_leak_check response");
+                return;
+            }
+        };
+        let daemon_tx = self.tx.clone();
+        tokio::spawn(async move {
+            let results = leak_check.await.map_err(Error::LeakCheck);
+            // Leaks found on demand are broadcast just like those found by the automatic check
+            for result in results.iter().flatten() {
+                if let LeakStatus::LeakDetected(..) = result.status {
+                    let _ = daemon_tx.send(InternalDaemonEvent::LeakDetected(result.clone()));
+                }
+            }
+            Self::oneshot_send(tx, results, "run_leak_check response");
+        });
+    }
+
+    fn on_get_diagnostics(&mut self, tx: oneshot::Sender<Diagnostics>) {
+        let (dns_tx, dns_rx) = oneshot::channel();
+        self.send_tunnel_command(TunnelCommand::GetDnsState(dns_tx));
+        let relay_list = RelayListMetadata::new(
+            &self.relay_selector.get_relays(),
+            self.relay_selector.last_updated().into(),
+        );
+        #[cfg(target_os = "linux")]
+        let route_manager = self.route_manager.clone();
+        tokio::spawn(async move {
+            let diagnostics = Diagnostics {
+                firewall: diagnostics::firewall().await,
+                routes: diagnostics::routes(
+                    #[cfg(target_os = "linux")]
+                    &route_manager,
+                )
+                .await,
+                dns: dns_rx
+                    .await
+                    .unwrap_or_else(|_| "The tunnel state machine is down".to_owned()),
+                relay_list,
+            };
+            Self::oneshot_send(tx, diagnostics, "diagnostics response");
+        });
+    }
+
     fn on_is_performing_post_upgrade(&self, tx: oneshot::Sender<bool>) {
         let performing_post_upgrade = !self.migration_complete.is_complete();
         Self::oneshot_send(tx, performing_post_upgrade, "performing post upgrade");
@@ -1963,6 +2312,119 @@ impl Daemon {
         Self::oneshot_send(tx, result, "clear_split_tunnel_processes response");
     }
 
+    #[cfg(target_os = "linux")]
+    fn on_get_split_tunnel_mechanism(&self, tx: oneshot::Sender<split_tunnel::ExclusionMechanism>) {
+        let mechanism = self.exclude_pids.mechanism();
+        Self::oneshot_send(tx, mechanism, "get_split_tunnel_mechanism response");
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_set_split_tunnel_mode(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        mode: SplitTunnelMode,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.split_tunnel.mode = mode)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                        self.settings.split_tunnel.effective_mode(),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_split_tunnel_mode response");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_add_firewall_exception(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        exception: FirewallException,
+    ) {
+        let mut exceptions = self.settings.firewall_exceptions.clone();
+        exceptions.push(exception);
+        if let Err(error) = FirewallException::validate_all(&exceptions) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Invalid firewall exception")
+            );
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidFirewallException(error)),
+                "add_firewall_exception response",
+            );
+            return;
+        }
+        self.update_firewall_exceptions(tx, "add_firewall_exception response", |exceptions| {
+            if !exceptions.contains(&exception) {
+                exceptions.push(exception);
+            }
+        })
+        .await;
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_remove_firewall_exception(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        exception: FirewallException,
+    ) {
+        self.update_firewall_exceptions(tx, "remove_firewall_exception response", |exceptions| {
+            exceptions.retain(|existing| *existing != exception)
+        })
+        .await;
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_clear_firewall_exceptions(&mut self, tx: ResponseTx<(), Error>) {
+        self.update_firewall_exceptions(tx, "clear_firewall_exceptions response", Vec::clear)
+            .await;
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn update_firewall_exceptions(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        response_msg: &'static str,
+        update: impl FnOnce(&mut Vec<FirewallException>) + Send + 'static,
+    ) {
+        match self
+            .settings
+            .update(move |settings| update(&mut settings.firewall_exceptions))
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::FirewallExceptions(
+                        self.settings.firewall_exceptions.clone(),
+                        oneshot_map(tx, move |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), response_msg);
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), response_msg);
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(Error::SettingsError(e)), response_msg);
+            }
+        }
+    }
+
     /// Update the split app paths in both the settings and tunnel
     #[cfg(any(windows, target_os = "android"))]
     fn set_split_tunnel_paths(
@@ -2084,7 +2546,54 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
+    /// Update the split app paths in both the settings and the app monitor
+    #[cfg(target_os = "linux")]
+    fn set_split_tunnel_paths(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        _response_msg: &'static str,
+        settings: Settings,
+        update: ExcludedPathsUpdate,
+    ) {
+        match update {
+            ExcludedPathsUpdate::SetPaths(ref paths) => {
+                self.update_excluded_apps(settings.split_tunnel.enable_exclusions, paths)
+            }
+            ExcludedPathsUpdate::SetState(state) => {
+                self.update_excluded_apps(state, &settings.split_tunnel.apps);
+                let mode = SplitTunnelSettings {
+                    enable_exclusions: state,
+                    ..settings.split_tunnel
+                }
+                .effective_mode();
+                let (mode_tx, _) = oneshot::channel();
+                self.send_tunnel_command(TunnelCommand::SplitTunnelMode(mode, mode_tx));
+            }
+        }
+        let _ = self
+            .tx
+            .send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+    }
+
+    #[cfg(target_os = "linux")]
+    fn update_excluded_apps(&self, enabled: bool, apps: &HashSet<SplitApp>) {
+        let Some(exclude_apps) = &self.exclude_apps else {
+            if enabled && !apps.is_empty() {
+                log::warn!("Not excluding apps since processes cannot be monitored");
+            }
+            return;
+        };
+        let apps = if enabled {
+            apps.iter()
+                .cloned()
+                .map(|app| PathBuf::from(app.to_tunnel_command_repr()))
+                .collect()
+        } else {
+            vec![]
+        };
+        exclude_apps.set_apps(apps);
+    }
+
     fn on_add_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: SplitApp) {
         let settings = self.settings.to_settings();
 
@@ -2102,7 +2611,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_remove_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: impl Into<SplitApp>) {
         let settings = self.settings.to_settings();
 
@@ -2120,7 +2628,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_clear_split_tunnel_apps(&mut self, tx: ResponseTx<(), Error>) {
         let settings = self.settings.to_settings();
         let new_list = HashSet::new();
@@ -2132,7 +2639,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_set_split_tunnel_state(&mut self, tx: ResponseTx<(), Error>, state: bool) {
         let settings = self.settings.to_settings();
         self.set_split_tunnel_paths(
@@ -2196,8 +2702,9 @@ impl Daemon {
         {
             Ok(settings_changed) => {
                 if settings_changed {
+                    // A settings profile may override the new value
                     self.send_tunnel_command(TunnelCommand::AllowLan(
-                        allow_lan,
+                        self.settings.allow_lan,
                         oneshot_map(tx, |tx, ()| {
                             Self::oneshot_send(tx, Ok(()), "set_allow_lan response");
                         }),
@@ -2251,7 +2758,7 @@ impl Daemon {
             Ok(settings_changed) => {
                 if settings_changed {
                     self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
-                        block_when_disconnected,
+                        self.settings.block_when_disconnected,
                         oneshot_map(tx, |tx, ()| {
                             Self::oneshot_send(tx, Ok(()), "set_block_when_disconnected response");
                         }),
@@ -2636,6 +3143,81 @@ impl Daemon {
         }
     }
 
+    async fn on_set_retry_order(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        retry_order: Option<Vec<RetryAttempt>>,
+    ) {
+        if let Some(retry_order) = &retry_order {
+            if let Err(error) = self.relay_selector.validate_retry_order(retry_order) {
+                log::error!("{}", error.display_chain_with_msg("Invalid retry order"));
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::InvalidRetryOrder(error)),
+                    "set_retry_order response",
+                );
+                return;
+            }
+        }
+        match self
+            .settings
+            .update(move |settings| settings.retry_order = retry_order)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_retry_order response");
+                if settings_changed {
+                    self.reconnect_tunnel();
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(Error::SettingsError(e)), "set_retry_order response");
+            }
+        }
+    }
+
+    async fn on_set_metrics_settings(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        metrics_settings: MetricsSettings,
+    ) {
+        // Start the endpoint before saving the settings, so that it is never saved as enabled
+        // when it is invalid or cannot be bound
+        if let Err(error) = self.metrics.set_settings(&metrics_settings).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to start metrics endpoint")
+            );
+            let error = match error {
+                metrics::Error::InvalidEndpoint(error) => Error::InvalidMetricsEndpoint(error),
+                error => Error::MetricsEndpoint(error),
+            };
+            Self::oneshot_send(tx, Err(error), "set_metrics_settings response");
+            return;
+        }
+        let new_settings = metrics_settings.clone();
+        let result = match self
+            .settings
+            .update(move |settings| settings.metrics = new_settings)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                // Restore the endpoint that matches the saved settings
+                if let Err(error) = self.metrics.set_settings(&self.settings.metrics).await {
+                    log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to restore metrics endpoint")
+                    );
+                }
+                Err(Error::SettingsError(e))
+            }
+        };
+        Self::oneshot_send(tx, result, "set_metrics_settings response");
+    }
+
     async fn on_set_wireguard_mtu(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2696,6 +3278,50 @@ impl Daemon {
         }
     }
 
+    async fn on_set_wireguard_connectivity_check(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        options: talpid_types::net::wireguard::ConnectivityCheckOptions,
+    ) {
+        if let Err(error) = options.validate() {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Invalid connectivity check options")
+            );
+            Self::oneshot_send(
+                tx,
+                Err(Error::InvalidConnectivityCheck(error)),
+                "set_wireguard_connectivity_check response",
+            );
+            return;
+        }
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.wireguard.connectivity = options)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_connectivity_check response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the connectivity check changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(
+                    tx,
+                    Err(Error::SettingsError(e)),
+                    "set_wireguard_connectivity_check response",
+                );
+            }
+        }
+    }
+
     fn on_rotate_wireguard_key(&self, tx: ResponseTx<(), Error>) {
         let manager = self.account_manager.clone();
         tokio::spawn(async move {
@@ -2718,6 +3344,111 @@ impl Daemon {
         Self::oneshot_send(tx, result, "get_wireguard_key response");
     }
 
+    async fn on_export_wireguard_config(&self, tx: ResponseTx<String, Error>) {
+        let result = self.export_wireguard_config().await;
+        Self::oneshot_send(tx, result, "export_wireguard_config response");
+    }
+
+    async fn export_wireguard_config(&self) -> Result<String, Error> {
+        let TunnelState::Connected { endpoint, .. } = &self.tunnel_state else {
+            return Err(Error::NoWireguardTunnel);
+        };
+        let parameters = self
+            .parameters_generator
+            .get_last_wireguard_parameters()
+            .await
+            .ok_or(Error::NoWireguardTunnel)?;
+        let connection = &parameters.connection;
+
+        let gateways: Vec<IpAddr> = std::iter::once(IpAddr::from(connection.ipv4_gateway))
+            .chain(connection.ipv6_gateway.map(IpAddr::from))
+            .collect();
+        let dns = dns::plain_resolvers(&self.settings.tunnel_options.dns_options, &gateways);
+        // Prefer the MTU that is actually in use, since it may have been lowered by MTU detection
+        let mtu = endpoint.mtu.or(parameters.options.mtu);
+
+        let config =
+            mullvad_types::wg_quick::WgQuickConfig::from_connection_config(connection, dns, mtu)
+                .map_err(Error::ExportWireguardConfig)?;
+        Ok(config.to_string())
+    }
+
+    async fn on_import_wireguard_config(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) {
+        let result = self
+            .import_wireguard_config(config, ipv4_gateway, ipv6_gateway)
+            .await;
+        Self::oneshot_send(tx, result, "import_wireguard_config response");
+    }
+
+    /// Apply the relay, MTU and DNS settings of a wg-quick config in a single settings update,
+    /// so that no connection attempt is made with only some of them applied.
+    async fn import_wireguard_config(
+        &mut self,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) -> Result<(), Error> {
+        let config: mullvad_types::wg_quick::WgQuickConfig =
+            config.parse().map_err(Error::ImportWireguardConfig)?;
+        let custom_endpoint = config
+            .to_custom_tunnel_endpoint(ipv4_gateway, ipv6_gateway)
+            .map_err(Error::ImportWireguardConfig)?;
+
+        // The gateways are used for DNS unless other servers are set. Private addresses in the
+        // custom DNS settings are not reached through the tunnel, so the gateways are left out.
+        let ConnectionConfig::Wireguard(connection) = &custom_endpoint.config else {
+            unreachable!("wg-quick configs are always WireGuard configs");
+        };
+        let gateways = [
+            Some(IpAddr::from(connection.ipv4_gateway)),
+            connection.ipv6_gateway.map(IpAddr::from),
+        ];
+        let custom_dns: Vec<IpAddr> = config
+            .interface
+            .dns
+            .iter()
+            .filter(|&&address| !gateways.contains(&Some(address)))
+            .copied()
+            .collect();
+        let mtu = config.interface.mtu;
+
+        let settings_changed = self
+            .settings
+            .update(move |settings| {
+                settings.set_relay_settings(RelaySettings::CustomTunnelEndpoint(custom_endpoint));
+                if mtu.is_some() {
+                    settings.tunnel_options.wireguard.mtu = mtu;
+                }
+                let dns_options = &mut settings.tunnel_options.dns_options;
+                if custom_dns.is_empty() {
+                    dns_options.state = DnsState::Default;
+                } else {
+                    dns_options.state = DnsState::Custom;
+                    dns_options.custom_options = CustomDnsOptions {
+                        addresses: custom_dns,
+                        encryption: None,
+                    };
+                }
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+
+        if settings_changed {
+            let (tx, _rx) = oneshot::channel();
+            let dns = dns::addresses_from_options(&self.settings.tunnel_options.dns_options);
+            self.send_tunnel_command(TunnelCommand::Dns(dns, tx));
+            log::info!("Initiating tunnel restart because a WireGuard config was imported");
+            self.reconnect_tunnel();
+        }
+        Ok(())
+    }
+
     async fn on_create_custom_list(
         &mut self,
         tx: ResponseTx<mullvad_types::custom_list::Id, Error>,
@@ -2746,6 +3477,41 @@ impl Daemon {
         Self::oneshot_send(tx, result, "clear_custom_lists response");
     }
 
+    async fn on_create_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        profile: SettingsProfile,
+    ) {
+        let result = self.create_settings_profile(profile).await;
+        Self::oneshot_send(tx, result, "create_settings_profile response");
+    }
+
+    async fn on_delete_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        name: String,
+    ) {
+        let result = self.delete_settings_profile(name).await;
+        Self::oneshot_send(tx, result, "delete_settings_profile response");
+    }
+
+    fn on_get_settings_profiles(&self, tx: oneshot::Sender<ProfileList>) {
+        Self::oneshot_send(
+            tx,
+            self.get_settings_profiles(),
+            "get_settings_profiles response",
+        );
+    }
+
+    fn on_set_active_settings_profile(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        name: Option<String>,
+    ) {
+        let result = self.set_active_settings_profile(name);
+        Self::oneshot_send(tx, result, "set_active_settings_profile response");
+    }
+
     async fn on_add_access_method(
         &mut self,
         tx: ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -2816,6 +3582,37 @@ impl Daemon {
         });
     }
 
+    async fn on_set_pinned_api_access_method(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        access_method: Option<mullvad_types::access_method::Id>,
+    ) {
+        let result = self
+            .pin_api_access_method(access_method)
+            .await
+            .map_err(Error::AccessMethodError);
+        Self::oneshot_send(tx, result, "set_pinned_api_access_method response");
+    }
+
+    fn on_get_api_access_method_stats(&mut self, tx: ResponseTx<Vec<AccessMethodStats>, Error>) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle.stats().await.map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "get_api_access_method_stats response");
+        });
+    }
+
+    fn on_get_system_proxy(&mut self, tx: ResponseTx<DetectedSystemProxy, Error>) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .system_proxy()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "get_system_proxy response");
+        });
+    }
+
     fn on_test_proxy_as_access_method(
         &mut self,
         tx: ResponseTx<bool, Error>,
@@ -2910,7 +3707,19 @@ impl Daemon {
     }
 
     fn on_get_settings(&self, tx: oneshot::Sender<Settings>) {
-        Self::oneshot_send(tx, self.settings.to_settings(), "get_settings response");
+        Self::oneshot_send(
+            tx,
+            self.settings.base_settings().clone(),
+            "get_settings response",
+        );
+    }
+
+    fn on_get_effective_settings(&self, tx: oneshot::Sender<Settings>) {
+        Self::oneshot_send(
+            tx,
+            self.settings.to_settings(),
+            "get_effective_settings response",
+        );
     }
 
     async fn on_reset_settings(&mut self, tx: ResponseTx<(), settings::Error>) {
@@ -2926,6 +3735,21 @@ impl Daemon {
             let (tx, _rx) = oneshot::channel();
             self.send_tunnel_command(TunnelCommand::SetExcludedApps(tx, vec![]));
         }
+        #[cfg(target_os = "linux")]
+        {
+            self.update_excluded_apps(false, &HashSet::new());
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                self.settings.split_tunnel.effective_mode(),
+                tx,
+            ));
+
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::FirewallExceptions(
+                self.settings.firewall_exceptions.clone(),
+                tx,
+            ));
+        }
 
         #[cfg(not(target_os = "android"))]
         {
@@ -2946,6 +3770,12 @@ impl Daemon {
         self.version_updater_handle
             .set_show_beta_releases(self.settings.show_beta_releases)
             .await;
+        if let Err(error) = self.metrics.set_settings(&self.settings.metrics).await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to update metrics endpoint")
+            );
+        }
         let access_mode_handler = self.access_mode_handler.clone();
         tokio::spawn(async move {
             if let Err(error) = access_mode_handler.rotate().await {
@@ -3068,7 +3898,7 @@ impl Daemon {
     }
 
     fn on_export_json_settings(&mut self, tx: ResponseTx<String, settings::patch::Error>) {
-        let result = settings::patch::export_settings(&self.settings);
+        let result = settings::patch::export_settings(self.settings.base_settings());
         Self::oneshot_send(tx, result, "export_json_settings response");
     }
 
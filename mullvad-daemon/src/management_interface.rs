@@ -10,26 +10,31 @@ use mullvad_management_interface::{
 };
 use mullvad_types::{
     account::AccountNumber,
+    connection_history::ConnectionHistoryFilter,
+    metrics::MetricsSettings,
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        RetryAttempt,
     },
     relay_list::RelayList,
     settings::{DnsOptions, Settings},
     states::{TargetState, TunnelState},
     version,
-    wireguard::{RotationInterval, RotationIntervalError},
+    wireguard::{RotationInterval, RotationIntervalError, TunnelStats},
 };
 use std::{
     path::Path,
     str::FromStr,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use talpid_types::ErrorExt;
 use tokio::time::timeout;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
 const RPC_SERVER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often [`types::TunnelStats`] are emitted by `StreamTunnelStats`.
+const TUNNEL_STATS_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -54,6 +59,7 @@ const USED_VOUCHER_MESSAGE: &str = "This voucher code has already been used";
 impl ManagementService for ManagementServiceImpl {
     type GetSplitTunnelProcessesStream = UnboundedReceiverStream<Result<i32, Status>>;
     type EventsListenStream = EventsListenerReceiver;
+    type StreamTunnelStatsStream = UnboundedReceiverStream<Result<types::TunnelStats, Status>>;
 
     // Control and get the tunnel state
     //
@@ -92,6 +98,85 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(types::TunnelState::from(state)))
     }
 
+    async fn stream_tunnel_stats(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<Self::StreamTunnelStatsStream> {
+        log::debug!("stream_tunnel_stats");
+        let daemon_tx = self.daemon_tx.clone();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TUNNEL_STATS_INTERVAL);
+            let mut previous = (Instant::now(), vec![]);
+            loop {
+                interval.tick().await;
+
+                let (stats_tx, stats_rx) = oneshot::channel();
+                if daemon_tx
+                    .send(DaemonCommand::GetTunnelStats(stats_tx))
+                    .is_err()
+                {
+                    break;
+                }
+                let Ok(peers) = stats_rx.await else {
+                    break;
+                };
+                let peers = peers.unwrap_or_default();
+
+                let now = Instant::now();
+                let stats = TunnelStats::from_samples(&peers, &previous.1, now - previous.0);
+                previous = (now, peers);
+
+                if tx.send(Ok(types::TunnelStats::from(stats))).is_err() {
+                    // The client has stopped listening
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+
+    async fn get_connection_history(
+        &self,
+        request: Request<types::ConnectionHistoryFilter>,
+    ) -> ServiceResult<types::ConnectionHistory> {
+        log::debug!("get_connection_history");
+        let filter = ConnectionHistoryFilter::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetConnectionHistory(tx, filter))?;
+        let entries = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::ConnectionHistory {
+            entries: entries
+                .into_iter()
+                .map(types::ConnectionHistoryEntry::from)
+                .collect(),
+        }))
+    }
+
+    async fn run_leak_check(&self, _: Request<()>) -> ServiceResult<types::LeakCheckResults> {
+        log::debug!("run_leak_check");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RunLeakCheck(tx))?;
+        let results = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(types::LeakCheckResults {
+            results: results
+                .into_iter()
+                .map(types::LeakTestResult::from)
+                .collect(),
+        }))
+    }
+
+    async fn get_diagnostics(&self, _: Request<()>) -> ServiceResult<types::Diagnostics> {
+        log::debug!("get_diagnostics");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDiagnostics(tx))?;
+        let diagnostics = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::Diagnostics::from(diagnostics)))
+    }
+
     // Control the daemon and receive events
     //
 
@@ -247,6 +332,15 @@ impl ManagementService for ManagementServiceImpl {
             .map(|settings| Response::new(types::Settings::from(&settings)))
     }
 
+    async fn get_effective_settings(&self, _: Request<()>) -> ServiceResult<types::Settings> {
+        log::debug!("get_effective_settings");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetEffectiveSettings(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|settings| Response::new(types::Settings::from(&settings)))
+    }
+
     async fn reset_settings(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("reset_settings");
         let (tx, rx) = oneshot::channel();
@@ -401,6 +495,20 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_wireguard_connectivity_check(
+        &self,
+        request: Request<types::ConnectivityCheckOptions>,
+    ) -> ServiceResult<()> {
+        let options =
+            talpid_types::net::wireguard::ConnectivityCheckOptions::try_from(request.into_inner())
+                .map_err(map_protobuf_type_err)?;
+        log::debug!("set_wireguard_connectivity_check({options:?})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardConnectivityCheck(tx, options))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     async fn set_dns_options(&self, request: Request<types::DnsOptions>) -> ServiceResult<()> {
         let options = DnsOptions::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
         log::debug!("set_dns_options({:?})", options);
@@ -432,6 +540,42 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_retry_order(&self, request: Request<types::RetryOrder>) -> ServiceResult<()> {
+        let retry_order = request
+            .into_inner()
+            .attempts
+            .into_iter()
+            .map(RetryAttempt::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_retry_order({:?})", retry_order);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRetryOrder(tx, Some(retry_order)))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
+    async fn reset_retry_order(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("reset_retry_order");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRetryOrder(tx, None))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
+    async fn set_metrics_settings(
+        &self,
+        request: Request<types::MetricsSettings>,
+    ) -> ServiceResult<()> {
+        let settings =
+            MetricsSettings::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_metrics_settings({:?})", settings);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetMetricsSettings(tx, settings))?;
+        self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(()))
+    }
+
     // Account management
     //
 
@@ -630,6 +774,45 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
+    async fn export_wireguard_config(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("export_wireguard_config");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportWireguardConfig(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn import_wireguard_config(
+        &self,
+        request: Request<types::WireguardConfigImport>,
+    ) -> ServiceResult<()> {
+        log::debug!("import_wireguard_config");
+        let request = request.into_inner();
+        let ipv4_gateway = request
+            .ipv4_gateway
+            .map(|gateway| gateway.parse())
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid IPv4 gateway"))?;
+        let ipv6_gateway = request
+            .ipv6_gateway
+            .map(|gateway| gateway.parse())
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid IPv6 gateway"))?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportWireguardConfig(
+            tx,
+            request.config,
+            ipv4_gateway,
+            ipv6_gateway,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     // Custom lists
     //
 
@@ -678,6 +861,58 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    // Settings profiles
+
+    async fn create_settings_profile(
+        &self,
+        request: Request<types::SettingsProfile>,
+    ) -> ServiceResult<()> {
+        log::debug!("create_settings_profile");
+        let profile = mullvad_types::profile::SettingsProfile::try_from(request.into_inner())?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::CreateSettingsProfile(tx, profile))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn delete_settings_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("delete_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DeleteSettingsProfile(tx, name))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_settings_profiles(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::SettingsProfiles> {
+        log::debug!("get_settings_profiles");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSettingsProfiles(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(|profiles| Response::new(types::SettingsProfiles::from(profiles)))
+    }
+
+    async fn activate_settings_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        let name = request.into_inner();
+        log::debug!("activate_settings_profile({name})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetActiveSettingsProfile(tx, Some(name)))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn deactivate_settings_profile(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("deactivate_settings_profile");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetActiveSettingsProfile(tx, None))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     // Access Methods
 
     async fn add_api_access_method(
@@ -800,6 +1035,54 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn set_pinned_api_access_method(
+        &self,
+        request: Request<types::PinnedApiAccessMethod>,
+    ) -> ServiceResult<()> {
+        log::debug!("set_pinned_api_access_method");
+        let api_access_method = request
+            .into_inner()
+            .id
+            .map(mullvad_types::access_method::Id::try_from)
+            .transpose()?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetPinnedApiAccessMethod(
+            tx,
+            api_access_method,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_api_access_method_stats(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::ApiAccessMethodStatsList> {
+        log::debug!("get_api_access_method_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetApiAccessMethodStats(tx))?;
+        let stats = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(types::ApiAccessMethodStatsList {
+            stats: stats
+                .into_iter()
+                .map(types::ApiAccessMethodStats::from)
+                .collect(),
+        }))
+    }
+
+    async fn get_system_proxy(&self, _: Request<()>) -> ServiceResult<types::DetectedSystemProxy> {
+        log::debug!("get_system_proxy");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSystemProxy(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(types::DetectedSystemProxy::from)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     // Split tunneling
     //
 
@@ -882,7 +1165,111 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    async fn get_split_tunnel_mechanism(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("get_split_tunnel_mechanism");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetSplitTunnelMechanism(tx))?;
+        let mechanism = self.wait_for_result(rx).await?;
+        Ok(Response::new(mechanism.to_string()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn get_split_tunnel_mechanism(&self, _: Request<()>) -> ServiceResult<String> {
+        Err(Status::unimplemented(
+            "Excluding processes by PID is only supported on Linux",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_split_tunnel_mode(
+        &self,
+        request: Request<types::SplitTunnelMode>,
+    ) -> ServiceResult<()> {
+        use mullvad_types::settings::SplitTunnelMode;
+        let mode =
+            SplitTunnelMode::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_split_tunnel_mode({mode})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSplitTunnelMode(tx, mode))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_split_tunnel_mode(&self, _: Request<types::SplitTunnelMode>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Changing the split tunnel mode is only supported on Linux",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn add_firewall_exception(
+        &self,
+        request: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        use talpid_types::net::firewall::FirewallException;
+        let exception =
+            FirewallException::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("add_firewall_exception({exception})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddFirewallException(tx, exception))?;
+        self.wait_for_result(rx)
+            .await?
+            .map_err(map_daemon_error)
+            .map(Response::new)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn add_firewall_exception(
+        &self,
+        _: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Firewall exceptions are only supported on Linux",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn remove_firewall_exception(
+        &self,
+        request: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        use talpid_types::net::firewall::FirewallException;
+        let exception =
+            FirewallException::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("remove_firewall_exception({exception})");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveFirewallException(tx, exception))?;
+        self.wait_for_result(rx)
+            .await?
+            .map_err(map_daemon_error)
+            .map(Response::new)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn remove_firewall_exception(
+        &self,
+        _: Request<types::FirewallException>,
+    ) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Firewall exceptions are only supported on Linux",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn clear_firewall_exceptions(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_firewall_exceptions");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ClearFirewallExceptions(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map_err(map_daemon_error)
+            .map(Response::new)
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn clear_firewall_exceptions(&self, _: Request<()>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Firewall exceptions are only supported on Linux",
+        ))
+    }
+
     async fn add_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("add_split_tunnel_app");
@@ -895,12 +1282,6 @@ impl ManagementService for ManagementServiceImpl {
             .map(Response::new)
     }
 
-    #[cfg(target_os = "linux")]
-    async fn add_split_tunnel_app(&self, _: Request<String>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
-
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn remove_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("remove_split_tunnel_app");
@@ -912,12 +1293,7 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn remove_split_tunnel_app(&self, _: Request<String>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn clear_split_tunnel_apps(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("clear_split_tunnel_apps");
         let (tx, rx) = oneshot::channel();
@@ -927,12 +1303,7 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn clear_split_tunnel_apps(&self, _: Request<()>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn set_split_tunnel_state(&self, request: Request<bool>) -> ServiceResult<()> {
         log::debug!("set_split_tunnel_state");
         let enabled = request.into_inner();
@@ -943,10 +1314,6 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
             .map(Response::new)
     }
-    #[cfg(target_os = "linux")]
-    async fn set_split_tunnel_state(&self, _: Request<bool>) -> ServiceResult<()> {
-        Ok(Response::new(()))
-    }
 
     #[cfg(windows)]
     async fn get_excluded_processes(
@@ -1266,6 +1633,16 @@ impl ManagementInterfaceEventBroadcaster {
         })
     }
 
+    /// Notify that a leak test detected a leak.
+    pub(crate) fn notify_leak_detected(&self, leak: mullvad_types::leak_check::LeakTestResult) {
+        log::debug!("Broadcasting leak detected event");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::LeakDetected(
+                types::LeakTestResult::from(leak),
+            )),
+        })
+    }
+
     /// Notify that the api access method changed.
     pub(crate) fn notify_new_access_method_event(
         &self,
@@ -1302,6 +1679,31 @@ fn map_daemon_error(error: crate::Error) -> Status {
             Status::unauthenticated(error.to_string())
         }
         DaemonError::VersionCheckError(error) => map_version_check_error(error),
+        DaemonError::InvalidRetryOrder(error) => {
+            Status::invalid_argument(error.display_chain_with_msg("Invalid retry order"))
+        }
+        #[cfg(target_os = "linux")]
+        DaemonError::InvalidFirewallException(error) => {
+            Status::invalid_argument(error.display_chain_with_msg("Invalid firewall exception"))
+        }
+        DaemonError::InvalidMetricsEndpoint(error) => {
+            Status::invalid_argument(error.display_chain_with_msg("Invalid metrics endpoint"))
+        }
+        DaemonError::InvalidConnectivityCheck(error) => Status::invalid_argument(
+            error.display_chain_with_msg("Invalid connectivity check options"),
+        ),
+        DaemonError::NoWireguardTunnel => Status::failed_precondition(error.to_string()),
+        DaemonError::LeakCheckNotConnected => Status::failed_precondition(error.to_string()),
+        DaemonError::LeakCheck(error) => Status::aborted(error.to_string()),
+        DaemonError::ExportWireguardConfig(error) => Status::failed_precondition(
+            error.display_chain_with_msg("Failed to export WireGuard config"),
+        ),
+        DaemonError::ImportWireguardConfig(error) => Status::invalid_argument(
+            error.display_chain_with_msg("Failed to import WireGuard config"),
+        ),
+        DaemonError::MetricsEndpoint(error) => {
+            Status::unavailable(error.display_chain_with_msg("Failed to start metrics endpoint"))
+        }
         error => Status::unknown(error.to_string()),
     }
 }
@@ -0,0 +1,764 @@
+//! Optional local HTTP endpoint that exports statistics about the daemon in the Prometheus text
+//! exposition format, so that headless installations can be scraped by a monitoring system.
+//!
+//! The endpoint is disabled by default and can only be bound to a loopback address or a unix
+//! socket at a fixed path. See [`mullvad_types::metrics::MetricsSettings`].
+
+use crate::device::AccountManagerHandle;
+use mullvad_api::rest::RequestStats;
+use mullvad_relay_selector::RelaySelector;
+#[cfg(unix)]
+use mullvad_types::metrics::METRICS_SOCKET_PATH;
+use mullvad_types::{
+    metrics::{MetricsEndpoint, MetricsSettings},
+    states::TunnelState,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use talpid_types::ErrorExt;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+/// Maximum size of the request line and headers of a request.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// How long to wait for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Labels of the `mullvad_tunnel_state` metric, one for each tunnel state.
+const TUNNEL_STATES: [&str; 5] = [
+    "disconnected",
+    "connecting",
+    "connected",
+    "disconnecting",
+    "error",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid metrics endpoint")]
+    InvalidEndpoint(#[source] mullvad_types::metrics::Error),
+    #[error("Failed to bind metrics endpoint {0}")]
+    Bind(MetricsEndpoint, #[source] io::Error),
+}
+
+/// Runs the metrics endpoint while it is enabled, and keeps track of the statistics that the
+/// daemon does not store anywhere else.
+#[derive(Clone)]
+pub struct MetricsExporter {
+    sources: Sources,
+    server: Arc<tokio::sync::Mutex<Option<Server>>>,
+}
+
+impl MetricsExporter {
+    pub fn new(
+        api_stats: RequestStats,
+        relay_selector: RelaySelector,
+        account_manager: AccountManagerHandle,
+    ) -> Self {
+        MetricsExporter {
+            sources: Sources {
+                tunnel: Arc::new(Mutex::new(TunnelMetrics::default())),
+                api_stats,
+                relay_selector,
+                account_manager,
+            },
+            server: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Start, stop or rebind the endpoint to match `settings`. An endpoint that fails
+    /// [`MetricsEndpoint::validate`] is never bound, and the current one keeps running.
+    pub async fn set_settings(&self, settings: &MetricsSettings) -> Result<(), Error> {
+        let endpoint = Some(&settings.endpoint).filter(|_| settings.enabled);
+        if let Some(endpoint) = endpoint {
+            endpoint.validate().map_err(Error::InvalidEndpoint)?;
+        }
+        let mut server = self.server.lock().await;
+        if server.as_ref().map(|server| &server.endpoint) == endpoint {
+            return Ok(());
+        }
+
+        // Stop the current server first, since the new one may use the same address
+        *server = None;
+        if let Some(endpoint) = endpoint {
+            *server = Some(Server::start(endpoint.clone(), self.sources.clone()).await?);
+            log::info!("Serving metrics on {endpoint}");
+        }
+        Ok(())
+    }
+
+    /// Call when the tunnel transitions to a new state.
+    pub fn on_tunnel_state(&self, tunnel_state: &TunnelState) {
+        self.sources
+            .tunnel
+            .lock()
+            .unwrap()
+            .record(tunnel_state, Instant::now());
+    }
+}
+
+#[derive(Clone)]
+struct Sources {
+    tunnel: Arc<Mutex<TunnelMetrics>>,
+    api_stats: RequestStats,
+    relay_selector: RelaySelector,
+    account_manager: AccountManagerHandle,
+}
+
+impl Sources {
+    async fn snapshot(&self) -> Snapshot {
+        let (tunnel_state, connection_attempts, connected, connected_total, relay_reconnects) = {
+            let tunnel = self.tunnel.lock().unwrap();
+            let connected = tunnel
+                .connected_since
+                .map(|since| since.elapsed())
+                .unwrap_or_default();
+            (
+                tunnel.state,
+                tunnel.connection_attempts,
+                connected,
+                tunnel.connected_total + connected,
+                tunnel.relay_reconnects.clone(),
+            )
+        };
+
+        let last_updated = self.relay_selector.last_updated();
+        let relay_list_age = if last_updated == UNIX_EPOCH {
+            None
+        } else {
+            SystemTime::now().duration_since(last_updated).ok()
+        };
+
+        let wireguard_key_age = match self.account_manager.data().await {
+            Ok(state) => state.device().and_then(|device| {
+                (chrono::Utc::now() - device.device.wg_data.created)
+                    .to_std()
+                    .ok()
+            }),
+            Err(error) => {
+                log::debug!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to get device state for metrics")
+                );
+                None
+            }
+        };
+
+        Snapshot {
+            tunnel_state,
+            connection_attempts,
+            connected,
+            connected_total,
+            relay_reconnects,
+            api_requests_succeeded: self.api_stats.succeeded(),
+            api_requests_failed: self.api_stats.failed(),
+            relay_list_age,
+            wireguard_key_age,
+        }
+    }
+}
+
+/// Statistics derived from the tunnel state transitions.
+struct TunnelMetrics {
+    state: &'static str,
+    /// Number of times the daemon has started connecting to a relay
+    connection_attempts: u64,
+    /// When the tunnel was last connected, if it still is
+    connected_since: Option<Instant>,
+    /// Total time connected, excluding the current connection
+    connected_total: Duration,
+    /// Number of connection attempts per relay hostname that were not initiated from the
+    /// disconnected state, i.e. retries and reconnects after the tunnel went down
+    relay_reconnects: BTreeMap<String, u64>,
+}
+
+impl Default for TunnelMetrics {
+    fn default() -> Self {
+        TunnelMetrics {
+            state: "disconnected",
+            connection_attempts: 0,
+            connected_since: None,
+            connected_total: Duration::ZERO,
+            relay_reconnects: BTreeMap::new(),
+        }
+    }
+}
+
+impl TunnelMetrics {
+    fn record(&mut self, tunnel_state: &TunnelState, now: Instant) {
+        let previous_state = self.state;
+        self.state = state_label(tunnel_state);
+
+        match tunnel_state {
+            TunnelState::Connected { .. } => {
+                self.connected_since.get_or_insert(now);
+            }
+            _ => {
+                if let Some(since) = self.connected_since.take() {
+                    self.connected_total += now.saturating_duration_since(since);
+                }
+            }
+        }
+
+        if let TunnelState::Connecting { location, .. } = tunnel_state {
+            self.connection_attempts += 1;
+            let hostname = location
+                .as_ref()
+                .and_then(|location| location.hostname.as_ref());
+            if let Some(hostname) = hostname.filter(|_| previous_state != "disconnected") {
+                *self.relay_reconnects.entry(hostname.clone()).or_default() += 1;
+            }
+        }
+    }
+}
+
+fn state_label(tunnel_state: &TunnelState) -> &'static str {
+    match tunnel_state {
+        TunnelState::Disconnected { .. } => "disconnected",
+        TunnelState::Connecting { .. } => "connecting",
+        TunnelState::Connected { .. } => "connected",
+        TunnelState::Disconnecting(_) => "disconnecting",
+        TunnelState::Error(_) => "error",
+    }
+}
+
+/// Values of all metrics at the time of a scrape.
+struct Snapshot {
+    tunnel_state: &'static str,
+    connection_attempts: u64,
+    connected: Duration,
+    connected_total: Duration,
+    relay_reconnects: BTreeMap<String, u64>,
+    api_requests_succeeded: u64,
+    api_requests_failed: u64,
+    relay_list_age: Option<Duration>,
+    wireguard_key_age: Option<Duration>,
+}
+
+/// Encode `snapshot` in the Prometheus text exposition format.
+fn encode(snapshot: &Snapshot) -> String {
+    let mut encoder = Encoder::default();
+
+    encoder.family(
+        "mullvad_tunnel_state",
+        "gauge",
+        "Current tunnel state. The series of the current state is 1.",
+    );
+    for state in TUNNEL_STATES {
+        encoder.sample(
+            "mullvad_tunnel_state",
+            &[("state", state)],
+            u8::from(state == snapshot.tunnel_state),
+        );
+    }
+
+    encoder.family(
+        "mullvad_connection_attempts_total",
+        "counter",
+        "Number of times the daemon has started connecting to a relay.",
+    );
+    encoder.sample(
+        "mullvad_connection_attempts_total",
+        &[],
+        snapshot.connection_attempts,
+    );
+
+    encoder.family(
+        "mullvad_connected_seconds",
+        "gauge",
+        "Time since the tunnel was connected, or 0 if it is not connected.",
+    );
+    encoder.sample(
+        "mullvad_connected_seconds",
+        &[],
+        snapshot.connected.as_secs_f64(),
+    );
+
+    encoder.family(
+        "mullvad_connected_seconds_total",
+        "counter",
+        "Total time that the tunnel has been connected since the daemon started.",
+    );
+    encoder.sample(
+        "mullvad_connected_seconds_total",
+        &[],
+        snapshot.connected_total.as_secs_f64(),
+    );
+
+    encoder.family(
+        "mullvad_relay_reconnects_total",
+        "counter",
+        "Number of retries and reconnects per relay.",
+    );
+    for (relay, reconnects) in &snapshot.relay_reconnects {
+        encoder.sample(
+            "mullvad_relay_reconnects_total",
+            &[("relay", relay)],
+            reconnects,
+        );
+    }
+
+    encoder.family(
+        "mullvad_api_requests_total",
+        "counter",
+        "Number of completed requests to the Mullvad API.",
+    );
+    encoder.sample(
+        "mullvad_api_requests_total",
+        &[("result", "success")],
+        snapshot.api_requests_succeeded,
+    );
+    encoder.sample(
+        "mullvad_api_requests_total",
+        &[("result", "failure")],
+        snapshot.api_requests_failed,
+    );
+
+    encoder.family(
+        "mullvad_relay_list_age_seconds",
+        "gauge",
+        "Time since the relay list was fetched.",
+    );
+    if let Some(age) = snapshot.relay_list_age {
+        encoder.sample("mullvad_relay_list_age_seconds", &[], age.as_secs_f64());
+    }
+
+    encoder.family(
+        "mullvad_wireguard_key_age_seconds",
+        "gauge",
+        "Time since the WireGuard key of the device was created.",
+    );
+    if let Some(age) = snapshot.wireguard_key_age {
+        encoder.sample("mullvad_wireguard_key_age_seconds", &[], age.as_secs_f64());
+    }
+
+    encoder.output
+}
+
+#[derive(Default)]
+struct Encoder {
+    output: String,
+}
+
+impl Encoder {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        self.output.push_str(&format!("# HELP {name} {help}\n"));
+        self.output.push_str(&format!("# TYPE {name} {kind}\n"));
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value)))
+                .collect();
+            self.output.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.output.push_str(&format!(" {value}\n"));
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A running metrics endpoint. The endpoint is stopped when this is dropped.
+struct Server {
+    endpoint: MetricsEndpoint,
+    task: JoinHandle<()>,
+    #[cfg(unix)]
+    socket: Option<UnixSocket>,
+}
+
+impl Server {
+    async fn start(endpoint: MetricsEndpoint, sources: Sources) -> Result<Self, Error> {
+        #[cfg(unix)]
+        let mut socket = None;
+        let task = match &endpoint {
+            MetricsEndpoint::Tcp(address) => {
+                let listener = TcpListener::bind(address)
+                    .await
+                    .map_err(|error| Error::Bind(endpoint.clone(), error))?;
+                tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => spawn_connection(stream, sources.clone()),
+                            Err(error) => log_accept_error(error),
+                        }
+                    }
+                })
+            }
+            #[cfg(unix)]
+            MetricsEndpoint::Unix => {
+                let (listener, created_socket) =
+                    UnixSocket::bind().map_err(|error| Error::Bind(endpoint.clone(), error))?;
+                socket = Some(created_socket);
+                tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => spawn_connection(stream, sources.clone()),
+                            Err(error) => log_accept_error(error),
+                        }
+                    }
+                })
+            }
+            #[cfg(not(unix))]
+            MetricsEndpoint::Unix => {
+                return Err(Error::Bind(
+                    endpoint.clone(),
+                    io::Error::from(io::ErrorKind::Unsupported),
+                ))
+            }
+        };
+        Ok(Server {
+            endpoint,
+            task,
+            #[cfg(unix)]
+            socket,
+        })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+        #[cfg(unix)]
+        if let Some(socket) = self.socket.take() {
+            socket.remove();
+        }
+    }
+}
+
+/// Identifies the unix socket created by the daemon, so that it is never confused with another
+/// file that has since replaced it.
+#[cfg(unix)]
+struct UnixSocket {
+    device: u64,
+    inode: u64,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Bind a unix socket at [`METRICS_SOCKET_PATH`]. A socket that was left behind by a previous
+    /// daemon is replaced, but any other kind of file is left alone.
+    fn bind() -> io::Result<(tokio::net::UnixListener, Self)> {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+
+        match std::fs::symlink_metadata(METRICS_SOCKET_PATH) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                std::fs::remove_file(METRICS_SOCKET_PATH)?
+            }
+            Ok(_) => return Err(io::Error::from(io::ErrorKind::AlreadyExists)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error),
+        }
+        let listener = tokio::net::UnixListener::bind(METRICS_SOCKET_PATH)?;
+        let metadata = std::fs::symlink_metadata(METRICS_SOCKET_PATH)?;
+        let socket = UnixSocket {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        };
+        // Don't rely on the umask of the daemon to keep other users out
+        if let Err(error) =
+            std::fs::set_permissions(METRICS_SOCKET_PATH, PermissionsExt::from_mode(0o600))
+        {
+            socket.remove();
+            return Err(error);
+        }
+        Ok((listener, socket))
+    }
+
+    /// Remove the socket, unless the path no longer refers to it.
+    fn remove(self) {
+        use std::os::unix::fs::MetadataExt;
+
+        match std::fs::symlink_metadata(METRICS_SOCKET_PATH) {
+            Ok(metadata) if metadata.dev() == self.device && metadata.ino() == self.inode => {
+                if let Err(error) = std::fs::remove_file(METRICS_SOCKET_PATH) {
+                    log::warn!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to remove metrics socket")
+                    );
+                }
+            }
+            _ => log::debug!("Metrics socket was replaced, not removing it"),
+        }
+    }
+}
+
+fn log_accept_error(error: io::Error) {
+    log::error!(
+        "{}",
+        error.display_chain_with_msg("Failed to accept metrics connection")
+    );
+}
+
+fn spawn_connection<S>(stream: S, sources: Sources)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(error) = handle_connection(stream, sources).await {
+            log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to serve metrics request")
+            );
+        }
+    });
+}
+
+async fn handle_connection<S>(mut stream: S, sources: Sources) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(&mut stream))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    let response = match parse_request_line(&request) {
+        Some(("GET", "/metrics")) => {
+            let body = encode(&sources.snapshot().await);
+            http_response("200 OK", CONTENT_TYPE, &body)
+        }
+        Some(("GET", _)) => http_response("404 Not Found", "text/plain", "Not found\n"),
+        Some(_) => http_response(
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n",
+        ),
+        None => http_response("400 Bad Request", "text/plain", "Bad request\n"),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read until the end of the request headers. The body, if any, is ignored.
+async fn read_request_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request too large",
+            ));
+        }
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+/// Returns the method and path of an HTTP/1.x request, without any query string.
+fn parse_request_line(request: &str) -> Option<(&str, &str)> {
+    let mut parts = request.lines().next()?.split(' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let version = parts.next()?;
+    if !version.starts_with("HTTP/1.") || parts.next().is_some() {
+        return None;
+    }
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method, path))
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::location::GeoIpLocation;
+    use talpid_types::{
+        net::{Endpoint, TransportProtocol, TunnelEndpoint, TunnelType},
+        tunnel::ActionAfterDisconnect,
+    };
+
+    fn connecting(hostname: &str) -> TunnelState {
+        TunnelState::Connecting {
+            endpoint: TunnelEndpoint {
+                endpoint: Endpoint::new([10, 0, 0, 1], 51820, TransportProtocol::Udp),
+                tunnel_type: TunnelType::Wireguard,
+                quantum_resistant: false,
+                proxy: None,
+                obfuscation: None,
+                entry_endpoint: None,
+                middle_endpoints: vec![],
+                tunnel_interface: None,
+                mtu: None,
+                #[cfg(daita)]
+                daita: false,
+            },
+            location: Some(GeoIpLocation {
+                ipv4: None,
+                ipv6: None,
+                country: "Sweden".to_owned(),
+                city: None,
+                latitude: 0.0,
+                longitude: 0.0,
+                mullvad_exit_ip: true,
+                hostname: Some(hostname.to_owned()),
+                bridge_hostname: None,
+                entry_hostname: None,
+                obfuscator_hostname: None,
+            }),
+            feature_indicators: Default::default(),
+            active_profile: None,
+        }
+    }
+
+    fn connected(hostname: &str) -> TunnelState {
+        match connecting(hostname) {
+            TunnelState::Connecting {
+                endpoint,
+                location,
+                feature_indicators,
+                active_profile,
+            } => TunnelState::Connected {
+                endpoint,
+                location,
+                feature_indicators,
+                active_profile,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn disconnected() -> TunnelState {
+        TunnelState::Disconnected {
+            location: None,
+            #[cfg(not(target_os = "android"))]
+            locked_down: false,
+        }
+    }
+
+    #[test]
+    fn test_tunnel_metrics() {
+        let start = Instant::now();
+        let mut metrics = TunnelMetrics::default();
+
+        metrics.record(&connecting("se-got-wg-001"), start);
+        metrics.record(&connected("se-got-wg-001"), start);
+        metrics.record(&connected("se-got-wg-001"), start + Duration::from_secs(5));
+        assert_eq!(metrics.connected_since, Some(start));
+
+        // The tunnel goes down and the daemon reconnects to another relay
+        metrics.record(
+            &connecting("se-got-wg-002"),
+            start + Duration::from_secs(10),
+        );
+        metrics.record(
+            &connecting("se-got-wg-002"),
+            start + Duration::from_secs(12),
+        );
+        metrics.record(
+            &TunnelState::Disconnecting(ActionAfterDisconnect::Nothing),
+            start + Duration::from_secs(13),
+        );
+        metrics.record(&disconnected(), start + Duration::from_secs(14));
+        metrics.record(
+            &connecting("se-got-wg-001"),
+            start + Duration::from_secs(15),
+        );
+
+        assert_eq!(metrics.state, "connecting");
+        assert_eq!(metrics.connection_attempts, 4);
+        assert_eq!(metrics.connected_since, None);
+        assert_eq!(metrics.connected_total, Duration::from_secs(10));
+        assert_eq!(
+            metrics.relay_reconnects,
+            BTreeMap::from([("se-got-wg-002".to_owned(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let snapshot = Snapshot {
+            tunnel_state: "connected",
+            connection_attempts: 3,
+            connected: Duration::from_millis(90_500),
+            connected_total: Duration::from_secs(3600),
+            relay_reconnects: BTreeMap::from([
+                ("se-got-wg-001".to_owned(), 2),
+                ("se-\"sto\"\\wg".to_owned(), 1),
+            ]),
+            api_requests_succeeded: 10,
+            api_requests_failed: 1,
+            relay_list_age: Some(Duration::from_secs(120)),
+            wireguard_key_age: None,
+        };
+
+        let expected = r#"# HELP mullvad_tunnel_state Current tunnel state. The series of the current state is 1.
+# TYPE mullvad_tunnel_state gauge
+mullvad_tunnel_state{state="disconnected"} 0
+mullvad_tunnel_state{state="connecting"} 0
+mullvad_tunnel_state{state="connected"} 1
+mullvad_tunnel_state{state="disconnecting"} 0
+mullvad_tunnel_state{state="error"} 0
+# HELP mullvad_connection_attempts_total Number of times the daemon has started connecting to a relay.
+# TYPE mullvad_connection_attempts_total counter
+mullvad_connection_attempts_total 3
+# HELP mullvad_connected_seconds Time since the tunnel was connected, or 0 if it is not connected.
+# TYPE mullvad_connected_seconds gauge
+mullvad_connected_seconds 90.5
+# HELP mullvad_connected_seconds_total Total time that the tunnel has been connected since the daemon started.
+# TYPE mullvad_connected_seconds_total counter
+mullvad_connected_seconds_total 3600
+# HELP mullvad_relay_reconnects_total Number of retries and reconnects per relay.
+# TYPE mullvad_relay_reconnects_total counter
+mullvad_relay_reconnects_total{relay="se-\"sto\"\\wg"} 1
+mullvad_relay_reconnects_total{relay="se-got-wg-001"} 2
+# HELP mullvad_api_requests_total Number of completed requests to the Mullvad API.
+# TYPE mullvad_api_requests_total counter
+mullvad_api_requests_total{result="success"} 10
+mullvad_api_requests_total{result="failure"} 1
+# HELP mullvad_relay_list_age_seconds Time since the relay list was fetched.
+# TYPE mullvad_relay_list_age_seconds gauge
+mullvad_relay_list_age_seconds 120
+# HELP mullvad_wireguard_key_age_seconds Time since the WireGuard key of the device was created.
+# TYPE mullvad_wireguard_key_age_seconds gauge
+"#;
+        assert_eq!(encode(&snapshot), expected);
+    }
+
+    #[test]
+    fn test_parse_request_line() {
+        assert_eq!(
+            parse_request_line("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(
+            parse_request_line("GET /metrics?name[]=x HTTP/1.0\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(
+            parse_request_line("POST / HTTP/1.1\r\n\r\n"),
+            Some(("POST", "/"))
+        );
+        assert_eq!(parse_request_line("GET /metrics\r\n\r\n"), None);
+        assert_eq!(parse_request_line("\r\n\r\n"), None);
+    }
+}
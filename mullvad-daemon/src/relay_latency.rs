@@ -0,0 +1,169 @@
+//! Measures the round-trip time to relays so that the relay selector can prefer relays that are
+//! close to the user, network-wise. See [`mullvad_relay_selector::latency`].
+//!
+//! Relays are only probed while the daemon is disconnected and not in lockdown mode, since the
+//! firewall blocks the probes in every other state.
+
+use futures::{select, FutureExt, StreamExt};
+use mullvad_relay_selector::RelaySelector;
+use mullvad_types::relay_list::Relay;
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+use talpid_types::{tunnel::TunnelStateTransition, ErrorExt};
+use tokio::sync::mpsc;
+
+/// How often the relays should be probed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// How often to check whether it is time to probe the relays again.
+const PROBE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long to wait for a reply from a single relay.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum number of relays to probe simultaneously.
+const MAX_CONCURRENT_PINGS: usize = 16;
+
+/// An actor that periodically measures the latency to the relays matching the current
+/// constraints, and feeds the results to the relay selector.
+pub struct RelayLatencyProber {
+    tunnel_state_tx: mpsc::UnboundedSender<TunnelStateTransition>,
+}
+
+/// [RelayLatencyProber] internal task state.
+struct Task {
+    tunnel_state_rx: mpsc::UnboundedReceiver<TunnelStateTransition>,
+    relay_selector: RelaySelector,
+    /// Whether the firewall currently lets probes through. See [may_probe].
+    may_probe: bool,
+    last_probe: Option<Instant>,
+}
+
+impl RelayLatencyProber {
+    pub fn new(relay_selector: RelaySelector) -> Self {
+        let (tunnel_state_tx, tunnel_state_rx) = mpsc::unbounded_channel();
+
+        let task = Task {
+            tunnel_state_rx,
+            relay_selector,
+            may_probe: false,
+            last_probe: None,
+        };
+
+        tokio::task::spawn(task.run());
+
+        RelayLatencyProber { tunnel_state_tx }
+    }
+
+    /// Call when we transition to a new tunnel state.
+    pub fn on_tunnel_state_transition(&mut self, tunnel_state: TunnelStateTransition) {
+        if self.tunnel_state_tx.send(tunnel_state).is_err() {
+            log::error!("Relay latency prober unexpectedly closed");
+        }
+    }
+}
+
+impl Task {
+    async fn run(mut self) {
+        loop {
+            if self.should_probe() {
+                let candidates = self.relay_selector.latency_probe_candidates();
+                if !candidates.is_empty() {
+                    // Discard the measurements if the tunnel state changes, since the probes may
+                    // have been blocked or routed through the tunnel.
+                    select! {
+                        measurements = probe_relays(candidates).fuse() => {
+                            log::debug!("Measured latency to {} relays", measurements.len());
+                            self.relay_selector.update_latencies(measurements);
+                            self.last_probe = Some(Instant::now());
+                        }
+                        tunnel_state = self.tunnel_state_rx.recv().fuse() => {
+                            let Some(tunnel_state) = tunnel_state else {
+                                break;
+                            };
+                            self.may_probe = may_probe(&tunnel_state);
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            select! {
+                _ = tokio::time::sleep(PROBE_CHECK_INTERVAL).fuse() => (),
+                tunnel_state = self.tunnel_state_rx.recv().fuse() => {
+                    let Some(tunnel_state) = tunnel_state else {
+                        break; // All RelayLatencyProber handles dropped.
+                    };
+                    self.may_probe = may_probe(&tunnel_state);
+                }
+            }
+        }
+    }
+
+    fn should_probe(&self) -> bool {
+        self.may_probe
+            && self
+                .last_probe
+                .map(|last_probe| last_probe.elapsed() >= PROBE_INTERVAL)
+                .unwrap_or(true)
+    }
+}
+
+/// Returns whether the firewall lets probes through in `tunnel_state`.
+fn may_probe(tunnel_state: &TunnelStateTransition) -> bool {
+    matches!(
+        tunnel_state,
+        TunnelStateTransition::Disconnected { locked_down: false }
+    )
+}
+
+/// Ping each relay in `relays` once, and return the round-trip times of the relays that replied.
+async fn probe_relays(relays: Vec<Relay>) -> Vec<(String, Duration)> {
+    let client = match Client::new(&Config::default()) {
+        Ok(client) => client,
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to create socket for probing relay latency")
+            );
+            return vec![];
+        }
+    };
+
+    futures::stream::iter(relays.into_iter().enumerate())
+        .map(|(sequence, relay)| {
+            let client = client.clone();
+            async move {
+                let rtt = ping(&client, IpAddr::V4(relay.ipv4_addr_in), sequence as u16)
+                    .await
+                    .inspect_err(|error| {
+                        log::trace!("Failed to measure latency to {}: {error}", relay.hostname)
+                    })
+                    .ok()?;
+                Some((relay.hostname, rtt))
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_PINGS)
+        .filter_map(|measurement| async move { measurement })
+        .collect()
+        .await
+}
+
+async fn ping(
+    client: &Client,
+    address: IpAddr,
+    sequence: u16,
+) -> Result<Duration, surge_ping::SurgeError> {
+    let (_packet, rtt) = client
+        .pinger(address, PingIdentifier(ping_identifier()))
+        .await
+        .timeout(PING_TIMEOUT)
+        .ping(PingSequence(sequence), &[0; 8])
+        .await?;
+    Ok(rtt)
+}
+
+/// Returns an ICMP identifier which is unlikely to collide with pings sent by other processes.
+fn ping_identifier() -> u16 {
+    std::process::id() as u16
+}
@@ -1,6 +1,7 @@
 use futures::TryFutureExt;
 use mullvad_types::{
     custom_list::Error as CustomListError,
+    profile::Error as ProfileError,
     relay_constraints::{RelayConstraints, RelaySettings, WireguardConstraints},
     settings::{DnsState, Settings},
 };
@@ -10,7 +11,7 @@ use std::{
     path::{Path, PathBuf},
 };
 use talpid_core::firewall::is_local_address;
-use talpid_types::ErrorExt;
+use talpid_types::{net::firewall::FirewallException, ErrorExt};
 use tokio::{
     fs,
     io::{self, AsyncWriteExt},
@@ -57,6 +58,10 @@ impl From<Error> for mullvad_management_interface::Status {
                 let custom_list_err = *err.downcast::<CustomListError>().unwrap();
                 handle_custom_list_error(custom_list_err)
             }
+            Error::UpdateFailed(err) if err.downcast_ref::<ProfileError>().is_some() => {
+                let profile_err = *err.downcast::<ProfileError>().unwrap();
+                handle_profile_error(profile_err)
+            }
             Error::SerializeError(..) | Error::ParseError(..) | Error::UpdateFailed(..) => {
                 Status::new(Code::Internal, error.to_string())
             }
@@ -89,11 +94,35 @@ fn handle_custom_list_error(
     }
 }
 
+fn handle_profile_error(profile_err: ProfileError) -> mullvad_management_interface::Status {
+    use mullvad_management_interface::{Code, Status};
+    match profile_err {
+        error @ ProfileError::DuplicateName => Status::new(Code::AlreadyExists, error.to_string()),
+        error @ ProfileError::ProfileNotFound => Status::new(Code::NotFound, error.to_string()),
+        error @ (ProfileError::NameTooLong
+        | ProfileError::EmptyName
+        | ProfileError::InvalidMacAddress(_)) => {
+            Status::new(Code::InvalidArgument, error.to_string())
+        }
+    }
+}
+
+/// Stores the user settings and keeps them in sync with the settings file.
+///
+/// The settings that are saved to disk are the base settings. If a settings profile is active,
+/// its overrides are applied on top of the base settings, and the result is what the rest of the
+/// daemon sees when dereferencing the persister.
 pub struct SettingsPersister {
     settings: Settings,
+    /// Name of the active settings profile, if any.
+    active_profile: Option<String>,
+    /// `settings` with the overrides of the active profile applied.
+    effective_settings: Settings,
     path: PathBuf,
     #[allow(clippy::type_complexity)]
     on_change_listeners: Vec<Box<dyn Fn(&Settings) + Send + Sync>>,
+    #[allow(clippy::type_complexity)]
+    on_base_change_listeners: Vec<Box<dyn Fn(&Settings) + Send + Sync>>,
 }
 
 pub type MadeChanges = bool;
@@ -119,11 +148,24 @@ impl SettingsPersister {
             should_save |= !settings.show_beta_releases;
             settings.show_beta_releases = true;
         }
+        // The exceptions may have been edited by hand, so apply the same checks as when they
+        // are added through the management interface
+        for (exception, error) in FirewallException::retain_valid(&mut settings.firewall_exceptions)
+        {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg(&format!("Dropping firewall exception {exception}"))
+            );
+            should_save = true;
+        }
 
         let mut persister = SettingsPersister {
+            effective_settings: settings.clone(),
             settings,
+            active_profile: None,
             path,
             on_change_listeners: vec![],
+            on_base_change_listeners: vec![],
         };
 
         if should_save {
@@ -229,6 +271,8 @@ impl SettingsPersister {
     /// Resets default settings
     pub async fn reset(&mut self) -> Result<(), Error> {
         self.settings = Self::default_settings();
+        self.active_profile = None;
+        self.update_effective_settings();
         let path = self.path.clone();
         self.save()
             .or_else(|e| async move {
@@ -248,12 +292,72 @@ impl SettingsPersister {
         Ok(())
     }
 
+    /// Returns the settings currently in effect, i.e. including any overrides from the active
+    /// settings profile.
     pub const fn settings(&self) -> &Settings {
-        &self.settings
+        &self.effective_settings
     }
 
     pub fn to_settings(&self) -> Settings {
-        self.settings.clone()
+        self.effective_settings.clone()
+    }
+
+    /// Returns the settings as they are stored on disk, without any profile overrides.
+    pub const fn base_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Returns the name of the active settings profile, if any.
+    pub fn active_profile(&self) -> Option<&str> {
+        self.active_profile.as_deref()
+    }
+
+    /// Activate the settings profile with the given name, or deactivate the current profile if
+    /// `name` is `None`. The active profile is not persisted.
+    ///
+    /// Returns a boolean indicating whether the effective settings changed.
+    pub fn set_active_profile(&mut self, name: Option<String>) -> Result<MadeChanges, Error> {
+        if let Some(name) = &name {
+            if self.settings.profiles.get(name).is_none() {
+                return Err(Error::UpdateFailed(Box::new(ProfileError::ProfileNotFound)));
+            }
+        }
+        if self.active_profile == name {
+            return Ok(false);
+        }
+
+        log::info!(
+            "Active settings profile: {}",
+            name.as_deref().unwrap_or("none")
+        );
+        self.active_profile = name;
+        let changed = self.update_effective_settings();
+        if changed {
+            self.notify_listeners();
+        }
+        Ok(changed)
+    }
+
+    /// Recompute the effective settings from the base settings and the active profile.
+    /// Returns whether the effective settings changed.
+    fn update_effective_settings(&mut self) -> bool {
+        let profile = self
+            .active_profile
+            .as_deref()
+            .and_then(|name| self.settings.profiles.get(name));
+        if profile.is_none() {
+            // The active profile may have been removed
+            self.active_profile = None;
+        }
+
+        let mut effective_settings = self.settings.clone();
+        if let Some(profile) = profile {
+            profile.overrides.apply(&mut effective_settings);
+        }
+
+        let changed = effective_settings != self.effective_settings;
+        self.effective_settings = effective_settings;
+        changed
     }
 
     /// Modifies `Settings::default()` somewhat, e.g. depending on whether a beta version
@@ -350,6 +454,7 @@ impl SettingsPersister {
 
         Self::save_inner(&self.path, &new_settings).await?;
         self.settings = new_settings;
+        self.update_effective_settings();
 
         self.notify_listeners();
 
@@ -359,10 +464,11 @@ impl SettingsPersister {
     /// Return a compact summary of important settings
     pub fn summary(&self) -> SettingsSummary<'_> {
         SettingsSummary {
-            settings: &self.settings,
+            settings: &self.effective_settings,
         }
     }
 
+    /// Register a listener that receives the effective settings whenever they may have changed.
     pub fn register_change_listener(
         &mut self,
         change_listener: impl Fn(&Settings) + Send + Sync + 'static,
@@ -370,8 +476,21 @@ impl SettingsPersister {
         self.on_change_listeners.push(Box::new(change_listener));
     }
 
+    /// Register a listener that receives the base settings, without any profile overrides,
+    /// whenever the settings may have changed.
+    pub fn register_base_change_listener(
+        &mut self,
+        change_listener: impl Fn(&Settings) + Send + Sync + 'static,
+    ) {
+        self.on_base_change_listeners
+            .push(Box::new(change_listener));
+    }
+
     fn notify_listeners(&self) {
         for listener in &self.on_change_listeners {
+            listener(&self.effective_settings);
+        }
+        for listener in &self.on_base_change_listeners {
             listener(&self.settings);
         }
     }
@@ -386,7 +505,7 @@ impl Deref for SettingsPersister {
     type Target = Settings;
 
     fn deref(&self) -> &Self::Target {
-        &self.settings
+        &self.effective_settings
     }
 }
 
@@ -515,7 +634,7 @@ impl SettingsSummary<'_> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use mullvad_types::settings::SettingsVersion;
+    use mullvad_types::{profile::SettingsProfile, settings::SettingsVersion};
 
     #[test]
     #[should_panic]
@@ -668,4 +787,44 @@ mod test {
             "The daemon should block the internet if settings are corrupt"
         );
     }
+
+    /// The overrides of the active profile should only affect the effective settings, and
+    /// deactivating the profile should restore the base settings.
+    #[test]
+    fn test_active_profile_overrides() {
+        let mut settings = Settings::default();
+        let mut profile = SettingsProfile::new("home");
+        profile.overrides.allow_lan = Some(true);
+        settings.profiles.add(profile).unwrap();
+
+        let mut persister = SettingsPersister {
+            effective_settings: settings.clone(),
+            settings,
+            active_profile: None,
+            path: PathBuf::new(),
+            on_change_listeners: vec![],
+            on_base_change_listeners: vec![],
+        };
+        let notified_allow_lan = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let base_allow_lan = notified_allow_lan.clone();
+        persister.register_base_change_listener(move |settings| {
+            base_allow_lan.lock().unwrap().push(settings.allow_lan)
+        });
+
+        assert!(persister
+            .set_active_profile(Some("home".to_owned()))
+            .unwrap());
+        assert!(persister.allow_lan);
+        assert!(!persister.base_settings().allow_lan);
+        // Clients modify the settings they are notified of, so they must never see the overrides
+        assert_eq!(*notified_allow_lan.lock().unwrap(), vec![false]);
+
+        assert!(persister
+            .set_active_profile(Some("work".to_owned()))
+            .is_err());
+        assert_eq!(persister.active_profile(), Some("home"));
+
+        assert!(persister.set_active_profile(None).unwrap());
+        assert!(!persister.allow_lan);
+    }
 }
@@ -171,7 +171,7 @@ pub async fn merge_validate_patch(
     settings: &mut SettingsPersister,
     json_patch: &str,
 ) -> Result<(), Error> {
-    let new_settings = merge_validate_patch_inner(settings, json_patch)?;
+    let new_settings = merge_validate_patch_inner(settings.base_settings(), json_patch)?;
 
     settings
         .update(move |settings| *settings = new_settings)
@@ -0,0 +1,136 @@
+use crate::{dns, settings, Daemon};
+use futures::channel::oneshot;
+use mullvad_types::{
+    profile::{ProfileList, SettingsProfile},
+    settings::Settings,
+    states::TunnelState,
+};
+use talpid_core::tunnel_state_machine::TunnelCommand;
+use talpid_types::{net::NetworkInfo, ErrorExt};
+
+impl Daemon {
+    /// Add a new settings profile.
+    ///
+    /// Returns an error if the name is invalid or not unique.
+    pub async fn create_settings_profile(
+        &mut self,
+        profile: SettingsProfile,
+    ) -> Result<(), settings::Error> {
+        self.settings
+            .try_update(|settings| settings.profiles.add(profile))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a settings profile. If the profile is active, the base settings are restored.
+    pub async fn delete_settings_profile(&mut self, name: String) -> Result<(), settings::Error> {
+        let old_settings = self.settings.to_settings();
+        let was_active = self.settings.active_profile() == Some(name.as_str());
+
+        self.settings
+            .try_update(|settings| settings.profiles.remove(&name))
+            .await?;
+
+        if was_active {
+            self.apply_profile_settings(&old_settings);
+        }
+        Ok(())
+    }
+
+    pub fn get_settings_profiles(&self) -> ProfileList {
+        ProfileList {
+            profiles: self.settings.profiles.to_vec(),
+            active_profile: self.settings.active_profile().map(str::to_owned),
+        }
+    }
+
+    /// Activate the settings profile with the given name, or restore the base settings if `name`
+    /// is `None`.
+    pub fn set_active_settings_profile(
+        &mut self,
+        name: Option<String>,
+    ) -> Result<(), settings::Error> {
+        let old_settings = self.settings.to_settings();
+        let old_profile = self.settings.active_profile().map(str::to_owned);
+
+        self.settings.set_active_profile(name)?;
+
+        if self.settings.active_profile() != old_profile.as_deref() {
+            self.apply_profile_settings(&old_settings);
+        }
+        Ok(())
+    }
+
+    /// Activate the first settings profile with a rule matching the new network.
+    ///
+    /// If no profile matches, a profile that was activated because of its rules is deactivated.
+    /// See [`mullvad_types::profile::ProfileSettings::profile_for_network`].
+    pub(crate) fn handle_network_change(&mut self, network: NetworkInfo) {
+        log::debug!("Network changed: {network:?}");
+
+        let new_profile = self
+            .settings
+            .profiles
+            .profile_for_network(self.settings.active_profile(), &network)
+            .map(str::to_owned);
+
+        if let Err(error) = self.set_active_settings_profile(new_profile) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to activate settings profile")
+            );
+        }
+    }
+
+    /// Apply the effective settings to the tunnel after the active settings profile changed,
+    /// and update the active profile in the current tunnel state.
+    fn apply_profile_settings(&mut self, old_settings: &Settings) {
+        let new_settings = self.settings.to_settings();
+
+        if new_settings.allow_lan != old_settings.allow_lan {
+            self.send_tunnel_command(TunnelCommand::AllowLan(
+                new_settings.allow_lan,
+                oneshot::channel().0,
+            ));
+        }
+        #[cfg(not(target_os = "android"))]
+        if new_settings.block_when_disconnected != old_settings.block_when_disconnected {
+            self.send_tunnel_command(TunnelCommand::BlockWhenDisconnected(
+                new_settings.block_when_disconnected,
+                oneshot::channel().0,
+            ));
+        }
+        if new_settings.tunnel_options.dns_options != old_settings.tunnel_options.dns_options {
+            self.send_tunnel_command(TunnelCommand::Dns(
+                dns::addresses_from_options(&new_settings.tunnel_options.dns_options),
+                oneshot::channel().0,
+            ));
+        }
+
+        let active_profile = self.settings.active_profile().map(str::to_owned);
+        if let TunnelState::Connecting {
+            active_profile: current_profile,
+            ..
+        }
+        | TunnelState::Connected {
+            active_profile: current_profile,
+            ..
+        } = &mut self.tunnel_state
+        {
+            if *current_profile != active_profile {
+                *current_profile = active_profile;
+                self.management_interface
+                    .notifier()
+                    .notify_new_state(self.tunnel_state.clone());
+            }
+        }
+
+        if new_settings.relay_settings != old_settings.relay_settings
+            || new_settings.tunnel_options.generic.enable_ipv6
+                != old_settings.tunnel_options.generic.enable_ipv6
+        {
+            log::info!("Initiating tunnel restart because the settings profile changed");
+            self.reconnect_tunnel();
+        }
+    }
+}
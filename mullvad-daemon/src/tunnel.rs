@@ -63,6 +63,8 @@ struct InnerParametersGenerator {
     account_manager: AccountManagerHandle,
 
     last_generated_relays: Option<LastSelectedRelays>,
+    last_wireguard_parameters: Option<wireguard::TunnelParameters>,
+    last_retry_attempt: u32,
 }
 
 impl ParametersGenerator {
@@ -79,6 +81,8 @@ impl ParametersGenerator {
             account_manager,
 
             last_generated_relays: None,
+            last_wireguard_parameters: None,
+            last_retry_attempt: 0,
         })))
     }
 
@@ -103,6 +107,16 @@ impl ParametersGenerator {
         }
     }
 
+    /// Gets the retry attempt that the last tunnel parameters were generated for.
+    pub async fn get_last_retry_attempt(&self) -> u32 {
+        self.0.lock().await.last_retry_attempt
+    }
+
+    /// Gets the last generated tunnel parameters, if they were for a WireGuard tunnel.
+    pub async fn get_last_wireguard_parameters(&self) -> Option<wireguard::TunnelParameters> {
+        self.0.lock().await.last_wireguard_parameters.clone()
+    }
+
     /// Gets the location associated with the last generated tunnel parameters.
     pub async fn get_last_location(&self) -> Option<GeoIpLocation> {
         let inner = self.0.lock().await;
@@ -162,6 +176,7 @@ impl InnerParametersGenerator {
         retry_attempt: u32,
         ipv6: bool,
     ) -> Result<TunnelParameters, Error> {
+        self.last_retry_attempt = retry_attempt;
         let data = self.device().await?;
         let selected_relay = self
             .relay_selector
@@ -200,7 +215,7 @@ impl InnerParametersGenerator {
 
                 let (wg_entry, wg_exit) = match inner {
                     WireguardConfig::Singlehop { exit } => (None, exit),
-                    WireguardConfig::Multihop { exit, entry } => (Some(entry), exit),
+                    WireguardConfig::Multihop { exit, entry, .. } => (Some(entry), exit),
                 };
                 let server_override = {
                     let first_relay = wg_entry.as_ref().unwrap_or(&wg_exit);
@@ -272,6 +287,7 @@ impl InnerParametersGenerator {
             connection: wireguard::ConnectionConfig {
                 tunnel,
                 peer: endpoint.peer,
+                middle_peers: endpoint.middle_peers,
                 exit_peer: endpoint.exit_peer,
                 ipv4_gateway: endpoint.ipv4_gateway,
                 ipv6_gateway: Some(endpoint.ipv6_gateway),
@@ -304,9 +320,12 @@ impl TunnelParametersGenerator for ParametersGenerator {
         let generator = self.0.clone();
         Box::pin(async move {
             let mut inner = generator.lock().await;
-            inner
-                .generate(retry_attempt, ipv6)
-                .await
+            let result = inner.generate(retry_attempt, ipv6).await;
+            inner.last_wireguard_parameters = match &result {
+                Ok(TunnelParameters::Wireguard(parameters)) => Some(parameters.clone()),
+                _ => None,
+            };
+            result
                 .inspect_err(|error| {
                     log::error!(
                         "{}",
@@ -14,7 +14,7 @@ use std::{
 };
 
 #[cfg(target_os = "linux")]
-use talpid_types::cgroup::{find_net_cls_mount, SPLIT_TUNNEL_CGROUP_NAME};
+use talpid_types::cgroup::{find_exclusion_hierarchy, SPLIT_TUNNEL_CGROUP_NAME};
 
 #[cfg(target_os = "linux")]
 const PROGRAM_NAME: &str = "mullvad-exclude";
@@ -40,11 +40,11 @@ enum Error {
     #[error("An argument contains interior nul bytes")]
     ArgumentNul(#[source] NulError),
 
-    #[error("Failed to find net_cls controller")]
-    FindNetClsController(#[source] io::Error),
+    #[error("Failed to find a net_cls or cgroup v2 hierarchy")]
+    FindCgroupHierarchy(#[source] io::Error),
 
-    #[error("No net_cls controller")]
-    NoNetClsController,
+    #[error("No net_cls or cgroup v2 hierarchy")]
+    NoCgroupHierarchy,
 }
 
 fn main() {
@@ -85,9 +85,10 @@ fn run() -> Result<Infallible, Error> {
         .collect::<Result<Vec<CString>, NulError>>()
         .map_err(Error::ArgumentNul)?;
 
-    let cgroup_dir = find_net_cls_mount()
-        .map_err(Error::FindNetClsController)?
-        .ok_or(Error::NoNetClsController)?;
+    // Use the same hierarchy as the daemon: net_cls if it is mounted, otherwise cgroup v2
+    let (_mechanism, cgroup_dir) = find_exclusion_hierarchy()
+        .map_err(Error::FindCgroupHierarchy)?
+        .ok_or(Error::NoCgroupHierarchy)?;
 
     let procs_path = cgroup_dir
         .join(SPLIT_TUNNEL_CGROUP_NAME)
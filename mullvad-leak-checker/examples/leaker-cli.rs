@@ -12,6 +12,12 @@ pub enum LeakMethod {
     /// Check for leaks by binding to a non-tunnel interface and probing for reachable nodes.
     Traceroute(#[clap(flatten)] TracerouteOpt),
 
+    /// Check for DNS leaks by sending queries on a non-tunnel interface.
+    Dns(#[clap(flatten)] mullvad_leak_checker::dns::DnsLeakOpt),
+
+    /// Check for IPv6 leaks by trying to reach hosts over IPv6 on non-tunnel interfaces.
+    Ipv6(#[clap(flatten)] mullvad_leak_checker::ipv6::Ipv6LeakOpt),
+
     /// Ask `am.i.mullvad.net` whether you are leaking.
     #[cfg(feature = "am-i-mullvad")]
     AmIMullvad(#[clap(flatten)] mullvad_leak_checker::am_i_mullvad::AmIMullvadOpt),
@@ -28,6 +34,8 @@ async fn main() -> anyhow::Result<()> {
 
     let leak_status = match &opt.method {
         LeakMethod::Traceroute(opt) => mullvad_leak_checker::traceroute::run_leak_test(opt).await,
+        LeakMethod::Dns(opt) => mullvad_leak_checker::dns::run_leak_test(opt).await,
+        LeakMethod::Ipv6(opt) => mullvad_leak_checker::ipv6::run_leak_test(opt).await,
         #[cfg(feature = "am-i-mullvad")]
         LeakMethod::AmIMullvad(opt) => mullvad_leak_checker::am_i_mullvad::run_leak_test(opt).await,
     };
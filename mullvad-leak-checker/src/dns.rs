@@ -0,0 +1,319 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query},
+    rr::{Name, RecordType},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::time::timeout;
+
+use crate::{
+    util::{bind_socket_to_interface, Ip},
+    Interface, LeakInfo, LeakStatus,
+};
+
+/// Capturing of DNS queries sent by the system resolver.
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct DnsLeakOpt {
+    /// Non-tunnel interface to send the DNS queries on
+    #[clap(short, long)]
+    pub interface: Interface,
+
+    /// Resolver to query directly on the interface. May be given multiple times. Defaults to a
+    /// few well-known public resolvers.
+    #[clap(short, long = "resolver", default_values_t = DEFAULT_RESOLVERS)]
+    pub resolvers: Vec<IpAddr>,
+
+    /// Port that the resolvers listen on
+    #[clap(long, default_value_t = DNS_PORT)]
+    pub port: u16,
+
+    /// Also look up a unique name using the system resolver, and watch the interface for the
+    /// query. This opens a packet socket, which requires root.
+    #[clap(long)]
+    #[cfg(target_os = "linux")]
+    pub system_resolver: bool,
+
+    /// Resolver that the system resolver is expected to query outside the tunnel, such as a
+    /// custom DNS server on the local network. Queries to it are not counted as leaks. May be
+    /// given multiple times.
+    #[clap(long = "allowed-resolver")]
+    #[cfg(target_os = "linux")]
+    pub allowed_resolvers: Vec<IpAddr>,
+}
+
+pub const DNS_PORT: u16 = 53;
+
+/// Resolvers to query directly if none are given on the command line.
+pub const DEFAULT_RESOLVERS: [IpAddr; 4] = [
+    IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+    IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+];
+
+/// Domain under which the uniquely labelled names are looked up.
+const QUERY_DOMAIN: &str = "dns-leak.am.i.mullvad.net.";
+
+/// Time to wait for a response, or for a query to show up on the interface.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// [try_run_leak_test], but on an error, assume we aren't leaking.
+pub async fn run_leak_test(opt: &DnsLeakOpt) -> LeakStatus {
+    try_run_leak_test(opt)
+        .await
+        .inspect_err(|e| log::debug!("Leak test errored, assuming no leak. {e:?}"))
+        .unwrap_or(LeakStatus::NoLeak)
+}
+
+/// Run a DNS leak test.
+///
+/// A query for a uniquely labelled name is sent straight to each resolver from a socket bound to
+/// `interface`. If any resolver answers, the query was not blocked by the firewall.
+///
+/// On Linux, the test can additionally look up a unique name using the system resolver while
+/// capturing the packets that leave through `interface`. If a plaintext query for the name is seen
+/// there, the system resolver is sending queries outside the tunnel. This catches misconfigured
+/// resolvers that the firewall lets through, e.g. on the local network. Resolvers that are meant
+/// to be reached outside the tunnel should be listed in `allowed_resolvers`.
+///
+/// Queries are only sent to the given resolvers, so the test can be pointed at a local stand-in,
+/// such as a resolver in another network namespace. If no resolvers are given, only the system
+/// resolver is tested, if enabled.
+pub async fn try_run_leak_test(opt: &DnsLeakOpt) -> anyhow::Result<LeakStatus> {
+    let resolvers = &opt.resolvers;
+
+    let queries = resolvers
+        .iter()
+        .map(|&resolver| query_resolver(&opt.interface, SocketAddr::new(resolver, opt.port)));
+    let results = futures::future::join_all(queries).await;
+
+    let mut leaking_resolvers = vec![];
+    let mut errors = vec![];
+    for (&resolver, result) in resolvers.iter().zip(results) {
+        match result {
+            Ok(true) => leaking_resolvers.push(resolver),
+            Ok(false) => (),
+            Err(error) => {
+                log::debug!("Failed to query {resolver}: {error:#}");
+                errors.push(error);
+            }
+        }
+    }
+    // Some resolvers may be unusable, e.g. IPv6 ones on an interface without IPv6, but if all of
+    // them are, nothing was tested
+    if errors.len() == resolvers.len() {
+        if let Some(error) = errors.pop() {
+            return Err(error);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if opt.system_resolver {
+        for resolver in linux::capture_system_resolver_queries(&opt.interface).await? {
+            if opt.allowed_resolvers.contains(&resolver) {
+                log::debug!("Ignoring query to allowed resolver {resolver}");
+            } else if !leaking_resolvers.contains(&resolver) {
+                leaking_resolvers.push(resolver);
+            }
+        }
+    }
+
+    if leaking_resolvers.is_empty() {
+        return Ok(LeakStatus::NoLeak);
+    }
+    Ok(LeakStatus::LeakDetected(LeakInfo::DnsLeak {
+        resolvers: leaking_resolvers,
+        interface: opt.interface.clone(),
+    }))
+}
+
+/// Send a query for a unique name to `resolver` from a socket bound to `interface`, and return
+/// whether the resolver answered.
+pub(crate) async fn query_resolver(
+    interface: &Interface,
+    resolver: SocketAddr,
+) -> anyhow::Result<bool> {
+    let (domain, ip_version) = match resolver {
+        SocketAddr::V4(..) => (Domain::IPV4, Ip::v4()),
+        SocketAddr::V6(..) => (Domain::IPV6, Ip::v6()),
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))
+        .context("Failed to open UDP socket")?;
+    bind_socket_to_interface(&socket, interface, ip_version)
+        .context("Failed to bind UDP socket to interface")?;
+    socket
+        .set_nonblocking(true)
+        .context("Failed to set UDP socket to nonblocking")?;
+    let socket = tokio::net::UdpSocket::from_std(std::net::UdpSocket::from(socket))
+        .context("Failed to register UDP socket")?;
+
+    let probe = Probe::new();
+    log::debug!("Sending DNS query for {} to {resolver}", probe.name());
+
+    // The firewall may reject the packet outright, which is exactly what should happen
+    if let Err(error) = socket.send_to(&probe.query()?, resolver).await {
+        log::debug!("Failed to send DNS query to {resolver}: {error}");
+        return Ok(false);
+    }
+
+    let wait_for_response = async {
+        let mut buf = [0u8; 512];
+        loop {
+            let (len, source) = socket.recv_from(&mut buf).await?;
+            if source == resolver && probe.is_response(&buf[..len]) {
+                return io::Result::Ok(());
+            }
+        }
+    };
+
+    match timeout(RESPONSE_TIMEOUT, wait_for_response).await {
+        Ok(Ok(())) => {
+            log::debug!("{resolver} answered a DNS query sent outside the tunnel");
+            Ok(true)
+        }
+        Ok(Err(error)) => {
+            log::debug!("Failed to receive DNS response from {resolver}: {error}");
+            Ok(false)
+        }
+        Err(_timeout) => Ok(false),
+    }
+}
+
+/// A DNS query for a name that has not been looked up before.
+struct Probe {
+    id: u16,
+    label: String,
+}
+
+impl Probe {
+    fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Probe {
+            id: nanos as u16,
+            label: format!("{:x}-{nanos:x}", std::process::id()),
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("{}.{QUERY_DOMAIN}", self.label)
+    }
+
+    /// Encode the query in the DNS wire format.
+    fn query(&self) -> anyhow::Result<Vec<u8>> {
+        let name = Name::from_ascii(self.name()).context("Invalid query name")?;
+        let mut message = Message::new();
+        message
+            .set_id(self.id)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(true)
+            .add_query(Query::query(name, RecordType::A));
+        message.to_vec().context("Failed to encode DNS query")
+    }
+
+    /// Return whether `packet` is a response to this query. Any response code counts, since the
+    /// name does not exist.
+    fn is_response(&self, packet: &[u8]) -> bool {
+        Message::from_vec(packet).is_ok_and(|message| {
+            message.id() == self.id && message.message_type() == MessageType::Response
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_probe_response() {
+        let probe = Probe::new();
+        let query = probe.query().unwrap();
+        assert!(!probe.is_response(&query));
+
+        let mut response = Message::from_vec(&query).unwrap();
+        response.set_message_type(MessageType::Response);
+        assert!(probe.is_response(&response.to_vec().unwrap()));
+
+        response.set_id(probe.id.wrapping_add(1));
+        assert!(!probe.is_response(&response.to_vec().unwrap()));
+    }
+
+    /// Only the command line falls back to the public resolvers.
+    #[test]
+    fn test_default_resolvers() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            opt: DnsLeakOpt,
+        }
+
+        let cli = <Cli as clap::Parser>::parse_from(["leak-check", "--interface", "lo"]);
+        assert_eq!(cli.opt.resolvers, DEFAULT_RESOLVERS);
+    }
+
+    /// Without resolvers or the system resolver, nothing is queried.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_no_resolvers() {
+        let opt = DnsLeakOpt {
+            interface: Interface::Name("lo".to_owned()),
+            resolvers: vec![],
+            port: DNS_PORT,
+            system_resolver: false,
+            allowed_resolvers: vec![],
+        };
+
+        let status = try_run_leak_test(&opt).await.unwrap();
+        assert!(matches!(status, LeakStatus::NoLeak), "{status:?}");
+    }
+
+    /// Answer every query with an empty response, like a resolver that knows no names.
+    #[cfg(target_os = "linux")]
+    async fn spawn_stand_in_resolver() -> SocketAddr {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, source) = socket.recv_from(&mut buf).await.unwrap();
+                let mut message = Message::from_vec(&buf[..len]).unwrap();
+                message.set_message_type(MessageType::Response);
+                let _ = socket.send_to(&message.to_vec().unwrap(), source).await;
+            }
+        });
+        address
+    }
+
+    /// Nothing blocks the loopback interface, so queries to a stand-in resolver on it leak.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_leak_to_stand_in_resolver() {
+        let resolver = spawn_stand_in_resolver().await;
+        let opt = DnsLeakOpt {
+            interface: Interface::Name("lo".to_owned()),
+            resolvers: vec![resolver.ip()],
+            port: resolver.port(),
+            system_resolver: false,
+            allowed_resolvers: vec![],
+        };
+
+        let status = try_run_leak_test(&opt).await.unwrap();
+        let LeakStatus::LeakDetected(LeakInfo::DnsLeak { resolvers, .. }) = status else {
+            panic!("expected a DNS leak, got {status:?}");
+        };
+        assert_eq!(resolvers, vec![resolver.ip()]);
+    }
+}
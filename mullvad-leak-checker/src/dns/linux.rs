@@ -0,0 +1,196 @@
+use std::{
+    convert::Infallible,
+    io::{self, Read},
+    mem,
+    net::IpAddr,
+    os::fd::AsRawFd,
+};
+
+use anyhow::Context;
+use futures::{future::pending, select, FutureExt};
+use nix::{libc, net::if_::if_nametoindex};
+use pnet_packet::{
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::Ipv4Packet,
+    ipv6::Ipv6Packet,
+    tcp::TcpPacket,
+    udp::UdpPacket,
+    Packet,
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{io::unix::AsyncFd, time::sleep};
+
+use super::{Probe, DNS_PORT, RESPONSE_TIMEOUT};
+use crate::Interface;
+
+/// Look up a unique name using the system resolver while capturing the packets sent on
+/// `interface`. Returns the destinations of any plaintext DNS queries for the name.
+pub async fn capture_system_resolver_queries(interface: &Interface) -> anyhow::Result<Vec<IpAddr>> {
+    let Interface::Name(interface) = interface;
+    let socket = open_capture_socket(interface)?;
+
+    let probe = Probe::new();
+    let name = probe.name();
+    log::debug!("Looking up {name} using the system resolver");
+
+    let mut resolvers = vec![];
+    let lookup = async {
+        // Only where the query goes matters, not the result
+        let _ = tokio::net::lookup_host((name.as_str(), 0)).await;
+        pending::<()>().await
+    };
+    select! {
+        Err(error) = capture_queries(&socket, probe.label.as_bytes(), &mut resolvers).fuse() => {
+            return Err(error).context("Failed to capture packets");
+        }
+        _ = lookup.fuse() => (),
+        _ = sleep(RESPONSE_TIMEOUT).fuse() => (),
+    }
+
+    for resolver in &resolvers {
+        log::debug!("The system resolver sent a query to {resolver} outside the tunnel");
+    }
+    Ok(resolvers)
+}
+
+/// Open a packet socket that receives every IP packet sent or received on `interface`.
+fn open_capture_socket(interface: &str) -> anyhow::Result<AsyncFd<Socket>> {
+    let protocol = (libc::ETH_P_ALL as u16).to_be();
+
+    // Datagram packet sockets strip the link-layer header, so every packet starts with an IP header
+    let socket = Socket::new(
+        Domain::PACKET,
+        Type::DGRAM,
+        Some(Protocol::from(i32::from(protocol))),
+    )
+    .context("Failed to open packet socket")?;
+
+    let index = if_nametoindex(interface).context("Failed to get interface index")?;
+
+    // SAFETY: sockaddr_ll is a plain C struct, for which all zeroes is a valid value
+    let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_protocol = protocol;
+    address.sll_ifindex = index as i32;
+
+    // SAFETY: `address` is a valid sockaddr_ll, and the length passed is its size
+    let result = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&address as *const libc::sockaddr_ll).cast(),
+            mem::size_of_val(&address) as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .context("Failed to bind packet socket to interface");
+    }
+
+    socket
+        .set_nonblocking(true)
+        .context("Failed to set packet socket to nonblocking")?;
+    AsyncFd::new(socket).context("Failed to register packet socket")
+}
+
+/// Read packets from `socket` and record the destination of every DNS query containing `label`.
+async fn capture_queries(
+    socket: &AsyncFd<Socket>,
+    label: &[u8],
+    resolvers: &mut Vec<IpAddr>,
+) -> io::Result<Infallible> {
+    let mut buf = vec![0u8; usize::from(u16::MAX)];
+    loop {
+        let mut guard = socket.readable().await?;
+        let Ok(result) = guard.try_io(|socket| {
+            let mut socket = socket.get_ref();
+            socket.read(&mut buf)
+        }) else {
+            continue;
+        };
+
+        if let Some(destination) = dns_query_destination(&buf[..result?], label) {
+            if !resolvers.contains(&destination) {
+                resolvers.push(destination);
+            }
+        }
+    }
+}
+
+/// Return the destination of `packet` if it is a plaintext DNS query containing `label`.
+fn dns_query_destination(packet: &[u8], label: &[u8]) -> Option<IpAddr> {
+    match packet.first()? >> 4 {
+        4 => {
+            let packet = Ipv4Packet::new(packet)?;
+            is_dns_query(packet.get_next_level_protocol(), packet.payload(), label)
+                .then_some(IpAddr::V4(packet.get_destination()))
+        }
+        6 => {
+            let packet = Ipv6Packet::new(packet)?;
+            is_dns_query(packet.get_next_header(), packet.payload(), label)
+                .then_some(IpAddr::V6(packet.get_destination()))
+        }
+        _ => None,
+    }
+}
+
+fn is_dns_query(protocol: IpNextHeaderProtocol, payload: &[u8], label: &[u8]) -> bool {
+    // Some resolvers randomize the case of the name
+    let contains_label = |payload: &[u8]| {
+        payload
+            .windows(label.len())
+            .any(|window| window.eq_ignore_ascii_case(label))
+    };
+    match protocol {
+        IpNextHeaderProtocols::Udp => UdpPacket::new(payload).is_some_and(|packet| {
+            packet.get_destination() == DNS_PORT && contains_label(packet.payload())
+        }),
+        IpNextHeaderProtocols::Tcp => TcpPacket::new(payload).is_some_and(|packet| {
+            packet.get_destination() == DNS_PORT && contains_label(packet.payload())
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pnet_packet::{ipv4::MutableIpv4Packet, udp::MutableUdpPacket};
+    use std::net::Ipv4Addr;
+
+    fn udp_packet(destination_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut udp = MutableUdpPacket::owned(vec![0u8; 8 + payload.len()]).unwrap();
+        udp.set_source(40000);
+        udp.set_destination(destination_port);
+        udp.set_length(udp.packet().len() as u16);
+        udp.set_payload(payload);
+
+        let mut ip = MutableIpv4Packet::owned(vec![0u8; 20 + udp.packet().len()]).unwrap();
+        ip.set_version(4);
+        ip.set_header_length(5);
+        ip.set_total_length(ip.packet().len() as u16);
+        ip.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+        ip.set_destination(Ipv4Addr::new(192, 168, 1, 1));
+        ip.set_payload(udp.packet());
+        ip.packet().to_vec()
+    }
+
+    #[test]
+    fn test_dns_query_destination() {
+        let probe = Probe::new();
+        let query = probe.query().unwrap();
+        let label = probe.label.as_bytes();
+
+        assert_eq!(
+            dns_query_destination(&udp_packet(DNS_PORT, &query), label),
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)))
+        );
+        assert_eq!(
+            dns_query_destination(&udp_packet(5353, &query), label),
+            None
+        );
+        assert_eq!(
+            dns_query_destination(&udp_packet(DNS_PORT, b"unrelated"), label),
+            None
+        );
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Context;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::time::timeout;
+
+use crate::{
+    dns::{query_resolver, DNS_PORT},
+    util::{bind_socket_to_interface, Ip},
+    Interface, LeakInfo, LeakStatus,
+};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Ipv6LeakOpt {
+    /// Non-tunnel interface to send the probes on. May be given multiple times. Defaults to every
+    /// interface that has a global IPv6 address, except loopback and point-to-point interfaces.
+    #[clap(short, long = "interface")]
+    pub interfaces: Vec<Interface>,
+
+    /// Interface to never send probes on, such as the tunnel interface. May be given multiple
+    /// times.
+    #[clap(long = "exclude-interface")]
+    pub exclude_interfaces: Vec<String>,
+
+    /// Destination of the probes. May be given multiple times. Defaults to a few well-known public
+    /// DNS resolvers.
+    #[clap(short, long = "destination")]
+    pub destinations: Vec<Ipv6Addr>,
+
+    /// Port to send the UDP and TCP probes to. The UDP probe is a DNS query.
+    #[clap(long, default_value_t = DNS_PORT)]
+    pub port: u16,
+}
+
+/// Destinations to probe if none are specified. These answer DNS queries over both UDP and TCP,
+/// and reply to pings.
+pub const DEFAULT_DESTINATIONS: [Ipv6Addr; 3] = [
+    Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111),
+    Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888),
+    Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0xfe),
+];
+
+/// Time to wait for a connection to be established, or for a ping to be answered.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// [try_run_leak_test], but on an error, assume we aren't leaking.
+pub async fn run_leak_test(opt: &Ipv6LeakOpt) -> LeakStatus {
+    try_run_leak_test(opt)
+        .await
+        .inspect_err(|e| log::debug!("Leak test errored, assuming no leak. {e:?}"))
+        .unwrap_or(LeakStatus::NoLeak)
+}
+
+/// Run an IPv6 leak test.
+///
+/// For every interface with a global IPv6 address, sockets are bound to the interface and used to
+/// reach each destination over UDP, TCP and, on unix, ICMPv6. Getting any kind of answer means the
+/// firewall let IPv6 traffic leave outside the tunnel. This matters most when IPv6 is disabled in
+/// the tunnel, since nothing but the firewall then keeps IPv6 traffic off the physical interfaces.
+///
+/// The ICMPv6 probe needs a raw socket, and therefore root privileges. Without them, only the UDP
+/// and TCP probes are used.
+pub async fn try_run_leak_test(opt: &Ipv6LeakOpt) -> anyhow::Result<LeakStatus> {
+    let interfaces = if opt.interfaces.is_empty() {
+        find_ipv6_interfaces()?
+    } else {
+        opt.interfaces.clone()
+    };
+    let interfaces: Vec<_> = interfaces
+        .into_iter()
+        .filter(|interface| {
+            !matches!(interface, Interface::Name(name) if opt.exclude_interfaces.contains(name))
+        })
+        .collect();
+    if interfaces.is_empty() {
+        log::debug!("No interfaces with a global IPv6 address to send probes on");
+        return Ok(LeakStatus::NoLeak);
+    }
+
+    let destinations = if opt.destinations.is_empty() {
+        DEFAULT_DESTINATIONS.to_vec()
+    } else {
+        opt.destinations.clone()
+    };
+
+    let mut errors = vec![];
+    for interface in &interfaces {
+        let probes = destinations
+            .iter()
+            .map(|&destination| probe_destination(interface, destination, opt.port));
+        let results = futures::future::join_all(probes).await;
+
+        let mut reachable_nodes = vec![];
+        for (&destination, result) in destinations.iter().zip(results) {
+            match result {
+                Ok(true) => reachable_nodes.push(destination),
+                Ok(false) => (),
+                Err(error) => {
+                    log::debug!("Failed to probe {destination} on {interface:?}: {error:#}");
+                    errors.push(error);
+                }
+            }
+        }
+
+        if !reachable_nodes.is_empty() {
+            return Ok(LeakStatus::LeakDetected(LeakInfo::Ipv6Leak {
+                reachable_nodes,
+                interface: interface.clone(),
+            }));
+        }
+    }
+
+    // If not a single destination could be probed on any interface, nothing was tested
+    if errors.len() == interfaces.len() * destinations.len() {
+        if let Some(error) = errors.pop() {
+            return Err(error);
+        }
+    }
+    Ok(LeakStatus::NoLeak)
+}
+
+/// Try to reach `destination` over every protocol, and return whether any attempt got an answer.
+/// Only fails if every protocol failed.
+async fn probe_destination(
+    interface: &Interface,
+    destination: Ipv6Addr,
+    port: u16,
+) -> anyhow::Result<bool> {
+    let address = SocketAddr::new(IpAddr::V6(destination), port);
+    let icmp = async {
+        #[cfg(unix)]
+        return Some(ping(interface, destination).await);
+        // Binding a raw ICMPv6 socket to an interface is not supported on Windows
+        #[cfg(not(unix))]
+        None
+    };
+    let (udp, tcp, icmp) = futures::join!(
+        query_resolver(interface, address),
+        connect_tcp(interface, address),
+        icmp,
+    );
+    let results: Vec<_> = [("UDP", Some(udp)), ("TCP", Some(tcp)), ("ICMPv6", icmp)]
+        .into_iter()
+        .filter_map(|(protocol, result)| Some((protocol, result?)))
+        .collect();
+
+    let attempts = results.len();
+    let mut reachable = false;
+    let mut errors = vec![];
+    for (protocol, result) in results {
+        match result {
+            Ok(true) => {
+                log::debug!("Reached {destination} over {protocol} on {interface:?}");
+                reachable = true;
+            }
+            Ok(false) => (),
+            Err(error) => {
+                log::debug!("Failed to probe {destination} over {protocol}: {error:#}");
+                errors.push(error);
+            }
+        }
+    }
+
+    if errors.len() == attempts {
+        if let Some(error) = errors.pop() {
+            return Err(error);
+        }
+    }
+    Ok(reachable)
+}
+
+/// Try to open a TCP connection to `destination` from a socket bound to `interface`.
+async fn connect_tcp(interface: &Interface, destination: SocketAddr) -> anyhow::Result<bool> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))
+        .context("Failed to open TCP socket")?;
+    bind_socket_to_interface(&socket, interface, Ip::v6())
+        .context("Failed to bind TCP socket to interface")?;
+    socket
+        .set_nonblocking(true)
+        .context("Failed to set TCP socket to nonblocking")?;
+    let socket = tokio::net::TcpSocket::from_std_stream(std::net::TcpStream::from(socket));
+
+    match timeout(RESPONSE_TIMEOUT, socket.connect(destination)).await {
+        Ok(Ok(_stream)) => Ok(true),
+        Ok(Err(error)) => {
+            // Most likely rejected by the firewall, or no route
+            log::debug!("Failed to connect to {destination}: {error}");
+            Ok(false)
+        }
+        Err(_timeout) => Ok(false),
+    }
+}
+
+/// Send an ICMPv6 echo request to `destination` from a raw socket bound to `interface`, and
+/// return whether it was answered.
+#[cfg(unix)]
+async fn ping(interface: &Interface, destination: Ipv6Addr) -> anyhow::Result<bool> {
+    use pnet_packet::{
+        icmpv6::{
+            echo_request::{EchoRequest, MutableEchoRequestPacket},
+            Icmpv6Code, Icmpv6Types,
+        },
+        Packet,
+    };
+
+    const ECHO_REQUEST_HEADER_LEN: usize = 8;
+    const PAYLOAD: &[u8] = b"mullvad-leak-checker";
+
+    let socket = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))
+        .context("Failed to open ICMPv6 socket")?;
+    bind_socket_to_interface(&socket, interface, Ip::v6())
+        .context("Failed to bind ICMPv6 socket to interface")?;
+    socket
+        .set_nonblocking(true)
+        .context("Failed to set ICMPv6 socket to nonblocking")?;
+    // HACK: Wrap the raw socket in a tokio::net::UdpSocket to be able to use it async
+    let socket = tokio::net::UdpSocket::from_std(std::net::UdpSocket::from(socket))
+        .context("Failed to register ICMPv6 socket")?;
+
+    let identifier = std::process::id() as u16;
+    let mut packet =
+        MutableEchoRequestPacket::owned(vec![0u8; ECHO_REQUEST_HEADER_LEN + PAYLOAD.len()])
+            .unwrap();
+    // The kernel fills in the checksum of ICMPv6 packets sent on raw sockets
+    packet.populate(&EchoRequest {
+        icmpv6_type: Icmpv6Types::EchoRequest,
+        icmpv6_code: Icmpv6Code(0),
+        checksum: 0,
+        identifier,
+        sequence_number: 1,
+        payload: PAYLOAD.to_vec(),
+    });
+
+    if let Err(error) = socket.send_to(packet.packet(), (destination, 0)).await {
+        log::debug!("Failed to send ICMPv6 echo request to {destination}: {error}");
+        return Ok(false);
+    }
+
+    let wait_for_reply = async {
+        let mut buf = [0u8; 1024];
+        loop {
+            let (len, source) = socket.recv_from(&mut buf).await?;
+            if source.ip() == IpAddr::V6(destination) && is_echo_reply(&buf[..len], identifier) {
+                return std::io::Result::Ok(());
+            }
+        }
+    };
+
+    match timeout(RESPONSE_TIMEOUT, wait_for_reply).await {
+        Ok(Ok(())) => Ok(true),
+        Ok(Err(error)) => {
+            log::debug!("Failed to receive ICMPv6 echo reply from {destination}: {error}");
+            Ok(false)
+        }
+        Err(_timeout) => Ok(false),
+    }
+}
+
+/// Return whether `packet` is an ICMPv6 echo reply with the given identifier.
+#[cfg(unix)]
+fn is_echo_reply(packet: &[u8], identifier: u16) -> bool {
+    use pnet_packet::icmpv6::{echo_reply::EchoReplyPacket, Icmpv6Types};
+
+    EchoReplyPacket::new(packet).is_some_and(|packet| {
+        packet.get_icmpv6_type() == Icmpv6Types::EchoReply && packet.get_identifier() == identifier
+    })
+}
+
+/// Find every interface that is up and has a global IPv6 address. Loopback and point-to-point
+/// interfaces are skipped, since tunnel interfaces are point-to-point.
+#[cfg(unix)]
+fn find_ipv6_interfaces() -> anyhow::Result<Vec<Interface>> {
+    use nix::net::if_::InterfaceFlags;
+
+    let mut names: Vec<String> = vec![];
+    for address in nix::ifaddrs::getifaddrs().context("Failed to get interface addresses")? {
+        if !address.flags.contains(InterfaceFlags::IFF_UP)
+            || address
+                .flags
+                .intersects(InterfaceFlags::IFF_LOOPBACK | InterfaceFlags::IFF_POINTOPOINT)
+        {
+            continue;
+        }
+        let Some(ip) = address
+            .address
+            .as_ref()
+            .and_then(|address| address.as_sockaddr_in6())
+            .map(|address| address.ip())
+        else {
+            continue;
+        };
+        if is_global_unicast(&ip) && !names.contains(&address.interface_name) {
+            log::debug!("Found IPv6 address {ip} on {}", address.interface_name);
+            names.push(address.interface_name);
+        }
+    }
+    Ok(names.into_iter().map(Interface::Name).collect())
+}
+
+#[cfg(target_os = "windows")]
+fn find_ipv6_interfaces() -> anyhow::Result<Vec<Interface>> {
+    anyhow::bail!("Finding interfaces with IPv6 addresses is not supported, specify an interface")
+}
+
+/// Return whether `ip` may be used to reach the internet, i.e. is not a loopback, link-local,
+/// unique local or multicast address.
+#[cfg(unix)]
+fn is_global_unicast(ip: &Ipv6Addr) -> bool {
+    let is_link_local = ip.segments()[0] & 0xffc0 == 0xfe80;
+    let is_unique_local = ip.segments()[0] & 0xfe00 == 0xfc00;
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || is_link_local
+        || is_unique_local)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_global_unicast() {
+        let global: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert!(is_global_unicast(&global));
+
+        for ip in ["::", "::1", "fe80::1", "fd00::1", "ff02::1"] {
+            assert!(
+                !is_global_unicast(&ip.parse().unwrap()),
+                "{ip} is not global"
+            );
+        }
+    }
+
+    /// Tests that run in network namespaces, and therefore require root.
+    #[cfg(target_os = "linux")]
+    mod netns {
+        use super::*;
+        use nix::libc;
+        use std::{process::Command, sync::mpsc, thread};
+        use tokio::{
+            io::AsyncWriteExt,
+            net::{TcpListener, UdpSocket},
+        };
+
+        const LOCAL_INTERFACE: &str = "leaktest0";
+        const PEER_INTERFACE: &str = "leaktest1";
+        const LOCAL_IP: &str = "2001:db8::1";
+        const PEER_IP: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+        /// Move the current thread into a new network namespace. Processes spawned from the
+        /// thread are started in the same namespace.
+        fn enter_new_netns() {
+            // SAFETY: unshare has no memory safety requirements
+            let result = unsafe { libc::unshare(libc::CLONE_NEWNET) };
+            assert_eq!(
+                result,
+                0,
+                "unshare failed: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+
+        fn ip(args: &str) {
+            let status = Command::new("ip")
+                .args(args.split(' '))
+                .status()
+                .expect("Failed to run ip");
+            assert!(status.success(), "ip {args} failed");
+        }
+
+        /// Spawn a thread with its own network namespace, standing in for a host on the internet
+        /// at [PEER_IP]. It answers DNS queries, accepts TCP connections and, like any host,
+        /// answers pings. Returns the thread ID, which identifies the namespace, and a channel to
+        /// send on once [PEER_INTERFACE] has been moved into the namespace.
+        fn spawn_peer(ready_tx: mpsc::Sender<()>) -> (libc::pid_t, mpsc::Sender<()>) {
+            let (tid_tx, tid_rx) = mpsc::channel();
+            let (moved_tx, moved_rx) = mpsc::channel();
+            thread::spawn(move || {
+                enter_new_netns();
+                // SAFETY: gettid has no memory safety requirements
+                tid_tx.send(unsafe { libc::gettid() }).unwrap();
+                moved_rx.recv().unwrap();
+
+                ip(&format!("addr add {PEER_IP}/64 dev {PEER_INTERFACE} nodad"));
+                ip(&format!("link set {PEER_INTERFACE} up"));
+
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                runtime.block_on(async move {
+                    let address = SocketAddr::new(IpAddr::V6(PEER_IP), DNS_PORT);
+                    let udp = UdpSocket::bind(address).await.unwrap();
+                    let tcp = TcpListener::bind(address).await.unwrap();
+                    ready_tx.send(()).unwrap();
+
+                    let answer_queries = async {
+                        let mut buf = [0u8; 512];
+                        loop {
+                            let (len, source) = udp.recv_from(&mut buf).await.unwrap();
+                            // Flip the QR bit to turn the query into an empty response
+                            buf[2] |= 0x80;
+                            let _ = udp.send_to(&buf[..len], source).await;
+                        }
+                    };
+                    let accept_connections = async {
+                        loop {
+                            let (mut stream, _) = tcp.accept().await.unwrap();
+                            let _ = stream.shutdown().await;
+                        }
+                    };
+                    futures::join!(answer_queries, accept_connections);
+                });
+            });
+            (tid_rx.recv().unwrap(), moved_tx)
+        }
+
+        /// Run the leak test in a new network namespace, which is connected to a peer over a veth
+        /// pair.
+        fn run_leak_test_in_netns(opt: Ipv6LeakOpt) -> LeakStatus {
+            thread::spawn(move || {
+                enter_new_netns();
+                let (ready_tx, ready_rx) = mpsc::channel();
+                let (peer, moved_tx) = spawn_peer(ready_tx);
+
+                ip(&format!(
+                    "link add {LOCAL_INTERFACE} type veth peer name {PEER_INTERFACE}"
+                ));
+                ip(&format!("link set {PEER_INTERFACE} netns {peer}"));
+                moved_tx.send(()).unwrap();
+                ip(&format!(
+                    "addr add {LOCAL_IP}/64 dev {LOCAL_INTERFACE} nodad"
+                ));
+                ip(&format!("link set {LOCAL_INTERFACE} up"));
+                ready_rx.recv().unwrap();
+
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(try_run_leak_test(&opt))
+                    .unwrap()
+            })
+            .join()
+            .unwrap()
+        }
+
+        /// Nothing blocks IPv6 in the namespace, so the peer should be reachable through the
+        /// discovered interface.
+        #[test]
+        #[ignore = "requires root"]
+        fn test_leak_in_netns() {
+            let status = run_leak_test_in_netns(Ipv6LeakOpt {
+                interfaces: vec![],
+                exclude_interfaces: vec![],
+                destinations: vec![PEER_IP],
+                port: DNS_PORT,
+            });
+
+            let LeakStatus::LeakDetected(LeakInfo::Ipv6Leak {
+                reachable_nodes,
+                interface: Interface::Name(interface),
+            }) = status
+            else {
+                panic!("expected an IPv6 leak, got {status:?}");
+            };
+            assert_eq!(reachable_nodes, vec![PEER_IP]);
+            assert_eq!(interface, LOCAL_INTERFACE);
+        }
+
+        #[test]
+        #[ignore = "requires root"]
+        fn test_excluded_interface_in_netns() {
+            let status = run_leak_test_in_netns(Ipv6LeakOpt {
+                interfaces: vec![],
+                exclude_interfaces: vec![LOCAL_INTERFACE.to_owned()],
+                destinations: vec![PEER_IP],
+                port: DNS_PORT,
+            });
+            assert!(matches!(status, LeakStatus::NoLeak), "{status:?}");
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use std::{fmt, net::IpAddr};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv6Addr},
+};
 
 #[cfg(feature = "am-i-mullvad")]
 pub mod am_i_mullvad;
+pub mod dns;
+pub mod ipv6;
 pub mod traceroute;
 mod util;
 
@@ -20,11 +25,52 @@ pub enum LeakInfo {
         interface: Interface,
     },
 
+    /// Managed to get answers to DNS queries sent on the physical interface, or saw the system
+    /// resolver send queries on it.
+    DnsLeak {
+        resolvers: Vec<IpAddr>,
+        interface: Interface,
+    },
+
+    /// Managed to reach hosts over IPv6 on a physical interface, bypassing the tunnel.
+    Ipv6Leak {
+        reachable_nodes: Vec<Ipv6Addr>,
+        interface: Interface,
+    },
+
     /// Queried a <https://am.i.mullvad.net>, and was not mullvad.
     #[cfg(feature = "am-i-mullvad")]
     AmIMullvad { ip: IpAddr },
 }
 
+impl LeakInfo {
+    /// The interface that the traffic leaked on, if known.
+    pub fn interface(&self) -> Option<&Interface> {
+        match self {
+            LeakInfo::NodeReachableOnInterface { interface, .. }
+            | LeakInfo::DnsLeak { interface, .. }
+            | LeakInfo::Ipv6Leak { interface, .. } => Some(interface),
+            #[cfg(feature = "am-i-mullvad")]
+            LeakInfo::AmIMullvad { .. } => None,
+        }
+    }
+
+    /// The addresses that were reached outside the tunnel, or the public IP that was seen.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        match self {
+            LeakInfo::NodeReachableOnInterface {
+                reachable_nodes, ..
+            } => reachable_nodes.clone(),
+            LeakInfo::DnsLeak { resolvers, .. } => resolvers.clone(),
+            LeakInfo::Ipv6Leak {
+                reachable_nodes, ..
+            } => reachable_nodes.iter().copied().map(IpAddr::V6).collect(),
+            #[cfg(feature = "am-i-mullvad")]
+            LeakInfo::AmIMullvad { ip } => vec![*ip],
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Interface {
     Name(String),
@@ -42,6 +88,21 @@ impl From<String> for Interface {
     }
 }
 
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => f.write_str(name),
+
+            // SAFETY: u64 is valid for all bit patterns, so reading the union as a u64 is safe.
+            #[cfg(target_os = "windows")]
+            Self::Luid(luid) => write!(f, "LUID {:#x}", unsafe { luid.Value }),
+
+            #[cfg(target_os = "macos")]
+            Self::Index(index) => write!(f, "index {index}"),
+        }
+    }
+}
+
 impl fmt::Debug for Interface {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -8,7 +8,7 @@ mod windows;
 
 /// Traceroute implementation for unix.
 #[cfg(unix)]
-mod unix;
+pub(crate) mod unix;
 
 #[derive(Clone, clap::Args)]
 pub struct TracerouteOpt {
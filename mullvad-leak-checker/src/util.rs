@@ -86,3 +86,34 @@ pub fn get_interface_ip(interface: &Interface, ip_version: Ip) -> anyhow::Result
 
     anyhow::bail!("Interface {interface:?} has no valid IP to bind to");
 }
+
+/// Bind `socket` so that the packets it sends leave through `interface`.
+pub fn bind_socket_to_interface(
+    socket: &socket2::Socket,
+    interface: &Interface,
+    ip_version: Ip,
+) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use crate::traceroute::unix::Traceroute;
+        #[cfg(target_os = "android")]
+        type Impl = crate::traceroute::unix::android::TracerouteAndroid;
+        #[cfg(target_os = "linux")]
+        type Impl = crate::traceroute::unix::linux::TracerouteLinux;
+        #[cfg(target_os = "macos")]
+        type Impl = crate::traceroute::unix::macos::TracerouteMacos;
+
+        Impl::bind_socket_to_interface(socket, interface, ip_version)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use anyhow::Context;
+
+        let ip = get_interface_ip(interface, ip_version)?;
+        log::debug!("Binding socket to {ip} ({interface:?})");
+        socket
+            .bind(&std::net::SocketAddr::new(ip, 0).into())
+            .context("Failed to bind socket to interface IP")
+    }
+}
@@ -8,6 +8,7 @@ use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
     access_method::AccessMethodSetting,
     device::{DeviceEvent, RemoveDeviceEvent},
+    leak_check::LeakTestResult,
     relay_list::RelayList,
     settings::Settings,
     states::TunnelState,
@@ -16,19 +17,32 @@ use mullvad_types::{
 
 #[cfg(not(target_os = "android"))]
 use mullvad_types::{
-    access_method::{self, AccessMethod},
+    access_method::{self, AccessMethod, AccessMethodStats, DetectedSystemProxy},
     account::{AccountData, AccountNumber, VoucherSubmission},
+    connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter},
     custom_list::{CustomList, Id},
     device::{Device, DeviceId, DeviceState},
+    diagnostics::Diagnostics,
     features::FeatureIndicators,
+    metrics::MetricsSettings,
+    profile::{ProfileList, SettingsProfile},
     relay_constraints::{
         BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        RetryAttempt,
     },
-    settings::DnsOptions,
-    wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    settings::{DnsOptions, SplitTunnelMode},
+    wireguard::{PublicKey, QuantumResistantState, RotationInterval, TunnelStats},
 };
 #[cfg(not(target_os = "android"))]
-use std::{path::Path, str::FromStr};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+    str::FromStr,
+};
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::firewall::FirewallException;
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 #[cfg(not(target_os = "android"))]
@@ -51,6 +65,7 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    LeakDetected(LeakTestResult),
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -81,6 +96,9 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::LeakDetected(result) => LeakTestResult::try_from(result)
+                .map(DaemonEvent::LeakDetected)
+                .map_err(Error::InvalidResponse),
         }
     }
 }
@@ -133,6 +151,60 @@ impl MullvadProxyClient {
         TunnelState::try_from(state).map_err(Error::InvalidResponse)
     }
 
+    pub async fn stream_tunnel_stats(&mut self) -> Result<impl Stream<Item = Result<TunnelStats>>> {
+        let stream = self
+            .0
+            .stream_tunnel_stats(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+
+        Ok(stream.map(|item| {
+            TunnelStats::try_from(item.map_err(Error::Rpc)?).map_err(Error::InvalidResponse)
+        }))
+    }
+
+    pub async fn get_connection_history(
+        &mut self,
+        filter: ConnectionHistoryFilter,
+    ) -> Result<Vec<ConnectionHistoryEntry>> {
+        let history = self
+            .0
+            .get_connection_history(types::ConnectionHistoryFilter::from(filter))
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        history
+            .entries
+            .into_iter()
+            .map(|entry| ConnectionHistoryEntry::try_from(entry).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    pub async fn run_leak_check(&mut self) -> Result<Vec<LeakTestResult>> {
+        let results = self
+            .0
+            .run_leak_check(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        results
+            .results
+            .into_iter()
+            .map(|result| LeakTestResult::try_from(result).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    pub async fn get_diagnostics(&mut self) -> Result<Diagnostics> {
+        let diagnostics = self
+            .0
+            .get_diagnostics(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Diagnostics::try_from(diagnostics).map_err(Error::InvalidResponse)
+    }
+
     pub async fn events_listen(&mut self) -> Result<impl Stream<Item = Result<DaemonEvent>>> {
         let listener = self
             .0
@@ -242,6 +314,30 @@ impl MullvadProxyClient {
             })
     }
 
+    pub async fn get_api_access_method_stats(&mut self) -> Result<Vec<AccessMethodStats>> {
+        let list = self
+            .0
+            .get_api_access_method_stats(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        list.stats
+            .into_iter()
+            .map(|stats| AccessMethodStats::try_from(stats).map_err(Error::InvalidResponse))
+            .collect()
+    }
+
+    pub async fn get_system_proxy(&mut self) -> Result<DetectedSystemProxy> {
+        self.0
+            .get_system_proxy(())
+            .await
+            .map_err(Error::Rpc)
+            .map(tonic::Response::into_inner)
+            .and_then(|detected| {
+                DetectedSystemProxy::try_from(detected).map_err(Error::InvalidResponse)
+            })
+    }
+
     pub async fn test_api_access_method(&mut self, id: access_method::Id) -> Result<bool> {
         let result = self
             .0
@@ -314,6 +410,18 @@ impl MullvadProxyClient {
         Settings::try_from(settings).map_err(Error::InvalidResponse)
     }
 
+    /// Returns the settings currently in effect, including the overrides of the active settings
+    /// profile. Use [`Self::get_settings`] for settings that are about to be modified.
+    pub async fn get_effective_settings(&mut self) -> Result<Settings> {
+        let settings = self
+            .0
+            .get_effective_settings(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Settings::try_from(settings).map_err(Error::InvalidResponse)
+    }
+
     pub async fn reset_settings(&mut self) -> Result<()> {
         self.0.reset_settings(()).await.map_err(Error::Rpc)?;
         Ok(())
@@ -403,6 +511,18 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_wireguard_connectivity_check(
+        &mut self,
+        options: ConnectivityCheckOptions,
+    ) -> Result<()> {
+        let options = types::ConnectivityCheckOptions::from(options);
+        self.0
+            .set_wireguard_connectivity_check(options)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_dns_options(&mut self, options: DnsOptions) -> Result<()> {
         let options = types::DnsOptions::from(&options);
         self.0.set_dns_options(options).await.map_err(Error::Rpc)?;
@@ -426,6 +546,33 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_retry_order(&mut self, retry_order: Vec<RetryAttempt>) -> Result<()> {
+        let retry_order = types::RetryOrder {
+            attempts: retry_order
+                .into_iter()
+                .map(types::RetryAttempt::from)
+                .collect(),
+        };
+        self.0
+            .set_retry_order(retry_order)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn reset_retry_order(&mut self) -> Result<()> {
+        self.0.reset_retry_order(()).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_metrics_settings(&mut self, settings: MetricsSettings) -> Result<()> {
+        self.0
+            .set_metrics_settings(types::MetricsSettings::from(settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn create_new_account(&mut self) -> Result<AccountNumber> {
         Ok(self
             .0
@@ -568,6 +715,33 @@ impl MullvadProxyClient {
         PublicKey::try_from(key).map_err(Error::InvalidResponse)
     }
 
+    pub async fn export_wireguard_config(&mut self) -> Result<String> {
+        Ok(self
+            .0
+            .export_wireguard_config(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner())
+    }
+
+    /// Replace the relay, MTU and DNS settings with those of a wg-quick config in a single update.
+    pub async fn import_wireguard_config(
+        &mut self,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) -> Result<()> {
+        self.0
+            .import_wireguard_config(types::WireguardConfigImport {
+                config,
+                ipv4_gateway: ipv4_gateway.map(|gateway| gateway.to_string()),
+                ipv6_gateway: ipv6_gateway.map(|gateway| gateway.to_string()),
+            })
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn create_custom_list(&mut self, name: String) -> Result<Id> {
         let id = self
             .0
@@ -603,6 +777,48 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn create_settings_profile(&mut self, profile: SettingsProfile) -> Result<()> {
+        self.0
+            .create_settings_profile(types::SettingsProfile::from(profile))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn delete_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .delete_settings_profile(name)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_settings_profiles(&mut self) -> Result<ProfileList> {
+        let profiles = self
+            .0
+            .get_settings_profiles(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        ProfileList::try_from(profiles).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn activate_settings_profile(&mut self, name: String) -> Result<()> {
+        self.0
+            .activate_settings_profile(name)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn deactivate_settings_profile(&mut self) -> Result<()> {
+        self.0
+            .deactivate_settings_profile(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn add_access_method(
         &mut self,
         name: String,
@@ -661,6 +877,21 @@ impl MullvadProxyClient {
             .map(drop)
     }
 
+    /// Pin an access method, so that `AccessModeSelector` always tries it
+    /// first. Passing `None` unpins the currently pinned access method.
+    pub async fn set_pinned_access_method(
+        &mut self,
+        api_access_method: Option<access_method::Id>,
+    ) -> Result<()> {
+        self.0
+            .set_pinned_api_access_method(types::PinnedApiAccessMethod {
+                id: api_access_method.map(types::Uuid::from),
+            })
+            .await
+            .map_err(Error::Rpc)
+            .map(drop)
+    }
+
     pub async fn get_split_tunnel_processes(&mut self) -> Result<Vec<i32>> {
         use futures::TryStreamExt;
 
@@ -697,6 +928,48 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Return a description of the mechanism used to identify excluded processes
+    pub async fn get_split_tunnel_mechanism(&mut self) -> Result<String> {
+        self.0
+            .get_split_tunnel_mechanism(())
+            .await
+            .map_err(Error::Rpc)
+            .map(|response| response.into_inner())
+    }
+
+    pub async fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> Result<()> {
+        let mode = types::SplitTunnelMode::from(mode);
+        self.0
+            .set_split_tunnel_mode(mode)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn add_firewall_exception(&mut self, exception: FirewallException) -> Result<()> {
+        self.0
+            .add_firewall_exception(types::FirewallException::from(exception))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_firewall_exception(&mut self, exception: FirewallException) -> Result<()> {
+        self.0
+            .remove_firewall_exception(types::FirewallException::from(exception))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn clear_firewall_exceptions(&mut self) -> Result<()> {
+        self.0
+            .clear_firewall_exceptions(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn add_split_tunnel_app<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let path = path.as_ref().to_str().ok_or(Error::PathMustBeUtf8)?;
         self.0
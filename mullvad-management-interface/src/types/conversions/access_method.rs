@@ -11,11 +11,13 @@ mod settings {
                 direct: Some(settings.direct().clone().into()),
                 mullvad_bridges: Some(settings.mullvad_bridges().clone().into()),
                 encrypted_dns_proxy: Some(settings.encrypted_dns_proxy().clone().into()),
+                system_proxy: Some(settings.system_proxy().clone().into()),
                 custom: settings
                     .iter_custom()
                     .cloned()
                     .map(|method| method.into())
                     .collect(),
+                pinned: settings.pinned().map(|method| method.get_id().into()),
             }
         }
     }
@@ -45,18 +47,151 @@ mod settings {
                 ))
                 .and_then(access_method::AccessMethodSetting::try_from)?;
 
+            let system_proxy = settings
+                .system_proxy
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "Could not deserialize System proxy Access Method from protobuf",
+                ))
+                .and_then(access_method::AccessMethodSetting::try_from)?;
+
             let custom = settings
                 .custom
                 .iter()
                 .map(access_method::AccessMethodSetting::try_from)
                 .collect::<Result<Vec<_>, _>>()?;
 
-            Ok(access_method::Settings::new(
+            let pinned = settings
+                .pinned
+                .map(access_method::Id::try_from)
+                .transpose()?;
+
+            let mut settings = access_method::Settings::new(
                 direct,
                 mullvad_bridges,
                 encrypted_dns_proxy,
+                system_proxy,
                 custom,
-            ))
+            );
+            settings.set_pinned(pinned).map_err(|_| {
+                FromProtobufTypeError::InvalidArgument("Pinned access method does not exist")
+            })?;
+            Ok(settings)
+        }
+    }
+}
+
+/// Implements conversions for the [`crate::types::proto::ApiAccessMethodStats`]
+/// type to the internal [`mullvad_types::access_method::AccessMethodStats`]
+/// data type.
+mod stats {
+    use crate::types::{
+        conversions::{from_timestamp, to_timestamp},
+        proto, FromProtobufTypeError,
+    };
+    use mullvad_types::access_method::{AccessMethodStats, Id};
+
+    impl From<AccessMethodStats> for proto::ApiAccessMethodStats {
+        fn from(stats: AccessMethodStats) -> Self {
+            Self {
+                id: Some(stats.id.into()),
+                successes: stats.successes,
+                failures: stats.failures,
+                consecutive_failures: stats.consecutive_failures,
+                latency: stats.latency.map(|latency| {
+                    prost_types::Duration::try_from(latency)
+                        .expect("Failed to convert std::time::Duration to prost_types::Duration")
+                }),
+                last_success: stats.last_success.map(to_timestamp),
+                last_failure: stats.last_failure.map(to_timestamp),
+                health: stats.health,
+            }
+        }
+    }
+
+    impl TryFrom<proto::ApiAccessMethodStats> for AccessMethodStats {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(stats: proto::ApiAccessMethodStats) -> Result<Self, Self::Error> {
+            let id = stats
+                .id
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "Access method statistics are missing an id",
+                ))
+                .and_then(Id::try_from)?;
+            let latency = stats
+                .latency
+                .map(std::time::Duration::try_from)
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?;
+
+            Ok(AccessMethodStats {
+                id,
+                successes: stats.successes,
+                failures: stats.failures,
+                consecutive_failures: stats.consecutive_failures,
+                latency,
+                last_success: stats.last_success.map(from_timestamp).transpose()?,
+                last_failure: stats.last_failure.map(from_timestamp).transpose()?,
+                health: stats.health,
+            })
+        }
+    }
+}
+
+/// Implements conversions for the [`crate::types::proto::DetectedSystemProxy`]
+/// type to the internal [`mullvad_types::access_method::DetectedSystemProxy`]
+/// data type.
+mod system_proxy {
+    use crate::types::{
+        proto::{
+            self,
+            detected_system_proxy::{Bypassed, Detected, Found, Unusable},
+        },
+        FromProtobufTypeError,
+    };
+    use mullvad_types::access_method::DetectedSystemProxy;
+    use talpid_types::net::proxy::CustomProxy;
+
+    impl From<DetectedSystemProxy> for proto::DetectedSystemProxy {
+        fn from(detected: DetectedSystemProxy) -> Self {
+            let detected = match detected {
+                DetectedSystemProxy::None => None,
+                DetectedSystemProxy::Found { source, proxy } => Some(Detected::Found(Found {
+                    source,
+                    proxy: Some(proto::CustomProxy::from(proxy)),
+                })),
+                DetectedSystemProxy::Bypassed { source } => {
+                    Some(Detected::Bypassed(Bypassed { source }))
+                }
+                DetectedSystemProxy::Unusable { source, reason } => {
+                    Some(Detected::Unusable(Unusable { source, reason }))
+                }
+            };
+            Self { detected }
+        }
+    }
+
+    impl TryFrom<proto::DetectedSystemProxy> for DetectedSystemProxy {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(detected: proto::DetectedSystemProxy) -> Result<Self, Self::Error> {
+            Ok(match detected.detected {
+                None => DetectedSystemProxy::None,
+                Some(Detected::Found(Found { source, proxy })) => {
+                    let proxy = proxy
+                        .ok_or(FromProtobufTypeError::InvalidArgument(
+                            "Detected system proxy is missing a proxy",
+                        ))
+                        .and_then(CustomProxy::try_from)?;
+                    DetectedSystemProxy::Found { source, proxy }
+                }
+                Some(Detected::Bypassed(Bypassed { source })) => {
+                    DetectedSystemProxy::Bypassed { source }
+                }
+                Some(Detected::Unusable(Unusable { source, reason })) => {
+                    DetectedSystemProxy::Unusable { source, reason }
+                }
+            })
         }
     }
 }
@@ -69,7 +204,9 @@ mod data {
     use mullvad_types::access_method::{
         AccessMethod, AccessMethodSetting, BuiltInAccessMethod, Id,
     };
-    use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+    use talpid_types::net::proxy::{
+        CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote,
+    };
 
     impl TryFrom<proto::AccessMethodSetting> for AccessMethodSetting {
         type Error = FromProtobufTypeError;
@@ -130,6 +267,7 @@ mod data {
                 proto::access_method::AccessMethod::EncryptedDnsProxy(proxy) => {
                     AccessMethod::from(proxy)
                 }
+                proto::access_method::AccessMethod::SystemProxy(proxy) => AccessMethod::from(proxy),
                 proto::access_method::AccessMethod::Custom(custom) => {
                     CustomProxy::try_from(custom).map(AccessMethod::from)?
                 }
@@ -164,6 +302,12 @@ mod data {
         }
     }
 
+    impl From<proto::access_method::SystemProxy> for AccessMethod {
+        fn from(_value: proto::access_method::SystemProxy) -> Self {
+            AccessMethod::from(BuiltInAccessMethod::SystemProxy)
+        }
+    }
+
     impl TryFrom<proto::Socks5Local> for AccessMethod {
         type Error = FromProtobufTypeError;
 
@@ -180,6 +324,14 @@ mod data {
         }
     }
 
+    impl TryFrom<proto::HttpConnect> for AccessMethod {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpConnect) -> Result<Self, Self::Error> {
+            HttpConnect::try_from(value).map(AccessMethod::from)
+        }
+    }
+
     impl TryFrom<proto::Shadowsocks> for AccessMethod {
         type Error = FromProtobufTypeError;
 
@@ -210,6 +362,11 @@ mod data {
                         proto::access_method::EncryptedDnsProxy {},
                     )
                 }
+                mullvad_types::access_method::BuiltInAccessMethod::SystemProxy => {
+                    proto::access_method::AccessMethod::SystemProxy(
+                        proto::access_method::SystemProxy {},
+                    )
+                }
             }
         }
     }
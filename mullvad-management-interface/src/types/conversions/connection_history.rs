@@ -0,0 +1,55 @@
+use super::{from_timestamp, to_timestamp, FromProtobufTypeError};
+use crate::types::proto;
+use mullvad_types::connection_history::{ConnectionHistoryEntry, ConnectionHistoryFilter};
+
+impl From<ConnectionHistoryFilter> for proto::ConnectionHistoryFilter {
+    fn from(filter: ConnectionHistoryFilter) -> Self {
+        proto::ConnectionHistoryFilter {
+            since: filter.since.map(to_timestamp),
+            until: filter.until.map(to_timestamp),
+            limit: filter
+                .limit
+                .map(|limit| u32::try_from(limit).unwrap_or(u32::MAX)),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionHistoryFilter> for ConnectionHistoryFilter {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(filter: proto::ConnectionHistoryFilter) -> Result<Self, Self::Error> {
+        Ok(ConnectionHistoryFilter {
+            since: filter.since.map(from_timestamp).transpose()?,
+            until: filter.until.map(from_timestamp).transpose()?,
+            limit: filter.limit.map(|limit| limit as usize),
+        })
+    }
+}
+
+impl From<ConnectionHistoryEntry> for proto::ConnectionHistoryEntry {
+    fn from(entry: ConnectionHistoryEntry) -> Self {
+        proto::ConnectionHistoryEntry {
+            time: Some(to_timestamp(entry.time)),
+            state: Some(proto::TunnelState::from(entry.state)),
+            retry_attempt: entry.retry_attempt,
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectionHistoryEntry> for ConnectionHistoryEntry {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(entry: proto::ConnectionHistoryEntry) -> Result<Self, Self::Error> {
+        let time = entry
+            .time
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing time"))?;
+        let state = entry.state.ok_or(FromProtobufTypeError::InvalidArgument(
+            "missing tunnel state",
+        ))?;
+        Ok(ConnectionHistoryEntry {
+            time: from_timestamp(time)?,
+            retry_attempt: entry.retry_attempt,
+            state: mullvad_types::states::TunnelState::try_from(state)?,
+        })
+    }
+}
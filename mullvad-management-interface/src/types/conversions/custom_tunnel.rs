@@ -1,5 +1,5 @@
 use crate::types::{
-    conversions::{bytes_to_privkey, bytes_to_pubkey},
+    conversions::{bytes_to_privkey, bytes_to_psk, bytes_to_pubkey},
     proto, FromProtobufTypeError,
 };
 use talpid_types::net::wireguard;
@@ -47,6 +47,11 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                 ))?;
 
                 let public_key = bytes_to_pubkey(&peer.public_key)?;
+                let psk = peer
+                    .preshared_key
+                    .as_deref()
+                    .map(bytes_to_psk)
+                    .transpose()?;
 
                 let ipv4_gateway = config.ipv4_gateway.parse().map_err(|_err| {
                     FromProtobufTypeError::InvalidArgument("invalid IPv4 gateway")
@@ -90,10 +95,11 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                             public_key,
                             allowed_ips,
                             endpoint,
-                            psk: None,
+                            psk,
                             #[cfg(daita)]
                             constant_packet_size: false,
                         },
+                        middle_peers: vec![],
                         exit_peer: None,
                         ipv4_gateway,
                         ipv6_gateway,
@@ -142,6 +148,11 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
                                 .map(|address| address.to_string())
                                 .collect(),
                             endpoint: config.peer.endpoint.to_string(),
+                            preshared_key: config
+                                .peer
+                                .psk
+                                .as_ref()
+                                .map(|psk| psk.as_bytes().to_vec()),
                         }),
                         ipv4_gateway: config.ipv4_gateway.to_string(),
                         ipv6_gateway: config
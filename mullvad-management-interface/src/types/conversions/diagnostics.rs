@@ -0,0 +1,73 @@
+use super::{from_timestamp, to_timestamp, FromProtobufTypeError};
+use crate::types::proto;
+use mullvad_types::diagnostics::{Diagnostics, RelayListMetadata};
+
+impl From<Diagnostics> for proto::Diagnostics {
+    fn from(diagnostics: Diagnostics) -> Self {
+        proto::Diagnostics {
+            firewall: diagnostics.firewall,
+            routes: diagnostics.routes,
+            dns: diagnostics.dns,
+            relay_list: Some(proto::diagnostics::RelayListMetadata::from(
+                diagnostics.relay_list,
+            )),
+        }
+    }
+}
+
+impl From<RelayListMetadata> for proto::diagnostics::RelayListMetadata {
+    fn from(metadata: RelayListMetadata) -> Self {
+        proto::diagnostics::RelayListMetadata {
+            etag: metadata.etag,
+            last_updated: Some(to_timestamp(metadata.last_updated)),
+            countries: metadata.countries,
+            cities: metadata.cities,
+            relays: metadata.relays,
+            active_relays: metadata.active_relays,
+            wireguard_relays: metadata.wireguard_relays,
+            openvpn_relays: metadata.openvpn_relays,
+            bridges: metadata.bridges,
+        }
+    }
+}
+
+impl TryFrom<proto::Diagnostics> for Diagnostics {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(diagnostics: proto::Diagnostics) -> Result<Self, Self::Error> {
+        let relay_list = diagnostics
+            .relay_list
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing relay list metadata",
+            ))?;
+        Ok(Diagnostics {
+            firewall: diagnostics.firewall,
+            routes: diagnostics.routes,
+            dns: diagnostics.dns,
+            relay_list: RelayListMetadata::try_from(relay_list)?,
+        })
+    }
+}
+
+impl TryFrom<proto::diagnostics::RelayListMetadata> for RelayListMetadata {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(metadata: proto::diagnostics::RelayListMetadata) -> Result<Self, Self::Error> {
+        let last_updated = metadata
+            .last_updated
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing relay list update time",
+            ))?;
+        Ok(RelayListMetadata {
+            etag: metadata.etag,
+            last_updated: from_timestamp(last_updated)?,
+            countries: metadata.countries,
+            cities: metadata.cities,
+            relays: metadata.relays,
+            active_relays: metadata.active_relays,
+            wireguard_relays: metadata.wireguard_relays,
+            openvpn_relays: metadata.openvpn_relays,
+            bridges: metadata.bridges,
+        })
+    }
+}
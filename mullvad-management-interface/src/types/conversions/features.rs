@@ -6,11 +6,16 @@ impl From<mullvad_types::features::FeatureIndicator> for proto::FeatureIndicator
         match feature {
             mullvad_types::features::FeatureIndicator::QuantumResistance => QuantumResistance,
             mullvad_types::features::FeatureIndicator::Multihop => Multihop,
+            mullvad_types::features::FeatureIndicator::Cascade => Cascade,
             mullvad_types::features::FeatureIndicator::BridgeMode => BridgeMode,
             mullvad_types::features::FeatureIndicator::SplitTunneling => SplitTunneling,
+            mullvad_types::features::FeatureIndicator::InverseSplitTunneling => {
+                InverseSplitTunneling
+            }
             mullvad_types::features::FeatureIndicator::LockdownMode => LockdownMode,
             mullvad_types::features::FeatureIndicator::Udp2Tcp => Udp2Tcp,
             mullvad_types::features::FeatureIndicator::Shadowsocks => Shadowsocks,
+            mullvad_types::features::FeatureIndicator::Quic => Quic,
             mullvad_types::features::FeatureIndicator::LanSharing => LanSharing,
             mullvad_types::features::FeatureIndicator::DnsContentBlockers => DnsContentBlockers,
             mullvad_types::features::FeatureIndicator::CustomDns => CustomDns,
@@ -27,11 +32,14 @@ impl From<proto::FeatureIndicator> for mullvad_types::features::FeatureIndicator
         match feature {
             proto::FeatureIndicator::QuantumResistance => Self::QuantumResistance,
             proto::FeatureIndicator::Multihop => Self::Multihop,
+            proto::FeatureIndicator::Cascade => Self::Cascade,
             proto::FeatureIndicator::BridgeMode => Self::BridgeMode,
             proto::FeatureIndicator::SplitTunneling => Self::SplitTunneling,
+            proto::FeatureIndicator::InverseSplitTunneling => Self::InverseSplitTunneling,
             proto::FeatureIndicator::LockdownMode => Self::LockdownMode,
             proto::FeatureIndicator::Udp2Tcp => Self::Udp2Tcp,
             proto::FeatureIndicator::Shadowsocks => Self::Shadowsocks,
+            proto::FeatureIndicator::Quic => Self::Quic,
             proto::FeatureIndicator::LanSharing => Self::LanSharing,
             proto::FeatureIndicator::DnsContentBlockers => Self::DnsContentBlockers,
             proto::FeatureIndicator::CustomDns => Self::CustomDns,
@@ -0,0 +1,80 @@
+use super::FromProtobufTypeError;
+use crate::types::proto;
+use mullvad_types::leak_check::{LeakInfo, LeakStatus, LeakTest, LeakTestResult};
+
+impl From<LeakTest> for proto::leak_test_result::LeakTest {
+    fn from(test: LeakTest) -> Self {
+        match test {
+            LeakTest::Traceroute => proto::leak_test_result::LeakTest::Traceroute,
+            LeakTest::Dns => proto::leak_test_result::LeakTest::Dns,
+            LeakTest::Ipv6 => proto::leak_test_result::LeakTest::Ipv6,
+            LeakTest::AmIMullvad => proto::leak_test_result::LeakTest::AmIMullvad,
+        }
+    }
+}
+
+impl From<proto::leak_test_result::LeakTest> for LeakTest {
+    fn from(test: proto::leak_test_result::LeakTest) -> Self {
+        match test {
+            proto::leak_test_result::LeakTest::Traceroute => LeakTest::Traceroute,
+            proto::leak_test_result::LeakTest::Dns => LeakTest::Dns,
+            proto::leak_test_result::LeakTest::Ipv6 => LeakTest::Ipv6,
+            proto::leak_test_result::LeakTest::AmIMullvad => LeakTest::AmIMullvad,
+        }
+    }
+}
+
+impl From<LeakTestResult> for proto::LeakTestResult {
+    fn from(result: LeakTestResult) -> Self {
+        use proto::leak_test_result::Status;
+
+        let status = match result.status {
+            LeakStatus::NoLeak => Status::NoLeak(()),
+            LeakStatus::LeakDetected(info) => {
+                Status::LeakDetected(proto::leak_test_result::LeakInfo {
+                    interface: info.interface,
+                    addresses: info
+                        .addresses
+                        .iter()
+                        .map(|address| address.to_string())
+                        .collect(),
+                })
+            }
+            LeakStatus::Error(error) => Status::Error(error),
+        };
+        proto::LeakTestResult {
+            test: i32::from(proto::leak_test_result::LeakTest::from(result.test)),
+            status: Some(status),
+        }
+    }
+}
+
+impl TryFrom<proto::LeakTestResult> for LeakTestResult {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(result: proto::LeakTestResult) -> Result<Self, Self::Error> {
+        use proto::leak_test_result::Status;
+
+        let test = proto::leak_test_result::LeakTest::try_from(result.test)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid leak test"))?;
+        let status = match result.status.ok_or(FromProtobufTypeError::InvalidArgument(
+            "missing leak status",
+        ))? {
+            Status::NoLeak(()) => LeakStatus::NoLeak,
+            Status::LeakDetected(info) => LeakStatus::LeakDetected(LeakInfo {
+                interface: info.interface,
+                addresses: info
+                    .addresses
+                    .iter()
+                    .map(|address| address.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid address"))?,
+            }),
+            Status::Error(error) => LeakStatus::Error(error),
+        };
+        Ok(LeakTestResult {
+            test: LeakTest::from(test),
+            status,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use super::FromProtobufTypeError;
+use crate::types::proto;
+use mullvad_types::metrics::{MetricsEndpoint, MetricsSettings};
+
+impl From<MetricsSettings> for proto::MetricsSettings {
+    fn from(settings: MetricsSettings) -> Self {
+        let endpoint = match settings.endpoint {
+            MetricsEndpoint::Tcp(address) => {
+                proto::metrics_settings::Endpoint::Tcp(address.to_string())
+            }
+            MetricsEndpoint::Unix => proto::metrics_settings::Endpoint::UnixSocket(()),
+        };
+        proto::MetricsSettings {
+            enabled: settings.enabled,
+            endpoint: Some(endpoint),
+        }
+    }
+}
+
+impl TryFrom<proto::MetricsSettings> for MetricsSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::MetricsSettings) -> Result<Self, Self::Error> {
+        let endpoint = match settings.endpoint {
+            Some(proto::metrics_settings::Endpoint::Tcp(address)) => MetricsEndpoint::Tcp(
+                super::arg_from_str(&address, "invalid metrics socket address")?,
+            ),
+            Some(proto::metrics_settings::Endpoint::UnixSocket(())) => MetricsEndpoint::Unix,
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "missing metrics endpoint",
+                ))
+            }
+        };
+        Ok(MetricsSettings {
+            enabled: settings.enabled,
+            endpoint,
+        })
+    }
+}
@@ -2,12 +2,17 @@ use std::str::FromStr;
 
 mod access_method;
 mod account;
+mod connection_history;
 mod custom_list;
 mod custom_tunnel;
 mod device;
+mod diagnostics;
 mod features;
+mod leak_check;
 mod location;
+mod metrics;
 mod net;
+mod profile;
 pub mod relay_constraints;
 mod relay_list;
 mod settings;
@@ -23,6 +28,20 @@ pub enum FromProtobufTypeError {
     InvalidArgument(&'static str),
 }
 
+fn to_timestamp(time: chrono::DateTime<chrono::Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn from_timestamp(
+    time: prost_types::Timestamp,
+) -> Result<chrono::DateTime<chrono::Utc>, FromProtobufTypeError> {
+    chrono::DateTime::from_timestamp(time.seconds, time.nanos as u32)
+        .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+}
+
 fn bytes_to_pubkey(
     bytes: &[u8],
 ) -> Result<talpid_types::net::wireguard::PublicKey, FromProtobufTypeError> {
@@ -39,6 +58,14 @@ fn bytes_to_privkey(
     ))
 }
 
+fn bytes_to_psk(
+    bytes: &[u8],
+) -> Result<talpid_types::net::wireguard::PresharedKey, FromProtobufTypeError> {
+    Ok(talpid_types::net::wireguard::PresharedKey::from(
+        *bytes_to_wg_key(bytes, "invalid preshared key")?,
+    ))
+}
+
 fn bytes_to_wg_key<'a>(
     bytes: &'a [u8],
     error_msg: &'static str,
@@ -39,6 +39,12 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                         net::ObfuscationType::Shadowsocks => {
                             i32::from(proto::obfuscation_endpoint::ObfuscationType::Shadowsocks)
                         }
+                        net::ObfuscationType::Quic => {
+                            i32::from(proto::obfuscation_endpoint::ObfuscationType::Quic)
+                        }
+                        net::ObfuscationType::Socks5 => {
+                            i32::from(proto::obfuscation_endpoint::ObfuscationType::Socks5)
+                        }
                     },
                 }
             }),
@@ -46,9 +52,20 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                 address: entry.address.to_string(),
                 protocol: i32::from(proto::TransportProtocol::from(entry.protocol)),
             }),
-            tunnel_metadata: endpoint
-                .tunnel_interface
-                .map(|tunnel_interface| proto::TunnelMetadata { tunnel_interface }),
+            middle_endpoints: endpoint
+                .middle_endpoints
+                .into_iter()
+                .map(|middle| proto::Endpoint {
+                    address: middle.address.to_string(),
+                    protocol: i32::from(proto::TransportProtocol::from(middle.protocol)),
+                })
+                .collect(),
+            tunnel_metadata: endpoint.tunnel_interface.map(|tunnel_interface| {
+                proto::TunnelMetadata {
+                    tunnel_interface,
+                    mtu: endpoint.mtu.map(u32::from),
+                }
+            }),
             #[cfg(daita)]
             daita: endpoint.daita,
             #[cfg(not(daita))]
@@ -123,6 +140,12 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                                 Ok(proto::obfuscation_endpoint::ObfuscationType::Shadowsocks) => {
                                     talpid_net::ObfuscationType::Shadowsocks
                                 }
+                                Ok(proto::obfuscation_endpoint::ObfuscationType::Quic) => {
+                                    talpid_net::ObfuscationType::Quic
+                                }
+                                Ok(proto::obfuscation_endpoint::ObfuscationType::Socks5) => {
+                                    talpid_net::ObfuscationType::Socks5
+                                }
                                 Err(_) => {
                                     return Err(FromProtobufTypeError::InvalidArgument(
                                         "unknown obfuscation type",
@@ -141,6 +164,21 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                     })
                 })
                 .transpose()?,
+            middle_endpoints: endpoint
+                .middle_endpoints
+                .iter()
+                .map(|middle| {
+                    Ok(talpid_net::Endpoint {
+                        address: arg_from_str(&middle.address, "invalid middle endpoint address")?,
+                        protocol: try_transport_protocol_from_i32(middle.protocol)?,
+                    })
+                })
+                .collect::<Result<_, FromProtobufTypeError>>()?,
+            mtu: endpoint
+                .tunnel_metadata
+                .as_ref()
+                .and_then(|tunnel_metadata| tunnel_metadata.mtu)
+                .map(|mtu| mtu as u16),
             tunnel_interface: endpoint
                 .tunnel_metadata
                 .map(|tunnel_metadata| tunnel_metadata.tunnel_interface),
@@ -159,6 +197,64 @@ impl From<talpid_types::net::TransportProtocol> for proto::TransportProtocol {
     }
 }
 
+impl From<talpid_types::net::firewall::FirewallException> for proto::FirewallException {
+    fn from(exception: talpid_types::net::firewall::FirewallException) -> Self {
+        use proto::firewall_exception::{Exception, Inbound, Outbound};
+        use talpid_types::net::firewall::FirewallException;
+
+        let exception = match exception {
+            FirewallException::Outbound {
+                network,
+                port,
+                protocol,
+            } => Exception::Outbound(Outbound {
+                network: network.to_string(),
+                port: port.map(u32::from),
+                protocol: i32::from(proto::TransportProtocol::from(protocol)),
+            }),
+            FirewallException::Inbound { port, protocol } => Exception::Inbound(Inbound {
+                port: u32::from(port),
+                protocol: i32::from(proto::TransportProtocol::from(protocol)),
+            }),
+        };
+        proto::FirewallException {
+            exception: Some(exception),
+        }
+    }
+}
+
+impl TryFrom<proto::FirewallException> for talpid_types::net::firewall::FirewallException {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(exception: proto::FirewallException) -> Result<Self, Self::Error> {
+        use proto::firewall_exception::Exception;
+        use talpid_types::net::firewall::FirewallException;
+
+        let try_port = |port: u32| {
+            u16::try_from(port).map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))
+        };
+
+        match exception
+            .exception
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing firewall exception",
+            ))? {
+            Exception::Outbound(outbound) => Ok(FirewallException::Outbound {
+                network: outbound
+                    .network
+                    .parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid network"))?,
+                port: outbound.port.map(try_port).transpose()?,
+                protocol: try_transport_protocol_from_i32(outbound.protocol)?,
+            }),
+            Exception::Inbound(inbound) => Ok(FirewallException::Inbound {
+                port: try_port(inbound.port)?,
+                protocol: try_transport_protocol_from_i32(inbound.protocol)?,
+            }),
+        }
+    }
+}
+
 impl From<talpid_types::net::IpVersion> for proto::IpVersion {
     fn from(version: talpid_types::net::IpVersion) -> Self {
         match version {
@@ -211,7 +307,7 @@ mod proxy {
 
     use crate::types::{proto, FromProtobufTypeError};
     use talpid_types::net::proxy::{
-        CustomProxy, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        CustomProxy, HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
     };
 
     impl TryFrom<proto::CustomProxy> for CustomProxy {
@@ -228,6 +324,9 @@ mod proxy {
                 Some(proto::custom_proxy::ProxyMethod::Shadowsocks(shadowsocks)) => {
                     CustomProxy::Shadowsocks(Shadowsocks::try_from(shadowsocks)?)
                 }
+                Some(proto::custom_proxy::ProxyMethod::HttpConnect(http)) => {
+                    CustomProxy::HttpConnect(HttpConnect::try_from(http)?)
+                }
                 None => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "CustomProxy missing proxy_method field",
@@ -277,6 +376,29 @@ mod proxy {
         }
     }
 
+    impl TryFrom<proto::HttpConnect> for HttpConnect {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpConnect) -> Result<Self, Self::Error> {
+            let ip = value.ip.parse::<Ipv4Addr>().map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Could not parse HTTP CONNECT message from protobuf",
+                )
+            })?;
+            let port = value.port as u16;
+            let mut http = match value.auth {
+                Some(credentials) => {
+                    let auth = HttpAuth::try_from(credentials)?;
+                    HttpConnect::new_with_authentication((ip, port), auth)
+                }
+                None => HttpConnect::new((ip, port)),
+            };
+            http.tls = value.tls;
+
+            Ok(http)
+        }
+    }
+
     impl TryFrom<proto::Shadowsocks> for Shadowsocks {
         type Error = FromProtobufTypeError;
 
@@ -314,6 +436,11 @@ mod proxy {
                             config,
                         ))
                     }
+                    CustomProxy::HttpConnect(config) => {
+                        proto::custom_proxy::ProxyMethod::HttpConnect(proto::HttpConnect::from(
+                            config,
+                        ))
+                    }
                 }),
             }
         }
@@ -353,6 +480,17 @@ mod proxy {
         }
     }
 
+    impl From<HttpConnect> for proto::HttpConnect {
+        fn from(value: HttpConnect) -> Self {
+            proto::HttpConnect {
+                ip: value.endpoint.ip().to_string(),
+                port: value.endpoint.port() as u32,
+                auth: value.auth.map(proto::HttpAuth::from),
+                tls: value.tls,
+            }
+        }
+    }
+
     impl From<SocksAuth> for proto::SocksAuth {
         fn from(value: SocksAuth) -> Self {
             proto::SocksAuth {
@@ -374,4 +512,26 @@ mod proxy {
             })
         }
     }
+
+    impl From<HttpAuth> for proto::HttpAuth {
+        fn from(value: HttpAuth) -> Self {
+            proto::HttpAuth {
+                username: value.username().to_string(),
+                password: value.password().to_string(),
+            }
+        }
+    }
+
+    impl TryFrom<proto::HttpAuth> for HttpAuth {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpAuth) -> Result<Self, Self::Error> {
+            HttpAuth::new(value.username, value.password).map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Failed to parse HTTP proxy with authentication. \
+                     Make sure the credentials are valid.",
+                )
+            })
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::profile::{
+    MacAddress, NetworkRule, ProfileList, SettingsOverrides, SettingsProfile,
+};
+
+impl From<SettingsProfile> for proto::SettingsProfile {
+    fn from(profile: SettingsProfile) -> Self {
+        Self {
+            name: profile.name,
+            overrides: Some(proto::settings_profile::Overrides::from(profile.overrides)),
+            rules: profile
+                .rules
+                .into_iter()
+                .map(proto::settings_profile::NetworkRule::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::SettingsProfile> for SettingsProfile {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(profile: proto::SettingsProfile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: profile.name,
+            overrides: profile
+                .overrides
+                .map(SettingsOverrides::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            rules: profile
+                .rules
+                .into_iter()
+                .map(NetworkRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl From<SettingsOverrides> for proto::settings_profile::Overrides {
+    fn from(overrides: SettingsOverrides) -> Self {
+        Self {
+            relay_settings: overrides.relay_settings.map(proto::RelaySettings::from),
+            allow_lan: overrides.allow_lan,
+            #[cfg(not(target_os = "android"))]
+            block_when_disconnected: overrides.block_when_disconnected,
+            #[cfg(target_os = "android")]
+            block_when_disconnected: None,
+            enable_ipv6: overrides.enable_ipv6,
+            dns_options: overrides.dns_options.as_ref().map(proto::DnsOptions::from),
+        }
+    }
+}
+
+impl TryFrom<proto::settings_profile::Overrides> for SettingsOverrides {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(overrides: proto::settings_profile::Overrides) -> Result<Self, Self::Error> {
+        Ok(Self {
+            relay_settings: overrides
+                .relay_settings
+                .map(mullvad_types::relay_constraints::RelaySettings::try_from)
+                .transpose()?,
+            allow_lan: overrides.allow_lan,
+            #[cfg(not(target_os = "android"))]
+            block_when_disconnected: overrides.block_when_disconnected,
+            enable_ipv6: overrides.enable_ipv6,
+            dns_options: overrides
+                .dns_options
+                .map(mullvad_types::settings::DnsOptions::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+impl From<NetworkRule> for proto::settings_profile::NetworkRule {
+    fn from(rule: NetworkRule) -> Self {
+        use proto::settings_profile::network_rule::Rule;
+
+        let rule = match rule {
+            NetworkRule::GatewayMac(mac) => Rule::GatewayMac(mac.to_string()),
+            NetworkRule::Interface(interface) => Rule::Interface(interface),
+            NetworkRule::DhcpDomain(domain) => Rule::DhcpDomain(domain),
+        };
+        Self { rule: Some(rule) }
+    }
+}
+
+impl TryFrom<proto::settings_profile::NetworkRule> for NetworkRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::settings_profile::NetworkRule) -> Result<Self, Self::Error> {
+        use proto::settings_profile::network_rule::Rule;
+
+        match rule.rule {
+            Some(Rule::GatewayMac(mac)) => Ok(NetworkRule::GatewayMac(
+                mac.parse::<MacAddress>()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid MAC address"))?,
+            )),
+            Some(Rule::Interface(interface)) => Ok(NetworkRule::Interface(interface)),
+            Some(Rule::DhcpDomain(domain)) => Ok(NetworkRule::DhcpDomain(domain)),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing network rule",
+            )),
+        }
+    }
+}
+
+impl From<ProfileList> for proto::SettingsProfiles {
+    fn from(list: ProfileList) -> Self {
+        Self {
+            profiles: list
+                .profiles
+                .into_iter()
+                .map(proto::SettingsProfile::from)
+                .collect(),
+            active_profile: list.active_profile,
+        }
+    }
+}
+
+impl TryFrom<proto::SettingsProfiles> for ProfileList {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(list: proto::SettingsProfiles) -> Result<Self, Self::Error> {
+        Ok(Self {
+            profiles: list
+                .profiles
+                .into_iter()
+                .map(SettingsProfile::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            active_profile: list.active_profile,
+        })
+    }
+}
@@ -39,6 +39,12 @@ impl TryFrom<&proto::WireguardConstraints>
                     .ok()
                 })
                 .unwrap_or(Constraint::Any),
+            middle_locations: constraints
+                .middle_locations
+                .iter()
+                .cloned()
+                .map(Constraint::<mullvad_types::relay_constraints::LocationConstraint>::try_from)
+                .collect::<Result<_, _>>()?,
         })
     }
 }
@@ -123,6 +129,7 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         tunnel_protocol,
                         wireguard_constraints,
                         openvpn_constraints,
+                        prefer_low_latency: settings.prefer_low_latency,
                     },
                 ))
             }
@@ -145,8 +152,25 @@ impl From<mullvad_types::relay_constraints::BridgeState> for proto::BridgeState
 
 impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::ObfuscationSettings {
     fn from(settings: &mullvad_types::relay_constraints::ObfuscationSettings) -> Self {
+        Self {
+            selected_obfuscation: i32::from(
+                proto::obfuscation_settings::SelectedObfuscation::from(
+                    settings.selected_obfuscation,
+                ),
+            ),
+            udp2tcp: Some(proto::Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
+            shadowsocks: Some(proto::ShadowsocksSettings::from(&settings.shadowsocks)),
+            socks5: Some(proto::Socks5ObfuscationSettings::from(&settings.socks5)),
+        }
+    }
+}
+
+impl From<mullvad_types::relay_constraints::SelectedObfuscation>
+    for proto::obfuscation_settings::SelectedObfuscation
+{
+    fn from(obfuscation: mullvad_types::relay_constraints::SelectedObfuscation) -> Self {
         use mullvad_types::relay_constraints::SelectedObfuscation;
-        let selected_obfuscation = i32::from(match settings.selected_obfuscation {
+        match obfuscation {
             SelectedObfuscation::Auto => proto::obfuscation_settings::SelectedObfuscation::Auto,
             SelectedObfuscation::Off => proto::obfuscation_settings::SelectedObfuscation::Off,
             SelectedObfuscation::Udp2Tcp => {
@@ -155,11 +179,18 @@ impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::Obf
             SelectedObfuscation::Shadowsocks => {
                 proto::obfuscation_settings::SelectedObfuscation::Shadowsocks
             }
-        });
+            SelectedObfuscation::Quic => proto::obfuscation_settings::SelectedObfuscation::Quic,
+            SelectedObfuscation::Socks5 => proto::obfuscation_settings::SelectedObfuscation::Socks5,
+        }
+    }
+}
+
+impl From<&mullvad_types::relay_constraints::Socks5ObfuscationSettings>
+    for proto::Socks5ObfuscationSettings
+{
+    fn from(settings: &mullvad_types::relay_constraints::Socks5ObfuscationSettings) -> Self {
         Self {
-            selected_obfuscation,
-            udp2tcp: Some(proto::Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
-            shadowsocks: Some(proto::ShadowsocksSettings::from(&settings.shadowsocks)),
+            proxy: settings.proxy.clone().map(proto::Socks5Remote::from),
         }
     }
 }
@@ -271,6 +302,18 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                             .entry_location
                             .option()
                             .map(proto::LocationConstraint::from),
+                        middle_locations: constraints
+                            .wireguard_constraints
+                            .middle_locations
+                            .iter()
+                            .map(|location| {
+                                location
+                                    .clone()
+                                    .option()
+                                    .map(proto::LocationConstraint::from)
+                                    .unwrap_or_default()
+                            })
+                            .collect(),
                     }),
 
                     openvpn_constraints: Some(proto::OpenvpnConstraints {
@@ -280,6 +323,8 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                             .option()
                             .map(proto::TransportPort::from),
                     }),
+
+                    prefer_low_latency: constraints.prefer_low_latency,
                 })
             }
         };
@@ -443,20 +488,8 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
     type Error = FromProtobufTypeError;
 
     fn try_from(settings: proto::ObfuscationSettings) -> Result<Self, Self::Error> {
-        use mullvad_types::relay_constraints::SelectedObfuscation;
-        use proto::obfuscation_settings::SelectedObfuscation as IpcSelectedObfuscation;
         let selected_obfuscation =
-            match IpcSelectedObfuscation::try_from(settings.selected_obfuscation) {
-                Ok(IpcSelectedObfuscation::Auto) => SelectedObfuscation::Auto,
-                Ok(IpcSelectedObfuscation::Off) => SelectedObfuscation::Off,
-                Ok(IpcSelectedObfuscation::Udp2tcp) => SelectedObfuscation::Udp2Tcp,
-                Ok(IpcSelectedObfuscation::Shadowsocks) => SelectedObfuscation::Shadowsocks,
-                Err(_) => {
-                    return Err(FromProtobufTypeError::InvalidArgument(
-                        "invalid obfuscation settings",
-                    ));
-                }
-            };
+            try_selected_obfuscation_from_i32(settings.selected_obfuscation)?;
 
         let udp2tcp = match settings.udp2tcp {
             Some(settings) => {
@@ -479,10 +512,52 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
             }
         };
 
+        // Older clients do not know about SOCKS5 obfuscation
+        let socks5 = match settings.socks5 {
+            Some(settings) => {
+                mullvad_types::relay_constraints::Socks5ObfuscationSettings::try_from(settings)?
+            }
+            None => Default::default(),
+        };
+
         Ok(Self {
             selected_obfuscation,
             udp2tcp,
             shadowsocks,
+            socks5,
+        })
+    }
+}
+
+pub fn try_selected_obfuscation_from_i32(
+    selected_obfuscation: i32,
+) -> Result<mullvad_types::relay_constraints::SelectedObfuscation, FromProtobufTypeError> {
+    use mullvad_types::relay_constraints::SelectedObfuscation;
+    use proto::obfuscation_settings::SelectedObfuscation as IpcSelectedObfuscation;
+    match IpcSelectedObfuscation::try_from(selected_obfuscation) {
+        Ok(IpcSelectedObfuscation::Auto) => Ok(SelectedObfuscation::Auto),
+        Ok(IpcSelectedObfuscation::Off) => Ok(SelectedObfuscation::Off),
+        Ok(IpcSelectedObfuscation::Udp2tcp) => Ok(SelectedObfuscation::Udp2Tcp),
+        Ok(IpcSelectedObfuscation::Shadowsocks) => Ok(SelectedObfuscation::Shadowsocks),
+        Ok(IpcSelectedObfuscation::Quic) => Ok(SelectedObfuscation::Quic),
+        Ok(IpcSelectedObfuscation::Socks5) => Ok(SelectedObfuscation::Socks5),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid obfuscation settings",
+        )),
+    }
+}
+
+impl TryFrom<proto::Socks5ObfuscationSettings>
+    for mullvad_types::relay_constraints::Socks5ObfuscationSettings
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::Socks5ObfuscationSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            proxy: settings
+                .proxy
+                .map(talpid_types::net::proxy::Socks5Remote::try_from)
+                .transpose()?,
         })
     }
 }
@@ -579,6 +654,82 @@ impl TryFrom<proto::RelayOverride> for mullvad_types::relay_constraints::RelayOv
     }
 }
 
+impl From<mullvad_types::relay_constraints::RetryAttempt> for proto::RetryAttempt {
+    fn from(attempt: mullvad_types::relay_constraints::RetryAttempt) -> Self {
+        use mullvad_types::relay_constraints::RetryAttempt;
+        use proto::retry_attempt;
+
+        let attempt = match attempt {
+            RetryAttempt::Any => retry_attempt::Attempt::Any(retry_attempt::Any {}),
+            RetryAttempt::Wireguard {
+                port,
+                ip_version,
+                obfuscation,
+            } => retry_attempt::Attempt::Wireguard(retry_attempt::Wireguard {
+                port: port.map(u32::from).option(),
+                ip_version: ip_version
+                    .option()
+                    .map(|ipv| i32::from(proto::IpVersion::from(ipv))),
+                obfuscation: i32::from(proto::obfuscation_settings::SelectedObfuscation::from(
+                    obfuscation,
+                )),
+            }),
+            RetryAttempt::Openvpn { port, bridge } => {
+                retry_attempt::Attempt::Openvpn(retry_attempt::Openvpn {
+                    port: port.option().map(proto::TransportPort::from),
+                    bridge,
+                })
+            }
+        };
+
+        Self {
+            attempt: Some(attempt),
+        }
+    }
+}
+
+impl TryFrom<proto::RetryAttempt> for mullvad_types::relay_constraints::RetryAttempt {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(attempt: proto::RetryAttempt) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_constraints::{RetryAttempt, TransportPort};
+        use proto::retry_attempt;
+
+        let attempt = attempt
+            .attempt
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing retry attempt",
+            ))?;
+
+        match attempt {
+            retry_attempt::Attempt::Any(_) => Ok(RetryAttempt::Any),
+            retry_attempt::Attempt::Wireguard(attempt) => {
+                let ip_version = attempt
+                    .ip_version
+                    .map(|version| {
+                        proto::IpVersion::try_from(version)
+                            .map(talpid_types::net::IpVersion::from)
+                            .map_err(|_| {
+                                FromProtobufTypeError::InvalidArgument(
+                                    "invalid IP protocol version",
+                                )
+                            })
+                    })
+                    .transpose()?;
+                Ok(RetryAttempt::Wireguard {
+                    port: Constraint::from(attempt.port.map(|port| port as u16)),
+                    ip_version: Constraint::from(ip_version),
+                    obfuscation: try_selected_obfuscation_from_i32(attempt.obfuscation)?,
+                })
+            }
+            retry_attempt::Attempt::Openvpn(attempt) => Ok(RetryAttempt::Openvpn {
+                port: Constraint::from(attempt.port.map(TransportPort::try_from).transpose()?),
+                bridge: attempt.bridge,
+            }),
+        }
+    }
+}
+
 pub fn try_providers_constraint_from_proto(
     providers: &[String],
 ) -> Result<Constraint<mullvad_types::relay_constraints::Providers>, FromProtobufTypeError> {
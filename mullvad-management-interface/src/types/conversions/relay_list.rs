@@ -140,6 +140,10 @@ impl From<mullvad_types::relay_list::Relay> for proto::Relay {
                             .iter()
                             .map(|addr| addr.to_string())
                             .collect(),
+                        quic: data.quic.map(|quic| proto::QuicEndpointData {
+                            domain: quic.domain,
+                            addr_in: quic.addr_in.iter().map(|addr| addr.to_string()).collect(),
+                        }),
                     },
                 )),
                 _ => None,
@@ -266,6 +270,10 @@ impl TryFrom<proto::Relay> for mullvad_types::relay_list::Relay {
                                 })
                             })
                             .collect::<Result<_, FromProtobufTypeError>>()?,
+                        quic: data
+                            .quic
+                            .map(mullvad_types::relay_list::QuicEndpointData::try_from)
+                            .transpose()?,
                     },
                 )
             }
@@ -373,6 +381,25 @@ impl TryFrom<proto::ShadowsocksEndpointData>
     }
 }
 
+impl TryFrom<proto::QuicEndpointData> for mullvad_types::relay_list::QuicEndpointData {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(quic: proto::QuicEndpointData) -> Result<Self, FromProtobufTypeError> {
+        Ok(mullvad_types::relay_list::QuicEndpointData {
+            domain: quic.domain,
+            addr_in: quic
+                .addr_in
+                .iter()
+                .map(|addr| {
+                    addr.parse().map_err(|_err| {
+                        FromProtobufTypeError::InvalidArgument("invalid QUIC address")
+                    })
+                })
+                .collect::<Result<_, FromProtobufTypeError>>()?,
+        })
+    }
+}
+
 impl TryFrom<proto::WireguardEndpointData> for mullvad_types::relay_list::WireguardEndpointData {
     type Error = FromProtobufTypeError;
 
@@ -4,7 +4,6 @@ use talpid_types::ErrorExt;
 
 impl From<&mullvad_types::settings::Settings> for proto::Settings {
     fn from(settings: &mullvad_types::settings::Settings) -> Self {
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
         let split_tunnel = {
             let apps = settings
                 .split_tunnel
@@ -22,10 +21,9 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
             Some(proto::SplitTunnelSettings {
                 enable_exclusions: settings.split_tunnel.enable_exclusions,
                 apps,
+                mode: Some(proto::SplitTunnelMode::from(settings.split_tunnel.mode)),
             })
         };
-        #[cfg(target_os = "linux")]
-        let split_tunnel = None;
 
         Self {
             relay_settings: Some(proto::RelaySettings::from(settings.get_relay_settings())),
@@ -57,6 +55,29 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .cloned()
                 .map(proto::RelayOverride::from)
                 .collect(),
+            retry_order: settings
+                .retry_order
+                .as_ref()
+                .map(|retry_order| proto::RetryOrder {
+                    attempts: retry_order
+                        .iter()
+                        .cloned()
+                        .map(proto::RetryAttempt::from)
+                        .collect(),
+                }),
+            profiles: settings
+                .profiles
+                .iter()
+                .cloned()
+                .map(proto::SettingsProfile::from)
+                .collect(),
+            firewall_exceptions: settings
+                .firewall_exceptions
+                .iter()
+                .copied()
+                .map(proto::FirewallException::from)
+                .collect(),
+            metrics: Some(proto::MetricsSettings::from(settings.metrics.clone())),
         }
     }
 }
@@ -85,7 +106,52 @@ impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
                     .iter()
                     .map(|addr| addr.to_string())
                     .collect(),
+                encryption: options
+                    .custom_options
+                    .encryption
+                    .as_ref()
+                    .map(proto::DnsEncryption::from),
             }),
+            split_rules: options
+                .split_rules
+                .iter()
+                .map(proto::SplitDnsRule::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&mullvad_types::settings::DnsEncryption> for proto::DnsEncryption {
+    fn from(encryption: &mullvad_types::settings::DnsEncryption) -> Self {
+        use mullvad_types::settings::EncryptedDnsProtocol;
+
+        let (protocol, path) = match &encryption.protocol {
+            EncryptedDnsProtocol::Https { path, .. } => {
+                (proto::dns_encryption::Protocol::Https, path.clone())
+            }
+            EncryptedDnsProtocol::Tls { .. } => {
+                (proto::dns_encryption::Protocol::Tls, String::new())
+            }
+        };
+        proto::DnsEncryption {
+            protocol: protocol as i32,
+            hostname: encryption.protocol.hostname().to_owned(),
+            port: u32::from(encryption.protocol.port()),
+            spki_pin: encryption.spki_pin.as_ref().map(|pin| pin.to_string()),
+            path,
+        }
+    }
+}
+
+impl From<&mullvad_types::settings::SplitDnsRule> for proto::SplitDnsRule {
+    fn from(rule: &mullvad_types::settings::SplitDnsRule) -> Self {
+        proto::SplitDnsRule {
+            domain: rule.domain().to_owned(),
+            resolvers: rule
+                .resolvers()
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect(),
         }
     }
 }
@@ -107,6 +173,9 @@ impl From<&mullvad_types::settings::TunnelOptions> for proto::TunnelOptions {
                 daita: Some(proto::DaitaSettings::from(options.wireguard.daita.clone())),
                 #[cfg(not(daita))]
                 daita: None,
+                connectivity: Some(proto::ConnectivityCheckOptions::from(
+                    options.wireguard.connectivity,
+                )),
             }),
             generic: Some(proto::tunnel_options::GenericOptions {
                 enable_ipv6: options.generic.enable_ipv6,
@@ -162,12 +231,16 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .ok_or(FromProtobufTypeError::InvalidArgument(
                     "missing api access methods settings",
                 ))?;
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
         let split_tunnel = settings
             .split_tunnel
             .ok_or(FromProtobufTypeError::InvalidArgument(
                 "missing split tunnel options",
             ))?;
+        let metrics = settings
+            .metrics
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing metrics settings",
+            ))?;
 
         Ok(Self {
             relay_settings: mullvad_types::relay_constraints::RelaySettings::try_from(
@@ -187,9 +260,31 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .into_iter()
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
+            retry_order: settings
+                .retry_order
+                .map(|retry_order| {
+                    retry_order
+                        .attempts
+                        .into_iter()
+                        .map(mullvad_types::relay_constraints::RetryAttempt::try_from)
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?,
+            profiles: mullvad_types::profile::ProfileSettings::from(
+                settings
+                    .profiles
+                    .into_iter()
+                    .map(mullvad_types::profile::SettingsProfile::try_from)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
             show_beta_releases: settings.show_beta_releases,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
-            split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
+            split_tunnel: mullvad_types::settings::SplitTunnelSettings::try_from(split_tunnel)?,
+            firewall_exceptions: settings
+                .firewall_exceptions
+                .into_iter()
+                .map(talpid_types::net::firewall::FirewallException::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            metrics: mullvad_types::metrics::MetricsSettings::try_from(metrics)?,
             obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
                 obfuscation_settings,
             )?,
@@ -223,13 +318,49 @@ pub fn try_bridge_state_from_i32(
     }
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-impl From<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
-    fn from(value: proto::SplitTunnelSettings) -> Self {
-        use mullvad_types::settings::{SplitApp, SplitTunnelSettings};
-        SplitTunnelSettings {
+impl TryFrom<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(value: proto::SplitTunnelSettings) -> Result<Self, Self::Error> {
+        use mullvad_types::settings::{SplitApp, SplitTunnelMode, SplitTunnelSettings};
+        Ok(SplitTunnelSettings {
             enable_exclusions: value.enable_exclusions,
             apps: value.apps.into_iter().map(SplitApp::from).collect(),
+            mode: value
+                .mode
+                .map(SplitTunnelMode::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl From<mullvad_types::settings::SplitTunnelMode> for proto::SplitTunnelMode {
+    fn from(mode: mullvad_types::settings::SplitTunnelMode) -> Self {
+        use mullvad_types::settings::SplitTunnelMode;
+        Self {
+            mode: i32::from(match mode {
+                SplitTunnelMode::Exclude => proto::split_tunnel_mode::Mode::Exclude,
+                SplitTunnelMode::Include => proto::split_tunnel_mode::Mode::Include,
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::SplitTunnelMode> for mullvad_types::settings::SplitTunnelMode {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(mode: proto::SplitTunnelMode) -> Result<Self, Self::Error> {
+        match proto::split_tunnel_mode::Mode::try_from(mode.mode) {
+            Ok(proto::split_tunnel_mode::Mode::Exclude) => {
+                Ok(mullvad_types::settings::SplitTunnelMode::Exclude)
+            }
+            Ok(proto::split_tunnel_mode::Mode::Include) => {
+                Ok(mullvad_types::settings::SplitTunnelMode::Include)
+            }
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid split tunnel mode",
+            )),
         }
     }
 }
@@ -294,6 +425,12 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "missing daita settings",
                     ))?,
+                connectivity: wireguard_options
+                    .connectivity
+                    .map(net::wireguard::ConnectivityCheckOptions::try_from)
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "missing connectivity check options",
+                    ))??,
             },
             generic: net::GenericTunnelOptions {
                 enable_ipv6: generic_options.enable_ipv6,
@@ -309,8 +446,9 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
     fn try_from(options: proto::DnsOptions) -> Result<Self, Self::Error> {
         use mullvad_types::settings::{
             CustomDnsOptions as MullvadCustomDnsOptions,
-            DefaultDnsOptions as MullvadDefaultDnsOptions, DnsOptions as MullvadDnsOptions,
-            DnsState as MullvadDnsState,
+            DefaultDnsOptions as MullvadDefaultDnsOptions, DnsEncryption as MullvadDnsEncryption,
+            DnsOptions as MullvadDnsOptions, DnsState as MullvadDnsState,
+            SplitDnsRule as MullvadSplitDnsRule,
         };
 
         let state = match proto::dns_options::DnsState::try_from(options.state) {
@@ -356,7 +494,67 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?,
+                encryption: custom_options
+                    .encryption
+                    .map(MullvadDnsEncryption::try_from)
+                    .transpose()?,
             },
+            split_rules: options
+                .split_rules
+                .into_iter()
+                .map(MullvadSplitDnsRule::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
         })
     }
 }
+
+impl TryFrom<proto::DnsEncryption> for mullvad_types::settings::DnsEncryption {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(encryption: proto::DnsEncryption) -> Result<Self, Self::Error> {
+        use mullvad_types::settings::EncryptedDnsProtocol;
+
+        let hostname = encryption.hostname;
+        let port = u16::try_from(encryption.port)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid DNS server port"))?;
+        let protocol = match proto::dns_encryption::Protocol::try_from(encryption.protocol) {
+            Ok(proto::dns_encryption::Protocol::Https) => {
+                EncryptedDnsProtocol::https(hostname, port, &encryption.path).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid DNS-over-HTTPS path")
+                })?
+            }
+            Ok(proto::dns_encryption::Protocol::Tls) => {
+                EncryptedDnsProtocol::Tls { hostname, port }
+            }
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid encrypted DNS protocol",
+                ))
+            }
+        };
+        let spki_pin = encryption
+            .spki_pin
+            .map(|pin| pin.parse())
+            .transpose()
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid SPKI pin"))?;
+
+        Ok(Self { protocol, spki_pin })
+    }
+}
+
+impl TryFrom<proto::SplitDnsRule> for mullvad_types::settings::SplitDnsRule {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(rule: proto::SplitDnsRule) -> Result<Self, Self::Error> {
+        let resolvers = rule
+            .resolvers
+            .into_iter()
+            .map(|addr| {
+                addr.parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP address"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(&rule.domain, resolvers)
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid split DNS rule"))
+    }
+}
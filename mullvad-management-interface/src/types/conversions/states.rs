@@ -47,23 +47,27 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                 endpoint,
                 location,
                 feature_indicators,
+                active_profile,
             } => proto::tunnel_state::State::Connecting(proto::tunnel_state::Connecting {
                 relay_info: Some(proto::TunnelStateRelayInfo {
                     tunnel_endpoint: Some(proto::TunnelEndpoint::from(endpoint)),
                     location: location.map(proto::GeoIpLocation::from),
                 }),
                 feature_indicators: Some(proto::FeatureIndicators::from(feature_indicators)),
+                active_profile,
             }),
             MullvadTunnelState::Connected {
                 endpoint,
                 location,
                 feature_indicators,
+                active_profile,
             } => proto::tunnel_state::State::Connected(proto::tunnel_state::Connected {
                 relay_info: Some(proto::TunnelStateRelayInfo {
                     tunnel_endpoint: Some(proto::TunnelEndpoint::from(endpoint)),
                     location: location.map(proto::GeoIpLocation::from),
                 }),
                 feature_indicators: Some(proto::FeatureIndicators::from(feature_indicators)),
+                active_profile,
             }),
             MullvadTunnelState::Disconnecting(after_disconnect) => {
                 proto::tunnel_state::State::Disconnecting(proto::tunnel_state::Disconnecting {
@@ -280,6 +284,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         location,
                     }),
                 feature_indicators,
+                active_profile,
             })) => MullvadState::Connecting {
                 endpoint: talpid_net::TunnelEndpoint::try_from(tunnel_endpoint)?,
                 location: location
@@ -290,6 +295,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "Missing feature indicators",
                     ))?,
+                active_profile,
             },
             Some(proto::tunnel_state::State::Connected(proto::tunnel_state::Connected {
                 relay_info:
@@ -298,6 +304,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         location,
                     }),
                 feature_indicators,
+                active_profile,
             })) => MullvadState::Connected {
                 endpoint: talpid_net::TunnelEndpoint::try_from(tunnel_endpoint)?,
                 location: location
@@ -308,6 +315,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "Missing feature indicators",
                     ))?,
+                active_profile,
             },
             Some(proto::tunnel_state::State::Disconnecting(
                 proto::tunnel_state::Disconnecting { after_disconnect },
@@ -92,3 +92,116 @@ impl From<proto::DaitaSettings> for mullvad_types::wireguard::DaitaSettings {
         }
     }
 }
+
+impl From<talpid_types::net::wireguard::ConnectivityCheckOptions>
+    for proto::ConnectivityCheckOptions
+{
+    fn from(options: talpid_types::net::wireguard::ConnectivityCheckOptions) -> Self {
+        use talpid_types::net::wireguard::PingTarget;
+
+        let to_proto = |duration: std::time::Duration| {
+            prost_types::Duration::try_from(duration)
+                .expect("Failed to convert std::time::Duration to prost_types::Duration")
+        };
+        proto::ConnectivityCheckOptions {
+            establish_timeout: Some(to_proto(options.establish_timeout)),
+            ping_timeout: Some(to_proto(options.ping_timeout)),
+            traffic_timeout: Some(to_proto(options.traffic_timeout)),
+            ping_interval: Some(to_proto(options.ping_interval)),
+            ping_address: match options.ping_target {
+                PingTarget::Gateway => None,
+                PingTarget::Address(address) => Some(address.to_string()),
+            },
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectivityCheckOptions>
+    for talpid_types::net::wireguard::ConnectivityCheckOptions
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: proto::ConnectivityCheckOptions) -> Result<Self, Self::Error> {
+        use talpid_types::net::wireguard::PingTarget;
+
+        let from_proto = |duration: Option<prost_types::Duration>| {
+            duration
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing connectivity check timeout",
+                ))
+                .and_then(|duration| {
+                    std::time::Duration::try_from(duration)
+                        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))
+                })
+        };
+        let ping_target = match options.ping_address {
+            None => PingTarget::Gateway,
+            Some(address) => PingTarget::Address(address.parse().map_err(|_| {
+                FromProtobufTypeError::InvalidArgument("invalid ping target address")
+            })?),
+        };
+
+        Ok(talpid_types::net::wireguard::ConnectivityCheckOptions {
+            establish_timeout: from_proto(options.establish_timeout)?,
+            ping_timeout: from_proto(options.ping_timeout)?,
+            traffic_timeout: from_proto(options.traffic_timeout)?,
+            ping_interval: from_proto(options.ping_interval)?,
+            ping_target,
+        })
+    }
+}
+
+impl From<mullvad_types::wireguard::TunnelStats> for proto::TunnelStats {
+    fn from(stats: mullvad_types::wireguard::TunnelStats) -> Self {
+        proto::TunnelStats {
+            peers: stats
+                .peers
+                .into_iter()
+                .map(|peer| proto::tunnel_stats::PeerStats {
+                    public_key: peer.public_key.as_bytes().to_vec(),
+                    tx_bytes: peer.tx_bytes,
+                    rx_bytes: peer.rx_bytes,
+                    tx_rate: peer.tx_rate,
+                    rx_rate: peer.rx_rate,
+                    last_handshake: peer.last_handshake.map(|time| Timestamp {
+                        seconds: time.timestamp(),
+                        nanos: time.timestamp_subsec_nanos() as i32,
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::TunnelStats> for mullvad_types::wireguard::TunnelStats {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(stats: proto::TunnelStats) -> Result<Self, Self::Error> {
+        let peers = stats
+            .peers
+            .into_iter()
+            .map(|peer| {
+                let last_handshake = peer
+                    .last_handshake
+                    .map(|time| {
+                        DateTime::from_timestamp(time.seconds, time.nanos as u32)
+                            .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+                    })
+                    .transpose()?;
+                Ok(mullvad_types::wireguard::PeerTrafficStats {
+                    public_key: talpid_types::net::wireguard::PublicKey::try_from(
+                        peer.public_key.as_slice(),
+                    )
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid wireguard key"))?,
+                    tx_bytes: peer.tx_bytes,
+                    rx_bytes: peer.rx_bytes,
+                    tx_rate: peer.tx_rate,
+                    rx_rate: peer.rx_rate,
+                    last_handshake,
+                })
+            })
+            .collect::<Result<_, FromProtobufTypeError>>()?;
+
+        Ok(mullvad_types::wireguard::TunnelStats { peers })
+    }
+}
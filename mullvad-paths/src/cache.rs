@@ -1,6 +1,10 @@
 use crate::Result;
 use std::{env, path::PathBuf};
 
+/// Name of the connection history journal in the cache directory. Each line contains one
+/// JSON-encoded entry.
+pub const CONNECTION_HISTORY_FILENAME: &str = "connection-history.jsonl";
+
 /// Creates and returns the cache directory pointed to by `MULLVAD_CACHE_DIR`, or the default
 /// one if that variable is unset.
 pub fn cache_dir() -> Result<PathBuf> {
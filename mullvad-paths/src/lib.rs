@@ -72,7 +72,9 @@ fn create_and_return(
 }
 
 mod cache;
-pub use crate::cache::{cache_dir, get_cache_dir, get_default_cache_dir};
+pub use crate::cache::{
+    cache_dir, get_cache_dir, get_default_cache_dir, CONNECTION_HISTORY_FILENAME,
+};
 
 mod logs;
 pub use crate::logs::{get_default_log_dir, get_log_dir, log_dir};
@@ -0,0 +1,274 @@
+//! Problem report bundles are uncompressed tar (ustar) archives with a separate file for each log
+//! and each piece of diagnostic information. Before a bundle is sent, it is flattened into the
+//! same format as a text report, since that is what the API accepts.
+
+use crate::LOG_DELIMITER;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+const BLOCK_SIZE: usize = 512;
+
+const NAME_LEN: usize = 100;
+const SIZE_OFFSET: usize = 124;
+const SIZE_LEN: usize = 12;
+const CHECKSUM_OFFSET: usize = 148;
+const CHECKSUM_LEN: usize = 8;
+const MAGIC_OFFSET: usize = 257;
+const MAGIC: &[u8] = b"ustar\0";
+
+/// Name of the bundle entry that holds the system information, as `key: value` lines.
+pub const METADATA_ENTRY: &str = "metadata.txt";
+
+/// Write `entries` as a tar archive. Names longer than the 100 bytes supported by the format are
+/// truncated.
+#[cfg(not(target_os = "android"))]
+pub fn write<W: Write>(mut output: W, entries: &[(String, String)]) -> io::Result<()> {
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    for (name, content) in entries {
+        output.write_all(&header(name, content.len() as u64, mtime))?;
+        output.write_all(content.as_bytes())?;
+        output.write_all(&[0; BLOCK_SIZE][..padding(content.len())])?;
+    }
+    // The archive ends with two empty blocks
+    output.write_all(&[0; 2 * BLOCK_SIZE])?;
+    output.flush()
+}
+
+#[cfg(not(target_os = "android"))]
+fn header(name: &str, size: u64, mtime: u64) -> [u8; BLOCK_SIZE] {
+    let mut header = [0u8; BLOCK_SIZE];
+
+    let mut name_len = name.len().min(NAME_LEN);
+    while !name.is_char_boundary(name_len) {
+        name_len -= 1;
+    }
+    header[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+
+    let mut set_octal = |offset: usize, len: usize, value: u64| {
+        let field = format!("{value:0width$o}\0", width = len - 1);
+        header[offset..offset + len].copy_from_slice(field.as_bytes());
+    };
+    // mode, uid and gid
+    set_octal(100, 8, 0o444);
+    set_octal(108, 8, 0);
+    set_octal(116, 8, 0);
+    set_octal(SIZE_OFFSET, SIZE_LEN, size);
+    set_octal(136, 12, mtime);
+    // Regular file
+    header[156] = b'0';
+    header[MAGIC_OFFSET..MAGIC_OFFSET + MAGIC.len()].copy_from_slice(MAGIC);
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with the checksum field itself set to spaces
+    header[CHECKSUM_OFFSET..CHECKSUM_OFFSET + CHECKSUM_LEN].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    let field = format!("{checksum:06o}\0 ");
+    header[CHECKSUM_OFFSET..CHECKSUM_OFFSET + CHECKSUM_LEN].copy_from_slice(field.as_bytes());
+
+    header
+}
+
+fn padding(size: usize) -> usize {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+/// Returns whether the file at `path` is a bundle rather than a text report.
+pub fn is_bundle(path: &Path) -> io::Result<bool> {
+    let mut header = [0u8; BLOCK_SIZE];
+    match File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(is_header(&header)),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+fn is_header(block: &[u8; BLOCK_SIZE]) -> bool {
+    // Accept both POSIX and GNU tar, in case a bundle has been unpacked and repacked
+    &block[MAGIC_OFFSET..MAGIC_OFFSET + 5] == b"ustar"
+}
+
+/// Read all entries of the bundle at `path`. Content that is not valid UTF-8 is replaced
+/// lossily. Entries that are not regular files, such as directories, are skipped.
+pub fn read(path: &Path) -> io::Result<Vec<(String, String)>> {
+    read_from(BufReader::new(File::open(path)?))
+}
+
+fn read_from<R: Read>(mut input: R) -> io::Result<Vec<(String, String)>> {
+    let mut entries = vec![];
+    let mut header = [0u8; BLOCK_SIZE];
+    loop {
+        match input.read_exact(&mut header) {
+            Ok(()) => (),
+            // Some archivers omit the trailing empty blocks
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        if !is_header(&header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid tar header",
+            ));
+        }
+
+        let size = parse_octal(&header[SIZE_OFFSET..SIZE_OFFSET + SIZE_LEN])?;
+        let mut content = vec![0u8; size];
+        input.read_exact(&mut content)?;
+        io::copy(
+            &mut (&mut input).take(padding(size) as u64),
+            &mut io::sink(),
+        )?;
+
+        let is_file = matches!(header[156], b'0' | 0);
+        if is_file {
+            let name_len = header[..NAME_LEN]
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(NAME_LEN);
+            let name = String::from_utf8_lossy(&header[..name_len]).into_owned();
+            entries.push((name, String::from_utf8_lossy(&content).into_owned()));
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_octal(field: &[u8]) -> io::Result<usize> {
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+    usize::from_str_radix(digits, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid size in tar header"))
+}
+
+/// Concatenate the entries of a bundle into a text report of at most `max_size` bytes. The
+/// metadata entry is always included first. Entries that do not fit are truncated to their last
+/// bytes, like logs are when a text report is collected, with the space shared evenly among them.
+pub fn flatten(entries: &[(String, String)], max_size: usize) -> String {
+    // IMPORTANT: Make sure this stays in sync with `ProblemReport::write_to`.
+    let mut report = String::from("System information:\n");
+    if let Some((_, metadata)) = entries.iter().find(|(name, _)| name == METADATA_ENTRY) {
+        report.push_str(metadata);
+        if !metadata.ends_with('\n') {
+            report.push('\n');
+        }
+    }
+    report.push('\n');
+
+    let entries: Vec<_> = entries
+        .iter()
+        .filter(|(name, _)| name != METADATA_ENTRY)
+        .collect();
+    let headers_len: usize = entries
+        .iter()
+        .map(|(name, _)| section(name, "").len())
+        .sum();
+    let space = max_size.saturating_sub(report.len() + headers_len);
+    let max_entry_size = fair_share(entries.iter().map(|(_, content)| content.len()), space);
+
+    for (name, content) in entries {
+        report.push_str(&section(name, tail(content, max_entry_size)));
+    }
+    report
+}
+
+/// Return the largest size that `sizes` can be capped to while still adding up to at most
+/// `space`.
+fn fair_share(sizes: impl Iterator<Item = usize>, mut space: usize) -> usize {
+    let mut sizes: Vec<usize> = sizes.collect();
+    sizes.sort_unstable();
+    let mut remaining = sizes.len();
+    for size in sizes {
+        let share = space / remaining;
+        if size > share {
+            return share;
+        }
+        space -= size;
+        remaining -= 1;
+    }
+    usize::MAX
+}
+
+/// Return the last `max_bytes` bytes of `content`, or fewer to start at a character boundary.
+fn tail(content: &str, max_bytes: usize) -> &str {
+    let mut start = content.len().saturating_sub(max_bytes);
+    while !content.is_char_boundary(start) {
+        start += 1;
+    }
+    &content[start..]
+}
+
+fn section(name: &str, content: &str) -> String {
+    format!("{LOG_DELIMITER}\nLog: {name}\n{LOG_DELIMITER}\n{content}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProblemReport;
+
+    fn entries() -> Vec<(String, String)> {
+        vec![
+            (METADATA_ENTRY.to_owned(), "id: 1\nos: Linux\n".to_owned()),
+            ("logs/daemon.log".to_owned(), "a".repeat(1000)),
+            ("routes.txt".to_owned(), "0.0.0.0/0 via wlan0\n".to_owned()),
+            ("empty.txt".to_owned(), String::new()),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut archive = vec![];
+        write(&mut archive, &entries()).unwrap();
+        assert_eq!(archive.len() % BLOCK_SIZE, 0);
+        assert!(is_header(archive[..BLOCK_SIZE].try_into().unwrap()));
+        assert_eq!(read_from(archive.as_slice()).unwrap(), entries());
+    }
+
+    #[test]
+    fn rejects_text_report() {
+        let report = "System information:\nid: 1\n\n".repeat(100);
+        assert!(read_from(report.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn flatten_keeps_metadata_parseable() {
+        let report = flatten(&entries(), usize::MAX);
+        let metadata = ProblemReport::parse_metadata(&report).unwrap();
+        assert_eq!(metadata["os"], "Linux");
+        assert!(report.contains("Log: routes.txt\n"));
+        assert!(!report.contains("Omitted"));
+    }
+
+    #[test]
+    fn flatten_truncates_entries_that_do_not_fit() {
+        let report = flatten(&entries(), 900);
+        assert_eq!(report.len(), 900);
+        assert!(report.contains("Log: logs/daemon.log\n"));
+        assert!(report.contains("Log: routes.txt\n====================\n0.0.0.0/0 via wlan0\n"));
+        assert!(report.contains("Log: empty.txt\n"));
+        let metadata = ProblemReport::parse_metadata(&report).unwrap();
+        assert_eq!(metadata["os"], "Linux");
+    }
+
+    #[test]
+    fn fair_share_caps_only_large_entries() {
+        assert_eq!(fair_share([10, 20].into_iter(), 100), usize::MAX);
+        assert_eq!(fair_share([10, 200, 300].into_iter(), 100), 45);
+        assert_eq!(fair_share([].into_iter(), 0), usize::MAX);
+    }
+
+    #[test]
+    fn tail_starts_at_char_boundary() {
+        assert_eq!(tail("abc", 2), "bc");
+        assert_eq!(tail("abc", 5), "abc");
+        assert_eq!(tail("aéb", 2), "b");
+    }
+}
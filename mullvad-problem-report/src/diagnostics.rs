@@ -0,0 +1,160 @@
+//! Collects the settings and state of the daemon into bundle entries. Nothing here is essential,
+//! so failures are added to the report as errors instead.
+
+use crate::ProblemReport;
+use mullvad_management_interface::MullvadProxyClient;
+use serde_json::Value;
+
+/// Settings that may contain credentials or keys. Their values are replaced entirely, since
+/// the redaction that is applied to the rest of the report does not recognize them.
+const SENSITIVE_SETTINGS: &[&str] = &[
+    "password",
+    "username",
+    "private_key",
+    "preshared_key",
+    "psk",
+];
+
+/// Returns the settings, tunnel state and diagnostics of the daemon as redacted bundle entries.
+pub fn collect(problem_report: &mut ProblemReport) -> Vec<(String, String)> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build();
+    match runtime {
+        Ok(runtime) => runtime.block_on(collect_inner(problem_report)),
+        Err(error) => {
+            problem_report.add_error("Failed to collect daemon state", &error);
+            vec![]
+        }
+    }
+}
+
+async fn collect_inner(problem_report: &mut ProblemReport) -> Vec<(String, String)> {
+    let mut client = match MullvadProxyClient::new().await {
+        Ok(client) => client,
+        Err(error) => {
+            problem_report.add_error("Failed to connect to the daemon", &error);
+            return vec![];
+        }
+    };
+    let mut entries = vec![];
+
+    match client
+        .get_settings()
+        .await
+        .map(|settings| serde_json::to_value(&settings))
+    {
+        Ok(Ok(mut settings)) => {
+            sanitize_settings(&mut settings);
+            entries.push(entry(
+                problem_report,
+                "settings.json",
+                &format!("{settings:#}\n"),
+            ));
+        }
+        Ok(Err(error)) => problem_report.add_error("Failed to serialize settings", &error),
+        Err(error) => problem_report.add_error("Failed to get settings", &error),
+    }
+
+    match client
+        .get_tunnel_state()
+        .await
+        .map(|state| serde_json::to_value(&state))
+    {
+        Ok(Ok(state)) => entries.push(entry(
+            problem_report,
+            "tunnel-state.json",
+            &format!("{state:#}\n"),
+        )),
+        Ok(Err(error)) => problem_report.add_error("Failed to serialize tunnel state", &error),
+        Err(error) => problem_report.add_error("Failed to get tunnel state", &error),
+    }
+
+    match client.get_diagnostics().await {
+        Ok(diagnostics) => {
+            if let Some(firewall) = &diagnostics.firewall {
+                entries.push(entry(problem_report, "firewall.txt", firewall));
+            }
+            if let Some(routes) = &diagnostics.routes {
+                entries.push(entry(problem_report, "routes.txt", routes));
+            }
+            entries.push(entry(
+                problem_report,
+                "dns.txt",
+                &format!("{}\n", diagnostics.dns),
+            ));
+            match serde_json::to_value(&diagnostics.relay_list) {
+                Ok(relay_list) => entries.push(entry(
+                    problem_report,
+                    "relay-list.json",
+                    &format!("{relay_list:#}\n"),
+                )),
+                Err(error) => {
+                    problem_report.add_error("Failed to serialize relay list metadata", &error)
+                }
+            }
+        }
+        Err(error) => problem_report.add_error("Failed to get daemon diagnostics", &error),
+    }
+
+    entries
+}
+
+fn entry(problem_report: &ProblemReport, name: &str, content: &str) -> (String, String) {
+    (name.to_owned(), problem_report.redact(content))
+}
+
+/// Remove the values of [`SENSITIVE_SETTINGS`] anywhere in `settings`.
+fn sanitize_settings(settings: &mut Value) {
+    match settings {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SENSITIVE_SETTINGS.contains(&key.as_str()) {
+                    if !value.is_null() {
+                        *value = Value::from("[REDACTED]");
+                    }
+                } else {
+                    sanitize_settings(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(sanitize_settings),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sanitizes_nested_credentials() {
+        let mut settings = json!({
+            "api_access_methods": {
+                "custom": [{
+                    "name": "proxy",
+                    "access_method": {
+                        "Custom": {
+                            "Socks5Remote": {
+                                "authentication": { "username": "user", "password": "hunter2" }
+                            }
+                        }
+                    }
+                }]
+            },
+            "custom_tunnel": { "private_key": "secret", "psk": null },
+            "allow_lan": true
+        });
+        sanitize_settings(&mut settings);
+
+        let authentication = &settings["api_access_methods"]["custom"][0]["access_method"]
+            ["Custom"]["Socks5Remote"]["authentication"];
+        assert_eq!(authentication["username"], "[REDACTED]");
+        assert_eq!(authentication["password"], "[REDACTED]");
+        assert_eq!(settings["custom_tunnel"]["private_key"], "[REDACTED]");
+        assert_eq!(settings["custom_tunnel"]["psk"], Value::Null);
+        assert_eq!(settings["api_access_methods"]["custom"][0]["name"], "proxy");
+        assert_eq!(settings["allow_lan"], true);
+    }
+}
@@ -12,6 +12,9 @@ use std::{
 };
 use talpid_types::ErrorExt;
 
+pub mod bundle;
+#[cfg(not(target_os = "android"))]
+mod diagnostics;
 pub mod metadata;
 
 /// Maximum number of bytes to read from each log file
@@ -86,6 +89,10 @@ pub enum LogError {
     #[error("Unable to get log directory")]
     GetLogDir(#[source] mullvad_paths::Error),
 
+    #[cfg(not(target_os = "android"))]
+    #[error("Unable to get cache directory")]
+    GetCacheDir(#[source] mullvad_paths::Error),
+
     #[error("Failed to list the files in the log directory: {path}")]
     ListLogDir {
         path: String,
@@ -112,7 +119,50 @@ pub fn collect_report<P: AsRef<Path>>(
     #[cfg(target_os = "android")] android_log_dir: &Path,
 ) -> Result<(), Error> {
     let mut problem_report = ProblemReport::new(redact_custom_strings);
+    collect_logs(
+        &mut problem_report,
+        extra_logs,
+        #[cfg(target_os = "android")]
+        android_log_dir,
+    );
+
+    write_problem_report(output_path, &problem_report).map_err(|source| Error::WriteReportError {
+        path: output_path.display().to_string(),
+        source,
+    })
+}
+
+/// Collect a problem report bundle. In addition to the logs, it contains the settings and state
+/// of the daemon, if it is running.
+#[cfg(not(target_os = "android"))]
+pub fn collect_bundle<P: AsRef<Path>>(
+    extra_logs: &[P],
+    output_path: &Path,
+    redact_custom_strings: Vec<String>,
+) -> Result<(), Error> {
+    let mut problem_report = ProblemReport::new(redact_custom_strings);
+    collect_logs(&mut problem_report, extra_logs);
+    let diagnostics = diagnostics::collect(&mut problem_report);
 
+    let mut entries = problem_report.bundle_entries();
+    entries.extend(diagnostics);
+    if let Some(errors) = problem_report.errors() {
+        entries.push(("errors.txt".to_owned(), errors));
+    }
+
+    create_report_file(output_path)
+        .and_then(|file| bundle::write(BufWriter::new(file), &entries))
+        .map_err(|source| Error::WriteReportError {
+            path: output_path.display().to_string(),
+            source,
+        })
+}
+
+fn collect_logs<P: AsRef<Path>>(
+    problem_report: &mut ProblemReport,
+    extra_logs: &[P],
+    #[cfg(target_os = "android")] android_log_dir: &Path,
+) {
     let daemon_logs_dir = {
         #[cfg(target_os = "android")]
         {
@@ -167,13 +217,18 @@ pub fn collect_report<P: AsRef<Path>>(
         Ok(logcat_path) => problem_report.add_log(&logcat_path),
         Err(error) => problem_report.add_error("Failed to collect logcat", &error),
     }
+    #[cfg(not(target_os = "android"))]
+    match mullvad_paths::get_cache_dir().map_err(LogError::GetCacheDir) {
+        Ok(cache_dir) => {
+            let history_path = cache_dir.join(mullvad_paths::CONNECTION_HISTORY_FILENAME);
+            if history_path.exists() {
+                problem_report.add_log(&history_path);
+            }
+        }
+        Err(error) => problem_report.add_error("Failed to find connection history", &error),
+    }
 
     problem_report.add_logs(extra_logs);
-
-    write_problem_report(output_path, &problem_report).map_err(|source| Error::WriteReportError {
-        path: output_path.display().to_string(),
-        source,
-    })
 }
 
 /// Returns an iterator over all files in the given directory that has the `.log` extension.
@@ -256,6 +311,26 @@ fn write_logcat_to_file(log_dir: &Path) -> Result<PathBuf, io::Error> {
         .map(|_| logcat_path)
 }
 
+/// Returns the report at `report_path` exactly as it is uploaded by [`send_problem_report`].
+/// Bundles are flattened into the text format, truncating files that do not fit.
+pub fn read_report(report_path: &Path) -> Result<String, Error> {
+    read_report_inner(report_path)
+        .map(normalize_newlines)
+        .map_err(|source| Error::ReadProblemReportError {
+            path: report_path.display().to_string(),
+            source,
+        })
+}
+
+fn read_report_inner(report_path: &Path) -> io::Result<String> {
+    if bundle::is_bundle(report_path)? {
+        let entries = bundle::read(report_path)?;
+        Ok(bundle::flatten(&entries, REPORT_MAX_SIZE))
+    } else {
+        read_file_lossy(report_path, REPORT_MAX_SIZE)
+    }
+}
+
 pub fn send_problem_report(
     user_email: &str,
     user_message: &str,
@@ -263,14 +338,7 @@ pub fn send_problem_report(
     cache_dir: &Path,
     endpoint: ApiEndpoint,
 ) -> Result<(), Error> {
-    let report_content = normalize_newlines(
-        read_file_lossy(report_path, REPORT_MAX_SIZE).map_err(|source| {
-            Error::ReadProblemReportError {
-                path: report_path.display().to_string(),
-                source,
-            }
-        })?,
-    );
+    let report_content = read_report(report_path)?;
 
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(2)
@@ -334,22 +402,36 @@ async fn send_problem_report_inner(
 }
 
 fn write_problem_report(path: &Path, problem_report: &ProblemReport) -> io::Result<()> {
+    let file = create_report_file(path)?;
+    problem_report.write_to(BufWriter::new(file))?;
+    Ok(())
+}
+
+fn create_report_file(path: &Path) -> io::Result<File> {
     let file = File::create(path)?;
     let mut permissions = file.metadata()?.permissions();
     permissions.set_readonly(true);
     file.set_permissions(permissions)?;
-    problem_report.write_to(BufWriter::new(file))?;
-    Ok(())
+    Ok(file)
 }
 
 #[derive(Debug)]
 struct ProblemReport {
     metadata: BTreeMap<String, String>,
-    logs: Vec<(String, String)>,
+    logs: Vec<Log>,
     log_paths: HashSet<PathBuf>,
     redact_custom_strings: Vec<String>,
 }
 
+/// A log file, or an error that prevented a log from being collected.
+#[derive(Debug)]
+struct Log {
+    label: String,
+    content: String,
+    /// Name of the log file, which is `None` for errors.
+    file_name: Option<String>,
+}
+
 impl ProblemReport {
     /// Creates a new problem report with system information. Logs can be added with `add_log`.
     /// Logs will have all strings in `redact_custom_strings` removed from them.
@@ -390,7 +472,14 @@ impl ProblemReport {
                     ))
                 },
             ));
-            self.logs.push((redacted_path, content));
+            let file_name = expanded_path
+                .file_name()
+                .map(|file_name| self.redact(&file_name.to_string_lossy()));
+            self.logs.push(Log {
+                label: redacted_path,
+                content,
+                file_name,
+            });
             log::info!("Adding {}", expanded_path.display());
         }
     }
@@ -398,7 +487,49 @@ impl ProblemReport {
     /// Attach an error to the report.
     pub fn add_error(&mut self, message: &'static str, error: &impl ErrorExt) {
         let redacted_error = self.redact(&error.display_chain());
-        self.logs.push((message.to_string(), redacted_error));
+        self.logs.push(Log {
+            label: message.to_string(),
+            content: redacted_error,
+            file_name: None,
+        });
+    }
+
+    /// Returns the metadata and the logs as bundle entries. Logs with the same file name are
+    /// told apart by a numeric prefix. Errors are not included, see [`Self::errors`].
+    #[cfg(not(target_os = "android"))]
+    fn bundle_entries(&self) -> Vec<(String, String)> {
+        let mut metadata = String::new();
+        for (key, value) in &self.metadata {
+            metadata.push_str(&format!("{key}: {value}\n"));
+        }
+        let mut entries = vec![(bundle::METADATA_ENTRY.to_owned(), metadata)];
+
+        let mut names = HashSet::new();
+        for log in &self.logs {
+            let Some(file_name) = &log.file_name else {
+                continue;
+            };
+            let mut name = format!("logs/{file_name}");
+            let mut index = 2;
+            while !names.insert(name.clone()) {
+                name = format!("logs/{index}-{file_name}");
+                index += 1;
+            }
+            entries.push((name, log.content.clone()));
+        }
+        entries
+    }
+
+    /// Returns all errors that have been added to the report, or `None` if there are none.
+    #[cfg(not(target_os = "android"))]
+    fn errors(&self) -> Option<String> {
+        let errors: Vec<_> = self
+            .logs
+            .iter()
+            .filter(|log| log.file_name.is_none())
+            .map(|log| format!("{}: {}\n", log.label, log.content))
+            .collect();
+        (!errors.is_empty()).then(|| errors.concat())
     }
 
     fn redact(&self, input: &str) -> String {
@@ -458,11 +589,11 @@ impl ProblemReport {
         }
         // Write empty line to separate metadata from first log
         write_line!(output)?;
-        for (label, content) in &self.logs {
+        for log in &self.logs {
             write_line!(output, "{}", LOG_DELIMITER)?;
-            write_line!(output, "Log: {}", label)?;
+            write_line!(output, "Log: {}", log.label)?;
             write_line!(output, "{}", LOG_DELIMITER)?;
-            output.write_all(content.as_bytes())?;
+            output.write_all(log.content.as_bytes())?;
             write_line!(output)?;
         }
         Ok(())
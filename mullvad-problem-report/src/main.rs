@@ -1,6 +1,6 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use mullvad_api::ApiEndpoint;
-use mullvad_problem_report::{collect_report, Error};
+use mullvad_problem_report::{collect_bundle, collect_report, read_report, Error};
 use std::{
     env,
     path::{Path, PathBuf},
@@ -36,6 +36,16 @@ enum Cli {
         /// List of strings to remove from the report
         #[arg(long)]
         redact: Vec<String>,
+        /// Format of the collected report
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Print a collected problem report exactly as it will be sent
+    Preview {
+        /// Path to a previously collected report file
+        #[arg(required = true, long, short = 'r')]
+        report: PathBuf,
     },
 
     /// Send collected problem report
@@ -52,6 +62,14 @@ enum Cli {
     },
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    /// A single text file containing all logs
+    Text,
+    /// A tar archive with a separate file for each log, the settings and the daemon state
+    Bundle,
+}
+
 fn run() -> Result<(), Error> {
     env_logger::init();
 
@@ -60,14 +78,24 @@ fn run() -> Result<(), Error> {
             output,
             extra_logs,
             redact,
+            format,
         } => {
-            collect_report(&extra_logs, &output, redact)?;
+            match format {
+                Format::Text => collect_report(&extra_logs, &output, redact)?,
+                Format::Bundle => collect_bundle(&extra_logs, &output, redact)?,
+            }
 
             println!("Problem report written to {}", output.display());
             println!();
+            println!("Inspect exactly what will be sent via the preview subcommand. See:");
+            println!(" $ {} preview --help", env::args().next().unwrap());
+            println!();
             println!("Send the problem report to support via the send subcommand. See:");
             println!(" $ {} send --help", env::args().next().unwrap());
         }
+        Cli::Preview { report } => {
+            print!("{}", read_report(&report)?);
+        }
         Cli::Send {
             report,
             email,
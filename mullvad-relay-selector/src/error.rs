@@ -19,6 +19,9 @@ pub enum Error {
     #[error("No relays matching current constraints")]
     NoRelay,
 
+    #[error("No retry attempt is compatible with the current constraints")]
+    IncompatibleRetryOrder,
+
     #[error("No bridges matching current constraints")]
     NoBridge,
 
@@ -59,9 +59,11 @@ pub fn wireguard_endpoint(
 ) -> Result<MullvadWireguardEndpoint, Error> {
     match relay {
         WireguardConfig::Singlehop { exit } => wireguard_singlehop_endpoint(query, data, exit),
-        WireguardConfig::Multihop { exit, entry } => {
-            wireguard_multihop_endpoint(query, data, exit, entry)
-        }
+        WireguardConfig::Multihop {
+            exit,
+            entry,
+            middle,
+        } => wireguard_multihop_endpoint(query, data, exit, middle, entry),
     }
 }
 
@@ -88,33 +90,33 @@ fn wireguard_singlehop_endpoint(
     };
     Ok(MullvadWireguardEndpoint {
         peer: peer_config,
+        middle_peers: vec![],
         exit_peer: None,
         ipv4_gateway: data.ipv4_gateway,
         ipv6_gateway: data.ipv6_gateway,
     })
 }
 
-/// Configure a multihop connection using the entry & exit relay data.
+/// Configure a multihop connection using the entry, middle & exit relay data.
 ///
 /// # Note
-/// In a multihop circuit, we need to provide an exit peer configuration in addition to the
-/// peer configuration.
+/// In a multihop circuit, we need to provide an exit peer configuration (and middle peer
+/// configurations, if any) in addition to the peer configuration.
 fn wireguard_multihop_endpoint(
     query: &WireguardRelayQuery,
     data: &WireguardEndpointData,
     exit: &Relay,
+    middle: &[Relay],
     entry: &Relay,
 ) -> Result<MullvadWireguardEndpoint, Error> {
     /// The standard port on which an exit relay accepts connections from an entry relay in a
     /// multihop circuit.
     const WIREGUARD_EXIT_PORT: u16 = 51820;
-    let exit_endpoint = {
-        let ip = exit.ipv4_addr_in;
-        // The port that the exit relay listens for incoming connections from entry
-        // relays is *not* derived from the original query / user settings.
-        let port = WIREGUARD_EXIT_PORT;
-        SocketAddr::from((ip, port))
-    };
+    // The port that the exit (and middle) relays listen for incoming connections from other
+    // relays on is *not* derived from the original query / user settings.
+    let inner_endpoint =
+        |relay: &Relay| SocketAddr::from((relay.ipv4_addr_in, WIREGUARD_EXIT_PORT));
+    let exit_endpoint = inner_endpoint(exit);
     let exit = PeerConfig {
         public_key: get_public_key(exit)?.clone(),
         endpoint: exit_endpoint,
@@ -128,6 +130,25 @@ fn wireguard_multihop_endpoint(
         constant_packet_size: false,
     };
 
+    // Each middle peer should only be able to route incoming VPN traffic to the next hop.
+    let mut next_hop = exit.endpoint.ip();
+    let mut middle_peers = Vec::with_capacity(middle.len());
+    for relay in middle.iter().rev() {
+        let peer = PeerConfig {
+            public_key: get_public_key(relay)?.clone(),
+            endpoint: inner_endpoint(relay),
+            allowed_ips: vec![IpNetwork::from(next_hop)],
+            // This will be filled in later
+            psk: None,
+            // This will be filled in later
+            #[cfg(daita)]
+            constant_packet_size: false,
+        };
+        next_hop = peer.endpoint.ip();
+        middle_peers.push(peer);
+    }
+    middle_peers.reverse();
+
     let entry_endpoint = {
         let host = get_address_for_wireguard_relay(query, entry)?;
         let port = get_port_for_wireguard_relay(query, data)?;
@@ -137,8 +158,8 @@ fn wireguard_multihop_endpoint(
         public_key: get_public_key(entry)?.clone(),
         endpoint: entry_endpoint,
         // The entry peer should only be able to route incoming VPN traffic to the
-        // exit peer.
-        allowed_ips: vec![IpNetwork::from(exit.endpoint.ip())],
+        // next hop.
+        allowed_ips: vec![IpNetwork::from(next_hop)],
         // This will be filled in later
         psk: None,
         // This will be filled in later
@@ -148,6 +169,7 @@ fn wireguard_multihop_endpoint(
 
     Ok(MullvadWireguardEndpoint {
         peer: entry,
+        middle_peers,
         exit_peer: Some(exit),
         ipv4_gateway: data.ipv4_gateway,
         ipv6_gateway: data.ipv6_gateway,
@@ -8,7 +8,9 @@ use std::{
 use mullvad_types::{
     constraints::Constraint,
     endpoint::MullvadWireguardEndpoint,
-    relay_constraints::{ShadowsocksSettings, Udp2TcpObfuscationSettings},
+    relay_constraints::{
+        ShadowsocksSettings, Socks5ObfuscationSettings, Udp2TcpObfuscationSettings,
+    },
     relay_list::Relay,
 };
 use rand::{
@@ -23,10 +25,17 @@ use crate::SelectedObfuscator;
 /// For relays that have no additional IPs, only ports provided by the relay list are available.
 const SHADOWSOCKS_EXTRA_PORT_RANGES: &[RangeInclusive<u16>] = &[1..=u16::MAX];
 
+/// QUIC obfuscation is always served on the HTTPS port, so that it resembles HTTP/3.
+const QUIC_OBFUSCATION_PORT: u16 = 443;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Found no valid port matching the selected settings")]
     NoMatchingPort,
+    #[error("The relay does not support QUIC obfuscation")]
+    QuicUnsupported,
+    #[error("No SOCKS5 proxy has been configured for obfuscation")]
+    NoSocks5Proxy,
 }
 
 /// Picks a relay at random from `relays`, but don't pick `exclude`.
@@ -141,6 +150,52 @@ pub fn get_shadowsocks_obfuscator(
     })
 }
 
+pub fn get_quic_obfuscator(
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> Result<SelectedObfuscator, Error> {
+    let quic = match &relay.endpoint_data {
+        mullvad_types::relay_list::RelayEndpointData::Wireguard(wg) => wg.quic.as_ref(),
+        _ => panic!("expected wireguard relay"),
+    }
+    .ok_or(Error::QuicUnsupported)?;
+
+    let in_ip = get_quic_obfuscator_ip(endpoint.peer.endpoint.ip(), &quic.addr_in);
+    let config = ObfuscatorConfig::Quic {
+        endpoint: SocketAddr::new(in_ip, QUIC_OBFUSCATION_PORT),
+        hostname: quic.domain.clone(),
+    };
+
+    Ok(SelectedObfuscator { config, relay })
+}
+
+/// Relay WireGuard traffic to the entry peer of `endpoint` through the user's SOCKS5 proxy.
+pub fn get_socks5_obfuscator(
+    settings: &Socks5ObfuscationSettings,
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> Result<SelectedObfuscator, Error> {
+    let proxy = settings.proxy.as_ref().ok_or(Error::NoSocks5Proxy)?;
+    let config = ObfuscatorConfig::Socks5 {
+        endpoint: proxy.endpoint,
+        auth: proxy.auth.clone(),
+        wireguard_endpoint: endpoint.peer.endpoint,
+    };
+
+    Ok(SelectedObfuscator { config, relay })
+}
+
+/// Return one of `quic_in_addrs` that belongs to the same IP version as `wg_in_addr`, or
+/// `wg_in_addr` if there is none.
+fn get_quic_obfuscator_ip(wg_in_addr: IpAddr, quic_in_addrs: &[IpAddr]) -> IpAddr {
+    quic_in_addrs
+        .iter()
+        .filter(|addr| addr.is_ipv4() == wg_in_addr.is_ipv4())
+        .choose(&mut rand::thread_rng())
+        .copied()
+        .unwrap_or(wg_in_addr)
+}
+
 /// Return an obfuscation config for the wireguard server at `wg_in_addr` or one of `extra_in_addrs`
 /// (unless empty). `wg_in_addr_port_ranges` contains all valid ports for `wg_in_addr`, and
 /// `SHADOWSOCKS_EXTRA_PORT_RANGES` contains valid ports for `extra_in_addrs`.
@@ -220,7 +275,8 @@ pub fn select_random_port<R: RangeBounds<u16> + Iterator<Item = u16> + Clone>(
 #[cfg(test)]
 mod tests {
     use super::{
-        get_shadowsocks_obfuscator_inner, port_if_in_range, SHADOWSOCKS_EXTRA_PORT_RANGES,
+        get_quic_obfuscator_ip, get_shadowsocks_obfuscator_inner, port_if_in_range,
+        SHADOWSOCKS_EXTRA_PORT_RANGES,
     };
     use mullvad_types::constraints::Constraint;
     use std::{net::IpAddr, ops::RangeInclusive};
@@ -357,4 +413,22 @@ mod tests {
             "expected match for within-range port"
         );
     }
+
+    /// QUIC addresses of the wrong IP version should be ignored
+    #[test]
+    fn test_quic_addrs() {
+        let wg_in_ip: IpAddr = "1.2.3.4".parse().unwrap();
+        let quic_in_addrs: &[IpAddr] = &["::2".parse().unwrap(), "192.0.2.123".parse().unwrap()];
+
+        assert_eq!(
+            get_quic_obfuscator_ip(wg_in_ip, quic_in_addrs),
+            quic_in_addrs[1],
+            "expected QUIC address of the same IP version"
+        );
+        assert_eq!(
+            get_quic_obfuscator_ip(wg_in_ip, &quic_in_addrs[..1]),
+            wg_in_ip,
+            "expected WireGuard address to be used"
+        );
+    }
 }
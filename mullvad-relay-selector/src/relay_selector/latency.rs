@@ -0,0 +1,277 @@
+//! This module keeps track of measured round-trip times to relays, and provides a way of biasing
+//! the relay weights towards the relays which responded the fastest.
+//!
+//! The relay selector does not measure anything by itself. Measurements are fed to it through
+//! [`crate::RelaySelector::update_latencies`], which makes it possible to inject a fake latency
+//! table when testing.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use mullvad_types::relay_list::{Relay, RelayList};
+
+/// How long a latency measurement is considered to be valid.
+pub const LATENCY_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Relays which have not been measured are given the weight they would have had if their RTT was
+/// this many times higher than the RTT of the fastest relay.
+const UNMEASURED_RTT_FACTOR: f64 = 2.0;
+
+/// All relay weights are multiplied by this factor before being biased, so that relays with small
+/// weights can still be told apart after the bias has been applied.
+const WEIGHT_SCALE: f64 = 1000.0;
+
+/// A table of recently measured round-trip times to relays, keyed by hostname.
+#[derive(Debug, Clone, Default)]
+pub struct RelayLatencies {
+    measurements: HashMap<String, Measurement>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Measurement {
+    rtt: Duration,
+    measured_at: Instant,
+}
+
+impl RelayLatencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a round-trip time to the relay with hostname `hostname`, measured just now.
+    pub fn insert(&mut self, hostname: impl Into<String>, rtt: Duration) {
+        self.insert_measured_at(hostname, rtt, Instant::now());
+    }
+
+    /// Record a round-trip time to the relay with hostname `hostname`, measured at `measured_at`.
+    pub fn insert_measured_at(
+        &mut self,
+        hostname: impl Into<String>,
+        rtt: Duration,
+        measured_at: Instant,
+    ) {
+        self.measurements
+            .insert(hostname.into(), Measurement { rtt, measured_at });
+    }
+
+    /// Return the round-trip time to the relay with hostname `hostname`, unless it has not been
+    /// measured or the measurement has expired.
+    pub fn get(&self, hostname: &str) -> Option<Duration> {
+        self.get_at(hostname, Instant::now())
+    }
+
+    fn get_at(&self, hostname: &str, now: Instant) -> Option<Duration> {
+        self.measurements
+            .get(hostname)
+            .filter(|measurement| measurement.is_valid_at(now))
+            .map(|measurement| measurement.rtt)
+    }
+
+    /// Returns true if there are no valid measurements.
+    pub fn is_empty(&self) -> bool {
+        self.fastest_at(Instant::now()).is_none()
+    }
+
+    /// Remove all measurements which have expired.
+    pub fn remove_expired(&mut self) {
+        self.remove_expired_at(Instant::now());
+    }
+
+    fn remove_expired_at(&mut self, now: Instant) {
+        self.measurements
+            .retain(|_, measurement| measurement.is_valid_at(now));
+    }
+
+    /// When the first of the currently valid measurements expires, or `None` if there are no
+    /// valid measurements.
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.next_expiry_at(Instant::now())
+    }
+
+    fn next_expiry_at(&self, now: Instant) -> Option<Instant> {
+        self.measurements
+            .values()
+            .filter(|measurement| measurement.is_valid_at(now))
+            .map(|measurement| measurement.measured_at + LATENCY_EXPIRY)
+            .min()
+    }
+
+    /// The lowest round-trip time among all valid measurements.
+    fn fastest_at(&self, now: Instant) -> Option<Duration> {
+        self.measurements
+            .values()
+            .filter(|measurement| measurement.is_valid_at(now))
+            .map(|measurement| measurement.rtt)
+            .min()
+    }
+
+    /// Scale the weight of every relay in `relay_list` by how close its round-trip time is to the
+    /// fastest measured relay. The weight of a relay is divided by the square of the ratio between
+    /// its RTT and the lowest RTT, so a relay which is twice as slow as the fastest one is picked a
+    /// quarter as often as it otherwise would have been. Note that this changes the absolute
+    /// weights, and only the weights relative to each other are meaningful afterwards.
+    ///
+    /// Relays without a valid measurement are treated as if they were [`UNMEASURED_RTT_FACTOR`]
+    /// times slower than the fastest relay. If there are no valid measurements at all, the relay
+    /// list is left untouched.
+    pub(crate) fn apply_bias(&self, relay_list: &mut RelayList) {
+        self.apply_bias_at(relay_list, Instant::now())
+    }
+
+    fn apply_bias_at(&self, relay_list: &mut RelayList, now: Instant) {
+        let Some(fastest) = self.fastest_at(now) else {
+            return;
+        };
+        // Avoid dividing by zero if some measurement was suspiciously fast
+        let fastest = fastest.max(Duration::from_micros(1)).as_secs_f64();
+
+        for relay in relay_list
+            .countries
+            .iter_mut()
+            .flat_map(|country| country.cities.iter_mut())
+            .flat_map(|city| city.relays.iter_mut())
+        {
+            let rtt_factor = self
+                .get_at(&relay.hostname, now)
+                .map(|rtt| rtt.as_secs_f64() / fastest)
+                .unwrap_or(UNMEASURED_RTT_FACTOR)
+                .max(1.0);
+            relay.weight = biased_weight(relay, rtt_factor);
+        }
+    }
+}
+
+impl Measurement {
+    fn is_valid_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.measured_at) < LATENCY_EXPIRY
+    }
+}
+
+/// Divide the (scaled) weight of `relay` by the square of `rtt_factor`.
+///
+/// Relays with a non-zero weight never end up with a weight of zero, since that would exclude them
+/// from being selected at all.
+fn biased_weight(relay: &Relay, rtt_factor: f64) -> u64 {
+    if relay.weight == 0 {
+        return 0;
+    }
+    let weight = relay.weight as f64 * WEIGHT_SCALE / rtt_factor.powi(2);
+    (weight.round() as u64).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mullvad_types::{
+        location::Location,
+        relay_list::{RelayEndpointData, RelayListCity, RelayListCountry},
+    };
+
+    fn relay(hostname: &str, weight: u64) -> Relay {
+        Relay {
+            hostname: hostname.to_owned(),
+            ipv4_addr_in: "10.0.0.1".parse().unwrap(),
+            ipv6_addr_in: None,
+            overridden_ipv4: false,
+            overridden_ipv6: false,
+            include_in_country: true,
+            active: true,
+            owned: true,
+            provider: "provider".to_owned(),
+            weight,
+            endpoint_data: RelayEndpointData::Openvpn,
+            location: Location {
+                country: "Sweden".to_owned(),
+                country_code: "se".to_owned(),
+                city: "Gothenburg".to_owned(),
+                city_code: "got".to_owned(),
+                latitude: 57.70887,
+                longitude: 11.97456,
+            },
+        }
+    }
+
+    fn relay_list(relays: Vec<Relay>) -> RelayList {
+        RelayList {
+            countries: vec![RelayListCountry {
+                name: "Sweden".to_owned(),
+                code: "se".to_owned(),
+                cities: vec![RelayListCity {
+                    name: "Gothenburg".to_owned(),
+                    code: "got".to_owned(),
+                    latitude: 57.70887,
+                    longitude: 11.97456,
+                    relays,
+                }],
+            }],
+            ..RelayList::empty()
+        }
+    }
+
+    fn weights(relay_list: &RelayList) -> Vec<u64> {
+        relay_list.relays().map(|relay| relay.weight).collect()
+    }
+
+    /// Faster relays should keep more of their weight than slower relays
+    #[test]
+    fn test_bias_towards_fastest() {
+        let mut latencies = RelayLatencies::new();
+        latencies.insert("fast", Duration::from_millis(10));
+        latencies.insert("slow", Duration::from_millis(20));
+
+        let mut list = relay_list(vec![
+            relay("fast", 100),
+            relay("slow", 100),
+            relay("unmeasured", 100),
+        ]);
+        latencies.apply_bias(&mut list);
+
+        assert_eq!(weights(&list), vec![100_000, 25_000, 25_000]);
+    }
+
+    /// Expired measurements should be ignored
+    #[test]
+    fn test_expired_measurements() {
+        let mut latencies = RelayLatencies::new();
+        let measured_at = Instant::now();
+        let expired = measured_at + LATENCY_EXPIRY;
+        latencies.insert_measured_at("fast", Duration::from_millis(10), measured_at);
+
+        assert_eq!(
+            latencies.get_at("fast", measured_at),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(latencies.get_at("fast", expired), None);
+        assert_eq!(latencies.fastest_at(expired), None);
+        assert_eq!(latencies.next_expiry_at(measured_at), Some(expired));
+        assert_eq!(latencies.next_expiry_at(expired), None);
+
+        let mut list = relay_list(vec![relay("fast", 100), relay("slow", 50)]);
+        latencies.apply_bias_at(&mut list, expired);
+        assert_eq!(weights(&list), vec![100, 50]);
+
+        latencies.remove_expired_at(expired);
+        assert!(latencies.measurements.is_empty());
+    }
+
+    /// A relay with a non-zero weight should never be excluded, and a relay with a zero weight
+    /// should never be included
+    #[test]
+    fn test_bias_preserves_zero_weights() {
+        let mut latencies = RelayLatencies::new();
+        latencies.insert("fast", Duration::from_millis(1));
+        latencies.insert("very-slow", Duration::from_secs(10));
+        latencies.insert("disabled", Duration::from_millis(1));
+
+        let mut list = relay_list(vec![
+            relay("fast", 1),
+            relay("very-slow", 1),
+            relay("disabled", 0),
+        ]);
+        latencies.apply_bias(&mut list);
+
+        assert_eq!(weights(&list), vec![1000, 1, 0]);
+    }
+}
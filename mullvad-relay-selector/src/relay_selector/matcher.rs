@@ -147,12 +147,22 @@ fn filter_on_obfuscation(
                 relay,
             )
         }
+        // Only a subset of relays accept QUIC connections
+        ObfuscationQuery::Quic => filter_on_quic(relay),
 
-        // If Shadowsocks is not a requirement, then there are no relay-specific constraints
+        // Other obfuscation protocols have no relay-specific constraints
         _ => true,
     }
 }
 
+/// Returns whether `relay` accepts WireGuard over QUIC.
+fn filter_on_quic(relay: &Relay) -> bool {
+    matches!(
+        &relay.endpoint_data,
+        RelayEndpointData::Wireguard(WireguardRelayEndpointData { quic: Some(_), .. })
+    )
+}
+
 /// Returns whether `relay` satisfies the Shadowsocks filter posed by `port`.
 fn filter_on_shadowsocks(
     port_ranges: &[RangeInclusive<u16>],
@@ -2,11 +2,13 @@
 
 pub mod detailer;
 mod helpers;
+pub mod latency;
 mod matcher;
 mod parsed_relays;
 pub mod query;
 pub mod relays;
 
+use latency::RelayLatencies;
 use matcher::{filter_matching_bridges, filter_matching_relay_list};
 use parsed_relays::ParsedRelays;
 use relays::{Multihop, Singlehop, WireguardConfig};
@@ -23,7 +25,7 @@ use crate::{
 use std::{
     path::Path,
     sync::{Arc, LazyLock, Mutex},
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime},
 };
 
 use chrono::{DateTime, Local};
@@ -37,7 +39,7 @@ use mullvad_types::{
     relay_constraints::{
         BridgeSettings, BridgeState, InternalBridgeConstraints, ObfuscationSettings,
         OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySettings, ResolvedBridgeSettings,
-        WireguardConstraints,
+        RetryAttempt, WireguardConstraints,
     },
     relay_list::{Relay, RelayEndpointData, RelayList},
     settings::Settings,
@@ -57,6 +59,9 @@ use talpid_types::{
 /// prioritize on successive connection attempts. Note that these will *never* override user
 /// preferences. See [the documentation on `RelayQuery`][RelayQuery] for further details.
 ///
+/// This list is used unless the user has defined their own retry order, see
+/// [`Settings::retry_order`].
+///
 /// This list should be kept in sync with the expected behavior defined in `docs/relay-selector.md`
 pub static RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
     use query::builder::{IpVersion, RelayQueryBuilder};
@@ -102,6 +107,16 @@ pub static RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
 pub struct RelaySelector {
     config: Arc<Mutex<SelectorConfig>>,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    latencies: Arc<Mutex<RelayLatencies>>,
+    biased_relays: Arc<Mutex<Option<BiasedRelays>>>,
+}
+
+/// The relays with their weights biased by the latency measurements. This is cached since biasing
+/// requires a copy of the relay list, and it is only valid until the relays or the measurements
+/// change, or until the first of the measurements expires.
+struct BiasedRelays {
+    relays: Arc<ParsedRelays>,
+    valid_until: Option<Instant>,
 }
 
 #[derive(Clone)]
@@ -116,6 +131,8 @@ pub struct SelectorConfig {
     // OpenVPN specific data
     pub bridge_state: BridgeState,
     pub bridge_settings: BridgeSettings,
+    /// The queries to try on successive connection attempts. See [`RETRY_ORDER`].
+    pub retry_order: Vec<RelayQuery>,
 }
 
 impl SelectorConfig {
@@ -148,10 +165,27 @@ impl SelectorConfig {
             obfuscation_settings: settings.obfuscation_settings.clone(),
             custom_lists: settings.custom_lists.clone(),
             relay_overrides: settings.relay_overrides.clone(),
+            retry_order: retry_order_queries(settings.retry_order.as_deref()),
         }
     }
 }
 
+/// Map a user-defined retry order to relay queries, falling back to [`RETRY_ORDER`] if the user
+/// has not defined one. Invalid retry attempts are skipped.
+fn retry_order_queries(retry_order: Option<&[RetryAttempt]>) -> Vec<RelayQuery> {
+    let Some(retry_order) = retry_order else {
+        return RETRY_ORDER.clone();
+    };
+    retry_order
+        .iter()
+        .filter_map(|attempt| {
+            RelayQuery::try_from(attempt.clone())
+                .inspect_err(|_| log::warn!("Ignoring invalid retry attempt: {attempt}"))
+                .ok()
+        })
+        .collect()
+}
+
 /// Extra relay constraints not specified in `relay_settings`.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct AdditionalRelayConstraints {
@@ -308,6 +342,7 @@ impl Default for SelectorConfig {
             bridge_state: default_settings.bridge_state,
             custom_lists: default_settings.custom_lists,
             relay_overrides: default_settings.relay_overrides,
+            retry_order: RETRY_ORDER.clone(),
         }
     }
 }
@@ -348,6 +383,7 @@ impl<'a> TryFrom<NormalSelectorConfig<'a>> for RelayQuery {
                 ip_version,
                 use_multihop,
                 entry_location,
+                middle_locations,
             } = wireguard_constraints;
             let AdditionalWireguardConstraints {
                 daita,
@@ -359,6 +395,7 @@ impl<'a> TryFrom<NormalSelectorConfig<'a>> for RelayQuery {
                 ip_version,
                 use_multihop: Constraint::Only(use_multihop),
                 entry_location,
+                middle_locations: Constraint::Only(middle_locations),
                 obfuscation: ObfuscationQuery::from(obfuscation_settings),
                 daita: Constraint::Only(daita),
                 daita_use_multihop_if_necessary: Constraint::Only(daita_use_multihop_if_necessary),
@@ -437,6 +474,8 @@ impl RelaySelector {
         RelaySelector {
             config: Arc::new(Mutex::new(config)),
             parsed_relays: Arc::new(Mutex::new(unsynchronized_parsed_relays)),
+            latencies: Arc::new(Mutex::new(RelayLatencies::new())),
+            biased_relays: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -448,11 +487,21 @@ impl RelaySelector {
                 &config.relay_overrides,
             ))),
             config: Arc::new(Mutex::new(config)),
+            latencies: Arc::new(Mutex::new(RelayLatencies::new())),
+            biased_relays: Arc::new(Mutex::new(None)),
         }
     }
 
     pub fn set_config(&mut self, config: SelectorConfig) {
         self.set_overrides(&config.relay_overrides);
+        if config.retry_order != *RETRY_ORDER
+            && !Self::is_compatible_retry_order(&config.retry_order, &config)
+        {
+            log::warn!(
+                "No retry attempt is compatible with the current constraints. Using the default \
+                 retry order instead"
+            );
+        }
         let mut config_mutex = self.config.lock().unwrap();
         *config_mutex = config;
     }
@@ -460,11 +509,116 @@ impl RelaySelector {
     pub fn set_relays(&self, relays: RelayList) {
         let mut parsed_relays = self.parsed_relays.lock().unwrap();
         parsed_relays.update(relays);
+        self.clear_biased_relays();
+    }
+
+    /// Replace all latency measurements with `latencies`.
+    pub fn set_latencies(&self, latencies: RelayLatencies) {
+        let mut latencies_guard = self.latencies.lock().unwrap();
+        *latencies_guard = latencies;
+        self.clear_biased_relays();
+    }
+
+    /// Record freshly measured round-trip times, given as pairs of relay hostnames and RTTs.
+    /// Expired measurements are discarded.
+    pub fn update_latencies(&self, measurements: impl IntoIterator<Item = (String, Duration)>) {
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies.remove_expired();
+        for (hostname, rtt) in measurements {
+            latencies.insert(hostname, rtt);
+        }
+        self.clear_biased_relays();
+    }
+
+    /// Returns the relays whose latency should be measured for the current constraints. This is
+    /// empty unless [`RelayConstraints::prefer_low_latency`] is enabled.
+    ///
+    /// The candidates are all tunnel relays which match the user's location (and entry and middle
+    /// locations, if multihop is enabled), provider and ownership constraints.
+    pub fn latency_probe_candidates(&self) -> Vec<Relay> {
+        let config_guard = self.config.lock().unwrap();
+        let SpecializedSelectorConfig::Normal(normal_config) =
+            SpecializedSelectorConfig::from(&*config_guard)
+        else {
+            return vec![];
+        };
+        if !normal_config.user_preferences.prefer_low_latency {
+            return vec![];
+        }
+        let Ok(query) = RelayQuery::try_from(normal_config.clone()) else {
+            return vec![];
+        };
+        let parsed_relays = self.parsed_relays.lock().unwrap();
+
+        let mut candidates =
+            filter_matching_relay_list(&query, &parsed_relays, normal_config.custom_lists);
+        if query.wireguard_constraints().multihop() {
+            let hop_locations = std::iter::once(&query.wireguard_constraints().entry_location)
+                .chain(query.wireguard_constraints().middle_locations());
+            for location in hop_locations {
+                let mut hop_query = query.clone();
+                if hop_query.set_location(location.clone()).is_ok() {
+                    candidates.extend(filter_matching_relay_list(
+                        &hop_query,
+                        &parsed_relays,
+                        normal_config.custom_lists,
+                    ));
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .filter(|relay| !matcher::filter_bridge(relay))
+            .unique_by(|relay| relay.hostname.clone())
+            .collect()
+    }
+
+    /// If the user prefers relays with a low latency, return a copy of `parsed_relays` where the
+    /// relay weights have been biased by the measured latencies. Returns `None` if the relay
+    /// weights should be left untouched.
+    ///
+    /// The copy is cached, so `parsed_relays` must be the relays guarded by `self.parsed_relays`.
+    fn latency_biased_relays(
+        &self,
+        user_config: &NormalSelectorConfig<'_>,
+        parsed_relays: &ParsedRelays,
+    ) -> Option<Arc<ParsedRelays>> {
+        if !user_config.user_preferences.prefer_low_latency {
+            return None;
+        }
+        let latencies = self.latencies.lock().unwrap();
+        if latencies.is_empty() {
+            log::debug!("Ignoring latency preference since no relays have been measured");
+            return None;
+        }
+
+        let mut biased_relays = self.biased_relays.lock().unwrap();
+        let now = Instant::now();
+        if let Some(cached) = biased_relays.as_ref().filter(|cached| {
+            cached
+                .valid_until
+                .is_some_and(|valid_until| now < valid_until)
+        }) {
+            return Some(cached.relays.clone());
+        }
+        let relays = Arc::new(parsed_relays.with_latency_bias(&latencies));
+        *biased_relays = Some(BiasedRelays {
+            relays: relays.clone(),
+            valid_until: latencies.next_expiry(),
+        });
+        Some(relays)
+    }
+
+    /// Drop the cached [`BiasedRelays`]. This must be done whenever the relays or the latency
+    /// measurements change.
+    fn clear_biased_relays(&self) {
+        *self.biased_relays.lock().unwrap() = None;
     }
 
     fn set_overrides(&mut self, relay_overrides: &[RelayOverride]) {
         let mut parsed_relays = self.parsed_relays.lock().unwrap();
         parsed_relays.set_overrides(relay_overrides);
+        self.clear_biased_relays();
     }
 
     /// Returns all countries and cities. The cities in the object returned does not have any
@@ -531,21 +685,67 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let parsed_relays = &self.parsed_relays.lock().unwrap();
+                let biased_relays = self.latency_biased_relays(&normal_config, parsed_relays);
+                let parsed_relays = biased_relays.as_deref().unwrap_or(parsed_relays);
                 Self::get_relay_inner(&query, parsed_relays, normal_config.custom_lists)
             }
         }
     }
 
     /// Returns a random relay and relay endpoint matching the current constraints corresponding to
-    /// `retry_attempt` in [`RETRY_ORDER`] while considering [runtime_params][`RuntimeParameters`].
-    ///
-    /// [`RETRY_ORDER`]: crate::RETRY_ORDER
+    /// `retry_attempt` in the configured [retry order][`SelectorConfig::retry_order`] while
+    /// considering [runtime_params][`RuntimeParameters`].
     pub fn get_relay(
         &self,
         retry_attempt: usize,
         runtime_params: RuntimeParameters,
     ) -> Result<GetRelay, Error> {
-        self.get_relay_with_custom_params(retry_attempt, &RETRY_ORDER, runtime_params)
+        let retry_order = self.config.lock().unwrap().retry_order.clone();
+        match self.get_relay_with_custom_params(retry_attempt, &retry_order, runtime_params.clone())
+        {
+            // A user-defined retry order may stop matching anything when the relay constraints or
+            // the relay list change. Fall back to the default retry order rather than failing
+            // every attempt.
+            Err(Error::NoRelay) if retry_order != *RETRY_ORDER => {
+                log::debug!("No relay matches the retry order. Falling back to the default one");
+                self.get_relay_with_custom_params(retry_attempt, &RETRY_ORDER, runtime_params)
+            }
+            result => result,
+        }
+    }
+
+    /// Check whether `retry_order` may be used as the retry order. Every attempt must be valid on
+    /// its own, and at least one of them has to be compatible with the current relay constraints.
+    /// Any retry order is accepted if a custom tunnel endpoint is used, since the retry order is
+    /// not used then.
+    pub fn validate_retry_order(&self, retry_order: &[RetryAttempt]) -> Result<(), Error> {
+        let queries = retry_order
+            .iter()
+            .cloned()
+            .map(RelayQuery::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if Self::is_compatible_retry_order(&queries, &self.config.lock().unwrap()) {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleRetryOrder)
+        }
+    }
+
+    /// Whether any query in `retry_order` can be combined with the relay constraints in `config`.
+    /// This is always the case if a custom tunnel endpoint is used.
+    fn is_compatible_retry_order(retry_order: &[RelayQuery], config: &SelectorConfig) -> bool {
+        let SpecializedSelectorConfig::Normal(normal_config) =
+            SpecializedSelectorConfig::from(config)
+        else {
+            return true;
+        };
+        let Ok(user_query) = RelayQuery::try_from(normal_config) else {
+            return false;
+        };
+        retry_order
+            .iter()
+            .any(|query| query.clone().intersection(user_query.clone()).is_some())
     }
 
     /// Returns a random relay and relay endpoint matching the current constraints defined by
@@ -567,6 +767,8 @@ impl RelaySelector {
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let parsed_relays = &self.parsed_relays.lock().unwrap();
+                let biased_relays = self.latency_biased_relays(&normal_config, parsed_relays);
+                let parsed_relays = biased_relays.as_deref().unwrap_or(parsed_relays);
                 // Merge user preferences with the relay selector's default preferences.
                 let query = Self::pick_and_merge_query(
                     retry_attempt,
@@ -742,6 +944,11 @@ impl RelaySelector {
                     }
                 }
             }
+        } else if !query.wireguard_constraints().middle_locations().is_empty() {
+            // Every hop of a cascade is chosen explicitly, so the automatic entry selection for
+            // DAITA does not apply here.
+            let cascade = Self::get_wireguard_cascade_config(query, custom_lists, parsed_relays)?;
+            WireguardConfig::from(cascade)
         } else {
             // A DAITA compatible entry should be used even when the exit is DAITA compatible.
             // This only makes sense in context: The user is no longer able to explicitly choose an
@@ -877,6 +1084,67 @@ impl RelaySelector {
         Ok(Multihop::new(entry.clone(), exit.clone()))
     }
 
+    /// This function selects a valid entry, middle and exit relays to be used in a multihop
+    /// configuration with more than two hops. No two hops will share a relay or a provider.
+    ///
+    /// # Returns
+    /// * An `Err` if no relay can be chosen for some hop
+    /// * `Ok(WireguardConfig::Multihop)` otherwise
+    fn get_wireguard_cascade_config(
+        query: &RelayQuery,
+        custom_lists: &CustomListsSettings,
+        parsed_relays: &ParsedRelays,
+    ) -> Result<Multihop, Error> {
+        let wireguard_constraints = query.wireguard_constraints();
+
+        // DAITA should only be enabled for the entry relay
+        let mut inner_relay_query = query.clone();
+        let mut wg_constraints = wireguard_constraints.clone();
+        wg_constraints.daita = Constraint::Only(false);
+        inner_relay_query.set_wireguard_constraints(wg_constraints)?;
+
+        // One query per hop, ordered from the entry to the exit relay.
+        let mut hop_queries = vec![];
+        let mut entry_relay_query = query.clone();
+        entry_relay_query.set_location(wireguard_constraints.entry_location.clone())?;
+        hop_queries.push(entry_relay_query);
+        for location in wireguard_constraints.middle_locations() {
+            let mut middle_relay_query = inner_relay_query.clone();
+            middle_relay_query.set_location(location.clone())?;
+            hop_queries.push(middle_relay_query);
+        }
+        hop_queries.push(inner_relay_query);
+
+        let hop_candidates = hop_queries
+            .iter()
+            .map(|query| filter_matching_relay_list(query, parsed_relays, custom_lists))
+            .collect_vec();
+
+        // Pick relays for the most constrained hops first, so that the choices made for the other
+        // hops are less likely to leave them without any candidates.
+        let mut picked: Vec<Option<&Relay>> = vec![None; hop_candidates.len()];
+        let hops = (0..hop_candidates.len()).sorted_by_key(|&hop| hop_candidates[hop].len());
+        for hop in hops {
+            let available = hop_candidates[hop].iter().filter(|candidate| {
+                picked.iter().flatten().all(|relay| {
+                    relay.hostname != candidate.hostname && relay.provider != candidate.provider
+                })
+            });
+            let relay = helpers::pick_random_relay_weighted(available, |relay| relay.weight)
+                .ok_or(Error::NoRelay)?;
+            picked[hop] = Some(relay);
+        }
+
+        let mut relays = picked
+            .into_iter()
+            .map(|relay| relay.expect("a relay was picked for every hop").clone())
+            .collect_vec();
+        let exit = relays.pop().expect("there is an exit hop");
+        let entry = relays.remove(0);
+
+        Ok(Multihop::new(entry, exit).with_middle(relays))
+    }
+
     /// Constructs a [`MullvadEndpoint`] with details for how to connect to `relay`.
     ///
     /// [`MullvadEndpoint`]: mullvad_types::endpoint::MullvadEndpoint
@@ -932,6 +1200,14 @@ impl RelaySelector {
 
                 Ok(Some(obfuscation))
             }
+            ObfuscationQuery::Quic => helpers::get_quic_obfuscator(obfuscator_relay, endpoint)
+                .map(Some)
+                .map_err(box_obfsucation_error),
+            ObfuscationQuery::Socks5(settings) => {
+                helpers::get_socks5_obfuscator(settings, obfuscator_relay, endpoint)
+                    .map(Some)
+                    .map_err(box_obfsucation_error)
+            }
         }
     }
 
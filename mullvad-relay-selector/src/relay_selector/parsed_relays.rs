@@ -20,6 +20,7 @@ use mullvad_types::{
     relay_list::{Relay, RelayList},
 };
 
+use super::latency::RelayLatencies;
 use crate::{constants::UDP2TCP_PORTS, error::Error};
 
 pub(crate) struct ParsedRelays {
@@ -77,6 +78,19 @@ impl ParsedRelays {
         self.overrides = new_overrides.to_vec();
     }
 
+    /// Return a copy of `self` where the relay weights have been biased towards relays with a low
+    /// measured latency. See [`RelayLatencies`] for details.
+    pub(crate) fn with_latency_bias(&self, latencies: &RelayLatencies) -> Self {
+        let mut parsed_list = self.parsed_list.clone();
+        latencies.apply_bias(&mut parsed_list);
+        ParsedRelays {
+            last_updated: self.last_updated,
+            parsed_list,
+            original_list: self.original_list.clone(),
+            overrides: self.overrides.clone(),
+        }
+    }
+
     pub(crate) fn empty() -> Self {
         ParsedRelays {
             last_updated: UNIX_EPOCH,
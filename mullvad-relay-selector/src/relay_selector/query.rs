@@ -34,13 +34,14 @@ use mullvad_types::{
     relay_constraints::{
         BridgeConstraints, BridgeSettings, BridgeState, BridgeType, LocationConstraint,
         ObfuscationSettings, OpenVpnConstraints, Ownership, Providers, RelayConstraints,
-        RelaySettings, SelectedObfuscation, ShadowsocksSettings, TransportPort,
-        Udp2TcpObfuscationSettings, WireguardConstraints,
+        RelaySettings, RetryAttempt, SelectedObfuscation, ShadowsocksSettings,
+        Socks5ObfuscationSettings, TransportPort, Udp2TcpObfuscationSettings, WireguardConstraints,
+        MAX_MIDDLE_HOPS,
     },
     wireguard::QuantumResistantState,
     Intersection,
 };
-use talpid_types::net::{proxy::CustomProxy, IpVersion, TunnelType};
+use talpid_types::net::{proxy::CustomProxy, IpVersion, TransportProtocol, TunnelType};
 
 /// Represents a query for a relay based on various constraints.
 ///
@@ -114,6 +115,13 @@ impl RelayQuery {
             }
             self.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
         }
+        let middle_hops = self.wireguard_constraints.middle_locations().len();
+        if middle_hops > MAX_MIDDLE_HOPS {
+            log::error!(
+                "Cannot use more than {MAX_MIDDLE_HOPS} middle relays (requested {middle_hops})"
+            );
+            return Err(Error::InvalidConstraints);
+        }
         Ok(())
     }
 
@@ -220,6 +228,9 @@ impl RelayQuery {
             tunnel_protocol: self.tunnel_protocol,
             wireguard_constraints: self.wireguard_constraints.into_constraints(),
             openvpn_constraints: self.openvpn_constraints.into_constraints(),
+            // Latency bias is a preference of the relay selector, not a constraint on the relays
+            // which may be selected, and is thus not part of the query.
+            prefer_low_latency: false,
         };
 
         (constraints, bridge_state, bridge_settings, obfuscation)
@@ -259,6 +270,55 @@ impl From<RelayQuery> for RelaySettings {
     }
 }
 
+impl TryFrom<RetryAttempt> for RelayQuery {
+    type Error = Error;
+
+    /// The mapping from a user-defined [`RetryAttempt`] to [`RelayQuery`]. Fails if the attempt
+    /// asks for an OpenVPN bridge over UDP.
+    fn try_from(attempt: RetryAttempt) -> Result<Self, Self::Error> {
+        let mut query = RelayQuery::default();
+        match attempt {
+            RetryAttempt::Any => (),
+            RetryAttempt::Wireguard {
+                port,
+                ip_version,
+                obfuscation,
+            } => {
+                query.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
+                query.wireguard_constraints.port = port;
+                query.wireguard_constraints.ip_version = ip_version;
+                query.wireguard_constraints.obfuscation =
+                    ObfuscationQuery::from(ObfuscationSettings {
+                        selected_obfuscation: obfuscation,
+                        ..Default::default()
+                    });
+            }
+            RetryAttempt::Openvpn { port, bridge } => {
+                query.tunnel_protocol = Constraint::Only(TunnelType::OpenVpn);
+                query.openvpn_constraints.port = port;
+                if bridge {
+                    // Bridges only support TCP
+                    query.openvpn_constraints.port = match port {
+                        Constraint::Any => Constraint::Only(TransportPort {
+                            protocol: TransportProtocol::Tcp,
+                            port: Constraint::Any,
+                        }),
+                        Constraint::Only(TransportPort {
+                            protocol: TransportProtocol::Udp,
+                            ..
+                        }) => return Err(Error::InvalidConstraints),
+                        tcp_port => tcp_port,
+                    };
+                    query.openvpn_constraints.bridge_settings =
+                        BridgeQuery::Normal(BridgeConstraints::default());
+                }
+            }
+        }
+        query.validate()?;
+        Ok(query)
+    }
+}
+
 /// A query for a relay with Wireguard-specific properties, such as `multihop` and [wireguard
 /// obfuscation][`SelectedObfuscation`].
 ///
@@ -274,6 +334,7 @@ pub struct WireguardRelayQuery {
     pub ip_version: Constraint<IpVersion>,
     pub use_multihop: Constraint<bool>,
     pub entry_location: Constraint<LocationConstraint>,
+    pub middle_locations: Constraint<Vec<Constraint<LocationConstraint>>>,
     pub obfuscation: ObfuscationQuery,
     pub daita: Constraint<bool>,
     pub daita_use_multihop_if_necessary: Constraint<bool>,
@@ -287,6 +348,8 @@ pub enum ObfuscationQuery {
     Auto,
     Udp2tcp(Udp2TcpObfuscationSettings),
     Shadowsocks(ShadowsocksSettings),
+    Quic,
+    Socks5(Socks5ObfuscationSettings),
 }
 
 impl ObfuscationQuery {
@@ -310,6 +373,15 @@ impl ObfuscationQuery {
                 shadowsocks: settings,
                 ..Default::default()
             },
+            ObfuscationQuery::Quic => ObfuscationSettings {
+                selected_obfuscation: SelectedObfuscation::Quic,
+                ..Default::default()
+            },
+            ObfuscationQuery::Socks5(settings) => ObfuscationSettings {
+                selected_obfuscation: SelectedObfuscation::Socks5,
+                socks5: settings,
+                ..Default::default()
+            },
         }
     }
 }
@@ -327,6 +399,8 @@ impl From<ObfuscationSettings> for ObfuscationQuery {
             SelectedObfuscation::Shadowsocks => {
                 ObfuscationQuery::Shadowsocks(obfuscation.shadowsocks)
             }
+            SelectedObfuscation::Quic => ObfuscationQuery::Quic,
+            SelectedObfuscation::Socks5 => ObfuscationQuery::Socks5(obfuscation.socks5),
         }
     }
 }
@@ -342,6 +416,10 @@ impl Intersection for ObfuscationQuery {
             (ObfuscationQuery::Shadowsocks(a), ObfuscationQuery::Shadowsocks(b)) => {
                 Some(ObfuscationQuery::Shadowsocks(a.intersection(b)?))
             }
+            (ObfuscationQuery::Quic, ObfuscationQuery::Quic) => Some(ObfuscationQuery::Quic),
+            (ObfuscationQuery::Socks5(a), ObfuscationQuery::Socks5(b)) => {
+                Some(ObfuscationQuery::Socks5(a.intersection(b)?))
+            }
             _ => None,
         }
     }
@@ -351,6 +429,15 @@ impl WireguardRelayQuery {
     pub fn multihop(&self) -> bool {
         matches!(self.use_multihop, Constraint::Only(true))
     }
+
+    /// Locations of the relays between the entry and exit relay. Empty unless multihop is
+    /// enabled.
+    pub fn middle_locations(&self) -> &[Constraint<LocationConstraint>] {
+        match &self.middle_locations {
+            Constraint::Only(locations) if self.multihop() => locations,
+            _ => &[],
+        }
+    }
 }
 
 impl WireguardRelayQuery {
@@ -360,6 +447,7 @@ impl WireguardRelayQuery {
             ip_version: Constraint::Any,
             use_multihop: Constraint::Any,
             entry_location: Constraint::Any,
+            middle_locations: Constraint::Any,
             obfuscation: ObfuscationQuery::Auto,
             daita: Constraint::Any,
             daita_use_multihop_if_necessary: Constraint::Any,
@@ -373,6 +461,7 @@ impl WireguardRelayQuery {
             port: self.port,
             ip_version: self.ip_version,
             entry_location: self.entry_location,
+            middle_locations: self.middle_locations.unwrap_or_default(),
             use_multihop: self.use_multihop.unwrap_or(false),
         }
     }
@@ -391,6 +480,7 @@ impl From<WireguardRelayQuery> for WireguardConstraints {
             port: value.port,
             ip_version: value.ip_version,
             entry_location: value.entry_location,
+            middle_locations: value.middle_locations.unwrap_or_default(),
             use_multihop: value.use_multihop.unwrap_or(false),
         }
     }
@@ -512,11 +602,12 @@ pub mod builder {
         constraints::Constraint,
         relay_constraints::{
             BridgeConstraints, LocationConstraint, RelayConstraints, SelectedObfuscation,
-            ShadowsocksSettings, TransportPort, Udp2TcpObfuscationSettings,
+            ShadowsocksSettings, Socks5ObfuscationSettings, TransportPort,
+            Udp2TcpObfuscationSettings,
         },
         wireguard::QuantumResistantState,
     };
-    use talpid_types::net::TunnelType;
+    use talpid_types::net::{proxy::Socks5Remote, TunnelType};
 
     use super::{BridgeQuery, ObfuscationQuery, RelayQuery};
 
@@ -543,6 +634,9 @@ pub mod builder {
     ///  in the final `RelayQuery` is `Constraint::Any`.
     pub struct Any;
 
+    /// QUIC obfuscation has no settings of its own, so this type marks that it has been selected.
+    pub struct Quic;
+
     // This impl-block is quantified over all configurations, e.g. [`Any`],
     // [`WireguardRelayQuery`] & [`OpenVpnRelayQuery`]
     impl<VpnProtocol> RelayQueryBuilder<VpnProtocol> {
@@ -744,6 +838,17 @@ pub mod builder {
             self.query.wireguard_constraints.entry_location = Constraint::Only(location.into());
             self
         }
+
+        /// Add a relay location between the entry and the exit relay in a multihop
+        /// configuration. Middle relays are traversed in the order that they are added.
+        pub fn middle(mut self, location: impl Into<LocationConstraint>) -> Self {
+            let location = Constraint::Only(location.into());
+            match &mut self.query.wireguard_constraints.middle_locations {
+                Constraint::Only(locations) => locations.push(location),
+                middle_locations => *middle_locations = Constraint::Only(vec![location]),
+            }
+            self
+        }
     }
 
     impl<Multihop, Daita, QuantumResistant>
@@ -794,6 +899,44 @@ pub mod builder {
                 protocol,
             }
         }
+
+        /// Enable QUIC obfuscation.
+        pub fn quic(
+            mut self,
+        ) -> RelayQueryBuilder<Wireguard<Multihop, Quic, Daita, QuantumResistant>> {
+            let protocol = Wireguard {
+                multihop: self.protocol.multihop,
+                obfuscation: Quic,
+                daita: self.protocol.daita,
+                quantum_resistant: self.protocol.quantum_resistant,
+            };
+            self.query.wireguard_constraints.obfuscation = ObfuscationQuery::Quic;
+            RelayQueryBuilder {
+                query: self.query,
+                protocol,
+            }
+        }
+
+        /// Enable SOCKS5 obfuscation through `proxy`.
+        pub fn socks5(
+            mut self,
+            proxy: Socks5Remote,
+        ) -> RelayQueryBuilder<
+            Wireguard<Multihop, Socks5ObfuscationSettings, Daita, QuantumResistant>,
+        > {
+            let obfuscation = Socks5ObfuscationSettings { proxy: Some(proxy) };
+            let protocol = Wireguard {
+                multihop: self.protocol.multihop,
+                obfuscation: obfuscation.clone(),
+                daita: self.protocol.daita,
+                quantum_resistant: self.protocol.quantum_resistant,
+            };
+            self.query.wireguard_constraints.obfuscation = ObfuscationQuery::Socks5(obfuscation);
+            RelayQueryBuilder {
+                query: self.query,
+                protocol,
+            }
+        }
     }
 
     impl<Multihop, Daita, QuantumResistant>
@@ -952,12 +1095,12 @@ mod test {
     use mullvad_types::{
         constraints::Constraint,
         relay_constraints::{
-            ObfuscationSettings, SelectedObfuscation, ShadowsocksSettings,
-            Udp2TcpObfuscationSettings,
+            ObfuscationSettings, RetryAttempt, SelectedObfuscation, ShadowsocksSettings,
+            TransportPort, Udp2TcpObfuscationSettings,
         },
     };
     use proptest::prelude::*;
-    use talpid_types::net::TunnelType;
+    use talpid_types::net::{IpVersion, TransportProtocol, TunnelType};
 
     use super::{builder::RelayQueryBuilder, Intersection, ObfuscationQuery, RelayQuery};
 
@@ -1049,6 +1192,7 @@ mod test {
                 shadowsocks: ShadowsocksSettings {
                     port: port2,
                 },
+                socks5: Default::default(),
             });
             assert_eq!(query, ObfuscationQuery::Auto);
         }
@@ -1131,4 +1275,67 @@ mod test {
             Constraint::Only(TunnelType::Wireguard)
         );
     }
+
+    /// User-defined retry attempts should map to the same queries as the equivalent queries
+    /// constructed with the builder
+    #[test]
+    fn test_retry_attempt_to_query() {
+        assert_eq!(
+            RelayQuery::try_from(RetryAttempt::Any).unwrap(),
+            RelayQueryBuilder::new().build()
+        );
+        assert_eq!(
+            RelayQuery::try_from(RetryAttempt::Wireguard {
+                port: Constraint::Any,
+                ip_version: Constraint::Only(IpVersion::V6),
+                obfuscation: SelectedObfuscation::Udp2Tcp,
+            })
+            .unwrap(),
+            RelayQueryBuilder::new()
+                .wireguard()
+                .udp2tcp()
+                .ip_version(IpVersion::V6)
+                .build()
+        );
+        assert_eq!(
+            RelayQuery::try_from(RetryAttempt::Openvpn {
+                port: Constraint::Only(TransportPort {
+                    protocol: TransportProtocol::Tcp,
+                    port: Constraint::Only(443),
+                }),
+                bridge: false,
+            })
+            .unwrap(),
+            RelayQueryBuilder::new()
+                .openvpn()
+                .transport_protocol(TransportProtocol::Tcp)
+                .port(443)
+                .build()
+        );
+        assert_eq!(
+            RelayQuery::try_from(RetryAttempt::Openvpn {
+                port: Constraint::Any,
+                bridge: true,
+            })
+            .unwrap(),
+            RelayQueryBuilder::new()
+                .openvpn()
+                .transport_protocol(TransportProtocol::Tcp)
+                .bridge()
+                .build()
+        );
+    }
+
+    /// Bridges only support TCP, so a retry attempt using a bridge over UDP is invalid
+    #[test]
+    fn test_retry_attempt_bridge_over_udp() {
+        RelayQuery::try_from(RetryAttempt::Openvpn {
+            port: Constraint::Only(TransportPort {
+                protocol: TransportProtocol::Udp,
+                port: Constraint::Any,
+            }),
+            bridge: true,
+        })
+        .expect_err("expected bridge over UDP to be invalid");
+    }
 }
@@ -4,15 +4,19 @@
 use mullvad_types::relay_list::{Relay, RelayEndpointData};
 
 /// - [`WireguardConfig::Singlehop`]: A wireguard relay where VPN traffic enters and exits.
-/// - [`WireguardConfig::Multihop`]: Two wireguard relays to be used in a multihop circuit. VPN
-///   traffic will enter through `entry` and eventually exit through `exit` before the traffic will
-///   actually be routed to the internet.
+/// - [`WireguardConfig::Multihop`]: Two or more wireguard relays to be used in a multihop circuit.
+///   VPN traffic will enter through `entry`, pass through each relay in `middle` and eventually
+///   exit through `exit` before the traffic will actually be routed to the internet.
 #[derive(Clone, Debug)]
 pub enum WireguardConfig {
     /// An exit relay.
     Singlehop { exit: Relay },
-    /// An entry and an exit relay.
-    Multihop { exit: Relay, entry: Relay },
+    /// An entry and an exit relay, optionally with relays in between.
+    Multihop {
+        exit: Relay,
+        entry: Relay,
+        middle: Vec<Relay>,
+    },
 }
 
 /// A type representing single Wireguard relay.
@@ -25,7 +29,7 @@ pub enum WireguardConfig {
 /// additional validation which guarantees that the relay actually is a Wireguard relay, while
 /// [`Relay`] is not guaranteed to be a Wireguard relay.
 pub struct Singlehop(Relay);
-/// A type representing two Wireguard relay - an entry and an exit.
+/// A type representing two or more Wireguard relays - an entry, an exit and any relays in between.
 ///
 /// Before you can read any data out of a [`Multihop`] value uou need to convert it to
 /// [`WireguardConfig`]. This is easy since [`Multihop`] implements [`Into<WireguardConfig>`].
@@ -35,6 +39,7 @@ pub struct Singlehop(Relay);
 /// validation on the entry and exit relays.
 pub struct Multihop {
     entry: Relay,
+    middle: Vec<Relay>,
     exit: Relay,
 }
 
@@ -49,6 +54,7 @@ impl From<Multihop> for WireguardConfig {
         WireguardConfig::Multihop {
             exit: relay.exit,
             entry: relay.entry,
+            middle: relay.middle,
         }
     }
 }
@@ -76,6 +82,21 @@ impl Multihop {
             entry.endpoint_data,
             RelayEndpointData::Wireguard(_)
         ));
-        Multihop { exit, entry }
+        Multihop {
+            exit,
+            entry,
+            middle: Vec::new(),
+        }
+    }
+
+    /// Place `middle` between the entry and the exit relay, in the order that traffic passes
+    /// through them.
+    pub fn with_middle(mut self, middle: Vec<Relay>) -> Self {
+        // FIXME: This assert would be better to encode at the type level.
+        assert!(middle
+            .iter()
+            .all(|relay| matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))));
+        self.middle = middle;
+        self
     }
 }
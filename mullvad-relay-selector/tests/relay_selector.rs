@@ -1,12 +1,15 @@
 //! Tests for verifying that the relay selector works as expected.
 
+use ipnetwork::IpNetwork;
 use std::{
     collections::HashSet,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::LazyLock,
+    time::Duration,
 };
 use talpid_types::net::{
     obfuscation::ObfuscatorConfig,
+    proxy::Socks5Remote,
     wireguard::PublicKey,
     Endpoint, IpVersion,
     TransportProtocol::{Tcp, Udp},
@@ -14,6 +17,7 @@ use talpid_types::net::{
 };
 
 use mullvad_relay_selector::{
+    latency::RelayLatencies,
     query::{builder::RelayQueryBuilder, BridgeQuery, ObfuscationQuery, OpenVpnRelayQuery},
     Error, GetRelay, RelaySelector, RuntimeParameters, SelectedObfuscator, SelectorConfig,
     WireguardConfig, RETRY_ORDER,
@@ -24,13 +28,14 @@ use mullvad_types::{
     location::Location,
     relay_constraints::{
         BridgeConstraints, BridgeState, GeographicLocationConstraint, Ownership, Providers,
-        RelayOverride, TransportPort,
+        RelayConstraints, RelayOverride, RetryAttempt, SelectedObfuscation, TransportPort,
     },
     relay_list::{
-        BridgeEndpointData, OpenVpnEndpoint, OpenVpnEndpointData, Relay, RelayEndpointData,
-        RelayList, RelayListCity, RelayListCountry, ShadowsocksEndpointData, WireguardEndpointData,
-        WireguardRelayEndpointData,
+        BridgeEndpointData, OpenVpnEndpoint, OpenVpnEndpointData, QuicEndpointData, Relay,
+        RelayEndpointData, RelayList, RelayListCity, RelayListCountry, ShadowsocksEndpointData,
+        WireguardEndpointData, WireguardRelayEndpointData,
     },
+    settings::Settings,
 };
 
 static DUMMY_LOCATION: LazyLock<Location> = LazyLock::new(|| Location {
@@ -71,6 +76,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: true,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: None,
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -92,6 +98,10 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: false,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: Some(QuicEndpointData {
+                            domain: QUIC_RELAY_DOMAIN.to_string(),
+                            addr_in: QUIC_RELAY_ADDRS.to_vec(),
+                        }),
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -113,6 +123,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: true,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: None,
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -239,6 +250,7 @@ static SHADOWSOCKS_RELAY: LazyLock<Relay> = LazyLock::new(|| Relay {
         public_key: PublicKey::from_base64("eaNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=").unwrap(),
         daita: false,
         shadowsocks_extra_addr_in: SHADOWSOCKS_RELAY_EXTRA_ADDRS.to_vec(),
+        quic: None,
     }),
     location: DUMMY_LOCATION.clone(),
 });
@@ -251,6 +263,13 @@ const SHADOWSOCKS_RELAY_EXTRA_ADDRS: &[IpAddr; 2] = &[
 static SHADOWSOCKS_RELAY_LOCATION: LazyLock<GeographicLocationConstraint> =
     LazyLock::new(|| GeographicLocationConstraint::hostname("se", "got", "se1337-wireguard"));
 
+/// se10-wireguard is the only relay that accepts WireGuard over QUIC
+const QUIC_RELAY_DOMAIN: &str = "se10-wireguard.relays.mullvad.net";
+const QUIC_RELAY_ADDRS: &[IpAddr; 2] = &[
+    IpAddr::V4(Ipv4Addr::new(185, 213, 154, 70)),
+    IpAddr::V6(Ipv6Addr::new(0x2a03, 0x1b20, 5, 0xf011, 0, 0, 0, 0xa10e)),
+];
+
 // Helper functions
 fn unwrap_relay(get_result: GetRelay) -> Relay {
     match get_result {
@@ -400,8 +419,10 @@ fn test_retry_order() {
                 assert!(match &query.wireguard_constraints().obfuscation {
                     ObfuscationQuery::Auto => true,
                     ObfuscationQuery::Off => obfuscator.is_none(),
-                    ObfuscationQuery::Udp2tcp(_) | ObfuscationQuery::Shadowsocks(_) =>
-                        obfuscator.is_some(),
+                    ObfuscationQuery::Udp2tcp(_)
+                    | ObfuscationQuery::Shadowsocks(_)
+                    | ObfuscationQuery::Quic
+                    | ObfuscationQuery::Socks5(_) => obfuscator.is_some(),
                 });
             }
             GetRelay::OpenVpn {
@@ -527,6 +548,7 @@ fn test_wireguard_entry() {
                             .unwrap(),
                             daita: false,
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                         }),
                         location: DUMMY_LOCATION.clone(),
                     },
@@ -548,6 +570,7 @@ fn test_wireguard_entry() {
                             .unwrap(),
                             daita: false,
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                         }),
                         location: DUMMY_LOCATION.clone(),
                     },
@@ -593,7 +616,7 @@ fn test_wireguard_entry() {
         let relay = relay_selector.get_relay_by_query(query).unwrap();
         match relay {
             GetRelay::Wireguard {
-                inner: WireguardConfig::Multihop { exit, entry },
+                inner: WireguardConfig::Multihop { exit, entry, .. },
                 ..
             } => {
                 assert_eq!(entry.hostname, specific_hostname);
@@ -621,7 +644,7 @@ fn test_wireguard_entry() {
         let relay = relay_selector.get_relay_by_query(query).unwrap();
         match relay {
             GetRelay::Wireguard {
-                inner: WireguardConfig::Multihop { exit, entry },
+                inner: WireguardConfig::Multihop { exit, entry, .. },
                 ..
             } => {
                 assert_eq!(exit.hostname, specific_hostname);
@@ -673,6 +696,69 @@ fn test_wireguard_entry_hostname_collision() {
         .is_ok())
 }
 
+/// Test that a cascade of three Wireguard relays is made up of distinct relays run by distinct
+/// providers, and that each peer only routes traffic to the next hop.
+#[test]
+fn test_wireguard_cascade() {
+    let relay_selector = default_relay_selector();
+
+    for _ in 0..100 {
+        let query = RelayQueryBuilder::new()
+            .wireguard()
+            .multihop()
+            .middle(GeographicLocationConstraint::country("se"))
+            .build();
+
+        let relay = relay_selector.get_relay_by_query(query).unwrap();
+        let GetRelay::Wireguard {
+            endpoint,
+            inner:
+                WireguardConfig::Multihop {
+                    exit,
+                    entry,
+                    middle,
+                },
+            ..
+        } = relay
+        else {
+            panic!("Relay selector should have picked a Wireguard cascade");
+        };
+
+        assert_eq!(middle.len(), 1);
+        let hops = [&entry, &middle[0], &exit];
+        let hostnames: HashSet<_> = hops.iter().map(|relay| &relay.hostname).collect();
+        let providers: HashSet<_> = hops.iter().map(|relay| &relay.provider).collect();
+        assert_eq!(hostnames.len(), hops.len());
+        assert_eq!(providers.len(), hops.len());
+
+        let exit_peer = endpoint.exit_peer.unwrap();
+        assert_eq!(endpoint.middle_peers.len(), 1);
+        let middle_peer = &endpoint.middle_peers[0];
+        assert_eq!(
+            endpoint.peer.allowed_ips,
+            vec![IpNetwork::from(middle_peer.endpoint.ip())]
+        );
+        assert_eq!(
+            middle_peer.allowed_ips,
+            vec![IpNetwork::from(exit_peer.endpoint.ip())]
+        );
+    }
+
+    // A middle relay may not be reused for another hop
+    let host1 = GeographicLocationConstraint::hostname("se", "got", "se9-wireguard");
+    let host2 = GeographicLocationConstraint::hostname("se", "got", "se10-wireguard");
+    let invalid_cascade_query = RelayQueryBuilder::new()
+        .wireguard()
+        .location(host1.clone())
+        .multihop()
+        .entry(host2)
+        .middle(host1)
+        .build();
+    assert!(relay_selector
+        .get_relay_by_query(invalid_cascade_query)
+        .is_err());
+}
+
 /// Test that the relay selector:
 /// * returns an OpenVPN relay given a constraint of a valid transport protocol + port combo
 /// * does *not* return an OpenVPN relay given a constraint of an *invalid* transport protocol +
@@ -856,6 +942,87 @@ fn test_selecting_wireguard_over_shadowsocks_extra_ips() {
     }
 }
 
+/// Test whether QUIC obfuscation is only used with relays that support it
+#[test]
+fn test_selecting_wireguard_over_quic() {
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .quic()
+        .ip_version(IpVersion::V4)
+        .build();
+    assert_eq!(
+        query.wireguard_constraints().obfuscation,
+        ObfuscationQuery::Quic
+    );
+
+    for _ in 0..10 {
+        let relay = relay_selector.get_relay_by_query(query.clone()).unwrap();
+        match relay {
+            GetRelay::Wireguard {
+                obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Quic { endpoint, hostname }, .. }),
+                inner: WireguardConfig::Singlehop { exit },
+                ..
+            } => {
+                assert_eq!(exit.hostname, "se10-wireguard");
+                assert_eq!(hostname, QUIC_RELAY_DOMAIN);
+                assert_eq!(endpoint.ip(), QUIC_RELAY_ADDRS[0]);
+                assert_eq!(endpoint.port(), 443);
+            }
+            wrong_relay => panic!(
+                "Relay selector should have picked a Wireguard relay with QUIC, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+}
+
+/// Test that SOCKS5 obfuscation relays traffic to the entry relay through the user's proxy
+#[test]
+fn test_selecting_wireguard_over_socks5() {
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+    let proxy = Socks5Remote::new((Ipv4Addr::new(192, 0, 2, 1), 1080));
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .socks5(proxy.clone())
+        .build();
+
+    let relay = relay_selector.get_relay_by_query(query).unwrap();
+    match relay {
+        GetRelay::Wireguard {
+            obfuscator:
+                Some(SelectedObfuscator {
+                    config:
+                        ObfuscatorConfig::Socks5 {
+                            endpoint,
+                            auth,
+                            wireguard_endpoint,
+                        },
+                    ..
+                }),
+            endpoint: wg_endpoint,
+            ..
+        } => {
+            assert_eq!(endpoint, proxy.endpoint);
+            assert_eq!(auth, None);
+            assert_eq!(wireguard_endpoint, wg_endpoint.peer.endpoint);
+        }
+        wrong_relay => panic!(
+            "Relay selector should have picked a Wireguard relay with SOCKS5, instead chose {wrong_relay:?}"
+        ),
+    }
+
+    // Without a configured proxy, no obfuscator can be selected
+    let mut query = RelayQueryBuilder::new().wireguard().build();
+    let mut wireguard_constraints = query.wireguard_constraints().clone();
+    wireguard_constraints.obfuscation = ObfuscationQuery::Socks5(Default::default());
+    query
+        .set_wireguard_constraints(wireguard_constraints)
+        .unwrap();
+    assert!(relay_selector.get_relay_by_query(query).is_err());
+}
+
 /// Ignore extra IPv4 addresses when overrides are set
 #[test]
 fn test_selecting_wireguard_ignore_extra_ips_override_v4() {
@@ -1198,6 +1365,7 @@ fn test_include_in_country() {
                             )
                             .unwrap(),
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                             daita: false,
                         }),
                         location: DUMMY_LOCATION.clone(),
@@ -1219,6 +1387,7 @@ fn test_include_in_country() {
                             )
                             .unwrap(),
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                             daita: false,
                         }),
                         location: DUMMY_LOCATION.clone(),
@@ -1446,7 +1615,7 @@ fn test_daita_smart_routing_overrides_multihop() {
             .expect("Expected to find a relay with daita_use_multihop_if_necessary");
         match relay {
                 GetRelay::Wireguard {
-                    inner: WireguardConfig::Multihop { entry, exit: _ },
+                    inner: WireguardConfig::Multihop { entry, .. },
                     ..
                 } => {
                     assert!(supports_daita(&entry), "entry relay must support DAITA");
@@ -1556,7 +1725,7 @@ fn test_daita() {
         .expect("Expected to find a relay with daita_use_multihop_if_necessary");
     match relay {
         GetRelay::Wireguard {
-            inner: WireguardConfig::Multihop { exit, entry },
+            inner: WireguardConfig::Multihop { exit, entry, .. },
             ..
         } => {
             assert!(supports_daita(&entry), "entry relay must support DAITA");
@@ -1617,7 +1786,7 @@ fn test_daita() {
     let relay = relay_selector.get_relay_by_query(query).unwrap();
     match relay {
         GetRelay::Wireguard {
-            inner: WireguardConfig::Multihop { exit: _, entry },
+            inner: WireguardConfig::Multihop { entry, .. },
             ..
         } => {
             assert!(supports_daita(&entry), "entry relay must support DAITA");
@@ -1638,7 +1807,7 @@ fn test_daita() {
     let relay = relay_selector.get_relay_by_query(query).unwrap();
     match relay {
         GetRelay::Wireguard {
-            inner: WireguardConfig::Multihop { exit, entry: _ },
+            inner: WireguardConfig::Multihop { exit, .. },
             ..
         } => {
             assert!(
@@ -1679,3 +1848,211 @@ fn valid_user_setting_should_yield_relay() {
         }
     }
 }
+
+/// Return a relay selector which prefers relays with a low latency.
+fn low_latency_relay_selector() -> RelaySelector {
+    let (mut user_constraints, ..) = RelayQueryBuilder::new()
+        .location(GeographicLocationConstraint::country("se"))
+        .build()
+        .into_settings();
+    user_constraints.prefer_low_latency = true;
+
+    let config = SelectorConfig {
+        relay_settings: user_constraints.into(),
+        ..SelectorConfig::default()
+    };
+    RelaySelector::from_list(config, RELAYS.clone())
+}
+
+/// If the user prefers relays with a low latency, the relay selector should be heavily biased
+/// towards the relay with the lowest measured round-trip time.
+#[test]
+fn test_prefer_low_latency() {
+    const FAST_RELAY: &str = "se10-wireguard";
+    let relay_selector = low_latency_relay_selector();
+
+    // Inject a fake latency table where one relay is much faster than every other relay
+    let mut latencies = RelayLatencies::new();
+    for relay in RELAYS.relays() {
+        latencies.insert(relay.hostname.clone(), Duration::from_secs(1));
+    }
+    latencies.insert(FAST_RELAY, Duration::from_millis(1));
+    relay_selector.set_latencies(latencies);
+
+    let fast_picks = (0..100)
+        .filter(|_| {
+            let relay = relay_selector
+                .get_relay(0, RuntimeParameters::default())
+                .unwrap();
+            unwrap_relay(relay).hostname == FAST_RELAY
+        })
+        .count();
+    assert!(
+        fast_picks > 90,
+        "expected {FAST_RELAY} to be preferred, but it was only picked {fast_picks} out of 100 times"
+    );
+}
+
+/// New latency measurements should be taken into account by the next relay selection.
+#[test]
+fn test_latency_updates_are_applied() {
+    const FAST_RELAY: &str = "se10-wireguard";
+    const NEW_FAST_RELAY: &str = "se9-wireguard";
+    let relay_selector = low_latency_relay_selector();
+    let pick_count = |hostname: &str| {
+        (0..100)
+            .filter(|_| {
+                let relay = relay_selector
+                    .get_relay(0, RuntimeParameters::default())
+                    .unwrap();
+                unwrap_relay(relay).hostname == hostname
+            })
+            .count()
+    };
+
+    let mut latencies = RelayLatencies::new();
+    for relay in RELAYS.relays() {
+        latencies.insert(relay.hostname.clone(), Duration::from_secs(1));
+    }
+    latencies.insert(FAST_RELAY, Duration::from_millis(1));
+    relay_selector.set_latencies(latencies);
+    assert!(pick_count(FAST_RELAY) > 90);
+
+    relay_selector.update_latencies([
+        (FAST_RELAY.to_owned(), Duration::from_secs(1)),
+        (NEW_FAST_RELAY.to_owned(), Duration::from_millis(1)),
+    ]);
+    let fast_picks = pick_count(NEW_FAST_RELAY);
+    assert!(
+        fast_picks > 90,
+        "expected {NEW_FAST_RELAY} to be preferred after the update, but it was only picked \
+        {fast_picks} out of 100 times"
+    );
+}
+
+/// Relays should only be probed for their latency if the user prefers relays with a low latency,
+/// and bridges should never be probed.
+#[test]
+fn test_latency_probe_candidates() {
+    let relay_selector = default_relay_selector();
+    assert!(relay_selector.latency_probe_candidates().is_empty());
+
+    let relay_selector = low_latency_relay_selector();
+    let candidates = relay_selector.latency_probe_candidates();
+    assert!(!candidates.is_empty());
+    assert!(
+        candidates
+            .iter()
+            .all(|relay| !matches!(relay.endpoint_data, RelayEndpointData::Bridge)),
+        "expected no bridges among the candidates: {candidates:?}"
+    );
+}
+
+/// A user-defined retry order should be used instead of [`RETRY_ORDER`], and the relay selector
+/// should loop back to its start once it has been exhausted.
+#[test]
+fn test_user_defined_retry_order() {
+    let settings = Settings {
+        retry_order: Some(vec![
+            RetryAttempt::Openvpn {
+                port: Constraint::Any,
+                bridge: false,
+            },
+            RetryAttempt::Wireguard {
+                port: Constraint::Any,
+                ip_version: Constraint::Any,
+                obfuscation: SelectedObfuscation::Udp2Tcp,
+            },
+        ]),
+        ..Settings::default()
+    };
+    let relay_selector =
+        RelaySelector::from_list(SelectorConfig::from_settings(&settings), RELAYS.clone());
+
+    for retry_attempt in 0..4 {
+        let relay = relay_selector
+            .get_relay(retry_attempt, RuntimeParameters::default())
+            .unwrap_or_else(|_| panic!("Retry attempt {retry_attempt} did not yield any relay"));
+        if retry_attempt % 2 == 0 {
+            assert_eq!(tunnel_type(&unwrap_relay(relay)), TunnelType::OpenVpn);
+        } else {
+            match relay {
+                GetRelay::Wireguard {
+                    obfuscator: Some(SelectedObfuscator { config, .. }),
+                    ..
+                } => assert!(matches!(config, ObfuscatorConfig::Udp2Tcp { .. })),
+                relay => panic!("Expected a WireGuard relay using udp2tcp, got {relay:?}"),
+            }
+        }
+    }
+}
+
+/// If the relay constraints change so that no attempt in a user-defined retry order is compatible
+/// with them, the relay selector should fall back to [`RETRY_ORDER`] instead of failing.
+#[test]
+fn test_incompatible_retry_order_falls_back_to_default() {
+    let mut settings = Settings {
+        retry_order: Some(vec![RetryAttempt::Openvpn {
+            port: Constraint::Any,
+            bridge: false,
+        }]),
+        ..Settings::default()
+    };
+    let mut relay_selector =
+        RelaySelector::from_list(SelectorConfig::from_settings(&settings), RELAYS.clone());
+
+    settings.relay_settings = RelayConstraints {
+        tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+        ..RelayConstraints::default()
+    }
+    .into();
+    relay_selector.set_config(SelectorConfig::from_settings(&settings));
+
+    for retry_attempt in 0..RETRY_ORDER.len() {
+        let relay = relay_selector
+            .get_relay(retry_attempt, RuntimeParameters::default())
+            .unwrap_or_else(|_| panic!("Retry attempt {retry_attempt} did not yield any relay"));
+        assert_eq!(tunnel_type(&unwrap_relay(relay)), TunnelType::Wireguard);
+    }
+}
+
+/// A retry order should be rejected if it contains an invalid attempt, or if none of its attempts
+/// are compatible with the user's constraints.
+#[test]
+fn test_validate_retry_order() {
+    let config = SelectorConfig {
+        relay_settings: RelayConstraints {
+            tunnel_protocol: Constraint::Only(TunnelType::Wireguard),
+            ..RelayConstraints::default()
+        }
+        .into(),
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+    let openvpn = RetryAttempt::Openvpn {
+        port: Constraint::Any,
+        bridge: false,
+    };
+
+    assert!(matches!(
+        relay_selector.validate_retry_order(&[openvpn.clone()]),
+        Err(Error::IncompatibleRetryOrder)
+    ));
+    assert!(matches!(
+        relay_selector.validate_retry_order(&[]),
+        Err(Error::IncompatibleRetryOrder)
+    ));
+    relay_selector
+        .validate_retry_order(&[openvpn, RetryAttempt::Any])
+        .expect("expected the retry order to be compatible");
+    assert!(matches!(
+        relay_selector.validate_retry_order(&[RetryAttempt::Openvpn {
+            port: Constraint::Only(TransportPort {
+                protocol: Udp,
+                port: Constraint::Any,
+            }),
+            bridge: true,
+        }]),
+        Err(Error::InvalidConstraints)
+    ));
+}
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+use std::time::Duration;
+use talpid_types::net::proxy::{CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote};
 
 /// Settings for API access methods.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -10,8 +12,13 @@ pub struct Settings {
     mullvad_bridges: AccessMethodSetting,
     #[serde(default = "Settings::create_encrypted_dns_proxy")]
     encrypted_dns_proxy: AccessMethodSetting,
+    #[serde(default = "Settings::create_system_proxy")]
+    system_proxy: AccessMethodSetting,
     /// Custom API access methods.
     custom: Vec<AccessMethodSetting>,
+    /// Access method to always try first, regardless of how well it has worked previously.
+    #[serde(default)]
+    pinned: Option<Id>,
 }
 
 impl Settings {
@@ -19,13 +26,16 @@ impl Settings {
         direct: AccessMethodSetting,
         mullvad_bridges: AccessMethodSetting,
         encrypted_dns_proxy: AccessMethodSetting,
+        system_proxy: AccessMethodSetting,
         custom: Vec<AccessMethodSetting>,
     ) -> Settings {
         Settings {
             direct,
             mullvad_bridges,
             encrypted_dns_proxy,
+            system_proxy,
             custom,
+            pinned: None,
         }
     }
 
@@ -52,6 +62,9 @@ impl Settings {
                 AccessMethod::Custom(_) => {
                     self.custom
                         .retain(|method| method.get_id() != *api_access_method);
+                    if self.pinned.as_ref() == Some(api_access_method) {
+                        self.pinned = None;
+                    }
                     self.ensure_consistent_state();
                     Ok(())
                 }
@@ -83,9 +96,32 @@ impl Settings {
     /// Remove all custom access methods.
     pub fn clear_custom(&mut self) {
         self.custom.clear();
+        if self.pinned().is_none() {
+            self.pinned = None;
+        }
         self.ensure_consistent_state();
     }
 
+    /// Return the access method that should always be tried first, if any.
+    pub fn pinned(&self) -> Option<&AccessMethodSetting> {
+        let pinned = self.pinned.as_ref()?;
+        self.iter().find(|setting| setting.get_id() == *pinned)
+    }
+
+    /// Pin an access method, so that it is always tried first. Passing `None` unpins the
+    /// currently pinned access method.
+    ///
+    /// This function will return an error if there is no access method with the given id.
+    pub fn set_pinned(&mut self, api_access_method: Option<Id>) -> Result<(), Error> {
+        if let Some(id) = &api_access_method {
+            if !self.iter().any(|setting| setting.get_id() == *id) {
+                return Err(Error::NoSuchMethod(id.clone()));
+            }
+        }
+        self.pinned = api_access_method;
+        Ok(())
+    }
+
     /// Check that `self` contains atleast one enabled access methods. If not,
     /// the `Direct` access method is re-enabled.
     fn ensure_consistent_state(&mut self) {
@@ -100,6 +136,7 @@ impl Settings {
         once(&self.direct)
             .chain(once(&self.mullvad_bridges))
             .chain(once(&self.encrypted_dns_proxy))
+            .chain(once(&self.system_proxy))
             .chain(&self.custom)
     }
 
@@ -109,6 +146,7 @@ impl Settings {
         once(&mut self.direct)
             .chain(once(&mut self.mullvad_bridges))
             .chain(once(&mut self.encrypted_dns_proxy))
+            .chain(once(&mut self.system_proxy))
             .chain(&mut self.custom)
     }
 
@@ -135,6 +173,10 @@ impl Settings {
         &self.encrypted_dns_proxy
     }
 
+    pub fn system_proxy(&self) -> &AccessMethodSetting {
+        &self.system_proxy
+    }
+
     fn create_direct() -> AccessMethodSetting {
         let method = BuiltInAccessMethod::Direct;
         AccessMethodSetting::new(method.canonical_name(), true, AccessMethod::from(method))
@@ -149,6 +191,13 @@ impl Settings {
         let method = BuiltInAccessMethod::EncryptedDnsProxy;
         AccessMethodSetting::new(method.canonical_name(), true, AccessMethod::from(method))
     }
+
+    fn create_system_proxy() -> AccessMethodSetting {
+        let method = BuiltInAccessMethod::SystemProxy;
+        // Disabled by default, so that API traffic doesn't silently start going through whatever
+        // proxy the environment of the daemon happens to point to.
+        AccessMethodSetting::new(method.canonical_name(), false, AccessMethod::from(method))
+    }
 }
 
 impl Default for Settings {
@@ -157,7 +206,9 @@ impl Default for Settings {
             direct: Settings::create_direct(),
             mullvad_bridges: Settings::create_mullvad_bridges(),
             encrypted_dns_proxy: Settings::create_encrypted_dns_proxy(),
+            system_proxy: Settings::create_system_proxy(),
             custom: vec![],
+            pinned: None,
         }
     }
 }
@@ -167,6 +218,9 @@ pub enum Error {
     /// Built-in access methods can not be removed
     #[error("Cannot remove built-in access method {}", attempted)]
     RemoveBuiltin { attempted: BuiltInAccessMethod },
+    /// There is no access method with the given id
+    #[error("No access method with id {0}")]
+    NoSuchMethod(Id),
 }
 
 /// API Access Method datastructure
@@ -181,7 +235,7 @@ pub struct AccessMethodSetting {
     pub access_method: AccessMethod,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Id(uuid::Uuid);
 
 impl Id {
@@ -288,6 +342,8 @@ pub enum BuiltInAccessMethod {
     Direct,
     Bridge,
     EncryptedDnsProxy,
+    /// The proxy configured on the system, if any. See [`DetectedSystemProxy`].
+    SystemProxy,
 }
 
 impl AccessMethod {
@@ -305,6 +361,7 @@ impl BuiltInAccessMethod {
             BuiltInAccessMethod::Direct => "Direct".to_string(),
             BuiltInAccessMethod::Bridge => "Mullvad Bridges".to_string(),
             BuiltInAccessMethod::EncryptedDnsProxy => "Encrypted DNS proxy".to_string(),
+            BuiltInAccessMethod::SystemProxy => "System proxy".to_string(),
         }
     }
 }
@@ -333,6 +390,12 @@ impl From<Socks5Remote> for AccessMethod {
     }
 }
 
+impl From<HttpConnect> for AccessMethod {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value).into()
+    }
+}
+
 impl From<Socks5Local> for AccessMethod {
     fn from(value: Socks5Local) -> Self {
         CustomProxy::Socks5Local(value).into()
@@ -344,3 +407,54 @@ impl From<Shadowsocks> for AccessMethod {
         CustomProxy::Shadowsocks(value).into()
     }
 }
+
+/// Statistics on how well an access method has worked for reaching the API.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessMethodStats {
+    pub id: Id,
+    /// Number of requests that reached the API
+    pub successes: u64,
+    /// Number of requests that failed due to network errors
+    pub failures: u64,
+    /// Number of requests that have failed since the last successful one
+    pub consecutive_failures: u64,
+    /// Moving average of the time it took the API to respond
+    pub latency: Option<Duration>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Score between 0 and 1, where recent outcomes weigh more than old ones. Access methods
+    /// are tried in order of decreasing health.
+    pub health: f64,
+}
+
+/// The outcome of looking for a proxy configured on the system, which is what the
+/// [`BuiltInAccessMethod::SystemProxy`] access method connects through.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DetectedSystemProxy {
+    /// No proxy is configured.
+    None,
+    /// A proxy is configured in `source` and can be used to reach the API.
+    Found { source: String, proxy: CustomProxy },
+    /// A proxy is configured in `source`, but the API is excluded from it.
+    Bypassed { source: String },
+    /// A proxy is configured in `source`, but it cannot be used.
+    Unusable { source: String, reason: String },
+}
+
+impl std::fmt::Display for DetectedSystemProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DetectedSystemProxy::None => f.write_str("No proxy configured"),
+            DetectedSystemProxy::Found { source, proxy } => {
+                let endpoint = proxy.get_remote_endpoint().endpoint;
+                write!(f, "{endpoint} (from {source})")
+            }
+            DetectedSystemProxy::Bypassed { source } => {
+                write!(f, "The API is excluded from the proxy (from {source})")
+            }
+            DetectedSystemProxy::Unusable { source, reason } => {
+                write!(f, "Unusable proxy: {reason} (from {source})")
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::states::TunnelState;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A tunnel state transition recorded by the daemon.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionHistoryEntry {
+    pub time: DateTime<Utc>,
+    /// The connection attempt that the tunnel parameters were generated for. Only set when
+    /// connecting or connected.
+    pub retry_attempt: Option<u32>,
+    pub state: TunnelState,
+}
+
+/// Selects which entries to return from the connection history.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionHistoryFilter {
+    /// Exclude entries recorded before this time
+    pub since: Option<DateTime<Utc>>,
+    /// Exclude entries recorded after this time
+    pub until: Option<DateTime<Utc>>,
+    /// Return at most this many of the most recent matching entries
+    pub limit: Option<usize>,
+}
+
+impl ConnectionHistoryFilter {
+    pub fn matches(&self, entry: &ConnectionHistoryEntry) -> bool {
+        self.since.map(|since| entry.time >= since).unwrap_or(true)
+            && self.until.map(|until| entry.time <= until).unwrap_or(true)
+    }
+
+    /// Returns the matching entries, oldest first. `entries` must be sorted by time.
+    #[allow(single_use_lifetimes)]
+    pub fn apply<'a>(
+        &self,
+        entries: impl DoubleEndedIterator<Item = &'a ConnectionHistoryEntry>,
+    ) -> Vec<ConnectionHistoryEntry> {
+        let mut matching: Vec<_> = entries
+            .rev()
+            .filter(|entry| self.matches(entry))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::tunnel::ActionAfterDisconnect;
+
+    #[test]
+    fn test_filter() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let entries: Vec<_> = (0..5)
+            .map(|minute| ConnectionHistoryEntry {
+                time: start + chrono::Duration::minutes(minute),
+                retry_attempt: None,
+                state: TunnelState::Disconnecting(ActionAfterDisconnect::Nothing),
+            })
+            .collect();
+        let times = |filter: ConnectionHistoryFilter| -> Vec<_> {
+            filter
+                .apply(entries.iter())
+                .into_iter()
+                .map(|entry| (entry.time - start).num_minutes())
+                .collect()
+        };
+
+        assert_eq!(times(ConnectionHistoryFilter::default()), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            times(ConnectionHistoryFilter {
+                limit: Some(2),
+                ..Default::default()
+            }),
+            [3, 4]
+        );
+        assert_eq!(
+            times(ConnectionHistoryFilter {
+                since: Some(start + chrono::Duration::minutes(1)),
+                until: Some(start + chrono::Duration::minutes(3)),
+                limit: Some(2),
+            }),
+            [2, 3]
+        );
+    }
+}
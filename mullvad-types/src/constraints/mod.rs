@@ -137,6 +137,7 @@ impl_intersection_partialeq!(bool);
 impl_intersection_partialeq!(relay_constraints::Providers);
 // NOTE: should take actual intersection
 impl_intersection_partialeq!(relay_constraints::LocationConstraint);
+impl_intersection_partialeq!(Vec<Constraint<relay_constraints::LocationConstraint>>);
 impl_intersection_partialeq!(relay_constraints::Ownership);
 // NOTE: it contains an inner constraint
 impl_intersection_partialeq!(talpid_types::net::TransportProtocol);
@@ -0,0 +1,58 @@
+use crate::relay_list::{RelayEndpointData, RelayList};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Daemon state that is collected into problem reports. Errors encountered while collecting an
+/// item are reported in place of its contents.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    /// The active firewall ruleset. Only collected on Linux, where it is the nftables ruleset.
+    pub firewall: Option<String>,
+    /// The routes in all routing tables. Only collected on Linux, since talpid-routing cannot list
+    /// the routing tables on other platforms.
+    pub routes: Option<String>,
+    /// The DNS configuration that is currently applied, and how it was applied.
+    pub dns: String,
+    pub relay_list: RelayListMetadata,
+}
+
+/// Summary of the relay list that the daemon is using.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayListMetadata {
+    pub etag: Option<String>,
+    pub last_updated: DateTime<Utc>,
+    pub countries: u32,
+    pub cities: u32,
+    pub relays: u32,
+    /// Number of relays that are currently active
+    pub active_relays: u32,
+    pub wireguard_relays: u32,
+    pub openvpn_relays: u32,
+    pub bridges: u32,
+}
+
+impl RelayListMetadata {
+    pub fn new(relay_list: &RelayList, last_updated: DateTime<Utc>) -> Self {
+        let count = |predicate: fn(&RelayEndpointData) -> bool| {
+            relay_list
+                .relays()
+                .filter(|relay| predicate(&relay.endpoint_data))
+                .count() as u32
+        };
+        RelayListMetadata {
+            etag: relay_list.etag.clone(),
+            last_updated,
+            countries: relay_list.countries.len() as u32,
+            cities: relay_list
+                .countries
+                .iter()
+                .map(|country| country.cities.len() as u32)
+                .sum(),
+            relays: relay_list.relays().count() as u32,
+            active_relays: relay_list.relays().filter(|relay| relay.active).count() as u32,
+            wireguard_relays: count(|data| matches!(data, RelayEndpointData::Wireguard(_))),
+            openvpn_relays: count(|data| matches!(data, RelayEndpointData::Openvpn)),
+            bridges: count(|data| matches!(data, RelayEndpointData::Bridge)),
+        }
+    }
+}
@@ -12,6 +12,7 @@ pub enum MullvadEndpoint {
 #[derive(Debug, Clone)]
 pub struct MullvadWireguardEndpoint {
     pub peer: wireguard::PeerConfig,
+    pub middle_peers: Vec<wireguard::PeerConfig>,
     pub exit_peer: Option<wireguard::PeerConfig>,
     pub ipv4_gateway: Ipv4Addr,
     pub ipv6_gateway: Ipv6Addr,
@@ -3,7 +3,7 @@ use std::{
     fmt::{Debug, Display},
 };
 
-use crate::settings::{DnsState, Settings};
+use crate::settings::{DnsState, Settings, SplitTunnelMode};
 use serde::{Deserialize, Serialize};
 use talpid_types::net::{ObfuscationType, TunnelEndpoint, TunnelType};
 
@@ -68,11 +68,15 @@ impl FromIterator<FeatureIndicator> for FeatureIndicators {
 pub enum FeatureIndicator {
     QuantumResistance,
     Multihop,
+    /// Multihop through more than two relays.
+    Cascade,
     BridgeMode,
     SplitTunneling,
+    InverseSplitTunneling,
     LockdownMode,
     Udp2Tcp,
     Shadowsocks,
+    Quic,
     LanSharing,
     DnsContentBlockers,
     CustomDns,
@@ -87,11 +91,14 @@ impl FeatureIndicator {
         match self {
             FeatureIndicator::QuantumResistance => "Quantum Resistance",
             FeatureIndicator::Multihop => "Multihop",
+            FeatureIndicator::Cascade => "Cascade",
             FeatureIndicator::BridgeMode => "Bridge Mode",
             FeatureIndicator::SplitTunneling => "Split Tunneling",
+            FeatureIndicator::InverseSplitTunneling => "Inverse Split Tunneling",
             FeatureIndicator::LockdownMode => "Lockdown Mode",
             FeatureIndicator::Udp2Tcp => "Udp2Tcp",
             FeatureIndicator::Shadowsocks => "Shadowsocks",
+            FeatureIndicator::Quic => "QUIC",
             FeatureIndicator::LanSharing => "LAN Sharing",
             FeatureIndicator::DnsContentBlockers => "Dns Content Blocker",
             FeatureIndicator::CustomDns => "Custom Dns",
@@ -122,10 +129,9 @@ pub fn compute_feature_indicators(
     endpoint: &TunnelEndpoint,
     server_ip_override: bool,
 ) -> FeatureIndicators {
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     let split_tunneling = settings.split_tunnel.enable_exclusions;
-    #[cfg(not(any(windows, target_os = "android", target_os = "macos")))]
-    let split_tunneling = false;
+    let inverse_split_tunneling =
+        settings.split_tunnel.effective_mode() == SplitTunnelMode::Include;
 
     #[cfg(not(target_os = "android"))]
     let lockdown_mode = settings.block_when_disconnected;
@@ -138,7 +144,14 @@ pub fn compute_feature_indicators(
     let custom_dns = settings.tunnel_options.dns_options.state == DnsState::Custom;
 
     let generic_features = [
-        (split_tunneling, FeatureIndicator::SplitTunneling),
+        (
+            split_tunneling && !inverse_split_tunneling,
+            FeatureIndicator::SplitTunneling,
+        ),
+        (
+            inverse_split_tunneling,
+            FeatureIndicator::InverseSplitTunneling,
+        ),
         (lan_sharing, FeatureIndicator::LanSharing),
         (dns_content_blockers, FeatureIndicator::DnsContentBlockers),
         (custom_dns, FeatureIndicator::CustomDns),
@@ -170,11 +183,17 @@ pub fn compute_feature_indicators(
                 .as_ref()
                 .filter(|obfuscation| obfuscation.obfuscation_type == ObfuscationType::Shadowsocks)
                 .is_some();
+            let quic = endpoint
+                .obfuscation
+                .as_ref()
+                .filter(|obfuscation| obfuscation.obfuscation_type == ObfuscationType::Quic)
+                .is_some();
 
             let mtu = settings.tunnel_options.wireguard.mtu.is_some();
 
             let mut daita = false;
             let multihop = endpoint.entry_endpoint.is_some();
+            let cascade = !endpoint.middle_endpoints.is_empty();
 
             #[cfg(daita)]
             if endpoint.daita {
@@ -183,9 +202,11 @@ pub fn compute_feature_indicators(
 
             vec![
                 (quantum_resistant, FeatureIndicator::QuantumResistance),
-                (multihop, FeatureIndicator::Multihop),
+                (multihop && !cascade, FeatureIndicator::Multihop),
+                (cascade, FeatureIndicator::Cascade),
                 (udp_tcp, FeatureIndicator::Udp2Tcp),
                 (shadowsocks, FeatureIndicator::Shadowsocks),
+                (quic, FeatureIndicator::Quic),
                 (mtu, FeatureIndicator::CustomMtu),
                 (daita, FeatureIndicator::Daita),
             ]
@@ -226,7 +247,9 @@ mod tests {
             proxy: Default::default(),
             obfuscation: Default::default(),
             entry_endpoint: Default::default(),
+            middle_endpoints: Default::default(),
             tunnel_interface: Default::default(),
+            mtu: Default::default(),
             daita: Default::default(),
         };
 
@@ -337,6 +360,21 @@ mod tests {
             expected_indicators
         );
 
+        endpoint.middle_endpoints = vec![Endpoint {
+            address: SocketAddr::from(([1, 2, 3, 5], 51820)),
+            protocol: TransportProtocol::Udp,
+        }];
+        expected_indicators.0.remove(&FeatureIndicator::Multihop);
+        expected_indicators.0.insert(FeatureIndicator::Cascade);
+        assert_eq!(
+            compute_feature_indicators(&settings, &endpoint, false),
+            expected_indicators,
+            "A cascade should replace the multihop feature indicator"
+        );
+        endpoint.middle_endpoints.clear();
+        expected_indicators.0.remove(&FeatureIndicator::Cascade);
+        expected_indicators.0.insert(FeatureIndicator::Multihop);
+
         endpoint.obfuscation = Some(ObfuscationEndpoint {
             endpoint: Endpoint {
                 address: SocketAddr::from(([1, 2, 3, 4], 443)),
@@ -356,6 +394,13 @@ mod tests {
             compute_feature_indicators(&settings, &endpoint, false),
             expected_indicators
         );
+        endpoint.obfuscation.as_mut().unwrap().obfuscation_type = ObfuscationType::Quic;
+        expected_indicators.0.remove(&FeatureIndicator::Shadowsocks);
+        expected_indicators.0.insert(FeatureIndicator::Quic);
+        assert_eq!(
+            compute_feature_indicators(&settings, &endpoint, false),
+            expected_indicators
+        );
 
         settings.tunnel_options.wireguard.mtu = Some(1300);
         expected_indicators.0.insert(FeatureIndicator::CustomMtu);
@@ -425,11 +470,14 @@ mod tests {
         match FeatureIndicator::QuantumResistance {
             FeatureIndicator::QuantumResistance => {}
             FeatureIndicator::Multihop => {}
+            FeatureIndicator::Cascade => {}
             FeatureIndicator::BridgeMode => {}
             FeatureIndicator::SplitTunneling => {}
+            FeatureIndicator::InverseSplitTunneling => {}
             FeatureIndicator::LockdownMode => {}
             FeatureIndicator::Udp2Tcp => {}
             FeatureIndicator::Shadowsocks => {}
+            FeatureIndicator::Quic => {}
             FeatureIndicator::LanSharing => {}
             FeatureIndicator::DnsContentBlockers => {}
             FeatureIndicator::CustomDns => {}
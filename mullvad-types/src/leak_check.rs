@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::IpAddr};
+
+/// A test for traffic leaving outside the tunnel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeakTest {
+    /// Send probes with a low TTL on the physical interface and listen for replies from the
+    /// network nodes they reach.
+    Traceroute,
+    /// Send DNS queries on the physical interface.
+    Dns,
+    /// Try to reach hosts over IPv6 on the physical interfaces.
+    Ipv6,
+    /// Ask <https://am.i.mullvad.net> whether the traffic comes from a Mullvad relay.
+    AmIMullvad,
+}
+
+impl fmt::Display for LeakTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeakTest::Traceroute => f.write_str("traceroute"),
+            LeakTest::Dns => f.write_str("DNS"),
+            LeakTest::Ipv6 => f.write_str("IPv6"),
+            LeakTest::AmIMullvad => f.write_str("am.i.mullvad.net"),
+        }
+    }
+}
+
+/// The outcome of a single leak test.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakTestResult {
+    pub test: LeakTest,
+    pub status: LeakStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeakStatus {
+    NoLeak,
+    LeakDetected(LeakInfo),
+    /// The test could not be run, so nothing is known about leaks.
+    Error(String),
+}
+
+/// Where traffic leaked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakInfo {
+    /// The interface that the traffic left through, if known.
+    pub interface: Option<String>,
+    /// Addresses that were reached outside the tunnel. For [LeakTest::AmIMullvad], this is the
+    /// public IP that the traffic came from.
+    pub addresses: Vec<IpAddr>,
+}
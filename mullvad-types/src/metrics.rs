@@ -0,0 +1,121 @@
+//! Settings for the local metrics endpoint, which exports daemon statistics in the Prometheus text
+//! format.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{Ipv4Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// Port that the metrics endpoint listens on unless another address is configured.
+pub const DEFAULT_METRICS_PORT: u16 = 9583;
+
+/// Path of the unix socket that the metrics endpoint listens on. The location is fixed, since the
+/// daemon runs as root and must not create or remove files at paths chosen by its clients.
+#[cfg(unix)]
+pub const METRICS_SOCKET_PATH: &str = "/var/run/mullvad-vpn-metrics";
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("The metrics endpoint must be bound to a loopback address: {0}")]
+    NotLoopback(SocketAddr),
+    #[error("Invalid metrics endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("Unix sockets are not supported on this platform")]
+    UnixSocketUnsupported,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsSettings {
+    /// Whether the metrics endpoint is running
+    pub enabled: bool,
+    /// Where the metrics endpoint listens
+    pub endpoint: MetricsEndpoint,
+}
+
+/// Address of the metrics endpoint. It must never be reachable from other hosts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsEndpoint {
+    /// HTTP over TCP on a loopback address
+    Tcp(SocketAddr),
+    /// HTTP over a unix domain socket at [`METRICS_SOCKET_PATH`]. Only root can connect to it
+    Unix,
+}
+
+impl MetricsEndpoint {
+    pub fn validate(&self) -> Result<(), Error> {
+        match self {
+            MetricsEndpoint::Tcp(address) if !address.ip().is_loopback() => {
+                Err(Error::NotLoopback(*address))
+            }
+            MetricsEndpoint::Tcp(_) => Ok(()),
+            MetricsEndpoint::Unix if cfg!(not(unix)) => Err(Error::UnixSocketUnsupported),
+            MetricsEndpoint::Unix => Ok(()),
+        }
+    }
+}
+
+impl Default for MetricsEndpoint {
+    fn default() -> Self {
+        MetricsEndpoint::Tcp(SocketAddr::from((
+            Ipv4Addr::LOCALHOST,
+            DEFAULT_METRICS_PORT,
+        )))
+    }
+}
+
+impl fmt::Display for MetricsEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricsEndpoint::Tcp(address) => write!(f, "http://{address}/metrics"),
+            #[cfg(unix)]
+            MetricsEndpoint::Unix => write!(f, "unix:{METRICS_SOCKET_PATH}"),
+            #[cfg(not(unix))]
+            MetricsEndpoint::Unix => write!(f, "unix"),
+        }
+    }
+}
+
+impl FromStr for MetricsEndpoint {
+    type Err = Error;
+
+    /// Parses a socket address, such as `127.0.0.1:9583`, or `unix`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let endpoint = match s.parse::<SocketAddr>() {
+            Ok(address) => MetricsEndpoint::Tcp(address),
+            Err(_) if s == "unix" => MetricsEndpoint::Unix,
+            Err(_) => return Err(Error::InvalidEndpoint(s.to_owned())),
+        };
+        endpoint.validate()?;
+        Ok(endpoint)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            "127.0.0.1:9583".parse::<MetricsEndpoint>(),
+            Ok(MetricsEndpoint::default())
+        );
+        assert_eq!(
+            "[::1]:8080".parse::<MetricsEndpoint>(),
+            Ok(MetricsEndpoint::Tcp("[::1]:8080".parse().unwrap()))
+        );
+        assert!(matches!(
+            "0.0.0.0:9583".parse::<MetricsEndpoint>(),
+            Err(Error::NotLoopback(_))
+        ));
+        #[cfg(unix)]
+        assert_eq!("unix".parse::<MetricsEndpoint>(), Ok(MetricsEndpoint::Unix));
+        assert!(matches!(
+            "/tmp/metrics.sock".parse::<MetricsEndpoint>(),
+            Err(Error::InvalidEndpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+//! Named settings profiles that are layered over [`Settings`].
+//!
+//! A profile overrides a subset of the settings while it is active. Profiles may carry
+//! [`NetworkRule`]s, which allow the daemon to activate them automatically when the host joins a
+//! matching network.
+
+use crate::{
+    relay_constraints::RelaySettings,
+    settings::{DnsOptions, Settings},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Deref, str::FromStr};
+use talpid_types::net::NetworkInfo;
+
+const PROFILE_NAME_MAX_SIZE: usize = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Settings profile name too long")]
+    NameTooLong,
+    #[error("Settings profile name must not be empty")]
+    EmptyName,
+    #[error("Settings profile with name already exists")]
+    DuplicateName,
+    #[error("Settings profile not found")]
+    ProfileNotFound,
+    #[error("Invalid MAC address: {0}")]
+    InvalidMacAddress(String),
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProfileSettings {
+    profiles: Vec<SettingsProfile>,
+}
+
+impl ProfileSettings {
+    pub fn add(&mut self, new_profile: SettingsProfile) -> Result<(), Error> {
+        if new_profile.name.is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if new_profile.name.chars().count() > PROFILE_NAME_MAX_SIZE {
+            return Err(Error::NameTooLong);
+        }
+        if self.get(&new_profile.name).is_some() {
+            return Err(Error::DuplicateName);
+        }
+        self.profiles.push(new_profile);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or(Error::ProfileNotFound)?;
+        self.profiles.remove(index);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingsProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Return the first profile with a rule matching `network`, if any.
+    pub fn find_matching(&self, network: &NetworkInfo) -> Option<&SettingsProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.matches_network(network))
+    }
+
+    /// Return the name of the profile that should be active on `network`, given the currently
+    /// `active` profile.
+    ///
+    /// A profile without any rules is only ever activated by the user, and remains active if no
+    /// other profile matches. While the gateway's MAC address is unknown, the active profile is
+    /// kept if it has a rule for it, since the address is typically resolved shortly after the
+    /// network changes.
+    pub fn profile_for_network<'a>(
+        &'a self,
+        active: Option<&str>,
+        network: &NetworkInfo,
+    ) -> Option<&'a str> {
+        let active = active.and_then(|name| self.get(name));
+        if let Some(active) = active.filter(|profile| profile.awaits_gateway_mac(network)) {
+            return Some(&active.name);
+        }
+        self.find_matching(network)
+            .or(active.filter(|profile| profile.rules.is_empty()))
+            .map(|profile| profile.name.as_str())
+    }
+}
+
+impl From<Vec<SettingsProfile>> for ProfileSettings {
+    fn from(profiles: Vec<SettingsProfile>) -> Self {
+        Self { profiles }
+    }
+}
+
+impl IntoIterator for ProfileSettings {
+    type Item = SettingsProfile;
+    type IntoIter = <Vec<SettingsProfile> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.profiles.into_iter()
+    }
+}
+
+impl Deref for ProfileSettings {
+    type Target = [SettingsProfile];
+
+    fn deref(&self) -> &Self::Target {
+        &self.profiles
+    }
+}
+
+/// All settings profiles, along with the name of the active one.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ProfileList {
+    pub profiles: Vec<SettingsProfile>,
+    pub active_profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettingsProfile {
+    pub name: String,
+    #[serde(default)]
+    pub overrides: SettingsOverrides,
+    /// The profile is activated automatically when any of these rules match the current network.
+    #[serde(default)]
+    pub rules: Vec<NetworkRule>,
+}
+
+impl SettingsProfile {
+    pub fn new(name: impl Into<String>) -> Self {
+        SettingsProfile {
+            name: name.into(),
+            overrides: SettingsOverrides::default(),
+            rules: vec![],
+        }
+    }
+
+    pub fn matches_network(&self, network: &NetworkInfo) -> bool {
+        self.rules.iter().any(|rule| rule.matches(network))
+    }
+
+    /// Whether the profile has a gateway MAC rule that can't be evaluated on `network` yet.
+    fn awaits_gateway_mac(&self, network: &NetworkInfo) -> bool {
+        network.gateway_mac.is_none()
+            && self
+                .rules
+                .iter()
+                .any(|rule| matches!(rule, NetworkRule::GatewayMac(_)))
+    }
+}
+
+/// Settings that replace the corresponding values in [`Settings`] while a profile is active.
+/// Fields that are `None` are left untouched.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SettingsOverrides {
+    pub relay_settings: Option<RelaySettings>,
+    pub allow_lan: Option<bool>,
+    #[cfg(not(target_os = "android"))]
+    pub block_when_disconnected: Option<bool>,
+    pub enable_ipv6: Option<bool>,
+    pub dns_options: Option<DnsOptions>,
+}
+
+impl SettingsOverrides {
+    /// Apply the overrides to `settings`.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(relay_settings) = &self.relay_settings {
+            settings.set_relay_settings(relay_settings.clone());
+        }
+        if let Some(allow_lan) = self.allow_lan {
+            settings.allow_lan = allow_lan;
+        }
+        #[cfg(not(target_os = "android"))]
+        if let Some(block_when_disconnected) = self.block_when_disconnected {
+            settings.block_when_disconnected = block_when_disconnected;
+        }
+        if let Some(enable_ipv6) = self.enable_ipv6 {
+            settings.tunnel_options.generic.enable_ipv6 = enable_ipv6;
+        }
+        if let Some(dns_options) = &self.dns_options {
+            settings.tunnel_options.dns_options = dns_options.clone();
+        }
+    }
+}
+
+/// A condition on the network that the host is attached to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkRule {
+    /// Link layer address of the default gateway.
+    GatewayMac(MacAddress),
+    /// Name of the interface that the default route goes through.
+    Interface(String),
+    /// Domain name handed out by the DHCP server. Compared case-insensitively.
+    DhcpDomain(String),
+}
+
+impl NetworkRule {
+    pub fn matches(&self, network: &NetworkInfo) -> bool {
+        match self {
+            NetworkRule::GatewayMac(mac) => network.gateway_mac == Some(mac.0),
+            NetworkRule::Interface(interface) => network.interface.as_ref() == Some(interface),
+            NetworkRule::DhcpDomain(domain) => network
+                .dhcp_domain
+                .as_ref()
+                .is_some_and(|dhcp_domain| dhcp_domain.eq_ignore_ascii_case(domain)),
+        }
+    }
+}
+
+impl fmt::Display for NetworkRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkRule::GatewayMac(mac) => write!(f, "gateway MAC {mac}"),
+            NetworkRule::Interface(interface) => write!(f, "interface {interface}"),
+            NetworkRule::DhcpDomain(domain) => write!(f, "DHCP domain {domain}"),
+        }
+    }
+}
+
+/// A link layer address, serialized as colon-separated hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidMacAddress(s.to_owned());
+        let mut address = [0u8; 6];
+        let mut octets = s.split([':', '-']);
+        for byte in address.iter_mut() {
+            let octet = octets.next().ok_or_else(invalid)?;
+            if octet.len() != 2 {
+                return Err(invalid());
+            }
+            *byte = u8::from_str_radix(octet, 16).map_err(|_| invalid())?;
+        }
+        if octets.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(MacAddress(address))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl Serialize for MacAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn home_network() -> NetworkInfo {
+        NetworkInfo {
+            interface: Some("wlan0".to_owned()),
+            gateway_mac: Some([0xa4, 0x91, 0xb1, 0x0c, 0x3e, 0x7f]),
+            dhcp_domain: Some("Home.lan".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_mac_address_roundtrip() {
+        let mac: MacAddress = "A4:91:b1:0c:3e:7F".parse().unwrap();
+        assert_eq!(mac.0, [0xa4, 0x91, 0xb1, 0x0c, 0x3e, 0x7f]);
+        assert_eq!(mac.to_string(), "a4:91:b1:0c:3e:7f");
+        assert_eq!("a4-91-b1-0c-3e-7f".parse::<MacAddress>().unwrap(), mac);
+
+        assert!("a4:91:b1:0c:3e".parse::<MacAddress>().is_err());
+        assert!("a4:91:b1:0c:3e:7f:00".parse::<MacAddress>().is_err());
+        assert!("a4:91:b1:0c:3e:zz".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn test_network_rules() {
+        let network = home_network();
+        let mac = "a4:91:b1:0c:3e:7f".parse().unwrap();
+
+        assert!(NetworkRule::GatewayMac(mac).matches(&network));
+        assert!(NetworkRule::Interface("wlan0".to_owned()).matches(&network));
+        assert!(!NetworkRule::Interface("eth0".to_owned()).matches(&network));
+        assert!(NetworkRule::DhcpDomain("home.LAN".to_owned()).matches(&network));
+        assert!(!NetworkRule::DhcpDomain("home.lan".to_owned()).matches(&NetworkInfo::default()));
+    }
+
+    #[test]
+    fn test_find_matching_profile() {
+        let mut profiles = ProfileSettings::default();
+        profiles.add(SettingsProfile::new("manual")).unwrap();
+        let mut home = SettingsProfile::new("home");
+        home.rules
+            .push(NetworkRule::DhcpDomain("home.lan".to_owned()));
+        profiles.add(home).unwrap();
+
+        assert!(matches!(
+            profiles.add(SettingsProfile::new("home")),
+            Err(Error::DuplicateName)
+        ));
+        assert_eq!(
+            profiles
+                .find_matching(&home_network())
+                .map(|profile| profile.name.as_str()),
+            Some("home")
+        );
+        assert!(profiles.find_matching(&NetworkInfo::default()).is_none());
+    }
+
+    #[test]
+    fn test_profile_is_kept_until_gateway_mac_is_resolved() {
+        let mut profiles = ProfileSettings::default();
+        profiles.add(SettingsProfile::new("manual")).unwrap();
+        let mut home = SettingsProfile::new("home");
+        home.rules.push(NetworkRule::GatewayMac(
+            "a4:91:b1:0c:3e:7f".parse().unwrap(),
+        ));
+        profiles.add(home).unwrap();
+
+        let unresolved = NetworkInfo {
+            gateway_mac: None,
+            ..home_network()
+        };
+        assert_eq!(profiles.profile_for_network(None, &unresolved), None);
+        assert_eq!(
+            profiles.profile_for_network(Some("home"), &unresolved),
+            Some("home"),
+            "an unknown gateway MAC should not deactivate the profile"
+        );
+        assert_eq!(
+            profiles.profile_for_network(Some("home"), &home_network()),
+            Some("home")
+        );
+
+        let other_network = NetworkInfo {
+            gateway_mac: Some([0; 6]),
+            ..home_network()
+        };
+        assert_eq!(
+            profiles.profile_for_network(Some("home"), &other_network),
+            None
+        );
+        assert_eq!(
+            profiles.profile_for_network(Some("manual"), &other_network),
+            Some("manual")
+        );
+        assert_eq!(
+            profiles.profile_for_network(Some("manual"), &home_network()),
+            Some("home")
+        );
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut settings = Settings::default();
+        let overrides = SettingsOverrides {
+            allow_lan: Some(true),
+            enable_ipv6: Some(true),
+            ..Default::default()
+        };
+        overrides.apply(&mut settings);
+
+        assert!(settings.allow_lan);
+        assert!(settings.tunnel_options.generic.enable_ipv6);
+        assert_eq!(settings.relay_settings, Settings::default().relay_settings);
+    }
+}
@@ -15,7 +15,10 @@ use std::{
     net::{Ipv4Addr, Ipv6Addr},
     str::FromStr,
 };
-use talpid_types::net::{proxy::CustomProxy, IpVersion, TransportProtocol, TunnelType};
+use talpid_types::net::{
+    proxy::{CustomProxy, Socks5Remote},
+    IpVersion, TransportProtocol, TunnelType,
+};
 
 /// Specifies a specific endpoint or [`RelayConstraints`] to use when `mullvad-daemon` selects a
 /// relay.
@@ -126,6 +129,8 @@ pub struct RelayConstraints {
     pub tunnel_protocol: Constraint<TunnelType>,
     pub wireguard_constraints: WireguardConstraints,
     pub openvpn_constraints: OpenVpnConstraints,
+    /// Bias the relay selection towards relays with a low measured round-trip time.
+    pub prefer_low_latency: bool,
 }
 
 pub struct RelayConstraintsFormatter<'a> {
@@ -157,7 +162,16 @@ impl fmt::Display for RelayConstraintsFormatter<'_> {
                 })
         )?;
         writeln!(f, "Provider(s): {}", self.constraints.providers)?;
-        write!(f, "Ownership: {}", self.constraints.ownership)
+        writeln!(f, "Ownership: {}", self.constraints.ownership)?;
+        write!(
+            f,
+            "Prefer low latency: {}",
+            if self.constraints.prefer_low_latency {
+                "on"
+            } else {
+                "off"
+            }
+        )
     }
 }
 
@@ -401,6 +415,12 @@ impl fmt::Display for OpenVpnConstraints {
     }
 }
 
+/// The maximum number of relays that may be placed between the entry and the exit relay.
+///
+/// This is limited by the number of endpoints that the firewall can allow in the tunnel while
+/// ephemeral peers are being negotiated.
+pub const MAX_MIDDLE_HOPS: usize = 1;
+
 /// [`Constraint`]s applicable to WireGuard relays.
 #[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case", default)]
@@ -409,6 +429,9 @@ pub struct WireguardConstraints {
     pub ip_version: Constraint<IpVersion>,
     pub use_multihop: bool,
     pub entry_location: Constraint<LocationConstraint>,
+    /// Locations of additional relays between the entry and the exit relay, in the order that
+    /// traffic passes through them. Only used if multihop is enabled.
+    pub middle_locations: Vec<Constraint<LocationConstraint>>,
 }
 
 impl WireguardConstraints {
@@ -445,6 +468,15 @@ impl fmt::Display for WireguardConstraintsFormatter<'_> {
                 }
             });
             write!(f, ", multihop entry {}", location)?;
+            for location in &self.constraints.middle_locations {
+                let location = location
+                    .as_ref()
+                    .map(|location| LocationConstraintFormatter {
+                        constraint: location,
+                        custom_lists: self.custom_lists,
+                    });
+                write!(f, ", middle {}", location)?;
+            }
         }
         Ok(())
     }
@@ -509,6 +541,8 @@ pub enum SelectedObfuscation {
     #[cfg_attr(feature = "clap", clap(name = "udp2tcp"))]
     Udp2Tcp,
     Shadowsocks,
+    Quic,
+    Socks5,
 }
 
 impl Intersection for SelectedObfuscation {
@@ -533,6 +567,8 @@ impl fmt::Display for SelectedObfuscation {
             SelectedObfuscation::Off => "off".fmt(f),
             SelectedObfuscation::Udp2Tcp => "udp2tcp".fmt(f),
             SelectedObfuscation::Shadowsocks => "shadowsocks".fmt(f),
+            SelectedObfuscation::Quic => "quic".fmt(f),
+            SelectedObfuscation::Socks5 => "socks5".fmt(f),
         }
     }
 }
@@ -567,6 +603,40 @@ impl fmt::Display for ShadowsocksSettings {
     }
 }
 
+/// User-supplied SOCKS5 proxy to relay WireGuard traffic through
+#[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Socks5ObfuscationSettings {
+    pub proxy: Option<Socks5Remote>,
+}
+
+impl Intersection for Socks5ObfuscationSettings {
+    fn intersection(self, other: Self) -> Option<Self>
+    where
+        Self: PartialEq,
+        Self: Sized,
+    {
+        // A missing proxy does not constrain anything
+        let proxy = match (self.proxy, other.proxy) {
+            (Some(left), Some(right)) if left != right => return None,
+            (left, right) => left.or(right),
+        };
+        Some(Socks5ObfuscationSettings { proxy })
+    }
+}
+
+impl fmt::Display for Socks5ObfuscationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.proxy {
+            None => write!(f, "no proxy"),
+            Some(proxy) if proxy.auth.is_some() => {
+                write!(f, "{} (with authentication)", proxy.endpoint)
+            }
+            Some(proxy) => write!(f, "{}", proxy.endpoint),
+        }
+    }
+}
+
 /// Contains obfuscation settings
 #[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -575,6 +645,7 @@ pub struct ObfuscationSettings {
     pub selected_obfuscation: SelectedObfuscation,
     pub udp2tcp: Udp2TcpObfuscationSettings,
     pub shadowsocks: ShadowsocksSettings,
+    pub socks5: Socks5ObfuscationSettings,
 }
 
 /// Limits the set of bridge servers to use in `mullvad-daemon`.
@@ -701,6 +772,69 @@ impl RelayOverride {
                 // Keep address if it's not overridden
                 not_overridden_v4 || not_overridden_v6
             });
+            if let Some(quic) = &mut data.quic {
+                quic.addr_in.retain(|addr| {
+                    (self.ipv4_addr_in.is_none() && addr.is_ipv4())
+                        || (self.ipv6_addr_in.is_none() && addr.is_ipv6())
+                });
+            }
+        }
+    }
+}
+
+/// A single step in a user-defined retry order. On each connection attempt, the relay selector
+/// combines the user's relay constraints with the next step in the retry order. Steps which
+/// conflict with the user's constraints are skipped.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryAttempt {
+    /// Add nothing to the user's constraints.
+    Any,
+    /// Connect to a WireGuard relay.
+    Wireguard {
+        port: Constraint<u16>,
+        ip_version: Constraint<IpVersion>,
+        obfuscation: SelectedObfuscation,
+    },
+    /// Connect to an OpenVPN relay.
+    Openvpn {
+        port: Constraint<TransportPort>,
+        /// Connect via a bridge. This requires TCP.
+        bridge: bool,
+    },
+}
+
+impl fmt::Display for RetryAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryAttempt::Any => write!(f, "any"),
+            RetryAttempt::Wireguard {
+                port,
+                ip_version,
+                obfuscation,
+            } => {
+                write!(f, "WireGuard")?;
+                if let Constraint::Only(port) = port {
+                    write!(f, ", port {port}")?;
+                }
+                if let Constraint::Only(ip_version) = ip_version {
+                    write!(f, ", {ip_version}")?;
+                }
+                if *obfuscation != SelectedObfuscation::Auto {
+                    write!(f, ", obfuscation {obfuscation}")?;
+                }
+                Ok(())
+            }
+            RetryAttempt::Openvpn { port, bridge } => {
+                write!(f, "OpenVPN")?;
+                if port.is_only() {
+                    write!(f, ", {}", OpenVpnConstraints { port: *port })?;
+                }
+                if *bridge {
+                    write!(f, ", via bridge")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -130,6 +130,7 @@ impl PartialEq for Relay {
     ///     #   .unwrap(),
     ///     #   daita: false,
     ///     #   shadowsocks_extra_addr_in: vec![],
+    ///     #   quic: None,
     ///     # }),
     ///     # location: mullvad_types::location::Location {
     ///     #   country: "Sweden".to_string(),
@@ -226,6 +227,20 @@ pub struct WireguardRelayEndpointData {
     /// Optional IP addresses used by Shadowsocks
     #[serde(default)]
     pub shadowsocks_extra_addr_in: Vec<IpAddr>,
+    /// Present if the server accepts WireGuard over QUIC
+    #[serde(default)]
+    pub quic: Option<QuicEndpointData>,
+}
+
+/// Data needed to connect to the QUIC obfuscation endpoint of a WireGuard relay.
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Debug)]
+pub struct QuicEndpointData {
+    /// Hostname that the server certificate is issued for
+    pub domain: String,
+    /// Additional IP addresses that accept QUIC connections. The regular address of the relay is
+    /// used if none of them belong to the IP version in use.
+    #[serde(default)]
+    pub addr_in: Vec<IpAddr>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
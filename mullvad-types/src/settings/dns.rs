@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+pub use talpid_types::net::dns::{DnsEncryption, EncryptedDnsProtocol, SpkiPin, SplitDnsRule};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -16,6 +17,9 @@ pub struct DnsOptions {
     pub state: DnsState,
     pub default_options: DefaultDnsOptions,
     pub custom_options: CustomDnsOptions,
+    /// Domains that are resolved by specific resolvers, regardless of `state`. Only supported on
+    /// Linux.
+    pub split_rules: Vec<SplitDnsRule>,
 }
 
 /// Default DNS config
@@ -34,6 +38,9 @@ pub struct DefaultDnsOptions {
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CustomDnsOptions {
     pub addresses: Vec<IpAddr>,
+    /// Reach `addresses` over DNS-over-HTTPS or DNS-over-TLS instead of plaintext DNS.
+    #[serde(default)]
+    pub encryption: Option<DnsEncryption>,
 }
 
 impl DefaultDnsOptions {
@@ -2,17 +2,19 @@ use crate::{
     access_method,
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    metrics::MetricsSettings,
+    profile::ProfileSettings,
     relay_constraints::{
         BridgeSettings, BridgeState, GeographicLocationConstraint, LocationConstraint,
         ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
-        RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
+        RelaySettingsFormatter, RetryAttempt, SelectedObfuscation, WireguardConstraints,
     },
     wireguard,
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use std::collections::HashSet;
-use talpid_types::net::{openvpn, GenericTunnelOptions};
+use talpid_types::net::{firewall::FirewallException, openvpn, GenericTunnelOptions};
+pub use talpid_types::split_tunnel::SplitTunnelMode;
 
 mod dns;
 
@@ -92,26 +94,50 @@ pub struct Settings {
     pub tunnel_options: TunnelOptions,
     /// Overrides for relays
     pub relay_overrides: Vec<RelayOverride>,
+    /// The order in which the relay selector should try different connection parameters on
+    /// successive connection attempts. The built-in retry order is used if this is `None`.
+    pub retry_order: Option<Vec<RetryAttempt>>,
+    /// Named settings profiles that can be layered over these settings
+    pub profiles: ProfileSettings,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
     /// Split tunneling settings
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     pub split_tunnel: SplitTunnelSettings,
+    /// User-defined traffic that the firewall allows in every tunnel state. Only enforced on
+    /// Linux.
+    pub firewall_exceptions: Vec<FirewallException>,
+    /// Local endpoint that exports statistics about the daemon
+    pub metrics: MetricsSettings,
     /// Specifies settings schema version
     pub settings_version: SettingsVersion,
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 #[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 pub struct SplitTunnelSettings {
     /// Toggles split tunneling on or off
     pub enable_exclusions: bool,
     /// Set of applications to exclude from the tunnel.
     pub apps: HashSet<SplitApp>,
+    /// Whether the split applications are excluded from the tunnel, or are the only ones that use
+    /// it. Only Linux supports [`SplitTunnelMode::Include`].
+    #[serde(default)]
+    pub mode: SplitTunnelMode,
+}
+
+impl SplitTunnelSettings {
+    /// The mode that should be enforced. Include mode is only in effect while split tunneling is
+    /// enabled, since it would otherwise exclude all traffic from the tunnel.
+    pub fn effective_mode(&self) -> SplitTunnelMode {
+        if self.enable_exclusions {
+            self.mode
+        } else {
+            SplitTunnelMode::Exclude
+        }
+    }
 }
 
 /// An application whose traffic should be excluded from any active tunnel.
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(std::path::PathBuf);
 
@@ -120,7 +146,7 @@ pub struct SplitApp(std::path::PathBuf);
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(String);
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl SplitApp {
     /// Convert the underlying path to a [`String`].
     /// This function will fail if the underlying path string is not valid UTF-8. See
@@ -156,14 +182,14 @@ impl SplitApp {
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl From<String> for SplitApp {
     fn from(value: String) -> Self {
         SplitApp::from(std::path::PathBuf::from(value))
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl From<std::path::PathBuf> for SplitApp {
     fn from(value: std::path::PathBuf) -> Self {
         SplitApp(value)
@@ -206,9 +232,12 @@ impl Default for Settings {
             auto_connect: false,
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
+            retry_order: None,
+            profiles: ProfileSettings::default(),
             show_beta_releases: false,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             split_tunnel: SplitTunnelSettings::default(),
+            firewall_exceptions: vec![],
+            metrics: MetricsSettings::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
         }
     }
@@ -274,7 +303,10 @@ pub struct TunnelOptions {
     pub dns_options: DnsOptions,
 }
 
-pub use dns::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+pub use dns::{
+    CustomDnsOptions, DefaultDnsOptions, DnsEncryption, DnsOptions, DnsState, EncryptedDnsProtocol,
+    SpkiPin, SplitDnsRule,
+};
 
 impl Default for TunnelOptions {
     fn default() -> Self {
@@ -289,3 +321,27 @@ impl Default for TunnelOptions {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_tunnel_effective_mode() {
+        let mut settings = SplitTunnelSettings {
+            mode: SplitTunnelMode::Include,
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.effective_mode(),
+            SplitTunnelMode::Exclude,
+            "include mode should have no effect while split tunneling is disabled"
+        );
+
+        settings.enable_exclusions = true;
+        assert_eq!(settings.effective_mode(), SplitTunnelMode::Include);
+
+        settings.mode = SplitTunnelMode::Exclude;
+        assert_eq!(settings.effective_mode(), SplitTunnelMode::Exclude);
+    }
+}
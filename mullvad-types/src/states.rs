@@ -79,11 +79,17 @@ pub enum TunnelState {
         endpoint: TunnelEndpoint,
         location: Option<GeoIpLocation>,
         feature_indicators: FeatureIndicators,
+        /// Name of the settings profile that was active when the state was entered
+        #[serde(default)]
+        active_profile: Option<String>,
     },
     Connected {
         endpoint: TunnelEndpoint,
         location: Option<GeoIpLocation>,
         feature_indicators: FeatureIndicators,
+        /// Name of the settings profile that was active when the state was entered
+        #[serde(default)]
+        active_profile: Option<String>,
     },
     Disconnecting(ActionAfterDisconnect),
     Error(ErrorState),
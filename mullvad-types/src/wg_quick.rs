@@ -0,0 +1,578 @@
+//! Parsing and serialization of `wg-quick` configuration files.
+//!
+//! Only the subset of the format that maps onto a WireGuard tunnel is supported. Keys that only
+//! make sense to `wg-quick` itself, such as `PostUp` or `Table`, are ignored.
+
+use crate::{ConnectionConfig, CustomTunnelEndpoint};
+use ipnetwork::IpNetwork;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+use talpid_types::net::wireguard::{self, PresharedKey, PrivateKey, PublicKey};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Line {0}: Expected a section header or 'Key = Value'")]
+    InvalidLine(usize),
+
+    #[error("Line {line}: Unknown section [{name}]")]
+    UnknownSection { line: usize, name: String },
+
+    #[error("Line {0}: Key is not inside a section")]
+    KeyOutsideSection(usize),
+
+    #[error("Line {line}: Invalid value for {key}: {value}")]
+    InvalidValue {
+        line: usize,
+        key: String,
+        value: String,
+    },
+
+    #[error("Line {0}: Duplicate [Interface] section")]
+    DuplicateInterface(usize),
+
+    #[error("Missing [Interface] section")]
+    MissingInterface,
+
+    #[error("Missing {key} in [{section}]")]
+    MissingKey {
+        section: &'static str,
+        key: &'static str,
+    },
+
+    #[error("Expected exactly one [Peer] section, found {0}")]
+    PeerCount(usize),
+
+    #[error("The peer has no endpoint")]
+    MissingEndpoint,
+
+    #[error("No IPv4 gateway was given, and it cannot be derived from the IPv4 Address")]
+    MissingGateway,
+
+    #[error("Multihop tunnels cannot be expressed as a wg-quick config")]
+    Multihop,
+}
+
+/// A parsed `wg-quick` configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgQuickConfig {
+    pub interface: Interface,
+    pub peers: Vec<Peer>,
+}
+
+/// The `[Interface]` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub private_key: PrivateKey,
+    pub addresses: Vec<IpNetwork>,
+    pub dns: Vec<IpAddr>,
+    pub mtu: Option<u16>,
+}
+
+/// A `[Peer]` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    pub public_key: PublicKey,
+    pub preshared_key: Option<PresharedKey>,
+    pub allowed_ips: Vec<IpNetwork>,
+    pub endpoint: Option<PeerEndpoint>,
+}
+
+/// The `Endpoint` of a peer. The host may be a domain name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerEndpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl FromStr for PeerEndpoint {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, port) = s.rsplit_once(':').ok_or(())?;
+        let port = port.parse().map_err(|_| ())?;
+        // IPv6 addresses are enclosed in brackets
+        let host = match host.strip_prefix('[') {
+            Some(host) => host.strip_suffix(']').ok_or(())?,
+            None if host.contains(':') => return Err(()),
+            None => host,
+        };
+        if host.is_empty() {
+            return Err(());
+        }
+        Ok(PeerEndpoint {
+            host: host.to_owned(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for PeerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl WgQuickConfig {
+    /// Create a config for an active tunnel. `dns` and `mtu` are not part of the connection
+    /// config, so they have to be provided separately.
+    pub fn from_connection_config(
+        config: &wireguard::ConnectionConfig,
+        dns: Vec<IpAddr>,
+        mtu: Option<u16>,
+    ) -> Result<Self, Error> {
+        if config.exit_peer.is_some() || !config.middle_peers.is_empty() {
+            return Err(Error::Multihop);
+        }
+        let endpoint = PeerEndpoint {
+            host: config.peer.endpoint.ip().to_string(),
+            port: config.peer.endpoint.port(),
+        };
+        Ok(WgQuickConfig {
+            interface: Interface {
+                private_key: config.tunnel.private_key.clone(),
+                addresses: config
+                    .tunnel
+                    .addresses
+                    .iter()
+                    .map(|&address| IpNetwork::from(address))
+                    .collect(),
+                dns,
+                mtu,
+            },
+            peers: vec![Peer {
+                public_key: config.peer.public_key.clone(),
+                preshared_key: config.peer.psk.clone(),
+                allowed_ips: config.peer.allowed_ips.clone(),
+                endpoint: Some(endpoint),
+            }],
+        })
+    }
+
+    /// Convert the config to a custom endpoint. The config must contain exactly one peer.
+    ///
+    /// wg-quick configs do not specify the in-tunnel gateways. Unless they are given, the first
+    /// host address in the network of each `Address` is used, such as `10.0.0.1` for
+    /// `10.0.0.2/24`. This is not possible for single-address networks such as `/32`, so an IPv4
+    /// gateway must be given for those.
+    pub fn to_custom_tunnel_endpoint(
+        &self,
+        ipv4_gateway: Option<Ipv4Addr>,
+        ipv6_gateway: Option<Ipv6Addr>,
+    ) -> Result<CustomTunnelEndpoint, Error> {
+        let [peer] = &self.peers[..] else {
+            return Err(Error::PeerCount(self.peers.len()));
+        };
+        let endpoint = peer.endpoint.as_ref().ok_or(Error::MissingEndpoint)?;
+
+        let ipv4_gateway = ipv4_gateway
+            .or_else(|| self.derive_ipv4_gateway())
+            .ok_or(Error::MissingGateway)?;
+        let ipv6_gateway = ipv6_gateway.or_else(|| self.derive_ipv6_gateway());
+
+        Ok(CustomTunnelEndpoint {
+            host: endpoint.host.clone(),
+            config: ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+                tunnel: wireguard::TunnelConfig {
+                    private_key: self.interface.private_key.clone(),
+                    addresses: self
+                        .interface
+                        .addresses
+                        .iter()
+                        .map(|network| network.ip())
+                        .collect(),
+                },
+                peer: wireguard::PeerConfig {
+                    public_key: peer.public_key.clone(),
+                    allowed_ips: peer.allowed_ips.clone(),
+                    // The host is resolved when connecting
+                    endpoint: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), endpoint.port),
+                    psk: peer.preshared_key.clone(),
+                    constant_packet_size: false,
+                },
+                middle_peers: vec![],
+                exit_peer: None,
+                ipv4_gateway,
+                ipv6_gateway,
+                #[cfg(target_os = "linux")]
+                fwmark: Some(crate::TUNNEL_FWMARK),
+            }),
+        })
+    }
+
+    fn derive_ipv4_gateway(&self) -> Option<Ipv4Addr> {
+        self.interface
+            .addresses
+            .iter()
+            .find_map(|network| match network {
+                IpNetwork::V4(network) if network.prefix() <= 30 => {
+                    let gateway = Ipv4Addr::from(u32::from(network.network()) + 1);
+                    (gateway != network.ip()).then_some(gateway)
+                }
+                _ => None,
+            })
+    }
+
+    fn derive_ipv6_gateway(&self) -> Option<Ipv6Addr> {
+        self.interface
+            .addresses
+            .iter()
+            .find_map(|network| match network {
+                IpNetwork::V6(network) if network.prefix() <= 126 => {
+                    let gateway = Ipv6Addr::from(u128::from(network.network()) + 1);
+                    (gateway != network.ip()).then_some(gateway)
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Fields of a section that have been read so far.
+#[derive(Default)]
+struct PartialSection {
+    line: usize,
+    private_key: Option<PrivateKey>,
+    public_key: Option<PublicKey>,
+    preshared_key: Option<PresharedKey>,
+    addresses: Vec<IpNetwork>,
+    allowed_ips: Vec<IpNetwork>,
+    dns: Vec<IpAddr>,
+    mtu: Option<u16>,
+    endpoint: Option<PeerEndpoint>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SectionKind {
+    Interface,
+    Peer,
+}
+
+impl FromStr for WgQuickConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut interface = None;
+        let mut peers = vec![];
+        let mut current: Option<(SectionKind, PartialSection)> = None;
+
+        let mut finish_section = |section: Option<(SectionKind, PartialSection)>| {
+            match section {
+                Some((SectionKind::Interface, section)) => {
+                    if interface.is_some() {
+                        return Err(Error::DuplicateInterface(section.line));
+                    }
+                    interface = Some(Interface {
+                        private_key: section.private_key.ok_or(Error::MissingKey {
+                            section: "Interface",
+                            key: "PrivateKey",
+                        })?,
+                        addresses: section.addresses,
+                        dns: section.dns,
+                        mtu: section.mtu,
+                    });
+                }
+                Some((SectionKind::Peer, section)) => peers.push(Peer {
+                    public_key: section.public_key.ok_or(Error::MissingKey {
+                        section: "Peer",
+                        key: "PublicKey",
+                    })?,
+                    preshared_key: section.preshared_key,
+                    allowed_ips: section.allowed_ips,
+                    endpoint: section.endpoint,
+                }),
+                None => (),
+            }
+            Ok(())
+        };
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.split_once('#') {
+                Some((line, _comment)) => line,
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or(Error::InvalidLine(line_number))?;
+                let kind = match name.trim() {
+                    "Interface" => SectionKind::Interface,
+                    "Peer" => SectionKind::Peer,
+                    name => {
+                        return Err(Error::UnknownSection {
+                            line: line_number,
+                            name: name.to_owned(),
+                        })
+                    }
+                };
+                let section = PartialSection {
+                    line: line_number,
+                    ..Default::default()
+                };
+                finish_section(current.replace((kind, section)))?;
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(Error::InvalidLine(line_number))?;
+            let (key, value) = (key.trim(), value.trim());
+            let (kind, section) = current
+                .as_mut()
+                .ok_or(Error::KeyOutsideSection(line_number))?;
+            let invalid_value = || Error::InvalidValue {
+                line: line_number,
+                key: key.to_owned(),
+                value: value.to_owned(),
+            };
+
+            match (*kind, key.to_ascii_lowercase().as_str()) {
+                (SectionKind::Interface, "privatekey") => {
+                    let key = PrivateKey::from_base64(value).map_err(|_| invalid_value())?;
+                    section.private_key = Some(key);
+                }
+                (SectionKind::Interface, "address") => {
+                    section
+                        .addresses
+                        .extend(parse_list::<IpNetwork>(value).map_err(|_| invalid_value())?);
+                }
+                (SectionKind::Interface, "dns") => {
+                    for entry in value.split(',').map(str::trim) {
+                        match entry.parse() {
+                            Ok(address) => section.dns.push(address),
+                            // Search domains have no equivalent in the DNS settings
+                            Err(_) => log::warn!("Ignoring DNS search domain '{entry}'"),
+                        }
+                    }
+                }
+                (SectionKind::Interface, "mtu") => {
+                    section.mtu = Some(value.parse().map_err(|_| invalid_value())?);
+                }
+                (SectionKind::Peer, "publickey") => {
+                    let key = PublicKey::from_base64(value).map_err(|_| invalid_value())?;
+                    section.public_key = Some(key);
+                }
+                (SectionKind::Peer, "presharedkey") => {
+                    let key = PresharedKey::from_base64(value).map_err(|_| invalid_value())?;
+                    section.preshared_key = Some(key);
+                }
+                (SectionKind::Peer, "allowedips") => {
+                    section
+                        .allowed_ips
+                        .extend(parse_list::<IpNetwork>(value).map_err(|_| invalid_value())?);
+                }
+                (SectionKind::Peer, "endpoint") => {
+                    section.endpoint = Some(value.parse().map_err(|_| invalid_value())?);
+                }
+                _ => log::warn!("Ignoring unsupported key '{key}' on line {line_number}"),
+            }
+        }
+        finish_section(current)?;
+
+        Ok(WgQuickConfig {
+            interface: interface.ok_or(Error::MissingInterface)?,
+            peers,
+        })
+    }
+}
+
+fn parse_list<T: FromStr>(value: &str) -> Result<Vec<T>, T::Err> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+impl fmt::Display for WgQuickConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interface = &self.interface;
+        writeln!(f, "[Interface]")?;
+        writeln!(f, "PrivateKey = {}", interface.private_key.to_base64())?;
+        if !interface.addresses.is_empty() {
+            writeln!(f, "Address = {}", join(&interface.addresses))?;
+        }
+        if !interface.dns.is_empty() {
+            writeln!(f, "DNS = {}", join(&interface.dns))?;
+        }
+        if let Some(mtu) = interface.mtu {
+            writeln!(f, "MTU = {mtu}")?;
+        }
+
+        for peer in &self.peers {
+            writeln!(f)?;
+            writeln!(f, "[Peer]")?;
+            writeln!(f, "PublicKey = {}", peer.public_key.to_base64())?;
+            if let Some(psk) = &peer.preshared_key {
+                writeln!(f, "PresharedKey = {}", psk.to_base64())?;
+            }
+            if !peer.allowed_ips.is_empty() {
+                writeln!(f, "AllowedIPs = {}", join(&peer.allowed_ips))?;
+            }
+            if let Some(endpoint) = &peer.endpoint {
+                writeln!(f, "Endpoint = {endpoint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Exported by the infra team
+[Interface]
+PrivateKey = mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI=
+Address = 10.68.1.2/32, fc00:bbbb:bbbb:bb01::5:102/128
+DNS = 10.64.0.1, example.com
+MTU = 1380
+PostUp = true
+
+[Peer]
+PublicKey = 3lgR7CZ+KWjf/7ofXdTOPSqIWqUtAKnI2nqnyd7zL0s=
+presharedkey = yRigiAA0K8Vc0B/E1mULgtzyqWLIOJa7nnSkk+ON4Ac=
+AllowedIPs = 0.0.0.0/0,::/0
+Endpoint = wg.example.com:51820 # trailing comment
+";
+
+    #[test]
+    fn test_parse() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+
+        assert_eq!(config.interface.addresses.len(), 2);
+        assert_eq!(config.interface.dns, vec![IpAddr::from([10, 64, 0, 1])]);
+        assert_eq!(config.interface.mtu, Some(1380));
+        let [peer] = &config.peers[..] else {
+            panic!("expected one peer");
+        };
+        assert!(peer.preshared_key.is_some());
+        assert_eq!(
+            peer.allowed_ips,
+            vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()]
+        );
+        assert_eq!(
+            peer.endpoint,
+            Some(PeerEndpoint {
+                host: "wg.example.com".to_owned(),
+                port: 51820,
+            })
+        );
+    }
+
+    /// Serializing and parsing a config should give back the same config.
+    #[test]
+    fn test_roundtrip() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+        let serialized = config.to_string();
+        assert_eq!(serialized.parse::<WgQuickConfig>().unwrap(), config);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "PrivateKey = abc".parse::<WgQuickConfig>(),
+            Err(Error::KeyOutsideSection(1))
+        ));
+        assert!(matches!(
+            "[Interface]\nMTU = big".parse::<WgQuickConfig>(),
+            Err(Error::InvalidValue { line: 2, .. })
+        ));
+        assert!(matches!(
+            "[Interface]\nAddress = 10.0.0.1/32".parse::<WgQuickConfig>(),
+            Err(Error::MissingKey { .. })
+        ));
+        assert!(matches!(
+            "[Wat]".parse::<WgQuickConfig>(),
+            Err(Error::UnknownSection { .. })
+        ));
+        assert!(matches!(
+            "".parse::<WgQuickConfig>(),
+            Err(Error::MissingInterface)
+        ));
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint: PeerEndpoint = "[2001:db8::1]:443".parse().unwrap();
+        assert_eq!(endpoint.host, "2001:db8::1");
+        assert_eq!(endpoint.to_string(), "[2001:db8::1]:443");
+        assert!("2001:db8::1:443".parse::<PeerEndpoint>().is_err());
+        assert!("example.com".parse::<PeerEndpoint>().is_err());
+    }
+
+    #[test]
+    fn test_to_custom_tunnel_endpoint() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+        let endpoint = config
+            .to_custom_tunnel_endpoint(Some(Ipv4Addr::new(10, 64, 0, 1)), None)
+            .unwrap();
+
+        assert_eq!(endpoint.host, "wg.example.com");
+        let ConnectionConfig::Wireguard(connection) = endpoint.config else {
+            panic!("expected a WireGuard config");
+        };
+        assert_eq!(connection.ipv4_gateway, Ipv4Addr::new(10, 64, 0, 1));
+        assert_eq!(connection.ipv6_gateway, None);
+        assert_eq!(connection.peer.endpoint.port(), 51820);
+        assert_eq!(connection.peer.psk, config.peers[0].preshared_key);
+
+        let exported =
+            WgQuickConfig::from_connection_config(&connection, config.interface.dns.clone(), None)
+                .unwrap();
+        assert_eq!(exported.interface.addresses, config.interface.addresses);
+        assert_eq!(exported.peers[0].allowed_ips, config.peers[0].allowed_ips);
+    }
+
+    /// Gateways are derived from the networks of the addresses, never from the DNS servers.
+    #[test]
+    fn test_derive_gateways() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+        assert!(matches!(
+            config.to_custom_tunnel_endpoint(None, None),
+            Err(Error::MissingGateway)
+        ));
+
+        let config: WgQuickConfig = CONFIG
+            .replace(
+                "10.68.1.2/32, fc00:bbbb:bbbb:bb01::5:102/128",
+                "10.0.0.2/24, fd00::2/64",
+            )
+            .parse()
+            .unwrap();
+        let endpoint = config.to_custom_tunnel_endpoint(None, None).unwrap();
+        let ConnectionConfig::Wireguard(connection) = endpoint.config else {
+            panic!("expected a WireGuard config");
+        };
+        assert_eq!(connection.ipv4_gateway, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(connection.ipv6_gateway, Some("fd00::1".parse().unwrap()));
+
+        // The host itself can't be the gateway
+        let config: WgQuickConfig = CONFIG
+            .replace("10.68.1.2/32", "10.0.0.1/24")
+            .parse()
+            .unwrap();
+        assert!(config.to_custom_tunnel_endpoint(None, None).is_err());
+    }
+}
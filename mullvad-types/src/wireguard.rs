@@ -251,6 +251,8 @@ pub struct TunnelOptions {
     pub daita: DaitaSettings,
     /// Interval used for automatic key rotation
     pub rotation_interval: Option<RotationInterval>,
+    /// Timeouts and ping target used to detect a broken tunnel
+    pub connectivity: wireguard::ConnectivityCheckOptions,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -262,6 +264,7 @@ impl Default for TunnelOptions {
             #[cfg(daita)]
             daita: DaitaSettings::default(),
             rotation_interval: None,
+            connectivity: wireguard::ConnectivityCheckOptions::default(),
         }
     }
 }
@@ -273,6 +276,7 @@ impl TunnelOptions {
             quantum_resistant: self.quantum_resistant.enabled(),
             #[cfg(daita)]
             daita: self.daita.enabled,
+            connectivity: self.connectivity,
         }
     }
 }
@@ -291,3 +295,92 @@ pub struct AssociatedAddresses {
     pub ipv4_address: ipnetwork::Ipv4Network,
     pub ipv6_address: ipnetwork::Ipv6Network,
 }
+
+/// Traffic statistics for the peers of the current tunnel.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TunnelStats {
+    pub peers: Vec<PeerTrafficStats>,
+}
+
+/// Byte counters and throughput for a single peer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PeerTrafficStats {
+    pub public_key: wireguard::PublicKey,
+    /// Bytes sent to the peer since the tunnel was started
+    pub tx_bytes: u64,
+    /// Bytes received from the peer since the tunnel was started
+    pub rx_bytes: u64,
+    /// Bytes per second sent to the peer since the previous sample
+    pub tx_rate: u64,
+    /// Bytes per second received from the peer since the previous sample
+    pub rx_rate: u64,
+    pub last_handshake: Option<DateTime<Utc>>,
+}
+
+impl TunnelStats {
+    /// Compute the throughput of each peer from two samples of its counters taken `elapsed`
+    /// apart. Peers missing from `previous` have no throughput yet.
+    pub fn from_samples(
+        current: &[wireguard::PeerStats],
+        previous: &[wireguard::PeerStats],
+        elapsed: Duration,
+    ) -> Self {
+        let rate = |current: u64, previous: Option<u64>| {
+            let secs = elapsed.as_secs_f64();
+            match previous.and_then(|previous| current.checked_sub(previous)) {
+                Some(delta) if secs > 0.0 => (delta as f64 / secs) as u64,
+                _ => 0,
+            }
+        };
+
+        let mut peers: Vec<_> = current
+            .iter()
+            .map(|peer| {
+                let previous = previous
+                    .iter()
+                    .find(|previous| previous.public_key == peer.public_key);
+                PeerTrafficStats {
+                    public_key: peer.public_key.clone(),
+                    tx_bytes: peer.tx_bytes,
+                    rx_bytes: peer.rx_bytes,
+                    tx_rate: rate(peer.tx_bytes, previous.map(|previous| previous.tx_bytes)),
+                    rx_rate: rate(peer.rx_bytes, previous.map(|previous| previous.rx_bytes)),
+                    last_handshake: peer.last_handshake.map(DateTime::<Utc>::from),
+                }
+            })
+            .collect();
+        peers.sort_by(|a, b| a.public_key.as_bytes().cmp(b.public_key.as_bytes()));
+
+        TunnelStats { peers }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tunnel_stats_rate() {
+        let peer = |key: u8, tx_bytes, rx_bytes| wireguard::PeerStats {
+            public_key: wireguard::PublicKey::from([key; 32]),
+            tx_bytes,
+            rx_bytes,
+            last_handshake: None,
+        };
+        let previous = [peer(1, 1000, 5000), peer(2, 100, 100)];
+        let current = [peer(1, 3000, 9000), peer(2, 50, 100), peer(3, 10, 10)];
+
+        let stats = TunnelStats::from_samples(&current, &previous, Duration::from_secs(2));
+
+        assert_eq!(stats.peers.len(), 3);
+        assert_eq!(
+            (stats.peers[0].tx_rate, stats.peers[0].rx_rate),
+            (1000, 2000)
+        );
+        // Counters that went backwards belong to a new tunnel
+        assert_eq!((stats.peers[1].tx_rate, stats.peers[1].rx_rate), (0, 0));
+        // Peers without a previous sample have no rate yet
+        assert_eq!((stats.peers[2].tx_rate, stats.peers[2].rx_rate), (0, 0));
+        assert_eq!(stats.peers[2].tx_bytes, 10);
+    }
+}
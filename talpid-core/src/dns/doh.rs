@@ -0,0 +1,217 @@
+//! A DNS-over-HTTPS (RFC 8484) client.
+//!
+//! hickory always sends DNS-over-HTTPS queries to `/dns-query`, but servers may expect them on
+//! any path. This client sends them to the path that the server was configured with.
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use h2::client::SendRequest;
+use hickory_proto::{error::ProtoError, op::Message};
+use hickory_rustls::{ClientConfig, ServerName};
+use http::{header, Request, StatusCode};
+use tokio::{net::TcpStream, sync::Mutex, task::JoinHandle};
+use tokio_rustls::TlsConnector;
+
+/// Media type of DNS messages in requests and responses.
+const DNS_MESSAGE_TYPE: &str = "application/dns-message";
+
+/// Largest DNS message that can be received.
+const MAX_MESSAGE_LEN: usize = u16::MAX as usize;
+
+/// How long to wait for a connection to be established, and for a response to a query.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// DNS-over-HTTPS errors
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The server has no address
+    #[error("No address to connect to")]
+    NoAddress,
+
+    /// The hostname cannot be used to verify the server certificate
+    #[error("Invalid server name: {0}")]
+    InvalidServerName(String),
+
+    /// Failed to connect to the server
+    #[error("Failed to connect to {0}")]
+    Connect(SocketAddr, #[source] io::Error),
+
+    /// The HTTP/2 connection failed
+    #[error("HTTP/2 error")]
+    Http(#[source] h2::Error),
+
+    /// Failed to create the HTTP request
+    #[error("Failed to create HTTP request")]
+    Request(#[source] http::Error),
+
+    /// The server did not accept the query
+    #[error("Unexpected HTTP status: {0}")]
+    Status(StatusCode),
+
+    /// Failed to encode the query, or to decode the response
+    #[error("Invalid DNS message")]
+    Message(#[source] ProtoError),
+
+    /// The response is larger than any DNS message
+    #[error("The response is too large")]
+    ResponseTooLarge,
+
+    /// The server did not respond in time
+    #[error("Timed out")]
+    Timeout,
+}
+
+/// Sends DNS queries to a DNS-over-HTTPS server, over a connection that is kept open between
+/// queries.
+pub struct DohClient {
+    addresses: Vec<SocketAddr>,
+    hostname: String,
+    uri: String,
+    tls_connector: TlsConnector,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// An open HTTP/2 connection to the server.
+struct Connection {
+    send_request: SendRequest<Bytes>,
+    /// Drives the connection. Finishes when the connection is closed.
+    task: JoinHandle<()>,
+}
+
+impl DohClient {
+    /// Create a client for the server called `hostname`, which is reached on `addresses` and
+    /// `port`. Queries are sent to `path`.
+    pub fn new(
+        addresses: &[IpAddr],
+        hostname: &str,
+        port: u16,
+        path: &str,
+        mut tls_config: ClientConfig,
+    ) -> Self {
+        tls_config.alpn_protocols = vec![b"h2".to_vec()];
+        let authority = if hostname.contains(':') {
+            format!("[{hostname}]:{port}")
+        } else {
+            format!("{hostname}:{port}")
+        };
+        DohClient {
+            addresses: addresses
+                .iter()
+                .map(|address| SocketAddr::new(*address, port))
+                .collect(),
+            hostname: hostname.to_owned(),
+            uri: format!("https://{authority}{path}"),
+            tls_connector: TlsConnector::from(Arc::new(tls_config)),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Send `query` to the server and return its response.
+    pub async fn query(&self, query: &Message) -> Result<Message, Error> {
+        let body = Bytes::from(query.to_vec().map_err(Error::Message)?);
+        let request = Request::post(&self.uri)
+            .header(header::CONTENT_TYPE, DNS_MESSAGE_TYPE)
+            .header(header::ACCEPT, DNS_MESSAGE_TYPE)
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(())
+            .map_err(Error::Request)?;
+
+        let send_request = self.send_request().await?;
+        tokio::time::timeout(TIMEOUT, Self::exchange(send_request, request, body))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    async fn exchange(
+        send_request: SendRequest<Bytes>,
+        request: Request<()>,
+        body: Bytes,
+    ) -> Result<Message, Error> {
+        let mut send_request = send_request.ready().await.map_err(Error::Http)?;
+        let (response, mut request_body) = send_request
+            .send_request(request, false)
+            .map_err(Error::Http)?;
+        request_body.send_data(body, true).map_err(Error::Http)?;
+
+        let response = response.await.map_err(Error::Http)?;
+        if response.status() != StatusCode::OK {
+            return Err(Error::Status(response.status()));
+        }
+
+        let mut response_body = response.into_body();
+        let mut message = vec![];
+        while let Some(data) = response_body.data().await {
+            let data = data.map_err(Error::Http)?;
+            let _ = response_body.flow_control().release_capacity(data.len());
+            if message.len() + data.len() > MAX_MESSAGE_LEN {
+                return Err(Error::ResponseTooLarge);
+            }
+            message.extend_from_slice(&data);
+        }
+        Message::from_vec(&message).map_err(Error::Message)
+    }
+
+    /// Return a handle for sending requests on the open connection, or on a new one if it has
+    /// been closed.
+    async fn send_request(&self) -> Result<SendRequest<Bytes>, Error> {
+        let mut connection = self.connection.lock().await;
+        match &*connection {
+            Some(connection) if !connection.task.is_finished() => {
+                Ok(connection.send_request.clone())
+            }
+            _ => {
+                let new_connection = self.connect().await?;
+                let send_request = new_connection.send_request.clone();
+                *connection = Some(new_connection);
+                Ok(send_request)
+            }
+        }
+    }
+
+    /// Connect to the first address of the server that can be reached.
+    async fn connect(&self) -> Result<Connection, Error> {
+        let server_name = ServerName::try_from(self.hostname.as_str())
+            .map_err(|_| Error::InvalidServerName(self.hostname.clone()))?;
+
+        let mut last_error = Error::NoAddress;
+        for &address in &self.addresses {
+            let connect = self.connect_to(address, server_name.clone());
+            match tokio::time::timeout(TIMEOUT, connect).await {
+                Ok(Ok(connection)) => return Ok(connection),
+                Ok(Err(error)) => last_error = error,
+                Err(_) => last_error = Error::Timeout,
+            }
+            log::debug!("Failed to connect to DNS-over-HTTPS server at {address}: {last_error}");
+        }
+        Err(last_error)
+    }
+
+    async fn connect_to(
+        &self,
+        address: SocketAddr,
+        server_name: ServerName,
+    ) -> Result<Connection, Error> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|error| Error::Connect(address, error))?;
+        let stream = self
+            .tls_connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|error| Error::Connect(address, error))?;
+        let (send_request, connection) =
+            h2::client::handshake(stream).await.map_err(Error::Http)?;
+
+        let task = tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                log::debug!("DNS-over-HTTPS connection closed: {error}");
+            }
+        });
+        Ok(Connection { send_request, task })
+    }
+}
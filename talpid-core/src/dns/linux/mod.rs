@@ -10,11 +10,19 @@ use self::{
 use std::{
     env,
     fmt::{self, Display},
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
 };
 use talpid_routing::RouteManagerHandle;
+use talpid_types::net::dns::SplitDnsRule;
 
-use super::ResolvedDnsConfig;
+use super::{
+    stub::{self, StubResolver},
+    ResolvedDnsConfig,
+};
+
+/// Address that the split DNS forwarder listens on, for DNS managers that cannot route queries
+/// by domain themselves.
+const SPLIT_DNS_FORWARDER_IP: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 153);
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -37,6 +45,10 @@ pub enum Error {
     #[error("Error in static /etc/resolv.conf DNS monitor")]
     StaticResolvConf(#[from] static_resolv_conf::Error),
 
+    /// Failed to start the split DNS forwarder
+    #[error("Failed to start split DNS forwarder")]
+    SplitDnsForwarder(#[from] stub::Error),
+
     /// No suitable DNS monitor implementation detected
     #[error("No suitable DNS monitor implementation detected")]
     NoDnsMonitor,
@@ -46,6 +58,7 @@ pub struct DnsMonitor {
     route_manager: RouteManagerHandle,
     handle: tokio::runtime::Handle,
     inner: Option<DnsMonitorHolder>,
+    split_dns_forwarder: Option<StubResolver>,
 }
 
 impl super::DnsMonitorT for DnsMonitor {
@@ -56,27 +69,66 @@ impl super::DnsMonitorT for DnsMonitor {
             route_manager,
             handle,
             inner: None,
+            split_dns_forwarder: None,
         })
     }
 
     fn set(&mut self, interface: &str, config: ResolvedDnsConfig) -> Result<()> {
         let servers = config.tunnel_config();
+        let split_rules = config.split_rules();
         self.reset()?;
         // Creating a new DNS monitor for each set, in case the system changed how it manages DNS.
         let mut inner = DnsMonitorHolder::new()?;
-        if !servers.is_empty() {
-            inner.set(&self.handle, &self.route_manager, interface, servers)?;
-            self.inner = Some(inner);
+        if servers.is_empty() {
+            return Ok(());
         }
+
+        if split_rules.is_empty() || matches!(inner, DnsMonitorHolder::SystemdResolved(_)) {
+            inner.set(
+                &self.handle,
+                &self.route_manager,
+                interface,
+                servers,
+                split_rules,
+            )?;
+        } else {
+            // Point the system at a forwarder that sends queries for split DNS domains to the
+            // resolvers of each rule, and everything else to the tunnel DNS servers.
+            let forwarder = self.handle.block_on(StubResolver::start_split(
+                SocketAddr::new(SPLIT_DNS_FORWARDER_IP.into(), stub::STUB_PORT),
+                servers,
+                split_rules,
+            ))?;
+            self.split_dns_forwarder = Some(forwarder);
+            inner.set(
+                &self.handle,
+                &self.route_manager,
+                interface,
+                &[IpAddr::from(SPLIT_DNS_FORWARDER_IP)],
+                &[],
+            )?;
+        }
+        self.inner = Some(inner);
         Ok(())
     }
 
     fn reset(&mut self) -> Result<()> {
+        if let Some(forwarder) = self.split_dns_forwarder.take() {
+            self.handle.block_on(forwarder.stop());
+        }
         if let Some(mut inner) = self.inner.take() {
             inner.reset(&self.handle)?;
         }
         Ok(())
     }
+
+    fn backend(&self) -> Option<String> {
+        let inner = self.inner.as_ref()?;
+        Some(match self.split_dns_forwarder {
+            Some(_) => format!("{inner} via split DNS forwarder"),
+            None => inner.to_string(),
+        })
+    }
 }
 
 pub enum DnsMonitorHolder {
@@ -148,6 +200,7 @@ impl DnsMonitorHolder {
         route_manager: &RouteManagerHandle,
         interface: &str,
         servers: &[IpAddr],
+        split_rules: &[SplitDnsRule],
     ) -> Result<()> {
         use self::DnsMonitorHolder::*;
         match self {
@@ -157,6 +210,7 @@ impl DnsMonitorHolder {
                 route_manager.clone(),
                 interface,
                 servers,
+                split_rules,
             ))?,
             NetworkManager(network_manager) => network_manager.set_dns(interface, servers)?,
         }
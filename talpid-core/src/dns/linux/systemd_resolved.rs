@@ -1,11 +1,17 @@
 use crate::linux::{iface_index, IfaceIndexLookupError};
-use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, net::IpAddr};
 use talpid_dbus::systemd_resolved::{AsyncHandle, SystemdResolved as DbusInterface};
 use talpid_routing::RouteManagerHandle;
-use talpid_types::ErrorExt;
+use talpid_types::{net::dns::SplitDnsRule, ErrorExt};
 
 pub(crate) use talpid_dbus::systemd_resolved::Error as SystemdDbusError;
 
+/// The original configuration of links that split DNS domains have been added to is saved here,
+/// so that it can be restored if the daemon stops without resetting it. Like the configuration
+/// of links, the file does not survive a reboot.
+const SPLIT_DNS_BACKUP_PATH: &str = "/run/mullvad-split-dns.mullvadbackup";
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -15,20 +21,39 @@ pub enum Error {
 
     #[error("Failed to resolve interface index with error {0}")]
     InterfaceNameError(#[from] IfaceIndexLookupError),
+
+    #[error("Failed to find the route to a split DNS resolver")]
+    RouteLookupError(#[source] talpid_routing::Error),
+
+    #[error("Failed to write split DNS backup to {0}")]
+    WriteBackup(&'static str, #[source] io::Error),
 }
 
 pub struct SystemdResolved {
     pub dbus_interface: AsyncHandle,
     tunnel_index: u32,
+    /// Non-tunnel links that split DNS domains have been added to
+    split_dns_links: Vec<SplitDnsLink>,
+}
+
+/// The original configuration of a link that split DNS domains have been added to.
+#[derive(Serialize, Deserialize)]
+struct SplitDnsLink {
+    interface_index: u32,
+    servers: Vec<IpAddr>,
+    domains: Vec<(String, bool)>,
 }
 
 impl SystemdResolved {
     pub fn new() -> Result<Self> {
-        let dbus_interface = DbusInterface::new()?.async_handle();
+        let dbus_interface = DbusInterface::new()?;
+        restore_split_dns_backup(&dbus_interface);
+        let dbus_interface = dbus_interface.async_handle();
 
         let systemd_resolved = SystemdResolved {
             dbus_interface,
             tunnel_index: 0,
+            split_dns_links: vec![],
         };
 
         Ok(systemd_resolved)
@@ -36,9 +61,10 @@ impl SystemdResolved {
 
     pub async fn set_dns(
         &mut self,
-        _route_manager: RouteManagerHandle,
+        route_manager: RouteManagerHandle,
         interface_name: &str,
         servers: &[IpAddr],
+        split_rules: &[SplitDnsRule],
     ) -> Result<()> {
         let tunnel_index = iface_index(interface_name)?;
         self.tunnel_index = tunnel_index;
@@ -60,10 +86,139 @@ impl SystemdResolved {
             .set_dns(self.tunnel_index, servers.to_vec())
             .await?;
 
+        self.set_split_dns(&route_manager, split_rules).await;
+
         Ok(())
     }
 
+    /// Add the domain of each split DNS rule as a routing domain to the link that its resolvers
+    /// are reached through, so that systemd-resolved sends queries for it there instead of to the
+    /// tunnel. The resolvers are put before the existing DNS servers of the link.
+    ///
+    /// The routing domain `~.` of the tunnel link cannot be combined with other servers on the
+    /// same link, so resolvers that are reached through the tunnel are ignored.
+    async fn set_split_dns(&mut self, route_manager: &RouteManagerHandle, rules: &[SplitDnsRule]) {
+        let mut links: BTreeMap<u32, (Vec<IpAddr>, Vec<&str>)> = BTreeMap::new();
+        for rule in rules {
+            for resolver in rule.resolvers() {
+                match self.non_tunnel_link(route_manager, *resolver).await {
+                    Ok(Some(link)) => {
+                        let (resolvers, domains) = links.entry(link).or_default();
+                        if !resolvers.contains(resolver) {
+                            resolvers.push(*resolver);
+                        }
+                        if !domains.contains(&rule.domain()) {
+                            domains.push(rule.domain());
+                        }
+                    }
+                    Ok(None) => log::warn!(
+                        "Ignoring split DNS resolver {resolver} for {}, since it is not \
+                        reachable outside the tunnel",
+                        rule.domain()
+                    ),
+                    Err(error) => log::error!(
+                        "{}",
+                        error.display_chain_with_msg("Failed to set up split DNS resolver")
+                    ),
+                }
+            }
+        }
+
+        for (link, (resolvers, domains)) in links {
+            if let Err(error) = self.add_split_dns_link(link, resolvers, &domains).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to add split DNS domains")
+                );
+            }
+        }
+    }
+
+    /// Return the index of the link that `resolver` is reached through, unless that is the
+    /// tunnel.
+    async fn non_tunnel_link(
+        &self,
+        route_manager: &RouteManagerHandle,
+        resolver: IpAddr,
+    ) -> Result<Option<u32>> {
+        let route = route_manager
+            .get_destination_route(resolver, None)
+            .await
+            .map_err(Error::RouteLookupError)?;
+        let Some(device) = route
+            .as_ref()
+            .and_then(|route| route.get_node().get_device())
+        else {
+            return Ok(None);
+        };
+        let index = iface_index(device)?;
+        Ok((index != self.tunnel_index).then_some(index))
+    }
+
+    async fn add_split_dns_link(
+        &mut self,
+        link: u32,
+        resolvers: Vec<IpAddr>,
+        domains: &[&str],
+    ) -> Result<()> {
+        let dns_state = self.dbus_interface.get_dns(link).await?;
+        let original_domains = self.dbus_interface.get_domains(link).await?;
+
+        let mut servers = resolvers;
+        for server in &dns_state.set_servers {
+            if !servers.contains(server) {
+                servers.push(*server);
+            }
+        }
+        let mut new_domains: Vec<(&str, bool)> = original_domains
+            .iter()
+            .map(|(domain, routing_only)| (domain.as_str(), *routing_only))
+            .collect();
+        new_domains.extend(domains.iter().map(|domain| (*domain, true)));
+
+        self.split_dns_links.push(SplitDnsLink {
+            interface_index: link,
+            servers: dns_state.set_servers,
+            domains: original_domains.clone(),
+        });
+        if let Err(error) = write_split_dns_backup(&self.split_dns_links) {
+            self.split_dns_links.pop();
+            return Err(error);
+        }
+
+        self.dbus_interface.set_dns(link, servers).await?;
+        self.dbus_interface.set_domains(link, &new_domains).await?;
+
+        log::debug!("Added split DNS domains {domains:?} to link {link}");
+        Ok(())
+    }
+
+    async fn reset_split_dns(&mut self) {
+        if self.split_dns_links.is_empty() {
+            return;
+        }
+        for link in self.split_dns_links.drain(..) {
+            let index = link.interface_index;
+            if let Err(error) = self.dbus_interface.set_dns(index, link.servers).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to restore DNS servers of link")
+                );
+            }
+            let domains = link.borrowed_domains();
+            if let Err(error) = self.dbus_interface.set_domains(index, &domains).await {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to restore search domains of link")
+                );
+            }
+        }
+        let _ = fs::remove_file(SPLIT_DNS_BACKUP_PATH);
+    }
+
     pub async fn reset(&mut self) -> Result<()> {
+        self.reset_split_dns().await;
+
         if let Err(error) = self
             .dbus_interface
             .set_domains(self.tunnel_index, &[])
@@ -80,3 +235,57 @@ impl SystemdResolved {
         Ok(())
     }
 }
+
+impl SplitDnsLink {
+    fn borrowed_domains(&self) -> Vec<(&str, bool)> {
+        self.domains
+            .iter()
+            .map(|(domain, routing_only)| (domain.as_str(), *routing_only))
+            .collect()
+    }
+}
+
+fn write_split_dns_backup(links: &[SplitDnsLink]) -> Result<()> {
+    let backup = serde_json::to_string(links).expect("split DNS backup is serializable");
+    fs::write(SPLIT_DNS_BACKUP_PATH, backup)
+        .map_err(|error| Error::WriteBackup(SPLIT_DNS_BACKUP_PATH, error))
+}
+
+/// Restore links that split DNS domains were added to, in case the daemon stopped without
+/// resetting them.
+fn restore_split_dns_backup(dbus_interface: &DbusInterface) {
+    let backup = match fs::read_to_string(SPLIT_DNS_BACKUP_PATH) {
+        Ok(backup) => backup,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return,
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to read split DNS backup")
+            );
+            return;
+        }
+    };
+    log::info!("Restoring links with split DNS domains from backup");
+    match serde_json::from_str::<Vec<SplitDnsLink>>(&backup) {
+        Ok(links) => {
+            for link in links {
+                let index = link.interface_index;
+                let result = dbus_interface
+                    .set_dns(index, link.servers.clone())
+                    .and_then(|_| dbus_interface.set_domains(index, &link.borrowed_domains()));
+                if let Err(error) = result {
+                    log::error!(
+                        "{}",
+                        error
+                            .display_chain_with_msg("Failed to restore link from split DNS backup")
+                    );
+                }
+            }
+        }
+        Err(error) => log::error!(
+            "{}",
+            error.display_chain_with_msg("Failed to parse split DNS backup")
+        ),
+    }
+    let _ = fs::remove_file(SPLIT_DNS_BACKUP_PATH);
+}
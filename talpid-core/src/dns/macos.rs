@@ -420,6 +420,10 @@ impl super::DnsMonitorT for DnsMonitor {
     fn reset(&mut self) -> Result<()> {
         self.state.lock().reset(&self.store)
     }
+
+    fn backend(&self) -> Option<String> {
+        Some("System Configuration framework".to_owned())
+    }
 }
 
 impl DnsMonitor {
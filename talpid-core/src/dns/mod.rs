@@ -1,5 +1,8 @@
 use std::fmt;
 use std::net::IpAddr;
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::dns::DnsEncryption;
+use talpid_types::net::dns::SplitDnsRule;
 
 #[cfg(target_os = "linux")]
 use talpid_routing::RouteManagerHandle;
@@ -25,16 +28,24 @@ mod imp;
 
 pub use self::imp::Error;
 
+#[cfg(not(target_os = "android"))]
+mod doh;
+#[cfg(not(target_os = "android"))]
+pub mod stub;
+
 /// DNS configuration
 #[derive(Debug, Clone, PartialEq)]
 pub struct DnsConfig {
     config: InnerDnsConfig,
+    /// Domains that are resolved by specific resolvers instead of `config`
+    split_rules: Vec<SplitDnsRule>,
 }
 
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
             config: InnerDnsConfig::Default,
+            split_rules: vec![],
         }
     }
 }
@@ -47,8 +58,46 @@ impl DnsConfig {
                 tunnel_config: tunnel_config.to_owned(),
                 non_tunnel_config: non_tunnel_config.to_owned(),
             },
+            split_rules: vec![],
+        }
+    }
+
+    /// Forward DNS queries to the specified addresses over an encrypted transport. The queries
+    /// are answered by a [stub::StubResolver] while the tunnel is up.
+    #[cfg(not(target_os = "android"))]
+    pub fn encrypted(addresses: &[IpAddr], encryption: DnsEncryption) -> Self {
+        DnsConfig {
+            config: InnerDnsConfig::Encrypted {
+                addresses: addresses.to_owned(),
+                encryption,
+            },
+            split_rules: vec![],
+        }
+    }
+
+    /// Return the upstream addresses and transport, if queries should be encrypted.
+    #[cfg(not(target_os = "android"))]
+    pub fn encryption(&self) -> Option<(&[IpAddr], &DnsEncryption)> {
+        match &self.config {
+            InnerDnsConfig::Encrypted {
+                addresses,
+                encryption,
+            } => Some((addresses, encryption)),
+            _ => None,
         }
     }
+
+    /// Resolve queries for the domains of `split_rules` using the resolvers of each rule.
+    /// Split DNS is currently only applied on Linux.
+    pub fn with_split_rules(mut self, split_rules: Vec<SplitDnsRule>) -> Self {
+        self.split_rules = split_rules;
+        self
+    }
+
+    /// Return the per-domain resolver rules.
+    pub fn split_rules(&self) -> &[SplitDnsRule] {
+        &self.split_rules
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -64,6 +113,13 @@ enum InnerDnsConfig {
         /// on non-tunnel interface, only allow them in the firewall.
         non_tunnel_config: Vec<IpAddr>,
     },
+    /// Forward queries to the specified addresses over an encrypted transport
+    #[cfg(not(target_os = "android"))]
+    Encrypted {
+        /// Addresses of the encrypted upstream
+        addresses: Vec<IpAddr>,
+        encryption: DnsEncryption,
+    },
 }
 
 impl DnsConfig {
@@ -76,6 +132,7 @@ impl DnsConfig {
             InnerDnsConfig::Default => ResolvedDnsConfig {
                 tunnel_config: default_tun_config.to_owned(),
                 non_tunnel_config: vec![],
+                split_rules: self.split_rules.clone(),
                 #[cfg(target_os = "macos")]
                 port,
             },
@@ -85,6 +142,18 @@ impl DnsConfig {
             } => ResolvedDnsConfig {
                 tunnel_config: tunnel_config.to_owned(),
                 non_tunnel_config: non_tunnel_config.to_owned(),
+                split_rules: self.split_rules.clone(),
+                #[cfg(target_os = "macos")]
+                port,
+            },
+            // The system resolver must be pointed at the stub resolver instead, which is only
+            // listening once the tunnel is up. No plaintext DNS is allowed until then, except to
+            // the resolvers of split DNS rules.
+            #[cfg(not(target_os = "android"))]
+            InnerDnsConfig::Encrypted { .. } => ResolvedDnsConfig {
+                tunnel_config: vec![],
+                non_tunnel_config: vec![],
+                split_rules: self.split_rules.clone(),
                 #[cfg(target_os = "macos")]
                 port,
             },
@@ -101,6 +170,8 @@ pub struct ResolvedDnsConfig {
     /// For the most part, the tunnel state machine will not handle any of this configuration
     /// on non-tunnel interface, only allow them in the firewall.
     non_tunnel_config: Vec<IpAddr>,
+    /// Domains that are resolved by specific resolvers
+    split_rules: Vec<SplitDnsRule>,
     /// Port to use
     #[cfg(target_os = "macos")]
     port: u16,
@@ -114,6 +185,10 @@ impl fmt::Display for ResolvedDnsConfig {
         f.write_str(" Non-tunnel DNS: ")?;
         Self::fmt_addr_set(f, &self.non_tunnel_config)?;
 
+        for rule in &self.split_rules {
+            write!(f, " Split DNS: {rule}")?;
+        }
+
         #[cfg(target_os = "macos")]
         write!(f, " Port: {}", self.port)?;
 
@@ -145,6 +220,31 @@ impl ResolvedDnsConfig {
         &self.non_tunnel_config
     }
 
+    /// Domains that should be resolved by specific resolvers rather than `tunnel_config`.
+    pub fn split_rules(&self) -> &[SplitDnsRule] {
+        &self.split_rules
+    }
+
+    /// Resolvers of split DNS rules that are reached through the tunnel.
+    pub fn tunnel_split_resolvers(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.split_resolvers()
+            .filter(|addr| !crate::firewall::is_local_address(addr))
+    }
+
+    /// Resolvers of split DNS rules that are on the local network. Queries sent to these are
+    /// visible outside the tunnel.
+    pub fn non_tunnel_split_resolvers(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.split_resolvers()
+            .filter(|addr| crate::firewall::is_local_address(addr))
+    }
+
+    fn split_resolvers(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.split_rules
+            .iter()
+            .flat_map(|rule| rule.resolvers())
+            .copied()
+    }
+
     /// Consume `self` and return a vector of all addresses
     pub fn addresses(self) -> impl Iterator<Item = IpAddr> {
         self.non_tunnel_config.into_iter().chain(self.tunnel_config)
@@ -167,6 +267,8 @@ impl ResolvedDnsConfig {
 /// Sets and monitors system DNS settings. Makes sure the desired DNS servers are being used.
 pub struct DnsMonitor {
     inner: imp::DnsMonitor,
+    /// Interface and configuration that were most recently applied.
+    current: Option<(String, ResolvedDnsConfig)>,
 }
 
 impl DnsMonitor {
@@ -182,19 +284,24 @@ impl DnsMonitor {
                 #[cfg(target_os = "linux")]
                 route_manager,
             )?,
+            current: None,
         })
     }
 
     /// Set DNS to the given servers. And start monitoring the system for changes.
     pub fn set(&mut self, interface: &str, config: ResolvedDnsConfig) -> Result<(), Error> {
         log::info!("Setting DNS servers: {config}",);
-        self.inner.set(interface, config)
+        self.current = None;
+        self.inner.set(interface, config.clone())?;
+        self.current = Some((interface.to_owned(), config));
+        Ok(())
     }
 
     /// Reset system DNS settings to what it was before being set by this instance.
     /// This succeeds if the interface does not exist.
     pub fn reset(&mut self) -> Result<(), Error> {
         log::info!("Resetting DNS");
+        self.current = None;
         self.inner.reset()
     }
 
@@ -203,10 +310,24 @@ impl DnsMonitor {
     /// as the interface will be destroyed.
     pub fn reset_before_interface_removal(&mut self) -> Result<(), Error> {
         log::info!("Resetting DNS");
+        self.current = None;
         self.inner.reset_before_interface_removal()
     }
 }
 
+/// Describes the DNS configuration that is currently applied, for troubleshooting.
+impl fmt::Display for DnsMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((interface, config)) = &self.current else {
+            return f.write_str("DNS is not managed");
+        };
+        let backend = self.inner.backend();
+        writeln!(f, "Backend: {}", backend.as_deref().unwrap_or("none"))?;
+        writeln!(f, "Interface: {interface}")?;
+        write!(f, "{config}")
+    }
+}
+
 trait DnsMonitorT: Sized {
     type Error: std::error::Error;
 
@@ -219,6 +340,11 @@ trait DnsMonitorT: Sized {
 
     fn reset(&mut self) -> Result<(), Self::Error>;
 
+    /// Name of the mechanism that is used to configure DNS, if any.
+    fn backend(&self) -> Option<String> {
+        None
+    }
+
     fn reset_before_interface_removal(&mut self) -> Result<(), Self::Error> {
         self.reset()
     }
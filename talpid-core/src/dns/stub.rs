@@ -0,0 +1,705 @@
+//! A stub resolver that forwards DNS queries to an encrypted upstream, or to resolvers chosen per
+//! domain.
+//!
+//! When custom DNS is configured with DNS-over-HTTPS or DNS-over-TLS, the system resolver is
+//! pointed at the stub, which listens on port 53 on the tunnel interface address. Every query is
+//! forwarded over an encrypted connection, so plaintext DNS never has to be allowed by the
+//! firewall, and the upstream is reached through the tunnel like any other traffic.
+//! See [StubResolver::start].
+//!
+//! DNS managers that cannot route queries by domain are instead pointed at a stub that sends
+//! queries for split DNS domains to the resolvers of the matching rule. See
+//! [StubResolver::start_split].
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures::channel::oneshot;
+use hickory_resolver::{
+    config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    TokioAsyncResolver,
+};
+use hickory_rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, CertificateError, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    proto::{
+        op::{Header, Message, MessageType, OpCode, Query, ResponseCode},
+        rr::{Name, Record},
+    },
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+    ServerFuture,
+};
+use rustls::{
+    pki_types::{CertificateDer, SubjectPublicKeyInfoDer},
+    server::ParsedCertificate,
+};
+use talpid_types::{
+    net::dns::{DnsEncryption, EncryptedDnsProtocol, SpkiPin, SplitDnsRule},
+    ErrorExt,
+};
+use tokio::net::{TcpListener, UdpSocket};
+
+use super::doh::DohClient;
+
+/// Port that the stub resolver listens on.
+pub const STUB_PORT: u16 = 53;
+
+/// Port that plaintext upstream resolvers are queried on.
+const UPSTREAM_PORT: u16 = 53;
+
+/// How long an idle TCP client may stay connected to the stub.
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stub resolver errors
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind a socket for the stub resolver
+    #[error("Failed to bind stub resolver socket")]
+    Bind(#[source] io::Error),
+}
+
+/// A running stub resolver. It is stopped when this is dropped, but [StubResolver::stop] should
+/// be used to wait until its sockets have been closed.
+pub struct StubResolver {
+    listen_addr: SocketAddr,
+    shutdown_tx: oneshot::Sender<()>,
+    server_task: tokio::task::JoinHandle<()>,
+}
+
+impl StubResolver {
+    /// Start answering queries received on `listen_addr`, over both UDP and TCP, by forwarding
+    /// them to `upstream_addresses` using `encryption`.
+    pub async fn start(
+        listen_addr: SocketAddr,
+        upstream_addresses: &[IpAddr],
+        encryption: &DnsEncryption,
+    ) -> Result<Self, Error> {
+        let upstream = Upstream {
+            default: encrypted_resolver(upstream_addresses, encryption),
+            split: vec![],
+        };
+        let stub = Self::serve(listen_addr, upstream).await?;
+        log::debug!(
+            "Started DNS stub resolver on {}, forwarding to {encryption}",
+            stub.listen_addr
+        );
+        Ok(stub)
+    }
+
+    /// Start answering queries received on `listen_addr`, over both UDP and TCP. Queries for the
+    /// domain of a rule in `split_rules` are forwarded to the resolvers of the most specific
+    /// matching rule, and all other queries to `default_upstream`.
+    pub async fn start_split(
+        listen_addr: SocketAddr,
+        default_upstream: &[IpAddr],
+        split_rules: &[SplitDnsRule],
+    ) -> Result<Self, Error> {
+        let with_port = |addresses: &[IpAddr]| -> Vec<SocketAddr> {
+            addresses
+                .iter()
+                .map(|addr| SocketAddr::new(*addr, UPSTREAM_PORT))
+                .collect()
+        };
+        let upstream = Upstream {
+            default: plaintext_resolver(&with_port(default_upstream)),
+            split: split_rules
+                .iter()
+                .map(|rule| {
+                    let resolver = plaintext_resolver(&with_port(rule.resolvers()));
+                    (rule.clone(), resolver)
+                })
+                .collect(),
+        };
+        let stub = Self::serve(listen_addr, upstream).await?;
+        log::debug!(
+            "Started split DNS forwarder on {} with {} rule(s)",
+            stub.listen_addr,
+            split_rules.len()
+        );
+        Ok(stub)
+    }
+
+    async fn serve(listen_addr: SocketAddr, upstream: Upstream) -> Result<Self, Error> {
+        let udp_socket = UdpSocket::bind(listen_addr).await.map_err(Error::Bind)?;
+        let listen_addr = udp_socket.local_addr().map_err(Error::Bind)?;
+        let tcp_listener = TcpListener::bind(listen_addr).await.map_err(Error::Bind)?;
+
+        let mut server = ServerFuture::new(Forwarder {
+            upstream,
+            listen_ip: listen_addr.ip(),
+        });
+        server.register_socket(udp_socket);
+        server.register_listener(tcp_listener, TCP_TIMEOUT);
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let server_task = tokio::spawn(async move {
+            let shut_down = tokio::select! {
+                result = server.block_until_done() => {
+                    if let Err(error) = result {
+                        log::error!("DNS stub resolver unexpectedly stopped: {error}");
+                    }
+                    false
+                }
+                _ = shutdown_rx => true,
+            };
+            if shut_down {
+                if let Err(error) = server.shutdown_gracefully().await {
+                    log::error!("Failed to shut down DNS stub resolver: {error}");
+                }
+            }
+        });
+
+        Ok(StubResolver {
+            listen_addr,
+            shutdown_tx,
+            server_task,
+        })
+    }
+
+    /// Address that the stub resolver is listening on.
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.listen_addr
+    }
+
+    /// Stop the stub resolver and wait for its sockets to be closed.
+    pub async fn stop(self) {
+        let _ = self.shutdown_tx.send(());
+        let _ = self.server_task.await;
+    }
+}
+
+/// Create a resolver that sends all queries to `addresses` over an encrypted transport.
+fn encrypted_resolver(addresses: &[IpAddr], encryption: &DnsEncryption) -> UpstreamResolver {
+    let tls_config = match &encryption.spki_pin {
+        Some(pin) => pinned_tls_client_config(pin),
+        None => webpki_tls_client_config(),
+    };
+    let hostname = encryption.protocol.hostname();
+    let port = encryption.protocol.port();
+    match &encryption.protocol {
+        EncryptedDnsProtocol::Https { path, .. } => {
+            UpstreamResolver::Https(DohClient::new(addresses, hostname, port, path, tls_config))
+        }
+        EncryptedDnsProtocol::Tls { .. } => {
+            let name_servers =
+                NameServerConfigGroup::from_ips_tls(addresses, port, hostname.to_owned(), true)
+                    .with_client_config(Arc::new(tls_config));
+            let config = ResolverConfig::from_parts(None, vec![], name_servers);
+            UpstreamResolver::Hickory(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
+        }
+    }
+}
+
+/// Create a resolver that sends all queries to `addresses` over plaintext UDP, falling back to TCP.
+fn plaintext_resolver(addresses: &[SocketAddr]) -> UpstreamResolver {
+    let mut name_servers = NameServerConfigGroup::new();
+    for protocol in [Protocol::Udp, Protocol::Tcp] {
+        for addr in addresses {
+            let mut config = NameServerConfig::new(*addr, protocol);
+            config.trust_negative_responses = true;
+            name_servers.push(config);
+        }
+    }
+
+    let config = ResolverConfig::from_parts(None, vec![], name_servers);
+    UpstreamResolver::Hickory(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
+}
+
+/// Create a TLS configuration that verifies the certificate against the webpki roots.
+fn webpki_tls_client_config() -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+/// Create a TLS configuration that only trusts the certificate with the pinned public key.
+fn pinned_tls_client_config(spki_pin: &SpkiPin) -> ClientConfig {
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(SpkiPinVerifier(spki_pin.clone())))
+        .with_no_client_auth()
+}
+
+/// Accepts a server certificate if and only if its public key matches the pin.
+struct SpkiPinVerifier(SpkiPin);
+
+impl ServerCertVerifier for SpkiPinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, hickory_rustls::Error> {
+        let spki = subject_public_key_info(&end_entity.0).ok_or(
+            hickory_rustls::Error::InvalidCertificate(CertificateError::BadEncoding),
+        )?;
+        let digest = ring::digest::digest(&ring::digest::SHA256, spki.as_ref());
+        if digest.as_ref() == self.0.as_bytes() {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            log::warn!("Rejecting DNS server certificate that does not match the SPKI pin");
+            Err(hickory_rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+/// Return the DER encoded SubjectPublicKeyInfo of a DER encoded X.509 certificate.
+fn subject_public_key_info(certificate: &[u8]) -> Option<SubjectPublicKeyInfoDer<'static>> {
+    let certificate = CertificateDer::from(certificate);
+    let parsed = ParsedCertificate::try_from(&certificate).ok()?;
+    Some(parsed.subject_public_key_info())
+}
+
+/// Resolvers that queries are forwarded to.
+struct Upstream {
+    default: UpstreamResolver,
+    split: Vec<(SplitDnsRule, UpstreamResolver)>,
+}
+
+impl Upstream {
+    /// Return the resolver of the most specific split DNS rule that matches `name`, or the
+    /// default resolver.
+    fn resolver_for(&self, name: &Name) -> &UpstreamResolver {
+        let name = name.to_ascii();
+        self.split
+            .iter()
+            .filter(|(rule, _)| rule.matches(&name))
+            .max_by_key(|(rule, _)| rule.domain().len())
+            .map(|(_, resolver)| resolver)
+            .unwrap_or(&self.default)
+    }
+}
+
+/// A resolver that queries can be forwarded to.
+enum UpstreamResolver {
+    /// Resolves queries over plaintext DNS or DNS-over-TLS.
+    Hickory(TokioAsyncResolver),
+    /// Passes queries on to a DNS-over-HTTPS server.
+    Https(DohClient),
+}
+
+impl UpstreamResolver {
+    /// Resolve `query`, and return the answers, or the response code to answer with instead.
+    async fn lookup(&self, query: &Query) -> Result<Vec<Record>, ResponseCode> {
+        match self {
+            UpstreamResolver::Hickory(resolver) => {
+                match resolver
+                    .lookup(query.name().clone(), query.query_type())
+                    .await
+                {
+                    Ok(lookup) => Ok(lookup.records().to_vec()),
+                    Err(error) => match error.kind() {
+                        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                            Err(*response_code)
+                        }
+                        _ => {
+                            log::debug!("Failed to forward DNS query: {error}");
+                            Err(ResponseCode::ServFail)
+                        }
+                    },
+                }
+            }
+            UpstreamResolver::Https(client) => {
+                let mut message = Message::new();
+                message
+                    .set_message_type(MessageType::Query)
+                    .set_op_code(OpCode::Query)
+                    .set_recursion_desired(true)
+                    .add_query(query.clone());
+                match client.query(&message).await {
+                    Ok(response) if response.response_code() == ResponseCode::NoError => {
+                        Ok(response.answers().to_vec())
+                    }
+                    Ok(response) => Err(response.response_code()),
+                    Err(error) => {
+                        log::debug!("Failed to forward DNS query: {}", error.display_chain());
+                        Err(ResponseCode::ServFail)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An implementation of [hickory_server::server::RequestHandler] that forwards queries to the
+/// upstream resolvers.
+struct Forwarder {
+    upstream: Upstream,
+    listen_ip: IpAddr,
+}
+
+impl Forwarder {
+    async fn forward<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> io::Result<ResponseInfo> {
+        let query = request.query().original();
+        let lookup = self.upstream.resolver_for(query.name()).lookup(query).await;
+
+        let builder = MessageResponseBuilder::from_message_request(request);
+        match lookup {
+            Ok(records) => {
+                let mut header = Header::response_from_request(request.header());
+                header.set_recursion_available(true);
+                let response = builder.build(
+                    header,
+                    records.iter(),
+                    std::iter::empty(),
+                    std::iter::empty(),
+                    std::iter::empty(),
+                );
+                response_handle.send_response(response).await
+            }
+            Err(response_code) => {
+                let response = builder.error_msg(request.header(), response_code);
+                response_handle.send_response(response).await
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for Forwarder {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        // Queries from the host itself are sent from the address that the stub listens on
+        let src = request.src().ip();
+        if !src.is_loopback() && src != self.listen_ip {
+            log::warn!("Dropping a stray request from outside: {}", request.src());
+            return Header::new().into();
+        }
+        if request.message_type() != MessageType::Query || request.op_code() != OpCode::Query {
+            log::trace!("Dropping non-query request: {:?}", request);
+            return Header::new().into();
+        }
+
+        match self.forward(request, response_handle).await {
+            Ok(info) => info,
+            Err(error) => {
+                log::error!("Failed to send response: {error}");
+                Header::new().into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use hickory_resolver::error::ResolveError;
+    use hickory_server::proto::rr::{rdata, RData, RecordType};
+    use std::net::Ipv4Addr;
+    use talpid_types::net::dns::DOH_PATH;
+
+    const CERT: &[u8] = include_bytes!("../../test-data/localhost-cert.der");
+    const KEY: &[u8] = include_bytes!("../../test-data/localhost-key.der");
+    /// SHA-256 digest of the public key in `CERT`
+    const CERT_SPKI_PIN: &str = "rMqMq7PxApjx5d8HInjd/tiX+5tmmujij6mlSGNDez0=";
+
+    /// Address that the stand-in upstream resolves every name to
+    const UPSTREAM_ANSWER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+    /// Address that the stand-in split DNS resolver resolves every name to
+    const SPLIT_UPSTREAM_ANSWER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+
+    /// A stand-in for a DNS server, which answers every query with the same address.
+    struct StandInUpstream(Ipv4Addr);
+
+    #[async_trait::async_trait]
+    impl RequestHandler for StandInUpstream {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            let query = request.query();
+            let mut record = Record::with(query.name().into(), RecordType::A, 60);
+            record.set_data(Some(RData::A(rdata::A(self.0))));
+
+            let response = MessageResponseBuilder::from_message_request(request).build(
+                Header::response_from_request(request.header()),
+                [&record],
+                [],
+                [],
+                [],
+            );
+            response_handle.send_response(response).await.unwrap()
+        }
+    }
+
+    async fn start_upstream(protocol: Protocol) -> (ServerFuture<StandInUpstream>, u16) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let certificate_and_key = (
+            vec![Certificate(CERT.to_vec())],
+            hickory_rustls::PrivateKey(KEY.to_vec()),
+        );
+
+        let mut server = ServerFuture::new(StandInUpstream(UPSTREAM_ANSWER));
+        match protocol {
+            Protocol::Https => server.register_https_listener(
+                listener,
+                TCP_TIMEOUT,
+                certificate_and_key,
+                Some("localhost".to_owned()),
+            ),
+            Protocol::Tls => {
+                server.register_tls_listener(listener, TCP_TIMEOUT, certificate_and_key)
+            }
+            _ => unreachable!("not an encrypted protocol"),
+        }
+        .unwrap();
+
+        (server, port)
+    }
+
+    /// Start a stand-in DNS-over-HTTPS server, which only answers queries sent to `path`, and
+    /// return its port.
+    async fn start_doh_upstream(path: &'static str) -> u16 {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut config = hickory_rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(CERT.to_vec())],
+                hickory_rustls::PrivateKey(KEY.to_vec()),
+            )
+            .unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let stream = acceptor.accept(stream).await.unwrap();
+                let mut connection = h2::server::handshake(stream).await.unwrap();
+                tokio::spawn(async move {
+                    while let Some(Ok((request, respond))) = connection.accept().await {
+                        tokio::spawn(answer_doh_query(request, respond, path));
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    async fn answer_doh_query(
+        request: http::Request<h2::RecvStream>,
+        mut respond: h2::server::SendResponse<Bytes>,
+        path: &str,
+    ) {
+        let status = if request.uri().path_and_query().unwrap().as_str() == path {
+            http::StatusCode::OK
+        } else {
+            http::StatusCode::NOT_FOUND
+        };
+        let response = http::Response::builder().status(status).body(()).unwrap();
+        if status != http::StatusCode::OK {
+            respond.send_response(response, true).unwrap();
+            return;
+        }
+
+        let mut body = request.into_body();
+        let mut query = vec![];
+        while let Some(data) = body.data().await {
+            let data = data.unwrap();
+            let _ = body.flow_control().release_capacity(data.len());
+            query.extend_from_slice(&data);
+        }
+        let query = Message::from_vec(&query).unwrap();
+
+        let mut record = Record::with(query.queries()[0].name().clone(), RecordType::A, 60);
+        record.set_data(Some(RData::A(rdata::A(UPSTREAM_ANSWER))));
+        let mut answer = Message::new();
+        answer
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .add_queries(query.queries().to_vec())
+            .add_answer(record);
+
+        let mut answer_body = respond.send_response(response, false).unwrap();
+        answer_body
+            .send_data(answer.to_vec().unwrap().into(), true)
+            .unwrap();
+    }
+
+    async fn start_plaintext_upstream(
+        answer: Ipv4Addr,
+    ) -> (ServerFuture<StandInUpstream>, SocketAddr) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut server = ServerFuture::new(StandInUpstream(answer));
+        server.register_socket(socket);
+        (server, addr)
+    }
+
+    /// Send a plaintext query for the A records of `name` to the stub resolver.
+    async fn query_stub(stub: &StubResolver, name: &str) -> Result<Vec<IpAddr>, ResolveError> {
+        let servers = NameServerConfigGroup::from_ips_clear(
+            &[stub.listen_addr().ip()],
+            stub.listen_addr().port(),
+            true,
+        );
+        let mut opts = ResolverOpts::default();
+        opts.attempts = 0;
+        let client =
+            TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, vec![], servers), opts);
+
+        client
+            .ipv4_lookup(name)
+            .await
+            .map(|lookup| lookup.iter().map(|a| IpAddr::V4(a.0)).collect())
+    }
+
+    async fn start_stub(protocol: EncryptedDnsProtocol, spki_pin: &str) -> StubResolver {
+        let encryption = DnsEncryption {
+            protocol,
+            spki_pin: Some(spki_pin.parse().unwrap()),
+        };
+        StubResolver::start(
+            (Ipv4Addr::LOCALHOST, 0).into(),
+            &[Ipv4Addr::LOCALHOST.into()],
+            &encryption,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_spki_pin_of_certificate() {
+        let spki = subject_public_key_info(CERT).unwrap();
+        let digest = ring::digest::digest(&ring::digest::SHA256, spki.as_ref());
+        let pin: SpkiPin = CERT_SPKI_PIN.parse().unwrap();
+        assert_eq!(digest.as_ref(), pin.as_bytes());
+
+        assert!(subject_public_key_info(&CERT[..CERT.len() / 2]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forward_over_https() {
+        let (_upstream, port) = start_upstream(Protocol::Https).await;
+        let protocol = EncryptedDnsProtocol::Https {
+            hostname: "localhost".to_owned(),
+            port,
+            path: DOH_PATH.to_owned(),
+        };
+        let stub = start_stub(protocol, CERT_SPKI_PIN).await;
+
+        assert_eq!(
+            query_stub(&stub, "example.com.").await.unwrap(),
+            vec![UPSTREAM_ANSWER]
+        );
+        stub.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_forward_over_tls() {
+        let (_upstream, port) = start_upstream(Protocol::Tls).await;
+        let protocol = EncryptedDnsProtocol::Tls {
+            hostname: "localhost".to_owned(),
+            port,
+        };
+        let stub = start_stub(protocol, CERT_SPKI_PIN).await;
+
+        assert_eq!(
+            query_stub(&stub, "example.com.").await.unwrap(),
+            vec![UPSTREAM_ANSWER]
+        );
+        stub.stop().await;
+    }
+
+    /// DNS-over-HTTPS queries must be sent to the path that the server was configured with.
+    #[tokio::test]
+    async fn test_forward_over_https_to_path() {
+        let port = start_doh_upstream("/resolve?ct=dns").await;
+
+        for (path, answered) in [("/resolve?ct=dns", true), (DOH_PATH, false)] {
+            let protocol = EncryptedDnsProtocol::Https {
+                hostname: "localhost".to_owned(),
+                port,
+                path: path.to_owned(),
+            };
+            let stub = start_stub(protocol, CERT_SPKI_PIN).await;
+
+            let answer = query_stub(&stub, "example.com.").await;
+            if answered {
+                assert_eq!(answer.unwrap(), vec![UPSTREAM_ANSWER], "{path}");
+            } else {
+                assert!(answer.is_err(), "{path}");
+            }
+            stub.stop().await;
+        }
+    }
+
+    /// The stub must not answer if the upstream certificate does not match the pin.
+    #[tokio::test]
+    async fn test_reject_unpinned_certificate() {
+        let (_upstream, port) = start_upstream(Protocol::Https).await;
+        let protocol = EncryptedDnsProtocol::Https {
+            hostname: "localhost".to_owned(),
+            port,
+            path: DOH_PATH.to_owned(),
+        };
+        let wrong_pin = SpkiPin::from([0u8; 32]).to_base64();
+        let stub = start_stub(protocol, &wrong_pin).await;
+
+        assert!(query_stub(&stub, "example.com.").await.is_err());
+        stub.stop().await;
+    }
+
+    /// Queries for the domain of a split DNS rule, and its subdomains, must go to the resolvers of
+    /// that rule. Everything else goes to the default upstream.
+    #[tokio::test]
+    async fn test_split_dns() {
+        let (_default_upstream, default_addr) = start_plaintext_upstream(UPSTREAM_ANSWER).await;
+        let (_split_upstream, split_addr) = start_plaintext_upstream(SPLIT_UPSTREAM_ANSWER).await;
+        let rule = SplitDnsRule::new("corp.example", vec![split_addr.ip()]).unwrap();
+        let upstream = Upstream {
+            default: plaintext_resolver(&[default_addr]),
+            split: vec![(rule, plaintext_resolver(&[split_addr]))],
+        };
+        let stub = StubResolver::serve((Ipv4Addr::LOCALHOST, 0).into(), upstream)
+            .await
+            .unwrap();
+
+        for (name, answer) in [
+            ("corp.example.", SPLIT_UPSTREAM_ANSWER),
+            ("host.CORP.example.", SPLIT_UPSTREAM_ANSWER),
+            ("notcorp.example.", UPSTREAM_ANSWER),
+            ("example.com.", UPSTREAM_ANSWER),
+        ] {
+            assert_eq!(
+                query_stub(&stub, name).await.unwrap(),
+                vec![answer],
+                "{name}"
+            );
+        }
+        stub.stop().await;
+    }
+}
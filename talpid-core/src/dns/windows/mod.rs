@@ -75,6 +75,10 @@ impl DnsMonitorT for DnsMonitor {
         }
         Ok(())
     }
+
+    fn backend(&self) -> Option<String> {
+        Some(self.inner.to_string())
+    }
 }
 
 enum DnsMonitorHolder {
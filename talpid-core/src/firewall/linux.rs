@@ -9,12 +9,16 @@ use std::{
     env,
     ffi::CStr,
     fs, io,
-    net::{IpAddr, Ipv4Addr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     sync::LazyLock,
 };
-use talpid_types::net::{
-    AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol, ALLOWED_LAN_MULTICAST_NETS,
-    ALLOWED_LAN_NETS,
+use talpid_types::{
+    net::{
+        firewall::FirewallException, AllowedEndpoint, AllowedTunnelTraffic, Endpoint,
+        TransportProtocol, ALLOWED_LAN_MULTICAST_NETS, ALLOWED_LAN_NETS,
+    },
+    split_tunnel::SplitTunnelMode,
+    ErrorExt,
 };
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
@@ -83,6 +87,17 @@ static DONT_SET_ARP_IGNORE: LazyLock<bool> = LazyLock::new(|| {
         .unwrap_or(false)
 });
 
+static SUPPORTS_SOCKET_CGROUPV2: LazyLock<bool> = LazyLock::new(|| {
+    let result = probe_socket_cgroupv2();
+    if let Err(error) = &result {
+        log::debug!(
+            "{}",
+            error.display_chain_with_msg("nftables does not support socket cgroupv2")
+        );
+    }
+    result.is_ok()
+});
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum Direction {
     In,
@@ -98,20 +113,31 @@ enum End {
 /// The Linux implementation for the firewall and DNS.
 pub struct Firewall {
     fwmark: u32,
+    split_tunnel_mode: SplitTunnelMode,
 }
 
 impl Firewall {
     pub fn from_args(args: FirewallArguments) -> Result<Self> {
-        Firewall::new(args.fwmark)
+        let mut firewall = Firewall::new(args.fwmark)?;
+        firewall.split_tunnel_mode = args.split_tunnel_mode;
+        Ok(firewall)
     }
 
     pub fn new(fwmark: u32) -> Result<Self> {
-        Ok(Firewall { fwmark })
+        Ok(Firewall {
+            fwmark,
+            split_tunnel_mode: SplitTunnelMode::default(),
+        })
+    }
+
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) {
+        self.split_tunnel_mode = mode;
     }
 
     pub fn apply_policy(&mut self, policy: FirewallPolicy) -> Result<()> {
         let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
-        let batch = PolicyBatch::new(&table).finalize(&policy, self.fwmark)?;
+        let batch =
+            PolicyBatch::new(&table).finalize(&policy, self.fwmark, self.split_tunnel_mode)?;
         Self::send_and_process(&batch)?;
         Self::apply_kernel_config(&policy);
         self.verify_tables(&[TABLE_NAME])
@@ -301,9 +327,15 @@ impl<'a> PolicyBatch<'a> {
 
     /// Finalize the nftnl message batch by adding every firewall rule needed to satisfy the given
     /// policy.
-    pub fn finalize(mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<FinalizedBatch> {
+    pub fn finalize(
+        mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<FinalizedBatch> {
         self.add_loopback_rules()?;
-        self.add_split_tunneling_rules(policy, fwmark)?;
+        self.add_split_tunneling_rules(policy, fwmark, split_tunnel_mode)?;
+        self.add_exception_rules(policy, fwmark)?;
         self.add_dhcp_client_rules();
         self.add_ndp_rules();
         self.add_policy_specific_rules(policy, fwmark)?;
@@ -311,48 +343,79 @@ impl<'a> PolicyBatch<'a> {
         Ok(self.batch.finalize())
     }
 
-    fn add_split_tunneling_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
+    fn add_split_tunneling_rules(
+        &mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<()> {
         // Send select DNS requests in the tunnel
         if let FirewallPolicy::Connected {
             tunnel, dns_config, ..
         } = policy
         {
-            for server in dns_config.tunnel_config() {
-                let allow_rule = allow_tunnel_dns_rule(
-                    &self.mangle_chain,
-                    &tunnel.interface,
-                    TransportProtocol::Udp,
-                    *server,
-                )?;
-                self.batch.add(&allow_rule, nftnl::MsgType::Add);
-                let allow_rule = allow_tunnel_dns_rule(
-                    &self.mangle_chain,
-                    &tunnel.interface,
-                    TransportProtocol::Tcp,
-                    *server,
-                )?;
-                self.batch.add(&allow_rule, nftnl::MsgType::Add);
+            let tunnel_servers = dns_config
+                .tunnel_config()
+                .iter()
+                .copied()
+                .chain(dns_config.tunnel_split_resolvers());
+            for server in tunnel_servers {
+                for protocol in [TransportProtocol::Udp, TransportProtocol::Tcp] {
+                    let allow_rule = match split_tunnel_mode {
+                        SplitTunnelMode::Exclude => allow_tunnel_dns_rule(
+                            &self.mangle_chain,
+                            &tunnel.interface,
+                            protocol,
+                            server,
+                        )?,
+                        // Only marked packets are routed through the tunnel in include mode
+                        SplitTunnelMode::Include => {
+                            include_tunnel_dns_rule(&self.mangle_chain, protocol, server)
+                        }
+                    };
+                    self.batch.add(&allow_rule, nftnl::MsgType::Add);
+                }
             }
         }
 
-        // Split tunneled processes have their PIDs added to a net_cls cgroup.
-        // This causes all packets sent by that process to be marked with the
-        // cgroups classid (`NET_CLS_CLASSID`). This rule checks incoming packets for that classid.
-        // If the packet has the classid set then the packet will have two new marks applied to it.
-        // The `split_tunnel::MARK` as a connection tracking mark and the `fwmark` as packet
-        // metadata.
-        let mut rule = Rule::new(&self.mangle_chain);
-        rule.add_expr(&nft_expr!(meta cgroup));
-        rule.add_expr(&nft_expr!(cmp == split_tunnel::NET_CLS_CLASSID));
-        // Loads `split_tunnel::MARK` into first nftnl register
-        rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
-        // Sets `split_tunnel::MARK` as connection tracker mark
-        rule.add_expr(&nft_expr!(ct mark set));
-        // Loads `fwmark` into first nftnl register
-        rule.add_expr(&nft_expr!(immediate data fwmark));
-        // Sets `fwmark` as metadata mark for packet
-        rule.add_expr(&nft_expr!(meta mark set));
-        self.batch.add(&rule, nftnl::MsgType::Add);
+        // Packets that should bypass the tunnel get two new marks applied to them. The
+        // `split_tunnel::MARK` as a connection tracking mark and the `fwmark` as packet metadata.
+        // In exclude mode, these are the packets sent by split tunneled processes. In include
+        // mode, it is every packet that was *not* sent by one. The packets that were are marked
+        // with `split_tunnel::INCLUDE_FWMARK` instead, since only those are routed through the
+        // tunnel.
+        for &(from_cgroup, marks) in split_tunnel_marks(split_tunnel_mode) {
+            let cmp_op = if from_cgroup {
+                expr::CmpOp::Eq
+            } else {
+                expr::CmpOp::Neq
+            };
+            let mut rule = Rule::new(&self.mangle_chain);
+            if let Some(cgroup_id) = split_tunnel::exclusion_cgroup_v2_id() {
+                // On cgroup v2 hosts, there is no class ID. Instead, packets are matched by the
+                // cgroup of the socket they were sent from.
+                let cgroup_id = cgroup_id.to_ne_bytes();
+                rule.add_expr(&SocketCgroupV2 {
+                    level: EXCLUSION_CGROUP_LEVEL,
+                });
+                rule.add_expr(&expr::Cmp::new(cmp_op, &cgroup_id[..]));
+            } else {
+                // Split tunneled processes have their PIDs added to a net_cls cgroup.
+                // This causes all packets sent by that process to be marked with the
+                // cgroups classid (`NET_CLS_CLASSID`). This rule checks outgoing packets for that
+                // classid.
+                rule.add_expr(&nft_expr!(meta cgroup));
+                rule.add_expr(&expr::Cmp::new(cmp_op, split_tunnel::NET_CLS_CLASSID));
+            }
+            match marks {
+                SplitTunnelMarks::Bypass => add_split_tunnel_marks(&mut rule, fwmark),
+                SplitTunnelMarks::Include => {
+                    rule.add_expr(&nft_expr!(immediate data split_tunnel::INCLUDE_FWMARK));
+                    rule.add_expr(&nft_expr!(meta mark set));
+                }
+            }
+            self.batch.add(&rule, nftnl::MsgType::Add);
+        }
 
         for chain in &[&self.in_chain, &self.out_chain, &self.forward_chain] {
             let mut rule = Rule::new(chain);
@@ -372,23 +435,54 @@ impl<'a> PolicyBatch<'a> {
             self.batch.add(&block_tunnel_rule, nftnl::MsgType::Add);
         }
 
-        // Fix source IP address in rerouted packets using masquerade.
-        // Don't masquerade packets on the loopback device.
-        let mut rule = Rule::new(&self.nat_chain);
+        match split_tunnel_mode {
+            SplitTunnelMode::Exclude => {
+                // Fix source IP address in rerouted packets using masquerade.
+                // Don't masquerade packets on the loopback device.
+                let mut rule = Rule::new(&self.nat_chain);
 
-        let iface_index = crate::linux::iface_index("lo")
-            .map_err(|e| Error::LookupIfaceIndexError("lo".to_string(), e))?;
-        rule.add_expr(&nft_expr!(meta oif));
-        rule.add_expr(&nft_expr!(cmp != iface_index));
+                let iface_index = crate::linux::iface_index("lo")
+                    .map_err(|e| Error::LookupIfaceIndexError("lo".to_string(), e))?;
+                rule.add_expr(&nft_expr!(meta oif));
+                rule.add_expr(&nft_expr!(cmp != iface_index));
 
-        rule.add_expr(&nft_expr!(ct mark));
-        rule.add_expr(&nft_expr!(cmp == split_tunnel::MARK));
+                rule.add_expr(&nft_expr!(ct mark));
+                rule.add_expr(&nft_expr!(cmp == split_tunnel::MARK));
 
-        rule.add_expr(&nft_expr!(masquerade));
-        if *ADD_COUNTERS {
-            rule.add_expr(&nft_expr!(counter));
+                rule.add_expr(&nft_expr!(masquerade));
+                if *ADD_COUNTERS {
+                    rule.add_expr(&nft_expr!(counter));
+                }
+                self.batch.add(&rule, nftnl::MsgType::Add);
+            }
+            SplitTunnelMode::Include => {
+                if let FirewallPolicy::Connected { tunnel, .. } = policy {
+                    // Included packets are rerouted into the tunnel after their source address
+                    // was picked from the main routing table, so fix it using masquerade.
+                    let mut rule = Rule::new(&self.nat_chain);
+                    check_iface(&mut rule, Direction::Out, &tunnel.interface)?;
+                    rule.add_expr(&nft_expr!(meta mark));
+                    rule.add_expr(&nft_expr!(cmp == split_tunnel::INCLUDE_FWMARK));
+                    rule.add_expr(&nft_expr!(masquerade));
+                    if *ADD_COUNTERS {
+                        rule.add_expr(&nft_expr!(counter));
+                    }
+                    self.batch.add(&rule, nftnl::MsgType::Add);
+
+                    // Look up the reverse path of incoming tunnel traffic in the tunnel routing
+                    // table, to prevent strict rpf from rejecting packets for included processes
+                    let mut prerouting_rule = Rule::new(&self.prerouting_chain);
+                    check_iface(&mut prerouting_rule, Direction::In, &tunnel.interface)?;
+                    prerouting_rule
+                        .add_expr(&nft_expr!(immediate data split_tunnel::INCLUDE_FWMARK));
+                    prerouting_rule.add_expr(&nft_expr!(meta mark set));
+                    if *ADD_COUNTERS {
+                        prerouting_rule.add_expr(&nft_expr!(counter));
+                    }
+                    self.batch.add(&prerouting_rule, nftnl::MsgType::Add);
+                }
+            }
         }
-        self.batch.add(&rule, nftnl::MsgType::Add);
 
         // Route incoming traffic correctly to prevent strict rpf from rejecting packets
         // for excluded processes
@@ -408,6 +502,44 @@ impl<'a> PolicyBatch<'a> {
         Ok(())
     }
 
+    /// Allow the user-defined exceptions. Outbound traffic is marked the same way as excluded
+    /// traffic, so it is routed outside the tunnel and accepted by the split tunneling rules in
+    /// every tunnel state.
+    fn add_exception_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
+        for exception in policy.exceptions() {
+            match *exception {
+                FirewallException::Outbound {
+                    network,
+                    port,
+                    protocol,
+                } => {
+                    // `check_net` compares against the network address, so clear any host bits
+                    let network =
+                        IpNetwork::new(network.network(), network.prefix()).unwrap_or(network);
+                    let mut rule = Rule::new(&self.mangle_chain);
+                    check_net(&mut rule, End::Dst, network);
+                    match port {
+                        Some(port) => check_port(&mut rule, protocol, End::Dst, port),
+                        None => check_l4proto(&mut rule, protocol),
+                    }
+                    add_split_tunnel_marks(&mut rule, fwmark);
+                    self.batch.add(&rule, nftnl::MsgType::Add);
+                }
+                FirewallException::Inbound { port, protocol } => {
+                    let Some(tunnel) = policy.tunnel() else {
+                        continue;
+                    };
+                    let mut rule = Rule::new(&self.in_chain);
+                    check_iface(&mut rule, Direction::In, &tunnel.interface)?;
+                    check_port(&mut rule, protocol, End::Dst, port);
+                    add_verdict(&mut rule, &Verdict::Accept);
+                    self.batch.add(&rule, nftnl::MsgType::Add);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn add_loopback_rules(&mut self) -> Result<()> {
         const LOOPBACK_IFACE_NAME: &str = "lo";
         self.batch.add(
@@ -543,8 +675,13 @@ impl<'a> PolicyBatch<'a> {
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                peer_udp_relay,
+                ..
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
+                if let Some(relay) = peer_udp_relay {
+                    self.add_allow_udp_relay_rules(*relay, fwmark);
+                }
                 self.add_allow_endpoint_rules(allowed_endpoint);
 
                 // Important to block DNS after allow relay rule (so the relay can operate
@@ -564,6 +701,11 @@ impl<'a> PolicyBatch<'a> {
                             self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint1)?;
                             self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint2)?;
                         }
+                        AllowedTunnelTraffic::Three(endpoint1, endpoint2, endpoint3) => {
+                            self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint1)?;
+                            self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint2)?;
+                            self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint3)?;
+                        }
                     }
                     if *allow_lan {
                         self.add_block_cve_2019_14899(tunnel);
@@ -576,31 +718,46 @@ impl<'a> PolicyBatch<'a> {
                 tunnel,
                 allow_lan,
                 dns_config,
+                peer_udp_relay,
+                ..
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
+                if let Some(relay) = peer_udp_relay {
+                    self.add_allow_udp_relay_rules(*relay, fwmark);
+                }
 
-                for server in dns_config.tunnel_config() {
+                let tunnel_servers = dns_config
+                    .tunnel_config()
+                    .iter()
+                    .copied()
+                    .chain(dns_config.tunnel_split_resolvers());
+                for server in tunnel_servers {
                     self.add_allow_tunnel_dns_rule(
                         &tunnel.interface,
                         TransportProtocol::Udp,
-                        *server,
+                        server,
                     )?;
                     self.add_allow_tunnel_dns_rule(
                         &tunnel.interface,
                         TransportProtocol::Tcp,
-                        *server,
+                        server,
                     )?;
                 }
-                for server in dns_config.non_tunnel_config() {
+                let non_tunnel_servers = dns_config
+                    .non_tunnel_config()
+                    .iter()
+                    .copied()
+                    .chain(policy.non_tunnel_split_dns_resolvers());
+                for server in non_tunnel_servers {
                     self.add_allow_local_dns_rule(
                         &tunnel.interface,
                         TransportProtocol::Udp,
-                        *server,
+                        server,
                     )?;
                     self.add_allow_local_dns_rule(
                         &tunnel.interface,
                         TransportProtocol::Tcp,
-                        *server,
+                        server,
                     )?;
                 }
 
@@ -616,6 +773,7 @@ impl<'a> PolicyBatch<'a> {
             FirewallPolicy::Blocked {
                 allow_lan,
                 allowed_endpoint,
+                ..
             } => {
                 if let Some(endpoint) = allowed_endpoint {
                     self.add_allow_endpoint_rules(endpoint);
@@ -694,6 +852,35 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
+    /// Allows UDP traffic marked with fwmark to the address that the obfuscator relays UDP
+    /// traffic through. The address is assigned by the proxy when the tunnel is being set up.
+    fn add_allow_udp_relay_rules(&mut self, relay: SocketAddr, fwmark: u32) {
+        let relay = Endpoint::from_socket_address(relay, TransportProtocol::Udp);
+
+        let mut prerouting_rule = Rule::new(&self.prerouting_chain);
+        // Mark incoming traffic from the relay with fwmark
+        check_endpoint(&mut prerouting_rule, End::Src, &relay);
+        prerouting_rule.add_expr(&nft_expr!(immediate data fwmark));
+        prerouting_rule.add_expr(&nft_expr!(meta mark set));
+        self.batch.add(&prerouting_rule, nftnl::MsgType::Add);
+
+        let mut in_rule = Rule::new(&self.in_chain);
+        check_endpoint(&mut in_rule, End::Src, &relay);
+        let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+        in_rule.add_expr(&nft_expr!(ct state));
+        in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+        in_rule.add_expr(&nft_expr!(cmp != 0u32));
+        add_verdict(&mut in_rule, &Verdict::Accept);
+        self.batch.add(&in_rule, nftnl::MsgType::Add);
+
+        let mut out_rule = Rule::new(&self.out_chain);
+        check_endpoint(&mut out_rule, End::Dst, &relay);
+        out_rule.add_expr(&nft_expr!(meta mark));
+        out_rule.add_expr(&nft_expr!(cmp == fwmark));
+        add_verdict(&mut out_rule, &Verdict::Accept);
+        self.batch.add(&out_rule, nftnl::MsgType::Add);
+    }
+
     /// Adds firewall rules allow traffic to flow to the API. Allows the app to reach the API in
     /// blocked states.
     fn add_allow_endpoint_rules(&mut self, endpoint: &AllowedEndpoint) {
@@ -938,6 +1125,33 @@ fn allow_tunnel_dns_rule<'a>(
     Ok(rule)
 }
 
+/// Mark DNS requests to `host` so that they are routed through the tunnel in include mode, even
+/// if they are not sent by an included process.
+fn include_tunnel_dns_rule<'a>(
+    chain: &'a Chain<'_>,
+    protocol: TransportProtocol,
+    host: IpAddr,
+) -> Rule<'a> {
+    let mut rule = Rule::new(chain);
+    check_port(&mut rule, protocol, End::Dst, 53);
+
+    let daddr = match host {
+        IpAddr::V4(_) => nft_expr!(payload ipv4 daddr),
+        IpAddr::V6(_) => nft_expr!(payload ipv6 daddr),
+    };
+    if chain.get_table().get_family() == ProtoFamily::Inet {
+        check_l3proto(&mut rule, host);
+    }
+
+    rule.add_expr(&daddr);
+    rule.add_expr(&nft_expr!(cmp == host));
+    rule.add_expr(&nft_expr!(immediate data split_tunnel::INCLUDE_FWMARK));
+    rule.add_expr(&nft_expr!(meta mark set));
+    add_verdict(&mut rule, &Verdict::Accept);
+
+    rule
+}
+
 fn allow_interface_rule<'a>(
     chain: &'a Chain<'_>,
     direction: Direction,
@@ -991,6 +1205,104 @@ fn check_net(rule: &mut Rule<'_>, end: End, net: impl Into<IpNetwork>) {
     rule.add_expr(&nft_expr!(cmp == net.ip()));
 }
 
+/// Mark packets from excluded processes so that they are routed outside the tunnel.
+fn add_split_tunnel_marks(rule: &mut Rule<'_>, fwmark: u32) {
+    // Loads `split_tunnel::MARK` into first nftnl register
+    rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
+    // Sets `split_tunnel::MARK` as connection tracker mark
+    rule.add_expr(&nft_expr!(ct mark set));
+    // Loads `fwmark` into first nftnl register
+    rule.add_expr(&nft_expr!(immediate data fwmark));
+    // Sets `fwmark` as metadata mark for packet
+    rule.add_expr(&nft_expr!(meta mark set));
+}
+
+/// How the split tunneling rules mark the packets that they match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SplitTunnelMarks {
+    /// Route the packets outside the tunnel, and accept them in every tunnel state.
+    Bypass,
+    /// Route the packets through the tunnel.
+    Include,
+}
+
+/// Return how the packets that were sent (`true`) or not sent (`false`) from the split tunneling
+/// cgroup should be marked in `mode`. Packets that aren't listed are left alone.
+fn split_tunnel_marks(mode: SplitTunnelMode) -> &'static [(bool, SplitTunnelMarks)] {
+    match mode {
+        SplitTunnelMode::Exclude => &[(true, SplitTunnelMarks::Bypass)],
+        SplitTunnelMode::Include => &[
+            (true, SplitTunnelMarks::Include),
+            (false, SplitTunnelMarks::Bypass),
+        ],
+    }
+}
+
+/// Depth of the split tunneling cgroup in the cgroup v2 hierarchy. It is a child of the root.
+const EXCLUSION_CGROUP_LEVEL: u32 = 1;
+
+/// Return whether the kernel can match packets by the cgroup v2 cgroup of their socket. This is
+/// only probed once, since it depends on nothing but the kernel.
+pub fn supports_socket_cgroupv2() -> bool {
+    *SUPPORTS_SOCKET_CGROUPV2
+}
+
+/// Add a rule using [`SocketCgroupV2`] to a temporary table, which is deleted again by the same
+/// batch. The kernel rejects the whole batch if it doesn't support the expression, or doesn't
+/// support it in the same kind of chain as the split tunneling rules.
+fn probe_socket_cgroupv2() -> Result<()> {
+    const PROBE_TABLE_NAME: &CStr = c"mullvadprobe";
+
+    let table = Table::new(&PROBE_TABLE_NAME, ProtoFamily::Inet);
+    let mut chain = Chain::new(&MANGLE_CHAIN_NAME, &table);
+    chain.set_hook(nftnl::Hook::Out, MANGLE_CHAIN_PRIORITY);
+    chain.set_type(nftnl::ChainType::Route);
+    chain.set_policy(nftnl::Policy::Accept);
+    let mut rule = Rule::new(&chain);
+    rule.add_expr(&SocketCgroupV2 {
+        level: EXCLUSION_CGROUP_LEVEL,
+    });
+    rule.add_expr(&expr::Cmp::new(expr::CmpOp::Eq, &0u64.to_ne_bytes()[..]));
+
+    let mut batch = Batch::new();
+    // Clear any table that was left behind, the same way as in `Firewall::reset_policy`
+    batch.add(&table, nftnl::MsgType::Add);
+    batch.add(&table, nftnl::MsgType::Del);
+    batch.add(&table, nftnl::MsgType::Add);
+    batch.add(&chain, nftnl::MsgType::Add);
+    batch.add(&rule, nftnl::MsgType::Add);
+    batch.add(&table, nftnl::MsgType::Del);
+    Firewall::send_and_process(&batch.finalize())
+}
+
+/// The `socket cgroupv2 level <level>` expression, which loads the ID of the cgroup v2 ancestor
+/// at `level` of the cgroup that owns the socket of a packet into the first register. This
+/// requires Linux 5.13 or later.
+struct SocketCgroupV2 {
+    level: u32,
+}
+
+impl nftnl::expr::Expression for SocketCgroupV2 {
+    fn to_expr(&self, _rule: &Rule<'_>) -> *mut nftnl::nftnl_sys::nftnl_expr {
+        use nftnl::nftnl_sys as sys;
+
+        // From `enum nft_socket_keys` and `enum nft_registers` in linux/netfilter/nf_tables.h
+        const NFT_SOCKET_CGROUPV2: u32 = 3;
+        const NFT_REG_1: u32 = 1;
+
+        // SAFETY: The expression is allocated by libnftnl and its ownership is passed to the
+        // caller, which adds it to the rule.
+        unsafe {
+            let expr = sys::nftnl_expr_alloc(c"socket".as_ptr());
+            assert!(!expr.is_null(), "Failed to allocate socket expression");
+            sys::nftnl_expr_set_u32(expr, sys::NFTNL_EXPR_SOCKET_KEY as u16, NFT_SOCKET_CGROUPV2);
+            sys::nftnl_expr_set_u32(expr, sys::NFTNL_EXPR_SOCKET_DREG as u16, NFT_REG_1);
+            sys::nftnl_expr_set_u32(expr, sys::NFTNL_EXPR_SOCKET_LEVEL as u16, self.level);
+            expr
+        }
+    }
+}
+
 fn check_icmpv6(rule: &mut Rule<'_>, r#type: u8, code: u8) {
     rule.add_expr(&nft_expr!(meta l4proto));
     rule.add_expr(&nft_expr!(cmp == libc::IPPROTO_ICMPV6 as u8));
@@ -1105,3 +1417,24 @@ fn batch_deprecated_tables(batch: &mut Batch) {
         batch.add(table, nftnl::MsgType::Del);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_tunnel_marks() {
+        assert_eq!(
+            split_tunnel_marks(SplitTunnelMode::Exclude),
+            [(true, SplitTunnelMarks::Bypass)]
+        );
+        assert_eq!(
+            split_tunnel_marks(SplitTunnelMode::Include),
+            [
+                (true, SplitTunnelMarks::Include),
+                (false, SplitTunnelMarks::Bypass)
+            ],
+            "only traffic from the cgroup should be routed through the tunnel in include mode"
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::env;
 use std::io;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::ptr;
 use std::sync::LazyLock;
 
@@ -168,11 +168,19 @@ impl Firewall {
                 AllowedTunnelTraffic::Two(endpoint1, endpoint2) => {
                     endpoint1.address != remote_address && endpoint2.address != remote_address
                 }
+                AllowedTunnelTraffic::Three(endpoint1, endpoint2, endpoint3) => {
+                    endpoint1.address != remote_address
+                        && endpoint2.address != remote_address
+                        && endpoint3.address != remote_address
+                }
             }
         } else {
             // Non-tunnel traffic: Clear all states except traffic destined for the VPN endpoint
             // Ephemeral peer exchange becomes unreliable otherwise
-            peer.address != remote_address || as_pfctl_proto(peer.protocol) != proto
+            let is_udp_relay =
+                policy.peer_udp_relay() == Some(remote_address) && proto == pfctl::Proto::Udp;
+            !is_udp_relay
+                && (peer.address != remote_address || as_pfctl_proto(peer.protocol) != proto)
         };
 
         Ok(should_delete)
@@ -333,6 +341,15 @@ impl Firewall {
             .build()?;
         rules.push(no_nat_to_vpn_server);
 
+        // no nat to [udp relay]
+        if let Some(relay) = policy.peer_udp_relay() {
+            let no_nat_to_udp_relay = pfctl::NatRuleBuilder::default()
+                .action(pfctl::NatRuleAction::NoNat)
+                .to(relay)
+                .build()?;
+            rules.push(no_nat_to_udp_relay);
+        }
+
         // no nat on [tun interface]
         let no_nat_on_tun = pfctl::NatRuleBuilder::default()
             .action(pfctl::NatRuleAction::NoNat)
@@ -371,8 +388,12 @@ impl Firewall {
                 allowed_tunnel_traffic,
                 redirect_interface,
                 dns_redirect_port: _,
+                peer_udp_relay,
             } => {
                 let mut rules = vec![self.get_allow_relay_rule(peer_endpoint)?];
+                if let Some(relay) = peer_udp_relay {
+                    rules.push(self.get_allow_udp_relay_rule(*relay, peer_endpoint)?);
+                }
                 rules.push(self.get_allowed_endpoint_rule(allowed_endpoint)?);
 
                 // Important to block DNS after allow relay rule (so the relay can operate
@@ -416,6 +437,7 @@ impl Firewall {
                 dns_config,
                 redirect_interface,
                 dns_redirect_port: _,
+                peer_udp_relay,
             } => {
                 let mut rules = vec![];
 
@@ -431,6 +453,9 @@ impl Firewall {
                 }
 
                 rules.push(self.get_allow_relay_rule(peer_endpoint)?);
+                if let Some(relay) = peer_udp_relay {
+                    rules.push(self.get_allow_udp_relay_rule(*relay, peer_endpoint)?);
+                }
 
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
@@ -596,6 +621,29 @@ impl Firewall {
         builder.build()
     }
 
+    /// Allow UDP traffic to the address that the obfuscator relays UDP traffic through, for the
+    /// same clients as `relay_endpoint`. The address is assigned by the proxy when the tunnel is
+    /// being set up.
+    fn get_allow_udp_relay_rule(
+        &self,
+        relay: SocketAddr,
+        relay_endpoint: &AllowedEndpoint,
+    ) -> Result<pfctl::FilterRule> {
+        let mut builder = self.create_rule_builder(FilterRuleAction::Pass);
+        builder
+            .direction(pfctl::Direction::Out)
+            .to(relay)
+            .proto(pfctl::Proto::Udp)
+            .keep_state(pfctl::StatePolicy::Keep)
+            .quick(true);
+
+        if !relay_endpoint.clients.allow_all() {
+            builder.user(Uid::from(super::ROOT_UID));
+        }
+
+        builder.build()
+    }
+
     /// Produces a rule that allows traffic to flow to the API. Allows the app (or other apps if
     /// configured) to reach the API in blocked states.
     fn get_allowed_endpoint_rule(
@@ -677,6 +725,15 @@ impl Firewall {
 
                 rules
             }
+            AllowedTunnelTraffic::Three(endpoint1, endpoint2, endpoint3) => {
+                let mut rules = Vec::with_capacity(3);
+                for endpoint in [endpoint1, endpoint2, endpoint3] {
+                    let pfctl_proto = as_pfctl_proto(endpoint.protocol);
+                    base_rule = base_rule.to(endpoint.address).proto(pfctl_proto);
+                    rules.push(base_rule.build()?);
+                }
+                rules
+            }
             AllowedTunnelTraffic::All => {
                 vec![base_rule.build()?]
             }
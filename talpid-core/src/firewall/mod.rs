@@ -6,6 +6,8 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::LazyLock,
 };
+#[cfg(target_os = "linux")]
+use talpid_types::net::firewall::FirewallException;
 use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, ALLOWED_LAN_NETS};
 
 #[cfg(target_os = "macos")]
@@ -24,6 +26,8 @@ mod imp;
 #[path = "android.rs"]
 mod imp;
 
+#[cfg(target_os = "linux")]
+pub use self::imp::supports_socket_cgroupv2;
 pub use self::imp::Error;
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -83,6 +87,11 @@ pub enum FirewallPolicy {
     Connecting {
         /// The peer endpoint that should be allowed.
         peer_endpoint: AllowedEndpoint,
+        /// Address that the obfuscator relays UDP traffic through, in addition to
+        /// `peer_endpoint`. UDP traffic to this address is allowed for the same clients as the
+        /// peer endpoint.
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        peer_udp_relay: Option<std::net::SocketAddr>,
         /// Metadata about the tunnel and tunnel interface.
         tunnel: Option<crate::tunnel::TunnelMetadata>,
         /// Flag setting if communication with LAN networks should be possible.
@@ -91,6 +100,9 @@ pub enum FirewallPolicy {
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+        /// User-defined traffic to allow.
+        #[cfg(target_os = "linux")]
+        exceptions: Vec<FirewallException>,
         /// Interface to redirect (VPN tunnel) traffic to
         #[cfg(target_os = "macos")]
         redirect_interface: Option<String>,
@@ -104,6 +116,11 @@ pub enum FirewallPolicy {
     Connected {
         /// The peer endpoint that should be allowed.
         peer_endpoint: AllowedEndpoint,
+        /// Address that the obfuscator relays UDP traffic through, in addition to
+        /// `peer_endpoint`. UDP traffic to this address is allowed for the same clients as the
+        /// peer endpoint.
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        peer_udp_relay: Option<std::net::SocketAddr>,
         /// Metadata about the tunnel and tunnel interface.
         tunnel: crate::tunnel::TunnelMetadata,
         /// Flag setting if communication with LAN networks should be possible.
@@ -111,6 +128,9 @@ pub enum FirewallPolicy {
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_config: ResolvedDnsConfig,
+        /// User-defined traffic to allow.
+        #[cfg(target_os = "linux")]
+        exceptions: Vec<FirewallException>,
         /// Interface to redirect (VPN tunnel) traffic to
         #[cfg(target_os = "macos")]
         redirect_interface: Option<String>,
@@ -126,6 +146,9 @@ pub enum FirewallPolicy {
         allow_lan: bool,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: Option<AllowedEndpoint>,
+        /// User-defined traffic to allow.
+        #[cfg(target_os = "linux")]
+        exceptions: Vec<FirewallException>,
         /// Destination port for DNS traffic redirection. Traffic destined to `127.0.0.1:53` will
         /// be redirected to `127.0.0.1:$dns_redirect_port`.
         #[cfg(target_os = "macos")]
@@ -143,6 +166,16 @@ impl FirewallPolicy {
         }
     }
 
+    /// Return the address that UDP traffic to the peer is relayed through, if any
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn peer_udp_relay(&self) -> Option<std::net::SocketAddr> {
+        match self {
+            FirewallPolicy::Connecting { peer_udp_relay, .. }
+            | FirewallPolicy::Connected { peer_udp_relay, .. } => *peer_udp_relay,
+            _ => None,
+        }
+    }
+
     /// Return the allowed endpoint, if available
     pub fn allowed_endpoint(&self) -> Option<&AllowedEndpoint> {
         match self {
@@ -189,6 +222,32 @@ impl FirewallPolicy {
             | FirewallPolicy::Blocked { allow_lan, .. } => *allow_lan,
         }
     }
+
+    /// Return the user-defined traffic to allow
+    #[cfg(target_os = "linux")]
+    pub fn exceptions(&self) -> &[FirewallException] {
+        match self {
+            FirewallPolicy::Connecting { exceptions, .. }
+            | FirewallPolicy::Connected { exceptions, .. }
+            | FirewallPolicy::Blocked { exceptions, .. } => exceptions,
+        }
+    }
+
+    /// Return the resolvers of split DNS rules that may be queried outside the tunnel. Queries
+    /// for the domains of those rules are visible on the local network, so this is only permitted
+    /// while connected and if LAN traffic is allowed. Otherwise, the queries are blocked rather
+    /// than sent through the tunnel to a resolver that is not expecting them.
+    #[cfg(target_os = "linux")]
+    pub fn non_tunnel_split_dns_resolvers(&self) -> Vec<IpAddr> {
+        match self {
+            FirewallPolicy::Connected {
+                allow_lan: true,
+                dns_config,
+                ..
+            } => dns_config.non_tunnel_split_resolvers().collect(),
+            _ => vec![],
+        }
+    }
 }
 
 impl fmt::Display for FirewallPolicy {
@@ -283,6 +342,10 @@ pub struct FirewallArguments {
     /// the tunnel and _leaked_ during blocked states.
     #[cfg(target_os = "linux")]
     pub fwmark: u32,
+    /// Whether the split tunneled processes are excluded from the tunnel, or are the only ones
+    /// that use it.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: talpid_types::split_tunnel::SplitTunnelMode,
 }
 
 /// State to enter during firewall init.
@@ -318,6 +381,13 @@ impl Firewall {
         self.inner.apply_policy(policy)
     }
 
+    /// Set whether the split tunneled processes are excluded from the tunnel, or are the only ones
+    /// that use it. This takes effect the next time a policy is applied.
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: talpid_types::split_tunnel::SplitTunnelMode) {
+        self.inner.set_split_tunnel_mode(mode);
+    }
+
     /// Resets/removes any currently enforced `FirewallPolicy`. Returns the system to the same state
     /// it had before any policy was applied through this `Firewall` instance.
     pub fn reset_policy(&mut self) -> Result<(), Error> {
@@ -239,7 +239,8 @@ impl Firewall {
 
         let mut endpoint1_ip = WideCString::new();
         let mut endpoint2_ip = WideCString::new();
-        let (endpoint1, endpoint2) = match allowed_tunnel_traffic {
+        let mut endpoint3_ip = WideCString::new();
+        let (endpoint1, endpoint2, endpoint3) = match allowed_tunnel_traffic {
             AllowedTunnelTraffic::One(endpoint) => {
                 endpoint1_ip = widestring_ip(endpoint.address.ip());
                 (
@@ -249,6 +250,7 @@ impl Firewall {
                         protocol: WinFwProt::from(endpoint.protocol),
                     }),
                     None,
+                    None,
                 )
             }
             AllowedTunnelTraffic::Two(endpoint1, endpoint2) => {
@@ -264,9 +266,30 @@ impl Firewall {
                     port: endpoint2.address.port(),
                     protocol: WinFwProt::from(endpoint2.protocol),
                 });
-                (endpoint1, endpoint2)
+                (endpoint1, endpoint2, None)
             }
-            AllowedTunnelTraffic::None | AllowedTunnelTraffic::All => (None, None),
+            AllowedTunnelTraffic::Three(endpoint1, endpoint2, endpoint3) => {
+                endpoint1_ip = widestring_ip(endpoint1.address.ip());
+                let endpoint1 = Some(WinFwEndpoint {
+                    ip: endpoint1_ip.as_ptr(),
+                    port: endpoint1.address.port(),
+                    protocol: WinFwProt::from(endpoint1.protocol),
+                });
+                endpoint2_ip = widestring_ip(endpoint2.address.ip());
+                let endpoint2 = Some(WinFwEndpoint {
+                    ip: endpoint2_ip.as_ptr(),
+                    port: endpoint2.address.port(),
+                    protocol: WinFwProt::from(endpoint2.protocol),
+                });
+                endpoint3_ip = widestring_ip(endpoint3.address.ip());
+                let endpoint3 = Some(WinFwEndpoint {
+                    ip: endpoint3_ip.as_ptr(),
+                    port: endpoint3.address.port(),
+                    protocol: WinFwProt::from(endpoint3.protocol),
+                });
+                (endpoint1, endpoint2, endpoint3)
+            }
+            AllowedTunnelTraffic::None | AllowedTunnelTraffic::All => (None, None, None),
         };
 
         let allowed_tunnel_traffic = WinFwAllowedTunnelTraffic {
@@ -279,6 +302,10 @@ impl Firewall {
                 .as_ref()
                 .map(|ep| ep as *const _)
                 .unwrap_or(ptr::null()),
+            endpoint3: endpoint3
+                .as_ref()
+                .map(|ep| ep as *const _)
+                .unwrap_or(ptr::null()),
         };
 
         let res = unsafe {
@@ -299,10 +326,13 @@ impl Firewall {
         // has returned.
         drop(endpoint1_ip);
         drop(endpoint2_ip);
+        drop(endpoint3_ip);
         #[allow(clippy::drop_non_drop)]
         drop(endpoint1);
         #[allow(clippy::drop_non_drop)]
         drop(endpoint2);
+        #[allow(clippy::drop_non_drop)]
+        drop(endpoint3);
         drop(relay_client_wstrs);
         res
     }
@@ -621,6 +651,7 @@ mod winfw {
         pub type_: WinFwAllowedTunnelTrafficType,
         pub endpoint1: *const WinFwEndpoint,
         pub endpoint2: *const WinFwEndpoint,
+        pub endpoint3: *const WinFwEndpoint,
     }
 
     #[repr(u8)]
@@ -630,6 +661,7 @@ mod winfw {
         All,
         One,
         Two,
+        Three,
     }
 
     impl From<&AllowedTunnelTraffic> for WinFwAllowedTunnelTrafficType {
@@ -639,6 +671,7 @@ mod winfw {
                 AllowedTunnelTraffic::All => WinFwAllowedTunnelTrafficType::All,
                 AllowedTunnelTraffic::One(..) => WinFwAllowedTunnelTrafficType::One,
                 AllowedTunnelTraffic::Two(..) => WinFwAllowedTunnelTrafficType::Two,
+                AllowedTunnelTraffic::Three(..) => WinFwAllowedTunnelTrafficType::Three,
             }
         }
     }
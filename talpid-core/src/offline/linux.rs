@@ -1,10 +1,15 @@
 use futures::{channel::mpsc::UnboundedSender, StreamExt};
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
     sync::Arc,
+    time::Duration,
 };
 use talpid_routing::RouteManagerHandle;
-use talpid_types::{net::Connectivity, ErrorExt};
+use talpid_types::{
+    net::{Connectivity, NetworkInfo},
+    ErrorExt,
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -26,6 +31,18 @@ const PUBLIC_INTERNET_ADDRESS_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(193, 138, 21
 const PUBLIC_INTERNET_ADDRESS_V6: IpAddr =
     IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6));
 
+/// The kernel's IPv4 neighbour table.
+const ARP_TABLE_PATH: &str = "/proc/net/arp";
+/// Directory where systemd-networkd stores DHCP leases, named by interface index.
+const NETWORKD_LEASE_DIR: &str = "/run/systemd/netif/leases";
+/// Directory where NetworkManager's internal DHCP client stores its leases.
+const NETWORK_MANAGER_LEASE_DIR: &str = "/var/lib/NetworkManager";
+
+/// How often to look for the gateway in the neighbour table while it is unresolved.
+const GATEWAY_MAC_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// How many times to look for the gateway in the neighbour table after a route change.
+const GATEWAY_MAC_MAX_RETRIES: u32 = 30;
+
 impl MonitorHandle {
     pub async fn connectivity(&self) -> Connectivity {
         check_connectivity(&self.route_manager, self.fwmark).await
@@ -34,10 +51,16 @@ impl MonitorHandle {
 
 pub async fn spawn_monitor(
     notify_tx: UnboundedSender<Connectivity>,
+    network_tx: UnboundedSender<NetworkInfo>,
     route_manager: RouteManagerHandle,
     fwmark: Option<u32>,
 ) -> Result<MonitorHandle> {
     let mut connectivity = check_connectivity(&route_manager, fwmark).await;
+    let (mut network, gateway) = network_info(&route_manager, fwmark).await;
+    let _ = network_tx.unbounded_send(network.clone());
+    // The neighbour entry of a new gateway usually appears some time after its route, so keep
+    // looking for it for a while
+    let mut mac_retries = gateway_mac_retries(&network, gateway);
 
     let mut listener = route_manager
         .change_listener()
@@ -53,16 +76,36 @@ pub async fn spawn_monitor(
     };
 
     tokio::spawn(async move {
-        while let Some(_event) = listener.next().await {
-            match sender.upgrade() {
-                Some(sender) => {
-                    let new_connectivity = check_connectivity(&route_manager, fwmark).await;
-                    if new_connectivity != connectivity {
-                        connectivity = new_connectivity;
-                        let _ = sender.unbounded_send(connectivity);
-                    }
+        loop {
+            let route_changed = tokio::select! {
+                event = listener.next() => match event {
+                    Some(_event) => true,
+                    None => return,
+                },
+                _ = tokio::time::sleep(GATEWAY_MAC_RETRY_INTERVAL), if mac_retries > 0 => {
+                    mac_retries -= 1;
+                    false
+                }
+            };
+            let Some(sender) = sender.upgrade() else {
+                return;
+            };
+            if route_changed {
+                let new_connectivity = check_connectivity(&route_manager, fwmark).await;
+                if new_connectivity != connectivity {
+                    connectivity = new_connectivity;
+                    let _ = sender.unbounded_send(connectivity);
                 }
-                None => return,
+            }
+            let (new_network, gateway) = network_info(&route_manager, fwmark).await;
+            if route_changed {
+                mac_retries = gateway_mac_retries(&new_network, gateway);
+            } else if new_network.gateway_mac.is_some() {
+                mac_retries = 0;
+            }
+            if new_network != network {
+                network = new_network;
+                let _ = network_tx.unbounded_send(network.clone());
             }
         }
     });
@@ -103,3 +146,165 @@ async fn check_connectivity(handle: &RouteManagerHandle, fwmark: Option<u32>) ->
         }
     }
 }
+
+/// Number of times to look for the gateway in the neighbour table of `network`.
+fn gateway_mac_retries(network: &NetworkInfo, gateway: Option<IpAddr>) -> u32 {
+    if gateway.is_some() && network.gateway_mac.is_none() {
+        GATEWAY_MAC_MAX_RETRIES
+    } else {
+        0
+    }
+}
+
+/// Identify the network that the non-tunnel default route goes through, along with the address
+/// of its gateway.
+async fn network_info(
+    handle: &RouteManagerHandle,
+    fwmark: Option<u32>,
+) -> (NetworkInfo, Option<IpAddr>) {
+    let route = match handle
+        .get_destination_route(PUBLIC_INTERNET_ADDRESS_V4, fwmark)
+        .await
+    {
+        Ok(Some(route)) => route,
+        Ok(None) => return (NetworkInfo::default(), None),
+        Err(err) => {
+            log::trace!(
+                "{}",
+                err.display_chain_with_msg("Failed to find default route for network info")
+            );
+            return (NetworkInfo::default(), None);
+        }
+    };
+
+    let node = route.get_node();
+    let Some(interface) = node.get_device().map(str::to_owned) else {
+        return (NetworkInfo::default(), None);
+    };
+    let gateway = node.get_address();
+    let gateway_mac = match gateway {
+        Some(gateway) => gateway_mac_address(gateway, &interface).await,
+        None => None,
+    };
+    let dhcp_domain = dhcp_domain(&interface).await;
+
+    let network = NetworkInfo {
+        interface: Some(interface),
+        gateway_mac,
+        dhcp_domain,
+    };
+    (network, gateway)
+}
+
+async fn gateway_mac_address(gateway: IpAddr, interface: &str) -> Option<[u8; 6]> {
+    let table = tokio::fs::read_to_string(ARP_TABLE_PATH).await.ok()?;
+    parse_arp_table(&table, gateway, interface)
+}
+
+/// Find the hardware address of `ip` on `interface` in the contents of `/proc/net/arp`.
+fn parse_arp_table(table: &str, ip: IpAddr, interface: &str) -> Option<[u8; 6]> {
+    table.lines().skip(1).find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let [address, _hw_type, _flags, hw_address, _mask, device] = columns[..] else {
+            return None;
+        };
+        if device != interface || address.parse::<IpAddr>().ok()? != ip {
+            return None;
+        }
+        parse_mac_address(hw_address)
+    })
+}
+
+fn parse_mac_address(address: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut octets = address.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(octets.next()?, 16).ok()?;
+    }
+    // Incomplete ARP entries have an all-zero address
+    (octets.next().is_none() && mac != [0u8; 6]).then_some(mac)
+}
+
+/// Look up the domain name from the DHCP lease of `interface`.
+///
+/// Both systemd-networkd and NetworkManager's internal DHCP client store leases in the same
+/// `KEY=value` format.
+async fn dhcp_domain(interface: &str) -> Option<String> {
+    if let Some(domain) = networkd_dhcp_domain(interface).await {
+        return Some(domain);
+    }
+    network_manager_dhcp_domain(interface).await
+}
+
+async fn networkd_dhcp_domain(interface: &str) -> Option<String> {
+    let index =
+        tokio::fs::read_to_string(Path::new("/sys/class/net").join(interface).join("ifindex"))
+            .await
+            .ok()?;
+    let lease = tokio::fs::read_to_string(Path::new(NETWORKD_LEASE_DIR).join(index.trim()))
+        .await
+        .ok()?;
+    parse_lease_domain(&lease)
+}
+
+async fn network_manager_dhcp_domain(interface: &str) -> Option<String> {
+    let suffix = format!("-{interface}.lease");
+    let mut entries = tokio::fs::read_dir(NETWORK_MANAGER_LEASE_DIR).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        if !file_name.starts_with("internal-") || !file_name.ends_with(&suffix) {
+            continue;
+        }
+        if let Ok(lease) = tokio::fs::read_to_string(entry.path()).await {
+            if let Some(domain) = parse_lease_domain(&lease) {
+                return Some(domain);
+            }
+        }
+    }
+    None
+}
+
+fn parse_lease_domain(lease: &str) -> Option<String> {
+    lease
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("DOMAINNAME="))
+        .map(|domain| domain.trim_matches('"').to_owned())
+        .filter(|domain| !domain.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ARP_TABLE: &str = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         a4:91:b1:0c:3e:7f     *        wlan0
+192.168.1.20     0x1         0x0         00:00:00:00:00:00     *        wlan0
+10.0.0.1         0x1         0x2         52:54:00:12:34:56     *        eth0
+";
+
+    #[test]
+    fn test_parse_arp_table() {
+        let gateway = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(
+            parse_arp_table(ARP_TABLE, gateway, "wlan0"),
+            Some([0xa4, 0x91, 0xb1, 0x0c, 0x3e, 0x7f])
+        );
+        // The same address on another interface is a different neighbour
+        assert_eq!(parse_arp_table(ARP_TABLE, gateway, "eth0"), None);
+        // Incomplete entries are ignored
+        let incomplete = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        assert_eq!(parse_arp_table(ARP_TABLE, incomplete, "wlan0"), None);
+    }
+
+    #[test]
+    fn test_parse_lease_domain() {
+        let lease =
+            "# This is private data. Do not parse.\nADDRESS=192.168.1.20\nDOMAINNAME=home.lan\n";
+        assert_eq!(parse_lease_domain(lease), Some("home.lan".to_owned()));
+        assert_eq!(parse_lease_domain("ADDRESS=192.168.1.20\n"), None);
+    }
+}
@@ -4,7 +4,10 @@ use futures::channel::mpsc::UnboundedSender;
 use std::sync::LazyLock;
 #[cfg(not(target_os = "android"))]
 use talpid_routing::RouteManagerHandle;
-use talpid_types::{net::Connectivity, ErrorExt};
+use talpid_types::{
+    net::{Connectivity, NetworkInfo},
+    ErrorExt,
+};
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -40,8 +43,13 @@ impl MonitorHandle {
     }
 }
 
+/// Spawn the offline monitor. Connectivity changes are sent on `sender`.
+///
+/// Changes to the identity of the network the host is attached to are sent on `network_sender`.
+/// This is currently only reported on Linux, and the sender is dropped on other platforms.
 pub async fn spawn_monitor(
     sender: UnboundedSender<Connectivity>,
+    network_sender: UnboundedSender<NetworkInfo>,
     #[cfg(not(target_os = "android"))] route_manager: RouteManagerHandle,
     #[cfg(target_os = "linux")] fwmark: Option<u32>,
     #[cfg(target_os = "android")] connectivity_listener: ConnectivityListener,
@@ -49,8 +57,12 @@ pub async fn spawn_monitor(
     let monitor = if *FORCE_DISABLE_OFFLINE_MONITOR {
         None
     } else {
+        #[cfg(not(target_os = "linux"))]
+        drop(network_sender);
         imp::spawn_monitor(
             sender,
+            #[cfg(target_os = "linux")]
+            network_sender,
             #[cfg(not(target_os = "android"))]
             route_manager,
             #[cfg(target_os = "linux")]
@@ -0,0 +1,304 @@
+//! Excludes applications by path. The kernel reports every `exec` through the process events
+//! connector, and processes that start running an excluded executable are moved into the
+//! exclusion cgroup. Their children inherit the cgroup.
+
+use super::{Error, PidManager};
+use std::{
+    collections::HashSet,
+    fs, io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use talpid_types::ErrorExt;
+use tokio::io::unix::AsyncFd;
+
+// From linux/connector.h and linux/cn_proc.h
+const CN_IDX_PROC: u32 = 0x1;
+const CN_VAL_PROC: u32 = 0x1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x2;
+
+/// Size of `struct nlmsghdr`
+const NLMSG_HEADER_LEN: usize = 16;
+/// Size of `struct cn_msg`, not including its payload
+const CN_MSG_HEADER_LEN: usize = 20;
+/// Offset of `event_data` in `struct proc_event`
+const PROC_EVENT_DATA_OFFSET: usize = 16;
+
+/// Excludes processes running any of a set of executables from the tunnel.
+pub struct AppMonitor {
+    /// Canonical paths of the excluded executables
+    apps: Arc<Mutex<HashSet<PathBuf>>>,
+    pid_manager: PidManager,
+    monitor_task: tokio::task::JoinHandle<()>,
+}
+
+impl AppMonitor {
+    /// Start listening for new processes. No applications are excluded until
+    /// [`AppMonitor::set_apps`] is called.
+    pub fn spawn(pid_manager: PidManager) -> Result<Self, Error> {
+        let socket = ProcEventSocket::open().map_err(Error::ProcessEvents)?;
+        let apps = Arc::new(Mutex::new(HashSet::new()));
+        let monitor_task =
+            tokio::spawn(monitor_processes(socket, pid_manager.clone(), apps.clone()));
+        Ok(AppMonitor {
+            apps,
+            pid_manager,
+            monitor_task,
+        })
+    }
+
+    /// Set the executables to exclude. Running processes of executables that were added are
+    /// excluded immediately, and running processes of executables that were removed are moved
+    /// back into the tunnel.
+    pub fn set_apps(&self, apps: impl IntoIterator<Item = PathBuf>) {
+        let new_apps: HashSet<PathBuf> = apps
+            .into_iter()
+            .map(|path| fs::canonicalize(&path).unwrap_or(path))
+            .collect();
+        let old_apps = mem::replace(&mut *self.apps.lock().unwrap(), new_apps.clone());
+
+        for (pid, exe) in running_processes() {
+            let result = match (old_apps.contains(&exe), new_apps.contains(&exe)) {
+                (false, true) => self.pid_manager.add(pid),
+                (true, false) => self.pid_manager.remove(pid),
+                _ => continue,
+            };
+            if let Err(error) = result {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!(
+                        "Failed to update exclusion of {} (PID {pid})",
+                        exe.display()
+                    ))
+                );
+            }
+        }
+    }
+}
+
+impl Drop for AppMonitor {
+    fn drop(&mut self) {
+        self.monitor_task.abort();
+    }
+}
+
+async fn monitor_processes(
+    socket: ProcEventSocket,
+    pid_manager: PidManager,
+    apps: Arc<Mutex<HashSet<PathBuf>>>,
+) {
+    let exclude_if_listed = |pid: i32| {
+        let Ok(exe) = fs::read_link(format!("/proc/{pid}/exe")) else {
+            // The process has already exited
+            return;
+        };
+        if !apps.lock().unwrap().contains(&exe) {
+            return;
+        }
+        match pid_manager.add(pid) {
+            Ok(()) => log::debug!("Excluded {} (PID {pid})", exe.display()),
+            Err(error) => log::error!(
+                "{}",
+                error.display_chain_with_msg(&format!("Failed to exclude PID {pid}"))
+            ),
+        }
+    };
+
+    loop {
+        match socket.next_exec().await {
+            Ok(pid) => exclude_if_listed(pid),
+            // The socket buffer overflowed, so some events were lost
+            Err(error) if error.raw_os_error() == Some(libc::ENOBUFS) => {
+                log::warn!("Missed process events. Checking all running processes");
+                for (pid, _) in running_processes() {
+                    exclude_if_listed(pid);
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Stopped listening for process events")
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Return the PID and executable of every process that can be inspected.
+fn running_processes() -> Vec<(i32, PathBuf)> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let exe = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+            Some((pid, exe))
+        })
+        .collect()
+}
+
+/// Netlink socket subscribed to process events from the kernel. Requires `CAP_NET_ADMIN`.
+struct ProcEventSocket(AsyncFd<OwnedFd>);
+
+impl ProcEventSocket {
+    fn open() -> io::Result<Self> {
+        // SAFETY: Creating a socket has no preconditions
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a valid socket that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `sockaddr_nl` is valid when zeroed
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        // SAFETY: `addr` is a valid `sockaddr_nl` of the given size
+        let result = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let request = listen_request(std::process::id());
+        // SAFETY: `request` is valid for reads of its length
+        let result = unsafe {
+            libc::send(
+                fd.as_raw_fd(),
+                request.as_ptr() as *const libc::c_void,
+                request.len(),
+                0,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        AsyncFd::new(fd).map(ProcEventSocket)
+    }
+
+    /// Wait for a process to call `exec`, and return its PID.
+    async fn next_exec(&self) -> io::Result<i32> {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let mut guard = self.0.readable().await?;
+            let result = guard.try_io(|fd| {
+                // SAFETY: `buffer` is valid for writes of its length
+                let len = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        buffer.as_mut_ptr() as *mut libc::c_void,
+                        buffer.len(),
+                        0,
+                    )
+                };
+                if len < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(len as usize)
+            });
+            match result {
+                Ok(Ok(len)) => {
+                    if let Some(pid) = parse_exec_event(&buffer[..len]) {
+                        return Ok(pid);
+                    }
+                }
+                Ok(Err(error)) => return Err(error),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Build the message that subscribes a process events socket to events.
+fn listen_request(port_id: u32) -> [u8; NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN + 4] {
+    let mut message = [0u8; NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN + 4];
+    let (nlmsghdr, rest) = message.split_at_mut(NLMSG_HEADER_LEN);
+    let (cn_msg, op) = rest.split_at_mut(CN_MSG_HEADER_LEN);
+
+    let len = (NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN + 4) as u32;
+    nlmsghdr[0..4].copy_from_slice(&len.to_ne_bytes());
+    nlmsghdr[4..6].copy_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+    nlmsghdr[12..16].copy_from_slice(&port_id.to_ne_bytes());
+
+    cn_msg[0..4].copy_from_slice(&CN_IDX_PROC.to_ne_bytes());
+    cn_msg[4..8].copy_from_slice(&CN_VAL_PROC.to_ne_bytes());
+    cn_msg[16..18].copy_from_slice(&(op.len() as u16).to_ne_bytes());
+
+    op.copy_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    message
+}
+
+/// Return the PID of the process in a process event message, if it is an `exec` event.
+fn parse_exec_event(message: &[u8]) -> Option<i32> {
+    let event = message.get(NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN..)?;
+    let what = u32::from_ne_bytes(event.get(0..4)?.try_into().ok()?);
+    if what != PROC_EVENT_EXEC {
+        return None;
+    }
+    // `struct exec_proc_event` is `process_pid` followed by `process_tgid`. The latter is the PID
+    // of the process, rather than of the thread.
+    let data = event.get(PROC_EVENT_DATA_OFFSET..)?;
+    Some(i32::from_ne_bytes(data.get(4..8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn proc_event(what: u32, pid: i32, tgid: i32) -> Vec<u8> {
+        let mut message = vec![0u8; NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN];
+        message.extend_from_slice(&what.to_ne_bytes());
+        message.extend_from_slice(&[0u8; 12]);
+        message.extend_from_slice(&pid.to_ne_bytes());
+        message.extend_from_slice(&tgid.to_ne_bytes());
+        message
+    }
+
+    #[test]
+    fn test_parse_exec_event() {
+        assert_eq!(
+            parse_exec_event(&proc_event(PROC_EVENT_EXEC, 1235, 1234)),
+            Some(1234)
+        );
+        // PROC_EVENT_FORK
+        assert_eq!(parse_exec_event(&proc_event(0x1, 1235, 1234)), None);
+        // Truncated message
+        assert_eq!(
+            parse_exec_event(&proc_event(PROC_EVENT_EXEC, 1235, 1234)[..50]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_listen_request() {
+        let request = listen_request(42);
+        assert_eq!(u32::from_ne_bytes(request[0..4].try_into().unwrap()), 40);
+        assert_eq!(u32::from_ne_bytes(request[12..16].try_into().unwrap()), 42);
+        assert_eq!(
+            u32::from_ne_bytes(request[16..20].try_into().unwrap()),
+            CN_IDX_PROC
+        );
+        assert_eq!(u16::from_ne_bytes(request[32..34].try_into().unwrap()), 4);
+        assert_eq!(
+            u32::from_ne_bytes(request[36..40].try_into().unwrap()),
+            PROC_CN_MCAST_LISTEN
+        );
+    }
+}
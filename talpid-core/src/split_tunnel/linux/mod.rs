@@ -1,9 +1,18 @@
 use std::{
     env, fs,
     io::{self, BufRead, BufReader, Write},
+    os::unix::fs::MetadataExt,
     path::PathBuf,
 };
-use talpid_types::cgroup::{find_net_cls_mount, SPLIT_TUNNEL_CGROUP_NAME};
+use talpid_types::{
+    cgroup::{find_exclusion_hierarchy, SPLIT_TUNNEL_CGROUP_NAME},
+    split_tunnel::SplitTunnelMode,
+};
+
+pub use app_monitor::AppMonitor;
+pub use talpid_types::cgroup::ExclusionMechanism;
+
+mod app_monitor;
 
 const DEFAULT_NET_CLS_DIR: &str = "/sys/fs/cgroup/net_cls";
 const NET_CLS_DIR_OVERRIDE_ENV_VAR: &str = "TALPID_NET_CLS_MOUNT_DIR";
@@ -14,6 +23,19 @@ pub const NET_CLS_CLASSID: u32 = 0x4d9f41;
 /// Value used to mark packets and associated connections.
 /// This should be an arbitrary but unique integer.
 pub const MARK: i32 = 0xf41;
+/// Firewall mark of packets that should be routed through the tunnel in include mode. Other
+/// packets are routed outside it.
+/// This should be an arbitrary but unique integer.
+pub const INCLUDE_FWMARK: u32 = 0x4d9f42;
+
+/// Return the firewall mark of the only traffic that should be routed through the tunnel in
+/// `mode`, or `None` if all traffic that isn't excluded should be.
+pub fn include_fwmark(mode: SplitTunnelMode) -> Option<u32> {
+    match mode {
+        SplitTunnelMode::Exclude => None,
+        SplitTunnelMode::Include => Some(INCLUDE_FWMARK),
+    }
+}
 
 /// Errors related to split tunneling.
 #[derive(thiserror::Error, Debug)]
@@ -45,30 +67,70 @@ pub enum Error {
     /// Unable to read /proc/mounts
     #[error("Failed to read /proc/mounts")]
     ListMounts(#[source] io::Error),
+
+    /// Unable to subscribe to process events.
+    #[error("Unable to listen for process events")]
+    ProcessEvents(#[source] io::Error),
+}
+
+/// Return the ID of the cgroup v2 cgroup that excluded processes are placed in, if that is how
+/// they are identified. The firewall matches the sockets of excluded processes using this ID.
+pub fn exclusion_cgroup_v2_id() -> Option<u64> {
+    match find_exclusion_hierarchy() {
+        Ok(Some((ExclusionMechanism::CgroupV2, cgroup_root)))
+            if crate::firewall::supports_socket_cgroupv2() =>
+        {
+            // The ID of a cgroup v2 cgroup is the inode number of its directory
+            fs::metadata(cgroup_root.join(SPLIT_TUNNEL_CGROUP_NAME))
+                .map(|metadata| metadata.ino())
+                .ok()
+        }
+        _ => None,
+    }
 }
 
 /// Manages PIDs in the Linux Cgroup excluded from the VPN tunnel.
+#[derive(Clone)]
 pub struct PidManager {
-    net_cls_path: PathBuf,
+    /// Root of the cgroup hierarchy that the exclusion cgroup is created in
+    cgroup_root: PathBuf,
+    mechanism: ExclusionMechanism,
 }
 
 impl PidManager {
     /// Creates a new PID Cgroup manager.
     ///
-    /// Finds the corresponding Cgroup to use. Will mount a `net_cls` filesystem
-    /// if none exists.
+    /// Finds the corresponding Cgroup to use. An existing `net_cls` hierarchy is used if there
+    /// is one, and otherwise the cgroup v2 hierarchy. Will mount a `net_cls` filesystem if
+    /// neither exists, or if the kernel is too old for the firewall to match cgroup v2 sockets.
     pub fn new() -> Result<PidManager, Error> {
+        let (mechanism, cgroup_root) = Self::create_cgroup()?;
         let manager = PidManager {
-            net_cls_path: Self::create_cgroup()?,
+            cgroup_root,
+            mechanism,
         };
         manager.setup_exclusion_group()?;
+        log::info!("Identifying excluded processes using {mechanism}");
         Ok(manager)
     }
 
+    /// Return the mechanism used to identify excluded processes.
+    pub fn mechanism(&self) -> ExclusionMechanism {
+        self.mechanism
+    }
+
     /// Set up cgroup used to track PIDs for split tunneling.
-    fn create_cgroup() -> Result<PathBuf, Error> {
-        if let Some(net_cls_path) = find_net_cls_mount().map_err(Error::ListMounts)? {
-            return Ok(net_cls_path);
+    fn create_cgroup() -> Result<(ExclusionMechanism, PathBuf), Error> {
+        match find_exclusion_hierarchy().map_err(Error::ListMounts)? {
+            Some((ExclusionMechanism::CgroupV2, _))
+                if !crate::firewall::supports_socket_cgroupv2() =>
+            {
+                // `socket cgroupv2` requires Linux 5.13. Since the net_cls hierarchy is preferred
+                // once it is mounted, every user of `find_exclusion_hierarchy` will agree on it.
+                log::warn!("The kernel cannot match cgroup v2 sockets, falling back to net_cls");
+            }
+            Some(hierarchy) => return Ok(hierarchy),
+            None => (),
         }
 
         let net_cls_dir = env::var(NET_CLS_DIR_OVERRIDE_ENV_VAR)
@@ -89,15 +151,20 @@ impl PidManager {
         )
         .map_err(Error::InitNetClsCGroup)?;
 
-        Ok(net_cls_dir)
+        Ok((ExclusionMechanism::NetCls, net_cls_dir))
     }
 
     fn setup_exclusion_group(&self) -> Result<(), Error> {
-        let exclusions_dir = self.net_cls_path.join(SPLIT_TUNNEL_CGROUP_NAME);
+        let exclusions_dir = self.cgroup_root.join(SPLIT_TUNNEL_CGROUP_NAME);
         if !exclusions_dir.exists() {
             fs::create_dir(exclusions_dir.clone()).map_err(Error::CreateCGroup)?;
         }
 
+        if self.mechanism == ExclusionMechanism::CgroupV2 {
+            // Sockets in the cgroup are matched by the firewall instead of by a class ID
+            return Ok(());
+        }
+
         let classid_path = exclusions_dir.join("net_cls.classid");
         fs::write(classid_path, NET_CLS_CLASSID.to_string().as_bytes())
             .map_err(Error::SetCGroupClassId)
@@ -106,7 +173,7 @@ impl PidManager {
     /// Add a PID to the Cgroup to have it excluded from the tunnel.
     pub fn add(&self, pid: i32) -> Result<(), Error> {
         let exclusions_path = self
-            .net_cls_path
+            .cgroup_root
             .join(SPLIT_TUNNEL_CGROUP_NAME)
             .join("cgroup.procs");
 
@@ -136,7 +203,7 @@ impl PidManager {
     /// Return a list of all PIDs currently in the Cgroup excluded from the tunnel.
     pub fn list(&self) -> Result<Vec<i32>, Error> {
         let exclusions_path = self
-            .net_cls_path
+            .cgroup_root
             .join(SPLIT_TUNNEL_CGROUP_NAME)
             .join("cgroup.procs");
 
@@ -174,6 +241,6 @@ impl PidManager {
             .write(true)
             .create(true)
             .truncate(false)
-            .open(self.net_cls_path.join("cgroup.procs"))
+            .open(self.cgroup_root.join("cgroup.procs"))
     }
 }
@@ -168,7 +168,9 @@ impl TunnelMonitor {
             TunnelParameters::OpenVpn(params) => match &params.proxy {
                 Some(CustomProxy::Shadowsocks(_)) => Some(std::env::current_exe().unwrap()),
                 Some(CustomProxy::Socks5Local(_)) => None,
-                Some(CustomProxy::Socks5Remote(_)) | None => Some(resource_dir.join("openvpn.exe")),
+                Some(CustomProxy::Socks5Remote(_)) | Some(CustomProxy::HttpConnect(_)) | None => {
+                    Some(resource_dir.join("openvpn.exe"))
+                }
             },
             _ => Some(std::env::current_exe().unwrap()),
         }
@@ -268,12 +270,45 @@ impl TunnelMonitor {
         }
     }
 
+    /// Returns a handle for reading traffic statistics, if the tunnel type supports it.
+    pub fn stats_handle(&self) -> Option<TunnelStatsHandle> {
+        match &self.monitor {
+            #[cfg(not(target_os = "android"))]
+            InternalTunnelMonitor::OpenVpn(_) => None,
+            InternalTunnelMonitor::Wireguard(monitor) => {
+                Some(TunnelStatsHandle(monitor.stats_handle()))
+            }
+        }
+    }
+
     /// Consumes the monitor and blocks until the tunnel exits or there is an error.
     pub fn wait(self) -> Result<()> {
         self.monitor.wait().map_err(Error::from)
     }
 }
 
+/// Reads per-peer traffic statistics from a running WireGuard tunnel.
+#[derive(Clone)]
+pub struct TunnelStatsHandle(talpid_wireguard::TunnelStatsHandle);
+
+impl TunnelStatsHandle {
+    /// Returns the current stats for each peer, or `None` if the tunnel is no longer up.
+    pub async fn get_stats(&self) -> Option<Vec<wireguard_types::PeerStats>> {
+        let stats = self.0.get_stats().await?;
+        Some(
+            stats
+                .into_iter()
+                .map(|(public_key, stats)| wireguard_types::PeerStats {
+                    public_key: wireguard_types::PublicKey::from(public_key),
+                    tx_bytes: stats.tx_bytes,
+                    rx_bytes: stats.rx_bytes,
+                    last_handshake: stats.last_handshake,
+                })
+                .collect(),
+        )
+    }
+}
+
 enum InternalTunnelMonitor {
     #[cfg(not(target_os = "android"))]
     OpenVpn(talpid_openvpn::OpenVpnMonitor),
@@ -1,6 +1,8 @@
 use futures::channel::{mpsc, oneshot};
 use futures::stream::Fuse;
 use futures::StreamExt;
+#[cfg(not(target_os = "android"))]
+use std::net::{IpAddr, SocketAddr};
 
 #[cfg(target_os = "android")]
 use talpid_tunnel::tun_provider::Error;
@@ -8,7 +10,9 @@ use talpid_types::net::{AllowedClients, AllowedEndpoint, TunnelParameters};
 use talpid_types::tunnel::{ErrorStateCause, FirewallPolicyError};
 use talpid_types::{BoxedError, ErrorExt};
 
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "android"))]
+use crate::dns::stub::{self, StubResolver};
+#[cfg(not(target_os = "android"))]
 use crate::dns::DnsConfig;
 use crate::dns::ResolvedDnsConfig;
 use crate::firewall::FirewallPolicy;
@@ -18,7 +22,7 @@ use crate::resolver::LOCAL_DNS_RESOLVER;
 use crate::tunnel::TunnelMonitor;
 use crate::tunnel::{TunnelEvent, TunnelMetadata};
 
-use super::connecting_state::TunnelCloseEvent;
+use super::connecting_state::{send_tunnel_stats, SharedTunnelStats, TunnelCloseEvent};
 use super::{
     AfterDisconnect, ConnectingState, DisconnectingState, ErrorState, EventConsequence,
     EventResult, SharedTunnelStateValues, TunnelCommand, TunnelCommandReceiver, TunnelState,
@@ -35,6 +39,9 @@ pub struct ConnectedState {
     tunnel_parameters: TunnelParameters,
     tunnel_close_event: TunnelCloseEvent,
     tunnel_close_tx: oneshot::Sender<()>,
+    tunnel_stats: SharedTunnelStats,
+    /// Tunnel MTU reported by path MTU detection
+    mtu: Option<u16>,
 }
 
 impl ConnectedState {
@@ -46,6 +53,7 @@ impl ConnectedState {
         tunnel_parameters: TunnelParameters,
         tunnel_close_event: TunnelCloseEvent,
         tunnel_close_tx: oneshot::Sender<()>,
+        tunnel_stats: SharedTunnelStats,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
         let connected_state = ConnectedState {
             metadata,
@@ -53,13 +61,11 @@ impl ConnectedState {
             tunnel_parameters,
             tunnel_close_event,
             tunnel_close_tx,
+            tunnel_stats,
+            mtu: None,
         };
 
-        let tunnel_interface = Some(connected_state.metadata.interface.clone());
-        let tunnel_endpoint = talpid_types::net::TunnelEndpoint {
-            tunnel_interface,
-            ..connected_state.tunnel_parameters.get_tunnel_endpoint()
-        };
+        let tunnel_endpoint = connected_state.tunnel_endpoint();
 
         if let Err(error) = connected_state.set_firewall_policy(shared_values) {
             DisconnectingState::enter(
@@ -82,6 +88,14 @@ impl ConnectedState {
         }
     }
 
+    fn tunnel_endpoint(&self) -> talpid_types::net::TunnelEndpoint {
+        talpid_types::net::TunnelEndpoint {
+            tunnel_interface: Some(self.metadata.interface.clone()),
+            mtu: self.mtu,
+            ..self.tunnel_parameters.get_tunnel_endpoint()
+        }
+    }
+
     fn set_firewall_policy(
         &self,
         shared_values: &mut SharedTunnelStateValues,
@@ -137,10 +151,14 @@ impl ConnectedState {
 
         FirewallPolicy::Connected {
             peer_endpoint,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            peer_udp_relay: self.metadata.udp_relay,
             tunnel: self.metadata.clone(),
             allow_lan: shared_values.allow_lan,
             #[cfg(not(target_os = "android"))]
             dns_config: Self::resolve_dns(&self.metadata, shared_values),
+            #[cfg(target_os = "linux")]
+            exceptions: shared_values.firewall_exceptions.clone(),
             #[cfg(target_os = "macos")]
             redirect_interface,
             #[cfg(target_os = "macos")]
@@ -152,6 +170,21 @@ impl ConnectedState {
         metadata: &TunnelMetadata,
         shared_values: &SharedTunnelStateValues,
     ) -> ResolvedDnsConfig {
+        // Encrypted DNS is answered by the stub resolver listening on the tunnel address
+        #[cfg(not(target_os = "android"))]
+        if let (Some(_), Some(stub_ip)) = (
+            shared_values.dns_config.encryption(),
+            Self::dns_stub_ip(metadata),
+        ) {
+            return DnsConfig::from_addresses(&[stub_ip], &[])
+                .with_split_rules(shared_values.dns_config.split_rules().to_vec())
+                .resolve(
+                    &[],
+                    #[cfg(target_os = "macos")]
+                    stub::STUB_PORT,
+                );
+        }
+
         shared_values.dns_config.resolve(
             &metadata.gateways(),
             #[cfg(target_os = "macos")]
@@ -159,7 +192,48 @@ impl ConnectedState {
         )
     }
 
+    /// Address that the DNS stub resolver listens on.
+    #[cfg(not(target_os = "android"))]
+    fn dns_stub_ip(metadata: &TunnelMetadata) -> Option<IpAddr> {
+        metadata.ips.iter().find(|ip| ip.is_ipv4()).copied()
+    }
+
+    /// Start a stub resolver if DNS queries should be encrypted, replacing any running one.
+    #[cfg(not(target_os = "android"))]
+    fn start_dns_stub(
+        &self,
+        shared_values: &mut SharedTunnelStateValues,
+    ) -> Result<(), stub::Error> {
+        Self::stop_dns_stub(shared_values);
+
+        let (Some((addresses, encryption)), Some(stub_ip)) = (
+            shared_values.dns_config.encryption(),
+            Self::dns_stub_ip(&self.metadata),
+        ) else {
+            return Ok(());
+        };
+        let listen_addr = SocketAddr::new(stub_ip, stub::STUB_PORT);
+        let dns_stub = shared_values.runtime.block_on(StubResolver::start(
+            listen_addr,
+            addresses,
+            encryption,
+        ))?;
+        shared_values.dns_stub = Some(dns_stub);
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn stop_dns_stub(shared_values: &mut SharedTunnelStateValues) {
+        if let Some(dns_stub) = shared_values.dns_stub.take() {
+            shared_values.runtime.block_on(dns_stub.stop());
+        }
+    }
+
     fn set_dns(&self, shared_values: &mut SharedTunnelStateValues) -> Result<(), BoxedError> {
+        #[cfg(not(target_os = "android"))]
+        self.start_dns_stub(shared_values)
+            .map_err(BoxedError::new)?;
+
         let dns_config: ResolvedDnsConfig = Self::resolve_dns(&self.metadata, shared_values);
 
         #[cfg(not(target_os = "macos"))]
@@ -202,6 +276,9 @@ impl ConnectedState {
     }
 
     fn reset_dns(shared_values: &mut SharedTunnelStateValues) {
+        #[cfg(not(target_os = "android"))]
+        Self::stop_dns_stub(shared_values);
+
         #[cfg(not(target_os = "macos"))]
         if let Err(error) = shared_values.dns_monitor.reset_before_interface_removal() {
             log::error!("{}", error.display_chain_with_msg("Unable to reset DNS"));
@@ -288,6 +365,46 @@ impl ConnectedState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::FirewallExceptions(exceptions, complete_tx)) => {
+                let consequence = if shared_values.set_firewall_exceptions(exceptions) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                send_tunnel_stats(shared_values, &self.tunnel_stats, tx);
+                SameState(self)
+            }
+            Some(TunnelCommand::GetDnsState(tx)) => {
+                let _ = tx.send(shared_values.dns_monitor.to_string());
+                SameState(self)
+            }
             #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 shared_values.allowed_endpoint = endpoint;
@@ -440,6 +557,13 @@ impl ConnectedState {
             Some((TunnelEvent::Down, _)) | None => {
                 self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
             }
+            Some((TunnelEvent::MtuDetected(mtu), _)) if self.mtu != Some(mtu) => {
+                // Report the new MTU by broadcasting the connected state again
+                let mut state = self;
+                state.mtu = Some(mtu);
+                let tunnel_endpoint = state.tunnel_endpoint();
+                NewState((state, TunnelStateTransition::Connected(tunnel_endpoint)))
+            }
             Some(_) => SameState(self),
         }
     }
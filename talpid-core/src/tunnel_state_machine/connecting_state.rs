@@ -9,7 +9,9 @@ use futures::{FutureExt, StreamExt};
 use talpid_routing::RouteManagerHandle;
 use talpid_tunnel::tun_provider::TunProvider;
 use talpid_tunnel::{EventHook, TunnelArgs, TunnelEvent, TunnelMetadata};
-use talpid_types::net::{AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelParameters};
+use talpid_types::net::{
+    wireguard::PeerStats, AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelParameters,
+};
 use talpid_types::tunnel::{ErrorStateCause, FirewallPolicyError};
 use talpid_types::ErrorExt;
 
@@ -25,9 +27,11 @@ use crate::dns::DnsConfig;
 use crate::firewall::FirewallPolicy;
 #[cfg(target_os = "macos")]
 use crate::resolver::LOCAL_DNS_RESOLVER;
-use crate::tunnel::{self, TunnelMonitor};
+use crate::tunnel::{self, TunnelMonitor, TunnelStatsHandle};
 
 pub(crate) type TunnelCloseEvent = Fuse<oneshot::Receiver<Option<ErrorStateCause>>>;
+/// Set by the tunnel monitor thread once the tunnel has been started.
+pub(crate) type SharedTunnelStats = Arc<Mutex<Option<TunnelStatsHandle>>>;
 
 #[cfg(target_os = "android")]
 const MAX_ATTEMPTS_WITH_SAME_TUN: u32 = 5;
@@ -45,6 +49,7 @@ pub struct ConnectingState {
     allowed_tunnel_traffic: AllowedTunnelTraffic,
     tunnel_close_event: TunnelCloseEvent,
     tunnel_close_tx: oneshot::Sender<()>,
+    tunnel_stats: SharedTunnelStats,
     retry_attempt: u32,
 }
 
@@ -183,10 +188,16 @@ impl ConnectingState {
 
         let policy = FirewallPolicy::Connecting {
             peer_endpoint,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            peer_udp_relay: tunnel_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.udp_relay),
             tunnel: tunnel_metadata.clone(),
             allow_lan: shared_values.allow_lan,
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
+            #[cfg(target_os = "linux")]
+            exceptions: shared_values.firewall_exceptions.clone(),
             #[cfg(target_os = "macos")]
             redirect_interface,
             #[cfg(target_os = "macos")]
@@ -230,6 +241,8 @@ impl ConnectingState {
         let (tunnel_close_event_tx, tunnel_close_event_rx) = oneshot::channel();
 
         let tunnel_parameters = parameters.clone();
+        let tunnel_stats = SharedTunnelStats::default();
+        let monitor_tunnel_stats = tunnel_stats.clone();
 
         tokio::task::spawn_blocking(move || {
             let start = Instant::now();
@@ -246,6 +259,7 @@ impl ConnectingState {
 
             let block_reason = match TunnelMonitor::start(&tunnel_parameters, &log_dir, args) {
                 Ok(monitor) => {
+                    *monitor_tunnel_stats.lock().unwrap() = monitor.stats_handle();
                     let reason = Self::wait_for_tunnel_monitor(monitor, retry_attempt);
                     log::debug!("Tunnel monitor exited with block reason: {:?}", reason);
                     reason
@@ -285,6 +299,7 @@ impl ConnectingState {
             allowed_tunnel_traffic: INITIAL_ALLOWED_TUNNEL_TRAFFIC,
             tunnel_close_event: tunnel_close_event_rx.fuse(),
             tunnel_close_tx,
+            tunnel_stats,
             retry_attempt,
         }
     }
@@ -403,6 +418,34 @@ impl ConnectingState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::FirewallExceptions(exceptions, complete_tx)) => {
+                let consequence = if shared_values.set_firewall_exceptions(exceptions) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            Some(TunnelCommand::GetTunnelStats(tx)) => {
+                send_tunnel_stats(shared_values, &self.tunnel_stats, tx);
+                SameState(self)
+            }
+            Some(TunnelCommand::GetDnsState(tx)) => {
+                let _ = tx.send(shared_values.dns_monitor.to_string());
+                SameState(self)
+            }
             #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
@@ -591,7 +634,10 @@ impl ConnectingState {
                 self.tunnel_parameters,
                 self.tunnel_close_event,
                 self.tunnel_close_tx,
+                self.tunnel_stats,
             )),
+            // MTU detection only runs once the tunnel is up
+            Some((TunnelEvent::MtuDetected(_), _)) => SameState(self),
             Some((TunnelEvent::Down, _)) => {
                 // It is important to reset this before the tunnel device is down,
                 // or else commands that reapply the firewall rules will fail since
@@ -634,6 +680,23 @@ impl ConnectingState {
     }
 }
 
+/// Reads the stats of the current tunnel, if it has been started, without blocking the state
+/// machine.
+pub(super) fn send_tunnel_stats(
+    shared_values: &SharedTunnelStateValues,
+    tunnel_stats: &SharedTunnelStats,
+    tx: oneshot::Sender<Option<Vec<PeerStats>>>,
+) {
+    let handle = tunnel_stats.lock().unwrap().clone();
+    shared_values.runtime.spawn(async move {
+        let stats = match handle {
+            Some(handle) => handle.get_stats().await,
+            None => None,
+        };
+        let _ = tx.send(stats);
+    });
+}
+
 #[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn should_retry(error: &tunnel::Error, retry_attempt: u32) -> bool {
     #[cfg(target_os = "windows")]
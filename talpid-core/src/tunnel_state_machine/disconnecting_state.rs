@@ -45,6 +45,26 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                    let _ = shared_values.set_split_tunnel_mode(mode);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::FirewallExceptions(exceptions, complete_tx)) => {
+                    let _ = shared_values.set_firewall_exceptions(exceptions);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Nothing
+                }
+                Some(TunnelCommand::GetDnsState(tx)) => {
+                    let _ = tx.send(shared_values.dns_monitor.to_string());
+                    AfterDisconnect::Nothing
+                }
                 #[cfg(not(target_os = "android"))]
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
@@ -101,6 +121,26 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                    let _ = shared_values.set_split_tunnel_mode(mode);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::FirewallExceptions(exceptions, complete_tx)) => {
+                    let _ = shared_values.set_firewall_exceptions(exceptions);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Block(reason)
+                }
+                Some(TunnelCommand::GetDnsState(tx)) => {
+                    let _ = tx.send(shared_values.dns_monitor.to_string());
+                    AfterDisconnect::Block(reason)
+                }
 
                 #[cfg(not(target_os = "android"))]
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
@@ -162,6 +202,26 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                    let _ = shared_values.set_split_tunnel_mode(mode);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::FirewallExceptions(exceptions, complete_tx)) => {
+                    let _ = shared_values.set_firewall_exceptions(exceptions);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::GetTunnelStats(tx)) => {
+                    let _ = tx.send(None);
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                Some(TunnelCommand::GetDnsState(tx)) => {
+                    let _ = tx.send(shared_values.dns_monitor.to_string());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 #[cfg(not(target_os = "android"))]
                 Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                     shared_values.allowed_endpoint = endpoint;
@@ -26,7 +26,7 @@ use talpid_routing::RouteManagerHandle;
 use talpid_tunnel::TunnelMetadata;
 use talpid_tunnel::{tun_provider::TunProvider, TunnelEvent};
 use talpid_tunnel_config_client::classic_mceliece::spawn_keypair_generator;
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use talpid_types::ErrorExt;
 
 use futures::{
@@ -45,8 +45,10 @@ use std::{
 };
 #[cfg(target_os = "android")]
 use talpid_types::{android::AndroidContext, ErrorExt};
+#[cfg(target_os = "linux")]
+use talpid_types::{net::firewall::FirewallException, split_tunnel::SplitTunnelMode};
 use talpid_types::{
-    net::{AllowedEndpoint, Connectivity, TunnelParameters},
+    net::{wireguard::PeerStats, AllowedEndpoint, Connectivity, NetworkInfo, TunnelParameters},
     tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
 };
 
@@ -109,6 +111,13 @@ pub struct InitialTunnelState {
     /// Apps to exclude from the tunnel.
     #[cfg(target_os = "android")]
     pub exclude_paths: Vec<String>,
+    /// Whether the split tunneled processes are excluded from the tunnel, or are the only ones
+    /// that use it.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: SplitTunnelMode,
+    /// User-defined traffic that the firewall should allow in every state.
+    #[cfg(target_os = "linux")]
+    pub firewall_exceptions: Vec<FirewallException>,
 }
 
 /// Identifiers for various network resources that should be unique to a given instance of a tunnel
@@ -131,6 +140,7 @@ pub async fn spawn(
     resource_dir: PathBuf,
     state_change_listener: impl Sender<TunnelStateTransition> + Send + 'static,
     offline_state_listener: mpsc::UnboundedSender<Connectivity>,
+    network_info_listener: mpsc::UnboundedSender<NetworkInfo>,
     route_manager: RouteManagerHandle,
     #[cfg(target_os = "windows")] volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
@@ -154,6 +164,7 @@ pub async fn spawn(
         settings: initial_settings,
         command_tx: weak_command_tx,
         offline_state_tx: offline_state_listener,
+        network_info_tx: network_info_listener,
         tunnel_parameters_generator,
         tun_provider,
         log_dir,
@@ -228,6 +239,18 @@ pub enum TunnelCommand {
         oneshot::Sender<Result<(), split_tunnel::Error>>,
         Vec<String>,
     ),
+    /// Set whether the split tunneled processes are excluded from the tunnel, or are the only
+    /// ones that use it.
+    #[cfg(target_os = "linux")]
+    SplitTunnelMode(SplitTunnelMode, oneshot::Sender<()>),
+    /// Set user-defined traffic that the firewall should allow.
+    #[cfg(target_os = "linux")]
+    FirewallExceptions(Vec<FirewallException>, oneshot::Sender<()>),
+    /// Request the traffic statistics of the current tunnel. `None` is returned if there is no
+    /// running tunnel or if the tunnel type does not report stats.
+    GetTunnelStats(oneshot::Sender<Option<Vec<PeerStats>>>),
+    /// Request a description of the DNS configuration that is currently applied.
+    GetDnsState(oneshot::Sender<String>),
 }
 
 type TunnelCommandReceiver = stream::Fuse<mpsc::UnboundedReceiver<TunnelCommand>>;
@@ -255,6 +278,7 @@ struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     settings: InitialTunnelState,
     command_tx: std::sync::Weak<mpsc::UnboundedSender<TunnelCommand>>,
     offline_state_tx: mpsc::UnboundedSender<Connectivity>,
+    network_info_tx: mpsc::UnboundedSender<NetworkInfo>,
     tunnel_parameters_generator: G,
     tun_provider: TunProvider,
     log_dir: Option<PathBuf>,
@@ -312,10 +336,20 @@ impl TunnelStateMachine {
             allow_lan: args.settings.allow_lan,
             #[cfg(target_os = "linux")]
             fwmark: args.linux_ids.fwmark,
+            #[cfg(target_os = "linux")]
+            split_tunnel_mode: args.settings.split_tunnel_mode,
         };
 
         let firewall = Firewall::from_args(fw_args).map_err(Error::InitFirewallError)?;
 
+        #[cfg(target_os = "linux")]
+        args.route_manager
+            .set_include_fwmark(crate::split_tunnel::include_fwmark(
+                args.settings.split_tunnel_mode,
+            ))
+            .await
+            .map_err(Error::InitRouteManagerError)?;
+
         let dns_monitor = DnsMonitor::new(
             #[cfg(target_os = "linux")]
             runtime.clone(),
@@ -338,6 +372,7 @@ impl TunnelStateMachine {
         });
         let offline_monitor = offline::spawn_monitor(
             offline_tx,
+            args.network_info_tx,
             #[cfg(not(target_os = "android"))]
             args.route_manager.clone(),
             #[cfg(target_os = "linux")]
@@ -384,8 +419,14 @@ impl TunnelStateMachine {
             allow_lan: args.settings.allow_lan,
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: args.settings.block_when_disconnected,
+            #[cfg(target_os = "linux")]
+            split_tunnel_mode: args.settings.split_tunnel_mode,
+            #[cfg(target_os = "linux")]
+            firewall_exceptions: args.settings.firewall_exceptions,
             connectivity,
             dns_config: args.settings.dns_config,
+            #[cfg(not(target_os = "android"))]
+            dns_stub: None,
             allowed_endpoint: args.settings.allowed_endpoint,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
@@ -477,10 +518,19 @@ struct SharedTunnelStateValues {
     /// Should network access be allowed when in the disconnected state.
     #[cfg(not(target_os = "android"))]
     block_when_disconnected: bool,
+    /// Whether the split tunneled processes are excluded from or included in the tunnel.
+    #[cfg(target_os = "linux")]
+    split_tunnel_mode: SplitTunnelMode,
+    /// User-defined traffic that the firewall should allow.
+    #[cfg(target_os = "linux")]
+    firewall_exceptions: Vec<FirewallException>,
     /// True when the computer is known to be offline.
     connectivity: Connectivity,
     /// DNS configuration to use.
     dns_config: crate::dns::DnsConfig,
+    /// Stub resolver that forwards queries to an encrypted DNS upstream while connected.
+    #[cfg(not(target_os = "android"))]
+    dns_stub: Option<crate::dns::stub::StubResolver>,
     /// Endpoint that should not be blocked by the firewall.
     allowed_endpoint: AllowedEndpoint,
     /// The generator of new `TunnelParameter`s
@@ -569,6 +619,39 @@ impl SharedTunnelStateValues {
         }
     }
 
+    /// Return whether the split tunnel mode changed. The firewall policy must be reapplied for
+    /// the new mode to take effect.
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> bool {
+        if self.split_tunnel_mode != mode {
+            self.split_tunnel_mode = mode;
+            self.firewall.set_split_tunnel_mode(mode);
+            let include_fwmark = crate::split_tunnel::include_fwmark(mode);
+            if let Err(error) = self
+                .runtime
+                .block_on(self.route_manager.set_include_fwmark(include_fwmark))
+            {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to update split tunnel routing rules")
+                );
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn set_firewall_exceptions(&mut self, exceptions: Vec<FirewallException>) -> bool {
+        if self.firewall_exceptions != exceptions {
+            self.firewall_exceptions = exceptions;
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn set_dns_config(&mut self, dns_config: DnsConfig) -> bool {
         if self.dns_config != dns_config {
             self.dns_config = dns_config;
@@ -0,0 +1,49 @@
+//! Client for PacRunner, which evaluates the proxy configuration of the system. NetworkManager
+//! hands PacRunner the proxy settings of each active connection, including PAC URLs obtained
+//! via WPAD or configured manually.
+use dbus::blocking::Proxy;
+use std::time::Duration;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to create a DBus connection")]
+    ConnectError(#[source] dbus::Error),
+
+    #[error("PacRunner is not running")]
+    NotAvailable,
+
+    #[error("Failed to look up proxy for URL")]
+    FindProxyError(#[source] dbus::Error),
+}
+
+const PACRUNNER_BUS: &str = "org.pacrunner";
+const PACRUNNER_CLIENT_PATH: &str = "/org/pacrunner/client";
+const CLIENT_INTERFACE: &str = "org.pacrunner.Client";
+const FIND_PROXY_FOR_URL: &str = "FindProxyForURL";
+
+const SERVICE_UNKNOWN: &str = "org.freedesktop.DBus.Error.ServiceUnknown";
+const NAME_HAS_NO_OWNER: &str = "org.freedesktop.DBus.Error.NameHasNoOwner";
+
+// Evaluating a PAC script may require fetching it first.
+const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns the proxies to use for `url`, in the format returned by the `FindProxyForURL`
+/// function of PAC scripts, e.g. `PROXY proxy.example.com:3128; DIRECT`.
+pub fn find_proxy_for_url(url: &str, host: &str) -> Result<String> {
+    let connection = crate::get_connection().map_err(Error::ConnectError)?;
+    let proxy = Proxy::new(
+        PACRUNNER_BUS,
+        PACRUNNER_CLIENT_PATH,
+        RPC_TIMEOUT,
+        &*connection,
+    );
+    proxy
+        .method_call(CLIENT_INTERFACE, FIND_PROXY_FOR_URL, (url, host))
+        .map(|(proxies,): (String,)| proxies)
+        .map_err(|error| match error.name() {
+            Some(SERVICE_UNKNOWN) | Some(NAME_HAS_NO_OWNER) => Error::NotAvailable,
+            _ => Error::FindProxyError(error),
+        })
+}
@@ -328,6 +328,25 @@ impl OpenVpnCommand {
                 args.push("255.255.255.255".to_owned());
                 args.push("net_gateway".to_owned());
             }
+            Some(CustomProxy::HttpConnect(ref http_proxy)) => {
+                args.push("--http-proxy".to_owned());
+                args.push(http_proxy.endpoint.ip().to_string());
+                args.push(http_proxy.endpoint.port().to_string());
+
+                if let Some(ref _auth) = http_proxy.auth {
+                    if let Some(ref auth_file) = self.proxy_auth_path {
+                        args.push(auth_file.to_string_lossy().to_string());
+                        args.push("basic".to_owned());
+                    } else {
+                        log::error!("Proxy credentials present but credentials file missing");
+                    }
+                }
+
+                args.push("--route".to_owned());
+                args.push(http_proxy.endpoint.ip().to_string());
+                args.push("255.255.255.255".to_owned());
+                args.push("net_gateway".to_owned());
+            }
             Some(CustomProxy::Shadowsocks(ref ss)) => {
                 args.push("--socks-proxy".to_owned());
                 args.push("127.0.0.1".to_owned());
@@ -457,7 +476,10 @@ impl OpenVpnProcHandle {
 mod tests {
     use super::OpenVpnCommand;
     use std::{ffi::OsString, net::Ipv4Addr};
-    use talpid_types::net::{Endpoint, TransportProtocol};
+    use talpid_types::net::{
+        proxy::{CustomProxy, HttpAuth, HttpConnect},
+        Endpoint, TransportProtocol,
+    };
 
     #[test]
     fn passes_one_remote() {
@@ -484,4 +506,21 @@ mod tests {
         assert!(testee_args.contains(&OsString::from("123")));
         assert!(testee_args.contains(&OsString::from("cde")));
     }
+
+    #[test]
+    fn passes_http_proxy_with_auth() {
+        let proxy = HttpConnect::new_with_authentication(
+            (Ipv4Addr::new(10, 0, 0, 1), 3128),
+            HttpAuth::new("user".to_owned(), "pass".to_owned()).unwrap(),
+        );
+        let testee_args = OpenVpnCommand::new("")
+            .proxy_settings(CustomProxy::HttpConnect(proxy))
+            .proxy_auth("./auth")
+            .get_arguments();
+
+        let expected = ["--http-proxy", "10.0.0.1", "3128", "./auth", "basic"].map(OsString::from);
+        assert!(testee_args
+            .windows(expected.len())
+            .any(|args| args == expected));
+    }
 }
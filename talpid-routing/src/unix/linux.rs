@@ -55,15 +55,45 @@ static SUPPRESS_RULE_V6: LazyLock<RuleMessage> = LazyLock::new(|| {
     v6_rule
 });
 
-fn all_rules(fwmark: u32, table: u32) -> [RuleMessage; 4] {
+/// Return the routing rules that send traffic to the tunnel routing table `table`. If
+/// `include_fwmark` is set, only traffic with that mark is routed via the tunnel. Otherwise, all
+/// traffic except the one marked with `fwmark` is.
+fn all_rules(fwmark: u32, table: u32, include_fwmark: Option<u32>) -> [RuleMessage; 4] {
+    let (tunnel_rule_v4, tunnel_rule_v6) = match include_fwmark {
+        Some(include_fwmark) => (
+            fwmark_rule_v4(include_fwmark, table),
+            fwmark_rule_v6(include_fwmark, table),
+        ),
+        None => (
+            no_fwmark_rule_v4(fwmark, table),
+            no_fwmark_rule_v6(fwmark, table),
+        ),
+    };
     [
-        no_fwmark_rule_v4(fwmark, table),
-        no_fwmark_rule_v6(fwmark, table),
+        tunnel_rule_v4,
+        tunnel_rule_v6,
         SUPPRESS_RULE_V4.clone(),
         SUPPRESS_RULE_V6.clone(),
     ]
 }
 
+fn fwmark_rule_v4(fwmark: u32, table: u32) -> RuleMessage {
+    RuleMessage {
+        header: RuleHeader {
+            family: AF_INET as u8,
+            action: FR_ACT_TO_TBL,
+            ..RuleHeader::default()
+        },
+        nlas: vec![RuleNla::FwMark(fwmark), RuleNla::Table(table)],
+    }
+}
+
+fn fwmark_rule_v6(fwmark: u32, table: u32) -> RuleMessage {
+    let mut v6_rule = fwmark_rule_v4(fwmark, table);
+    v6_rule.header.family = AF_INET6 as u8;
+    v6_rule
+}
+
 fn no_fwmark_rule_v4(fwmark: u32, table: u32) -> RuleMessage {
     RuleMessage {
         header: RuleHeader {
@@ -143,6 +173,10 @@ pub struct RouteManagerImpl {
     /// Firewall mark identifies traffic which shouldn't be routed via the tunnel routing table. It
     /// is used to construct a routing rule.
     fwmark: u32,
+    /// If set, only traffic with this firewall mark is routed via the tunnel routing table.
+    include_fwmark: Option<u32>,
+    /// Whether IPv6 is enabled in the routing rules, if they have been created.
+    routing_rules: Option<bool>,
 }
 
 impl RouteManagerImpl {
@@ -170,6 +204,8 @@ impl RouteManagerImpl {
             added_routes: HashSet::new(),
             table_id,
             fwmark,
+            include_fwmark: None,
+            routing_rules: None,
         };
 
         monitor.clear_routing_rules().await?;
@@ -181,8 +217,9 @@ impl RouteManagerImpl {
         use netlink_packet_route::constants::*;
 
         self.clear_routing_rules().await?;
+        self.routing_rules = Some(enable_ipv6);
 
-        for rule in all_rules(self.fwmark, self.table_id)
+        for rule in all_rules(self.fwmark, self.table_id, self.include_fwmark)
             .iter()
             .filter(|rule| rule.header.family as u16 == AF_INET || enable_ipv6)
         {
@@ -201,8 +238,9 @@ impl RouteManagerImpl {
     }
 
     async fn clear_routing_rules(&mut self) -> Result<()> {
+        self.routing_rules = None;
         let rules = self.get_rules().await?;
-        for rule in all_rules(self.fwmark, self.table_id) {
+        for rule in all_rules(self.fwmark, self.table_id, self.include_fwmark) {
             let mut matching_rule = None;
 
             // `RTM_DELRULE` is way too picky about which rules are considered the same.
@@ -240,6 +278,24 @@ impl RouteManagerImpl {
         Ok(())
     }
 
+    /// Route only traffic marked with `include_fwmark` via the tunnel routing table, or all
+    /// traffic that isn't marked with `fwmark` if it is `None`. Existing routing rules are
+    /// replaced.
+    async fn set_include_fwmark(&mut self, include_fwmark: Option<u32>) -> Result<()> {
+        if self.include_fwmark == include_fwmark {
+            return Ok(());
+        }
+        let routing_rules = self.routing_rules;
+        if routing_rules.is_some() {
+            self.clear_routing_rules().await?;
+        }
+        self.include_fwmark = include_fwmark;
+        if let Some(enable_ipv6) = routing_rules {
+            self.create_routing_rules(enable_ipv6).await?;
+        }
+        Ok(())
+    }
+
     async fn get_rules(&mut self) -> Result<Vec<RuleMessage>> {
         use netlink_packet_route::constants::*;
 
@@ -379,6 +435,9 @@ impl RouteManagerImpl {
             RouteManagerCommand::ClearRoutingRules(result_tx) => {
                 let _ = result_tx.send(self.clear_routing_rules().await);
             }
+            RouteManagerCommand::SetIncludeFwmark(include_fwmark, result_tx) => {
+                let _ = result_tx.send(self.set_include_fwmark(include_fwmark).await);
+            }
             RouteManagerCommand::NewChangeListener(result_tx) => {
                 let _ = result_tx.send(self.listen());
             }
@@ -388,6 +447,9 @@ impl RouteManagerImpl {
             RouteManagerCommand::GetMtuForRoute(ip, result_tx) => {
                 let _ = result_tx.send(self.get_mtu_for_route(ip).await);
             }
+            RouteManagerCommand::GetRoutes(result_tx) => {
+                let _ = result_tx.send(self.get_routes().await);
+            }
             RouteManagerCommand::ClearRoutes => {
                 log::debug!("Clearing routes");
                 self.cleanup_routes().await;
@@ -844,6 +906,44 @@ impl RouteManagerImpl {
             Err(err) => Err(Error::GetRoute(err)),
         }
     }
+
+    async fn get_routes(&mut self) -> Result<Vec<Route>> {
+        let mut routes = vec![];
+        for family in [AF_INET, AF_INET6] {
+            routes.extend(self.get_routes_for_family(family as u8).await?);
+        }
+        Ok(routes)
+    }
+
+    async fn get_routes_for_family(&mut self, family: u8) -> Result<Vec<Route>> {
+        use netlink_packet_route::constants::*;
+
+        let mut message = RouteMessage::default();
+        message.header.address_family = family;
+        let mut req = NetlinkMessage::from(RtnlMessage::GetRoute(message));
+        req.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+
+        let mut response = self.handle.request(req).map_err(Error::Netlink)?;
+
+        let mut routes = vec![];
+        while let Some(message) = response.next().await {
+            match message.payload {
+                NetlinkPayload::InnerMessage(RtnlMessage::NewRoute(route)) => {
+                    // Routes without a node, such as unreachable routes, cannot be represented.
+                    match self.parse_route_message(route) {
+                        Ok(Some(route)) => routes.push(route),
+                        Ok(None) => (),
+                        Err(error) => log::trace!("Skipping route: {error}"),
+                    }
+                }
+                NetlinkPayload::Error(error) => {
+                    return Err(Error::Netlink(rtnetlink::Error::NetlinkError(error)));
+                }
+                _ => (),
+            }
+        }
+        Ok(routes)
+    }
 }
 
 fn ip_to_bytes(addr: IpAddr) -> Vec<u8> {
@@ -934,4 +1034,20 @@ mod test {
         });
         std::mem::drop(manager);
     }
+
+    #[test]
+    fn test_include_fwmark_rules() {
+        let [exclude_v4, exclude_v6, ..] = all_rules(1, 2, None);
+        assert_eq!(exclude_v4.header.flags, FIB_RULE_INVERT);
+        assert_eq!(exclude_v4.nlas, [RuleNla::FwMark(1), RuleNla::Table(2)]);
+        assert_eq!(exclude_v6.header.family, AF_INET6 as u8);
+
+        let [include_v4, include_v6, suppress_v4, suppress_v6] = all_rules(1, 2, Some(3));
+        assert_eq!(include_v4.header.flags & FIB_RULE_INVERT, 0);
+        assert_eq!(include_v4.nlas, [RuleNla::FwMark(3), RuleNla::Table(2)]);
+        assert_eq!(include_v6.header.family, AF_INET6 as u8);
+        assert_eq!(include_v6.nlas, include_v4.nlas);
+        assert_eq!(suppress_v4.nlas, SUPPRESS_RULE_V4.nlas);
+        assert_eq!(suppress_v6.header.family, AF_INET6 as u8);
+    }
 }
@@ -89,6 +89,7 @@ pub(crate) enum RouteManagerCommand {
     Shutdown(oneshot::Sender<()>),
     CreateRoutingRules(bool, oneshot::Sender<Result<(), PlatformError>>),
     ClearRoutingRules(oneshot::Sender<Result<(), PlatformError>>),
+    SetIncludeFwmark(Option<u32>, oneshot::Sender<Result<(), PlatformError>>),
     NewChangeListener(oneshot::Sender<mpsc::UnboundedReceiver<CallbackMessage>>),
     GetMtuForRoute(IpAddr, oneshot::Sender<Result<u16, PlatformError>>),
     /// Attempt to fetch a route for the given destination with an optional firewall mark.
@@ -97,6 +98,8 @@ pub(crate) enum RouteManagerCommand {
         Option<Fwmark>,
         oneshot::Sender<Result<Option<Route>, PlatformError>>,
     ),
+    /// Fetch the routes in all routing tables.
+    GetRoutes(oneshot::Sender<Result<Vec<Route>, PlatformError>>),
 }
 
 /// Commands for the underlying route manager object.
@@ -330,6 +333,24 @@ impl RouteManagerHandle {
             .map_err(Error::PlatformError)
     }
 
+    /// Route only traffic with the firewall mark `include_fwmark` through the tunnel, or all
+    /// traffic that isn't excluded if it is `None`. This replaces any routing rules created by
+    /// [Self::create_routing_rules].
+    #[cfg(target_os = "linux")]
+    pub async fn set_include_fwmark(&self, include_fwmark: Option<u32>) -> Result<(), Error> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .unbounded_send(RouteManagerCommand::SetIncludeFwmark(
+                include_fwmark,
+                response_tx,
+            ))
+            .map_err(|_| Error::RouteManagerDown)?;
+        response_rx
+            .await
+            .map_err(|_| Error::ManagerChannelDown)?
+            .map_err(Error::PlatformError)
+    }
+
     /// Listen for route changes.
     #[cfg(target_os = "linux")]
     pub async fn change_listener(&self) -> Result<impl Stream<Item = CallbackMessage>, Error> {
@@ -373,4 +394,17 @@ impl RouteManagerHandle {
             .map_err(|_| Error::ManagerChannelDown)?
             .map_err(Error::PlatformError)
     }
+
+    /// Return the routes in all routing tables, both IPv4 and IPv6.
+    #[cfg(target_os = "linux")]
+    pub async fn get_routes(&self) -> Result<Vec<Route>, Error> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .unbounded_send(RouteManagerCommand::GetRoutes(response_tx))
+            .map_err(|_| Error::RouteManagerDown)?;
+        response_rx
+            .await
+            .map_err(|_| Error::ManagerChannelDown)?
+            .map_err(Error::PlatformError)
+    }
 }
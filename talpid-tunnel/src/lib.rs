@@ -1,5 +1,5 @@
 use std::{
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::Path,
     sync::{Arc, Mutex},
 };
@@ -80,6 +80,8 @@ pub struct TunnelMetadata {
     pub ipv4_gateway: Ipv4Addr,
     /// The IP to the IPv6 default gateway on the tunnel interface.
     pub ipv6_gateway: Option<Ipv6Addr>,
+    /// Address that the obfuscator relays UDP traffic to the peer through, if any.
+    pub udp_relay: Option<SocketAddr>,
 }
 
 impl TunnelMetadata {
@@ -102,6 +104,8 @@ pub enum TunnelEvent {
     InterfaceUp(TunnelMetadata, AllowedTunnelTraffic),
     /// Sent when the tunnel comes up and is ready for traffic.
     Up(TunnelMetadata),
+    /// Sent when path MTU detection has found the largest MTU that the tunnel can carry.
+    MtuDetected(u16),
     /// Sent when the tunnel goes down, but before destroying the tunnel device.
     Down,
 }
@@ -1,19 +1,77 @@
-use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+use std::{ffi::OsStr, fmt, fs, os::unix::ffi::OsStrExt, path::PathBuf};
 
 pub const SPLIT_TUNNEL_CGROUP_NAME: &str = "mullvad-exclusions";
 
+/// Mechanism used to identify processes that are excluded from the tunnel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionMechanism {
+    /// Excluded processes are in a cgroup v1 `net_cls` cgroup, whose class ID tags their packets.
+    NetCls,
+    /// Excluded processes are in a cgroup v2 cgroup, which the firewall matches by its ID.
+    CgroupV2,
+}
+
+impl fmt::Display for ExclusionMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionMechanism::NetCls => f.write_str("net_cls (cgroup v1)"),
+            ExclusionMechanism::CgroupV2 => f.write_str("cgroup v2"),
+        }
+    }
+}
+
+/// Find the cgroup hierarchy that excluded processes should be placed in, without mounting
+/// anything. An existing net_cls controller is preferred over the cgroup v2 hierarchy.
+pub fn find_exclusion_hierarchy() -> std::io::Result<Option<(ExclusionMechanism, PathBuf)>> {
+    let mounts = fs::read("/proc/mounts")?;
+    Ok(find_exclusion_hierarchy_inner(&mounts))
+}
+
+fn find_exclusion_hierarchy_inner(mounts: &[u8]) -> Option<(ExclusionMechanism, PathBuf)> {
+    find_net_cls_mount_inner(mounts)
+        .map(|path| (ExclusionMechanism::NetCls, path))
+        .or_else(|| {
+            find_cgroup2_mount_inner(mounts).map(|path| (ExclusionMechanism::CgroupV2, path))
+        })
+}
+
 /// Find the path of the cgroup v1 net_cls controller mount if it exists
 pub fn find_net_cls_mount() -> std::io::Result<Option<PathBuf>> {
     let mounts = fs::read("/proc/mounts")?;
     Ok(find_net_cls_mount_inner(&mounts))
 }
 
+/// Find the path of the cgroup v2 hierarchy mount if it exists
+pub fn find_cgroup2_mount() -> std::io::Result<Option<PathBuf>> {
+    let mounts = fs::read("/proc/mounts")?;
+    Ok(find_cgroup2_mount_inner(&mounts))
+}
+
 fn find_net_cls_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
     mounts
         .split(|byte| *byte == b'\n')
         .find_map(parse_mount_line)
 }
 
+fn find_cgroup2_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
+    mounts
+        .split(|byte| *byte == b'\n')
+        .find_map(parse_cgroup2_mount_line)
+}
+
+/// Parse the mount path of a cgroup2 filesystem, such as
+/// `cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime,nsdelegate 0 0`
+fn parse_cgroup2_mount_line(line: &[u8]) -> Option<PathBuf> {
+    let mut parts = line.split(|byte| *byte == b' ');
+    let _device_type = parts.next()?;
+    let mount_path = parts.next()?;
+    let filesystem_type = parts.next()?;
+    if filesystem_type != b"cgroup2" {
+        return None;
+    }
+    Some(PathBuf::from(OsStr::from_bytes(mount_path)))
+}
+
 fn parse_mount_line(line: &[u8]) -> Option<PathBuf> {
     // Each line contains multiple values separated by space.
     // `cgroup /sys/fs/cgroup/net_cls,net_prio cgroup
@@ -72,4 +130,39 @@ cgroup /nope
 
         assert_eq!(find_net_cls_mount_inner(input), None)
     }
+
+    #[test]
+    fn test_find_cgroup2_path() {
+        let input = br#"sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime,nsdelegate 0 0
+"#;
+
+        assert_eq!(
+            find_cgroup2_mount_inner(input),
+            Some(PathBuf::from("/sys/fs/cgroup"))
+        );
+        assert_eq!(
+            find_exclusion_hierarchy_inner(input),
+            Some((
+                ExclusionMechanism::CgroupV2,
+                PathBuf::from("/sys/fs/cgroup")
+            ))
+        );
+    }
+
+    /// An existing net_cls controller takes precedence on hybrid hierarchies
+    #[test]
+    fn test_prefer_net_cls_hierarchy() {
+        let input = br#"cgroup2 /sys/fs/cgroup/unified cgroup2 rw,nosuid,nodev,noexec,relatime 0 0
+cgroup /sys/fs/cgroup/net_cls,net_prio cgroup rw,nosuid,nodev,noexec,relatime,net_cls,net_prio 0 0
+"#;
+
+        assert_eq!(
+            find_exclusion_hierarchy_inner(input),
+            Some((
+                ExclusionMechanism::NetCls,
+                PathBuf::from("/sys/fs/cgroup/net_cls,net_prio")
+            ))
+        );
+    }
 }
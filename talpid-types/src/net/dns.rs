@@ -0,0 +1,369 @@
+//! Encrypted transports and per-domain resolvers for DNS queries.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Path that DNS-over-HTTPS queries are sent to if the URL has none.
+pub const DOH_PATH: &str = "/dns-query";
+/// Default port for DNS-over-HTTPS.
+pub const DOH_PORT: u16 = 443;
+/// Default port for DNS-over-TLS, as assigned by RFC 7858.
+pub const DOT_PORT: u16 = 853;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("Invalid DNS-over-HTTPS URL: {0}")]
+    InvalidDohUrl(String),
+    #[error("Invalid DNS-over-HTTPS path: {0}")]
+    InvalidDohPath(String),
+    #[error("Invalid DNS-over-TLS hostname: {0}")]
+    InvalidHostname(String),
+    #[error("Invalid SPKI pin. Expected a base64 encoded SHA-256 digest")]
+    InvalidSpkiPin,
+    #[error("Invalid domain: {0}")]
+    InvalidDomain(String),
+    #[error("No resolvers given for domain {0}")]
+    NoResolvers(String),
+}
+
+/// Encrypted transport used to reach a set of DNS resolvers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DnsEncryption {
+    pub protocol: EncryptedDnsProtocol,
+    /// If set, the server certificate is accepted if and only if its public key matches the pin.
+    /// The certificate chain is then not validated against the trusted root certificates.
+    #[serde(default)]
+    pub spki_pin: Option<SpkiPin>,
+}
+
+impl fmt::Display for DnsEncryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.protocol)?;
+        if let Some(pin) = &self.spki_pin {
+            write!(f, " (SPKI pin: {pin})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptedDnsProtocol {
+    /// DNS-over-HTTPS (RFC 8484). Queries are sent to `path` on the server.
+    Https {
+        hostname: String,
+        port: u16,
+        #[serde(default = "default_doh_path")]
+        path: String,
+    },
+    /// DNS-over-TLS (RFC 7858).
+    Tls { hostname: String, port: u16 },
+}
+
+impl EncryptedDnsProtocol {
+    /// Parse a DNS-over-HTTPS URL, such as `https://dns.example.com/dns-query`. The path
+    /// defaults to [`DOH_PATH`].
+    pub fn from_doh_url(url: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidDohUrl(url.to_owned());
+
+        let rest = url.strip_prefix("https://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+
+        let (hostname, port) = parse_authority(authority, DOH_PORT).ok_or_else(invalid)?;
+        Self::https(hostname, port, path).map_err(|_| invalid())
+    }
+
+    /// Create a DNS-over-HTTPS protocol that sends queries to `path`, which may include a query
+    /// string. An empty path is replaced by [`DOH_PATH`].
+    pub fn https(hostname: String, port: u16, path: &str) -> Result<Self, Error> {
+        let path = if path.is_empty() { DOH_PATH } else { path };
+        let valid = path.starts_with('/') && path.chars().all(|c| c.is_ascii_graphic() && c != '#');
+        if !valid {
+            return Err(Error::InvalidDohPath(path.to_owned()));
+        }
+        Ok(EncryptedDnsProtocol::Https {
+            hostname,
+            port,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Parse the name of a DNS-over-TLS server, optionally followed by a port, such as
+    /// `dns.example.com` or `dns.example.com:853`.
+    pub fn from_dot_hostname(hostname: &str) -> Result<Self, Error> {
+        let (hostname, port) = parse_authority(hostname, DOT_PORT)
+            .ok_or_else(|| Error::InvalidHostname(hostname.to_owned()))?;
+        Ok(EncryptedDnsProtocol::Tls { hostname, port })
+    }
+
+    /// Name that the server certificate must be valid for.
+    pub fn hostname(&self) -> &str {
+        match self {
+            EncryptedDnsProtocol::Https { hostname, .. } => hostname,
+            EncryptedDnsProtocol::Tls { hostname, .. } => hostname,
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        match self {
+            EncryptedDnsProtocol::Https { port, .. } => *port,
+            EncryptedDnsProtocol::Tls { port, .. } => *port,
+        }
+    }
+}
+
+impl fmt::Display for EncryptedDnsProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (scheme, path, default_port) = match self {
+            EncryptedDnsProtocol::Https { path, .. } => ("https", path.as_str(), DOH_PORT),
+            EncryptedDnsProtocol::Tls { .. } => ("tls", "", DOT_PORT),
+        };
+        let hostname = self.hostname();
+        if hostname.contains(':') {
+            write!(f, "{scheme}://[{hostname}]")?;
+        } else {
+            write!(f, "{scheme}://{hostname}")?;
+        }
+        if self.port() != default_port {
+            write!(f, ":{}", self.port())?;
+        }
+        f.write_str(path)
+    }
+}
+
+fn default_doh_path() -> String {
+    DOH_PATH.to_owned()
+}
+
+/// Parse `host[:port]`, where IPv6 literals must be enclosed in brackets if a port is given.
+fn parse_authority(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let (hostname, port) = match authority.rsplit_once(':') {
+        Some((hostname, port)) if !hostname.contains(':') || hostname.ends_with(']') => {
+            (hostname, port.parse().ok()?)
+        }
+        _ => (authority, default_port),
+    };
+    let hostname = hostname
+        .strip_prefix('[')
+        .and_then(|hostname| hostname.strip_suffix(']'))
+        .unwrap_or(hostname);
+    is_valid_hostname(hostname).then(|| (hostname.to_owned(), port))
+}
+
+/// Send queries for a domain, and all of its subdomains, to a specific set of resolvers instead
+/// of the tunnel DNS servers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedSplitDnsRule")]
+pub struct SplitDnsRule {
+    domain: String,
+    resolvers: Vec<IpAddr>,
+}
+
+/// A [SplitDnsRule] as it is serialized. Deserialized rules are validated by [SplitDnsRule::new].
+#[derive(Deserialize)]
+struct UncheckedSplitDnsRule {
+    domain: String,
+    resolvers: Vec<IpAddr>,
+}
+
+impl TryFrom<UncheckedSplitDnsRule> for SplitDnsRule {
+    type Error = Error;
+
+    fn try_from(rule: UncheckedSplitDnsRule) -> Result<Self, Self::Error> {
+        SplitDnsRule::new(&rule.domain, rule.resolvers)
+    }
+}
+
+impl SplitDnsRule {
+    /// Create a rule for `domain`. A leading `*.` and a trailing `.` are ignored, so
+    /// `*.corp.example` and `corp.example.` both match `corp.example` and its subdomains.
+    pub fn new(domain: &str, resolvers: Vec<IpAddr>) -> Result<Self, Error> {
+        let domain = Self::normalize_domain(domain)?;
+        if resolvers.is_empty() {
+            return Err(Error::NoResolvers(domain));
+        }
+        Ok(SplitDnsRule { domain, resolvers })
+    }
+
+    /// Return `domain` in the form used by rules, as described in [SplitDnsRule::new].
+    pub fn normalize_domain(domain: &str) -> Result<String, Error> {
+        let normalized = domain.strip_prefix("*.").unwrap_or(domain);
+        let normalized = normalized
+            .strip_suffix('.')
+            .unwrap_or(normalized)
+            .to_ascii_lowercase();
+        let valid = !normalized.contains(':')
+            && is_valid_hostname(&normalized)
+            && normalized
+                .split('.')
+                .all(|label| !label.is_empty() && label.len() <= 63);
+        if valid {
+            Ok(normalized)
+        } else {
+            Err(Error::InvalidDomain(domain.to_owned()))
+        }
+    }
+
+    /// Lowercase domain without a trailing dot, such as `corp.example`.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn resolvers(&self) -> &[IpAddr] {
+        &self.resolvers
+    }
+
+    /// Return whether `name` is the domain of this rule or one of its subdomains.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').as_bytes();
+        let domain = self.domain.as_bytes();
+        match name.len().checked_sub(domain.len()) {
+            Some(0) => name.eq_ignore_ascii_case(domain),
+            Some(prefix_len) => {
+                name[prefix_len - 1] == b'.' && name[prefix_len..].eq_ignore_ascii_case(domain)
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for SplitDnsRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ->", self.domain)?;
+        for (i, resolver) in self.resolvers.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{separator}{resolver}")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_hostname(hostname: &str) -> bool {
+    !hostname.is_empty()
+        && hostname
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'))
+}
+
+/// SHA-256 digest of a DER encoded SubjectPublicKeyInfo. Serialized as base64.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SpkiPin([u8; 32]);
+
+impl SpkiPin {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+}
+
+impl From<[u8; 32]> for SpkiPin {
+    fn from(digest: [u8; 32]) -> Self {
+        SpkiPin(digest)
+    }
+}
+
+impl FromStr for SpkiPin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digest = STANDARD.decode(s).map_err(|_| Error::InvalidSpkiPin)?;
+        let digest = <[u8; 32]>::try_from(digest).map_err(|_| Error::InvalidSpkiPin)?;
+        Ok(SpkiPin(digest))
+    }
+}
+
+impl fmt::Display for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+impl fmt::Debug for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SpkiPin({self})")
+    }
+}
+
+impl Serialize for SpkiPin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for SpkiPin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_doh_url() {
+        for (url, hostname, port, path) in [
+            (
+                "https://dns.example.com/dns-query",
+                "dns.example.com",
+                443,
+                "/dns-query",
+            ),
+            (
+                "https://dns.example.com",
+                "dns.example.com",
+                443,
+                "/dns-query",
+            ),
+            (
+                "https://dns.example.com:8443/resolve",
+                "dns.example.com",
+                8443,
+                "/resolve",
+            ),
+            (
+                "https://[2001:db8::1]/dns/query?x=1",
+                "2001:db8::1",
+                443,
+                "/dns/query?x=1",
+            ),
+        ] {
+            let protocol = EncryptedDnsProtocol::from_doh_url(url).unwrap();
+            assert_eq!(
+                protocol,
+                EncryptedDnsProtocol::Https {
+                    hostname: hostname.to_owned(),
+                    port,
+                    path: path.to_owned(),
+                },
+                "{url}"
+            );
+        }
+
+        for url in [
+            "http://dns.example.com/dns-query",
+            "https:///dns-query",
+            "https://dns.example.com/dns query",
+            "https://dns.example.com/dns-query#fragment",
+        ] {
+            assert!(EncryptedDnsProtocol::from_doh_url(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_doh_url_round_trip() {
+        for url in [
+            "https://dns.example.com/dns-query",
+            "https://dns.example.com:8443/resolve",
+        ] {
+            let protocol = EncryptedDnsProtocol::from_doh_url(url).unwrap();
+            assert_eq!(protocol.to_string(), url);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use super::TransportProtocol;
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+/// Shortest prefix allowed for an outbound exception to an IPv4 network.
+const MIN_IPV4_PREFIX: u8 = 8;
+/// Shortest prefix allowed for an outbound exception to an IPv6 network.
+const MIN_IPV6_PREFIX: u8 = 16;
+/// The outbound exceptions may together cover at most 1/2^N of the IPv4 or IPv6 address space.
+/// Otherwise, a few broad exceptions could add up to the whole internet.
+const MAX_COVERAGE_SHARE_LOG2: u32 = 4;
+
+/// Traffic that the firewall should allow regardless of the tunnel state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallException {
+    /// Allow connections to a host or network outside the tunnel, including while the kill
+    /// switch is blocking all other traffic.
+    Outbound {
+        network: IpNetwork,
+        /// Destination port. All ports are allowed if this is `None`.
+        port: Option<u16>,
+        protocol: TransportProtocol,
+    },
+    /// Allow incoming connections to a local port on the tunnel interface.
+    Inbound {
+        port: u16,
+        protocol: TransportProtocol,
+    },
+}
+
+impl FirewallException {
+    /// Reject exceptions that would make the kill switch ineffective or that cannot be enforced.
+    pub fn validate(&self) -> Result<(), FirewallExceptionError> {
+        match *self {
+            FirewallException::Outbound { network, port, .. } => {
+                let min_prefix = match network {
+                    IpNetwork::V4(_) => MIN_IPV4_PREFIX,
+                    IpNetwork::V6(_) => MIN_IPV6_PREFIX,
+                };
+                if network.prefix() < min_prefix {
+                    return Err(FirewallExceptionError::NetworkTooBroad(network));
+                }
+                if network.ip().is_unspecified()
+                    || network.ip().is_loopback()
+                    || network.ip().is_multicast()
+                {
+                    return Err(FirewallExceptionError::InvalidAddress(network));
+                }
+                if port == Some(0) {
+                    return Err(FirewallExceptionError::InvalidPort);
+                }
+            }
+            FirewallException::Inbound { port, .. } => {
+                if port == 0 {
+                    return Err(FirewallExceptionError::InvalidPort);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate each of `exceptions`, and reject them if their outbound networks together cover
+    /// too large a share of the address space. Overlapping networks are counted once each, so
+    /// this errs on the side of rejecting.
+    pub fn validate_all(exceptions: &[FirewallException]) -> Result<(), FirewallExceptionError> {
+        for exception in exceptions {
+            exception.validate()?;
+        }
+
+        let networks: BTreeSet<IpNetwork> = exceptions
+            .iter()
+            .filter_map(|exception| match exception {
+                FirewallException::Outbound { network, .. } => Some(*network),
+                FirewallException::Inbound { .. } => None,
+            })
+            .collect();
+        let (mut ipv4_addresses, mut ipv6_addresses) = (0u128, 0u128);
+        for network in networks {
+            match network {
+                IpNetwork::V4(network) => ipv4_addresses += 1 << (32 - u32::from(network.prefix())),
+                IpNetwork::V6(network) => {
+                    ipv6_addresses =
+                        ipv6_addresses.saturating_add(1 << (128 - u32::from(network.prefix())))
+                }
+            }
+        }
+        if ipv4_addresses > 1 << (32 - MAX_COVERAGE_SHARE_LOG2)
+            || ipv6_addresses > 1 << (128 - MAX_COVERAGE_SHARE_LOG2)
+        {
+            return Err(FirewallExceptionError::TooBroadCombined);
+        }
+        Ok(())
+    }
+
+    /// Keep the exceptions that pass [`Self::validate_all`] when they are added one at a time, in
+    /// order. The dropped exceptions are returned along with the reason they were rejected.
+    pub fn retain_valid(
+        exceptions: &mut Vec<FirewallException>,
+    ) -> Vec<(FirewallException, FirewallExceptionError)> {
+        let mut valid = Vec::with_capacity(exceptions.len());
+        let mut dropped = vec![];
+        for exception in exceptions.drain(..) {
+            valid.push(exception);
+            if let Err(error) = FirewallException::validate_all(&valid) {
+                valid.pop();
+                dropped.push((exception, error));
+            }
+        }
+        *exceptions = valid;
+        dropped
+    }
+}
+
+impl fmt::Display for FirewallException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FirewallException::Outbound {
+                network,
+                port: Some(port),
+                protocol,
+            } => write!(f, "outbound to {network} port {port}/{protocol}"),
+            FirewallException::Outbound {
+                network,
+                port: None,
+                protocol,
+            } => write!(f, "outbound to {network} all {protocol} ports"),
+            FirewallException::Inbound { port, protocol } => {
+                write!(f, "inbound on tunnel port {port}/{protocol}")
+            }
+        }
+    }
+}
+
+/// Returned by [`FirewallException::validate`] for exceptions that may not be added.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum FirewallExceptionError {
+    #[error(
+        "{0} is too broad. The prefix must be at least /{MIN_IPV4_PREFIX} for IPv4 and \
+         /{MIN_IPV6_PREFIX} for IPv6"
+    )]
+    NetworkTooBroad(IpNetwork),
+    #[error("The exceptions would together allow too much traffic to bypass the tunnel")]
+    TooBroadCombined,
+    #[error("{0} is not a unicast address")]
+    InvalidAddress(IpNetwork),
+    #[error("Port 0 is not a valid port")]
+    InvalidPort,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let outbound = |network: &str, port| FirewallException::Outbound {
+            network: network.parse().unwrap(),
+            port,
+            protocol: TransportProtocol::Tcp,
+        };
+
+        assert!(outbound("203.0.113.5/32", Some(22)).validate().is_ok());
+        assert!(outbound("2001:db8::/32", None).validate().is_ok());
+        assert_eq!(
+            outbound("0.0.0.0/0", None).validate(),
+            Err(FirewallExceptionError::NetworkTooBroad(
+                "0.0.0.0/0".parse().unwrap()
+            ))
+        );
+        assert!(outbound("::/0", Some(22)).validate().is_err());
+        assert!(outbound("0.0.0.0/1", None).validate().is_err());
+        assert!(outbound("8000::/1", None).validate().is_err());
+        assert!(outbound("10.0.0.0/8", None).validate().is_ok());
+        assert!(outbound("127.0.0.1/32", Some(22)).validate().is_err());
+        assert!(outbound("224.0.0.1/32", None).validate().is_err());
+        assert_eq!(
+            outbound("203.0.113.5/32", Some(0)).validate(),
+            Err(FirewallExceptionError::InvalidPort)
+        );
+
+        let inbound = |port| FirewallException::Inbound {
+            port,
+            protocol: TransportProtocol::Udp,
+        };
+        assert!(inbound(51820).validate().is_ok());
+        assert!(inbound(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let outbound = |network: &str| FirewallException::Outbound {
+            network: network.parse().unwrap(),
+            port: None,
+            protocol: TransportProtocol::Udp,
+        };
+
+        let private_networks: Vec<_> = ["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16"]
+            .into_iter()
+            .map(outbound)
+            .collect();
+        assert!(FirewallException::validate_all(&private_networks).is_ok());
+
+        // 16 /8 networks cover exactly 1/16 of the IPv4 address space
+        let slash_8_networks: Vec<_> = (1..=17)
+            .map(|octet| outbound(&format!("{octet}.0.0.0/8")))
+            .collect();
+        assert!(FirewallException::validate_all(&slash_8_networks[..16]).is_ok());
+        assert_eq!(
+            FirewallException::validate_all(&slash_8_networks),
+            Err(FirewallExceptionError::TooBroadCombined)
+        );
+
+        let ipv6_networks: Vec<_> = (0x2000..=0x3000)
+            .map(|segment: u16| outbound(&format!("{segment:x}::/16")))
+            .collect();
+        assert!(FirewallException::validate_all(&ipv6_networks[1..]).is_ok());
+        assert_eq!(
+            FirewallException::validate_all(&ipv6_networks),
+            Err(FirewallExceptionError::TooBroadCombined)
+        );
+    }
+
+    #[test]
+    fn test_retain_valid() {
+        let outbound = |network: &str| FirewallException::Outbound {
+            network: network.parse().unwrap(),
+            port: None,
+            protocol: TransportProtocol::Tcp,
+        };
+
+        let mut exceptions: Vec<_> = std::iter::once("0.0.0.0/0".to_owned())
+            .chain((1..=17).map(|octet| format!("{octet}.0.0.0/8")))
+            .map(|network| outbound(&network))
+            .collect();
+        let dropped = FirewallException::retain_valid(&mut exceptions);
+
+        assert_eq!(
+            dropped,
+            [
+                (
+                    outbound("0.0.0.0/0"),
+                    FirewallExceptionError::NetworkTooBroad("0.0.0.0/0".parse().unwrap())
+                ),
+                (
+                    outbound("17.0.0.0/8"),
+                    FirewallExceptionError::TooBroadCombined
+                ),
+            ]
+        );
+        assert_eq!(exceptions.len(), 16);
+        assert!(FirewallException::validate_all(&exceptions).is_ok());
+    }
+}
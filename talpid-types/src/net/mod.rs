@@ -12,6 +12,8 @@ use std::{
 
 use self::proxy::{CustomProxy, Socks5Local};
 
+pub mod dns;
+pub mod firewall;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
@@ -72,7 +74,9 @@ impl TunnelParameters {
                     .map(|proxy| proxy.get_remote_endpoint()),
                 obfuscation: None,
                 entry_endpoint: None,
+                middle_endpoints: vec![],
                 tunnel_interface: None,
+                mtu: None,
                 #[cfg(daita)]
                 daita: false,
             },
@@ -89,7 +93,9 @@ impl TunnelParameters {
                     .connection
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
+                middle_endpoints: params.connection.get_middle_endpoints(),
                 tunnel_interface: None,
+                mtu: None,
                 #[cfg(daita)]
                 daita: params.options.daita,
             },
@@ -200,7 +206,13 @@ pub struct TunnelEndpoint {
     pub proxy: Option<proxy::ProxyEndpoint>,
     pub obfuscation: Option<ObfuscationEndpoint>,
     pub entry_endpoint: Option<Endpoint>,
+    /// Relays between the entry and the exit relay, in the order that traffic passes through them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middle_endpoints: Vec<Endpoint>,
     pub tunnel_interface: Option<String>,
+    /// MTU of the tunnel interface, once it has been verified by path MTU detection.
+    #[serde(default)]
+    pub mtu: Option<u16>,
     #[cfg(daita)]
     pub daita: bool,
 }
@@ -219,6 +231,9 @@ impl fmt::Display for TunnelEndpoint {
                 }
             }
             TunnelType::Wireguard => {
+                for middle_endpoint in self.middle_endpoints.iter().rev() {
+                    write!(f, " via {middle_endpoint}")?;
+                }
                 if let Some(ref entry_endpoint) = self.entry_endpoint {
                     write!(f, " via {entry_endpoint}")?;
                 }
@@ -237,6 +252,8 @@ pub enum ObfuscationType {
     #[serde(rename = "udp2tcp")]
     Udp2Tcp,
     Shadowsocks,
+    Quic,
+    Socks5,
 }
 
 impl fmt::Display for ObfuscationType {
@@ -244,6 +261,8 @@ impl fmt::Display for ObfuscationType {
         match self {
             ObfuscationType::Udp2Tcp => "Udp2Tcp".fmt(f),
             ObfuscationType::Shadowsocks => "Shadowsocks".fmt(f),
+            ObfuscationType::Quic => "QUIC".fmt(f),
+            ObfuscationType::Socks5 => "SOCKS5".fmt(f),
         }
     }
 }
@@ -272,6 +291,20 @@ impl From<&ObfuscatorConfig> for ObfuscationEndpoint {
                 },
                 ObfuscationType::Shadowsocks,
             ),
+            ObfuscatorConfig::Quic { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Udp,
+                },
+                ObfuscationType::Quic,
+            ),
+            ObfuscatorConfig::Socks5 { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Tcp,
+                },
+                ObfuscationType::Socks5,
+            ),
         };
 
         ObfuscationEndpoint {
@@ -434,6 +467,9 @@ pub enum AllowedTunnelTraffic {
     /// Only allow communication with these two specific endpoints. The intended use case for this
     /// is while negotiating for example a PSK with both the entry & exit relays in a multihop setup.
     Two(Endpoint, Endpoint),
+    /// Only allow communication with these three specific endpoints. This is used while
+    /// negotiating with the exit relay of a multihop circuit with a middle relay.
+    Three(Endpoint, Endpoint, Endpoint),
 }
 
 impl AllowedTunnelTraffic {
@@ -454,6 +490,13 @@ impl fmt::Display for AllowedTunnelTraffic {
                 f.write_str(", ")?;
                 endpoint2.fmt(f)
             }
+            AllowedTunnelTraffic::Three(endpoint1, endpoint2, endpoint3) => {
+                endpoint1.fmt(f)?;
+                f.write_str(", ")?;
+                endpoint2.fmt(f)?;
+                f.write_str(", ")?;
+                endpoint3.fmt(f)
+            }
         }
     }
 }
@@ -625,3 +668,17 @@ impl Connectivity {
         matches!(self, Connectivity::Status { connected: false })
     }
 }
+
+/// Identifying details about the physical network the host is attached to.
+///
+/// Any of the fields may be missing if they could not be determined on the
+/// current platform or network.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkInfo {
+    /// Name of the interface that the default route goes through.
+    pub interface: Option<String>,
+    /// Link layer address of the default gateway.
+    pub gateway_mac: Option<[u8; 6]>,
+    /// Domain name handed out by the DHCP server.
+    pub dhcp_domain: Option<String>,
+}
@@ -1,22 +1,41 @@
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
-use super::{Endpoint, TransportProtocol};
+use super::{proxy::SocksAuth, Endpoint, TransportProtocol};
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Serialize, Debug)]
 pub enum ObfuscatorConfig {
-    Udp2Tcp { endpoint: SocketAddr },
-    Shadowsocks { endpoint: SocketAddr },
+    Udp2Tcp {
+        endpoint: SocketAddr,
+    },
+    Shadowsocks {
+        endpoint: SocketAddr,
+    },
+    /// WireGuard over QUIC datagrams. `hostname` is used to verify the server certificate.
+    Quic {
+        endpoint: SocketAddr,
+        hostname: String,
+    },
+    /// WireGuard relayed through a SOCKS5 proxy using UDP ASSOCIATE. `endpoint` is the TCP
+    /// endpoint of the proxy and `wireguard_endpoint` is the relay that the proxy forwards to.
+    Socks5 {
+        endpoint: SocketAddr,
+        auth: Option<SocksAuth>,
+        wireguard_endpoint: SocketAddr,
+    },
 }
 
 impl ObfuscatorConfig {
     pub fn get_obfuscator_endpoint(&self) -> Endpoint {
         match self {
-            ObfuscatorConfig::Udp2Tcp { endpoint } => Endpoint {
-                address: *endpoint,
-                protocol: TransportProtocol::Tcp,
-            },
-            ObfuscatorConfig::Shadowsocks { endpoint } => Endpoint {
+            ObfuscatorConfig::Udp2Tcp { endpoint } | ObfuscatorConfig::Socks5 { endpoint, .. } => {
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Tcp,
+                }
+            }
+            ObfuscatorConfig::Shadowsocks { endpoint }
+            | ObfuscatorConfig::Quic { endpoint, .. } => Endpoint {
                 address: *endpoint,
                 protocol: TransportProtocol::Udp,
             },
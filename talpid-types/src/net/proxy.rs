@@ -9,6 +9,9 @@ pub enum Error {
     /// Validation of SOCKS5 username or password failed.
     #[error("Invalid SOCKS5 authentication credentials: {0}")]
     InvalidSocksAuthValues(&'static str),
+    /// Validation of HTTP proxy username or password failed.
+    #[error("Invalid HTTP proxy authentication credentials: {0}")]
+    InvalidHttpAuthValues(&'static str),
 }
 
 /// Types of bridges that can be used to proxy a connection to a tunnel
@@ -44,6 +47,7 @@ pub enum CustomProxy {
     Shadowsocks(Shadowsocks),
     Socks5Local(Socks5Local),
     Socks5Remote(Socks5Remote),
+    HttpConnect(HttpConnect),
 }
 
 impl CustomProxy {
@@ -61,6 +65,10 @@ impl CustomProxy {
                 endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
                 proxy_type: ProxyType::Shadowsocks,
             },
+            CustomProxy::HttpConnect(settings) => ProxyEndpoint {
+                endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
+                proxy_type: ProxyType::Custom,
+            },
         }
     }
 }
@@ -83,6 +91,12 @@ impl From<Shadowsocks> for CustomProxy {
     }
 }
 
+impl From<HttpConnect> for CustomProxy {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Shadowsocks {
     pub endpoint: SocketAddr,
@@ -105,6 +119,73 @@ pub struct Socks5Remote {
     pub auth: Option<SocksAuth>,
 }
 
+/// A remote HTTP proxy which relays TCP connections using the `CONNECT` method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct HttpConnect {
+    pub endpoint: SocketAddr,
+    pub auth: Option<HttpAuth>,
+    /// Whether to talk to the proxy over TLS. The certificate of the proxy must be valid for its
+    /// IP address.
+    #[serde(default)]
+    pub tls: bool,
+}
+
+/// Credentials for HTTP Basic authentication against a proxy, as described in
+/// RFC 7617: <https://datatracker.ietf.org/doc/html/rfc7617>.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct HttpAuth {
+    username: String,
+    password: String,
+}
+
+impl HttpAuth {
+    /// Validate an HTTP Basic authentication.
+    ///
+    /// The username may not be empty, and neither the username nor the password may contain
+    /// control characters. The username may also not contain a colon, since that separates it
+    /// from the password.
+    ///
+    /// ```
+    /// use talpid_types::net::proxy::HttpAuth;
+    ///
+    /// assert!(HttpAuth::new("FooBar".to_string(), "hunter2".to_string()).is_ok());
+    /// assert!(HttpAuth::new("".to_string(), "hunter2".to_string()).is_err());
+    /// assert!(HttpAuth::new("Foo:Bar".to_string(), "hunter2".to_string()).is_err());
+    /// assert!(HttpAuth::new("FooBar".to_string(), "hunter\n2".to_string()).is_err());
+    /// ```
+    pub fn new(username: String, password: String) -> Result<Self, Error> {
+        if username.is_empty() {
+            return Err(Error::InvalidHttpAuthValues("Username may not be empty"));
+        }
+        if username.contains(':') {
+            return Err(Error::InvalidHttpAuthValues(
+                "Username may not contain a colon",
+            ));
+        }
+        if username
+            .chars()
+            .chain(password.chars())
+            .any(char::is_control)
+        {
+            return Err(Error::InvalidHttpAuthValues(
+                "Username and password may not contain control characters",
+            ));
+        }
+
+        Ok(HttpAuth { username, password })
+    }
+
+    /// Read the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Read the password.
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
 /// A valid SOCKS5 username/password authentication according to
 /// RFC 1929: <https://datatracker.ietf.org/doc/html/rfc1929>.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -233,6 +314,27 @@ impl Socks5Remote {
     }
 }
 
+impl HttpConnect {
+    pub fn new<I: Into<SocketAddr>>(endpoint: I) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            auth: None,
+            tls: false,
+        }
+    }
+
+    pub fn new_with_authentication<I: Into<SocketAddr>>(
+        endpoint: I,
+        authentication: HttpAuth,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            auth: Some(authentication),
+            tls: false,
+        }
+    }
+}
+
 /// List of ciphers usable by a Shadowsocks proxy.
 pub const SHADOWSOCKS_CIPHERS: [&str; 19] = [
     // Stream ciphers.
@@ -6,6 +6,8 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
+    time::{Duration, SystemTime},
 };
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -34,6 +36,9 @@ impl TunnelParameters {
 pub struct ConnectionConfig {
     pub tunnel: TunnelConfig,
     pub peer: PeerConfig,
+    /// Peers between `peer` and `exit_peer`, in the order that traffic passes through them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middle_peers: Vec<PeerConfig>,
     pub exit_peer: Option<PeerConfig>,
     /// Gateway used by the tunnel (a private address).
     pub ipv4_gateway: Ipv4Addr,
@@ -50,6 +55,16 @@ impl ConnectionConfig {
         }
     }
 
+    pub fn get_middle_endpoints(&self) -> Vec<Endpoint> {
+        self.middle_peers
+            .iter()
+            .map(|peer| Endpoint {
+                address: peer.endpoint,
+                protocol: TransportProtocol::Udp,
+            })
+            .collect()
+    }
+
     pub fn get_exit_endpoint(&self) -> Option<Endpoint> {
         self.exit_peer.as_ref().map(|peer| Endpoint {
             address: peer.endpoint,
@@ -66,10 +81,10 @@ pub struct PeerConfig {
     pub allowed_ips: Vec<IpNetwork>,
     /// IP address of the WireGuard server.
     pub endpoint: SocketAddr,
-    /// Preshared key (PSK). The PSK should never be persisted, so it does not serialize
-    /// or deserialize. A PSK is only used with quantum-resistant tunnels and are then
-    /// ephemeral and living in memory only.
-    #[serde(skip)]
+    /// Preshared key (PSK). Quantum-resistant tunnels negotiate an ephemeral PSK which is only
+    /// set on the running tunnel config and never ends up here. The PSK stored here is a static
+    /// one that belongs to a custom endpoint, so it is persisted along with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub psk: Option<PresharedKey>,
     /// Enable constant packet sizes for `entry_peer``
     #[cfg(daita)]
@@ -94,6 +109,160 @@ pub struct TunnelOptions {
     /// Enable DAITA during tunnel config
     #[cfg(daita)]
     pub daita: bool,
+    /// Timeouts used to decide whether the tunnel is working
+    pub connectivity: ConnectivityCheckOptions,
+}
+
+/// Parameters for the connectivity check that runs while a WireGuard tunnel is up. If the check
+/// fails, the tunnel is considered broken and a new connection attempt is made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConnectivityCheckOptions {
+    /// Time to wait for traffic when establishing a connection. This doubles after each failed
+    /// attempt, up to `ping_timeout`.
+    pub establish_timeout: Duration,
+    /// Time to wait for traffic after the first ping is sent before the connection is considered
+    /// lost.
+    pub ping_timeout: Duration,
+    /// Time without any traffic after which pings are sent to check the connection.
+    pub traffic_timeout: Duration,
+    /// Time between pings.
+    pub ping_interval: Duration,
+    /// Host that is pinged inside the tunnel.
+    pub ping_target: PingTarget,
+}
+
+impl ConnectivityCheckOptions {
+    pub const DEFAULT: Self = Self {
+        establish_timeout: Duration::from_secs(4),
+        ping_timeout: Duration::from_secs(15),
+        traffic_timeout: Duration::from_secs(120),
+        ping_interval: Duration::from_secs(3),
+        ping_target: PingTarget::Gateway,
+    };
+
+    pub const ESTABLISH_TIMEOUT_RANGE: RangeInclusive<Duration> =
+        Duration::from_secs(1)..=Duration::from_secs(60);
+    pub const PING_TIMEOUT_RANGE: RangeInclusive<Duration> =
+        Duration::from_secs(5)..=Duration::from_secs(300);
+    pub const TRAFFIC_TIMEOUT_RANGE: RangeInclusive<Duration> =
+        Duration::from_secs(10)..=Duration::from_secs(3600);
+    pub const PING_INTERVAL_RANGE: RangeInclusive<Duration> =
+        Duration::from_secs(1)..=Duration::from_secs(30);
+
+    /// Check that all timeouts are within their bounds and consistent with each other.
+    pub fn validate(&self) -> Result<(), ConnectivityCheckError> {
+        let check_range = |name, value: Duration, range: RangeInclusive<Duration>| {
+            if range.contains(&value) {
+                Ok(())
+            } else {
+                Err(ConnectivityCheckError::OutOfRange {
+                    name,
+                    value: value.as_secs(),
+                    min: range.start().as_secs(),
+                    max: range.end().as_secs(),
+                })
+            }
+        };
+        check_range(
+            "establish timeout",
+            self.establish_timeout,
+            Self::ESTABLISH_TIMEOUT_RANGE,
+        )?;
+        check_range("ping timeout", self.ping_timeout, Self::PING_TIMEOUT_RANGE)?;
+        check_range(
+            "traffic timeout",
+            self.traffic_timeout,
+            Self::TRAFFIC_TIMEOUT_RANGE,
+        )?;
+        check_range(
+            "ping interval",
+            self.ping_interval,
+            Self::PING_INTERVAL_RANGE,
+        )?;
+
+        if self.establish_timeout > self.ping_timeout {
+            return Err(ConnectivityCheckError::EstablishExceedsPingTimeout);
+        }
+        if self.ping_interval >= self.ping_timeout {
+            return Err(ConnectivityCheckError::IntervalExceedsPingTimeout);
+        }
+        if let PingTarget::Address(address) = self.ping_target {
+            if address.is_unspecified() || address.is_broadcast() || address.is_multicast() {
+                return Err(ConnectivityCheckError::InvalidPingTarget(address));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ConnectivityCheckOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Host to send ICMP echo requests to when checking the connectivity of a tunnel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PingTarget {
+    /// The gateway of the relay that the tunnel exits through
+    #[default]
+    Gateway,
+    /// Some other host that is reachable inside the tunnel
+    Address(Ipv4Addr),
+}
+
+impl fmt::Display for PingTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingTarget::Gateway => f.write_str("gateway"),
+            PingTarget::Address(address) => address.fmt(f),
+        }
+    }
+}
+
+impl std::str::FromStr for PingTarget {
+    type Err = std::net::AddrParseError;
+
+    /// Parses either `gateway` or an IPv4 address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("gateway") {
+            Ok(PingTarget::Gateway)
+        } else {
+            s.parse().map(PingTarget::Address)
+        }
+    }
+}
+
+/// Returned by [`ConnectivityCheckOptions::validate`] for options that may not be used.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectivityCheckError {
+    #[error("The {name} must be between {min} and {max} seconds, got {value}")]
+    OutOfRange {
+        name: &'static str,
+        value: u64,
+        min: u64,
+        max: u64,
+    },
+    #[error("The establish timeout may not exceed the ping timeout")]
+    EstablishExceedsPingTimeout,
+    #[error("The ping interval must be shorter than the ping timeout")]
+    IntervalExceedsPingTimeout,
+    #[error("{0} is not a valid ping target")]
+    InvalidPingTarget(Ipv4Addr),
+}
+
+/// Traffic counters for a single peer of a running WireGuard tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerStats {
+    pub public_key: PublicKey,
+    /// Number of bytes sent to the peer since the tunnel was started
+    pub tx_bytes: u64,
+    /// Number of bytes received from the peer since the tunnel was started
+    pub rx_bytes: u64,
+    /// Time of the most recent completed handshake, if any
+    pub last_handshake: Option<SystemTime>,
 }
 
 /// Wireguard x25519 private key
@@ -273,6 +442,14 @@ impl PresharedKey {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.as_bytes())
+    }
+
+    pub fn from_base64(key: &str) -> Result<Self, InvalidKey> {
+        key_from_base64(key)
+    }
 }
 
 impl From<Box<[u8; 32]>> for PresharedKey {
@@ -281,9 +458,34 @@ impl From<Box<[u8; 32]>> for PresharedKey {
     }
 }
 
+impl From<[u8; 32]> for PresharedKey {
+    fn from(key: [u8; 32]) -> PresharedKey {
+        PresharedKey(Box::new(key))
+    }
+}
+
+impl Serialize for PresharedKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PresharedKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_key(deserializer)
+    }
+}
+
 impl fmt::Debug for PresharedKey {
+    // PSKs of custom endpoints are long-lived secrets, so they must not end up in logs
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", &STANDARD.encode(self.as_bytes()))
+        f.debug_tuple("PresharedKey").field(&"<redacted>").finish()
     }
 }
 
@@ -314,3 +516,63 @@ fn key_from_base64<K: From<[u8; 32]>>(key: &str) -> Result<K, InvalidKey> {
     key.copy_from_slice(&bytes);
     Ok(From::from(key))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_psk_debug_is_redacted() {
+        let psk = PresharedKey::from([7u8; 32]);
+        let debug = format!("{:?}", Some(psk.clone()));
+        assert!(!debug.contains(&psk.to_base64()));
+    }
+
+    #[test]
+    fn test_validate_connectivity_check() {
+        assert!(ConnectivityCheckOptions::DEFAULT.validate().is_ok());
+
+        let options = ConnectivityCheckOptions {
+            establish_timeout: Duration::from_secs(30),
+            ping_timeout: Duration::from_secs(60),
+            ping_target: PingTarget::Address(Ipv4Addr::new(10, 64, 0, 1)),
+            ..ConnectivityCheckOptions::DEFAULT
+        };
+        assert!(options.validate().is_ok());
+
+        let options = ConnectivityCheckOptions {
+            establish_timeout: Duration::ZERO,
+            ..ConnectivityCheckOptions::DEFAULT
+        };
+        assert!(matches!(
+            options.validate(),
+            Err(ConnectivityCheckError::OutOfRange { .. })
+        ));
+
+        let options = ConnectivityCheckOptions {
+            establish_timeout: Duration::from_secs(20),
+            ..ConnectivityCheckOptions::DEFAULT
+        };
+        assert_eq!(
+            options.validate(),
+            Err(ConnectivityCheckError::EstablishExceedsPingTimeout)
+        );
+
+        let options = ConnectivityCheckOptions {
+            ping_timeout: Duration::from_secs(5),
+            ping_interval: Duration::from_secs(5),
+            establish_timeout: Duration::from_secs(5),
+            ..ConnectivityCheckOptions::DEFAULT
+        };
+        assert_eq!(
+            options.validate(),
+            Err(ConnectivityCheckError::IntervalExceedsPingTimeout)
+        );
+
+        let options = ConnectivityCheckOptions {
+            ping_target: PingTarget::Address(Ipv4Addr::BROADCAST),
+            ..ConnectivityCheckOptions::DEFAULT
+        };
+        assert!(options.validate().is_err());
+    }
+}
@@ -1,4 +1,25 @@
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf};
+
+/// Which traffic split tunneling applies to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitTunnelMode {
+    /// Traffic of the split processes is excluded from the tunnel.
+    #[default]
+    Exclude,
+    /// Only traffic of the split processes uses the tunnel. All other traffic is excluded.
+    Include,
+}
+
+impl fmt::Display for SplitTunnelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitTunnelMode::Exclude => f.write_str("exclude"),
+            SplitTunnelMode::Include => f.write_str("include"),
+        }
+    }
+}
 
 /// A process that is being excluded from the tunnel.
 #[derive(Debug, Clone)]
@@ -1,7 +1,7 @@
 use std::{
     borrow::Cow,
     ffi::CString,
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 use talpid_types::net::wireguard::{PeerConfig, PrivateKey};
 use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, GenericTunnelOptions};
@@ -17,6 +17,9 @@ pub struct Config {
     pub tunnel: wireguard::TunnelConfig,
     /// Entry peer
     pub entry_peer: wireguard::PeerConfig,
+    /// Peers between the entry and exit peer, in the order that traffic passes through them. Each
+    /// peer is reached through the one before it.
+    pub middle_peers: Vec<wireguard::PeerConfig>,
     /// Multihop exit peer
     pub exit_peer: Option<wireguard::PeerConfig>,
     /// IPv4 gateway
@@ -33,10 +36,14 @@ pub struct Config {
     pub enable_ipv6: bool,
     /// Obfuscator config to be used for reaching the relay.
     pub obfuscator_config: Option<ObfuscatorConfig>,
+    /// Address that the running obfuscator relays UDP traffic through, if any.
+    pub udp_relay: Option<SocketAddr>,
     /// Enable quantum-resistant PSK exchange
     pub quantum_resistant: bool,
     /// Enable DAITA
     pub daita: bool,
+    /// Parameters for the connectivity check
+    pub connectivity: wireguard::ConnectivityCheckOptions,
 }
 
 /// Configuration errors
@@ -92,6 +99,7 @@ impl Config {
         let mut config = Config {
             tunnel,
             entry_peer: connection.peer.clone(),
+            middle_peers: connection.middle_peers.clone(),
             exit_peer: connection.exit_peer.clone(),
             ipv4_gateway: connection.ipv4_gateway,
             ipv6_gateway,
@@ -101,11 +109,13 @@ impl Config {
             #[cfg(target_os = "linux")]
             enable_ipv6: generic_options.enable_ipv6,
             obfuscator_config: obfuscator_config.to_owned(),
+            udp_relay: None,
             quantum_resistant: wg_options.quantum_resistant,
             #[cfg(daita)]
             daita: wg_options.daita,
             #[cfg(not(daita))]
             daita: false,
+            connectivity: wg_options.connectivity,
         };
 
         for peer in config.peers_mut() {
@@ -150,6 +160,7 @@ impl Config {
         self.exit_peer
             .as_ref()
             .into_iter()
+            .chain(self.middle_peers.iter().rev())
             .chain(std::iter::once(&self.entry_peer))
     }
 
@@ -158,6 +169,7 @@ impl Config {
         self.exit_peer
             .as_mut()
             .into_iter()
+            .chain(self.middle_peers.iter_mut().rev())
             .chain(std::iter::once(&mut self.entry_peer))
     }
 
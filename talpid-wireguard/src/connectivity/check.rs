@@ -2,6 +2,7 @@ use std::net::Ipv4Addr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use talpid_types::net::wireguard::{ConnectivityCheckOptions, PingTarget};
 use tokio::sync::broadcast;
 use tokio::time::Instant;
 
@@ -28,18 +29,21 @@ use pinger::Pinger;
 /// timeout. A connection is considered to be established the first time an increase in incoming
 /// traffic is observed.
 ///
-/// The connectivity monitor will start sending pings and start the countdown to `PING_TIMEOUT` in
+/// The timeouts are taken from [ConnectivityCheckOptions].
+///
+/// The connectivity monitor will start sending pings and start the countdown to `ping_timeout` in
 /// the following cases:
 /// - In case that we have observed a bump in the outgoing traffic but no corresponding incoming
 ///   traffic for longer than `BYTES_RX_TIMEOUT`, then the monitor will start pinging.
 /// - In case that no increase in outgoing or incoming traffic has been observed for longer than
-///   `TRAFFIC_TIMEOUT`, then the monitor will start pinging as well.
+///   `traffic_timeout`, then the monitor will start pinging as well.
 ///
 /// Once a connection established, a connection is only considered broken once the connectivity
-/// monitor has started pinging and no traffic has been received for a duration of `PING_TIMEOUT`.
+/// monitor has started pinging and no traffic has been received for a duration of `ping_timeout`.
 pub struct Check {
     conn_state: ConnState,
     ping_state: PingState,
+    options: ConnectivityCheckOptions,
     cancel_receiver: CancelReceiver,
     retry_attempt: u32,
 }
@@ -95,12 +99,19 @@ impl CancelToken {
 }
 
 impl Check {
+    /// Create a new [Check]. Unless another ping target is configured in `options`, pings are sent
+    /// to `gateway`.
     pub fn new(
-        addr: Ipv4Addr,
+        gateway: Ipv4Addr,
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
+        options: ConnectivityCheckOptions,
         retry_attempt: u32,
         cancel_receiver: CancelReceiver,
     ) -> Result<Check, Error> {
+        let addr = match options.ping_target {
+            PingTarget::Gateway => gateway,
+            PingTarget::Address(addr) => addr,
+        };
         Ok(Check {
             conn_state: ConnState::new(Instant::now(), Default::default()),
             ping_state: PingState::new(
@@ -108,6 +119,7 @@ impl Check {
                 #[cfg(any(target_os = "macos", target_os = "linux"))]
                 interface,
             )?,
+            options,
             retry_attempt,
             cancel_receiver,
         })
@@ -121,6 +133,7 @@ impl Check {
             Check {
                 conn_state,
                 ping_state,
+                options: ConnectivityCheckOptions::default(),
                 retry_attempt: 0,
                 cancel_receiver,
             },
@@ -142,9 +155,9 @@ impl Check {
             .map_err(Error::PingError)?;
         self.establish_connectivity_inner(
             self.retry_attempt,
-            ESTABLISH_TIMEOUT,
+            self.options.establish_timeout,
             ESTABLISH_TIMEOUT_MULTIPLIER,
-            MAX_ESTABLISH_TIMEOUT,
+            self.options.ping_timeout,
             tunnel_handle,
         )
         .await
@@ -176,6 +189,7 @@ impl Check {
                 if Self::check_connectivity_interval(
                     &mut self.conn_state,
                     &mut self.ping_state,
+                    &self.options,
                     Instant::now(),
                     check_timeout,
                     tunnel_handle,
@@ -221,8 +235,9 @@ impl Check {
         Self::check_connectivity_interval(
             &mut self.conn_state,
             &mut self.ping_state,
+            &self.options,
             now,
-            PING_TIMEOUT,
+            self.options.ping_timeout,
             tunnel_handle,
         )
         .await
@@ -232,6 +247,7 @@ impl Check {
     async fn check_connectivity_interval(
         conn_state: &mut ConnState,
         ping_state: &mut PingState,
+        options: &ConnectivityCheckOptions,
         now: Instant,
         timeout: Duration,
         tunnel_handle: &TunnelType,
@@ -247,7 +263,7 @@ impl Check {
                     return Ok(true);
                 }
 
-                Self::maybe_send_ping(conn_state, ping_state, now).await?;
+                Self::maybe_send_ping(conn_state, ping_state, options, now).await?;
                 Ok(!ping_state.ping_timed_out(timeout) && conn_state.connected())
             }
         }
@@ -268,16 +284,18 @@ impl Check {
     async fn maybe_send_ping(
         conn_state: &mut ConnState,
         ping_state: &mut PingState,
+        options: &ConnectivityCheckOptions,
         now: Instant,
     ) -> Result<(), Error> {
         // Only send out a ping if we haven't received a byte in a while or no traffic has flowed
-        // in the last 2 minutes, but if a ping already has been sent out, only send one out every
-        // 3 seconds.
-        if (conn_state.rx_timed_out() || conn_state.traffic_timed_out())
+        // for `traffic_timeout`, but if a ping already has been sent out, only send one out every
+        // `ping_interval`.
+        if (conn_state.rx_timed_out() || conn_state.traffic_timed_out(options.traffic_timeout))
             && ping_state
                 .initial_ping_timestamp
                 .map(|initial_ping_timestamp| {
-                    initial_ping_timestamp.elapsed() / ping_state.num_pings_sent < SECONDS_PER_PING
+                    initial_ping_timestamp.elapsed() / ping_state.num_pings_sent
+                        < options.ping_interval
                 })
                 .unwrap_or(true)
         {
@@ -446,17 +464,14 @@ impl ConnState {
     }
 
     // check if no bytes have been sent or received in a while
-    pub fn traffic_timed_out(&self) -> bool {
+    pub fn traffic_timed_out(&self, timeout: Duration) -> bool {
         match self {
             ConnState::Connecting { .. } => self.rx_timed_out(),
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
                 ..
-            } => {
-                rx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
-                    || tx_timestamp.elapsed() >= TRAFFIC_TIMEOUT
-            }
+            } => rx_timestamp.elapsed() >= timeout || tx_timestamp.elapsed() >= timeout,
         }
     }
 
@@ -480,7 +495,7 @@ mod test {
 
         assert!(!conn_state.connected());
         assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.traffic_timed_out(TRAFFIC_TIMEOUT));
     }
 
     /// Test if ConnState::Connecting will timeout after not receiving any traffic after
@@ -492,7 +507,7 @@ mod test {
 
         assert!(!conn_state.connected());
         assert!(conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(conn_state.traffic_timed_out(TRAFFIC_TIMEOUT));
     }
 
     /// Test if ConnState::Connecting correctly transitions into ConnState::Connected if traffic is
@@ -507,13 +522,14 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(Instant::now(), stats);
 
         assert!(conn_state.connected());
         assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.traffic_timed_out(TRAFFIC_TIMEOUT));
     }
 
     /// Test if ConnState::Connected correctly times out after TRAFFIC_TIMEOUT when no traffic is
@@ -532,13 +548,14 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(connect_time, stats);
 
         assert!(conn_state.connected());
         assert!(!conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(conn_state.traffic_timed_out(TRAFFIC_TIMEOUT));
     }
 
     /// Test if ConnState::Connected correctly times out after BYTES_RX_TIMEOUT when no incoming
@@ -556,6 +573,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(start, stats);
@@ -567,13 +585,14 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 1,
+                last_handshake: None,
             },
         );
         conn_state.update(update_time, stats);
 
         assert!(conn_state.connected());
         assert!(conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.traffic_timed_out(TRAFFIC_TIMEOUT));
     }
 
     #[tokio::test]
@@ -591,9 +610,14 @@ mod test {
         // Mock the state - connectivity has been established
         checker.conn_state = connected_state(start);
         // A ping was sent to verify connectivity
-        Check::maybe_send_ping(&mut checker.conn_state, &mut checker.ping_state, start)
-            .await
-            .unwrap();
+        Check::maybe_send_ping(
+            &mut checker.conn_state,
+            &mut checker.ping_state,
+            &checker.options,
+            start,
+        )
+        .await
+        .unwrap();
         assert!(!checker.check_connectivity(now, &tunnel).await.unwrap())
     }
 
@@ -648,6 +672,7 @@ mod test {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 MockTunnel::new(move || Ok(tunnel_stats.clone())).boxed()
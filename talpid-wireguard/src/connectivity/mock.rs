@@ -1,5 +1,7 @@
 use std::future::Future;
 use std::pin::Pin;
+use std::time::Duration;
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
 use tokio::time::Instant;
 
 use super::check::{CancelToken, ConnState, PingState};
@@ -12,6 +14,9 @@ use pinger::Pinger;
 // Convenient re-exports
 pub use crate::stats::{Stats, StatsMap};
 
+pub const TRAFFIC_TIMEOUT: Duration = ConnectivityCheckOptions::DEFAULT.traffic_timeout;
+pub const PING_TIMEOUT: Duration = ConnectivityCheckOptions::DEFAULT.ping_timeout;
+
 #[derive(Default)]
 pub(crate) struct MockPinger {
     on_send_ping: Option<Box<dyn FnMut() + Send + Sync>>,
@@ -35,6 +40,7 @@ pub fn connected_state(timestamp: Instant) -> ConnState {
         Stats {
             tx_bytes: 0,
             rx_bytes: 0,
+            last_handshake: None,
         },
     );
     ConnState::Connected {
@@ -65,6 +71,7 @@ impl MockTunnel {
             Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         let peers = std::sync::Mutex::new(map);
@@ -89,6 +96,7 @@ impl MockTunnel {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 Ok(map)
@@ -3,7 +3,9 @@
 
 #[cfg(target_os = "android")] // On Android, the Tunnel trait is not imported by default.
 use super::Tunnel;
-use super::{config::Config, obfuscation::ObfuscatorHandle, CloseMsg, Error, TunnelType};
+use super::{
+    config::Config, obfuscation::ObfuscatorHandle, CloseMsg, Error, TunnelType, WireguardMonitor,
+};
 
 #[cfg(target_os = "android")]
 use std::sync::Mutex;
@@ -14,6 +16,7 @@ use std::{
 };
 #[cfg(target_os = "android")]
 use talpid_tunnel::tun_provider::TunProvider;
+use talpid_tunnel::{EventHook, TunnelEvent};
 
 use ipnetwork::IpNetwork;
 use talpid_tunnel_config_client::EphemeralPeer;
@@ -31,6 +34,7 @@ pub async fn config_ephemeral_peers(
     retry_attempt: u32,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
     close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+    event_hook: &mut EventHook,
 ) -> std::result::Result<(), CloseMsg> {
     let iface_name = {
         let tunnel = tunnel.lock().await;
@@ -44,8 +48,15 @@ pub async fn config_ephemeral_peers(
     log::trace!("Temporarily lowering tunnel MTU before ephemeral peer config");
     try_set_ipv4_mtu(&iface_name, talpid_tunnel::MIN_IPV4_MTU);
 
-    config_ephemeral_peers_inner(tunnel, config, retry_attempt, obfuscator, close_obfs_sender)
-        .await?;
+    config_ephemeral_peers_inner(
+        tunnel,
+        config,
+        retry_attempt,
+        obfuscator,
+        close_obfs_sender,
+        event_hook,
+    )
+    .await?;
 
     log::trace!("Resetting tunnel MTU");
     try_set_ipv4_mtu(&iface_name, config.mtu);
@@ -75,6 +86,7 @@ pub async fn config_ephemeral_peers(
     retry_attempt: u32,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
     close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+    event_hook: &mut EventHook,
     #[cfg(target_os = "android")] tun_provider: Arc<Mutex<TunProvider>>,
 ) -> Result<(), CloseMsg> {
     config_ephemeral_peers_inner(
@@ -83,6 +95,7 @@ pub async fn config_ephemeral_peers(
         retry_attempt,
         obfuscator,
         close_obfs_sender,
+        event_hook,
         #[cfg(target_os = "android")]
         tun_provider,
     )
@@ -95,6 +108,7 @@ async fn config_ephemeral_peers_inner(
     retry_attempt: u32,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
     close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+    event_hook: &mut EventHook,
     #[cfg(target_os = "android")] tun_provider: Arc<Mutex<TunProvider>>,
 ) -> Result<(), CloseMsg> {
     let ephemeral_private_key = PrivateKey::new_from_random();
@@ -114,9 +128,45 @@ async fn config_ephemeral_peers_inner(
 
     log::debug!("Retrieved ephemeral peer");
 
+    // Negotiate with the middle peers, starting with the one closest to the exit
+    for hop in (0..config.middle_peers.len()).rev() {
+        // Set up tunnel to lead to this middle peer
+        let mut middle_tun_config = config.clone();
+        let mut middle_peer = middle_tun_config.middle_peers.remove(hop);
+        middle_tun_config.middle_peers.truncate(hop);
+        middle_peer
+            .allowed_ips
+            .push(IpNetwork::new(IpAddr::V4(config.ipv4_gateway), 32).unwrap());
+        middle_tun_config.exit_peer = Some(middle_peer);
+
+        let close_obfs_sender = close_obfs_sender.clone();
+        let middle_config = reconfigure_tunnel(
+            tunnel,
+            middle_tun_config,
+            obfuscator.clone(),
+            close_obfs_sender,
+            event_hook,
+            #[cfg(target_os = "android")]
+            &tun_provider,
+        )
+        .await?;
+        let middle_ephemeral_peer = request_ephemeral_peer(
+            retry_attempt,
+            &middle_config,
+            ephemeral_private_key.public_key(),
+            config.quantum_resistant,
+            false,
+        )
+        .await?;
+        log::debug!("Successfully exchanged PSK with middle peer");
+
+        config.middle_peers[hop].psk = middle_ephemeral_peer.psk;
+    }
+
     if config.is_multihop() {
         // Set up tunnel to lead to entry
         let mut entry_tun_config = config.clone();
+        entry_tun_config.middle_peers.clear();
         entry_tun_config.exit_peer = None;
         entry_tun_config
             .entry_peer
@@ -129,6 +179,7 @@ async fn config_ephemeral_peers_inner(
             entry_tun_config,
             obfuscator.clone(),
             close_obfs_sender,
+            event_hook,
             #[cfg(target_os = "android")]
             &tun_provider,
         )
@@ -160,6 +211,7 @@ async fn config_ephemeral_peers_inner(
         config.clone(),
         obfuscator,
         close_obfs_sender,
+        event_hook,
         #[cfg(target_os = "android")]
         &tun_provider,
     )
@@ -191,6 +243,7 @@ async fn reconfigure_tunnel(
     mut config: Config,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
     close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+    event_hook: &mut EventHook,
     tun_provider: &Arc<Mutex<TunProvider>>,
 ) -> Result<Config, CloseMsg> {
     let mut obfs_guard = obfuscator.lock().await;
@@ -204,6 +257,7 @@ async fn reconfigure_tunnel(
         )
         .await
         .map_err(CloseMsg::ObfuscatorFailed)?;
+        allow_udp_relay(tunnel, &config, event_hook).await;
     }
     {
         let mut shared_tunnel = tunnel.lock().await;
@@ -228,6 +282,7 @@ async fn reconfigure_tunnel(
     mut config: Config,
     obfuscator: Arc<AsyncMutex<Option<ObfuscatorHandle>>>,
     close_obfs_sender: sync_mpsc::Sender<CloseMsg>,
+    event_hook: &mut EventHook,
 ) -> Result<Config, CloseMsg> {
     let mut obfs_guard = obfuscator.lock().await;
     if let Some(obfuscator_handle) = obfs_guard.take() {
@@ -235,6 +290,7 @@ async fn reconfigure_tunnel(
         *obfs_guard = super::obfuscation::apply_obfuscation_config(&mut config, close_obfs_sender)
             .await
             .map_err(CloseMsg::ObfuscatorFailed)?;
+        allow_udp_relay(tunnel, &config, event_hook).await;
     }
 
     {
@@ -289,3 +345,26 @@ async fn request_ephemeral_peer(
 
     Ok(ephemeral)
 }
+
+/// A restarted obfuscator may relay UDP traffic through a new address. Let it through the
+/// firewall before the tunnel is reconfigured to send traffic to it.
+async fn allow_udp_relay(
+    tunnel: &Arc<AsyncMutex<Option<TunnelType>>>,
+    config: &Config,
+    event_hook: &mut EventHook,
+) {
+    if config.udp_relay.is_none() {
+        return;
+    }
+    let iface_name = {
+        let tunnel = tunnel.lock().await;
+        let tunnel = tunnel.as_ref().expect("tunnel was None");
+        tunnel.get_interface_name()
+    };
+    event_hook
+        .on_event(TunnelEvent::InterfaceUp(
+            WireguardMonitor::tunnel_metadata(&iface_name, config),
+            WireguardMonitor::allowed_traffic_during_tunnel_config(config),
+        ))
+        .await;
+}
@@ -5,7 +5,7 @@
 use self::config::Config;
 #[cfg(windows)]
 use futures::channel::mpsc;
-use futures::future::Future;
+use futures::future::{self, Future};
 use obfuscation::ObfuscatorHandle;
 #[cfg(target_os = "android")]
 use std::borrow::Cow;
@@ -16,7 +16,7 @@ use std::{
     net::IpAddr,
     path::Path,
     pin::Pin,
-    sync::{mpsc as sync_mpsc, Arc, Mutex},
+    sync::{mpsc as sync_mpsc, Arc, Mutex, Weak},
 };
 #[cfg(any(target_os = "linux", target_os = "windows"))]
 use std::{env, sync::LazyLock};
@@ -42,7 +42,8 @@ mod connectivity;
 mod ephemeral;
 mod logging;
 mod obfuscation;
-mod stats;
+/// Tunnel traffic statistics
+pub mod stats;
 #[cfg(wireguard_go)]
 mod wireguard_go;
 #[cfg(target_os = "linux")]
@@ -87,6 +88,11 @@ pub enum Error {
     #[error("Tunnel obfuscation failed")]
     ObfuscationError(#[source] tunnel_obfuscation::Error),
 
+    /// SOCKS5 obfuscation is not supported on this platform
+    #[cfg(any(target_os = "windows", target_os = "android"))]
+    #[error("SOCKS5 obfuscation is not supported on this platform")]
+    Socks5ObfuscationUnsupported,
+
     /// Failed to set up connectivity monitor
     #[error("Connectivity monitor failed")]
     ConnectivityMonitorError(#[source] connectivity::Error),
@@ -191,6 +197,15 @@ impl WireguardMonitor {
             }
             config.mtu = clamp_mtu(params, config.mtu);
         }
+        // Upper bound for MTU detection, which may raise the MTU again after lowering it. The
+        // obfuscator adds its own overhead to every packet.
+        let packet_overhead = obfuscator
+            .as_ref()
+            .map(|obfuscator| obfuscator.packet_overhead())
+            .unwrap_or(0);
+        let max_mtu = clamp_mtu(params, u16::MAX)
+            .saturating_sub(packet_overhead)
+            .max(config.mtu);
 
         #[cfg(target_os = "windows")]
         let (setup_done_tx, setup_done_rx) = mpsc::channel(0);
@@ -216,6 +231,7 @@ impl WireguardMonitor {
             gateway,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             iface_name.clone(),
+            config.connectivity,
             args.retry_attempt,
             cancel_receiver,
         )
@@ -275,6 +291,7 @@ impl WireguardMonitor {
                     args.retry_attempt,
                     obfuscator.clone(),
                     ephemeral_obfs_sender,
+                    &mut event_hook,
                 )
                 .await
                 {
@@ -295,36 +312,6 @@ impl WireguardMonitor {
                     .await;
             }
 
-            if detect_mtu {
-                let config = config.clone();
-                let iface_name = iface_name.clone();
-                tokio::task::spawn(async move {
-                    if config.daita {
-                        // TODO: For now, we assume the MTU during the tunnel lifetime.
-                        // We could instead poke maybenot whenever we detect changes to it.
-                        log::warn!("MTU detection is not supported with DAITA. Skipping");
-                        return;
-                    }
-
-                    if let Err(e) = mtu_detection::automatic_mtu_correction(
-                        gateway,
-                        iface_name,
-                        config.mtu,
-                        #[cfg(windows)]
-                        config.ipv6_gateway.is_some(),
-                    )
-                    .await
-                    {
-                        log::error!(
-                            "{}",
-                            e.display_chain_with_msg(
-                                "Failed to automatically adjust MTU based on dropped packets"
-                            )
-                        );
-                    };
-                });
-            }
-
             let lock = tunnel.lock().await;
             let borrowed_tun = lock.as_ref().expect("The tunnel was dropped unexpectedly");
             match connectivity_monitor
@@ -356,10 +343,37 @@ impl WireguardMonitor {
             let metadata = Self::tunnel_metadata(&iface_name, &config);
             event_hook.on_event(TunnelEvent::Up(metadata)).await;
 
-            if let Err(error) = connectivity::Monitor::init(connectivity_monitor)
-                .run(Arc::downgrade(&tunnel))
-                .await
-            {
+            let mtu_monitor = async {
+                if !detect_mtu {
+                    return future::pending().await;
+                }
+                if config.daita {
+                    // TODO: For now, we assume the MTU during the tunnel lifetime.
+                    // We could instead poke maybenot whenever we detect changes to it.
+                    log::warn!("MTU detection is not supported with DAITA. Skipping");
+                    return future::pending().await;
+                }
+                let error = mtu_detection::monitor_mtu(
+                    gateway,
+                    iface_name.clone(),
+                    config.mtu,
+                    max_mtu,
+                    #[cfg(windows)]
+                    config.ipv6_gateway.is_some(),
+                    event_hook.clone(),
+                )
+                .await;
+                log::error!("{}", error.display_chain_with_msg("MTU detection failed"));
+                future::pending().await
+            };
+
+            // The MTU monitor never returns, so this only completes when the tunnel goes down
+            let connectivity_result = tokio::select! {
+                result = connectivity::Monitor::init(connectivity_monitor)
+                    .run(Arc::downgrade(&tunnel)) => result,
+                () = mtu_monitor => unreachable!("MTU monitor never completes"),
+            };
+            if let Err(error) = connectivity_result {
                 log::error!(
                     "{}",
                     error.display_chain_with_msg("Connectivity monitor failed")
@@ -425,6 +439,7 @@ impl WireguardMonitor {
         let (cancel_token, cancel_receiver) = connectivity::CancelToken::new();
         let connectivity_check = connectivity::Check::new(
             config.ipv4_gateway,
+            config.connectivity,
             args.retry_attempt,
             cancel_receiver.clone(),
         )
@@ -481,6 +496,7 @@ impl WireguardMonitor {
                     args.retry_attempt,
                     obfuscator.clone(),
                     ephemeral_obfs_sender,
+                    &mut event_hook,
                     args.tun_provider,
                 )
                 .await
@@ -543,7 +559,18 @@ impl WireguardMonitor {
                 talpid_tunnel_config_client::CONFIG_SERVICE_PORT,
                 TransportProtocol::Tcp,
             );
-            if config.is_multihop() {
+            if let Some(middle_peer) = config.middle_peers.first() {
+                // Each middle peer and the exit peer are negotiated with through the hop before
+                // it, so allow traffic to them as well.
+                AllowedTunnelTraffic::Three(
+                    config_endpoint,
+                    Endpoint::from_socket_address(middle_peer.endpoint, TransportProtocol::Udp),
+                    Endpoint::from_socket_address(
+                        config.exit_peer().endpoint,
+                        TransportProtocol::Udp,
+                    ),
+                )
+            } else if config.is_multihop() {
                 // If multihop is enabled, allow traffic to the exit peer as well.
                 AllowedTunnelTraffic::Two(
                     config_endpoint,
@@ -823,6 +850,13 @@ impl WireguardMonitor {
         wait_result
     }
 
+    /// Returns a handle for reading the traffic statistics of the tunnel while it is up.
+    pub fn stats_handle(&self) -> TunnelStatsHandle {
+        TunnelStatsHandle {
+            tunnel: Arc::downgrade(&self.tunnel),
+        }
+    }
+
     /// Tear down the tunnel.
     ///
     /// NOTE: will panic if called from within a tokio runtime.
@@ -950,20 +984,12 @@ impl WireguardMonitor {
 
     #[cfg(any(target_os = "linux", target_os = "macos"))]
     fn apply_route_mtu_for_multihop(route: RequiredRoute, config: &Config) -> RequiredRoute {
-        use talpid_tunnel::{IPV4_HEADER_SIZE, IPV6_HEADER_SIZE, WIREGUARD_HEADER_SIZE};
-
         if !config.is_multihop() {
             route
         } else {
-            // Set route MTU by subtracting the WireGuard overhead from the tunnel MTU. Plus
-            // some margin to make room for padding bytes.
-            let ip_overhead = match route.prefix.is_ipv4() {
-                true => IPV4_HEADER_SIZE,
-                false => IPV6_HEADER_SIZE,
-            };
-            const PADDING_BYTES_MARGIN: u16 = 15;
-            let mtu = config.mtu - ip_overhead - WIREGUARD_HEADER_SIZE - PADDING_BYTES_MARGIN;
-
+            // Traffic is wrapped once more for every peer between the entry and the exit peer
+            let extra_layers = 1 + config.middle_peers.len();
+            let mtu = multihop_route_mtu(config.mtu, extra_layers, route.prefix.is_ipv4());
             route.mtu(mtu)
         }
     }
@@ -991,6 +1017,7 @@ impl WireguardMonitor {
             ips: config.tunnel.addresses.clone(),
             ipv4_gateway: config.ipv4_gateway,
             ipv6_gateway: config.ipv6_gateway,
+            udp_relay: config.udp_relay,
         }
     }
 }
@@ -1013,12 +1040,43 @@ async fn log_tunnel_data_usage(config: &Config, tunnel: &Arc<AsyncMutex<Option<T
     {
         log::warn!("Exit peer stats: {:?}", stats);
     };
+    for peer in config.middle_peers.iter().rev() {
+        if let Some(stats) = tunnel_stats.get(peer.public_key.as_bytes()) {
+            log::warn!("Middle peer stats: {:?}", stats);
+        }
+    }
     let pubkey = config.entry_peer.public_key.as_bytes();
     if let Some(stats) = tunnel_stats.get(pubkey) {
         log::warn!("Entry peer stats: {:?}", stats);
     }
 }
 
+/// Reads traffic statistics from a running tunnel without keeping it alive.
+#[derive(Clone)]
+pub struct TunnelStatsHandle {
+    tunnel: Weak<AsyncMutex<Option<TunnelType>>>,
+}
+
+impl TunnelStatsHandle {
+    /// Returns the current stats for each peer, or `None` if the tunnel has been torn down or
+    /// the stats could not be read.
+    pub async fn get_stats(&self) -> Option<stats::StatsMap> {
+        let tunnel = self.tunnel.upgrade()?;
+        let tunnel = tunnel.lock().await;
+        let tunnel = tunnel.as_ref()?;
+        match tunnel.get_tunnel_stats().await {
+            Ok(stats) => Some(stats),
+            Err(error) => {
+                log::debug!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to obtain tunnel stats")
+                );
+                None
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 enum CloseMsg {
     Stop,
@@ -1174,6 +1232,24 @@ fn get_desired_mtu(params: &TunnelParameters) -> u16 {
     params.options.mtu.unwrap_or(DEFAULT_MTU)
 }
 
+/// Calculates the MTU of routes inside a multihop tunnel, where traffic is wrapped in
+/// `extra_layers` layers of WireGuard on top of the outermost one.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn multihop_route_mtu(tunnel_mtu: u16, extra_layers: usize, ipv4: bool) -> u16 {
+    use talpid_tunnel::{IPV4_HEADER_SIZE, IPV6_HEADER_SIZE, WIREGUARD_HEADER_SIZE};
+
+    // Set route MTU by subtracting the WireGuard overhead from the tunnel MTU for every layer.
+    // Plus some margin to make room for padding bytes.
+    let ip_overhead = match ipv4 {
+        true => IPV4_HEADER_SIZE,
+        false => IPV6_HEADER_SIZE,
+    };
+    const PADDING_BYTES_MARGIN: u16 = 15;
+    let layer_overhead = ip_overhead + WIREGUARD_HEADER_SIZE + PADDING_BYTES_MARGIN;
+    let extra_layers = u16::try_from(extra_layers).unwrap_or(u16::MAX);
+    tunnel_mtu.saturating_sub(layer_overhead.saturating_mul(extra_layers))
+}
+
 /// Calculates and appropriate tunnel MTU based on the given peer MTU minus header sizes
 fn clamp_mtu(params: &TunnelParameters, peer_mtu: u16) -> u16 {
     use talpid_tunnel::{
@@ -1204,3 +1280,19 @@ fn clamp_mtu(params: &TunnelParameters, peer_mtu: u16) -> u16 {
         .saturating_sub(total_header_size)
         .clamp(min_mtu, max_peer_mtu)
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_multihop_route_mtu() {
+        use super::multihop_route_mtu;
+
+        // Two hops
+        assert_eq!(multihop_route_mtu(1380, 1, true), 1380 - 75);
+        assert_eq!(multihop_route_mtu(1380, 1, false), 1380 - 95);
+        // Three hops: the overhead is paid for both the middle and the exit peer
+        assert_eq!(multihop_route_mtu(1380, 2, true), 1380 - 2 * 75);
+        assert_eq!(multihop_route_mtu(1380, 2, false), 1380 - 2 * 95);
+    }
+}
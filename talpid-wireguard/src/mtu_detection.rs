@@ -1,9 +1,12 @@
-use std::{io, net::IpAddr, time::Duration};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
 
-use futures::{future, stream::FuturesUnordered, Future, TryStreamExt};
 use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError};
-use talpid_tunnel::{ICMP_HEADER_SIZE, IPV4_HEADER_SIZE, MIN_IPV4_MTU};
-use tokio_stream::StreamExt;
+use talpid_tunnel::{EventHook, TunnelEvent, ICMP_HEADER_SIZE, IPV4_HEADER_SIZE, MIN_IPV4_MTU};
+use talpid_types::ErrorExt;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -22,55 +25,142 @@ pub enum Error {
     /// Failed to detect MTU because of an IO error when setting up the ping socket
     #[error("Failed to detect MTU because of an IO error when setting up the ping socket.")]
     MtuDetectionSetupSocket(#[source] io::Error),
-
-    /// Failed to set buffer size
-    #[cfg(target_os = "macos")]
-    #[error("Failed to set buffer size")]
-    MtuSetBufferSize(#[source] nix::Error),
 }
 
-/// Max time to wait for any ping, when this expires, we give up and throw an error.
-const PING_TIMEOUT: Duration = Duration::from_secs(10);
-/// Max time to wait after the first ping arrives. Every ping after this timeout is
-/// considered dropped, so we return the largest collected packet size.
-const PING_OFFSET_TIMEOUT: Duration = Duration::from_secs(2);
-const MTU_STEP_SIZE: u16 = 20;
-
-/// Verify that the current MTU doesn't cause dropped packets, otherwise lower it to the
-/// largest value which doesn't.
+/// Time between two rounds of MTU detection.
+const DETECTION_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Max time to wait for the response to a single ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of pings of a given size that must all be dropped before the size is considered too
+/// large. A single lost ping should not lower the MTU.
+const PING_ATTEMPTS: u16 = 2;
+/// Number of pings sent when trying to raise the MTU. While they are in flight, packets larger
+/// than the current MTU may be dropped, so there is only one.
+const RAISE_PING_ATTEMPTS: u16 = 1;
+
+/// Periodically detect the largest MTU that doesn't cause dropped packets and apply it to the
+/// tunnel interface. This follows changes to the path MTU while the tunnel is up, e.g. when
+/// roaming between networks, and raises the MTU again when larger packets get through, up to
+/// `max_mtu`. Every detected MTU is reported to `event_hook`.
 ///
 /// Note: This does not take fragmentation into account, so it should only be used as an extra
 /// safety measure after the normal MTU calculation using header sizes and safety margins.
-pub async fn automatic_mtu_correction(
-    gateway: std::net::Ipv4Addr,
+/// `max_mtu` must already account for the overhead of any obfuscation.
+///
+/// This only returns if the ping socket could not be created.
+pub async fn monitor_mtu(
+    gateway: Ipv4Addr,
     iface_name: String,
-    current_tunnel_mtu: u16,
+    initial_mtu: u16,
+    max_mtu: u16,
     #[cfg(windows)] ipv6: bool,
-) -> Result<(), Error> {
-    log::debug!("Starting MTU detection");
-    let verified_mtu = detect_mtu(
+    mut event_hook: EventHook,
+) -> Error {
+    let mut prober = match PingProber::new(
         gateway,
         #[cfg(any(target_os = "macos", target_os = "linux"))]
-        iface_name.clone(),
-        current_tunnel_mtu,
-    )
-    .await?;
-
-    if verified_mtu != current_tunnel_mtu {
-        log::warn!("Lowering MTU from {} to {verified_mtu}", current_tunnel_mtu);
-
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        talpid_net::unix::set_mtu(&iface_name, verified_mtu).map_err(Error::SetMtu)?;
-        #[cfg(windows)]
-        set_mtu_windows(verified_mtu, iface_name, ipv6).map_err(Error::SetMtu)?;
-    } else {
-        log::debug!("MTU {verified_mtu} verified to not drop packets");
+        &iface_name,
+        max_mtu,
+    ) {
+        Ok(prober) => prober,
+        Err(error) => return error,
+    };
+    let mut current_mtu = initial_mtu;
+    let mut dropped_mtu = None;
+    let mut set_iface_mtu = |mtu| {
+        set_mtu(
+            &iface_name,
+            mtu,
+            #[cfg(windows)]
+            ipv6,
+        )
+    };
+
+    loop {
+        log::debug!("Starting MTU detection");
+        match detect_and_set_mtu(
+            &mut prober,
+            &mut set_iface_mtu,
+            current_mtu,
+            max_mtu,
+            &mut dropped_mtu,
+        )
+        .await
+        {
+            Ok(verified_mtu) => {
+                current_mtu = verified_mtu;
+                event_hook
+                    .on_event(TunnelEvent::MtuDetected(verified_mtu))
+                    .await;
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(
+                        "Failed to automatically adjust MTU based on dropped packets"
+                    )
+                );
+            }
+        }
+        tokio::time::sleep(DETECTION_INTERVAL).await;
+    }
+}
+
+/// Run a single round of MTU detection and set the tunnel MTU to the result.
+///
+/// Packets larger than the MTU of the tunnel interface can't be sent through it at all, so sizes
+/// up to `current_mtu` are probed without touching the interface, and the MTU is only lowered
+/// once a smaller size has been confirmed. Raising the MTU can only be confirmed by a larger
+/// packet getting through, so the interface MTU is raised for a single ping and restored right
+/// away if it is dropped. This is done for one size per round, searching between `current_mtu`
+/// and `dropped_mtu`, the smallest size known to be dropped, or `max_mtu` if there is none.
+async fn detect_and_set_mtu(
+    prober: &mut impl Probe,
+    set_mtu: &mut impl FnMut(u16) -> Result<(), Error>,
+    current_mtu: u16,
+    max_mtu: u16,
+    dropped_mtu: &mut Option<u16>,
+) -> Result<u16, Error> {
+    if !prober.probe(current_mtu, PING_ATTEMPTS).await? {
+        *dropped_mtu = Some(current_mtu);
+        if current_mtu <= MIN_IPV4_MTU {
+            return Err(Error::MtuDetectionAllDropped);
+        }
+        let verified_mtu = search_mtu(prober, MIN_IPV4_MTU, current_mtu - 1).await?;
+        set_mtu(verified_mtu)?;
+        log::warn!("Lowering MTU from {current_mtu} to {verified_mtu}");
+        return Ok(verified_mtu);
+    }
+
+    let candidate = match *dropped_mtu {
+        Some(dropped) if dropped > current_mtu + 1 => current_mtu + (dropped - current_mtu) / 2,
+        // The search has converged, or nothing is known to be dropped. Start over from the top,
+        // since the path may have changed since
+        _ => max_mtu,
     };
+    if candidate > current_mtu {
+        set_mtu(candidate)?;
+        if prober.probe(candidate, RAISE_PING_ATTEMPTS).await? {
+            log::info!("Raising MTU from {current_mtu} to {candidate}");
+            return Ok(candidate);
+        }
+        set_mtu(current_mtu)?;
+        *dropped_mtu = Some(candidate);
+    }
+    log::debug!("MTU {current_mtu} verified to not drop packets");
+    Ok(current_mtu)
+}
+
+fn set_mtu(iface_name: &str, mtu: u16, #[cfg(windows)] ipv6: bool) -> Result<(), Error> {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    talpid_net::unix::set_mtu(iface_name, mtu).map_err(Error::SetMtu)?;
+    #[cfg(windows)]
+    set_mtu_windows(mtu, iface_name, ipv6).map_err(Error::SetMtu)?;
     Ok(())
 }
 
 #[cfg(windows)]
-fn set_mtu_windows(verified_mtu: u16, iface_name: String, ipv6: bool) -> io::Result<()> {
+fn set_mtu_windows(verified_mtu: u16, iface_name: &str, ipv6: bool) -> io::Result<()> {
     use talpid_windows::net::{set_mtu, AddressFamily};
 
     let luid = talpid_windows::net::luid_from_alias(iface_name)?;
@@ -87,61 +177,84 @@ fn set_mtu_windows(verified_mtu: u16, iface_name: String, ipv6: bool) -> io::Res
     Ok(())
 }
 
-/// Detects the maximum MTU that does not cause dropped packets.
-///
-/// The detection works by sending evenly spread out range of pings between 576 and the given
-/// current tunnel MTU, and returning the maximum packet size that was returned within a
-/// timeout.
-async fn detect_mtu(
-    gateway: std::net::Ipv4Addr,
-    #[cfg(any(target_os = "macos", target_os = "linux"))] iface_name: String,
-    current_mtu: u16,
-) -> Result<u16, Error> {
-    let linspace = mtu_spacing(MIN_IPV4_MTU, current_mtu, MTU_STEP_SIZE);
-
-    let config_builder = Config::builder().kind(surge_ping::ICMP::V4);
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    let config_builder = config_builder.interface(&iface_name);
-    let client = Client::new(&config_builder.build()).map_err(Error::MtuDetectionSetupSocket)?;
-
-    // For macos, the default socket receive buffer size seems to be too small to handle the
-    // data we are sending here. The consequence will be dropped packets causing the MTU
-    // detection to set a low value. Here we manually increase this value, which fixes
-    // the problem.
-    // NOTE: If pings drop on other unix platforms too, then enable this fix for them
-    #[cfg(target_os = "macos")]
-    {
-        use nix::sys::socket::{setsockopt, sockopt};
-        let fd = client.get_socket().get_native_sock();
-        let buf_size = linspace.iter().map(|sz| usize::from(*sz)).sum();
-        setsockopt(fd, sockopt::SndBuf, &buf_size).map_err(Error::MtuSetBufferSize)?;
-        setsockopt(fd, sockopt::RcvBuf, &buf_size).map_err(Error::MtuSetBufferSize)?;
+/// Checks whether packets of a given size make it through the tunnel.
+trait Probe {
+    /// Returns whether a packet with a total size of `mtu` got through, sending at most
+    /// `attempts` packets.
+    async fn probe(&mut self, mtu: u16, attempts: u16) -> Result<bool, Error>;
+}
+
+/// Finds the largest MTU in `mtu_min..=mtu_max` that doesn't cause dropped packets, using a binary
+/// search. Sizes up to the returned MTU are assumed to work as well.
+async fn search_mtu(prober: &mut impl Probe, mtu_min: u16, mtu_max: u16) -> Result<u16, Error> {
+    assert!(mtu_min <= mtu_max);
+
+    // Most of the time, nothing has changed since the last round
+    if prober.probe(mtu_max, PING_ATTEMPTS).await? {
+        return Ok(mtu_max);
+    }
+    if !prober.probe(mtu_min, PING_ATTEMPTS).await? {
+        return Err(Error::MtuDetectionAllDropped);
+    }
+
+    // Invariant: `working` gets through, `dropped` does not
+    let (mut working, mut dropped) = (mtu_min, mtu_max);
+    while dropped - working > 1 {
+        let mtu = working + (dropped - working) / 2;
+        if prober.probe(mtu, PING_ATTEMPTS).await? {
+            working = mtu;
+        } else {
+            dropped = mtu;
+        }
+    }
+    Ok(working)
+}
+
+/// Sends ICMP echo requests of the probed size to the gateway through the tunnel.
+struct PingProber {
+    client: Client,
+    gateway: Ipv4Addr,
+    sequence: u16,
+    /// Shared buffer to reduce allocations
+    payload_buf: Vec<u8>,
+}
+
+impl PingProber {
+    fn new(
+        gateway: Ipv4Addr,
+        #[cfg(any(target_os = "macos", target_os = "linux"))] iface_name: &str,
+        max_mtu: u16,
+    ) -> Result<Self, Error> {
+        let config_builder = Config::builder().kind(surge_ping::ICMP::V4);
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        let config_builder = config_builder.interface(iface_name);
+        let client =
+            Client::new(&config_builder.build()).map_err(Error::MtuDetectionSetupSocket)?;
+
+        Ok(Self {
+            client,
+            gateway,
+            sequence: 0,
+            payload_buf: vec![0; usize::from(max_mtu)],
+        })
     }
+}
 
-    // Shared buffer to reduce allocations
-    let payload_buf = vec![0; current_mtu as usize];
-
-    // Send a ping for each MTU in the linspace
-    let ping_stream = linspace
-        .into_iter()
-        .enumerate()
-        .map(|(sequence, mtu)| {
-            let client = client.clone();
-            let payload_size = (mtu - IPV4_HEADER_SIZE - ICMP_HEADER_SIZE) as usize;
-            let payload = &payload_buf[0..payload_size];
-            // Return a future that sends a ping of size MTU, receives the result, and returns the
-            // validated MTU
-            async move {
-                log::trace!("Sending ICMP ping of total size {mtu}");
-                let (packet, _duration) = client
-                    .pinger(IpAddr::V4(gateway), PingIdentifier(0))
-                    .await
-                    .timeout(PING_TIMEOUT)
-                    .ping(PingSequence(sequence as u16), payload)
-                    .await?;
-
-                // Validate the received ping response
-                {
+impl Probe for PingProber {
+    async fn probe(&mut self, mtu: u16, attempts: u16) -> Result<bool, Error> {
+        let payload_size = usize::from(mtu - IPV4_HEADER_SIZE - ICMP_HEADER_SIZE);
+        let payload = &self.payload_buf[0..payload_size];
+        let mut pinger = self
+            .client
+            .pinger(IpAddr::V4(self.gateway), PingIdentifier(0))
+            .await;
+        pinger.timeout(PING_TIMEOUT);
+
+        for _ in 0..attempts {
+            self.sequence = self.sequence.wrapping_add(1);
+            log::trace!("Sending ICMP ping of total size {mtu}");
+            match pinger.ping(PingSequence(self.sequence), payload).await {
+                Ok((packet, _duration)) => {
                     let surge_ping::IcmpPacket::V4(packet) = packet else {
                         unreachable!("ICMP ping response was not of IPv4 type");
                     };
@@ -153,224 +266,153 @@ async fn detect_mtu(
                         size, mtu,
                         "Ping response should be of identical size to request"
                     );
+                    return Ok(true);
                 }
-                Ok(mtu)
+                Err(SurgeError::Timeout { .. }) => continue,
+                Err(error) => return Err(Error::MtuDetectionUnexpected(error)),
             }
-        })
-        .collect::<FuturesUnordered<_>>();
-
-    max_ping_size(ping_stream).await
-}
-
-/// Consumes a stream of pings, and returns the largest packet size within [`PING_OFFSET_TIMEOUT`]
-/// from the first ping response. Short circuits on errors.
-async fn max_ping_size(
-    mut ping_stream: FuturesUnordered<impl Future<Output = Result<u16, SurgeError>>>,
-) -> Result<u16, Error> {
-    let first_ping_size = ping_stream
-        .next()
-        .await
-        .expect("At least one pings should be sent")
-        // Short-circuit and return on error
-        .map_err(|e| match e {
-            // If the first ping we get back timed out, then all of them did
-            SurgeError::Timeout { .. } => Error::MtuDetectionAllDropped,
-            // Unexpected error type
-            e => Error::MtuDetectionUnexpected(e),
-        })?;
-
-    ping_stream
-        .timeout(PING_OFFSET_TIMEOUT) // Start the timeout after the first ping has arrived
-        .map_while(|res| res.ok()) // Stop waiting for more pings after this timeout
-        .try_fold(first_ping_size, |acc, mtu| future::ready(Ok(acc.max(mtu)))) // Get largest ping
-        .await
-        .map_err(Error::MtuDetectionUnexpected)
-}
-
-/// Creates a linear spacing of MTU values with the given step size. Always includes the given
-/// end points.
-fn mtu_spacing(mtu_min: u16, mtu_max: u16, step_size: u16) -> Vec<u16> {
-    assert!(mtu_min < mtu_max);
-    assert!(step_size < mtu_max);
-    assert_ne!(step_size, 0);
-
-    let second_mtu = (mtu_min + 1).next_multiple_of(step_size);
-    let in_between = (second_mtu..mtu_max).step_by(step_size as usize);
-
-    let mut ret = Vec::with_capacity(in_between.clone().count() + 2);
-    ret.push(mtu_min);
-    ret.extend(in_between);
-    ret.push(mtu_max);
-    ret
+        }
+        Ok(false)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
+    use std::{cell::Cell, rc::Rc};
 
-    proptest! {
-        #[test]
-        fn mtu_spacing_properties(mtu_min in 0..800u16, mtu_max in 800..2000u16, step_size in 1..800u16)  {
-            let mtu_spacing = mtu_spacing(mtu_min, mtu_max, step_size);
-
-            // The MTU linspace should contain the end points exactly once
-            prop_assert_eq!(mtu_spacing.iter().filter(|mtu| mtu == &&mtu_min).count(), 1);
-            prop_assert_eq!(mtu_spacing.iter().filter(|mtu| mtu == &&mtu_max).count(), 1);
-            // It should be allocated with no wasted capacity
-            prop_assert_eq!(mtu_spacing.capacity(), mtu_spacing.len());
-            // The spacing should be no greater than step size
-            let mut diffs = mtu_spacing.windows(2).map(|win| win[1]-win[0]);
-            prop_assert!(diffs.all(|diff| diff <= step_size));
-
-        }
+    /// Drops every packet larger than the path MTU and records how many probes were sent.
+    struct MockProber {
+        path_mtu: u16,
+        probes: usize,
     }
 
-    /// Tests for the timeout behavior described by [`PING_OFFSET_TIMEOUT`] and [`PING_TIMEOUT`].
-    ///
-    /// Note that time is mocked using [`tokio::time::pause`]. When all current tasks are sleeping,
-    /// the clock will auto advance until the next one wakes up,
-    /// see <https://docs.rs/tokio/latest/tokio/time/fn.pause.html#auto-advance> for details.
-    mod timeout {
-        use super::*;
-        use rand::{distributions::Uniform, thread_rng};
-        use std::pin::Pin;
-        use tokio::test;
-
-        // Convenience functions for creating dynamic ping futures, required by `FuturesUnordered`
-        // to manipulate the outcome and delay of mocked pings individually
-
-        /// Ping response that is available immediately
-        fn ready_ping<T: Send + 'static>(x: T) -> Pin<Box<dyn Future<Output = T>>> {
-            Box::pin(future::ready(x))
+    impl Probe for MockProber {
+        async fn probe(&mut self, mtu: u16, _attempts: u16) -> Result<bool, Error> {
+            self.probes += 1;
+            Ok(mtu <= self.path_mtu)
         }
+    }
 
-        /// Ping response that is available immediately and wraps result in Ok()
-        fn ok_ping<T: Send + 'static, E: Send + 'static>(
-            t: T,
-        ) -> Pin<Box<dyn Future<Output = Result<T, E>>>> {
-            ready_ping(Ok(t))
-        }
+    fn search(path_mtu: u16, mtu_min: u16, mtu_max: u16) -> (Result<u16, Error>, usize) {
+        let mut prober = MockProber {
+            path_mtu,
+            probes: 0,
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(search_mtu(&mut prober, mtu_min, mtu_max));
+        (result, prober.probes)
+    }
 
-        /// Ping response that is available immediately and  wraps result in Err()
-        fn err_ping<T: Send + 'static, E: Send + 'static>(
-            e: E,
-        ) -> Pin<Box<dyn Future<Output = Result<T, E>>>> {
-            ready_ping(Err(e))
-        }
+    proptest! {
+        #[test]
+        fn search_mtu_finds_path_mtu(mtu_min in 500..800u16, mtu_max in 800..2000u16, path_mtu in 500..2000u16) {
+            let (result, probes) = search(path_mtu, mtu_min, mtu_max);
 
-        /// Ping response that is delayed
-        fn delayed_ping<R: Send + 'static + Unpin>(
-            ret: R,
-            duration: Duration,
-        ) -> Pin<Box<dyn Future<Output = R>>> {
-            Box::pin(async move {
-                tokio::time::sleep(duration).await;
-                ret
-            })
+            if path_mtu < mtu_min {
+                prop_assert!(matches!(result, Err(Error::MtuDetectionAllDropped)));
+            } else {
+                prop_assert_eq!(result.unwrap(), path_mtu.min(mtu_max));
+            }
+            // The search should be logarithmic in the size of the range
+            let max_probes = 2 + (u32::from(mtu_max - mtu_min) + 1).ilog2() as usize + 1;
+            prop_assert!(probes <= max_probes);
         }
+    }
 
-        /// The largest ping size should be chosen if all of them return, regardless of return
-        /// order.
-        #[test(start_paused = true)]
-        async fn all_pings_ok() {
-            let mut rng = thread_rng();
-            // Random delay for each ping, but within PING_OFFSET_TIMEOUT of the first
-            let uniform = Uniform::new(Duration::ZERO, PING_OFFSET_TIMEOUT);
-            let pings = (0..=100)
-                .map(|p| delayed_ping(Ok(p), rng.sample(uniform)))
-                .collect();
-            let max = max_ping_size(pings).await.unwrap();
-            assert_eq!(max, 100);
-        }
+    /// Only a single probe should be needed if the largest MTU works.
+    #[test]
+    fn search_mtu_fast_path() {
+        let (result, probes) = search(1500, 576, 1380);
+        assert_eq!(result.unwrap(), 1380);
+        assert_eq!(probes, 1);
+    }
 
-        /// If pings arrive later than [`PING_OFFSET_TIMEOUT`] after the first ping, they should be
-        /// filtered out. The largest response before that point is chosen.
-        #[test(start_paused = true)]
-        async fn ping_timeout() {
-            let mut pings = FuturesUnordered::new();
-            let ok_pings = (0..=50).map(ok_ping);
-            pings.extend(ok_pings);
-            let dropped_pings = (51..=100)
-                .map(|p| delayed_ping(Ok(p), PING_OFFSET_TIMEOUT + Duration::from_secs(1)));
-            pings.extend(dropped_pings);
-
-            let max = max_ping_size(pings).await.unwrap();
-            assert_eq!(max, 50);
-        }
+    /// Drops packets larger than the path MTU. Like a real tunnel interface, it can't send packets
+    /// larger than its own MTU at all.
+    struct MockTunnel {
+        path_mtu: u16,
+        iface_mtu: Rc<Cell<u16>>,
+    }
 
-        /// The [`PING_OFFSET_TIMEOUT`] is counted from the return of the first ping, not from the
-        /// function call. Test that if all pings arrive after PING_OFFSET_TIMEOUT, but close to
-        /// each other in time, the largest return value is chosen as normal.
-        #[test(start_paused = true)]
-        async fn delay_first_ping() {
-            let mut rng = thread_rng();
-            // Random delay for each ping, but within PING_OFFSET_TIMEOUT of the first and no sooner
-            // than 5s
-            let uniform = Uniform::new(
-                Duration::from_secs(5),
-                Duration::from_secs(5) + PING_OFFSET_TIMEOUT,
+    impl Probe for MockTunnel {
+        async fn probe(&mut self, mtu: u16, _attempts: u16) -> Result<bool, Error> {
+            assert!(
+                mtu <= self.iface_mtu.get(),
+                "Probed {mtu} with an interface MTU of {}",
+                self.iface_mtu.get()
             );
-            let pings = (0..=100)
-                .map(|p| delayed_ping(Ok(p), rng.sample(uniform)))
-                .collect();
-            let max = max_ping_size(pings).await.unwrap();
-            assert_eq!(max, 100);
+            Ok(mtu <= self.path_mtu)
         }
+    }
 
-        /// If an unknown error type occurs, the MTU detection is aborted and that error is
-        /// propagated, even if some ping response came back ok.
-        #[test(start_paused = true)]
-        async fn unknown_error() {
-            let pings = FuturesUnordered::new();
-            pings.push(ok_ping(0));
-            pings.push(ok_ping(100));
-            pings.push(err_ping(SurgeError::NetworkError));
-
-            let e = max_ping_size(pings).await.unwrap_err();
-            assert!(matches!(
-                e,
-                Error::MtuDetectionUnexpected(SurgeError::NetworkError)
-            ));
-        }
+    /// Run a round of detection and return the result and the MTU of the interface afterwards.
+    fn detect_round(
+        tunnel: &mut MockTunnel,
+        current_mtu: u16,
+        dropped_mtu: &mut Option<u16>,
+    ) -> (u16, u16) {
+        let iface_mtu = tunnel.iface_mtu.clone();
+        let mut set_mtu = |mtu| {
+            iface_mtu.set(mtu);
+            Ok(())
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime
+            .block_on(detect_and_set_mtu(
+                tunnel,
+                &mut set_mtu,
+                current_mtu,
+                1420,
+                dropped_mtu,
+            ))
+            .unwrap();
+        (result, tunnel.iface_mtu.get())
+    }
 
-        /// An error of type [`SurgeError::Timeout`] signals that the total [`PING_TIMEOUT`] has
-        /// been reached. If this happens to the first ping we consider alls pings timed
-        /// out.
-        #[test(start_paused = true)]
-        async fn all_dropped() {
-            let pings = FuturesUnordered::new();
-            pings.push(delayed_ping(
-                Err(SurgeError::Timeout {
-                    seq: PingSequence(0),
-                }),
-                PING_TIMEOUT,
-            ));
-            pings.push(delayed_ping(Ok(100), PING_TIMEOUT + Duration::from_secs(1)));
-
-            let e = max_ping_size(pings).await.unwrap_err();
-            assert!(matches!(e, Error::MtuDetectionAllDropped));
+    /// A shrinking path MTU should be found without raising the interface MTU.
+    #[test]
+    fn detect_lowers_mtu() {
+        let mut tunnel = MockTunnel {
+            path_mtu: 1300,
+            iface_mtu: Rc::new(Cell::new(1420)),
+        };
+        let mut dropped_mtu = None;
+        assert_eq!(
+            detect_round(&mut tunnel, 1420, &mut dropped_mtu),
+            (1300, 1300)
+        );
+        assert_eq!(dropped_mtu, Some(1420));
+    }
+
+    /// The MTU should be raised one confirmed step per round, and the interface MTU should be
+    /// restored whenever a step fails.
+    #[test]
+    fn detect_raises_mtu() {
+        let mut tunnel = MockTunnel {
+            path_mtu: 1400,
+            iface_mtu: Rc::new(Cell::new(1280)),
+        };
+        let mut current_mtu = 1280;
+        let mut dropped_mtu = None;
+        for _ in 0..10 {
+            let (mtu, iface_mtu) = detect_round(&mut tunnel, current_mtu, &mut dropped_mtu);
+            assert_eq!(mtu, iface_mtu);
+            assert!(mtu >= current_mtu && mtu <= tunnel.path_mtu);
+            current_mtu = mtu;
         }
+        assert_eq!(current_mtu, 1400);
 
-        /// In the rare case that [`PING_TIMEOUT`] triggers before [`PING_OFFSET_TIMEOUT`], even
-        /// though some of the ping responses have come back, we still consider it abnormal
-        /// and choose to return an error instead of trusting result.
-        #[test(start_paused = true)]
-        async fn max_timeout_error() {
-            let pings = FuturesUnordered::new();
-            pings.push(delayed_ping(Ok(0), PING_TIMEOUT - Duration::from_secs(1)));
-            pings.push(delayed_ping(
-                Err(SurgeError::Timeout {
-                    seq: PingSequence(0),
-                }),
-                PING_TIMEOUT,
-            ));
-
-            let e = max_ping_size(pings).await.unwrap_err();
-            assert!(matches!(
-                e,
-                Error::MtuDetectionUnexpected(SurgeError::Timeout { seq: _ })
-            ));
+        // Larger sizes keep being tried in case the path improves
+        tunnel.path_mtu = 1420;
+        for _ in 0..10 {
+            (current_mtu, _) = detect_round(&mut tunnel, current_mtu, &mut dropped_mtu);
         }
+        assert_eq!(current_mtu, 1420);
     }
 }
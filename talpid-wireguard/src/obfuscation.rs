@@ -13,7 +13,7 @@ use talpid_tunnel::tun_provider::TunProvider;
 use talpid_types::{net::obfuscation::ObfuscatorConfig, ErrorExt};
 
 use tunnel_obfuscation::{
-    create_obfuscator, shadowsocks, udp2tcp, Settings as ObfuscationSettings,
+    create_obfuscator, quic, shadowsocks, socks5, udp2tcp, Settings as ObfuscationSettings,
 };
 
 /// Begin running obfuscation machine, if configured. This function will patch `config`'s endpoint
@@ -27,6 +27,13 @@ pub async fn apply_obfuscation_config(
         return Ok(None);
     };
 
+    // The firewall only lets a single relay endpoint through on Windows, and the TCP control
+    // connection cannot be excluded from the tunnel on Android.
+    #[cfg(any(target_os = "windows", target_os = "android"))]
+    if matches!(obfuscator_config, ObfuscatorConfig::Socks5 { .. }) {
+        return Err(Error::Socks5ObfuscationUnsupported);
+    }
+
     let settings = settings_from_config(
         obfuscator_config,
         #[cfg(target_os = "linux")]
@@ -45,6 +52,7 @@ pub async fn apply_obfuscation_config(
     bypass_vpn(tun_provider, obfuscator.remote_socket_fd()).await;
 
     patch_endpoint(config, obfuscator.endpoint());
+    config.udp_relay = obfuscator.udp_relay();
 
     let obfuscation_task = tokio::spawn(async move {
         match obfuscator.run().await {
@@ -96,6 +104,28 @@ fn settings_from_config(
                 fwmark,
             })
         }
+        ObfuscatorConfig::Quic { endpoint, hostname } => {
+            ObfuscationSettings::Quic(quic::Settings {
+                quic_endpoint: *endpoint,
+                hostname: hostname.clone(),
+                #[cfg(target_os = "linux")]
+                fwmark,
+            })
+        }
+        ObfuscatorConfig::Socks5 {
+            endpoint,
+            auth,
+            wireguard_endpoint,
+        } => ObfuscationSettings::Socks5(socks5::Settings {
+            proxy_endpoint: *endpoint,
+            auth: auth.as_ref().map(|auth| socks5::Credentials {
+                username: auth.username().to_owned(),
+                password: auth.password().to_owned(),
+            }),
+            wireguard_endpoint: *wireguard_endpoint,
+            #[cfg(target_os = "linux")]
+            fwmark,
+        }),
     }
 }
 
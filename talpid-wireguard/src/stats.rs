@@ -1,8 +1,14 @@
+use std::time::SystemTime;
+
 /// Contains bytes sent and received through a tunnel
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Stats {
+    /// Number of bytes sent to the peer
     pub tx_bytes: u64,
+    /// Number of bytes received from the peer
     pub rx_bytes: u64,
+    /// Time of the most recent completed handshake with the peer, if any.
+    pub last_handshake: Option<SystemTime>,
 }
 
 /// A map from peer pubkeys to peer stats.
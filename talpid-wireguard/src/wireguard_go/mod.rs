@@ -111,6 +111,8 @@ impl WgGoTunnel {
     }
 
     pub async fn set_config(self, config: &Config) -> Result<Self> {
+        Self::ensure_no_middle_peers(config)?;
+
         let state = self.as_state();
         let log_path = state._logging_context.path.clone();
         let cancel_receiver = state.cancel_receiver.clone();
@@ -160,6 +162,16 @@ impl WgGoTunnel {
     pub fn stop(self) -> Result<()> {
         self.into_state().stop()
     }
+
+    /// The multihop implementation in wireguard-go only supports an entry and an exit peer.
+    fn ensure_no_middle_peers(config: &Config) -> Result<()> {
+        if config.middle_peers.is_empty() {
+            Ok(())
+        } else {
+            log::error!("Multihop through more than two relays is not supported on Android");
+            Err(TunnelError::SetConfigError)
+        }
+    }
 }
 
 pub(crate) struct WgGoTunnelState {
@@ -446,6 +458,8 @@ impl WgGoTunnel {
         routes: impl Iterator<Item = IpNetwork>,
         cancel_receiver: connectivity::CancelReceiver,
     ) -> Result<Self> {
+        Self::ensure_no_middle_peers(config)?;
+
         let (mut tunnel_device, tunnel_fd) =
             Self::get_tunnel(Arc::clone(&tun_provider), config, routes)?;
 
@@ -521,8 +535,9 @@ impl WgGoTunnel {
         let state = self.as_state();
         let addr = state.config.ipv4_gateway;
         let cancel_receiver = state.cancel_receiver.clone();
-        let mut check = connectivity::Check::new(addr, 0, cancel_receiver)
-            .map_err(|err| TunnelError::RecoverableStartWireguardError(Box::new(err)))?;
+        let mut check =
+            connectivity::Check::new(addr, state.config.connectivity, 0, cancel_receiver)
+                .map_err(|err| TunnelError::RecoverableStartWireguardError(Box::new(err)))?;
 
         // TODO: retry attempt?
 
@@ -598,6 +613,7 @@ impl Tunnel for WgGoTunnel {
 
 mod stats {
     use super::{Stats, StatsMap};
+    use std::time::{Duration, SystemTime};
 
     #[derive(thiserror::Error, Debug, PartialEq)]
     pub enum Error {
@@ -615,6 +631,8 @@ mod stats {
             let mut peer = None;
             let mut tx_bytes = None;
             let mut rx_bytes = None;
+            let mut handshake_sec = 0;
+            let mut handshake_nsec = 0;
 
             // parts iterates over keys and values
             let parts = config.split('\n').filter_map(|line| {
@@ -633,6 +651,20 @@ mod stats {
                         peer = Some(buffer);
                         tx_bytes = None;
                         rx_bytes = None;
+                        handshake_sec = 0;
+                        handshake_nsec = 0;
+                    }
+                    "last_handshake_time_sec" => {
+                        handshake_sec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
+                    }
+                    "last_handshake_time_nsec" => {
+                        handshake_nsec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
                     }
                     "rx_bytes" => {
                         rx_bytes = Some(
@@ -662,6 +694,7 @@ mod stats {
                         Self {
                             tx_bytes: tx_bytes_val,
                             rx_bytes: rx_bytes_val,
+                            last_handshake: handshake_time(handshake_sec, handshake_nsec),
                         },
                     );
                     peer = None;
@@ -673,6 +706,14 @@ mod stats {
         }
     }
 
+    /// A zero timestamp means that no handshake has been completed yet.
+    fn handshake_time(sec: u64, nsec: u32) -> Option<SystemTime> {
+        if sec == 0 && nsec == 0 {
+            return None;
+        }
+        SystemTime::UNIX_EPOCH.checked_add(Duration::new(sec, nsec))
+    }
+
     #[cfg(test)]
     mod test {
         use super::super::stats::{Error, Stats};
@@ -688,6 +729,13 @@ mod stats {
             assert_eq!(actual_keys, [pubkey]);
             assert_eq!(stats[&pubkey].rx_bytes, 2396);
             assert_eq!(stats[&pubkey].tx_bytes, 2740);
+            assert_eq!(
+                stats[&pubkey].last_handshake,
+                Some(
+                    std::time::SystemTime::UNIX_EPOCH
+                        + std::time::Duration::new(1578420649, 369416131)
+                )
+            );
         }
 
         #[test]
@@ -22,6 +22,7 @@ use std::{
     pin::Pin,
     ptr,
     sync::{Arc, LazyLock, Mutex},
+    time::{Duration, SystemTime},
 };
 use talpid_types::{BoxedError, ErrorExt};
 use talpid_windows::net;
@@ -1065,6 +1066,7 @@ impl Tunnel for WgNtTunnel {
                     Stats {
                         tx_bytes: peer.tx_bytes,
                         rx_bytes: peer.rx_bytes,
+                        last_handshake: filetime_to_system_time(peer.last_handshake),
                     },
                 );
             }
@@ -1118,6 +1120,16 @@ impl Tunnel for WgNtTunnel {
     }
 }
 
+/// Converts a `FILETIME` timestamp (100 ns intervals since 1601-01-01) to a `SystemTime`.
+/// Returns `None` if no handshake has been completed.
+fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    const UNIX_EPOCH_AS_FILETIME: u64 = 116_444_736_000_000_000;
+
+    // Peers that have never completed a handshake report 0, which is before the Unix epoch
+    let since_unix_epoch = filetime.checked_sub(UNIX_EPOCH_AS_FILETIME)?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_nanos(since_unix_epoch.saturating_mul(100)))
+}
+
 pub fn as_uninit_byte_slice<T: Copy + Sized>(value: &T) -> &[mem::MaybeUninit<u8>] {
     unsafe { std::slice::from_raw_parts(value as *const _ as *const _, mem::size_of::<T>()) }
 }
@@ -1151,14 +1163,17 @@ mod tests {
             psk: None,
             constant_packet_size: false,
         },
+        middle_peers: vec![],
         exit_peer: None,
         ipv4_gateway: "0.0.0.0".parse().unwrap(),
         ipv6_gateway: None,
         mtu: 0,
         obfuscator_config: None,
+        udp_relay: None,
         #[cfg(daita)]
         daita: false,
         quantum_resistant: false,
+        connectivity: Default::default(),
     });
 
     static WG_STRUCT_CONFIG: LazyLock<Interface> = LazyLock::new(|| Interface {
@@ -85,8 +85,10 @@ pub async fn test_dns_leak_custom_public_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                encryption: None,
             },
             state: settings::DnsState::Custom,
+            split_rules: vec![],
         })
         .await
         .expect("failed to configure DNS server");
@@ -117,8 +119,10 @@ pub async fn test_dns_leak_custom_private_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                encryption: None,
             },
             state: settings::DnsState::Custom,
+            split_rules: vec![],
         })
         .await
         .expect("failed to configure DNS server");
@@ -368,8 +372,10 @@ pub async fn test_dns_config_custom_private(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![IpAddr::V4(TEST_CONFIG.host_bridge_ip)],
+                encryption: None,
             },
             state: settings::DnsState::Custom,
+            split_rules: vec![],
         })
         .await
         .context("failed to configure DNS server")?;
@@ -402,8 +408,10 @@ pub async fn test_dns_config_custom_public(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![custom_ip],
+                encryption: None,
             },
             state: settings::DnsState::Custom,
+            split_rules: vec![],
         })
         .await
         .context("failed to configure DNS server")?;
@@ -508,6 +516,7 @@ pub async fn test_content_blockers(
                 default_options: test_opts,
                 custom_options: settings::CustomDnsOptions::default(),
                 state: settings::DnsState::Default,
+                split_rules: vec![],
             })
             .await
             .context("failed to configure DNS server")?;
@@ -673,6 +682,7 @@ async fn connect_local_wg_relay(mullvad_client: &mut MullvadProxyClient) -> Resu
                 constant_packet_size: false,
             },
             ipv4_gateway: CUSTOM_TUN_GATEWAY,
+            middle_peers: vec![],
             exit_peer: None,
             #[cfg(target_os = "linux")]
             fwmark: None,
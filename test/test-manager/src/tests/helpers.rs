@@ -667,6 +667,7 @@ pub fn unreachable_wireguard_tunnel() -> talpid_types::net::wireguard::Connectio
             psk: None,
             constant_packet_size: false,
         },
+        middle_peers: vec![],
         exit_peer: None,
         ipv4_gateway: Ipv4Addr::new(10, 64, 10, 1),
         ipv6_gateway: None,
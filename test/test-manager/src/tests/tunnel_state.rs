@@ -369,7 +369,9 @@ pub async fn test_connected_state(
                     proxy: None,
                     obfuscation: _,
                     entry_endpoint: None,
+                    middle_endpoints: _,
                     tunnel_interface: _,
+                    mtu: _,
                     daita: _,
                 },
             ..
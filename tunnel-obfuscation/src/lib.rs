@@ -1,7 +1,9 @@
 use async_trait::async_trait;
 use std::net::SocketAddr;
 
+pub mod quic;
 pub mod shadowsocks;
+pub mod socks5;
 pub mod udp2tcp;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -19,6 +21,18 @@ pub enum Error {
 
     #[error("Failed to run Shadowsocks")]
     RunShadowsocksObfuscator(#[source] shadowsocks::Error),
+
+    #[error("Failed to initialize QUIC obfuscator")]
+    CreateQuicObfuscator(#[source] quic::Error),
+
+    #[error("Failed to run QUIC obfuscator")]
+    RunQuicObfuscator(#[source] quic::Error),
+
+    #[error("Failed to initialize SOCKS5 obfuscator")]
+    CreateSocks5Obfuscator(#[source] socks5::Error),
+
+    #[error("Failed to run SOCKS5 obfuscator")]
+    RunSocks5Obfuscator(#[source] socks5::Error),
 }
 
 #[async_trait]
@@ -32,6 +46,12 @@ pub trait Obfuscator: Send {
     #[cfg(target_os = "android")]
     fn remote_socket_fd(&self) -> std::os::unix::io::RawFd;
 
+    /// Returns the address that outbound UDP traffic is sent to, if it differs from the
+    /// remote endpoint that the obfuscator was configured with.
+    fn udp_relay(&self) -> Option<SocketAddr> {
+        None
+    }
+
     /// The overhead (in bytes) of this obfuscation protocol.
     ///
     /// This is used when deciding on MTUs.
@@ -42,6 +62,8 @@ pub trait Obfuscator: Send {
 pub enum Settings {
     Udp2Tcp(udp2tcp::Settings),
     Shadowsocks(shadowsocks::Settings),
+    Quic(quic::Settings),
+    Socks5(socks5::Settings),
 }
 
 pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator>> {
@@ -54,6 +76,14 @@ pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator
             .await
             .map(box_obfuscator)
             .map_err(Error::CreateShadowsocksObfuscator),
+        Settings::Quic(s) => quic::Quic::new(s)
+            .await
+            .map(box_obfuscator)
+            .map_err(Error::CreateQuicObfuscator),
+        Settings::Socks5(s) => socks5::Socks5::new(s)
+            .await
+            .map(box_obfuscator)
+            .map_err(Error::CreateSocks5Obfuscator),
     }
 }
 
@@ -0,0 +1,461 @@
+//! QUIC obfuscation
+//!
+//! WireGuard packets are sent as QUIC DATAGRAM frames (RFC 9221) over a connection that
+//! negotiates HTTP/3 using ALPN. Each datagram is prefixed the same way as an HTTP datagram
+//! (RFC 9297) belonging to a proxied UDP flow, so that the traffic resembles ordinary HTTP/3 on
+//! UDP port 443. The relay terminates the QUIC connection and forwards the payload to its
+//! WireGuard interface.
+//!
+//! Note: It is important not to connect to the QUIC endpoint right away. The remote socket must be
+//! protected in `VpnService` so that the socket is not routed through the tunnel.
+
+use super::Obfuscator;
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+#[cfg(target_os = "linux")]
+use nix::sys::socket::{setsockopt, sockopt};
+use quinn::{
+    crypto::rustls::QuicClientConfig, ClientConfig, Connection, Endpoint, EndpointConfig,
+    IdleTimeout, SendDatagramError, TokioRuntime, TransportConfig,
+};
+use rustls::RootCertStore;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use std::os::fd::AsRawFd;
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{net::UdpSocket, sync::oneshot};
+
+/// ALPN identifier for HTTP/3
+const ALPN_H3: &[u8] = b"h3";
+
+/// Prefix of every datagram: the quarter stream ID of the first client-initiated request stream,
+/// followed by the context ID of UDP payloads. Both are encoded as single-byte varints.
+const HTTP_DATAGRAM_PREFIX: [u8; 2] = [0x00, 0x00];
+
+/// Largest UDP payload that fits in a 1500 byte IPv6 packet. QUIC probes for a larger path MTU
+/// from here, and falls back to its minimum of 1200 bytes if these packets are lost.
+const INITIAL_MTU: u16 = 1500 - 40 - 8;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind local UDP socket
+    #[error("Failed to bind UDP socket")]
+    BindUdp(#[source] io::Error),
+    /// Failed to bind remote UDP socket
+    #[error("Failed to bind remote UDP socket")]
+    BindRemoteUdp(#[source] io::Error),
+    /// Failed to set fwmark
+    #[cfg(target_os = "linux")]
+    #[error("Failed to set fwmark")]
+    SetFwmark(#[source] nix::Error),
+    /// Missing UDP listener address
+    #[error("Failed to retrieve UDP socket bind address")]
+    GetUdpLocalAddress(#[source] io::Error),
+    /// Failed to wait for UDP client
+    #[error("Failed to wait for UDP client")]
+    WaitForUdpClient(#[source] io::Error),
+    /// Failed to create QUIC endpoint
+    #[error("Failed to create QUIC endpoint")]
+    CreateEndpoint(#[source] io::Error),
+    /// Failed to create TLS configuration
+    #[error("Failed to create TLS configuration")]
+    TlsConfig(#[source] rustls::Error),
+    /// No TLS 1.3 cipher suite is usable with QUIC
+    #[error("No cipher suite usable with QUIC")]
+    QuicCipherSuite(#[source] quinn::crypto::rustls::NoInitialCipherSuite),
+    /// Failed to start connecting to the QUIC endpoint
+    #[error("Failed to connect to QUIC endpoint")]
+    StartConnect(#[source] quinn::ConnectError),
+    /// Failed to establish QUIC connection
+    #[error("Failed to connect to QUIC endpoint")]
+    Connect(#[source] quinn::ConnectionError),
+    /// The server does not accept datagrams
+    #[error("QUIC endpoint does not support datagrams")]
+    DatagramsUnsupported,
+}
+
+pub struct Quic {
+    udp_client_addr: SocketAddr,
+    server: tokio::task::JoinHandle<Result<()>>,
+    // The receiver will implicitly shut down when this is dropped
+    _shutdown_tx: oneshot::Sender<()>,
+    #[cfg(target_os = "android")]
+    outbound_fd: i32,
+}
+
+#[derive(Debug)]
+pub struct Settings {
+    /// Remote QUIC endpoint
+    pub quic_endpoint: SocketAddr,
+    /// Hostname that the server certificate is verified against. This is also sent as SNI.
+    pub hostname: String,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+impl Quic {
+    pub(crate) async fn new(settings: &Settings) -> Result<Self> {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        Self::with_root_certificates(settings, roots).await
+    }
+
+    async fn with_root_certificates(settings: &Settings, roots: RootCertStore) -> Result<Self> {
+        let (local_udp_socket, udp_client_addr) =
+            create_local_udp_socket(settings.quic_endpoint.is_ipv4()).await?;
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let remote_socket = create_remote_socket(
+            settings.quic_endpoint.is_ipv4(),
+            #[cfg(target_os = "linux")]
+            settings.fwmark,
+        )
+        .await?;
+
+        #[cfg(target_os = "android")]
+        let outbound_fd = remote_socket.as_raw_fd();
+
+        let endpoint = Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            remote_socket,
+            Arc::new(TokioRuntime),
+        )
+        .map_err(Error::CreateEndpoint)?;
+        let client_config = client_config(roots)?;
+
+        let server = tokio::spawn(run_forwarding(
+            endpoint,
+            client_config,
+            settings.quic_endpoint,
+            settings.hostname.clone(),
+            local_udp_socket,
+            shutdown_rx,
+        ));
+
+        Ok(Quic {
+            udp_client_addr,
+            server,
+            _shutdown_tx: shutdown_tx,
+            #[cfg(target_os = "android")]
+            outbound_fd,
+        })
+    }
+}
+
+fn client_config(roots: RootCertStore) -> Result<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls_config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(Error::TlsConfig)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+
+    let quic_config = QuicClientConfig::try_from(tls_config).map_err(Error::QuicCipherSuite)?;
+
+    let mut transport = TransportConfig::default();
+    transport
+        .initial_mtu(INITIAL_MTU)
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        .max_idle_timeout(Some(
+            IdleTimeout::try_from(MAX_IDLE_TIMEOUT).expect("idle timeout is valid"),
+        ));
+
+    let mut config = ClientConfig::new(Arc::new(quic_config));
+    config.transport_config(Arc::new(transport));
+    Ok(config)
+}
+
+async fn run_forwarding(
+    endpoint: Endpoint,
+    client_config: ClientConfig,
+    quic_endpoint: SocketAddr,
+    hostname: String,
+    local_udp_socket: UdpSocket,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> Result<()> {
+    wait_for_local_udp_client(&local_udp_socket)
+        .await
+        .map_err(Error::WaitForUdpClient)?;
+
+    let connection = endpoint
+        .connect_with(client_config, quic_endpoint, &hostname)
+        .map_err(Error::StartConnect)?
+        .await
+        .map_err(Error::Connect)?;
+
+    if connection.max_datagram_size().is_none() {
+        connection.close(0u32.into(), b"");
+        return Err(Error::DatagramsUnsupported);
+    }
+
+    let local_udp = Arc::new(local_udp_socket);
+
+    let mut client = tokio::spawn(handle_outgoing(connection.clone(), local_udp.clone()));
+    let mut server = tokio::spawn(handle_incoming(connection.clone(), local_udp));
+
+    tokio::select! {
+        _ = shutdown_rx => {
+            log::trace!("Stopping QUIC obfuscation");
+        }
+        _result = &mut server => log::trace!("QUIC connection closed"),
+        _result = &mut client => log::trace!("Local UDP client closed"),
+    }
+
+    client.abort();
+    server.abort();
+
+    connection.close(0u32.into(), b"");
+    endpoint.wait_idle().await;
+
+    Ok(())
+}
+
+async fn create_remote_socket(
+    ipv4: bool,
+    #[cfg(target_os = "linux")] fwmark: Option<u32>,
+) -> Result<std::net::UdpSocket> {
+    let random_bind_addr = if ipv4 {
+        SocketAddr::new("0.0.0.0".parse().unwrap(), 0)
+    } else {
+        SocketAddr::new("::".parse().unwrap(), 0)
+    };
+    let socket = UdpSocket::bind(random_bind_addr)
+        .await
+        .map_err(Error::BindRemoteUdp)?;
+    #[cfg(target_os = "linux")]
+    if let Some(fwmark) = fwmark {
+        setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark).map_err(Error::SetFwmark)?;
+    }
+
+    socket.into_std().map_err(Error::BindRemoteUdp)
+}
+
+async fn create_local_udp_socket(ipv4: bool) -> Result<(UdpSocket, SocketAddr)> {
+    let random_bind_addr = if ipv4 {
+        SocketAddr::new("127.0.0.1".parse().unwrap(), 0)
+    } else {
+        SocketAddr::new("::1".parse().unwrap(), 0)
+    };
+    let local_udp_socket = UdpSocket::bind(random_bind_addr)
+        .await
+        .map_err(Error::BindUdp)?;
+    let udp_client_addr = local_udp_socket
+        .local_addr()
+        .map_err(Error::GetUdpLocalAddress)?;
+
+    Ok((local_udp_socket, udp_client_addr))
+}
+
+/// Wait for a client to connect to `udp_listener` and connect the socket to that address
+async fn wait_for_local_udp_client(udp_listener: &UdpSocket) -> io::Result<()> {
+    log::trace!("Waiting for UDP socket client");
+    let client_addr = udp_listener.peek_sender().await?;
+
+    log::trace!("UDP connection from {client_addr}");
+    udp_listener.connect(client_addr).await
+}
+
+async fn handle_outgoing(connection: Connection, local_udp_read: Arc<UdpSocket>) {
+    let mut rx_buffer = vec![0u8; u16::MAX as usize];
+
+    loop {
+        let read_n = match local_udp_read.recv(&mut rx_buffer).await {
+            Ok(read_n) => read_n,
+            Err(error) => {
+                log::error!("Failed to read from local UDP socket: {error}");
+                break;
+            }
+        };
+
+        let mut datagram = BytesMut::with_capacity(HTTP_DATAGRAM_PREFIX.len() + read_n);
+        datagram.put_slice(&HTTP_DATAGRAM_PREFIX);
+        datagram.put_slice(&rx_buffer[0..read_n]);
+
+        match connection.send_datagram(datagram.freeze()) {
+            Ok(()) => (),
+            Err(SendDatagramError::TooLarge) => {
+                log::trace!("Dropping packet larger than the QUIC datagram limit");
+            }
+            Err(error) => {
+                log::error!("Failed to write to QUIC connection: {error}");
+                break;
+            }
+        }
+    }
+}
+
+async fn handle_incoming(connection: Connection, local_udp_write: Arc<UdpSocket>) {
+    loop {
+        let datagram = match connection.read_datagram().await {
+            Ok(datagram) => datagram,
+            Err(error) => {
+                log::error!("Failed to read from QUIC connection: {error}");
+                break;
+            }
+        };
+
+        let Some(payload) = strip_datagram_prefix(datagram) else {
+            log::trace!("Ignoring datagram with unexpected prefix");
+            continue;
+        };
+
+        if let Err(error) = local_udp_write.send(&payload).await {
+            log::error!("Failed to write to local UDP socket: {error}");
+            if is_fatal_socket_io_error(&error) {
+                break;
+            }
+        }
+    }
+}
+
+fn strip_datagram_prefix(datagram: Bytes) -> Option<Bytes> {
+    datagram
+        .starts_with(&HTTP_DATAGRAM_PREFIX)
+        .then(|| datagram.slice(HTTP_DATAGRAM_PREFIX.len()..))
+}
+
+#[async_trait]
+impl Obfuscator for Quic {
+    fn endpoint(&self) -> SocketAddr {
+        self.udp_client_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        match self.server.await {
+            Ok(result) => result.map_err(crate::Error::RunQuicObfuscator),
+            Err(_err) if _err.is_cancelled() => Ok(()),
+            Err(_err) => panic!("server handle panicked"),
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    fn remote_socket_fd(&self) -> std::os::unix::io::RawFd {
+        self.outbound_fd
+    }
+
+    fn packet_overhead(&self) -> u16 {
+        // Every WireGuard packet is sent in its own QUIC packet with a short header (RFC 9000,
+        // section 17.3), which looks like this: [flags][destination CID][packet number][frames]
+        // The frames are encrypted and followed by an AEAD tag. The destination connection ID is
+        // chosen by the server and may be up to 20 bytes long.
+        const SHORT_HEADER: usize = 1 + 20 + 4;
+        const AEAD_TAG: usize = 16;
+        // DATAGRAM frame type, followed by a length, which is at most a two-byte varint since
+        // datagrams never exceed 16383 bytes.
+        const DATAGRAM_FRAME: usize = 1 + 2;
+
+        let overhead = SHORT_HEADER + AEAD_TAG + DATAGRAM_FRAME + HTTP_DATAGRAM_PREFIX.len();
+
+        u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
+    }
+}
+
+fn is_fatal_socket_io_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::NotConnected
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quinn::{crypto::rustls::QuicServerConfig, ServerConfig};
+    use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+
+    const CERTIFICATE: &[u8] = include_bytes!("../test-data/localhost-cert.pem");
+    const PRIVATE_KEY: &[u8] = include_bytes!("../test-data/localhost-key.pem");
+
+    /// Start a QUIC server on localhost that echoes datagrams back to the client.
+    fn spawn_echo_server() -> SocketAddr {
+        let certificate = CertificateDer::from_pem_slice(CERTIFICATE).unwrap();
+        let private_key = PrivateKeyDer::from_pem_slice(PRIVATE_KEY).unwrap();
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut tls_config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate], private_key)
+            .unwrap();
+        tls_config.alpn_protocols = vec![ALPN_H3.to_vec()];
+        let server_config =
+            ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls_config).unwrap()));
+
+        let endpoint = Endpoint::server(server_config, "127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_addr = endpoint.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let connection = endpoint.accept().await.unwrap().await.unwrap();
+            while let Ok(datagram) = connection.read_datagram().await {
+                connection.send_datagram(datagram).unwrap();
+            }
+        });
+
+        server_addr
+    }
+
+    fn test_root_certificates() -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(CERTIFICATE).unwrap())
+            .unwrap();
+        roots
+    }
+
+    /// Forward a packet through the obfuscator to a local QUIC server and back again.
+    #[tokio::test]
+    async fn test_loopback() {
+        let settings = Settings {
+            quic_endpoint: spawn_echo_server(),
+            hostname: "localhost".to_owned(),
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        };
+        let obfuscator = Quic::with_root_certificates(&settings, test_root_certificates())
+            .await
+            .unwrap();
+        let obfuscator_addr = obfuscator.endpoint();
+        let obfuscator = tokio::spawn(Box::new(obfuscator).run());
+
+        let wireguard = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        wireguard.connect(obfuscator_addr).await.unwrap();
+
+        let packet = b"wireguard handshake initiation";
+        wireguard.send(packet).await.unwrap();
+
+        let mut rx_buffer = [0u8; 1500];
+        let read_n = tokio::time::timeout(Duration::from_secs(5), wireguard.recv(&mut rx_buffer))
+            .await
+            .expect("timed out waiting for echo")
+            .unwrap();
+        assert_eq!(&rx_buffer[..read_n], packet);
+
+        obfuscator.abort();
+    }
+
+    #[test]
+    fn test_strip_datagram_prefix() {
+        let datagram = Bytes::from_static(&[0x00, 0x00, 0xab, 0xcd]);
+        assert_eq!(
+            strip_datagram_prefix(datagram).as_deref(),
+            Some(&[0xab, 0xcd][..])
+        );
+        assert_eq!(
+            strip_datagram_prefix(Bytes::from_static(&[0x04, 0x00])),
+            None
+        );
+    }
+}
@@ -0,0 +1,736 @@
+//! SOCKS5 obfuscation
+//!
+//! Relays WireGuard traffic through a user-supplied SOCKS5 proxy using the UDP ASSOCIATE command
+//! described in RFC 1928: <https://datatracker.ietf.org/doc/html/rfc1928>. Proxies that require
+//! username/password authentication (RFC 1929) are supported.
+//!
+//! The TCP control connection must be kept open for as long as the association is used. The proxy
+//! is expected to relay datagrams from the same host that it accepts TCP connections on, since that
+//! is the only host which the firewall allows traffic to.
+
+use super::Obfuscator;
+use async_trait::async_trait;
+#[cfg(target_os = "linux")]
+use nix::sys::socket::{setsockopt, sockopt};
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use std::os::fd::AsRawFd;
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpSocket, TcpStream, UdpSocket},
+    sync::oneshot,
+};
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_METHOD_NONE: u8 = 0x00;
+const AUTH_METHOD_PASSWORD: u8 = 0x02;
+const PASSWORD_AUTH_VERSION: u8 = 0x01;
+const CMD_UDP_ASSOCIATE: u8 = 0x03;
+const REPLY_SUCCEEDED: u8 = 0x00;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind local UDP socket
+    #[error("Failed to bind UDP socket")]
+    BindUdp(#[source] io::Error),
+    /// Failed to bind remote UDP socket
+    #[error("Failed to bind remote UDP socket")]
+    BindRemoteUdp(#[source] io::Error),
+    /// Failed to create TCP socket
+    #[error("Failed to create TCP socket")]
+    CreateTcpSocket(#[source] io::Error),
+    /// Failed to set fwmark
+    #[cfg(target_os = "linux")]
+    #[error("Failed to set fwmark")]
+    SetFwmark(#[source] nix::Error),
+    /// Missing UDP listener address
+    #[error("Failed to retrieve UDP socket bind address")]
+    GetUdpLocalAddress(#[source] io::Error),
+    /// Failed to wait for UDP client
+    #[error("Failed to wait for UDP client")]
+    WaitForUdpClient(#[source] io::Error),
+    /// Failed to connect to the SOCKS5 proxy
+    #[error("Failed to connect to SOCKS5 proxy")]
+    ConnectProxy(#[source] io::Error),
+    /// Reading from or writing to the control connection failed
+    #[error("Failed to communicate with SOCKS5 proxy")]
+    ProxyIo(#[source] io::Error),
+    /// The proxy does not speak SOCKS5
+    #[error("SOCKS5 proxy sent an invalid response")]
+    InvalidResponse,
+    /// The proxy requires an authentication method that is not supported, or credentials that were
+    /// not provided
+    #[error("SOCKS5 proxy does not accept any of the offered authentication methods")]
+    NoAcceptableAuthMethod,
+    /// The proxy rejected the username or password
+    #[error("SOCKS5 proxy rejected the username or password")]
+    AuthenticationFailed,
+    /// The username or password is too long to be sent to the proxy
+    #[error("SOCKS5 username and password may not be longer than 255 bytes")]
+    CredentialsTooLong,
+    /// The proxy refused to relay UDP traffic
+    #[error("SOCKS5 proxy refused to relay UDP, reply code {0}")]
+    AssociateRefused(u8),
+    /// The proxy relays UDP traffic through a host other than itself
+    #[error("SOCKS5 proxy relays UDP through a different host: {0}")]
+    UnexpectedRelayAddress(IpAddr),
+    /// The proxy relays UDP traffic through a host given by name
+    #[error("SOCKS5 proxy relays UDP through a domain name, which is not supported")]
+    UnsupportedRelayAddress,
+    /// Failed to connect the remote UDP socket to the relay
+    #[error("Failed to connect to SOCKS5 UDP relay")]
+    ConnectRelay(#[source] io::Error),
+    /// The association ends when the control connection is closed
+    #[error("SOCKS5 proxy closed the control connection")]
+    ControlConnectionClosed,
+}
+
+pub struct Socks5 {
+    udp_client_addr: SocketAddr,
+    wireguard_endpoint: SocketAddr,
+    /// UDP relay address assigned by the proxy
+    relay_addr: SocketAddr,
+    server: tokio::task::JoinHandle<Result<()>>,
+    // The receiver will implicitly shut down when this is dropped
+    _shutdown_tx: oneshot::Sender<()>,
+    #[cfg(target_os = "android")]
+    outbound_fd: i32,
+}
+
+#[derive(Debug)]
+pub struct Settings {
+    /// Remote SOCKS5 proxy endpoint
+    pub proxy_endpoint: SocketAddr,
+    /// Credentials to authenticate with, if the proxy requires it
+    pub auth: Option<Credentials>,
+    /// Remote WireGuard endpoint
+    pub wireguard_endpoint: SocketAddr,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+/// Username and password for RFC 1929 authentication.
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Socks5 {
+    pub(crate) async fn new(settings: &Settings) -> Result<Self> {
+        let mut control = connect_proxy(
+            settings.proxy_endpoint,
+            #[cfg(target_os = "linux")]
+            settings.fwmark,
+        )
+        .await?;
+        authenticate(&mut control, settings.auth.as_ref()).await?;
+        let relay_addr = associate(&mut control, settings.proxy_endpoint).await?;
+        log::trace!("SOCKS5 proxy relays UDP through {relay_addr}");
+
+        let remote_socket = create_remote_udp_socket(
+            relay_addr.is_ipv4(),
+            #[cfg(target_os = "linux")]
+            settings.fwmark,
+        )
+        .await?;
+        remote_socket
+            .connect(relay_addr)
+            .await
+            .map_err(Error::ConnectRelay)?;
+
+        #[cfg(target_os = "android")]
+        let outbound_fd = remote_socket.as_raw_fd();
+
+        let (local_udp_socket, udp_client_addr) =
+            create_local_udp_socket(settings.wireguard_endpoint.is_ipv4()).await?;
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let server = tokio::spawn(run_forwarding(
+            control,
+            remote_socket,
+            local_udp_socket,
+            settings.wireguard_endpoint,
+            shutdown_rx,
+        ));
+
+        Ok(Socks5 {
+            udp_client_addr,
+            wireguard_endpoint: settings.wireguard_endpoint,
+            relay_addr,
+            server,
+            _shutdown_tx: shutdown_tx,
+            #[cfg(target_os = "android")]
+            outbound_fd,
+        })
+    }
+}
+
+async fn connect_proxy(
+    proxy_endpoint: SocketAddr,
+    #[cfg(target_os = "linux")] fwmark: Option<u32>,
+) -> Result<TcpStream> {
+    let socket = if proxy_endpoint.is_ipv4() {
+        TcpSocket::new_v4()
+    } else {
+        TcpSocket::new_v6()
+    }
+    .map_err(Error::CreateTcpSocket)?;
+    #[cfg(target_os = "linux")]
+    if let Some(fwmark) = fwmark {
+        setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark).map_err(Error::SetFwmark)?;
+    }
+    socket
+        .connect(proxy_endpoint)
+        .await
+        .map_err(Error::ConnectProxy)
+}
+
+/// Negotiate an authentication method with the proxy and authenticate using it.
+async fn authenticate(control: &mut TcpStream, auth: Option<&Credentials>) -> Result<()> {
+    let greeting: &[u8] = match auth {
+        Some(_) => &[SOCKS_VERSION, 2, AUTH_METHOD_NONE, AUTH_METHOD_PASSWORD],
+        None => &[SOCKS_VERSION, 1, AUTH_METHOD_NONE],
+    };
+    control.write_all(greeting).await.map_err(Error::ProxyIo)?;
+
+    let mut reply = [0u8; 2];
+    control
+        .read_exact(&mut reply)
+        .await
+        .map_err(Error::ProxyIo)?;
+    match (reply, auth) {
+        ([SOCKS_VERSION, AUTH_METHOD_NONE], _) => Ok(()),
+        ([SOCKS_VERSION, AUTH_METHOD_PASSWORD], Some(credentials)) => {
+            authenticate_password(control, credentials).await
+        }
+        ([SOCKS_VERSION, _], _) => Err(Error::NoAcceptableAuthMethod),
+        _ => Err(Error::InvalidResponse),
+    }
+}
+
+/// Username/password authentication, as described in RFC 1929.
+async fn authenticate_password(control: &mut TcpStream, credentials: &Credentials) -> Result<()> {
+    let username = credentials.username.as_bytes();
+    let password = credentials.password.as_bytes();
+    let (Ok(username_len), Ok(password_len)) =
+        (u8::try_from(username.len()), u8::try_from(password.len()))
+    else {
+        return Err(Error::CredentialsTooLong);
+    };
+
+    let mut request = Vec::with_capacity(3 + username.len() + password.len());
+    request.extend_from_slice(&[PASSWORD_AUTH_VERSION, username_len]);
+    request.extend_from_slice(username);
+    request.push(password_len);
+    request.extend_from_slice(password);
+    control.write_all(&request).await.map_err(Error::ProxyIo)?;
+
+    let mut reply = [0u8; 2];
+    control
+        .read_exact(&mut reply)
+        .await
+        .map_err(Error::ProxyIo)?;
+    match reply {
+        [PASSWORD_AUTH_VERSION, 0] => Ok(()),
+        [PASSWORD_AUTH_VERSION, _] => Err(Error::AuthenticationFailed),
+        _ => Err(Error::InvalidResponse),
+    }
+}
+
+/// Ask the proxy to relay UDP traffic. Returns the address of the relay.
+async fn associate(control: &mut TcpStream, proxy_endpoint: SocketAddr) -> Result<SocketAddr> {
+    // The address that the proxy will see our datagrams coming from is not known if there is a
+    // NAT in between, so let the proxy accept them from any address.
+    let unspecified_ip: IpAddr = if proxy_endpoint.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    };
+    let mut request = vec![SOCKS_VERSION, CMD_UDP_ASSOCIATE, 0x00];
+    write_address(&mut request, SocketAddr::new(unspecified_ip, 0));
+    control.write_all(&request).await.map_err(Error::ProxyIo)?;
+
+    let mut reply = [0u8; 4];
+    control
+        .read_exact(&mut reply)
+        .await
+        .map_err(Error::ProxyIo)?;
+    let [SOCKS_VERSION, status, _reserved, address_type] = reply else {
+        return Err(Error::InvalidResponse);
+    };
+    if status != REPLY_SUCCEEDED {
+        return Err(Error::AssociateRefused(status));
+    }
+
+    let relay_ip: IpAddr = match address_type {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            control
+                .read_exact(&mut octets)
+                .await
+                .map_err(Error::ProxyIo)?;
+            Ipv4Addr::from(octets).into()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            control
+                .read_exact(&mut octets)
+                .await
+                .map_err(Error::ProxyIo)?;
+            Ipv6Addr::from(octets).into()
+        }
+        ATYP_DOMAIN => return Err(Error::UnsupportedRelayAddress),
+        _ => return Err(Error::InvalidResponse),
+    };
+    let relay_port = control.read_u16().await.map_err(Error::ProxyIo)?;
+
+    // An unspecified address means that the relay is reached through the proxy's own address
+    let relay_ip = if relay_ip.is_unspecified() {
+        proxy_endpoint.ip()
+    } else {
+        relay_ip
+    };
+    if relay_ip != proxy_endpoint.ip() {
+        return Err(Error::UnexpectedRelayAddress(relay_ip));
+    }
+
+    Ok(SocketAddr::new(relay_ip, relay_port))
+}
+
+/// Append `ATYP`, `ADDR` and `PORT` fields describing `addr` to `buf`.
+fn write_address(buf: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buf.push(ATYP_IPV4);
+            buf.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buf.push(ATYP_IPV6);
+            buf.extend_from_slice(&ip.octets());
+        }
+    }
+    buf.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Return the header which must precede every datagram sent to `destination` through the relay.
+fn udp_header(destination: SocketAddr) -> Vec<u8> {
+    // RSV and FRAG are always zero since fragmentation is not used
+    let mut header = vec![0x00, 0x00, 0x00];
+    write_address(&mut header, destination);
+    header
+}
+
+/// Parse the header of a datagram received from the relay. Returns the address that the datagram
+/// was sent from and the length of the header, or `None` if the header is invalid or the datagram
+/// is a fragment.
+fn parse_udp_header(datagram: &[u8]) -> Option<(SocketAddr, usize)> {
+    let Some((&[0x00, 0x00, 0x00, address_type], rest)) = datagram.split_first_chunk::<4>() else {
+        return None;
+    };
+    let (ip, rest): (IpAddr, _) = match address_type {
+        ATYP_IPV4 => {
+            let (octets, rest) = rest.split_first_chunk::<4>()?;
+            (Ipv4Addr::from(*octets).into(), rest)
+        }
+        ATYP_IPV6 => {
+            let (octets, rest) = rest.split_first_chunk::<16>()?;
+            (Ipv6Addr::from(*octets).into(), rest)
+        }
+        _ => return None,
+    };
+    let (port, rest) = rest.split_first_chunk::<2>()?;
+    let source = SocketAddr::new(ip, u16::from_be_bytes(*port));
+    Some((source, datagram.len() - rest.len()))
+}
+
+async fn run_forwarding(
+    mut control: TcpStream,
+    remote_socket: UdpSocket,
+    local_udp_socket: UdpSocket,
+    wireguard_endpoint: SocketAddr,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> Result<()> {
+    wait_for_local_udp_client(&local_udp_socket)
+        .await
+        .map_err(Error::WaitForUdpClient)?;
+
+    let remote = Arc::new(remote_socket);
+    let local_udp = Arc::new(local_udp_socket);
+
+    let mut client = tokio::spawn(handle_outgoing(
+        remote.clone(),
+        local_udp.clone(),
+        wireguard_endpoint,
+    ));
+    let mut server = tokio::spawn(handle_incoming(remote, local_udp, wireguard_endpoint));
+
+    let result = tokio::select! {
+        _ = shutdown_rx => {
+            log::trace!("Stopping SOCKS5 obfuscation");
+            Ok(())
+        }
+        result = wait_for_control_close(&mut control) => result,
+        _result = &mut server => {
+            log::trace!("SOCKS5 UDP relay closed");
+            Ok(())
+        }
+        _result = &mut client => {
+            log::trace!("Local UDP client closed");
+            Ok(())
+        }
+    };
+
+    client.abort();
+    server.abort();
+
+    result
+}
+
+/// Wait until the proxy closes the control connection, which ends the association.
+async fn wait_for_control_close(control: &mut TcpStream) -> Result<()> {
+    let mut buf = [0u8; 64];
+    loop {
+        match control.read(&mut buf).await {
+            Ok(0) => return Err(Error::ControlConnectionClosed),
+            // The proxy is not supposed to send anything more
+            Ok(_) => continue,
+            Err(error) => return Err(Error::ProxyIo(error)),
+        }
+    }
+}
+
+async fn create_remote_udp_socket(
+    ipv4: bool,
+    #[cfg(target_os = "linux")] fwmark: Option<u32>,
+) -> Result<UdpSocket> {
+    let random_bind_addr = if ipv4 {
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)
+    } else {
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
+    };
+    let socket = UdpSocket::bind(random_bind_addr)
+        .await
+        .map_err(Error::BindRemoteUdp)?;
+    #[cfg(target_os = "linux")]
+    if let Some(fwmark) = fwmark {
+        setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark).map_err(Error::SetFwmark)?;
+    }
+
+    Ok(socket)
+}
+
+async fn create_local_udp_socket(ipv4: bool) -> Result<(UdpSocket, SocketAddr)> {
+    let random_bind_addr = if ipv4 {
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)
+    } else {
+        SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0)
+    };
+    let local_udp_socket = UdpSocket::bind(random_bind_addr)
+        .await
+        .map_err(Error::BindUdp)?;
+    let udp_client_addr = local_udp_socket
+        .local_addr()
+        .map_err(Error::GetUdpLocalAddress)?;
+
+    Ok((local_udp_socket, udp_client_addr))
+}
+
+/// Wait for a client to connect to `udp_listener` and connect the socket to that address
+async fn wait_for_local_udp_client(udp_listener: &UdpSocket) -> io::Result<()> {
+    log::trace!("Waiting for UDP socket client");
+    let client_addr = udp_listener.peek_sender().await?;
+
+    log::trace!("UDP connection from {client_addr}");
+    udp_listener.connect(client_addr).await
+}
+
+async fn handle_outgoing(
+    remote: Arc<UdpSocket>,
+    local_udp_read: Arc<UdpSocket>,
+    wireguard_endpoint: SocketAddr,
+) {
+    // Read each datagram into the buffer right after the header, so that it can be sent as is
+    let header = udp_header(wireguard_endpoint);
+    let mut tx_buffer = vec![0u8; header.len() + usize::from(u16::MAX)];
+    tx_buffer[..header.len()].copy_from_slice(&header);
+
+    loop {
+        let read_n = match local_udp_read.recv(&mut tx_buffer[header.len()..]).await {
+            Ok(read_n) => read_n,
+            Err(error) => {
+                log::error!("Failed to read from local UDP socket: {error}");
+                break;
+            }
+        };
+
+        if let Err(error) = remote.send(&tx_buffer[..header.len() + read_n]).await {
+            if is_fatal_socket_io_error(&error) {
+                log::error!("Failed to write to SOCKS5 UDP relay: {error}");
+                break;
+            }
+            log::trace!("Failed to write to SOCKS5 UDP relay: {error}");
+        }
+    }
+}
+
+async fn handle_incoming(
+    remote: Arc<UdpSocket>,
+    local_udp_write: Arc<UdpSocket>,
+    wireguard_endpoint: SocketAddr,
+) {
+    let mut rx_buffer = vec![0u8; usize::from(u16::MAX)];
+
+    loop {
+        let read_n = match remote.recv(&mut rx_buffer).await {
+            Ok(read_n) => read_n,
+            Err(error) => {
+                log::error!("Failed to read from SOCKS5 UDP relay: {error}");
+                break;
+            }
+        };
+
+        let payload_start = match parse_udp_header(&rx_buffer[..read_n]) {
+            Some((source, header_len)) if source == wireguard_endpoint => header_len,
+            Some((source, _)) => {
+                log::trace!("Ignoring packet from unexpected source: {source}");
+                continue;
+            }
+            None => {
+                log::trace!("Ignoring invalid or fragmented SOCKS5 UDP packet");
+                continue;
+            }
+        };
+
+        if let Err(error) = local_udp_write
+            .send(&rx_buffer[payload_start..read_n])
+            .await
+        {
+            log::error!("Failed to write to local UDP socket: {error}");
+            if is_fatal_socket_io_error(&error) {
+                break;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Obfuscator for Socks5 {
+    fn endpoint(&self) -> SocketAddr {
+        self.udp_client_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        match self.server.await {
+            Ok(result) => result.map_err(crate::Error::RunSocks5Obfuscator),
+            Err(_err) if _err.is_cancelled() => Ok(()),
+            Err(_err) => panic!("server handle panicked"),
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    fn remote_socket_fd(&self) -> std::os::unix::io::RawFd {
+        self.outbound_fd
+    }
+
+    fn udp_relay(&self) -> Option<SocketAddr> {
+        Some(self.relay_addr)
+    }
+
+    fn packet_overhead(&self) -> u16 {
+        let overhead = udp_header(self.wireguard_endpoint).len();
+        u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
+    }
+}
+
+fn is_fatal_socket_io_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::NotConnected
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    const USERNAME: &str = "wireguard";
+    const PASSWORD: &str = "hunter2";
+
+    /// Minimal SOCKS5 proxy which requires username/password authentication and relays a single
+    /// datagram and its reply. Like many real proxies, it replies with an unspecified relay
+    /// address.
+    async fn run_socks5_proxy(listener: TcpListener) {
+        let (mut control, _) = listener.accept().await.unwrap();
+
+        let mut greeting = [0u8; 2];
+        control.read_exact(&mut greeting).await.unwrap();
+        let mut methods = vec![0u8; usize::from(greeting[1])];
+        control.read_exact(&mut methods).await.unwrap();
+        assert!(methods.contains(&AUTH_METHOD_PASSWORD));
+        control
+            .write_all(&[SOCKS_VERSION, AUTH_METHOD_PASSWORD])
+            .await
+            .unwrap();
+
+        let mut username = vec![0u8; usize::from(read_auth_header(&mut control).await)];
+        control.read_exact(&mut username).await.unwrap();
+        let mut password = vec![0u8; usize::from(control.read_u8().await.unwrap())];
+        control.read_exact(&mut password).await.unwrap();
+        let authenticated = username == USERNAME.as_bytes() && password == PASSWORD.as_bytes();
+        let status = if authenticated { 0 } else { 1 };
+        control
+            .write_all(&[PASSWORD_AUTH_VERSION, status])
+            .await
+            .unwrap();
+        if !authenticated {
+            return;
+        }
+
+        // VER, CMD, RSV and an IPv4 address
+        let mut request = [0u8; 10];
+        control.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[1], CMD_UDP_ASSOCIATE);
+
+        let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let relay_port = relay.local_addr().unwrap().port();
+        let mut reply = vec![SOCKS_VERSION, REPLY_SUCCEEDED, 0x00];
+        write_address(
+            &mut reply,
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, relay_port)),
+        );
+        control.write_all(&reply).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        let (read_n, client) = relay.recv_from(&mut buf).await.unwrap();
+        let (destination, header_len) = parse_udp_header(&buf[..read_n]).unwrap();
+        let outbound = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        outbound
+            .send_to(&buf[header_len..read_n], destination)
+            .await
+            .unwrap();
+
+        let (read_n, source) = outbound.recv_from(&mut buf).await.unwrap();
+        let mut datagram = udp_header(source);
+        datagram.extend_from_slice(&buf[..read_n]);
+        relay.send_to(&datagram, client).await.unwrap();
+
+        // Keep the association alive until the client goes away
+        let _ = control.read(&mut [0u8; 1]).await;
+    }
+
+    async fn read_auth_header(control: &mut TcpStream) -> u8 {
+        assert_eq!(control.read_u8().await.unwrap(), PASSWORD_AUTH_VERSION);
+        control.read_u8().await.unwrap()
+    }
+
+    async fn spawn_proxy() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_endpoint = listener.local_addr().unwrap();
+        tokio::spawn(run_socks5_proxy(listener));
+        proxy_endpoint
+    }
+
+    fn settings(
+        proxy_endpoint: SocketAddr,
+        wireguard_endpoint: SocketAddr,
+        password: &str,
+    ) -> Settings {
+        Settings {
+            proxy_endpoint,
+            auth: Some(Credentials {
+                username: USERNAME.to_owned(),
+                password: password.to_owned(),
+            }),
+            wireguard_endpoint,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        }
+    }
+
+    /// Relay a datagram to an echo server through the proxy and check that the reply comes back.
+    #[tokio::test]
+    async fn test_relay_through_proxy() {
+        let wireguard_server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let wireguard_endpoint = wireguard_server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1500];
+            let (read_n, source) = wireguard_server.recv_from(&mut buf).await.unwrap();
+            wireguard_server
+                .send_to(&buf[..read_n], source)
+                .await
+                .unwrap();
+        });
+
+        let proxy_endpoint = spawn_proxy().await;
+        let obfuscator = Socks5::new(&settings(proxy_endpoint, wireguard_endpoint, PASSWORD))
+            .await
+            .expect("SOCKS5 handshake should succeed");
+        assert_eq!(obfuscator.packet_overhead(), 10);
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client
+            .send_to(b"handshake initiation", obfuscator.endpoint())
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 1500];
+        let read_n = tokio::time::timeout(Duration::from_secs(5), client.recv(&mut buf))
+            .await
+            .expect("timed out waiting for reply")
+            .unwrap();
+        assert_eq!(&buf[..read_n], b"handshake initiation");
+    }
+
+    #[tokio::test]
+    async fn test_authentication_failure() {
+        let proxy_endpoint = spawn_proxy().await;
+        let wireguard_endpoint = SocketAddr::from((Ipv4Addr::LOCALHOST, 51820));
+
+        let result = Socks5::new(&settings(proxy_endpoint, wireguard_endpoint, "wrong")).await;
+        assert!(matches!(result, Err(Error::AuthenticationFailed)));
+    }
+
+    #[test]
+    fn test_udp_header_roundtrip() {
+        for destination in [
+            SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 51820)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 443)),
+        ] {
+            let mut datagram = udp_header(destination);
+            let header_len = datagram.len();
+            datagram.extend_from_slice(b"payload");
+            assert_eq!(parse_udp_header(&datagram), Some((destination, header_len)));
+        }
+
+        // Fragments are not supported
+        let mut fragment = udp_header(SocketAddr::from((Ipv4Addr::LOCALHOST, 1)));
+        fragment[2] = 1;
+        assert_eq!(parse_udp_header(&fragment), None);
+    }
+}
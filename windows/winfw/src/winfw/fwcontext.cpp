@@ -262,6 +262,35 @@ bool FwContext::applyPolicyConnecting
 							));
 				break;
 			}
+			case WinFwAllowedTunnelTrafficType::Three:
+			{
+				auto endpoints = std::make_optional<baseline::PermitVpnTunnel::Endpoints>({
+						baseline::PermitVpnTunnel::Endpoint{
+						wfp::IpAddress(allowedTunnelTraffic.endpoint1->ip),
+						allowedTunnelTraffic.endpoint1->port,
+						allowedTunnelTraffic.endpoint1->protocol
+						},
+						std::make_optional<baseline::PermitVpnTunnel::Endpoint>({
+								wfp::IpAddress(allowedTunnelTraffic.endpoint2->ip),
+								allowedTunnelTraffic.endpoint2->port,
+								allowedTunnelTraffic.endpoint2->protocol
+								}),
+						std::make_optional<baseline::PermitVpnTunnel::Endpoint>({
+								wfp::IpAddress(allowedTunnelTraffic.endpoint3->ip),
+								allowedTunnelTraffic.endpoint3->port,
+								allowedTunnelTraffic.endpoint3->protocol
+								})
+				});
+				ruleset.emplace_back(std::make_unique<baseline::PermitVpnTunnel>(
+							*tunnelInterfaceAlias,
+							endpoints
+							));
+				ruleset.emplace_back(std::make_unique<baseline::PermitVpnTunnelService>(
+							*tunnelInterfaceAlias,
+							endpoints
+							));
+				break;
+			}
 			// For the "None" case, do nothing.
 		}
 	}
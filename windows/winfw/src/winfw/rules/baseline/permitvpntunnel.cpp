@@ -112,6 +112,15 @@ bool PermitVpnTunnel::apply(IObjectInstaller &objectInstaller)
 				objectInstaller
 			);
 	}
+	if (m_potentialEndpoints.value().thirdEndpoint.has_value())
+	{
+		AddEndpointFilter(
+				m_potentialEndpoints.value().thirdEndpoint.value(),
+				MullvadGuids::Filter_Baseline_PermitVpnTunnel_Outbound_Ipv4_3(),
+				MullvadGuids::Filter_Baseline_PermitVpnTunnel_Outbound_Ipv6_3(),
+				objectInstaller
+			);
+	}
 	return true;
 }
 
@@ -22,6 +22,7 @@ public:
 	struct Endpoints {
 		Endpoint entryEndpoint;
 		std::optional<Endpoint> exitEndpoint;
+		std::optional<Endpoint> thirdEndpoint;
 	};
 
 	PermitVpnTunnel(
@@ -114,6 +114,15 @@ bool PermitVpnTunnelService::apply(IObjectInstaller &objectInstaller)
 				objectInstaller
 			);
 	}
+	if (m_potentialEndpoints.value().thirdEndpoint.has_value())
+	{
+		AddEndpointFilter(
+				m_potentialEndpoints.value().thirdEndpoint.value(),
+				MullvadGuids::Filter_Baseline_PermitVpnTunnelService_Ipv4_3(),
+				MullvadGuids::Filter_Baseline_PermitVpnTunnelService_Ipv6_3(),
+				objectInstaller
+			);
+	}
 	return true;
 }
 
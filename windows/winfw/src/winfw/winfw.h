@@ -60,7 +60,8 @@ enum WinFwAllowedTunnelTrafficType : uint8_t
 	None,
 	All,
 	One,
-	Two
+	Two,
+	Three
 };
 
 typedef struct tag_WinFwAllowedTunnelTraffic
@@ -68,6 +69,7 @@ typedef struct tag_WinFwAllowedTunnelTraffic
 	WinFwAllowedTunnelTrafficType type;
 	WinFwEndpoint *endpoint1;
 	WinFwEndpoint *endpoint2;
+	WinFwEndpoint *endpoint3;
 }
 WinFwAllowedTunnelTraffic;
 